@@ -13,7 +13,7 @@ fn main() {
         if file_path.ends_with(".md") && file_path.contains("/agents/") {
             let agent_name = file_path
                 .split('/')
-                .last()
+                .next_back()
                 .unwrap_or("unknown")
                 .strip_suffix(".md")
                 .unwrap_or("unknown");
@@ -58,7 +58,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         println!("\n📊 Unknown objects by service:");
         let mut sorted: Vec<_> = by_service.into_iter().collect();
-        sorted.sort_by(|a, b| b.1.cmp(&a.1));
+        sorted.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
 
         for (service, count) in sorted.iter().take(10) {
             println!("  {}: {} unknown objects", service, count);
@@ -8,10 +8,11 @@
 
 use anyhow::Result;
 use pocketflow_rs::Context;
-use serde_json::Value;
 
 #[cfg(feature = "mcp")]
-use op_dbus::mcp::workflows::{McpWorkflowManager, McpWorkflowState};
+use op_dbus::mcp::workflows::McpWorkflowManager;
+#[cfg(feature = "mcp")]
+use serde_json::Value;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -46,7 +47,7 @@ async fn main() -> Result<()> {
         // Create context with sample code
         let mut context = Context::new();
         context.set(
-            "code".to_string(),
+            "code",
             Value::String(
                 r#"
     fn main() {
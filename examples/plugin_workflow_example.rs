@@ -4,11 +4,7 @@
 //! Each plugin becomes a node that can pass data to other plugins.
 
 use anyhow::Result;
-use op_dbus::state::plugin_workflow::{PluginWorkflowManager, PluginWorkflowState};
-use op_dbus::state::plugins;
-use pocketflow_rs::Context;
-use serde_json::Value;
-use std::sync::Arc;
+use op_dbus::state::plugin_workflow::PluginWorkflowManager;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -17,7 +13,7 @@ async fn main() -> Result<()> {
     println!("This demonstrates how plugins become workflow nodes.\n");
 
     // Create workflow manager
-    let mut workflow_manager = PluginWorkflowManager::new();
+    let _workflow_manager = PluginWorkflowManager::new();
 
     // Demonstrate plugin registration concept
     println!("🔧 Plugin Registration Concept:");
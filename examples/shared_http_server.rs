@@ -26,7 +26,7 @@ async fn api_status() -> impl IntoResponse {
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<()> {
     println!("🚀 Starting Example Shared HTTP/TLS Server...");
 
     // Create service routers for different services
@@ -69,11 +69,16 @@ async fn main() -> anyhow::Result<()> {
         use op_dbus::mcp::ollama::OllamaClient;
 
         let client = OllamaClient::cloud(api_key);
+        let model =
+            std::env::var("OLLAMA_DEFAULT_MODEL").unwrap_or_else(|_| "llama2".to_string());
 
         println!("🤖 Connecting to AI Service...");
 
         match client
-            .chat("Hello! Please respond with just 'Hello!' to confirm you're working.")
+            .simple_chat(
+                &model,
+                "Hello! Please respond with just 'Hello!' to confirm you're working.",
+            )
             .await
         {
             Ok(response) => {
@@ -18,7 +18,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Show top services by object count
     let mut all_services = result.system_services.clone();
     all_services.extend(result.session_services);
-    all_services.sort_by(|a, b| b.objects.len().cmp(&a.objects.len()));
+    all_services.sort_by_key(|s| std::cmp::Reverse(s.objects.len()));
 
     println!("\n🏆 Top services by object count:");
     for service in all_services.iter().take(10) {
@@ -994,8 +994,11 @@ Wants=network-online.target
 Requires=openvswitch-switch.service
 
 [Service]
-Type=simple
+Type=notify
 ExecStart=/usr/local/bin/op-dbus --state-file /etc/op-dbus/state.json $DHCP_FLAG run
+# Ready once the D-Bus service is up; the startup apply and readiness are in
+# STATUS=, and the watchdog is pinged while the daemon and its bus are alive
+WatchdogSec=120
 Restart=on-failure
 RestartSec=5s
 StandardOutput=journal
@@ -1,15 +1,13 @@
-#[path = "mcp/ollama.rs"]
-mod ollama;
-
 use anyhow::Result;
 use axum::{
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
     extract::State,
-    response::{Html, IntoResponse},
+    response::IntoResponse,
     routing::get,
     Router,
 };
 use futures::{SinkExt, StreamExt};
+use op_dbus::mcp::ollama;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
@@ -123,81 +121,64 @@ async fn handle_socket(socket: WebSocket, state: ChatState) {
 
     while let Some(Ok(message)) = receiver.next().await {
         if let Message::Text(text) = message {
-            // Parse incoming message
-            if let Ok(chat_msg) = serde_json::from_str::<ChatMessage>(&text) {
-                match chat_msg {
-                    ChatMessage::User { content, .. } => {
-                        // Store user message
-                        let timestamp = std::time::SystemTime::now()
-                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs();
-
-                        let user_msg = ChatMessage::User {
-                            content: content.clone(),
-                            timestamp,
-                        };
-
-                        // Add to conversation
-                        {
-                            let mut conversations = state.conversations.write().await;
-                            conversations
-                                .entry(conversation_id.clone())
-                                .or_insert_with(Vec::new)
-                                .push(user_msg.clone());
-                        }
+            // Parse incoming message; only user messages are answered
+            if let Ok(ChatMessage::User { content, .. }) = serde_json::from_str::<ChatMessage>(&text) {
+                // Store user message
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+
+                let user_msg = ChatMessage::User {
+                    content: content.clone(),
+                    timestamp,
+                };
+
+                // Add to conversation
+                {
+                    let mut conversations = state.conversations.write().await;
+                    conversations
+                        .entry(conversation_id.clone())
+                        .or_insert_with(Vec::new)
+                        .push(user_msg.clone());
+                }
 
-                        // Send back the user message for UI update
-                        if let Ok(response) = serde_json::to_string(&user_msg) {
-                            let _ = sender.send(Message::Text(response)).await;
-                        }
+                // Send back the user message for UI update
+                if let Ok(response) = serde_json::to_string(&user_msg) {
+                    let _ = sender.send(Message::Text(response)).await;
+                }
 
-                        // Generate AI response if Ollama client is available
-                        if let Some(ollama_client) = &state.ollama_client {
-                            let model = ollama_client.default_model();
-                            match ollama_client.simple_chat(&model, &content).await {
-                                Ok(ai_response) => {
-                                    let ai_msg = ChatMessage::Assistant {
-                                        content: ai_response,
-                                        timestamp: std::time::SystemTime::now()
-                                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                                            .unwrap()
-                                            .as_secs(),
-                                    };
-
-                                    // Add to conversation
-                                    {
-                                        let mut conversations = state.conversations.write().await;
-                                        conversations
-                                            .entry(conversation_id.clone())
-                                            .or_insert_with(Vec::new)
-                                            .push(ai_msg.clone());
-                                    }
-
-                                    // Send AI response
-                                    if let Ok(response) = serde_json::to_string(&ai_msg) {
-                                        let _ = sender.send(Message::Text(response)).await;
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("AI chat error: {}", e);
-                                    let error_msg = ChatMessage::Error {
-                                        content: format!("AI chat failed: {}", e),
-                                        timestamp: std::time::SystemTime::now()
-                                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                                            .unwrap()
-                                            .as_secs(),
-                                    };
-
-                                    if let Ok(response) = serde_json::to_string(&error_msg) {
-                                        let _ = sender.send(Message::Text(response)).await;
-                                    }
-                                }
+                // Generate AI response if Ollama client is available
+                if let Some(ollama_client) = &state.ollama_client {
+                    let model = ollama_client.default_model();
+                    match ollama_client.simple_chat(&model, &content).await {
+                        Ok(ai_response) => {
+                            let ai_msg = ChatMessage::Assistant {
+                                content: ai_response,
+                                timestamp: std::time::SystemTime::now()
+                                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                                    .unwrap()
+                                    .as_secs(),
+                            };
+
+                            // Add to conversation
+                            {
+                                let mut conversations = state.conversations.write().await;
+                                conversations
+                                    .entry(conversation_id.clone())
+                                    .or_insert_with(Vec::new)
+                                    .push(ai_msg.clone());
+                            }
+
+                            // Send AI response
+                            if let Ok(response) = serde_json::to_string(&ai_msg) {
+                                let _ = sender.send(Message::Text(response)).await;
                             }
-                        } else {
-                            // No Ollama client - send error message
+                        }
+                        Err(e) => {
+                            error!("AI chat error: {}", e);
                             let error_msg = ChatMessage::Error {
-                                content: "AI AI is not available. Please set OLLAMA_API_KEY environment variable.".to_string(),
+                                content: format!("AI chat failed: {}", e),
                                 timestamp: std::time::SystemTime::now()
                                     .duration_since(std::time::SystemTime::UNIX_EPOCH)
                                     .unwrap()
@@ -209,7 +190,19 @@ async fn handle_socket(socket: WebSocket, state: ChatState) {
                             }
                         }
                     }
-                    _ => {} // Ignore other message types
+                } else {
+                    // No Ollama client - send error message
+                    let error_msg = ChatMessage::Error {
+                        content: "AI AI is not available. Please set OLLAMA_API_KEY environment variable.".to_string(),
+                        timestamp: std::time::SystemTime::now()
+                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                    };
+
+                    if let Ok(response) = serde_json::to_string(&error_msg) {
+                        let _ = sender.send(Message::Text(response)).await;
+                    }
                 }
             }
         }
@@ -0,0 +1,531 @@
+//! Audit log of mutating operations
+//!
+//! Every front-end (CLI, D-Bus, web UI, MCP and the chat server) records
+//! who changed what, through which front-end, and how it ended. Records are
+//! appended as JSON lines to a size-rotated file and sent to the journal,
+//! and can be queried with `op-dbus audit search`.
+
+use crate::blockchain::plugin_footprint::current_actor;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Default audit log file
+pub const DEFAULT_AUDIT_LOG: &str = "/var/log/op-dbus/audit.jsonl";
+
+/// Default size at which the log is rotated
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default number of rotated files kept
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// Parameter names whose values are never written to the log
+const SECRET_KEYS: [&str; 8] = [
+    "password",
+    "passphrase",
+    "secret",
+    "token",
+    "private_key",
+    "api_key",
+    "credential",
+    "psk",
+];
+
+/// Front-end a mutation came in through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frontend {
+    Cli,
+    Dbus,
+    Web,
+    Mcp,
+    Chat,
+}
+
+impl Frontend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Frontend::Cli => "cli",
+            Frontend::Dbus => "dbus",
+            Frontend::Web => "web",
+            Frontend::Mcp => "mcp",
+            Frontend::Chat => "chat",
+        }
+    }
+}
+
+impl std::str::FromStr for Frontend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cli" => Ok(Frontend::Cli),
+            "dbus" => Ok(Frontend::Dbus),
+            "web" => Ok(Frontend::Web),
+            "mcp" => Ok(Frontend::Mcp),
+            "chat" => Ok(Frontend::Chat),
+            other => Err(anyhow::anyhow!(
+                "Unknown front-end '{}' (cli, dbus, web, mcp, chat)",
+                other
+            )),
+        }
+    }
+}
+
+/// How a mutation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// Refused before anything was changed (authorization, confirmation)
+    Denied,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Denied => "denied",
+        }
+    }
+}
+
+impl std::str::FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "success" => Ok(Outcome::Success),
+            "failure" => Ok(Outcome::Failure),
+            "denied" => Ok(Outcome::Denied),
+            other => Err(anyhow::anyhow!(
+                "Unknown outcome '{}' (success, failure, denied)",
+                other
+            )),
+        }
+    }
+}
+
+/// One audited mutation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub frontend: Frontend,
+    /// uid:, token: or cert: identity of the caller
+    pub actor: Option<String>,
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub actions: Value,
+    /// Request parameters, with secrets redacted
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// A successful `operation`, attributed to the current task's actor
+    pub fn new(frontend: Frontend, operation: impl Into<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            frontend,
+            actor: current_actor(),
+            operation: operation.into(),
+            plugin: None,
+            actions: Value::Null,
+            params: Value::Null,
+            outcome: Outcome::Success,
+            error: None,
+        }
+    }
+
+    /// Override the actor, for callers identified outside a request task
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn with_plugin(mut self, plugin: impl Into<String>) -> Self {
+        self.plugin = Some(plugin.into());
+        self
+    }
+
+    pub fn with_actions(mut self, actions: &impl Serialize) -> Self {
+        self.actions = serde_json::to_value(actions).unwrap_or(Value::Null);
+        self
+    }
+
+    /// Parameters of the request; secrets are redacted
+    pub fn with_params(mut self, params: &Value) -> Self {
+        self.params = redact(params);
+        self
+    }
+
+    /// Take the outcome from a result
+    pub fn with_result<T, E: std::fmt::Display>(self, result: &std::result::Result<T, E>) -> Self {
+        match result {
+            Ok(_) => self,
+            Err(e) => self.failed(e.to_string()),
+        }
+    }
+
+    pub fn failed(mut self, error: impl Into<String>) -> Self {
+        self.outcome = Outcome::Failure;
+        self.error = Some(error.into());
+        self
+    }
+
+    pub fn denied(mut self, error: impl Into<String>) -> Self {
+        self.outcome = Outcome::Denied;
+        self.error = Some(error.into());
+        self
+    }
+
+    /// Human-readable one-line summary
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{} {} via {} by {}: {}",
+            self.operation,
+            self.plugin.as_deref().unwrap_or("-"),
+            self.frontend.as_str(),
+            self.actor.as_deref().unwrap_or("unknown"),
+            self.outcome.as_str(),
+        );
+        if let Some(error) = &self.error {
+            line.push_str(&format!(" ({})", error));
+        }
+        line
+    }
+
+    /// Write the record to the global audit log
+    pub fn record(self) {
+        record(self)
+    }
+}
+
+/// Replace the values of secret-looking keys, recursively
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let lower = key.to_ascii_lowercase();
+                    if SECRET_KEYS.iter().any(|secret| lower.contains(secret)) {
+                        (key.clone(), Value::String("[redacted]".to_string()))
+                    } else {
+                        (key.clone(), redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Filter for `AuditLog::search`
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    /// Substring of the actor
+    pub actor: Option<String>,
+    pub plugin: Option<String>,
+    pub frontend: Option<Frontend>,
+    /// Substring of the operation
+    pub operation: Option<String>,
+    pub outcome: Option<Outcome>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Keep only the newest matches
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let contains = |field: Option<&str>, wanted: &Option<String>| match wanted {
+            Some(wanted) => field.is_some_and(|field| field.contains(wanted.as_str())),
+            None => true,
+        };
+        contains(record.actor.as_deref(), &self.actor)
+            && contains(Some(&record.operation), &self.operation)
+            && self
+                .plugin
+                .as_ref()
+                .is_none_or(|plugin| record.plugin.as_ref() == Some(plugin))
+            && self.frontend.is_none_or(|f| record.frontend == f)
+            && self.outcome.is_none_or(|o| record.outcome == o)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp <= until)
+    }
+}
+
+/// Rotating JSON-lines audit log
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    journal: bool,
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_files: DEFAULT_MAX_FILES,
+            journal: false,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Log configured by `OPDBUS_AUDIT_LOG`, `OPDBUS_AUDIT_MAX_BYTES`,
+    /// `OPDBUS_AUDIT_MAX_FILES` and `OPDBUS_AUDIT_JOURNAL` (on unless "0")
+    pub fn from_env() -> Self {
+        let env_number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+        Self::new(std::env::var("OPDBUS_AUDIT_LOG").unwrap_or_else(|_| DEFAULT_AUDIT_LOG.into()))
+            .with_rotation(
+                env_number("OPDBUS_AUDIT_MAX_BYTES").unwrap_or(DEFAULT_MAX_BYTES),
+                env_number("OPDBUS_AUDIT_MAX_FILES").unwrap_or(DEFAULT_MAX_FILES as u64) as usize,
+            )
+            .with_journal(std::env::var("OPDBUS_AUDIT_JOURNAL").map_or(true, |v| v != "0"))
+    }
+
+    /// Rotate once the file reaches `max_bytes`, keeping `max_files` old files
+    pub fn with_rotation(mut self, max_bytes: u64, max_files: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self.max_files = max_files;
+        self
+    }
+
+    /// Also send each record to the journal
+    pub fn with_journal(mut self, journal: bool) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record, rotating first if the file is full
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        {
+            let _guard = self.write_lock.lock().unwrap();
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let size = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+            if size > 0 && size + line.len() as u64 > self.max_bytes {
+                self.rotate()?;
+            }
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .mode(0o640)
+                .open(&self.path)
+                .with_context(|| format!("Failed to open {}", self.path.display()))?;
+            file.write_all(line.as_bytes())?;
+        }
+
+        if self.journal {
+            let json = line.trim_end();
+            let priority = if record.outcome == Outcome::Success {
+                "6"
+            } else {
+                "4"
+            };
+            let summary = record.summary();
+            let fields = [
+                ("MESSAGE", summary.as_str()),
+                ("PRIORITY", priority),
+                ("SYSLOG_IDENTIFIER", "op-dbus-audit"),
+                ("OPDBUS_AUDIT_ID", record.id.as_str()),
+                ("OPDBUS_FRONTEND", record.frontend.as_str()),
+                ("OPDBUS_ACTOR", record.actor.as_deref().unwrap_or("")),
+                ("OPDBUS_PLUGIN", record.plugin.as_deref().unwrap_or("")),
+                ("OPDBUS_OUTCOME", record.outcome.as_str()),
+                ("OPDBUS_AUDIT", json),
+            ];
+            if let Err(e) = crate::native::journal::send(&fields) {
+                log::debug!("Failed to send audit record to the journal: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// `audit.jsonl.N` for a rotation index; 0 is the live file
+    fn rotated(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            return self.path.clone();
+        }
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+        for index in (0..self.max_files).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated(index + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Existing log files, oldest first
+    pub fn files(&self) -> Vec<PathBuf> {
+        (0..=self.max_files)
+            .rev()
+            .map(|index| self.rotated(index))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    /// Records matching `query`, oldest first
+    pub fn search(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let mut records = Vec::new();
+        for path in self.files() {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            for line in std::io::BufReader::new(file).lines() {
+                let line = line?;
+                match serde_json::from_str::<AuditRecord>(&line) {
+                    Ok(record) if query.matches(&record) => records.push(record),
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::debug!("Skipping malformed audit line in {}: {}", path.display(), e)
+                    }
+                }
+            }
+        }
+        if let Some(limit) = query.limit {
+            let skip = records.len().saturating_sub(limit);
+            records.drain(..skip);
+        }
+        Ok(records)
+    }
+}
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// The process-wide audit log, configured from the environment
+///
+/// Unit tests write to a scratch file instead of the system log.
+pub fn global() -> &'static AuditLog {
+    AUDIT_LOG.get_or_init(|| {
+        if cfg!(test) {
+            AuditLog::new(std::env::temp_dir().join("op-dbus-test-audit.jsonl"))
+        } else {
+            AuditLog::from_env()
+        }
+    })
+}
+
+/// Write a record to the global audit log (best-effort)
+pub fn record(record: AuditRecord) {
+    if let Err(e) = global().append(&record) {
+        log::warn!(
+            "Failed to write audit record ({}): {:#}",
+            record.summary(),
+            e
+        );
+    }
+}
+
+/// Actor name of the user running this process
+pub fn process_actor() -> String {
+    format!("uid:{}", nix::unistd::getuid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_secrets() {
+        let params = json!({
+            "plugin": "net",
+            "wifi": {"ssid": "lab", "PSK": "hunter2"},
+            "users": [{"name": "a", "password_hash": "x"}],
+            "api_token": "abc",
+        });
+        assert_eq!(
+            redact(&params),
+            json!({
+                "plugin": "net",
+                "wifi": {"ssid": "lab", "PSK": "[redacted]"},
+                "users": [{"name": "a", "password_hash": "[redacted]"}],
+                "api_token": "[redacted]",
+            })
+        );
+    }
+
+    #[test]
+    fn test_append_rotate_and_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.jsonl")).with_rotation(400, 2);
+
+        for i in 0..12 {
+            let record = AuditRecord::new(Frontend::Web, "apply")
+                .with_actor(format!("uid:{}", i % 2))
+                .with_plugin(if i % 3 == 0 { "net" } else { "systemd" });
+            let record = if i == 9 {
+                record.failed("boom")
+            } else {
+                record
+            };
+            log.append(&record).unwrap();
+        }
+
+        let files = log.files();
+        assert_eq!(files.len(), 3);
+        assert!(files
+            .iter()
+            .all(|f| std::fs::metadata(f).unwrap().len() <= 400));
+
+        let all = log.search(&AuditQuery::default()).unwrap();
+        assert!(all.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let failed = log
+            .search(&AuditQuery {
+                outcome: Some(Outcome::Failure),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].error.as_deref(), Some("boom"));
+
+        let net = log
+            .search(&AuditQuery {
+                plugin: Some("net".to_string()),
+                actor: Some("uid:1".to_string()),
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(net.len(), 1);
+        assert_eq!(net[0].plugin.as_deref(), Some("net"));
+        assert_eq!(net[0].actor.as_deref(), Some("uid:1"));
+    }
+}
@@ -15,17 +15,8 @@ use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
-// These modules compile fine - no zbus
-mod ollama_mod {
-    include!("../mcp/ollama.rs");
-}
-
-mod ai_context_mod {
-    include!("../mcp/ai_context_provider.rs");
-}
-
-use ai_context_mod::{AiContextProvider, SystemContext};
-use ollama_mod::{ChatMessage, OllamaClient};
+use op_dbus::mcp::ai_context_provider::{AiContextProvider, SystemContext};
+use op_dbus::mcp::ollama::{ChatMessage, OllamaClient};
 
 #[derive(Clone)]
 struct AppState {
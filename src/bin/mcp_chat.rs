@@ -11,11 +11,14 @@ use std::sync::Arc;
 use tower_http::services::ServeDir;
 
 // Import from the existing MCP modules
+use op_dbus::http_tls_server::HttpAuth;
 use op_dbus::mcp::agent_registry::AgentRegistry;
 use op_dbus::mcp::chat_server::{create_chat_router, ChatServerState};
 use op_dbus::mcp::introspection_cache::IntrospectionCache;
 use op_dbus::mcp::ollama::OllamaClient;
-use op_dbus::mcp::tool_registry::{ToolRegistry, ToolRegistryService};
+use op_dbus::mcp::plugin_tool_bridge::PluginToolBridge;
+use op_dbus::mcp::tool_policy::ToolPolicy;
+use op_dbus::mcp::tool_registry::{PolkitMiddleware, ToolRegistry, ToolRegistryService};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -46,6 +49,30 @@ async fn main() -> anyhow::Result<()> {
     // Initialize tool and agent registries
     println!("📦 Initializing MCP components...");
     let tool_registry = Arc::new(ToolRegistry::new());
+
+    // The chat cannot ask for approval; the policy says what happens to
+    // High and Critical tools instead
+    let policy = ToolPolicy::load()?;
+    println!(
+        "🔐 Tools needing approval: {:?} (without_approver.chat in the tool policy)",
+        policy.without_approver("chat")
+    );
+    tool_registry
+        .set_approver(Arc::new(policy.unattended_approver("chat")))
+        .await;
+    tool_registry.set_policy(policy).await;
+
+    // Remote chat users have no polkit subject, so plugin changes are
+    // refused; apply through the dbus-mcp server or the unix socket instead
+    tool_registry
+        .add_middleware(Box::new(PolkitMiddleware::refusing(
+            "the chat server cannot identify its remote caller for polkit",
+        )))
+        .await;
+    PluginToolBridge::builtin(tool_registry.clone())
+        .await
+        .register_plugins_as_tools()
+        .await?;
     let agent_registry = Arc::new(AgentRegistry::new());
 
     // Register introspection tools
@@ -56,7 +83,7 @@ async fn main() -> anyhow::Result<()> {
     // Initialize introspection cache
     println!("💾 Initializing introspection cache...");
     let cache_path = PathBuf::from("/var/cache/dbus-introspection.db");
-    let introspection_cache = Arc::new(IntrospectionCache::new(&cache_path)?);
+    let _introspection_cache = Arc::new(IntrospectionCache::new(&cache_path)?);
 
     // Initialize tool registry service
     println!("🔧 Initializing tool registry service...");
@@ -95,6 +122,7 @@ async fn main() -> anyhow::Result<()> {
         .route("/", get(|| async { Redirect::permanent("/index.html") }))
         .merge(chat_router)
         .nest_service("/", ServeDir::new(&web_dir));
+    let app = HttpAuth::from_env().apply(app);
 
     // Bind to all interfaces (accessible remotely)
     let addr: SocketAddr = "0.0.0.0:8080".parse()?;
@@ -121,7 +149,11 @@ async fn main() -> anyhow::Result<()> {
     println!("\nPress Ctrl+C to stop\n");
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -4,6 +4,22 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::future::Future;
+
+tokio::task_local! {
+    static ACTOR: String;
+}
+
+/// Run `fut` with `actor` (who requested the change) recorded in the
+/// footprints created by it, as metadata key "actor"
+pub async fn with_actor<F: Future>(actor: impl Into<String>, fut: F) -> F::Output {
+    ACTOR.scope(actor.into(), fut).await
+}
+
+/// Actor of the current task, if one was set with `with_actor`
+pub fn current_actor() -> Option<String> {
+    ACTOR.try_with(|actor| actor.clone()).ok()
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginFootprint {
@@ -18,7 +34,11 @@ pub struct PluginFootprint {
 
 impl PluginFootprint {
     #[allow(dead_code)]
-    pub fn new(plugin_id: String, operation: String, metadata: serde_json::Value) -> Self {
+    pub fn new(plugin_id: String, operation: String, mut metadata: serde_json::Value) -> Self {
+        if let (Some(actor), serde_json::Value::Object(obj)) = (current_actor(), &mut metadata) {
+            obj.entry("actor").or_insert(actor.into());
+        }
+
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
@@ -81,11 +101,24 @@ impl FootprintGenerator {
             serde_json::to_string(data).context("Failed to serialize data for hashing")?;
         let data_hash = format!("{:x}", Sha256::digest(data_str.as_bytes()));
 
+        let mut metadata = metadata;
+        let actor = current_actor();
+        if let Some(actor) = &actor {
+            metadata
+                .get_or_insert_with(HashMap::new)
+                .entry("actor".to_string())
+                .or_insert_with(|| actor.clone().into());
+        }
+
         // Hash the entire operation context
-        let context = format!(
+        let mut context = format!(
             "{}:{}:{}:{}",
             self.plugin_id, operation, timestamp, data_hash
         );
+        if let Some(actor) = &actor {
+            context.push(':');
+            context.push_str(actor);
+        }
         let content_hash = format!("{:x}", Sha256::digest(context.as_bytes()));
 
         // Generate vector features for blockchain
@@ -421,4 +454,24 @@ mod tests {
         // Should have object features
         assert_eq!(footprint.vector_features[5], 1.0); // is_object
     }
+
+    #[tokio::test]
+    async fn test_actor_recorded() {
+        let generator = FootprintGenerator::new("net");
+        let data = serde_json::json!({"bridge": "vmbr0"});
+
+        let anonymous = generator.create_footprint("apply", &data, None).unwrap();
+        assert!(!anonymous.metadata.contains_key("actor"));
+
+        let (footprint, legacy) = with_actor("cert:node2.fleet", async {
+            (
+                generator.create_footprint("apply", &data, None).unwrap(),
+                PluginFootprint::new("net".into(), "apply".into(), serde_json::json!({})),
+            )
+        })
+        .await;
+        assert_eq!(footprint.metadata["actor"], "cert:node2.fleet");
+        assert_eq!(legacy.metadata["actor"], "cert:node2.fleet");
+        assert_eq!(current_actor(), None);
+    }
 }
@@ -3,16 +3,18 @@
 //!
 //! This module provides a streaming blockchain implementation that:
 //! 1. Automatically generates hashed footprints for all object modifications
-//! 2. Stores timing and vector data in separate btrfs subvolumes
+//! 2. Stores timing and vector data in separate volumes (btrfs subvolumes, or
+//!    plain directories on other filesystems)
 //! 3. Creates snapshots for each block
-//! 4. Streams vector data to remote vector databases via btrfs send/receive
+//! 4. Streams vector data to replicas via the native replication protocol
 
 use crate::blockchain::PluginFootprint;
-use anyhow::{Context, Result};
+use crate::replication::{ReplicaEndpoint, ReplicaTracker, ReplicationReport, ReplicationSender};
+use crate::storage::{self, SnapshotBackend};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::process::Command;
 use tokio::sync::RwLock;
 use tokio::time::{sleep, Duration, Instant};
 use tracing::{debug, info, warn};
@@ -161,6 +163,8 @@ pub struct StreamingBlockchain {
     snapshot_interval: SnapshotInterval,
     retention_policy: RetentionPolicy,
     last_snapshot_time: Arc<RwLock<Instant>>,
+    replica_tracker: Arc<ReplicaTracker>,
+    backend: Arc<dyn SnapshotBackend>,
 }
 
 impl StreamingBlockchain {
@@ -171,6 +175,16 @@ impl StreamingBlockchain {
     pub async fn new_with_interval(
         base_path: impl AsRef<Path>,
         snapshot_interval: SnapshotInterval,
+    ) -> Result<Self> {
+        let backend = storage::detect(base_path.as_ref());
+        Self::new_with_backend(base_path, snapshot_interval, backend).await
+    }
+
+    /// Create a blockchain on an explicit storage backend
+    pub async fn new_with_backend(
+        base_path: impl AsRef<Path>,
+        snapshot_interval: SnapshotInterval,
+        backend: Arc<dyn SnapshotBackend>,
     ) -> Result<Self> {
         let base_path = base_path.as_ref().to_path_buf();
         let timing_subvol = base_path.join("timing");
@@ -178,9 +192,16 @@ impl StreamingBlockchain {
         let state_subvol = base_path.join("state");
 
         tokio::fs::create_dir_all(&base_path).await?;
-        Self::create_subvolume(&timing_subvol).await?;
-        Self::create_subvolume(&vector_subvol).await?;
-        Self::create_subvolume(&state_subvol).await?;
+        backend.create_volume(&timing_subvol).await?;
+        backend.create_volume(&vector_subvol).await?;
+        backend.create_volume(&state_subvol).await?;
+        info!(
+            "Blockchain at {} using {} storage",
+            base_path.display(),
+            backend.kind()
+        );
+
+        let replica_tracker = Arc::new(ReplicaTracker::load(base_path.join("replicas.json"))?);
 
         Ok(Self {
             base_path,
@@ -190,26 +211,11 @@ impl StreamingBlockchain {
             snapshot_interval,
             retention_policy: RetentionPolicy::from_env(),
             last_snapshot_time: Arc::new(RwLock::new(Instant::now())),
+            replica_tracker,
+            backend,
         })
     }
 
-    async fn create_subvolume(path: &Path) -> Result<()> {
-        if !path.exists() {
-            let output = Command::new("btrfs")
-                .args(["subvolume", "create"])
-                .arg(path)
-                .output()
-                .await
-                .context("Failed to execute btrfs command")?;
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                anyhow::bail!("btrfs subvolume create failed: {}", stderr);
-            }
-        }
-        Ok(())
-    }
-
     pub async fn add_footprint(&self, footprint: PluginFootprint) -> Result<String> {
         let data = serde_json::json!({
             "plugin_id": footprint.plugin_id,
@@ -321,7 +327,7 @@ impl StreamingBlockchain {
         Ok(serde_json::from_str(&content)?)
     }
 
-    /// Get path to state subvolume (for btrfs send/receive)
+    /// Get path to state subvolume (for replication)
     pub fn state_subvolume_path(&self) -> &Path {
         &self.state_subvol
     }
@@ -439,59 +445,39 @@ impl StreamingBlockchain {
 
         // Snapshot timing (audit trail - indexed by block hash)
         let timing_snapshot = snapshot_dir.join(format!("timing-{}", block_hash));
-        let timing_result = Command::new("btrfs")
-            .args(["subvolume", "snapshot", "-r"])
-            .arg(&self.timing_subvol)
-            .arg(&timing_snapshot)
-            .output()
+        if let Err(e) = self
+            .backend
+            .snapshot(&self.timing_subvol, &timing_snapshot)
             .await
-            .context("Failed to create timing snapshot")?;
-
-        if !timing_result.status.success() {
-            warn!(
-                "Failed to create timing snapshot: {}",
-                String::from_utf8_lossy(&timing_result.stderr)
-            );
+        {
+            warn!("Failed to create timing snapshot: {:#}", e);
         }
 
         // Snapshot vectors (ML embeddings - indexed by block hash)
         let vector_snapshot = snapshot_dir.join(format!("vectors-{}", block_hash));
-        let vector_result = Command::new("btrfs")
-            .args(["subvolume", "snapshot", "-r"])
-            .arg(&self.vector_subvol)
-            .arg(&vector_snapshot)
-            .output()
+        if let Err(e) = self
+            .backend
+            .snapshot(&self.vector_subvol, &vector_snapshot)
             .await
-            .context("Failed to create vector snapshot")?;
-
-        if !vector_result.status.success() {
-            warn!(
-                "Failed to create vector snapshot: {}",
-                String::from_utf8_lossy(&vector_result.stderr)
-            );
+        {
+            warn!("Failed to create vector snapshot: {:#}", e);
         }
 
         // Snapshot state (current system state - indexed by timestamp for DR)
         let state_snapshot = snapshot_dir.join(format!("state-{}", timestamp));
-        let state_result = Command::new("btrfs")
-            .args(["subvolume", "snapshot", "-r"])
-            .arg(&self.state_subvol)
-            .arg(&state_snapshot)
-            .output()
+        match self
+            .backend
+            .snapshot(&self.state_subvol, &state_snapshot)
             .await
-            .context("Failed to create state snapshot")?;
-
-        if !state_result.status.success() {
-            warn!(
-                "Failed to create state snapshot: {}",
-                String::from_utf8_lossy(&state_result.stderr)
-            );
-        } else {
-            debug!("Created state snapshot: state-{}", timestamp);
+        {
+            Err(e) => warn!("Failed to create state snapshot: {:#}", e),
+            Ok(()) => {
+                debug!("Created state snapshot: state-{}", timestamp);
 
-            // Prune old state snapshots according to retention policy
-            if let Err(e) = self.prune_state_snapshots().await {
-                warn!("Failed to prune old snapshots: {}", e);
+                // Prune old state snapshots according to retention policy
+                if let Err(e) = self.prune_state_snapshots().await {
+                    warn!("Failed to prune old snapshots: {}", e);
+                }
             }
         }
 
@@ -499,71 +485,87 @@ impl StreamingBlockchain {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub async fn stream_vectors(&self, block_hash: &str, remote: &str) -> Result<()> {
+    /// Replicate the vector snapshot of a block to a replica receiver
+    ///
+    /// `remote` is a replica endpoint (`tcp://host:port`, `tls://host:port`,
+    /// `unix:/path`). The send is incremental against the newest vector
+    /// snapshot the replica has acknowledged.
+    pub async fn stream_vectors(
+        &self,
+        block_hash: &str,
+        remote: &str,
+    ) -> Result<ReplicationReport> {
         let vector_snapshot = self
             .base_path
             .join("snapshots")
             .join(format!("vectors-{}", block_hash));
+        let endpoint: ReplicaEndpoint = remote.parse()?;
 
-        info!("Streaming vectors for block {} to {}", block_hash, remote);
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(format!(
-                "btrfs send {} | ssh {} 'btrfs receive /var/lib/blockchain/vectors/'",
-                vector_snapshot.display(),
-                remote
-            ))
-            .output()
+        info!("Streaming vectors for block {} to {}", block_hash, endpoint);
+        self.replication_sender()?
+            .replicate(&endpoint, &vector_snapshot)
             .await
-            .context("Failed to stream vectors")?;
-
-        if !output.status.success() {
-            anyhow::bail!("Stream failed: {}", String::from_utf8_lossy(&output.stderr));
-        }
-
-        Ok(())
     }
 
-    #[allow(dead_code)]
-    pub async fn stream_to_replicas(&self, block_hash: &str, replicas: &[String]) -> Result<()> {
+    /// Replicate the vector snapshot of a block to several replicas
+    ///
+    /// Every replica is attempted; the call fails if any of them failed.
+    pub async fn stream_to_replicas(
+        &self,
+        block_hash: &str,
+        replicas: &[String],
+    ) -> Result<Vec<ReplicationReport>> {
         let vector_snapshot = self
             .base_path
             .join("snapshots")
             .join(format!("vectors-{}", block_hash));
-
-        let mut tee_args = Vec::new();
-        for replica in replicas {
-            tee_args.push(format!(
-                ">(ssh {} 'btrfs receive /var/lib/blockchain/vectors/')",
-                replica
-            ));
-        }
-
-        let cmd = format!(
-            "btrfs send {} | tee {} > /dev/null",
-            vector_snapshot.display(),
-            tee_args.join(" ")
-        );
+        let sender = self.replication_sender()?;
 
         info!("Streaming to {} replicas", replicas.len());
 
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(&cmd)
-            .output()
-            .await
-            .context("Failed to stream to replicas")?;
+        let mut reports = Vec::new();
+        let mut failures = Vec::new();
+        for replica in replicas {
+            let result = match replica.parse::<ReplicaEndpoint>() {
+                Ok(endpoint) => sender.replicate(&endpoint, &vector_snapshot).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(report) => reports.push(report),
+                Err(e) => {
+                    warn!("Replication to {} failed: {:#}", replica, e);
+                    failures.push(format!("{}: {:#}", replica, e));
+                }
+            }
+        }
 
-        if !output.status.success() {
+        if !failures.is_empty() {
             anyhow::bail!(
-                "Multi-replica stream failed: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "Multi-replica stream failed for {} of {} replicas: {}",
+                failures.len(),
+                replicas.len(),
+                failures.join("; ")
             );
         }
 
-        Ok(())
+        Ok(reports)
+    }
+
+    /// Per-replica acknowledged snapshots
+    pub fn replica_tracker(&self) -> &Arc<ReplicaTracker> {
+        &self.replica_tracker
+    }
+
+    /// Storage backend holding the subvolumes and snapshots
+    pub fn backend(&self) -> &Arc<dyn SnapshotBackend> {
+        &self.backend
+    }
+
+    fn replication_sender(&self) -> Result<ReplicationSender> {
+        ReplicationSender::from_env(
+            self.backend.stream_source(),
+            Arc::clone(&self.replica_tracker),
+        )
     }
 
     /// Get current snapshot interval configuration
@@ -702,26 +704,13 @@ impl StreamingBlockchain {
         for (name, _dt) in &snapshots {
             if !keep_snapshots.contains(name) {
                 let snapshot_path = snapshot_dir.join(name);
-                match Command::new("btrfs")
-                    .args(["subvolume", "delete"])
-                    .arg(&snapshot_path)
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            deleted_count += 1;
-                            debug!("Pruned old snapshot: {}", name);
-                        } else {
-                            warn!(
-                                "Failed to delete snapshot {}: {}",
-                                name,
-                                String::from_utf8_lossy(&output.stderr)
-                            );
-                        }
+                match self.backend.delete(&snapshot_path).await {
+                    Ok(()) => {
+                        deleted_count += 1;
+                        debug!("Pruned old snapshot: {}", name);
                     }
                     Err(e) => {
-                        warn!("Failed to execute btrfs delete for {}: {}", name, e);
+                        warn!("Failed to delete snapshot {}: {:#}", name, e);
                     }
                 }
             }
@@ -789,3 +778,47 @@ impl StreamingBlockchain {
         Ok(state_file)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{CopyMode, DirectoryBackend};
+
+    #[tokio::test]
+    async fn test_snapshots_on_directory_backend() {
+        let temp = tempfile::tempdir().unwrap();
+        let blockchain = StreamingBlockchain::new_with_backend(
+            temp.path(),
+            SnapshotInterval::PerOperation,
+            Arc::new(DirectoryBackend::with_mode(CopyMode::Hardlink)),
+        )
+        .await
+        .unwrap();
+
+        blockchain
+            .update_current_state(&serde_json::json!({"plugins": {}}))
+            .await
+            .unwrap();
+        let footprint = PluginFootprint::new(
+            "systemd".to_string(),
+            "apply".to_string(),
+            serde_json::json!({"unit": "nginx.service"}),
+        );
+        let hash = blockchain.add_footprint(footprint).await.unwrap();
+
+        let snapshots = temp.path().join("snapshots");
+        assert!(snapshots
+            .join(format!("timing-{}", hash))
+            .join(format!("{}.json", hash))
+            .exists());
+        assert!(snapshots
+            .join(format!("vectors-{}", hash))
+            .join(format!("{}.vec", hash))
+            .exists());
+
+        let states = blockchain.list_state_snapshots().await.unwrap();
+        assert_eq!(states.len(), 1);
+        let state_file = blockchain.rollback_to_snapshot(&states[0].0).await.unwrap();
+        assert!(state_file.exists());
+    }
+}
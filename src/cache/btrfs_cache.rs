@@ -5,6 +5,7 @@
 //! - SQLite index for O(1) lookups
 //! - Linux page cache for hot data
 //! - Automatic snapshot management
+//! - Incremental replication of snapshots to replicas
 //! - NUMA-aware memory allocation and CPU affinity
 
 use anyhow::{Context, Result};
@@ -14,11 +15,13 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
-    Mutex,
+    Arc, Mutex,
 };
 use tracing::{debug, info, warn};
 
 use super::snapshot_manager::{SnapshotConfig, SnapshotManager};
+use crate::replication::{ReplicaEndpoint, ReplicaTracker, ReplicationReport, ReplicationSender};
+use crate::storage::{self, SnapshotBackend};
 
 /// NUMA node information and CPU mapping
 #[derive(Debug, Clone)]
@@ -59,6 +62,7 @@ pub struct BtrfsCache {
     cache_dir: PathBuf,
     index: Mutex<rusqlite::Connection>,
     snapshot_manager: SnapshotManager,
+    replica_tracker: Arc<ReplicaTracker>,
     numa_nodes: Vec<NumaNode>,
     placement_strategy: CachePlacementStrategy,
     memory_policy: MemoryPolicy,
@@ -68,24 +72,28 @@ pub struct BtrfsCache {
 
 #[allow(dead_code)]
 impl BtrfsCache {
-    /// Create BTRFS subvolume at specified path
-    async fn create_btrfs_subvolume(path: &Path) -> Result<()> {
-        crate::native::btrfs::create_subvolume(path).await
+    /// Create new cache, using BTRFS subvolumes where available
+    pub async fn new(cache_dir: PathBuf) -> Result<Self> {
+        let backend = storage::detect(&cache_dir);
+        Self::new_with_backend(cache_dir, backend).await
     }
 
-    /// Create new BTRFS cache with proper subvolumes
-    pub async fn new(cache_dir: PathBuf) -> Result<Self> {
+    /// Create new cache on an explicit storage backend
+    pub async fn new_with_backend(
+        cache_dir: PathBuf,
+        backend: Arc<dyn SnapshotBackend>,
+    ) -> Result<Self> {
         // Ensure parent directory exists (not as subvolume)
         if let Some(parent) = cache_dir.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
 
-        // Create BTRFS subvolumes for cache structure
-        Self::create_btrfs_subvolume(&cache_dir).await?;
-        Self::create_btrfs_subvolume(&cache_dir.join("embeddings")).await?;
-        Self::create_btrfs_subvolume(&cache_dir.join("blocks")).await?;
-        Self::create_btrfs_subvolume(&cache_dir.join("queries")).await?;
-        Self::create_btrfs_subvolume(&cache_dir.join("diffs")).await?;
+        // Create volumes for cache structure
+        backend.create_volume(&cache_dir).await?;
+        backend.create_volume(&cache_dir.join("embeddings")).await?;
+        backend.create_volume(&cache_dir.join("blocks")).await?;
+        backend.create_volume(&cache_dir.join("queries")).await?;
+        backend.create_volume(&cache_dir.join("diffs")).await?;
 
         // Create regular directories within subvolumes
         tokio::fs::create_dir_all(cache_dir.join("embeddings/vectors")).await?;
@@ -137,7 +145,13 @@ impl BtrfsCache {
             prefix: "cache".to_string(),
         };
 
-        let snapshot_manager = SnapshotManager::new(cache_dir.clone(), snapshot_config);
+        // Replica acknowledgements live next to the snapshots they refer to
+        let replica_tracker = Arc::new(ReplicaTracker::load(
+            snapshot_config.snapshot_dir.join("replicas.json"),
+        )?);
+
+        let snapshot_manager =
+            SnapshotManager::new(cache_dir.clone(), snapshot_config).with_backend(backend);
 
         // Detect NUMA topology
         // Simple NUMA detection
@@ -166,6 +180,7 @@ impl BtrfsCache {
             cache_dir,
             index: Mutex::new(index),
             snapshot_manager,
+            replica_tracker,
             numa_nodes,
             placement_strategy,
             memory_policy,
@@ -591,7 +606,7 @@ impl BtrfsCache {
         Ok(())
     }
 
-    /// Create BTRFS snapshot of cache
+    /// Create read-only snapshot of cache
     pub async fn create_snapshot(&self) -> Result<PathBuf> {
         self.snapshot_manager.create_snapshot().await
     }
@@ -606,82 +621,42 @@ impl BtrfsCache {
         self.snapshot_manager.delete_all_snapshots().await
     }
 
-    /// Stream cache data to remote system using Btrfs send/receive with NUMA affinity
+    /// Stream a fresh cache snapshot to a replica receiver with NUMA affinity
+    ///
+    /// `remote` is a replica endpoint (`tcp://host:port`, `tls://host:port`,
+    /// `unix:/path`); the send is incremental against the newest cache
+    /// snapshot the replica has acknowledged.
     pub async fn stream_to_remote(
         &self,
-        remote_host: &str,
-        remote_path: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        remote: &str,
+    ) -> Result<ReplicationReport, Box<dyn std::error::Error + Send + Sync>> {
         // Apply NUMA affinity for streaming operations
         self.apply_numa_affinity("cache_streaming").await?;
 
+        let endpoint: ReplicaEndpoint = remote
+            .parse()
+            .map_err(|e| format!("Invalid replica endpoint '{}': {}", remote, e))?;
+
         let snapshot_path = self
             .create_snapshot()
             .await
             .map_err(|e| format!("Failed to create snapshot: {}", e))?;
 
-        info!(
-            "Streaming cache snapshot to {}:{}",
-            remote_host, remote_path
-        );
+        info!("Streaming cache snapshot to {}", endpoint);
 
-        let cmd = format!(
-            "btrfs send {} | ssh {} 'btrfs receive {}'",
-            snapshot_path.display(),
-            remote_host,
-            remote_path
-        );
+        let sender = ReplicationSender::from_env(
+            self.snapshot_manager.backend().stream_source(),
+            Arc::clone(&self.replica_tracker),
+        )
+        .map_err(|e| format!("Failed to configure replication: {}", e))?;
 
-        let output = tokio::process::Command::new("bash")
-            .arg("-c")
-            .arg(&cmd)
-            .output()
+        let report = sender
+            .replicate(&endpoint, &snapshot_path)
             .await
-            .map_err(|e| format!("Failed to execute btrfs stream command: {}", e))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Btrfs streaming failed: {}", stderr).into());
-        }
+            .map_err(|e| format!("Snapshot streaming failed: {:#}", e))?;
 
         info!("Successfully streamed cache snapshot");
-        Ok(())
-    }
-
-    /// Receive cache data from remote system with NUMA affinity
-    pub async fn receive_from_remote(
-        &self,
-        remote_host: &str,
-        remote_snapshot: &str,
-        local_path: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Apply NUMA affinity for receiving operations
-        self.apply_numa_affinity("cache_receiving").await?;
-
-        info!(
-            "Receiving cache snapshot from {}:{}",
-            remote_host, remote_snapshot
-        );
-
-        let cmd = format!(
-            "ssh {} 'btrfs send {}' | btrfs receive {}",
-            remote_host, remote_snapshot, local_path
-        );
-
-        let output = tokio::process::Command::new("bash")
-            .arg("-c")
-            .arg(&cmd)
-            .output()
-            .await
-            .map_err(|e| format!("Failed to execute btrfs receive command: {}", e))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Btrfs receive failed: {}", stderr).into());
-        }
-
-        info!("Successfully received cache snapshot");
-        Ok(())
+        Ok(report)
     }
 
     /// Get NUMA configuration info
@@ -807,6 +782,22 @@ impl CacheStats {
         }
     }
 
+    /// Fraction of lookups served from the cache
+    ///
+    /// Every entry's first access is the miss that stored it, so accesses
+    /// beyond one per entry are hits.
+    #[allow(dead_code)]
+    pub fn hit_ratio(&self) -> f64 {
+        if self.total_accesses == 0 {
+            0.0
+        } else {
+            let hits = self
+                .total_accesses
+                .saturating_sub(self.total_entries as u64);
+            hits as f64 / self.total_accesses as f64
+        }
+    }
+
     pub fn avg_accesses(&self) -> f64 {
         if self.total_entries == 0 {
             0.0
@@ -841,4 +832,17 @@ mod tests {
         assert_ne!(hash1, hash3);
         assert_eq!(hash1.len(), 64); // SHA256 hex length
     }
+
+    #[test]
+    fn test_hit_ratio() {
+        let stats = CacheStats {
+            total_entries: 2,
+            hot_entries: 0,
+            total_accesses: 8,
+            disk_usage_bytes: 0,
+            embeddings_size_bytes: 0,
+            blocks_size_bytes: 0,
+        };
+        assert_eq!(stats.hit_ratio(), 0.75);
+    }
 }
@@ -414,11 +414,9 @@ impl NumaStats {
     }
 
     pub fn avg_latency_ns(&self) -> u64 {
-        if self.operations == 0 {
-            0
-        } else {
-            self.total_latency_ns / self.operations
-        }
+        self.total_latency_ns
+            .checked_div(self.operations)
+            .unwrap_or(0)
     }
 
     pub fn local_hit_rate(&self) -> f64 {
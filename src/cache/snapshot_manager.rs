@@ -1,10 +1,13 @@
-//! BTRFS snapshot management with automatic rotation
+//! Cache snapshot management with automatic rotation
 //!
-//! Manages cache snapshots with configurable retention policy
+//! Manages cache snapshots with configurable retention policy. Snapshots are
+//! BTRFS subvolume snapshots or directory copies, depending on the storage
+//! backend.
 
-use anyhow::{Context, Result};
+use crate::storage::{self, SnapshotBackend};
+use anyhow::Result;
 use std::path::{Path, PathBuf};
-use tokio::process::Command;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct SnapshotConfig {
@@ -31,17 +34,30 @@ impl Default for SnapshotConfig {
 pub struct SnapshotManager {
     config: SnapshotConfig,
     source_subvol: PathBuf,
+    backend: Arc<dyn SnapshotBackend>,
 }
 
 impl SnapshotManager {
-    /// Create new snapshot manager
+    /// Create new snapshot manager, detecting the storage backend of the source
     pub fn new(source_subvol: PathBuf, config: SnapshotConfig) -> Self {
         Self {
+            backend: storage::detect(&source_subvol),
             config,
             source_subvol,
         }
     }
 
+    /// Use an explicit storage backend
+    pub fn with_backend(mut self, backend: Arc<dyn SnapshotBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Storage backend snapshots are created with
+    pub fn backend(&self) -> &Arc<dyn SnapshotBackend> {
+        &self.backend
+    }
+
     /// Create snapshot with automatic rotation
     pub async fn create_snapshot(&self) -> Result<PathBuf> {
         // Create snapshot directory if it doesn't exist
@@ -52,21 +68,16 @@ impl SnapshotManager {
         let snapshot_name = format!("{}@{}", self.config.prefix, timestamp);
         let snapshot_path = self.config.snapshot_dir.join(&snapshot_name);
 
-        log::info!("Creating BTRFS snapshot: {}", snapshot_name);
+        log::info!(
+            "Creating {} snapshot: {}",
+            self.backend.kind(),
+            snapshot_name
+        );
 
         // Create readonly snapshot
-        let output = Command::new("btrfs")
-            .args(["subvolume", "snapshot", "-r"])
-            .arg(&self.source_subvol)
-            .arg(&snapshot_path)
-            .output()
-            .await
-            .context("Failed to execute btrfs snapshot command")?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to create snapshot: {}", stderr);
-        }
+        self.backend
+            .snapshot(&self.source_subvol, &snapshot_path)
+            .await?;
 
         log::info!("Created snapshot: {}", snapshot_path.display());
 
@@ -151,20 +162,7 @@ impl SnapshotManager {
     /// Delete a specific snapshot
     pub async fn delete_snapshot(&self, snapshot_path: &Path) -> Result<()> {
         log::debug!("Deleting snapshot: {}", snapshot_path.display());
-
-        let output = Command::new("btrfs")
-            .args(["subvolume", "delete"])
-            .arg(snapshot_path)
-            .output()
-            .await
-            .context("Failed to execute btrfs delete command")?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to delete snapshot: {}", stderr);
-        }
-
-        Ok(())
+        self.backend.delete(snapshot_path).await
     }
 
     /// Delete all snapshots
@@ -214,4 +212,33 @@ mod tests {
         assert_eq!(config.max_snapshots, 24);
         assert_eq!(config.prefix, "cache");
     }
+
+    #[tokio::test]
+    async fn test_rotation_on_directory_backend() {
+        use crate::storage::{CopyMode, DirectoryBackend};
+
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("cache");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("entry"), b"cached").unwrap();
+
+        let config = SnapshotConfig {
+            snapshot_dir: temp.path().join("@cache-snapshots"),
+            max_snapshots: 1,
+            prefix: "cache".to_string(),
+        };
+        let manager = SnapshotManager::new(source, config)
+            .with_backend(Arc::new(DirectoryBackend::with_mode(CopyMode::Hardlink)));
+
+        let first = manager.create_snapshot().await.unwrap();
+        // Snapshot names have second resolution
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let second = manager.create_snapshot().await.unwrap();
+
+        assert_eq!(manager.list_snapshots().await.unwrap().len(), 1);
+        assert!(!first.exists());
+        assert_eq!(std::fs::read(second.join("entry")).unwrap(), b"cached");
+
+        assert_eq!(manager.delete_all_snapshots().await.unwrap(), 1);
+    }
 }
@@ -1,17 +1,19 @@
 //! BTRFS-based deployment image manager with symlink deduplication
 //!
 //! Creates deployment "images" as folders where:
-//! - Each folder is a BTRFS snapshot for streaming
+//! - Each folder is a read-only snapshot for streaming (BTRFS subvolume
+//!   snapshot, or a directory snapshot on other filesystems)
 //! - Files that exist in previous images are symlinked (deduplication)
 //! - New files are copied normally
 //! - Images can be streamed for deployment
 
+use crate::storage::{self, SnapshotBackend};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs as async_fs;
-use tokio::process::Command;
 
 /// Deployment image metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,11 +37,12 @@ pub struct FileEntry {
     pub hash: Option<String>, // SHA256 hash for deduplication
 }
 
-/// Image manager for BTRFS-based deployment images
+/// Image manager for snapshot-based deployment images
 pub struct ImageManager {
     base_path: PathBuf,
     images_dir: PathBuf,
     snapshots_dir: PathBuf,
+    backend: Arc<dyn SnapshotBackend>,
 }
 
 impl ImageManager {
@@ -49,42 +52,31 @@ impl ImageManager {
         Self {
             images_dir: base.join("images"),
             snapshots_dir: base.join("snapshots"),
+            backend: storage::detect(&base),
             base_path: base,
         }
     }
 
+    /// Use an explicit storage backend
+    pub fn with_backend(mut self, backend: Arc<dyn SnapshotBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
     /// Initialize the deployment directory structure
     pub async fn init(&self) -> Result<()> {
         async_fs::create_dir_all(&self.images_dir).await?;
         async_fs::create_dir_all(&self.snapshots_dir).await?;
 
-        // Check if we're on BTRFS
-        if self.is_btrfs(&self.base_path).await? {
-            log::info!("BTRFS filesystem detected - snapshots enabled");
-        } else {
-            log::warn!("Not on BTRFS - snapshots will be disabled");
-        }
+        log::info!(
+            "Deployment images in {} use {} storage",
+            self.base_path.display(),
+            self.backend.kind()
+        );
 
         Ok(())
     }
 
-    /// Check if path is on a BTRFS filesystem
-    async fn is_btrfs(&self, path: &Path) -> Result<bool> {
-        let output = Command::new("findmnt")
-            .args(["-n", "-o", "FSTYPE", "-T"])
-            .arg(path)
-            .output()
-            .await
-            .context("Failed to check filesystem type")?;
-
-        if output.status.success() {
-            let fstype = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            Ok(fstype == "btrfs")
-        } else {
-            Ok(false)
-        }
-    }
-
     /// Create a new deployment image
     ///
     /// # Arguments
@@ -100,9 +92,9 @@ impl ImageManager {
         // Get list of existing images (sorted by creation time)
         let existing_images = self.list_images().await?;
 
-        // Create image directory
+        // Create image volume
         let image_path = self.images_dir.join(image_name);
-        async_fs::create_dir_all(&image_path).await?;
+        self.backend.create_volume(&image_path).await?;
 
         let mut image_metadata = ImageMetadata {
             name: image_name.to_string(),
@@ -191,10 +183,8 @@ impl ImageManager {
         let metadata_json = serde_json::to_string_pretty(&image_metadata)?;
         async_fs::write(&metadata_path, metadata_json).await?;
 
-        // Create BTRFS snapshot for streaming
-        if self.is_btrfs(&self.base_path).await? {
-            self.create_image_snapshot(image_name).await?;
-        }
+        // Create read-only snapshot for streaming
+        self.create_image_snapshot(image_name).await?;
 
         log::info!(
             "Created image: {} (unique: {} bytes, symlinked: {} bytes)",
@@ -290,27 +280,23 @@ impl ImageManager {
         Ok(format!("{:x}", hash))
     }
 
-    /// Create BTRFS snapshot of an image for streaming
+    /// Create read-only snapshot of an image for streaming
     async fn create_image_snapshot(&self, image_name: &str) -> Result<PathBuf> {
         let image_path = self.images_dir.join(image_name);
         let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
         let snapshot_name = format!("{}-{}", image_name, timestamp);
         let snapshot_path = self.snapshots_dir.join(&snapshot_name);
 
-        log::info!("Creating BTRFS snapshot: {}", snapshot_name);
+        log::info!(
+            "Creating {} snapshot: {}",
+            self.backend.kind(),
+            snapshot_name
+        );
 
-        let output = Command::new("btrfs")
-            .args(["subvolume", "snapshot", "-r"])
-            .arg(&image_path)
-            .arg(&snapshot_path)
-            .output()
+        self.backend
+            .snapshot(&image_path, &snapshot_path)
             .await
-            .context("Failed to create BTRFS snapshot")?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to create snapshot: {}", stderr);
-        }
+            .with_context(|| format!("Failed to snapshot image {}", image_name))?;
 
         log::info!("Created snapshot: {}", snapshot_path.display());
         Ok(snapshot_path)
@@ -403,40 +389,18 @@ impl ImageManager {
                 let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
 
                 if name.starts_with(image_name) {
-                    if self.is_btrfs(&path).await? {
-                        let output = Command::new("btrfs")
-                            .args(["subvolume", "delete"])
-                            .arg(&path)
-                            .output()
-                            .await?;
-
-                        if !output.status.success() {
-                            log::warn!("Failed to delete snapshot: {}", path.display());
-                        }
-                    } else {
-                        async_fs::remove_dir_all(&path).await?;
+                    if let Err(e) = self.backend.delete(&path).await {
+                        log::warn!("Failed to delete snapshot {}: {:#}", path.display(), e);
                     }
                 }
             }
         }
 
-        // Delete image directory
-        if image_path.exists() {
-            if self.is_btrfs(&self.base_path).await? {
-                let output = Command::new("btrfs")
-                    .args(["subvolume", "delete"])
-                    .arg(&image_path)
-                    .output()
-                    .await?;
-
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    anyhow::bail!("Failed to delete image subvolume: {}", stderr);
-                }
-            } else {
-                async_fs::remove_dir_all(&image_path).await?;
-            }
-        }
+        // Delete image volume
+        self.backend
+            .delete(&image_path)
+            .await
+            .context("Failed to delete image volume")?;
 
         log::info!("Deleted image: {}", image_name);
         Ok(())
@@ -457,4 +421,31 @@ mod tests {
         assert!(temp_dir.path().join("images").exists());
         assert!(temp_dir.path().join("snapshots").exists());
     }
+
+    #[tokio::test]
+    async fn test_image_snapshots_on_directory_backend() {
+        use crate::storage::{CopyMode, DirectoryBackend};
+
+        let temp_dir = TempDir::new().unwrap();
+        let manager = ImageManager::new(temp_dir.path().join("deploy"))
+            .with_backend(Arc::new(DirectoryBackend::with_mode(CopyMode::Hardlink)));
+        manager.init().await.unwrap();
+
+        let input = temp_dir.path().join("op-dbus.conf");
+        std::fs::write(&input, b"config").unwrap();
+
+        manager
+            .create_image("STAGE", vec![input.clone()])
+            .await
+            .unwrap();
+        let snapshot = manager.get_streamable_snapshot("STAGE").await.unwrap();
+        assert_eq!(
+            std::fs::read(snapshot.join("op-dbus.conf")).unwrap(),
+            b"config"
+        );
+
+        manager.delete_image("STAGE").await.unwrap();
+        assert!(!snapshot.exists());
+        assert!(manager.list_images().await.unwrap().is_empty());
+    }
 }
@@ -0,0 +1,12 @@
+//! Small encoding helpers shared by the token store, mutual TLS, replication
+//! and state plans
+
+/// Lowercase hex encoding
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare secrets without returning early on the first differing byte
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -410,12 +410,13 @@ mod tests {
     async fn test_event_bus() {
         let bus = EventBus::new();
 
-        // Subscribe to events
-        let received = Arc::new(RwLock::new(Vec::new()));
+        // Subscribe to events; handlers run on the runtime, so they cannot
+        // block on an async lock
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
         let received_clone = received.clone();
 
         bus.subscribe("TestEvent", move |event| {
-            let mut r = received_clone.blocking_write();
+            let mut r = received_clone.lock().unwrap();
             r.push(event.event_type().to_string());
             Ok(())
         })
@@ -434,7 +435,7 @@ mod tests {
 
         // Check received
         tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-        let r = received.read().await;
+        let r = received.lock().unwrap();
         assert_eq!(r.len(), 1);
         assert_eq!(r[0], "TestEvent");
     }
@@ -156,7 +156,7 @@ pub mod utils {
 
     /// Check if a service is responding
     pub async fn check_service_health(
-        name: &str,
+        _name: &str,
         url: &str,
         timeout: std::time::Duration,
     ) -> ServiceHealth {
@@ -198,17 +198,41 @@ pub mod utils {
     }
 
     /// Check database connectivity
+    ///
+    /// `connection_string` is the path of a SQLite database, such as the
+    /// cache index; it is opened read-only and queried.
     pub async fn check_database_health(connection_string: &str) -> ServiceHealth {
         let start_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        // Placeholder - would implement actual DB health checks
-        ServiceHealth {
-            status: "healthy".to_string(),
-            message: Some("Database connection OK".to_string()),
-            last_check: start_time,
+        let path = connection_string.to_string();
+        let result = tokio::task::spawn_blocking(move || {
+            let conn = rusqlite::Connection::open_with_flags(
+                &path,
+                rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+            )?;
+            conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+        })
+        .await;
+
+        match result {
+            Ok(Ok(_)) => ServiceHealth {
+                status: "healthy".to_string(),
+                message: Some("Database connection OK".to_string()),
+                last_check: start_time,
+            },
+            Ok(Err(e)) => ServiceHealth {
+                status: "unhealthy".to_string(),
+                message: Some(format!("Database error: {}", e)),
+                last_check: start_time,
+            },
+            Err(e) => ServiceHealth {
+                status: "error".to_string(),
+                message: Some(format!("Database check panicked: {}", e)),
+                last_check: start_time,
+            },
         }
     }
 
@@ -0,0 +1,610 @@
+//! Local Certificate Authority
+//!
+//! Hosts without outbound network cannot use ACME and rarely have a
+//! certificate to auto-detect. Instead each host keeps a local CA in
+//! `OPDBUS_TLS_DIR` (default /var/lib/op-dbus/tls) and issues itself a
+//! short-lived server certificate for its hostname and addresses, renewed
+//! before expiry and hot-reloaded into the running server. Clients trust
+//! the host by importing `ca.pem` (`op-dbus tls export-ca`).
+//!
+//! A CA created on first use belongs to that host alone, so peers with their
+//! own CAs reject each other's server and client certificates. For a fleet,
+//! create one CA, copy its `ca.pem` and `ca.key` to every host with
+//! `op-dbus tls import-ca`, and each host issues its certificates from it.
+//!
+//! Keys are ECDSA P-256; certificates are generated with `rcgen` and read
+//! back with `x509-parser`.
+
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use x509_parser::prelude::{parse_x509_certificate, X509Certificate};
+
+use super::mtls::ClientAuthConfig;
+use super::tls::TlsError;
+
+pub const DEFAULT_TLS_DIR: &str = "/var/lib/op-dbus/tls";
+
+/// Lifetime of the CA certificate
+const CA_VALIDITY_DAYS: i64 = 3650;
+/// Lifetime of issued server certificates
+const SERVER_VALIDITY_DAYS: i64 = 90;
+/// Server certificates are reissued when they expire within this window
+const RENEW_BEFORE_DAYS: i64 = 30;
+/// How often the rotation task checks the server certificate
+const ROTATION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 3600);
+
+pub const CA_CERT: &str = "ca.pem";
+const CA_KEY: &str = "ca.key";
+pub const SERVER_CERT: &str = "server.pem";
+pub const SERVER_KEY: &str = "server.key";
+const SERVER_NAMES: &str = "server.json";
+
+/// DNS names and addresses a server certificate is issued for
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostNames {
+    pub dns: Vec<String>,
+    pub ips: Vec<IpAddr>,
+}
+
+impl HostNames {
+    /// Hostname, FQDN, localhost and the addresses of all interfaces
+    pub fn detect() -> Self {
+        let mut names = Self::default();
+        let hostname = gethostname::gethostname().to_string_lossy().to_string();
+        names.add_dns(&hostname);
+        if let Ok(content) = std::fs::read_to_string("/etc/hostname") {
+            names.add_dns(content.trim());
+        }
+        names.add_dns("localhost");
+
+        names.add_ip("127.0.0.1".parse().unwrap());
+        names.add_ip("::1".parse().unwrap());
+        if let Ok(addrs) = nix::ifaddrs::getifaddrs() {
+            for ifaddr in addrs {
+                let Some(address) = ifaddr.address else {
+                    continue;
+                };
+                if let Some(v4) = address.as_sockaddr_in() {
+                    names.add_ip(IpAddr::V4(std::net::Ipv4Addr::from(v4.ip())));
+                } else if let Some(v6) = address.as_sockaddr_in6() {
+                    // Link-local addresses need a scope id and cannot be in a SAN
+                    if v6.ip().segments()[0] & 0xffc0 != 0xfe80 {
+                        names.add_ip(IpAddr::V6(v6.ip()));
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    pub fn add_dns(&mut self, name: &str) {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        if !name.is_empty() && !self.dns.contains(&name) {
+            self.dns.push(name);
+        }
+    }
+
+    pub fn add_ip(&mut self, ip: IpAddr) {
+        if !self.ips.contains(&ip) {
+            self.ips.push(ip);
+        }
+    }
+
+    /// Whether every name and address in `other` is covered
+    pub fn covers(&self, other: &HostNames) -> bool {
+        other.dns.iter().all(|name| self.dns.contains(name))
+            && other.ips.iter().all(|ip| self.ips.contains(ip))
+    }
+}
+
+/// A CA certificate and key persisted in a directory
+pub struct LocalCa {
+    dir: PathBuf,
+    cert_pem: String,
+    /// The CA certificate as rcgen needs it for signing
+    issuer: Certificate,
+    key: KeyPair,
+}
+
+impl LocalCa {
+    /// `OPDBUS_TLS_DIR` or the default directory
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("OPDBUS_TLS_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_TLS_DIR))
+    }
+
+    /// Load the CA in `dir`, creating it on first use
+    pub fn open_or_create(dir: impl Into<PathBuf>) -> Result<Self, TlsError> {
+        let dir = dir.into();
+        let cert_path = dir.join(CA_CERT);
+        let key_path = dir.join(CA_KEY);
+
+        if cert_path.exists() && key_path.exists() {
+            let cert_pem = std::fs::read_to_string(&cert_path)?;
+            let key = KeyPair::from_pem(&std::fs::read_to_string(&key_path)?)
+                .map_err(|e| TlsError::InvalidCert(format!("Invalid CA key: {}", e)))?;
+            return Self::new(dir, cert_pem, key);
+        }
+
+        let hostname = gethostname::gethostname().to_string_lossy().to_string();
+        let key = KeyPair::generate().map_err(cert_error)?;
+        let now = Utc::now();
+        let mut params = CertificateParams::default();
+        params.distinguished_name = name(&format!("op-dbus CA {}", hostname));
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+        ];
+        params.not_before = to_time(now - Duration::hours(1))?;
+        params.not_after = to_time(now + Duration::days(CA_VALIDITY_DAYS))?;
+        let cert = params.self_signed(&key).map_err(cert_error)?;
+
+        std::fs::create_dir_all(&dir)?;
+        write_file(&key_path, key.serialize_pem().as_bytes(), 0o600)?;
+        write_file(&cert_path, cert.pem().as_bytes(), 0o644)?;
+        tracing::info!("Created local CA in {}", dir.display());
+
+        Ok(Self {
+            dir,
+            cert_pem: cert.pem(),
+            issuer: cert,
+            key,
+        })
+    }
+
+    /// Install an existing CA, such as one shared by the fleet, in `dir`
+    ///
+    /// The server certificate is reissued from it on the next
+    /// `ensure_server_cert`.
+    pub fn import(
+        dir: impl Into<PathBuf>,
+        cert_pem: &str,
+        key_pem: &str,
+    ) -> Result<Self, TlsError> {
+        let dir = dir.into();
+        let key = KeyPair::from_pem(key_pem)
+            .map_err(|e| TlsError::InvalidCert(format!("Invalid CA key: {}", e)))?;
+        let ca = Self::new(dir, cert_pem.to_string(), key)?;
+        let der = rustls_pemfile::certs(&mut cert_pem.as_bytes())?.remove(0);
+        if !parse_certificate(&der)?.is_ca() {
+            return Err(TlsError::InvalidCert(
+                "Not a CA certificate (basicConstraints CA:FALSE)".to_string(),
+            ));
+        }
+
+        std::fs::create_dir_all(&ca.dir)?;
+        match std::fs::remove_file(ca.dir.join(SERVER_NAMES)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        write_file(&ca.dir.join(CA_KEY), key_pem.as_bytes(), 0o600)?;
+        write_file(&ca.dir.join(CA_CERT), cert_pem.as_bytes(), 0o644)?;
+        tracing::info!("Imported CA into {}", ca.dir.display());
+        Ok(ca)
+    }
+
+    fn new(dir: PathBuf, cert_pem: String, key: KeyPair) -> Result<Self, TlsError> {
+        let der = rustls_pemfile::certs(&mut cert_pem.as_bytes())?
+            .into_iter()
+            .next()
+            .ok_or_else(|| TlsError::CertNotFound(dir.join(CA_CERT).display().to_string()))?;
+        if !key_matches(&der, &key)? {
+            return Err(TlsError::InvalidCert(format!(
+                "{} does not belong to {} in {}",
+                CA_KEY,
+                CA_CERT,
+                dir.display()
+            )));
+        }
+        let issuer = CertificateParams::from_ca_cert_pem(&cert_pem)
+            .and_then(|params| params.self_signed(&key))
+            .map_err(cert_error)?;
+        Ok(Self {
+            dir,
+            cert_pem,
+            issuer,
+            key,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn ca_cert_path(&self) -> PathBuf {
+        self.dir.join(CA_CERT)
+    }
+
+    pub fn server_cert_path(&self) -> PathBuf {
+        self.dir.join(SERVER_CERT)
+    }
+
+    pub fn server_key_path(&self) -> PathBuf {
+        self.dir.join(SERVER_KEY)
+    }
+
+    /// Whether `dir` already holds a CA
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(CA_CERT).exists() || dir.join(CA_KEY).exists()
+    }
+
+    /// The CA certificate, PEM encoded, for clients to trust
+    pub fn ca_pem(&self) -> String {
+        self.cert_pem.clone()
+    }
+
+    /// Issue a server certificate; returns the chain and key as PEM
+    pub fn issue(
+        &self,
+        names: &HostNames,
+        validity: Duration,
+    ) -> Result<(String, String), TlsError> {
+        if names.dns.is_empty() && names.ips.is_empty() {
+            return Err(TlsError::InvalidCert(
+                "No names to issue a certificate for".into(),
+            ));
+        }
+        let key = KeyPair::generate().map_err(cert_error)?;
+        let now = Utc::now();
+
+        let mut params = CertificateParams::new(names.dns.clone()).map_err(cert_error)?;
+        params
+            .subject_alt_names
+            .extend(names.ips.iter().map(|ip| SanType::IpAddress(*ip)));
+        params.distinguished_name = name(
+            &names
+                .dns
+                .first()
+                .cloned()
+                .unwrap_or_else(|| names.ips[0].to_string()),
+        );
+        params.is_ca = IsCa::ExplicitNoCa;
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![
+            ExtendedKeyUsagePurpose::ServerAuth,
+            ExtendedKeyUsagePurpose::ClientAuth,
+        ];
+        params.use_authority_key_identifier_extension = true;
+        params.not_before = to_time(now - Duration::hours(1))?;
+        params.not_after = to_time(now + validity)?;
+        let cert = params
+            .signed_by(&key, &self.issuer, &self.key)
+            .map_err(cert_error)?;
+
+        let chain = cert.pem() + &self.cert_pem;
+        Ok((chain, key.serialize_pem()))
+    }
+
+    /// Make sure a current server certificate for `names` exists
+    ///
+    /// Returns true when a new certificate was written. The key and chain
+    /// are separate files, so the names file is removed before and written
+    /// after them; a pair left half-written is reissued on the next call.
+    pub fn ensure_server_cert(&self, names: &HostNames) -> Result<bool, TlsError> {
+        let names_path = self.dir.join(SERVER_NAMES);
+        let issued_for: Option<HostNames> = std::fs::read(&names_path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok());
+        let current = read_pem_certs(&self.server_cert_path())
+            .ok()
+            .and_then(|certs| certs.into_iter().next());
+        let not_after = current
+            .as_deref()
+            .and_then(|der| certificate_validity(der).ok())
+            .map(|(_, not_after)| not_after);
+        let key_matches = current.as_deref().is_some_and(|der| {
+            std::fs::read_to_string(self.server_key_path())
+                .ok()
+                .and_then(|pem| KeyPair::from_pem(&pem).ok())
+                .is_some_and(|key| key_matches(der, &key).unwrap_or(false))
+        });
+
+        let reason = match (not_after, issued_for) {
+            (None, _) | (_, None) => "no server certificate",
+            _ if !key_matches => "server key does not match certificate",
+            (Some(not_after), _) if not_after - Utc::now() < Duration::days(RENEW_BEFORE_DAYS) => {
+                "server certificate expires soon"
+            }
+            (_, Some(issued)) if !issued.covers(names) => "host names changed",
+            _ => return Ok(false),
+        };
+
+        let (chain, key) = self.issue(names, Duration::days(SERVER_VALIDITY_DAYS))?;
+        match std::fs::remove_file(&names_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        write_file(&self.server_key_path(), key.as_bytes(), 0o600)?;
+        write_file(&self.server_cert_path(), chain.as_bytes(), 0o644)?;
+        write_file(
+            &names_path,
+            &serde_json::to_vec_pretty(names).map_err(|e| TlsError::InvalidCert(e.to_string()))?,
+            0o644,
+        )?;
+        tracing::info!(
+            "Issued server certificate for {:?} {:?} ({})",
+            names.dns,
+            names.ips,
+            reason
+        );
+        Ok(true)
+    }
+
+    /// Renew the server certificate when needed and reload it into `config`
+    ///
+    /// With `client_auth`, the reloaded config keeps verifying client certificates.
+    pub fn spawn_rotation(
+        self,
+        config: RustlsConfig,
+        client_auth: Option<ClientAuthConfig>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ROTATION_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match self.ensure_server_cert(&HostNames::detect()) {
+                    Ok(false) => {}
+                    Ok(true) => match self.reload(&config, client_auth.as_ref()).await {
+                        Ok(()) => tracing::info!("Reloaded TLS server certificate"),
+                        Err(e) => tracing::error!("Failed to reload TLS certificate: {}", e),
+                    },
+                    Err(e) => tracing::error!("TLS certificate rotation failed: {}", e),
+                }
+            }
+        })
+    }
+
+    async fn reload(
+        &self,
+        config: &RustlsConfig,
+        client_auth: Option<&ClientAuthConfig>,
+    ) -> Result<(), TlsError> {
+        let (cert, key) = (self.server_cert_path(), self.server_key_path());
+        match client_auth {
+            // Swap in one step so no handshake sees the config without client auth
+            Some(client_auth) => {
+                let fresh = RustlsConfig::from_pem_file(cert, key).await?;
+                config.reload_from_config(client_auth.server_config(&fresh)?);
+            }
+            None => config.reload_from_pem_file(cert, key).await?,
+        }
+        Ok(())
+    }
+}
+
+/// notBefore and notAfter of a DER certificate
+pub fn certificate_validity(der: &[u8]) -> Result<(DateTime<Utc>, DateTime<Utc>), TlsError> {
+    let cert = parse_certificate(der)?;
+    let validity = cert.validity();
+    let time = |timestamp: i64| {
+        Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(|| TlsError::InvalidCert("Certificate time out of range".to_string()))
+    };
+    Ok((
+        time(validity.not_before.timestamp())?,
+        time(validity.not_after.timestamp())?,
+    ))
+}
+
+/// Last commonName in the subject of a DER certificate
+pub fn certificate_common_name(der: &[u8]) -> Option<String> {
+    let cert = parse_certificate(der).ok()?;
+    let common_name = cert.subject().iter_common_name().last()?;
+    common_name.as_str().ok().map(str::to_string)
+}
+
+/// DER certificates in a PEM file
+pub fn read_pem_certs(path: &Path) -> Result<Vec<Vec<u8>>, TlsError> {
+    let content = std::fs::read(path)?;
+    Ok(rustls_pemfile::certs(&mut content.as_slice())?)
+}
+
+fn parse_certificate(der: &[u8]) -> Result<X509Certificate<'_>, TlsError> {
+    parse_x509_certificate(der)
+        .map(|(_, cert)| cert)
+        .map_err(|e| TlsError::InvalidCert(format!("Malformed certificate: {}", e)))
+}
+
+/// Whether `key` is the private key of the certificate `der`
+fn key_matches(der: &[u8], key: &KeyPair) -> Result<bool, TlsError> {
+    Ok(parse_certificate(der)?.public_key().raw == key.public_key_der().as_slice())
+}
+
+fn write_file(path: &Path, content: &[u8], mode: u32) -> Result<(), TlsError> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn name(common_name: &str) -> DistinguishedName {
+    let mut name = DistinguishedName::new();
+    name.push(DnType::OrganizationName, "op-dbus");
+    name.push(DnType::CommonName, common_name);
+    name
+}
+
+fn to_time(at: DateTime<Utc>) -> Result<time::OffsetDateTime, TlsError> {
+    time::OffsetDateTime::from_unix_timestamp(at.timestamp())
+        .map_err(|e| TlsError::InvalidCert(e.to_string()))
+}
+
+fn cert_error(e: rcgen::Error) -> TlsError {
+    TlsError::InvalidCert(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// TLS handshake against a server using `chain`/`key`, trusting `ca`
+    async fn handshake(chain: &str, key: &str, ca: &str, server_name: ServerName) -> bool {
+        let certs = rustls_pemfile::certs(&mut chain.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(Certificate)
+            .collect();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut key.as_bytes())
+            .unwrap()
+            .remove(0);
+        let server = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, PrivateKey(key))
+            .unwrap();
+
+        let mut roots = RootCertStore::empty();
+        for der in rustls_pemfile::certs(&mut ca.as_bytes()).unwrap() {
+            roots.add(&Certificate(der)).unwrap();
+        }
+        let client = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server));
+        let server_task = tokio::spawn(async move {
+            let mut stream = acceptor.accept(server_io).await.ok()?;
+            stream.write_all(b"ok").await.ok()?;
+            stream.shutdown().await.ok()
+        });
+
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client));
+        let result = match connector.connect(server_name, client_io).await {
+            Ok(mut stream) => {
+                let mut reply = Vec::new();
+                stream.read_to_end(&mut reply).await.is_ok() && reply == b"ok"
+            }
+            Err(_) => false,
+        };
+        server_task.abort();
+        result
+    }
+
+    #[tokio::test]
+    async fn test_issue_and_verify_server_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCa::open_or_create(dir.path()).unwrap();
+        let mut names = HostNames::default();
+        names.add_dns("node1.fleet.example");
+        names.add_ip("10.0.0.7".parse().unwrap());
+
+        let (chain, key) = ca.issue(&names, Duration::days(1)).unwrap();
+        let dns = ServerName::try_from("node1.fleet.example").unwrap();
+        let ip = ServerName::IpAddress("10.0.0.7".parse().unwrap());
+        assert!(handshake(&chain, &key, &ca.ca_pem(), dns.clone()).await);
+        assert!(handshake(&chain, &key, &ca.ca_pem(), ip).await);
+        let other = ServerName::try_from("node2.fleet.example").unwrap();
+        assert!(!handshake(&chain, &key, &ca.ca_pem(), other).await);
+
+        // A different CA is not trusted
+        let other_dir = tempfile::tempdir().unwrap();
+        let other_ca = LocalCa::open_or_create(other_dir.path()).unwrap();
+        assert!(!handshake(&chain, &key, &other_ca.ca_pem(), dns).await);
+
+        let leaf = rustls_pemfile::certs(&mut chain.as_bytes())
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            certificate_common_name(&leaf).as_deref(),
+            Some("node1.fleet.example")
+        );
+
+        // The CA persists across opens
+        let reopened = LocalCa::open_or_create(dir.path()).unwrap();
+        assert_eq!(reopened.ca_pem(), ca.ca_pem());
+    }
+
+    #[tokio::test]
+    async fn test_hosts_sharing_an_imported_ca_trust_each_other() {
+        let fleet_dir = tempfile::tempdir().unwrap();
+        let fleet = LocalCa::open_or_create(fleet_dir.path()).unwrap();
+        let fleet_key = std::fs::read_to_string(fleet_dir.path().join(CA_KEY)).unwrap();
+
+        let host_dir = tempfile::tempdir().unwrap();
+        let host = LocalCa::open_or_create(host_dir.path()).unwrap();
+        let mut names = HostNames::default();
+        names.add_dns("node2.fleet.example");
+        host.ensure_server_cert(&names).unwrap();
+
+        let host = LocalCa::import(host_dir.path(), &fleet.ca_pem(), &fleet_key).unwrap();
+        assert_eq!(host.ca_pem(), fleet.ca_pem());
+        // The certificate from the host's own CA is replaced
+        assert!(host.ensure_server_cert(&names).unwrap());
+        let chain = std::fs::read_to_string(host.server_cert_path()).unwrap();
+        let key = std::fs::read_to_string(host.server_key_path()).unwrap();
+        let dns = ServerName::try_from("node2.fleet.example").unwrap();
+        assert!(handshake(&chain, &key, &fleet.ca_pem(), dns).await);
+
+        let reopened = LocalCa::open_or_create(host_dir.path()).unwrap();
+        assert_eq!(reopened.ca_pem(), fleet.ca_pem());
+
+        // A key that does not belong to the certificate is refused
+        let other_dir = tempfile::tempdir().unwrap();
+        LocalCa::open_or_create(other_dir.path()).unwrap();
+        let other_key = std::fs::read_to_string(other_dir.path().join(CA_KEY)).unwrap();
+        assert!(LocalCa::import(other_dir.path(), &fleet.ca_pem(), &other_key).is_err());
+
+        // So is a server certificate
+        assert!(LocalCa::import(other_dir.path(), &chain, &key).is_err());
+    }
+
+    #[test]
+    fn test_rotation_decisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCa::open_or_create(dir.path()).unwrap();
+        let mut names = HostNames::default();
+        names.add_dns("localhost");
+
+        assert!(ca.ensure_server_cert(&names).unwrap());
+        assert!(!ca.ensure_server_cert(&names).unwrap());
+
+        let der = read_pem_certs(&ca.server_cert_path()).unwrap().remove(0);
+        let (not_before, not_after) = certificate_validity(&der).unwrap();
+        assert!(not_before < Utc::now());
+        assert_eq!((not_after - not_before).num_days(), SERVER_VALIDITY_DAYS);
+
+        // New address on the host
+        names.add_ip("192.168.1.20".parse().unwrap());
+        assert!(ca.ensure_server_cert(&names).unwrap());
+
+        // A crash between writing the key and the chain
+        let (_, other_key) = ca.issue(&names, Duration::days(1)).unwrap();
+        std::fs::write(ca.server_key_path(), other_key).unwrap();
+        assert!(ca.ensure_server_cert(&names).unwrap());
+        assert!(!ca.ensure_server_cert(&names).unwrap());
+
+        // Close to expiry
+        let (chain, key) = ca.issue(&names, Duration::days(2)).unwrap();
+        std::fs::write(ca.server_cert_path(), chain).unwrap();
+        std::fs::write(ca.server_key_path(), key).unwrap();
+        assert!(ca.ensure_server_cert(&names).unwrap());
+        assert!(!ca.ensure_server_cert(&names).unwrap());
+    }
+}
@@ -59,7 +59,7 @@ impl Metrics {
         // Extract service name from path (e.g., /api/chat -> chat)
         let service_name = extract_service_name(path);
         let service_metrics = services.entry(service_name.to_string())
-            .or_insert_with(|| ServiceMetrics::new(&service_name));
+            .or_insert_with(|| ServiceMetrics::new(service_name));
 
         service_metrics.record_request(method, status, duration).await;
     }
@@ -131,7 +131,7 @@ impl ServiceMetrics {
     }
 
     /// Record a request for this service
-    pub async fn record_request(&self, method: &str, status: u16, duration: f64) {
+    pub async fn record_request(&self, _method: &str, status: u16, duration: f64) {
         self.request_count.inc();
         self.request_duration.observe(duration);
 
@@ -154,6 +154,25 @@ fn extract_service_name(path: &str) -> &str {
     }
 }
 
+/// Encode everything in the default registry in the text format
+pub fn gather_text() -> String {
+    TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_default()
+}
+
+/// Serve `/metrics`, plus any extra `routes`, on its own listener
+///
+/// For processes without an HTTP server, such as the daemon.
+pub async fn serve_metrics(addr: std::net::SocketAddr, routes: axum::Router) -> anyhow::Result<()> {
+    let app = routes
+        .route("/metrics", axum::routing::get(handlers::gathered_metrics));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Serving metrics on http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
 /// Metrics middleware
 pub async fn metrics_middleware(
     metrics: axum::extract::State<Arc<Metrics>>,
@@ -197,6 +216,22 @@ pub mod handlers {
             .unwrap()
     }
 
+    /// Prometheus metrics endpoint without a `Metrics` instance
+    ///
+    /// Refreshes the sampled state-manager gauges before gathering.
+    pub async fn gathered_metrics() -> impl IntoResponse {
+        let text = tokio::task::spawn_blocking(|| {
+            crate::state::metrics::refresh();
+            gather_text()
+        })
+        .await
+        .unwrap_or_default();
+        axum::response::Response::builder()
+            .header("content-type", "text/plain; version=0.0.4; charset=utf-8")
+            .body(text)
+            .unwrap()
+    }
+
     /// JSON metrics endpoint
     pub async fn json_metrics(
         metrics: axum::extract::State<Arc<Metrics>>,
@@ -261,7 +296,7 @@ pub mod perf {
         }
 
         /// Monitor a function execution
-        pub async fn monitor<F, Fut, T>(&self, name: &str, f: F) -> T
+        pub async fn monitor<F, Fut, T>(&self, _name: &str, f: F) -> T
         where
             F: FnOnce() -> Fut,
             Fut: std::future::Future<Output = T>,
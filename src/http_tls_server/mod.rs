@@ -14,7 +14,7 @@
 //!
 //! ## Usage
 //!
-//! ```rust
+//! ```rust,ignore
 //! use http_tls_server::{ServerBuilder, ServiceRouter};
 //!
 //! // Create service router for your service
@@ -35,14 +35,20 @@
 pub mod server;
 pub mod router;
 pub mod tls;
+pub mod local_ca;
+pub mod mtls;
 pub mod request_filters;
 pub mod health;
 pub mod metrics;
+pub mod tokens;
 
 // Re-export main types for convenience
-pub use server::{Server, ServerBuilder};
+pub use server::{PeerCredentials, Server, ServerBuilder};
 pub use router::ServiceRouter;
+pub use request_filters::HttpAuth;
 pub use tls::{TlsConfig, CertificateSource};
+pub use local_ca::{HostNames, LocalCa};
+pub use mtls::{ClientAuthConfig, ClientAuthMode, PeerIdentity};
 
 // Common imports for users
 pub use axum::{
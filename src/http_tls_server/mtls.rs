@@ -0,0 +1,397 @@
+//! Mutual TLS Client Authentication
+//!
+//! Fleet members authenticate to each other with client certificates issued
+//! by a CA they trust (by default this host's local CA; peers share one only
+//! after `op-dbus tls import-ca` installs the same fleet CA on each). The
+//! certificate's common name is mapped to a role (a token `Scope`) through a
+//! roles file, and the resulting `PeerIdentity` is added to every request on
+//! the connection, where `api_key_auth` uses it in place of an API token.
+//!
+//! Configured with `OPDBUS_MTLS` (`optional` or `required`), `OPDBUS_MTLS_CA`
+//! and `OPDBUS_MTLS_ROLES`. The roles file looks like:
+//!
+//! ```json
+//! {"default": "read", "subjects": {"node2.fleet": "apply", "sha256:ab12...": "admin"}}
+//! ```
+
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+
+use super::local_ca::{self, LocalCa};
+use super::tls::TlsError;
+use super::tokens::Scope;
+use crate::encoding::to_hex;
+
+pub const DEFAULT_ROLES_FILE: &str = "/etc/op-dbus/mtls-roles.json";
+
+/// Whether clients must present a certificate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientAuthMode {
+    /// Clients without a certificate fall back to API tokens
+    Optional,
+    /// The TLS handshake fails without a valid client certificate
+    Required,
+}
+
+/// Roles of certificate subjects
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoleMap {
+    /// Role of verified certificates not listed in `subjects`
+    #[serde(default)]
+    pub default: Option<Scope>,
+    /// Common name, or `sha256:<fingerprint>`, to role
+    #[serde(default)]
+    pub subjects: HashMap<String, Scope>,
+}
+
+impl RoleMap {
+    pub fn load(path: &Path) -> Result<Self, TlsError> {
+        match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content).map_err(|e| {
+                TlsError::InvalidCert(format!("Invalid roles file {}: {}", path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fingerprint entries take precedence over the common name
+    pub fn role_of(&self, subject: &str, fingerprint: &str) -> Option<Scope> {
+        self.subjects
+            .get(&format!("sha256:{}", fingerprint))
+            .or_else(|| self.subjects.get(subject))
+            .copied()
+            .or(self.default)
+    }
+}
+
+/// Client certificate verification settings
+#[derive(Clone, Debug)]
+pub struct ClientAuthConfig {
+    pub mode: ClientAuthMode,
+    /// PEM bundle of the CAs client certificates must chain to
+    pub ca_path: PathBuf,
+    pub roles: Arc<RoleMap>,
+}
+
+impl ClientAuthConfig {
+    pub fn new(mode: ClientAuthMode, ca_path: impl Into<PathBuf>, roles: RoleMap) -> Self {
+        Self {
+            mode,
+            ca_path: ca_path.into(),
+            roles: Arc::new(roles),
+        }
+    }
+
+    /// Configuration from the environment, None unless `OPDBUS_MTLS` is set
+    pub fn from_env() -> Result<Option<Self>, TlsError> {
+        let mode = match std::env::var("OPDBUS_MTLS").as_deref() {
+            Ok("optional") => ClientAuthMode::Optional,
+            Ok("required") => ClientAuthMode::Required,
+            Ok("") | Ok("off") | Err(_) => return Ok(None),
+            Ok(other) => {
+                return Err(TlsError::InvalidCert(format!(
+                    "OPDBUS_MTLS must be 'optional' or 'required', not '{}'",
+                    other
+                )))
+            }
+        };
+        let ca_path = std::env::var_os("OPDBUS_MTLS_CA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| LocalCa::default_dir().join(local_ca::CA_CERT));
+        let roles_path = std::env::var_os("OPDBUS_MTLS_ROLES")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ROLES_FILE));
+        Ok(Some(Self::new(mode, ca_path, RoleMap::load(&roles_path)?)))
+    }
+
+    /// Verifier for client certificates chaining to the configured CAs
+    fn verifier(&self) -> Result<Arc<dyn rustls::server::ClientCertVerifier>, TlsError> {
+        let mut roots = rustls::RootCertStore::empty();
+        for der in local_ca::read_pem_certs(&self.ca_path)? {
+            roots
+                .add(&rustls::Certificate(der))
+                .map_err(|e| TlsError::InvalidCert(format!("Client CA: {}", e)))?;
+        }
+        if roots.is_empty() {
+            return Err(TlsError::CertNotFound(self.ca_path.display().to_string()));
+        }
+        Ok(match self.mode {
+            ClientAuthMode::Optional => {
+                rustls::server::AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()
+            }
+            ClientAuthMode::Required => {
+                rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed()
+            }
+        })
+    }
+
+    /// Server config verifying client certificates, with the server certificate of `certs`
+    pub fn server_config(
+        &self,
+        certs: &RustlsConfig,
+    ) -> Result<Arc<rustls::ServerConfig>, TlsError> {
+        let current = certs.get_inner();
+        let mut server = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(self.verifier()?)
+            .with_cert_resolver(current.cert_resolver.clone());
+        server.alpn_protocols = current.alpn_protocols.clone();
+        Ok(Arc::new(server))
+    }
+
+    /// Switch `config` to verify client certificates, keeping its server certificate
+    pub fn apply(&self, config: &RustlsConfig) -> Result<(), TlsError> {
+        config.reload_from_config(self.server_config(config)?);
+        Ok(())
+    }
+
+    /// Identity of a verified client certificate
+    pub fn identify(&self, der: &[u8]) -> PeerIdentity {
+        let fingerprint = to_hex(&Sha256::digest(der));
+        let subject = local_ca::certificate_common_name(der)
+            .unwrap_or_else(|| format!("sha256:{}", &fingerprint[..16]));
+        PeerIdentity {
+            role: self.roles.role_of(&subject, &fingerprint),
+            subject,
+            fingerprint,
+        }
+    }
+}
+
+/// A client authenticated by certificate
+///
+/// Added as a request extension on mutual TLS connections.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerIdentity {
+    /// Common name of the certificate subject
+    pub subject: String,
+    /// SHA-256 of the certificate, hex encoded
+    pub fingerprint: String,
+    /// None when the roles file does not grant anything
+    pub role: Option<Scope>,
+}
+
+impl PeerIdentity {
+    /// How the peer is recorded in footprints and logs
+    pub fn actor(&self) -> String {
+        format!("cert:{}#{}", self.subject, &self.fingerprint[..16])
+    }
+}
+
+/// TLS acceptor that adds the client's PeerIdentity to its requests
+#[derive(Clone)]
+pub struct MtlsAcceptor {
+    inner: RustlsAcceptor,
+    client_auth: ClientAuthConfig,
+}
+
+impl MtlsAcceptor {
+    pub fn new(config: RustlsConfig, client_auth: ClientAuthConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(config),
+            client_auth,
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for MtlsAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = WithPeerIdentity<S>;
+    type Future = BoxFuture<'static, std::io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let accept = self.inner.accept(stream, service);
+        let client_auth = self.client_auth.clone();
+        Box::pin(async move {
+            let (stream, inner) = accept.await?;
+            let identity = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| client_auth.identify(&cert.0));
+            if let Some(identity) = &identity {
+                tracing::debug!(
+                    "Client certificate {} ({:?})",
+                    identity.actor(),
+                    identity.role
+                );
+            }
+            Ok((stream, WithPeerIdentity { inner, identity }))
+        })
+    }
+}
+
+/// Service adding a connection's PeerIdentity to each request
+#[derive(Clone)]
+pub struct WithPeerIdentity<S> {
+    inner: S,
+    identity: Option<PeerIdentity>,
+}
+
+impl<S, B> tower::Service<axum::http::Request<B>> for WithPeerIdentity<S>
+where
+    S: tower::Service<axum::http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: axum::http::Request<B>) -> Self::Future {
+        if let Some(identity) = &self.identity {
+            request.extensions_mut().insert(identity.clone());
+        }
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_tls_server::HostNames;
+    use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+
+    fn names(dns: &str) -> HostNames {
+        let mut names = HostNames::default();
+        names.add_dns(dns);
+        names
+    }
+
+    fn client_config(ca: &LocalCa, cert: Option<(String, String)>) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        for der in rustls_pemfile::certs(&mut ca.ca_pem().as_bytes()).unwrap() {
+            roots.add(&Certificate(der)).unwrap();
+        }
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots);
+        match cert {
+            Some((chain, key)) => {
+                let chain = rustls_pemfile::certs(&mut chain.as_bytes())
+                    .unwrap()
+                    .into_iter()
+                    .map(Certificate)
+                    .collect();
+                let key = rustls_pemfile::pkcs8_private_keys(&mut key.as_bytes())
+                    .unwrap()
+                    .remove(0);
+                builder
+                    .with_client_auth_cert(chain, PrivateKey(key))
+                    .unwrap()
+            }
+            None => builder.with_no_client_auth(),
+        }
+    }
+
+    /// Handshake with the acceptor; the identity it attached, or None if it failed
+    async fn connect(
+        acceptor: &MtlsAcceptor,
+        client: ClientConfig,
+    ) -> Option<Option<PeerIdentity>> {
+        let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+        let server = acceptor.accept(server_io, ());
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client));
+        let client = connector.connect(ServerName::try_from("localhost").unwrap(), client_io);
+        let (server, client) = tokio::join!(server, client);
+        let (_, service) = server.ok()?;
+        // TLS 1.3 clients learn about a rejected certificate after the handshake
+        client.ok()?;
+        Some(service.identity)
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCa::open_or_create(dir.path()).unwrap();
+        ca.ensure_server_cert(&names("localhost")).unwrap();
+        let server_config =
+            RustlsConfig::from_pem_file(ca.server_cert_path(), ca.server_key_path())
+                .await
+                .unwrap();
+
+        let mut roles = RoleMap::default();
+        roles.subjects.insert("node2.fleet".into(), Scope::Apply);
+        let client_auth = ClientAuthConfig::new(ClientAuthMode::Optional, ca.ca_cert_path(), roles);
+        client_auth.apply(&server_config).unwrap();
+        let acceptor = MtlsAcceptor::new(server_config.clone(), client_auth.clone());
+
+        let node2 = ca
+            .issue(&names("node2.fleet"), chrono::Duration::days(1))
+            .unwrap();
+        let identity = connect(&acceptor, client_config(&ca, Some(node2.clone())))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.subject, "node2.fleet");
+        assert_eq!(identity.role, Some(Scope::Apply));
+        assert!(identity.actor().starts_with("cert:node2.fleet#"));
+
+        let node3 = ca
+            .issue(&names("node3.fleet"), chrono::Duration::days(1))
+            .unwrap();
+        let identity = connect(&acceptor, client_config(&ca, Some(node3)))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.role, None);
+
+        // Optional: anonymous clients connect without an identity
+        assert_eq!(
+            connect(&acceptor, client_config(&ca, None)).await,
+            Some(None)
+        );
+
+        // Certificates from another CA are rejected
+        let other_dir = tempfile::tempdir().unwrap();
+        let other_ca = LocalCa::open_or_create(other_dir.path()).unwrap();
+        let foreign = other_ca
+            .issue(&names("node2.fleet"), chrono::Duration::days(1))
+            .unwrap();
+        assert_eq!(
+            connect(&acceptor, client_config(&ca, Some(foreign))).await,
+            None
+        );
+
+        // Required: anonymous clients are rejected
+        let required = ClientAuthConfig {
+            mode: ClientAuthMode::Required,
+            ..client_auth
+        };
+        required.apply(&server_config).unwrap();
+        let acceptor = MtlsAcceptor::new(server_config, required);
+        assert_eq!(connect(&acceptor, client_config(&ca, None)).await, None);
+        assert!(connect(&acceptor, client_config(&ca, Some(node2)))
+            .await
+            .is_some());
+    }
+
+    #[test]
+    fn test_role_map() {
+        let roles: RoleMap = serde_json::from_str(
+            r#"{"default": "read", "subjects": {"node2": "apply", "sha256:abcd": "admin"}}"#,
+        )
+        .unwrap();
+        assert_eq!(roles.role_of("node2", "ffff"), Some(Scope::Apply));
+        assert_eq!(roles.role_of("node2", "abcd"), Some(Scope::Admin));
+        assert_eq!(roles.role_of("node9", "ffff"), Some(Scope::Read));
+        assert_eq!(RoleMap::default().role_of("node2", "ffff"), None);
+    }
+}
@@ -1,17 +1,29 @@
 //! HTTP Request Filters
 //!
 //! Native request filtering and processing for security, logging, rate limiting, etc.
+//!
+//! `HttpAuth` combines `rate_limit` and `api_key_auth`; see tokens.rs for
+//! how API tokens are stored.
 
 use axum::{
-    extract::Request,
-    http::{HeaderMap, StatusCode},
-    middleware::Next,
+    extract::{ConnectInfo, Request, State},
+    http::{header, uri::PathAndQuery, Method, StatusCode, Uri},
+    middleware::{from_fn, from_fn_with_state, Next},
     response::{IntoResponse, Response},
+    Router,
 };
-use std::time::Instant;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
+use crate::blockchain::plugin_footprint::with_actor;
+use super::mtls::PeerIdentity;
+use super::server::PeerCredentials;
+use super::tokens::{self, Scope, TokenError, TokenStore};
+
 /// Security middleware - adds security headers
 pub async fn security_headers(request: Request, next: Next) -> Response {
     let mut response = next.run(request).await;
@@ -36,7 +48,8 @@ pub async fn security_headers(request: Request, next: Next) -> Response {
 }
 
 /// Request logging middleware
-pub async fn request_logger(request: Request, next: Next) -> Response {
+pub async fn request_logger(mut request: Request, next: Next) -> Response {
+    take_query_token(&mut request);
     let start = Instant::now();
     let method = request.method().clone();
     let uri = request.uri().clone();
@@ -59,49 +72,345 @@ pub async fn request_logger(request: Request, next: Next) -> Response {
     response
 }
 
+/// Token bucket rate limiter keyed by client or token
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Buckets kept before idle ones are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+impl RateLimiter {
+    /// Allow `per_minute` requests per key, with bursts of up to `burst`
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            per_second: per_minute as f64 / 60.0,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one token for `key`, or return how long until one is available
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            let (per_second, burst) = (self.per_second, self.burst);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second
+                    < burst
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.per_second > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second))
+        } else {
+            Err(Duration::from_secs(60))
+        }
+    }
+}
+
+/// Token authentication and rate limiting for a router
+///
+/// Requests under the protected prefixes need a bearer token from the
+/// TokenStore or a client certificate with a role (PeerIdentity), unless
+/// they arrived over the unix socket (PeerCredentials), where polkit
+/// decides instead. Everything is rate limited per client.
+#[derive(Clone)]
+pub struct HttpAuth {
+    tokens: Option<Arc<TokenStore>>,
+    per_key: Arc<RateLimiter>,
+    per_ip: Arc<RateLimiter>,
+    protected: Vec<String>,
+}
+
+impl HttpAuth {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self {
+            tokens: Some(Arc::new(tokens)),
+            per_key: Arc::new(RateLimiter::new(600, 60)),
+            per_ip: Arc::new(RateLimiter::new(600, 60)),
+            protected: vec!["/api".to_string(), "/mcp".to_string(), "/ws".to_string()],
+        }
+    }
+
+    /// Rate limiting only
+    pub fn disabled() -> Self {
+        Self {
+            tokens: None,
+            ..Self::new(TokenStore::open(tokens::DEFAULT_TOKEN_FILE))
+        }
+    }
+
+    /// Configuration from the environment
+    ///
+    /// `OPDBUS_HTTP_AUTH=off` turns token checks off; `OPDBUS_RATE_LIMIT`
+    /// (requests per minute) and `OPDBUS_RATE_BURST` set both limiters.
+    pub fn from_env() -> Self {
+        let auth = match std::env::var("OPDBUS_HTTP_AUTH").as_deref() {
+            Ok("off") | Ok("0") | Ok("false") | Ok("disabled") => {
+                tracing::warn!("HTTP token authentication disabled by OPDBUS_HTTP_AUTH");
+                Self::disabled()
+            }
+            _ => Self::new(TokenStore::from_env()),
+        };
+        let env_u32 = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+        match (env_u32("OPDBUS_RATE_LIMIT"), env_u32("OPDBUS_RATE_BURST")) {
+            (None, None) => auth,
+            (limit, burst) => auth.with_rate_limit(limit.unwrap_or(600), burst.unwrap_or(60)),
+        }
+    }
+
+    /// Requests per minute and burst size, per token and per client address
+    pub fn with_rate_limit(mut self, per_minute: u32, burst: u32) -> Self {
+        self.per_key = Arc::new(RateLimiter::new(per_minute, burst));
+        self.per_ip = Arc::new(RateLimiter::new(per_minute, burst));
+        self
+    }
+
+    /// Also require a token under `prefix`
+    pub fn protect(mut self, prefix: impl Into<String>) -> Self {
+        self.protected.push(prefix.into());
+        self
+    }
+
+    /// Layer authentication and rate limiting onto `router`
+    ///
+    /// Outermost, `strip_query_token` takes the token out of the URI before
+    /// anything logs it; layers added later must do the same.
+    pub fn apply(self, router: Router) -> Router {
+        router
+            .layer(from_fn_with_state(self.clone(), api_key_auth))
+            .layer(from_fn_with_state(self, rate_limit))
+            .layer(from_fn(strip_query_token))
+    }
+
+    fn is_protected(&self, path: &str) -> bool {
+        self.protected.iter().any(|prefix| {
+            path.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+/// Scope a request method needs
+fn required_scope(method: &Method) -> Scope {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => Scope::Read,
+        Method::DELETE => Scope::Admin,
+        _ => Scope::Apply,
+    }
+}
+
+/// Bearer token, `x-api-key` header or the `access_token` query parameter
+/// of a WebSocket upgrade (see `take_query_token`)
+fn presented_token(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+        .map(|token| token.trim().to_string())
+        .or_else(|| request.extensions().get::<QueryToken>().map(|t| t.0.clone()))
+}
+
+/// `access_token` query parameter of a WebSocket upgrade, taken out of the URI
+#[derive(Clone)]
+struct QueryToken(String);
+
+fn is_websocket_upgrade(request: &Request) -> bool {
+    request
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.eq_ignore_ascii_case("websocket"))
+}
+
+/// Remove `access_token` from the request URI, so it is never logged
+///
+/// WebSocket clients cannot set headers and pass their token in the query;
+/// on upgrade requests it is kept as a QueryToken for `api_key_auth`. Other
+/// requests must send a header and lose the parameter.
+fn take_query_token(request: &mut Request) {
+    let Some(query) = request.uri().query() else {
+        return;
+    };
+    let mut token = None;
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| match pair.strip_prefix("access_token=") {
+            Some(value) => {
+                token = Some(value.to_string());
+                false
+            }
+            None => true,
+        })
+        .collect();
+    let Some(token) = token else {
+        return;
+    };
+
+    let path_and_query = if kept.is_empty() {
+        request.uri().path().to_string()
+    } else {
+        format!("{}?{}", request.uri().path(), kept.join("&"))
+    };
+    let mut parts = request.uri().clone().into_parts();
+    parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *request.uri_mut() = uri;
+    }
+    if is_websocket_upgrade(request) {
+        request.extensions_mut().insert(QueryToken(token));
+    }
+}
+
+/// Middleware running `take_query_token`; outermost, before any logging
+pub async fn strip_query_token(mut request: Request, next: Next) -> Response {
+    take_query_token(&mut request);
+    next.run(request).await
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        "Rate limit exceeded",
+    )
+        .into_response()
+}
+
 /// API key authentication middleware
+///
+/// Callers are identified by unix socket credentials, a client certificate
+/// (PeerIdentity) or an API token, in that order. A verified TokenRecord is
+/// added as a request extension, and the caller is recorded as the actor of
+/// footprints created while handling the request.
 pub async fn api_key_auth(
-    headers: HeaderMap,
-    request: Request,
+    State(auth): State<HttpAuth>,
+    mut request: Request,
     next: Next,
 ) -> Response {
-    // Check for API key in various headers
-    let api_key = headers.get("x-api-key")
-        .or_else(|| headers.get("authorization"))
-        .or_else(|| headers.get("x-password"))
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s.trim_start_matches("Bearer ").trim());
-
-    // For now, allow all requests (authentication is optional)
-    // In production, you would validate the API key here
-    if let Some(key) = api_key {
-        if !key.is_empty() {
-            tracing::debug!("API key provided: {}", if key.len() > 8 {
-                format!("{}...{}", &key[..4], &key[key.len()-4..])
-            } else {
-                "***".to_string()
-            });
+    match authenticate(&auth, &mut request) {
+        Ok(Some(actor)) => with_actor(actor, next.run(request)).await,
+        Ok(None) => next.run(request).await,
+        Err(response) => response,
+    }
+}
+
+/// The caller's actor name, or the response rejecting it
+#[allow(clippy::result_large_err)]
+fn authenticate(auth: &HttpAuth, request: &mut Request) -> Result<Option<String>, Response> {
+    // Local callers are authorized through polkit by the handlers
+    if let Some(peer) = request.extensions().get::<PeerCredentials>() {
+        return Ok(Some(format!("uid:{}", peer.uid)));
+    }
+    let enforced = auth.tokens.is_some() && auth.is_protected(request.uri().path());
+
+    if let Some(identity) = request.extensions().get::<PeerIdentity>() {
+        if enforced {
+            let Some(role) = identity.role else {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("Client certificate {} has no role", identity.subject),
+                )
+                    .into_response());
+            };
+            auth.per_key
+                .check(&format!("cert:{}", identity.fingerprint))
+                .map_err(too_many_requests)?;
+            check_scope(role.allows(required_scope(request.method())), &identity.actor(), request)?;
         }
+        return Ok(Some(identity.actor()));
     }
 
-    next.run(request).await
+    let (Some(tokens), true) = (&auth.tokens, enforced) else {
+        return Ok(None);
+    };
+    let Some(token) = presented_token(request) else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "API token required",
+        )
+            .into_response());
+    };
+    let record = match tokens.verify(&token) {
+        Ok(record) => record,
+        Err(TokenError::Unavailable(e)) => {
+            tracing::error!("Token store unavailable: {}", e);
+            return Err((StatusCode::SERVICE_UNAVAILABLE, "Token store unavailable").into_response());
+        }
+        Err(e) => {
+            tracing::debug!("Rejected API token: {}", e);
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer error=\"invalid_token\"")],
+                e.to_string(),
+            )
+                .into_response());
+        }
+    };
+
+    auth.per_key.check(&record.id).map_err(too_many_requests)?;
+    let actor = format!("token:{}", record.id);
+    check_scope(record.allows(required_scope(request.method())), &actor, request)?;
+
+    request.extensions_mut().insert(record);
+    Ok(Some(actor))
+}
+
+#[allow(clippy::result_large_err)]
+fn check_scope(allowed: bool, actor: &str, request: &Request) -> Result<(), Response> {
+    if allowed {
+        return Ok(());
+    }
+    Err((
+        StatusCode::FORBIDDEN,
+        format!("{} lacks the '{}' scope", actor, required_scope(request.method())),
+    )
+        .into_response())
 }
 
-/// Rate limiting middleware (basic implementation)
+/// Rate limiting middleware, per client address or local user
 pub async fn rate_limit(
+    State(auth): State<HttpAuth>,
     request: Request,
     next: Next,
 ) -> Response {
-    // Simple rate limiting based on IP
-    // In production, you'd want a more sophisticated solution
-    let client_ip = request.headers()
-        .get("x-forwarded-for")
-        .or_else(|| request.headers().get("x-real-ip"))
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("unknown");
+    let client = if let Some(peer) = request.extensions().get::<PeerCredentials>() {
+        format!("uid:{}", peer.uid)
+    } else if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        addr.ip().to_string()
+    } else {
+        "unknown".to_string()
+    };
 
-    // For now, just log and allow all requests
-    tracing::debug!("Request from IP: {}", client_ip);
+    if let Err(retry_after) = auth.per_ip.check(&client) {
+        tracing::debug!("Rate limited {}", client);
+        return too_many_requests(retry_after);
+    }
 
     next.run(request).await
 }
@@ -177,3 +486,175 @@ pub async fn timeout(
         ).into_response(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get};
+    use tower::ServiceExt;
+
+    fn app(auth: HttpAuth) -> Router {
+        auth.apply(
+            Router::new()
+                .route("/api/state", get(|| async { "ok" }).post(|| async { "applied" }))
+                .route("/index.html", get(|| async { "page" })),
+        )
+    }
+
+    async fn status(app: &Router, method: Method, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(60, 2);
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_ok());
+        let retry_after = limiter.check("a").unwrap_err();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1));
+        assert!(limiter.check("b").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_token_auth_and_scopes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::open(dir.path().join("tokens.json"));
+        let (_, reader) = store.create("reader", vec![Scope::Read], None).unwrap();
+        let (_, applier) = store.create("applier", vec![Scope::Apply], None).unwrap();
+        let app = app(HttpAuth::new(store));
+
+        assert_eq!(status(&app, Method::GET, "/index.html", None).await, StatusCode::OK);
+        assert_eq!(status(&app, Method::GET, "/api/state", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(&app, Method::GET, "/api/state", Some("opd_bad_token")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(&app, Method::GET, "/api/state", Some(&reader)).await, StatusCode::OK);
+        assert_eq!(
+            status(&app, Method::POST, "/api/state", Some(&reader)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(&app, Method::POST, "/api/state", Some(&applier)).await, StatusCode::OK);
+        // The query parameter is for WebSocket upgrades only
+        let with_query = format!("/api/state?access_token={}", reader);
+        assert_eq!(
+            status(&app, Method::GET, &with_query, None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let upgrade = Request::builder()
+            .uri(&with_query)
+            .header(header::UPGRADE, "websocket")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.oneshot(upgrade).await.unwrap().status(), StatusCode::OK);
+    }
+
+    /// Log output of a test, through a subscriber on the current thread
+    #[derive(Clone, Default)]
+    struct CapturedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_token_not_logged() {
+        let log = CapturedLog::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::open(dir.path().join("tokens.json"));
+        let (_, token) = store.create("reader", vec![Scope::Read], None).unwrap();
+        let authenticated = app(HttpAuth::new(store));
+        // Layered like ServerBuilder::build, and with request_logger outermost
+        let traced = authenticated
+            .clone()
+            .layer(TraceLayer::new_for_http())
+            .layer(from_fn(strip_query_token));
+        let logged = authenticated.layer(from_fn(request_logger));
+
+        for app in [traced, logged] {
+            let upgrade = Request::builder()
+                .uri(format!("/api/state?page=2&access_token={}", token))
+                .header(header::UPGRADE, "websocket")
+                .body(Body::empty())
+                .unwrap();
+            assert_eq!(app.oneshot(upgrade).await.unwrap().status(), StatusCode::OK);
+        }
+
+        let output = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("/api/state?page=2"), "{}", output);
+        assert!(!output.contains(&token) && !output.contains("access_token"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_roles() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = HttpAuth::new(TokenStore::open(dir.path().join("tokens.json")));
+        let app = auth.apply(Router::new().route(
+            "/api/state",
+            get(|| async { crate::blockchain::plugin_footprint::current_actor().unwrap() })
+                .post(|| async { "applied" }),
+        ));
+        let peer = |role| PeerIdentity {
+            subject: "node2.fleet".into(),
+            fingerprint: "ab".repeat(32),
+            role,
+        };
+        let send = |method, identity: PeerIdentity| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri("/api/state")
+                .body(Body::empty())
+                .unwrap();
+            request.extensions_mut().insert(identity);
+            app.clone().oneshot(request)
+        };
+
+        let response = send(Method::GET, peer(Some(Scope::Read))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"cert:node2.fleet#abababababababab");
+
+        let forbidden = send(Method::POST, peer(Some(Scope::Read))).await.unwrap();
+        assert_eq!(forbidden.status(), StatusCode::FORBIDDEN);
+        let applied = send(Method::POST, peer(Some(Scope::Apply))).await.unwrap();
+        assert_eq!(applied.status(), StatusCode::OK);
+        let unmapped = send(Method::GET, peer(None)).await.unwrap();
+        assert_eq!(unmapped.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_response() {
+        let app = app(HttpAuth::disabled().with_rate_limit(1, 1));
+        assert_eq!(status(&app, Method::GET, "/api/state", None).await, StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::builder().uri("/api/state").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+    }
+}
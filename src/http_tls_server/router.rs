@@ -136,7 +136,7 @@ impl ServiceRouter {
             routes.push(full_path);
         }
 
-        for (path, nested_router) in &self.nested_routers {
+        for (path, _nested_router) in &self.nested_routers {
             let full_path = if path.starts_with('/') {
                 format!("{}{}", self.base_path, path)
             } else {
@@ -3,15 +3,19 @@
 //! Based on the chat_main.rs implementation, this provides a configurable
 //! HTTP/TLS server that can be shared across different services.
 
-use axum::{Router, response::Redirect};
+use axum::{Extension, Router, response::Redirect};
+use axum_server::tls_rustls::RustlsConfig;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::TcpListener;
+use std::path::PathBuf;
+use tokio::net::{TcpListener, UnixListener};
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
+use super::local_ca::{self, LocalCa};
+use super::mtls::{ClientAuthConfig, ClientAuthMode, MtlsAcceptor};
+use super::request_filters::{strip_query_token, HttpAuth};
 use super::router::{RouterRegistry, ServiceRouter};
-use super::tls::{TlsConfig, CertificateSource};
+use super::tls::load_local_ca;
 use super::{ServerError, Result};
 
 /// Server configuration detected via introspection
@@ -33,19 +37,35 @@ pub enum TlsMode {
     Disabled,
     /// TLS enabled with certificate files
     Enabled { cert_path: String, key_path: String },
-    /// Auto-detect certificates
+    /// Auto-detect certificates, falling back to the local CA
     Auto,
+    /// Certificates issued and rotated by the local CA in `dir`
+    LocalCa { dir: PathBuf },
+}
+
+/// Credentials of the process connected over the unix socket
+///
+/// Added as a request extension; requests over TCP do not carry it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub pid: Option<u32>,
+    pub uid: u32,
+    pub gid: u32,
 }
 
 /// Server builder for configuring the HTTP/TLS server
 #[derive(Clone)]
 pub struct ServerBuilder {
     bind_addr: Option<String>,
+    unix_socket: Option<PathBuf>,
+    unix_socket_group: Option<String>,
     public_host: Option<String>,
     tls_mode: TlsMode,
     router_registry: RouterRegistry,
     cors_enabled: bool,
     tracing_enabled: bool,
+    auth: Option<HttpAuth>,
+    client_auth: Option<ClientAuthConfig>,
 }
 
 impl ServerBuilder {
@@ -53,11 +73,15 @@ impl ServerBuilder {
     pub fn new() -> Self {
         Self {
             bind_addr: None,
+            unix_socket: None,
+            unix_socket_group: None,
             public_host: None,
             tls_mode: TlsMode::Disabled,
             router_registry: RouterRegistry::new(),
             cors_enabled: true,
             tracing_enabled: true,
+            auth: None,
+            client_auth: None,
         }
     }
 
@@ -67,6 +91,18 @@ impl ServerBuilder {
         self
     }
 
+    /// Also serve on a unix socket, passing the caller's PeerCredentials
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Group allowed to connect to the unix socket, besides its owner
+    pub fn unix_socket_group(mut self, group: impl Into<String>) -> Self {
+        self.unix_socket_group = Some(group.into());
+        self
+    }
+
     /// Set the public host for URLs
     pub fn public_host(mut self, host: impl Into<String>) -> Self {
         self.public_host = Some(host.into());
@@ -88,6 +124,18 @@ impl ServerBuilder {
         self
     }
 
+    /// Enable HTTPS with a local CA, see `LocalCa`
+    pub fn https_local_ca(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.tls_mode = TlsMode::LocalCa { dir: dir.into() };
+        self
+    }
+
+    /// Ask HTTPS clients for certificates, see `ClientAuthConfig`
+    pub fn client_auth(mut self, config: ClientAuthConfig) -> Self {
+        self.client_auth = Some(config);
+        self
+    }
+
     /// Register a service router
     pub fn service_router(mut self, router: ServiceRouter) -> Self {
         let service_name = router.base_path().trim_start_matches('/').to_string();
@@ -116,6 +164,12 @@ impl ServerBuilder {
         self
     }
 
+    /// Require API tokens and rate limit requests (not /health or /metrics)
+    pub fn auth(mut self, auth: HttpAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
     /// Build the server
     pub async fn build(self) -> Result<Server> {
         let config = self.detect_config().await?;
@@ -123,6 +177,10 @@ impl ServerBuilder {
         // Build the complete router
         let mut app = self.router_registry.clone().build_complete_router();
 
+        if let Some(auth) = self.auth.clone() {
+            app = auth.apply(app);
+        }
+
         // Add global middleware
         if self.cors_enabled {
             app = app.layer(CorsLayer::permissive());
@@ -132,9 +190,18 @@ impl ServerBuilder {
             app = app.layer(TraceLayer::new_for_http());
         }
 
+        // Query tokens leave the URI before the trace layer logs it
+        app = app.layer(axum::middleware::from_fn(strip_query_token));
+
         // Add health check endpoint
         app = app.route("/health", axum::routing::get(health_check));
 
+        // Add Prometheus metrics endpoint
+        app = app.route(
+            "/metrics",
+            axum::routing::get(super::metrics::handlers::gathered_metrics),
+        );
+
         // Add root redirect if web service exists
         if self.router_registry.get_service("web").is_some() {
             app = app.route("/", axum::routing::get(|| async {
@@ -146,6 +213,9 @@ impl ServerBuilder {
             config,
             app,
             tls_mode: self.tls_mode,
+            unix_socket: self.unix_socket,
+            unix_socket_group: self.unix_socket_group,
+            client_auth: self.client_auth,
         })
     }
 
@@ -195,8 +265,13 @@ impl ServerBuilder {
                 let key_path = std::env::var("SSL_KEY_PATH")
                     .unwrap_or_else(|_| cert_path.replace(".pem", ".key"));
 
-                (!cert_path.is_empty() && std::path::Path::new(&cert_path).exists(), cert_path, key_path)
+                if !cert_path.is_empty() && std::path::Path::new(&cert_path).exists() {
+                    (true, cert_path, key_path)
+                } else {
+                    local_ca_paths(&LocalCa::default_dir())
+                }
             }
+            TlsMode::LocalCa { dir } => local_ca_paths(dir),
         };
 
         Ok(ServerConfig {
@@ -222,6 +297,9 @@ pub struct Server {
     config: ServerConfig,
     app: Router,
     tls_mode: TlsMode,
+    unix_socket: Option<PathBuf>,
+    unix_socket_group: Option<String>,
+    client_auth: Option<ClientAuthConfig>,
 }
 
 impl Server {
@@ -230,6 +308,18 @@ impl Server {
         let config = self.config.clone();
         let tls_mode = self.tls_mode.clone();
         let app = self.app;
+        let client_auth = self.client_auth;
+
+        // TCP still serves read-only clients when the socket cannot be bound
+        if let Some(path) = self.unix_socket {
+            match bind_unix_socket(&path, self.unix_socket_group.as_deref()) {
+                Ok(listener) => {
+                    info!("🔌 Unix socket listening on {}", path.display());
+                    tokio::spawn(serve_unix(listener, app.clone()));
+                }
+                Err(e) => warn!("Unix socket {} not available: {}", path.display(), e),
+            }
+        }
 
         let http_addr: SocketAddr = format!("{}:{}", config.bind_host, config.http_port)
             .parse()
@@ -253,21 +343,29 @@ impl Server {
                 info!("🌐 HTTP server listening on http://{}", http_addr);
                 info!("⚠️  HTTP is not secure - use HTTPS for production");
                 log_endpoints(&config, false);
-                axum::serve(listener, app).await
-                    .map_err(|e| ServerError::BindError(std::io::Error::new(
-                        std::io::ErrorKind::Other, e
-                    )))?;
+                axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+                    .map_err(|e| ServerError::BindError(std::io::Error::other(e)))?;
             }
             TlsMode::Enabled { cert_path, key_path } => {
                 // Try HTTPS first, fallback to HTTP
-                Self::serve_with_tls_fallback_helper(http_addr, https_addr, &cert_path, &key_path, app, config).await?;
+                let tls = RustlsConfig::from_pem_file(&cert_path, &key_path).await;
+                Self::serve_with_tls_fallback_helper(http_addr, https_addr, tls, app, config, client_auth).await?;
             }
             TlsMode::Auto => {
-                // Auto-detect certificates
-                let cert_path = detect_ssl_certificates().unwrap_or_else(|_| "".to_string());
-                let key_path = std::env::var("SSL_KEY_PATH")
-                    .unwrap_or_else(|_| cert_path.replace(".pem", ".key"));
-                Self::serve_with_tls_fallback_helper(http_addr, https_addr, &cert_path, &key_path, app, config).await?;
+                // Auto-detect certificates, or issue them from the local CA
+                let tls = match detect_ssl_certificates() {
+                    Ok(cert_path) => {
+                        let key_path = std::env::var("SSL_KEY_PATH")
+                            .unwrap_or_else(|_| cert_path.replace(".pem", ".key"));
+                        RustlsConfig::from_pem_file(&cert_path, &key_path).await
+                    }
+                    Err(_) => local_ca_rustls_config(&LocalCa::default_dir(), client_auth.clone()).await,
+                };
+                Self::serve_with_tls_fallback_helper(http_addr, https_addr, tls, app, config, client_auth).await?;
+            }
+            TlsMode::LocalCa { dir } => {
+                let tls = local_ca_rustls_config(&dir, client_auth.clone()).await;
+                Self::serve_with_tls_fallback_helper(http_addr, https_addr, tls, app, config, client_auth).await?;
             }
         }
 
@@ -277,39 +375,55 @@ impl Server {
     async fn serve_with_tls_fallback_helper(
         http_addr: SocketAddr,
         https_addr: SocketAddr,
-        cert_path: &str,
-        key_path: &str,
+        tls: std::io::Result<RustlsConfig>,
         app: Router,
         config: ServerConfig,
+        client_auth: Option<ClientAuthConfig>,
     ) -> Result<()> {
         // Use axum-server for HTTPS (Rust-only, no Node.js)
-        use axum_server::tls_rustls::RustlsConfig;
-
-        match RustlsConfig::from_pem_file(
-            std::path::Path::new(cert_path),
-            std::path::Path::new(key_path),
-        ).await {
+        match tls {
             Ok(rustls_config) => {
                 info!("🔒 HTTPS enabled - Loading TLS configuration...");
+                if let Some(client_auth) = &client_auth {
+                    client_auth.apply(&rustls_config)?;
+                    info!("🪪 Client certificates {:?}, CA {}", client_auth.mode, client_auth.ca_path.display());
+                }
 
-                // Start HTTP server (redirect or fallback)
+                // Start HTTP server (redirect or fallback); when client
+                // certificates are required, plain HTTP only redirects
                 let http_listener = TcpListener::bind(http_addr).await
                     .map_err(ServerError::BindError)?;
-                let http_app = app.clone();
+                let http_app = match &client_auth {
+                    Some(client_auth) if client_auth.mode == ClientAuthMode::Required => {
+                        https_redirect(config.https_port)
+                    }
+                    _ => app.clone(),
+                };
                 tokio::spawn(async move {
                     info!("🌐 HTTP server listening on http://{} (redirects to HTTPS)", http_addr);
-                    let _ = axum::serve(http_listener, http_app).await;
+                    let _ = axum::serve(
+                        http_listener,
+                        http_app.into_make_service_with_connect_info::<SocketAddr>(),
+                    ).await;
                 });
 
                 info!("🔒 HTTPS server listening on https://{}", https_addr);
                 log_endpoints(&config, true);
 
-                axum_server::bind_rustls(https_addr, rustls_config)
-                    .serve(app.into_make_service())
-                    .await
-                    .map_err(|e| ServerError::BindError(std::io::Error::new(
-                        std::io::ErrorKind::Other, e
-                    )))?;
+                let served = match client_auth {
+                    Some(client_auth) => {
+                        axum_server::bind(https_addr)
+                            .acceptor(MtlsAcceptor::new(rustls_config, client_auth))
+                            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                            .await
+                    }
+                    None => {
+                        axum_server::bind_rustls(https_addr, rustls_config)
+                            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                            .await
+                    }
+                };
+                served.map_err(|e| ServerError::BindError(std::io::Error::other(e)))?;
             }
             Err(e) => {
                 warn!("⚠️  HTTPS enabled but certificates not found, falling back to HTTP");
@@ -322,10 +436,8 @@ impl Server {
                 info!("🌐 HTTP server listening on http://{}", http_addr);
                 info!("⚠️  HTTP is not secure - use HTTPS for production");
                 log_endpoints(&config, false);
-                axum::serve(listener, app).await
-                    .map_err(|e| ServerError::BindError(std::io::Error::new(
-                        std::io::ErrorKind::Other, e
-                    )))?;
+                axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+                    .map_err(|e| ServerError::BindError(std::io::Error::other(e)))?;
             }
         }
 
@@ -344,6 +456,113 @@ impl Server {
     }
 }
 
+/// Server certificate and key paths of the local CA in `dir`
+fn local_ca_paths(dir: &std::path::Path) -> (bool, String, String) {
+    (
+        true,
+        dir.join(local_ca::SERVER_CERT).display().to_string(),
+        dir.join(local_ca::SERVER_KEY).display().to_string(),
+    )
+}
+
+/// Router that sends every request to the same path over HTTPS
+fn https_redirect(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: axum::http::HeaderMap, uri: axum::http::Uri| async move {
+        let host = headers
+            .get(axum::http::header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.rsplit_once(':').map_or(h, |(host, _)| host).to_string())
+            .unwrap_or_else(|| "localhost".to_string());
+        let path = uri.path_and_query().map_or("/", |pq| pq.as_str());
+        Redirect::permanent(&format!("https://{}:{}{}", host, https_port, path))
+    })
+}
+
+/// Load the local CA's server certificate and keep it rotated
+async fn local_ca_rustls_config(
+    dir: &std::path::Path,
+    client_auth: Option<ClientAuthConfig>,
+) -> std::io::Result<RustlsConfig> {
+    let (rustls_config, ca) = load_local_ca(dir)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    info!("🔏 Using certificates from the local CA in {}", dir.display());
+    ca.spawn_rotation(rustls_config.clone(), client_auth);
+    Ok(rustls_config)
+}
+
+/// Bind a unix socket its owner and `group` may connect to
+///
+/// Access is decided per request from the caller's credentials.
+fn bind_unix_socket(path: &std::path::Path, group: Option<&str>) -> Result<UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(ServerError::BindError)?;
+    }
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(ServerError::BindError(e));
+        }
+        _ => {}
+    }
+    let listener = UnixListener::bind(path).map_err(ServerError::BindError)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))
+        .map_err(ServerError::BindError)?;
+    if let Some(group) = group {
+        let not_found = || {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Group {} does not exist", group),
+            )
+        };
+        let gid = nix::unistd::Group::from_name(group)
+            .map_err(|e| ServerError::BindError(e.into()))?
+            .ok_or_else(|| ServerError::BindError(not_found()))?
+            .gid;
+        nix::unistd::chown(path, None, Some(gid))
+            .map_err(|e| ServerError::BindError(e.into()))?;
+    }
+    Ok(listener)
+}
+
+/// Serve HTTP/1.1 on a unix socket with PeerCredentials on every request
+async fn serve_unix(listener: UnixListener, app: Router) {
+    use hyper_util::rt::TokioIo;
+    use hyper_util::service::TowerToHyperService;
+
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Unix socket accept failed: {}", e);
+                continue;
+            }
+        };
+        let peer = match stream.peer_cred() {
+            Ok(cred) => PeerCredentials {
+                pid: cred.pid().and_then(|pid| u32::try_from(pid).ok()),
+                uid: cred.uid(),
+                gid: cred.gid(),
+            },
+            Err(e) => {
+                warn!("Failed to read peer credentials: {}", e);
+                continue;
+            }
+        };
+
+        let service = TowerToHyperService::new(app.clone().layer(Extension(peer)));
+        tokio::spawn(async move {
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Unix socket connection from {:?} failed: {}", peer.pid, e);
+            }
+        });
+    }
+}
+
 /// Health check handler
 async fn health_check() -> &'static str {
     "OK"
@@ -356,6 +575,7 @@ fn log_endpoints(config: &ServerConfig, https: bool) {
 
     info!("📡 Available endpoints:");
     info!("   - {scheme}://{}:{}/health", config.public_host, port);
+    info!("   - {scheme}://{}:{}/metrics", config.public_host, port);
     info!("   - {scheme}://{}:{}/", config.public_host, port);
 }
 
@@ -2,9 +2,11 @@
 //!
 //! Handles certificate detection, loading, and TLS configuration.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use super::local_ca::{self, HostNames, LocalCa};
+
 /// TLS configuration errors
 #[derive(Debug, Error)]
 pub enum TlsError {
@@ -29,6 +31,8 @@ pub enum CertificateSource {
     Auto,
     /// Let's Encrypt ACME
     LetsEncrypt { domain: String, email: String },
+    /// Server certificate issued by a local CA kept in `dir`
+    LocalCa { dir: PathBuf },
 }
 
 /// TLS configuration
@@ -73,6 +77,15 @@ impl TlsConfig {
         }
     }
 
+    /// Create TLS config with certificates from a local CA
+    pub fn local_ca(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_source: CertificateSource::LocalCa { dir: dir.into() },
+            min_tls_version: rustls::ProtocolVersion::TLSv1_2,
+            cipher_suites: rustls::DEFAULT_CIPHER_SUITES.to_vec(),
+        }
+    }
+
     /// Set minimum TLS version
     pub fn min_tls_version(mut self, version: rustls::ProtocolVersion) -> Self {
         self.min_tls_version = version;
@@ -96,8 +109,11 @@ impl TlsConfig {
                 ).await.map_err(|e| TlsError::Rustls(rustls::Error::General(e.to_string())))
             }
             CertificateSource::Auto => {
-                // Auto-detect certificates
-                let cert_path = detect_ssl_certificates()?;
+                // Auto-detect certificates, or provision them from the local CA
+                let Ok(cert_path) = detect_ssl_certificates() else {
+                    let (config, _) = load_local_ca(&LocalCa::default_dir()).await?;
+                    return Ok(config);
+                };
                 let key_path = std::env::var("SSL_KEY_PATH")
                     .unwrap_or_else(|_| cert_path.replace(".pem", ".key"));
 
@@ -121,6 +137,10 @@ impl TlsConfig {
                     Self::auto().build_rustls_config().await
                 }).await
             }
+            CertificateSource::LocalCa { dir } => {
+                let (config, _) = load_local_ca(dir).await?;
+                Ok(config)
+            }
         }
     }
 
@@ -132,6 +152,7 @@ impl TlsConfig {
             }
             CertificateSource::Auto => detect_ssl_certificates().is_ok(),
             CertificateSource::LetsEncrypt { .. } => false, // Not implemented yet
+            CertificateSource::LocalCa { .. } => true, // Provisioned on demand
         }
     }
 }
@@ -142,6 +163,22 @@ impl Default for TlsConfig {
     }
 }
 
+/// Provision the server certificate from the local CA in `dir` and load it
+///
+/// The CA is returned so the caller can keep the certificate rotated with
+/// `LocalCa::spawn_rotation`.
+pub async fn load_local_ca(
+    dir: &Path,
+) -> Result<(axum_server::tls_rustls::RustlsConfig, LocalCa), TlsError> {
+    let ca = LocalCa::open_or_create(dir)?;
+    ca.ensure_server_cert(&HostNames::detect())?;
+    let config = axum_server::tls_rustls::RustlsConfig::from_pem_file(
+        ca.server_cert_path(),
+        ca.server_key_path(),
+    ).await?;
+    Ok((config, ca))
+}
+
 /// Detect SSL certificates via introspection
 fn detect_ssl_certificates() -> Result<String, TlsError> {
     let cert_paths = [
@@ -171,18 +208,25 @@ fn detect_ssl_certificates() -> Result<String, TlsError> {
 pub mod cert_utils {
     use super::*;
 
-    /// Generate self-signed certificate for development
+    /// Issue a certificate for `domain` from the local CA
+    ///
+    /// Clients trust it through the CA certificate (`op-dbus tls export-ca`).
     pub fn generate_self_signed_cert(
         domain: &str,
         cert_path: &Path,
         key_path: &Path,
     ) -> Result<(), TlsError> {
-        // This would use rcgen or similar to generate certs
-        // For now, just return an error
-        Err(TlsError::Io(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
-            "Self-signed certificate generation not yet implemented"
-        )))
+        let ca = LocalCa::open_or_create(LocalCa::default_dir())?;
+        let mut names = HostNames::default();
+        match domain.parse() {
+            Ok(ip) => names.add_ip(ip),
+            Err(_) => names.add_dns(domain),
+        }
+        let (chain, key) = ca.issue(&names, chrono::Duration::days(365))?;
+        std::fs::write(cert_path, chain)?;
+        std::fs::write(key_path, key)?;
+        std::fs::set_permissions(key_path, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        Ok(())
     }
 
     /// Validate certificate
@@ -204,11 +248,11 @@ pub mod cert_utils {
 
     /// Get certificate expiration date
     pub fn get_cert_expiration(cert_path: &Path) -> Result<std::time::SystemTime, TlsError> {
-        // This would parse the certificate and extract expiration
-        // For now, just return an error
-        Err(TlsError::Io(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
-            "Certificate expiration checking not yet implemented"
-        )))
+        let der = local_ca::read_pem_certs(cert_path)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| TlsError::InvalidCert("No certificate in file".to_string()))?;
+        let (_, not_after) = local_ca::certificate_validity(&der)?;
+        Ok(not_after.into())
     }
 }
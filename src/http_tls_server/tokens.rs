@@ -0,0 +1,329 @@
+//! API Token Store
+//!
+//! Bearer tokens for the HTTP APIs, managed with `op-dbus token`. Only the
+//! SHA-256 of each secret is kept, in `OPDBUS_TOKEN_FILE` (default
+//! /etc/op-dbus/tokens.json, mode 0600). The file is re-read when it
+//! changes, so revocations take effect without a restart.
+//!
+//! Tokens look like `opd_<id>_<secret>`; the id is safe to log.
+
+use crate::encoding::{constant_time_eq, to_hex};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+pub const DEFAULT_TOKEN_FILE: &str = "/etc/op-dbus/tokens.json";
+
+const TOKEN_PREFIX: &str = "opd_";
+
+/// What a token may do; each scope includes the ones below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Queries
+    Read,
+    /// Changes to system state
+    Apply,
+    /// Destructive operations
+    Admin,
+}
+
+impl Scope {
+    pub fn allows(self, required: Scope) -> bool {
+        self >= required
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(Self::Read),
+            "apply" => Ok(Self::Apply),
+            "admin" => Ok(Self::Admin),
+            other => anyhow::bail!("Unknown scope '{}' (read, apply or admin)", other),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Apply => "apply",
+            Self::Admin => "admin",
+        })
+    }
+}
+
+/// A stored token, without its secret
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenRecord {
+    pub id: String,
+    pub name: String,
+    /// SHA-256 of the secret, hex encoded
+    hash: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TokenRecord {
+    pub fn allows(&self, required: Scope) -> bool {
+        self.scopes.iter().any(|scope| scope.allows(required))
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// Why a presented token was rejected
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TokenError {
+    #[error("Malformed API token")]
+    Malformed,
+    #[error("Unknown or revoked API token")]
+    Unknown,
+    #[error("API token {0} has expired")]
+    Expired(String),
+    #[error("Token store unavailable: {0}")]
+    Unavailable(String),
+}
+
+#[derive(Default)]
+struct Cached {
+    records: Vec<TokenRecord>,
+    modified: Option<SystemTime>,
+}
+
+/// Token file with a cache refreshed on modification
+pub struct TokenStore {
+    path: PathBuf,
+    cache: Mutex<Cached>,
+}
+
+impl TokenStore {
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: Mutex::new(Cached::default()),
+        }
+    }
+
+    /// Store at `OPDBUS_TOKEN_FILE` or the default path
+    pub fn from_env() -> Self {
+        Self::open(
+            std::env::var_os("OPDBUS_TOKEN_FILE")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_TOKEN_FILE)),
+        )
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create a token; the returned secret is not stored and cannot be shown again
+    pub fn create(
+        &self,
+        name: &str,
+        scopes: Vec<Scope>,
+        ttl: Option<chrono::Duration>,
+    ) -> Result<(TokenRecord, String)> {
+        if scopes.is_empty() {
+            anyhow::bail!("A token needs at least one scope");
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut id = [0u8; 6];
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut id);
+        rng.fill_bytes(&mut secret);
+        let id = to_hex(&id);
+        let secret = to_hex(&secret);
+
+        let now = Utc::now();
+        let record = TokenRecord {
+            id: id.clone(),
+            name: name.to_string(),
+            hash: hash_secret(&secret),
+            scopes,
+            created_at: now,
+            expires_at: ttl.map(|ttl| now + ttl),
+        };
+
+        let mut records = self.load()?;
+        records.push(record.clone());
+        self.save(&records)?;
+        Ok((record, format!("{}{}_{}", TOKEN_PREFIX, id, secret)))
+    }
+
+    pub fn list(&self) -> Result<Vec<TokenRecord>> {
+        self.load()
+    }
+
+    /// Remove tokens whose id or name is `id_or_name`; returns how many
+    pub fn revoke(&self, id_or_name: &str) -> Result<usize> {
+        let mut records = self.load()?;
+        let before = records.len();
+        records.retain(|r| r.id != id_or_name && r.name != id_or_name);
+        let revoked = before - records.len();
+        if revoked > 0 {
+            self.save(&records)?;
+        }
+        Ok(revoked)
+    }
+
+    /// Look up the record of a presented token
+    pub fn verify(&self, token: &str) -> std::result::Result<TokenRecord, TokenError> {
+        let (id, secret) = token
+            .strip_prefix(TOKEN_PREFIX)
+            .and_then(|rest| rest.split_once('_'))
+            .ok_or(TokenError::Malformed)?;
+
+        let records = self
+            .cached()
+            .map_err(|e| TokenError::Unavailable(e.to_string()))?;
+        let record = records
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or(TokenError::Unknown)?;
+
+        if !constant_time_eq(record.hash.as_bytes(), hash_secret(secret).as_bytes()) {
+            return Err(TokenError::Unknown);
+        }
+        if record.is_expired(Utc::now()) {
+            return Err(TokenError::Expired(record.id));
+        }
+        Ok(record)
+    }
+
+    /// Records from the cache, re-reading the file if it changed
+    fn cached(&self) -> Result<Vec<TokenRecord>> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        let mut cache = self.cache.lock().unwrap();
+        if modified.is_none() || cache.modified != modified {
+            cache.records = self.load()?;
+            cache.modified = modified;
+        }
+        Ok(cache.records.clone())
+    }
+
+    fn load(&self) -> Result<Vec<TokenRecord>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid token file {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", self.path.display())),
+        }
+    }
+
+    fn save(&self, records: &[TokenRecord]) -> Result<()> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        file.write_all(serde_json::to_string_pretty(records)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Parse a lifetime such as "90d", "12h", "30m" or "3600" (seconds)
+pub fn parse_ttl(value: &str) -> Result<chrono::Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().last() {
+        Some((idx, unit)) if unit.is_ascii_alphabetic() => (&value[..idx], unit),
+        _ => (value, 's'),
+    };
+    let number: i64 = number
+        .parse()
+        .with_context(|| format!("Invalid lifetime '{}'", value))?;
+    match unit {
+        's' => Ok(chrono::Duration::seconds(number)),
+        'm' => Ok(chrono::Duration::minutes(number)),
+        'h' => Ok(chrono::Duration::hours(number)),
+        'd' => Ok(chrono::Duration::days(number)),
+        _ => anyhow::bail!("Unknown lifetime unit in '{}' (s, m, h or d)", value),
+    }
+}
+
+fn hash_secret(secret: &str) -> String {
+    to_hex(&Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_verify_revoke() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::open(dir.path().join("tokens.json"));
+
+        let (record, token) = store.create("ci", vec![Scope::Apply], None).unwrap();
+        assert!(token.starts_with(&format!("opd_{}_", record.id)));
+        assert!(!std::fs::read_to_string(store.path())
+            .unwrap()
+            .contains(token.rsplit('_').next().unwrap()));
+
+        let verified = store.verify(&token).unwrap();
+        assert_eq!(verified.name, "ci");
+        assert!(verified.allows(Scope::Read));
+        assert!(verified.allows(Scope::Apply));
+        assert!(!verified.allows(Scope::Admin));
+
+        let forged = format!("opd_{}_{}", record.id, "0".repeat(64));
+        assert_eq!(store.verify(&forged), Err(TokenError::Unknown));
+        assert_eq!(store.verify("Bearer nonsense"), Err(TokenError::Malformed));
+
+        // Revoked by another process
+        let other = TokenStore::open(store.path());
+        assert_eq!(other.revoke("ci").unwrap(), 1);
+        assert_eq!(store.verify(&token), Err(TokenError::Unknown));
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expiry_and_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::open(dir.path().join("tokens.json"));
+        let (record, token) = store
+            .create(
+                "old",
+                vec![Scope::Read],
+                Some(chrono::Duration::seconds(-1)),
+            )
+            .unwrap();
+        assert_eq!(store.verify(&token), Err(TokenError::Expired(record.id)));
+
+        assert_eq!(parse_ttl("90d").unwrap(), chrono::Duration::days(90));
+        assert_eq!(parse_ttl("3600").unwrap(), chrono::Duration::hours(1));
+        assert!(parse_ttl("1y").is_err());
+        assert!("root".parse::<Scope>().is_err());
+    }
+}
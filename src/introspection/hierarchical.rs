@@ -318,7 +318,7 @@ impl HierarchicalIntrospector {
             used_object_manager = true;
 
             // Parse managed objects into our format
-            for (path, iface_data) in managed_objects {
+            for (path, _iface_data) in managed_objects {
                 let obj_data = self
                     .introspect_object_by_path(conn, service_name, &path)
                     .await?;
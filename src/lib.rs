@@ -3,16 +3,21 @@
 //! This crate provides declarative system state management through native Linux protocols.
 
 // Core modules
+pub mod audit;
 pub mod blockchain;
 pub mod cache;
 pub mod deployment;
+pub mod encoding;
 pub mod introspection;
 pub mod isp_migration;
 pub mod isp_support;
 pub mod native;
 pub mod nonnet_db;
+pub mod replication;
 pub mod snapshot;
 pub mod state;
+pub mod storage;
+pub mod task_queue;
 
 // Loose coupling modules
 pub mod event_bus;
@@ -1,14 +1,11 @@
 //! op-dbus - Operation D-Bus
 //! Declarative system state management via native protocols
 
-mod blockchain;
-mod cache;
 #[cfg(feature = "ml")]
-mod ml;
-mod native;
-mod nonnet_db;
-mod state;
-mod webui;
+use op_dbus::ml;
+#[cfg(feature = "web")]
+use op_dbus::webui;
+use op_dbus::{audit, cache, native, nonnet_db, replication, state, storage};
 
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
@@ -127,6 +124,7 @@ enum Commands {
     Cache(CacheCommands),
 
     /// Start web UI server
+    #[cfg(feature = "web")]
     Serve {
         /// Bind address
         #[arg(long, default_value = "0.0.0.0")]
@@ -145,6 +143,215 @@ enum Commands {
     /// Deployment image management (BTRFS snapshots with symlink deduplication)
     #[command(subcommand)]
     Image(ImageCommands),
+
+    /// Snapshot replication (btrfs send/receive or tar streams, by storage backend)
+    #[command(subcommand)]
+    Replica(ReplicaCommands),
+
+    /// API tokens for the HTTP servers (web UI, MCP, chat)
+    #[command(subcommand)]
+    Token(TokenCommands),
+
+    /// Local CA and server certificates for the HTTPS servers
+    #[command(subcommand)]
+    Tls(TlsCommands),
+
+    /// Audit log of changes made through any front-end
+    #[command(subcommand)]
+    Audit(AuditCommands),
+
+    /// Agent task queue and history
+    #[command(subcommand)]
+    Task(TaskCommands),
+}
+
+#[derive(Subcommand)]
+enum TaskCommands {
+    /// List tasks, newest first
+    List {
+        /// Agent instance id
+        #[arg(short, long)]
+        agent: Option<String>,
+        /// Status: pending, running, completed, failed or cancelled
+        #[arg(short, long)]
+        status: Option<String>,
+        /// Show at most this many tasks
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+        /// Print tasks as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show a task with its payload and result
+    Show {
+        /// Task id
+        id: String,
+    },
+
+    /// Cancel a pending or running task
+    Cancel {
+        /// Task id
+        id: String,
+    },
+
+    /// Queue a finished task again
+    Replay {
+        /// Task id
+        id: String,
+    },
+
+    /// Delete finished tasks older than the retention period
+    Purge,
+}
+
+#[derive(Subcommand)]
+enum AuditCommands {
+    /// Search the audit log, oldest first
+    Search {
+        /// Actor containing this text (e.g. uid:0, token:, cert:node2)
+        #[arg(long)]
+        actor: Option<String>,
+        /// Plugin name
+        #[arg(short, long)]
+        plugin: Option<String>,
+        /// Front-end: cli, dbus, web, mcp or chat
+        #[arg(long)]
+        frontend: Option<String>,
+        /// Operation containing this text (e.g. apply, token_create)
+        #[arg(long)]
+        operation: Option<String>,
+        /// Outcome: success, failure or denied
+        #[arg(long)]
+        outcome: Option<String>,
+        /// Only records after this time (RFC 3339, or an age such as 2h or 7d)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only records before this time (RFC 3339, or an age such as 2h or 7d)
+        #[arg(long)]
+        until: Option<String>,
+        /// Show at most this many of the newest matches
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+        /// Print records as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TlsCommands {
+    /// Print the CA certificate for clients to trust (creates the CA if needed)
+    ExportCa {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Install a CA shared by the fleet in place of this host's own CA
+    ImportCa {
+        /// CA certificate (PEM)
+        #[arg(long)]
+        cert: PathBuf,
+        /// CA private key (PEM, PKCS#8)
+        #[arg(long)]
+        key: PathBuf,
+        /// Replace an existing CA
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show the CA and server certificate
+    Status,
+
+    /// Issue a new server certificate for this host now
+    Renew,
+
+    /// Issue a client certificate for a fleet peer (mutual TLS)
+    Issue {
+        /// Common name; map it to a role in the mTLS roles file
+        name: String,
+        /// Additional IP address for the certificate; repeat for several
+        #[arg(long = "ip")]
+        ips: Vec<std::net::IpAddr>,
+        /// Certificate chain output file
+        #[arg(long)]
+        cert: PathBuf,
+        /// Private key output file (mode 0600)
+        #[arg(long)]
+        key: PathBuf,
+        /// Validity in days
+        #[arg(long, default_value = "90")]
+        days: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokenCommands {
+    /// Create a token and print it once
+    Create {
+        /// Name describing the token's user
+        name: String,
+        /// Scope (read, apply, admin); repeat for several
+        #[arg(short, long = "scope", default_value = "read")]
+        scopes: Vec<String>,
+        /// Lifetime such as 90d, 12h or 30m (default: no expiry)
+        #[arg(long)]
+        expires: Option<String>,
+    },
+
+    /// List tokens (secrets are never shown)
+    List,
+
+    /// Revoke a token by id or name
+    Revoke {
+        /// Token id or name
+        token: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReplicaCommands {
+    /// Run a replica receiver
+    Serve {
+        /// Listen endpoint (tcp://host:port, tls://host:port or unix:/path)
+        #[arg(short, long, default_value = "tcp://0.0.0.0:9574")]
+        listen: String,
+        /// Directory received snapshots are placed in
+        #[arg(short, long, default_value = "/var/lib/op-dbus/replica")]
+        target: PathBuf,
+        /// Store verified streams as files instead of applying them
+        #[arg(long)]
+        store_streams: bool,
+        /// TLS certificate chain (PEM), required for tls:// endpoints
+        #[arg(long)]
+        tls_cert: Option<PathBuf>,
+        /// TLS private key (PEM), required for tls:// endpoints
+        #[arg(long)]
+        tls_key: Option<PathBuf>,
+    },
+
+    /// Send a read-only snapshot to a replica
+    Send {
+        /// Snapshot path (e.g. /var/lib/op-dbus/blockchain/snapshots/vectors-<hash>)
+        snapshot: PathBuf,
+        /// Replica endpoint (tcp://host:port, tls://host:port or unix:/path)
+        #[arg(long)]
+        to: String,
+        /// CA certificate (PEM) trusted for tls:// endpoints
+        #[arg(long)]
+        tls_ca: Option<PathBuf>,
+        /// Replica tracker file (default: <snapshot dir>/../replicas.json)
+        #[arg(long)]
+        tracker: Option<PathBuf>,
+    },
+
+    /// Show acknowledged snapshots per replica
+    Status {
+        /// Replica tracker file
+        #[arg(long, default_value = "/var/lib/op-dbus/blockchain/replicas.json")]
+        tracker: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -481,50 +688,12 @@ dhcp-authoritative
 #[tokio::main]
 async fn main() -> Result<()> {
     init_logging()?;
-    let args = Cli::parse();
+    let mut args = Cli::parse();
 
     let state_manager = Arc::new(state::StateManager::new());
 
-    // Register core plugins manually
-    let plugins = vec![
-        (
-            "net",
-            Arc::new(state::plugins::NetStatePlugin::new())
-                as Arc<dyn crate::state::plugin::StatePlugin>,
-        ),
-        (
-            "systemd",
-            Arc::new(state::plugins::SystemdStatePlugin::new()),
-        ),
-        ("login1", Arc::new(state::plugins::Login1Plugin::new())),
-        ("lxc", Arc::new(state::plugins::LxcPlugin::new())),
-        ("sessdecl", Arc::new(state::plugins::SessDeclPlugin::new())),
-        ("dns", Arc::new(state::plugins::DnsResolverPlugin::new())),
-        ("pcidecl", Arc::new(state::plugins::PciDeclPlugin::new())),
-        (
-            "packagekit",
-            Arc::new(state::plugins::PackageKitPlugin::new()),
-        ),
-        #[cfg(feature = "openflow")]
-        ("openflow", Arc::new(state::plugins::OpenFlowPlugin::new())),
-        #[cfg(feature = "openflow")]
-        (
-            "privacy",
-            Arc::new(state::plugins::PrivacyPlugin::new(Default::default())),
-        ),
-        #[cfg(feature = "openflow")]
-        (
-            "netmaker",
-            Arc::new(state::plugins::NetmakerPlugin::new(Default::default())),
-        ),
-        #[cfg(feature = "openflow")]
-        (
-            "privacy_router",
-            Arc::new(state::plugins::PrivacyRouterPlugin::new(Default::default())),
-        ),
-    ];
-
-    for (name, plugin) in plugins {
+    // Register core plugins
+    for (name, plugin) in state::plugins::builtin() {
         state_manager.register_plugin(plugin.clone()).await;
         // Also register as workflow node
         state_manager.register_plugin_as_workflow_node(name, plugin);
@@ -544,13 +713,18 @@ async fn main() -> Result<()> {
     }
 
     // Start org.opdbus on system D-Bus to accept ApplyState calls for net plugin
+    let (dbus_up, dbus_started) = tokio::sync::oneshot::channel();
     {
         let sm = Arc::clone(&state_manager);
         tokio::spawn(async move {
-            if let Err(e) = crate::state::dbus_server::start_system_bus(sm).await {
-                log::warn!("Failed to start org.opdbus service: {}", e);
-            } else {
-                log::info!("D-Bus service started: org.opdbus at /org/opdbus/state/net");
+            match state::dbus_server::start_system_bus(sm).await {
+                Ok(_connection) => {
+                    log::info!("D-Bus service started: org.opdbus at /org/opdbus/state");
+                    let _ = dbus_up.send(());
+                    // Keep the connection alive
+                    std::future::pending::<()>().await;
+                }
+                Err(e) => log::warn!("Failed to start org.opdbus service: {}", e),
             }
         });
     }
@@ -565,7 +739,98 @@ async fn main() -> Result<()> {
         });
     }
 
-    match args.command.unwrap_or(Commands::Run { oneshot: false }) {
+    let command = args
+        .command
+        .take()
+        .unwrap_or(Commands::Run { oneshot: false });
+    let audit_record = cli_audit_record(&command);
+    let result = run_command(command, args, state_manager, dbus_started).await;
+    if let Some(record) = audit_record {
+        record.with_result(&result).record();
+    }
+    result
+}
+
+/// Audit record for subcommands that change the system
+fn cli_audit_record(command: &Commands) -> Option<audit::AuditRecord> {
+    use serde_json::json;
+
+    let record = |operation: &str, params: serde_json::Value| {
+        audit::AuditRecord::new(audit::Frontend::Cli, operation)
+            .with_actor(audit::process_actor())
+            .with_params(&params)
+    };
+    let record = match command {
+        Commands::Apply {
+            state_file,
+            dry_run: false,
+            plugin,
+        } => {
+            let record = record("apply", json!({ "state_file": state_file }));
+            match plugin {
+                Some(plugin) => record.with_plugin(plugin),
+                None => record,
+            }
+        }
+        Commands::ApplyContainer {
+            container_id,
+            state_file,
+        } => record(
+            "apply_container",
+            json!({ "container_id": container_id, "state_file": state_file }),
+        )
+        .with_plugin("lxc"),
+        Commands::Container(cmd) => {
+            let (operation, container_id) = match cmd {
+                ContainerCommands::Create { container_id, .. } => {
+                    ("container_create", container_id)
+                }
+                ContainerCommands::Start { container_id } => ("container_start", container_id),
+                ContainerCommands::Stop { container_id } => ("container_stop", container_id),
+                ContainerCommands::Destroy { container_id } => ("container_destroy", container_id),
+                ContainerCommands::List { .. } | ContainerCommands::Show { .. } => return None,
+            };
+            record(operation, json!({ "container_id": container_id })).with_plugin("lxc")
+        }
+        Commands::Token(TokenCommands::Create {
+            name,
+            scopes,
+            expires,
+        }) => record(
+            "token_create",
+            json!({ "name": name, "scopes": scopes, "expires": expires }),
+        ),
+        Commands::Token(TokenCommands::Revoke { token }) => {
+            record("token_revoke", json!({ "id_or_name": token }))
+        }
+        Commands::Task(TaskCommands::Cancel { id }) => record("task_cancel", json!({ "id": id })),
+        Commands::Task(TaskCommands::Replay { id }) => record("task_replay", json!({ "id": id })),
+        Commands::Tls(TlsCommands::Renew) => record("tls_renew", serde_json::Value::Null),
+        Commands::Tls(TlsCommands::ImportCa { cert, force, .. }) => {
+            record("tls_import_ca", json!({ "cert": cert, "force": force }))
+        }
+        Commands::Tls(TlsCommands::Issue {
+            name, ips, days, ..
+        }) => record(
+            "tls_issue",
+            json!({ "name": name, "ips": ips, "days": days }),
+        ),
+        _ => return None,
+    };
+    Some(record)
+}
+
+/// Run a subcommand
+///
+/// `dbus_started` resolves once the org.opdbus service is up, and is
+/// dropped if it failed to start.
+async fn run_command(
+    command: Commands,
+    args: Cli,
+    state_manager: Arc<state::StateManager>,
+    dbus_started: tokio::sync::oneshot::Receiver<()>,
+) -> Result<()> {
+    match command {
         Commands::Run { oneshot } => {
             // Set up DHCP server if requested
             if args.enable_dhcp_server {
@@ -575,15 +840,42 @@ async fn main() -> Result<()> {
             let state_file = args
                 .state_file
                 .unwrap_or_else(|| PathBuf::from("/etc/op-dbus/state.json"));
-            if state_file.exists() {
-                apply_state_from_file(&state_manager, &state_file).await?;
-            }
-
             if oneshot {
+                if state_file.exists() {
+                    apply_state_from_file(&state_manager, &state_file).await?;
+                }
                 info!("Oneshot mode: exiting after apply");
                 return Ok(());
             }
 
+            // Ready for systemd once the D-Bus service is up; the apply can
+            // take longer than the start timeout and is reported as STATUS=
+            if dbus_started.await.is_err() {
+                log::warn!("Starting without the org.opdbus D-Bus service");
+            }
+            let readiness = Arc::new(
+                state::health::ReadinessChecker::new(state_manager.clone())
+                    .with_state_file(&state_file),
+            );
+            if let Ok(addr) = std::env::var("OPDBUS_METRICS_ADDR") {
+                let addr = addr.parse().context("Invalid OPDBUS_METRICS_ADDR")?;
+                start_metrics(addr, readiness.clone()).await;
+            }
+            state::health::spawn_watchdog(readiness.clone());
+
+            if state_file.exists() {
+                readiness
+                    .set_activity(Some(format!(
+                        "Applying desired state from {}",
+                        state_file.display()
+                    )))
+                    .await;
+                let applied = apply_state_from_file(&state_manager, &state_file).await;
+                readiness.set_activity(None).await;
+                applied?;
+                spawn_drift_check(state_manager.clone(), state_file);
+            }
+
             info!("Daemon running, press Ctrl+C to stop");
             tokio::signal::ctrl_c().await?;
             Ok(())
@@ -993,12 +1285,16 @@ async fn main() -> Result<()> {
         #[cfg(any(feature = "mcp", feature = "web"))]
         Commands::Index(cmd) => handle_index_command(cmd).await,
 
+        #[cfg(feature = "web")]
         Commands::Serve { bind, port } => {
             info!("Starting web UI server on {}:{}", bind, port);
 
+            let defaults = crate::webui::WebConfig::default();
             let config = crate::webui::WebConfig {
                 bind_addr: bind,
                 port,
+                state_file: args.state_file.unwrap_or(defaults.state_file.clone()),
+                ..defaults
             };
 
             crate::webui::start_web_server(state_manager, config).await?;
@@ -1006,6 +1302,441 @@ async fn main() -> Result<()> {
         }
 
         Commands::Image(cmd) => handle_image_command(cmd).await,
+
+        Commands::Replica(cmd) => handle_replica_command(cmd).await,
+
+        Commands::Token(cmd) => handle_token_command(cmd),
+
+        Commands::Tls(cmd) => handle_tls_command(cmd),
+
+        Commands::Audit(cmd) => handle_audit_command(cmd),
+
+        Commands::Task(cmd) => handle_task_command(cmd),
+    }
+}
+
+fn handle_task_command(cmd: TaskCommands) -> Result<()> {
+    use op_dbus::task_queue::{TaskQuery, TaskStore};
+
+    let store = TaskStore::from_env()?;
+
+    match cmd {
+        TaskCommands::List {
+            agent,
+            status,
+            limit,
+            json,
+        } => {
+            let query = TaskQuery {
+                agent_id: agent,
+                status: status.as_deref().map(str::parse).transpose()?,
+                limit: Some(limit),
+            };
+            let tasks = store.list(&query)?;
+            if json {
+                for task in &tasks {
+                    println!("{}", serde_json::to_string(task)?);
+                }
+                return Ok(());
+            }
+            if tasks.is_empty() {
+                println!("No matching tasks in {}", store.path().display());
+                return Ok(());
+            }
+            for task in &tasks {
+                println!(
+                    "{}  {}  {:<9}  {:<20}  {}",
+                    task.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    task.id,
+                    task.status.as_str(),
+                    task.task_type,
+                    task.agent_id
+                );
+            }
+            Ok(())
+        }
+
+        TaskCommands::Show { id } => {
+            let task = store
+                .get(&id)?
+                .ok_or_else(|| anyhow::anyhow!("Unknown task {}", id))?;
+            println!("{}", serde_json::to_string_pretty(&task)?);
+            Ok(())
+        }
+
+        TaskCommands::Cancel { id } => {
+            if !store.cancel(&id)? {
+                anyhow::bail!("Task {} is unknown or already finished", id);
+            }
+            println!("Cancelled task {}", id);
+            Ok(())
+        }
+
+        TaskCommands::Replay { id } => {
+            let task = store.replay(&id)?;
+            println!("Queued task {} replaying {}", task.id, id);
+            Ok(())
+        }
+
+        TaskCommands::Purge => {
+            let purged = store.purge_expired()?;
+            println!(
+                "Purged {} tasks finished more than {}h ago",
+                purged,
+                store.retention().num_hours()
+            );
+            Ok(())
+        }
+    }
+}
+
+fn handle_audit_command(cmd: AuditCommands) -> Result<()> {
+    use op_dbus::http_tls_server::tokens::parse_ttl;
+
+    match cmd {
+        AuditCommands::Search {
+            actor,
+            plugin,
+            frontend,
+            operation,
+            outcome,
+            since,
+            until,
+            limit,
+            json,
+        } => {
+            // Times are RFC 3339 or an age such as 2h or 7d
+            let parse_time = |value: String| -> Result<chrono::DateTime<chrono::Utc>> {
+                match chrono::DateTime::parse_from_rfc3339(&value) {
+                    Ok(time) => Ok(time.with_timezone(&chrono::Utc)),
+                    Err(_) => Ok(chrono::Utc::now() - parse_ttl(&value)?),
+                }
+            };
+            let query = audit::AuditQuery {
+                actor,
+                plugin,
+                frontend: frontend.as_deref().map(str::parse).transpose()?,
+                operation,
+                outcome: outcome.as_deref().map(str::parse).transpose()?,
+                since: since.map(parse_time).transpose()?,
+                until: until.map(parse_time).transpose()?,
+                limit: Some(limit),
+            };
+
+            let log = audit::AuditLog::from_env();
+            let records = log.search(&query)?;
+            if json {
+                for record in &records {
+                    println!("{}", serde_json::to_string(record)?);
+                }
+                return Ok(());
+            }
+            if records.is_empty() {
+                println!("No matching records in {}", log.path().display());
+                return Ok(());
+            }
+            for record in &records {
+                println!(
+                    "{}  {}",
+                    record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    record.summary()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_tls_command(cmd: TlsCommands) -> Result<()> {
+    use op_dbus::http_tls_server::local_ca::{certificate_validity, read_pem_certs};
+    use op_dbus::http_tls_server::{HostNames, LocalCa};
+
+    let dir = LocalCa::default_dir();
+
+    match cmd {
+        TlsCommands::ExportCa { out } => {
+            let ca = LocalCa::open_or_create(&dir)?;
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, ca.ca_pem())?;
+                    println!("Wrote CA certificate to {}", path.display());
+                }
+                None => print!("{}", ca.ca_pem()),
+            }
+            Ok(())
+        }
+
+        TlsCommands::ImportCa { cert, key, force } => {
+            if LocalCa::exists(&dir) && !force {
+                anyhow::bail!(
+                    "{} already holds a CA; pass --force to replace it",
+                    dir.display()
+                );
+            }
+            let ca = LocalCa::import(
+                &dir,
+                &std::fs::read_to_string(&cert)?,
+                &std::fs::read_to_string(&key)?,
+            )?;
+            ca.ensure_server_cert(&HostNames::detect())?;
+            println!("Imported {} into {}", cert.display(), dir.display());
+            println!("Restart running servers to use the new server certificate.");
+            Ok(())
+        }
+
+        TlsCommands::Status => {
+            let ca = LocalCa::open_or_create(&dir)?;
+            println!("Directory: {}", ca.dir().display());
+            for (label, path) in [("CA", ca.ca_cert_path()), ("Server", ca.server_cert_path())] {
+                let Some(der) = read_pem_certs(&path)
+                    .ok()
+                    .and_then(|c| c.into_iter().next())
+                else {
+                    println!("{:<8} not issued", label);
+                    continue;
+                };
+                let (not_before, not_after) = certificate_validity(&der)?;
+                println!(
+                    "{:<8} valid {} to {}",
+                    label,
+                    not_before.format("%Y-%m-%d"),
+                    not_after.format("%Y-%m-%d")
+                );
+            }
+            let names = HostNames::detect();
+            println!("Names:     {}", names.dns.join(", "));
+            let ips: Vec<String> = names.ips.iter().map(|ip| ip.to_string()).collect();
+            println!("Addresses: {}", ips.join(", "));
+            Ok(())
+        }
+
+        TlsCommands::Renew => {
+            let ca = LocalCa::open_or_create(&dir)?;
+            std::fs::remove_file(ca.server_cert_path()).ok();
+            ca.ensure_server_cert(&HostNames::detect())?;
+            println!("Issued {}", ca.server_cert_path().display());
+            println!("Restart running servers to use it.");
+            Ok(())
+        }
+
+        TlsCommands::Issue {
+            name,
+            ips,
+            cert,
+            key,
+            days,
+        } => {
+            use std::io::Write;
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let ca = LocalCa::open_or_create(&dir)?;
+            let mut names = HostNames::default();
+            names.add_dns(&name);
+            for ip in ips {
+                names.add_ip(ip);
+            }
+            let (chain, key_pem) = ca.issue(&names, chrono::Duration::days(days))?;
+            std::fs::write(&cert, chain)?;
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&key)?
+                .write_all(key_pem.as_bytes())?;
+            println!("Issued {} for {}", cert.display(), name);
+            println!("Key written to {}", key.display());
+            Ok(())
+        }
+    }
+}
+
+fn handle_token_command(cmd: TokenCommands) -> Result<()> {
+    use op_dbus::http_tls_server::tokens::{parse_ttl, Scope, TokenStore};
+
+    let store = TokenStore::from_env();
+
+    match cmd {
+        TokenCommands::Create {
+            name,
+            scopes,
+            expires,
+        } => {
+            let scopes = scopes
+                .iter()
+                .map(|scope| scope.parse())
+                .collect::<Result<Vec<Scope>>>()?;
+            let ttl = expires.as_deref().map(parse_ttl).transpose()?;
+            let (record, token) = store.create(&name, scopes, ttl)?;
+
+            println!(
+                "Created token {} ({}) in {}",
+                record.id,
+                record.name,
+                store.path().display()
+            );
+            if let Some(expires_at) = record.expires_at {
+                println!("Expires: {}", expires_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            }
+            println!("\n{}\n", token);
+            println!("Store it now; it cannot be shown again.");
+            Ok(())
+        }
+
+        TokenCommands::List => {
+            let records = store.list()?;
+            if records.is_empty() {
+                println!("No tokens in {}", store.path().display());
+                return Ok(());
+            }
+
+            let now = chrono::Utc::now();
+            println!(
+                "{:<14} {:<20} {:<18} {:<20} EXPIRES",
+                "ID", "NAME", "SCOPES", "CREATED"
+            );
+            for record in records {
+                let scopes: Vec<String> = record.scopes.iter().map(|s| s.to_string()).collect();
+                let expires = match record.expires_at {
+                    Some(at) if record.is_expired(now) => {
+                        format!("expired {}", at.format("%Y-%m-%d"))
+                    }
+                    Some(at) => at.format("%Y-%m-%d %H:%M").to_string(),
+                    None => "never".to_string(),
+                };
+                println!(
+                    "{:<14} {:<20} {:<18} {:<20} {}",
+                    record.id,
+                    record.name,
+                    scopes.join(","),
+                    record.created_at.format("%Y-%m-%d %H:%M"),
+                    expires
+                );
+            }
+            Ok(())
+        }
+
+        TokenCommands::Revoke { token } => {
+            match store.revoke(&token)? {
+                0 => anyhow::bail!("No token with id or name '{}'", token),
+                n => println!("Revoked {} token(s)", n),
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn handle_replica_command(cmd: ReplicaCommands) -> Result<()> {
+    use crate::replication::{
+        transport, BtrfsReceiveSink, DirectorySink, ReplicaEndpoint, ReplicaListener,
+        ReplicaReceiver, ReplicaTracker, ReplicationSender, StreamSink, TarReceiveSink,
+    };
+    use crate::storage::{self, BackendKind};
+
+    match cmd {
+        ReplicaCommands::Serve {
+            listen,
+            target,
+            store_streams,
+            tls_cert,
+            tls_key,
+        } => {
+            let endpoint: ReplicaEndpoint = listen.parse()?;
+            let acceptor = match (tls_cert, tls_key) {
+                (Some(cert), Some(key)) => Some(transport::tls_acceptor(&cert, &key)?),
+                (None, None) => None,
+                _ => anyhow::bail!("--tls-cert and --tls-key must be given together"),
+            };
+
+            let sink: Arc<dyn StreamSink> = if store_streams {
+                Arc::new(DirectorySink::new(&target))
+            } else {
+                match storage::detect(&target).kind() {
+                    BackendKind::Btrfs => Arc::new(BtrfsReceiveSink::new(&target)),
+                    BackendKind::Directory => Arc::new(TarReceiveSink::new(&target)),
+                }
+            };
+            let receiver = Arc::new(ReplicaReceiver::new(
+                target.join(".spool"),
+                sink,
+                crate::replication::load_token()?,
+            ));
+
+            let listener = ReplicaListener::bind(&endpoint, acceptor).await?;
+            info!(
+                "Replica receiver listening on {} (target: {})",
+                endpoint,
+                target.display()
+            );
+
+            tokio::select! {
+                result = receiver.serve(listener) => result,
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Replica receiver shutting down");
+                    Ok(())
+                }
+            }
+        }
+
+        ReplicaCommands::Send {
+            snapshot,
+            to,
+            tls_ca,
+            tracker,
+        } => {
+            let endpoint: ReplicaEndpoint = to.parse()?;
+            let tracker_path = tracker.unwrap_or_else(|| {
+                snapshot
+                    .parent()
+                    .and_then(|dir| dir.parent())
+                    .unwrap_or_else(|| std::path::Path::new("/var/lib/op-dbus/blockchain"))
+                    .join("replicas.json")
+            });
+            let tracker = Arc::new(ReplicaTracker::load(&tracker_path)?);
+
+            let source = storage::detect(&snapshot).stream_source();
+            let mut sender = ReplicationSender::from_env(source, tracker)?;
+            if let Some(ca) = tls_ca {
+                sender = sender.with_tls(transport::tls_connector(&ca)?);
+            }
+
+            let report = sender.replicate(&endpoint, &snapshot).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            Ok(())
+        }
+
+        ReplicaCommands::Status { tracker } => {
+            if !tracker.exists() {
+                println!("No replication state found at {}", tracker.display());
+                return Ok(());
+            }
+
+            let tracker = ReplicaTracker::load(&tracker)?;
+            let records = tracker.records();
+            if records.is_empty() {
+                println!("No replicas recorded");
+                return Ok(());
+            }
+
+            println!("=== Replicas ({}) ===\n", records.len());
+            for (replica, record) in records {
+                println!("{}", replica);
+                println!("  Acknowledged: {}", record.acked.len());
+                if let Some(last) = record.acked.last() {
+                    let acked_at = chrono::DateTime::from_timestamp(last.acked_at, 0)
+                        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "invalid".to_string());
+                    println!("  Latest:       {} ({})", last.snapshot, acked_at);
+                    println!("  Parent:       {}", last.parent.as_deref().unwrap_or("-"));
+                    println!("  SHA-256:      {}", last.sha256);
+                }
+                if let Some(error) = &record.last_error {
+                    println!("  Last error:   {}", error);
+                }
+                println!();
+            }
+            Ok(())
+        }
     }
 }
 
@@ -1152,6 +1883,63 @@ async fn handle_image_command(cmd: ImageCommands) -> Result<()> {
     }
 }
 
+/// Export Prometheus metrics and the readiness probe on their own listener
+async fn start_metrics(
+    addr: std::net::SocketAddr,
+    readiness: Arc<state::health::ReadinessChecker>,
+) {
+    let cache_dir = PathBuf::from(
+        std::env::var("OPDBUS_CACHE_DIR").unwrap_or_else(|_| "/var/lib/op-dbus/@cache".to_string()),
+    );
+    if cache_dir.exists() {
+        match op_dbus::cache::BtrfsCache::new(cache_dir).await {
+            Ok(cache) => state::metrics::register_cache(Arc::new(cache)),
+            Err(e) => log::warn!("Cache metrics unavailable: {}", e),
+        }
+    }
+
+    let routes = axum::Router::new().route(
+        "/health/ready",
+        axum::routing::get(move || {
+            let readiness = readiness.clone();
+            async move { readiness.check().await }
+        }),
+    );
+    tokio::spawn(async move {
+        if let Err(e) = op_dbus::http_tls_server::metrics::serve_metrics(addr, routes).await {
+            log::error!("Metrics server failed: {}", e);
+        }
+    });
+}
+
+/// Periodically diff the desired state so drift shows up in the metrics
+///
+/// Runs every `OPDBUS_DRIFT_INTERVAL` seconds (default 300).
+fn spawn_drift_check(state_manager: Arc<state::StateManager>, state_file: PathBuf) {
+    let interval = std::env::var("OPDBUS_DRIFT_INTERVAL")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval));
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let diffs = match state_manager.load_desired_state(&state_file).await {
+                Ok(desired) => state_manager.show_diff(desired).await,
+                Err(e) => Err(e),
+            };
+            match diffs {
+                Ok(diffs) if !diffs.is_empty() => {
+                    log::warn!("Drift detected in {} plugin(s)", diffs.len())
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Drift check failed: {}", e),
+            }
+        }
+    });
+}
+
 async fn handle_cache_command(cmd: CacheCommands) -> Result<()> {
     let cache_dir = PathBuf::from(
         std::env::var("OPDBUS_CACHE_DIR").unwrap_or_else(|_| "/var/lib/op-dbus/@cache".to_string()),
@@ -1309,10 +2097,10 @@ async fn handle_index_command(cmd: IndexCommands) -> Result<()> {
             let snapshot_mgr =
                 SnapshotManager::with_policy(&snapshots_dir, RetentionPolicy::Rolling { keep: 3 });
 
-            match snapshot_mgr.create_snapshot(&output, None) {
+            match snapshot_mgr.create_snapshot(&output, None).await {
                 Ok(snapshot_path) => {
                     println!("   Snapshot: {}", snapshot_path.display());
-                    let snapshots = snapshot_mgr.list_snapshots()?;
+                    let snapshots = snapshot_mgr.list_snapshots().await?;
                     println!("   Total snapshots: {} (keeping last 3)", snapshots.len());
                 }
                 Err(e) => {
@@ -1333,7 +2121,7 @@ async fn handle_index_command(cmd: IndexCommands) -> Result<()> {
             println!("   (Incremental update - only scans changed services)");
             println!();
 
-            let indexer = DbusIndexer::new(&index).await?;
+            let _indexer = DbusIndexer::new(&index).await?;
             // TODO: Implement incremental update
             println!("⚠️  Incremental update not yet implemented - use 'build' for now");
 
@@ -1451,10 +2239,10 @@ async fn handle_index_command(cmd: IndexCommands) -> Result<()> {
             Ok(())
         }
 
-        IndexCommands::Snapshots { index } => {
+        IndexCommands::Snapshots { index: _ } => {
             let snapshots_dir = PathBuf::from("/var/lib/op-dbus/@snapshots/dbus-index");
             let snapshot_mgr = SnapshotManager::new(&snapshots_dir);
-            let snapshots = snapshot_mgr.list_snapshots()?;
+            let snapshots = snapshot_mgr.list_snapshots().await?;
 
             if snapshots.is_empty() {
                 println!("No snapshots found");
@@ -1493,7 +2281,9 @@ async fn handle_index_command(cmd: IndexCommands) -> Result<()> {
             let snapshot_mgr =
                 SnapshotManager::with_policy(&snapshots_dir, RetentionPolicy::Rolling { keep: 3 });
 
-            let snapshot_path = snapshot_mgr.create_snapshot(&index, name.as_deref())?;
+            let snapshot_path = snapshot_mgr
+                .create_snapshot(&index, name.as_deref())
+                .await?;
             println!("   Created: {}", snapshot_path.display());
 
             // Apply tag if provided
@@ -1503,18 +2293,18 @@ async fn handle_index_command(cmd: IndexCommands) -> Result<()> {
                 println!("   Tagged as: {}", tag_value);
             }
 
-            let snapshots = snapshot_mgr.list_snapshots()?;
+            let snapshots = snapshot_mgr.list_snapshots().await?;
             println!("   Total snapshots: {}", snapshots.len());
 
             Ok(())
         }
 
-        IndexCommands::Cleanup { index, force } => {
+        IndexCommands::Cleanup { index: _, force } => {
             let snapshots_dir = PathBuf::from("/var/lib/op-dbus/@snapshots/dbus-index");
             let snapshot_mgr =
                 SnapshotManager::with_policy(&snapshots_dir, RetentionPolicy::Rolling { keep: 3 });
 
-            let snapshots = snapshot_mgr.list_snapshots()?;
+            let snapshots = snapshot_mgr.list_snapshots().await?;
 
             if snapshots.len() <= 3 {
                 println!(
@@ -1543,9 +2333,9 @@ async fn handle_index_command(cmd: IndexCommands) -> Result<()> {
                 }
             }
 
-            snapshot_mgr.apply_retention_policy()?;
+            snapshot_mgr.apply_retention_policy().await?;
 
-            let remaining = snapshot_mgr.list_snapshots()?;
+            let remaining = snapshot_mgr.list_snapshots().await?;
             println!(
                 "✅ Cleanup complete - {} snapshot(s) remaining",
                 remaining.len()
@@ -10,7 +10,8 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot, RwLock};
 
 /// Agent specification
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,10 +42,18 @@ pub struct AgentSpec {
     /// Capabilities this agent provides
     pub capabilities: Vec<String>,
 
+    /// Commands an executing agent may run; empty keeps the agent's own list
+    #[serde(default)]
+    pub allowed_commands: Vec<String>,
+
     /// Whether this agent requires root privileges
     #[serde(default)]
     pub requires_root: bool,
 
+    /// Resource limits, for agents run as systemd units
+    #[serde(default)]
+    pub limits: AgentLimits,
+
     /// Maximum number of instances
     #[serde(default = "default_max_instances")]
     pub max_instances: usize,
@@ -57,6 +66,21 @@ pub struct AgentSpec {
     pub health_check: Option<HealthCheck>,
 }
 
+/// Resource limits of an agent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentLimits {
+    /// Memory limit in bytes or with a K/M/G/T suffix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<String>,
+
+    /// CPU time limit as a percentage of one CPU ("50%")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks_max: Option<u64>,
+}
+
 fn default_max_instances() -> usize {
     5
 }
@@ -111,6 +135,13 @@ pub enum AgentStatus {
     Failed { reason: String },
 }
 
+impl AgentStatus {
+    /// Whether the instance has a process, healthy or not
+    pub fn is_live(&self) -> bool {
+        !matches!(self, AgentStatus::Stopped | AgentStatus::Failed { .. })
+    }
+}
+
 /// Agent factory for creating agents
 #[async_trait]
 pub trait AgentFactory: Send + Sync {
@@ -119,12 +150,61 @@ pub trait AgentFactory: Send + Sync {
 
     /// Check if an agent type is supported
     fn supports(&self, agent_type: &str) -> bool;
+
+    /// Agents from this factory that are already running, such as those
+    /// started before the orchestrator restarted
+    async fn running_agents(
+        &self,
+        _specs: &HashMap<String, AgentSpec>,
+    ) -> Result<Vec<AgentHandle>> {
+        Ok(Vec::new())
+    }
+}
+
+/// How an agent instance ended
+#[derive(Debug, Clone)]
+pub struct AgentExit {
+    pub success: bool,
+    /// What happened, for logs and event listeners
+    pub reason: String,
+}
+
+/// A running agent, whatever runs it
+#[async_trait]
+pub trait AgentProcess: Send {
+    /// Process ID of the agent, if known
+    fn id(&self) -> Option<u32>;
+
+    /// Wait for the agent to exit; must be cancel safe
+    async fn wait(&mut self) -> Result<AgentExit>;
+
+    /// Stop the agent
+    async fn kill(&mut self) -> Result<()>;
+}
+
+#[async_trait]
+impl AgentProcess for tokio::process::Child {
+    fn id(&self) -> Option<u32> {
+        tokio::process::Child::id(self)
+    }
+
+    async fn wait(&mut self) -> Result<AgentExit> {
+        let status = tokio::process::Child::wait(self).await?;
+        Ok(AgentExit {
+            success: status.success(),
+            reason: format!("exited with {}", status),
+        })
+    }
+
+    async fn kill(&mut self) -> Result<()> {
+        Ok(tokio::process::Child::kill(self).await?)
+    }
 }
 
 /// Handle to a running agent
 pub struct AgentHandle {
     pub id: String,
-    pub process: tokio::process::Child,
+    pub process: Box<dyn AgentProcess>,
     pub spec: AgentSpec,
 }
 
@@ -142,6 +222,9 @@ impl AgentFactory for ProcessAgentFactory {
         // Add instance ID as environment variable
         cmd.env("AGENT_ID", instance_id);
         cmd.env("AGENT_TYPE", &spec.agent_type);
+        if !spec.allowed_commands.is_empty() {
+            cmd.env("AGENT_ALLOWED_COMMANDS", spec.allowed_commands.join(","));
+        }
 
         // Add custom environment variables
         for (key, value) in &spec.env {
@@ -160,7 +243,7 @@ impl AgentFactory for ProcessAgentFactory {
 
         Ok(AgentHandle {
             id: instance_id.to_string(),
-            process,
+            process: Box::new(process),
             spec: spec.clone(),
         })
     }
@@ -170,6 +253,43 @@ impl AgentFactory for ProcessAgentFactory {
     }
 }
 
+/// Lifecycle event of an agent instance, sent by its supervisor
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// The instance exited or could not be started again
+    Died { instance_id: String, reason: String },
+    /// The instance was started again under its restart policy
+    Restarted {
+        instance_id: String,
+        agent_type: String,
+        restart_count: u32,
+    },
+    /// The health check passed or failed past its threshold
+    HealthChanged { instance_id: String, healthy: bool },
+}
+
+/// First delay before restarting an agent, doubled for every restart
+const DEFAULT_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Well-known names of agents are `org.dbusmcp.Agent.<Kind>.<id>`
+const AGENT_BUS_PREFIX: &str = "org.dbusmcp.Agent.";
+
+/// Supervisor task of a running instance
+struct Supervisor {
+    stop: oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// What a supervisor shares with the registry
+#[derive(Clone)]
+struct SupervisorContext {
+    instances: Arc<RwLock<HashMap<String, AgentInstance>>>,
+    factories: Arc<RwLock<Vec<Box<dyn AgentFactory>>>>,
+    events: broadcast::Sender<AgentEvent>,
+    restart_backoff: Duration,
+}
+
 /// Agent registry for managing agent specifications and instances
 pub struct AgentRegistry {
     /// Registered agent specifications
@@ -181,8 +301,13 @@ pub struct AgentRegistry {
     /// Agent factories
     factories: Arc<RwLock<Vec<Box<dyn AgentFactory>>>>,
 
-    /// Agent handles
-    handles: Arc<RwLock<HashMap<String, AgentHandle>>>,
+    /// Supervisors owning the agent handles
+    supervisors: Arc<RwLock<HashMap<String, Supervisor>>>,
+
+    /// Lifecycle events from the supervisors
+    events: broadcast::Sender<AgentEvent>,
+
+    restart_backoff: Duration,
 }
 
 impl Default for AgentRegistry {
@@ -193,24 +318,29 @@ impl Default for AgentRegistry {
 
 impl AgentRegistry {
     pub fn new() -> Self {
-        let registry = Self {
+        // Default factory spawning processes, tried after custom ones
+        let default_factory: Box<dyn AgentFactory> = Box::new(ProcessAgentFactory);
+        let (events, _) = broadcast::channel(100);
+
+        Self {
             specs: Arc::new(RwLock::new(HashMap::new())),
             instances: Arc::new(RwLock::new(HashMap::new())),
-            factories: Arc::new(RwLock::new(Vec::new())),
-            handles: Arc::new(RwLock::new(HashMap::new())),
-        };
-
-        // Register default factory
-        let default_factory = Box::new(ProcessAgentFactory);
+            factories: Arc::new(RwLock::new(vec![default_factory])),
+            supervisors: Arc::new(RwLock::new(HashMap::new())),
+            events,
+            restart_backoff: DEFAULT_RESTART_BACKOFF,
+        }
+    }
 
-        // We need to do this in a blocking context since new() is not async
-        let factories = registry.factories.clone();
-        tokio::spawn(async move {
-            let mut factories = factories.write().await;
-            factories.push(default_factory);
-        });
+    /// Set the first delay before restarting an agent
+    pub fn with_restart_backoff(mut self, backoff: Duration) -> Self {
+        self.restart_backoff = backoff;
+        self
+    }
 
-        registry
+    /// Receive lifecycle events of all instances
+    pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
+        self.events.subscribe()
     }
 
     /// Register an agent specification
@@ -263,14 +393,14 @@ impl AgentRegistry {
         Ok(())
     }
 
-    /// Register a custom agent factory
+    /// Register a custom agent factory, tried before those registered earlier
     pub async fn register_factory(&self, factory: Box<dyn AgentFactory>) {
         let mut factories = self.factories.write().await;
-        factories.push(factory);
+        factories.insert(0, factory);
     }
 
     /// Spawn an agent instance
-    pub async fn spawn_agent(&self, agent_type: &str, config: Option<Value>) -> Result<String> {
+    pub async fn spawn_agent(&self, agent_type: &str, _config: Option<Value>) -> Result<String> {
         // Get the specification
         let specs = self.specs.read().await;
         let spec = specs
@@ -282,10 +412,7 @@ impl AgentRegistry {
         let instances = self.instances.read().await;
         let current_count = instances
             .values()
-            .filter(|i| {
-                i.agent_type == agent_type
-                    && matches!(i.status, AgentStatus::Running | AgentStatus::Healthy)
-            })
+            .filter(|i| i.agent_type == agent_type && i.status.is_live())
             .count();
 
         if current_count >= spec.max_instances {
@@ -309,50 +436,73 @@ impl AgentRegistry {
 
         // Create the agent
         let handle = factory.create_agent(&spec, &instance_id).await?;
-        let pid = handle.process.id();
+        drop(factories);
 
-        // Store the handle
-        let mut handles = self.handles.write().await;
-        handles.insert(instance_id.clone(), handle);
+        self.start_supervisor(handle).await;
+        Ok(instance_id)
+    }
+
+    /// Supervise the agents the factories find already running
+    ///
+    /// Returns the adopted instance IDs.
+    pub async fn adopt_running_agents(&self) -> Result<Vec<String>> {
+        let specs = self.specs.read().await.clone();
+        let mut running = Vec::new();
+        for factory in self.factories.read().await.iter() {
+            match factory.running_agents(&specs).await {
+                Ok(handles) => running.extend(handles),
+                Err(e) => log::warn!("Failed to list running agents: {}", e),
+            }
+        }
 
-        // Create instance record
+        let mut adopted = Vec::new();
+        for handle in running {
+            if self.instances.read().await.contains_key(&handle.id) {
+                continue;
+            }
+            adopted.push(handle.id.clone());
+            self.start_supervisor(handle).await;
+        }
+        Ok(adopted)
+    }
+
+    /// Record a new instance and hand its handle to a supervisor
+    async fn start_supervisor(&self, handle: AgentHandle) {
+        let instance_id = handle.id.clone();
         let instance = AgentInstance {
             id: instance_id.clone(),
-            agent_type: agent_type.to_string(),
-            pid,
+            agent_type: handle.spec.agent_type.clone(),
+            pid: handle.process.id(),
             status: AgentStatus::Starting,
             started_at: chrono::Utc::now(),
             last_health_check: None,
             restart_count: 0,
         };
-
-        // Store instance
         let mut instances = self.instances.write().await;
         instances.insert(instance_id.clone(), instance);
+        drop(instances);
 
-        // TODO: Start health check task if configured
-
-        Ok(instance_id)
+        // The supervisor owns the handle from here on
+        let context = SupervisorContext {
+            instances: self.instances.clone(),
+            factories: self.factories.clone(),
+            events: self.events.clone(),
+            restart_backoff: self.restart_backoff,
+        };
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(supervise(context, handle, stopped));
+        let mut supervisors = self.supervisors.write().await;
+        supervisors.insert(instance_id, Supervisor { stop, task });
     }
 
     /// Kill an agent instance
     pub async fn kill_agent(&self, instance_id: &str) -> Result<()> {
-        let mut handles = self.handles.write().await;
-
-        if let Some(mut handle) = handles.remove(instance_id) {
-            // Try graceful shutdown first
-            handle
-                .process
-                .kill()
-                .await
-                .context("Failed to kill agent process")?;
-
-            // Update instance status
-            let mut instances = self.instances.write().await;
-            if let Some(instance) = instances.get_mut(instance_id) {
-                instance.status = AgentStatus::Stopped;
-            }
+        let supervisor = self.supervisors.write().await.remove(instance_id);
 
+        if let Some(supervisor) = supervisor {
+            // The supervisor kills the process and marks the instance stopped
+            let _ = supervisor.stop.send(());
+            supervisor.task.await.context("Agent supervisor failed")?;
             Ok(())
         } else {
             Err(anyhow::anyhow!(
@@ -390,19 +540,281 @@ impl AgentRegistry {
     }
 }
 
+/// Watch an instance until it is stopped or gives up: reap its exit, apply
+/// the restart policy with backoff and run the health check
+async fn supervise(
+    context: SupervisorContext,
+    mut handle: AgentHandle,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let id = handle.id.clone();
+    let mut restart_count = 0;
+    context.set_status(&id, AgentStatus::Running, |_| {}).await;
+
+    loop {
+        let mut health = handle.spec.health_check.clone().map(HealthMonitor::new);
+        let interval = health.as_ref().map(HealthMonitor::interval);
+        let exit = loop {
+            let next_check = async {
+                match interval {
+                    Some(interval) => tokio::time::sleep(interval).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                status = handle.process.wait() => break status,
+                _ = &mut stop => {
+                    let _ = handle.process.kill().await;
+                    context.set_status(&id, AgentStatus::Stopped, |_| {}).await;
+                    return;
+                }
+                _ = next_check => {
+                    let monitor = health.as_mut().expect("checks run only with a monitor");
+                    let pid = handle.process.id();
+                    if let Some(healthy) = monitor.check(pid).await {
+                        let status = if healthy {
+                            AgentStatus::Healthy
+                        } else {
+                            AgentStatus::Unhealthy
+                        };
+                        let _ = context.events.send(AgentEvent::HealthChanged {
+                            instance_id: id.clone(),
+                            healthy,
+                        });
+                        context.set_status(&id, status, |_| {}).await;
+                    }
+                    context
+                        .set_status_with(&id, |instance| {
+                            instance.last_health_check = Some(chrono::Utc::now())
+                        })
+                        .await;
+                }
+            }
+        };
+
+        let (reason, failed) = match exit {
+            Ok(exit) => (exit.reason, !exit.success),
+            Err(e) => (format!("could not be waited for: {}", e), true),
+        };
+        let _ = context.events.send(AgentEvent::Died {
+            instance_id: id.clone(),
+            reason: reason.clone(),
+        });
+
+        let restart = match &handle.spec.restart_policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure { max_retries } => failed && restart_count < *max_retries,
+        };
+        if !restart {
+            let status = if failed {
+                AgentStatus::Failed { reason }
+            } else {
+                AgentStatus::Stopped
+            };
+            context.set_status(&id, status, |_| {}).await;
+            return;
+        }
+
+        // Back off before restarting, unless stopped meanwhile
+        let backoff = restart_backoff(context.restart_backoff, restart_count);
+        context.set_status(&id, AgentStatus::Starting, |_| {}).await;
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = &mut stop => {
+                context.set_status(&id, AgentStatus::Stopped, |_| {}).await;
+                return;
+            }
+        }
+
+        restart_count += 1;
+        handle = match context.create_agent(&handle.spec, &id).await {
+            Ok(handle) => handle,
+            Err(e) => {
+                let reason = format!("restart failed: {}", e);
+                let _ = context.events.send(AgentEvent::Died {
+                    instance_id: id.clone(),
+                    reason: reason.clone(),
+                });
+                context
+                    .set_status(&id, AgentStatus::Failed { reason }, |_| {})
+                    .await;
+                return;
+            }
+        };
+        let pid = handle.process.id();
+        context
+            .set_status(&id, AgentStatus::Running, |instance| {
+                instance.pid = pid;
+                instance.restart_count = restart_count;
+                instance.started_at = chrono::Utc::now();
+                instance.last_health_check = None;
+            })
+            .await;
+        let _ = context.events.send(AgentEvent::Restarted {
+            instance_id: id.clone(),
+            agent_type: handle.spec.agent_type.clone(),
+            restart_count,
+        });
+    }
+}
+
+/// Delay before the restart after `restarts` earlier ones
+fn restart_backoff(base: Duration, restarts: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(restarts))
+        .min(MAX_RESTART_BACKOFF)
+}
+
+impl SupervisorContext {
+    async fn set_status(
+        &self,
+        instance_id: &str,
+        status: AgentStatus,
+        update: impl FnOnce(&mut AgentInstance),
+    ) {
+        self.set_status_with(instance_id, |instance| {
+            instance.status = status;
+            update(instance);
+        })
+        .await;
+    }
+
+    async fn set_status_with(&self, instance_id: &str, update: impl FnOnce(&mut AgentInstance)) {
+        if let Some(instance) = self.instances.write().await.get_mut(instance_id) {
+            update(instance);
+        }
+    }
+
+    async fn create_agent(&self, spec: &AgentSpec, instance_id: &str) -> Result<AgentHandle> {
+        let factories = self.factories.read().await;
+        let factory = factories
+            .iter()
+            .find(|f| f.supports(&spec.agent_type))
+            .ok_or_else(|| {
+                anyhow::anyhow!("No factory supports agent type: {}", spec.agent_type)
+            })?;
+        factory.create_agent(spec, instance_id).await
+    }
+}
+
+/// Runs an instance's D-Bus health check and counts failures
+struct HealthMonitor {
+    check: HealthCheck,
+    connection: Option<zbus::Connection>,
+    /// Bus name the instance's process owns, once found
+    bus_name: Option<String>,
+    failures: u32,
+    healthy: Option<bool>,
+}
+
+impl HealthMonitor {
+    fn new(check: HealthCheck) -> Self {
+        Self {
+            check,
+            connection: None,
+            bus_name: None,
+            failures: 0,
+            healthy: None,
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.check.interval_secs.max(1))
+    }
+
+    /// Run one check; returns the new health when it changed
+    async fn check(&mut self, pid: Option<u32>) -> Option<bool> {
+        let timeout = Duration::from_secs(self.check.timeout_secs.max(1));
+        let passed = match tokio::time::timeout(timeout, self.call(pid)).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                log::debug!("Health check {} failed: {}", self.check.method, e);
+                false
+            }
+            Err(_) => {
+                log::debug!("Health check {} timed out", self.check.method);
+                false
+            }
+        };
+
+        let healthy = if passed {
+            self.failures = 0;
+            true
+        } else {
+            self.bus_name = None;
+            self.failures += 1;
+            if self.failures < self.check.unhealthy_threshold.max(1) {
+                return None;
+            }
+            false
+        };
+        if self.healthy == Some(healthy) {
+            return None;
+        }
+        self.healthy = Some(healthy);
+        Some(healthy)
+    }
+
+    /// Call the health check method on the agent owned by `pid`
+    async fn call(&mut self, pid: Option<u32>) -> Result<()> {
+        let pid = pid.context("Agent has no process")?;
+        if self.connection.is_none() {
+            self.connection = Some(zbus::Connection::system().await?);
+        }
+        let connection = self.connection.as_ref().expect("connected above");
+
+        if self.bus_name.is_none() {
+            self.bus_name = Some(find_agent_bus_name(connection, pid).await?);
+        }
+        let name = self.bus_name.as_deref().expect("found above");
+        // Agents serve `<prefix>.<Kind>` at the path spelling out their name
+        let interface = name
+            .rsplit_once('.')
+            .map(|(iface, _)| iface)
+            .unwrap_or(name);
+        let path = format!("/{}", name.replace('.', "/"));
+
+        let proxy = zbus::Proxy::new(connection, name, path.as_str(), interface).await?;
+        proxy
+            .call_method(self.check.method.as_str(), &())
+            .await
+            .with_context(|| format!("{} on {}", self.check.method, name))?;
+        Ok(())
+    }
+}
+
+/// Well-known agent name owned by the process `pid`
+async fn find_agent_bus_name(connection: &zbus::Connection, pid: u32) -> Result<String> {
+    let dbus = zbus::fdo::DBusProxy::new(connection).await?;
+    for name in dbus.list_names().await? {
+        if !name.starts_with(AGENT_BUS_PREFIX) {
+            continue;
+        }
+        let owner = dbus
+            .get_connection_unix_process_id(name.inner().clone())
+            .await;
+        if owner.ok() == Some(pid) {
+            return Ok(name.to_string());
+        }
+    }
+    anyhow::bail!("Process {} owns no agent bus name", pid)
+}
+
 /// Load default agent specifications
 pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
     let specs = vec![
         AgentSpec {
             agent_type: "executor".to_string(),
             name: "Command Executor".to_string(),
-            description: "Executes whitelisted shell commands".to_string(),
+            description: "Executes allowed commands in sandboxed transient units".to_string(),
             command: "dbus-agent-executor".to_string(),
             args: vec![],
             env: HashMap::new(),
             working_dir: None,
             capabilities: vec!["execute".to_string()],
-            requires_root: false,
+            allowed_commands: vec![],
+            requires_root: true, // Starts the sandboxed units
+            limits: AgentLimits::default(),
             max_instances: 3,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -425,7 +837,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "write".to_string(),
                 "delete".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -444,7 +858,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
             env: HashMap::new(),
             working_dir: None,
             capabilities: vec!["network".to_string()],
+            allowed_commands: vec![],
             requires_root: true,
+            limits: AgentLimits::default(),
             max_instances: 2,
             restart_policy: RestartPolicy::Always,
             health_check: Some(HealthCheck {
@@ -463,7 +879,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
             env: HashMap::new(),
             working_dir: None,
             capabilities: vec!["service".to_string()],
+            allowed_commands: vec![],
             requires_root: true,
+            limits: AgentLimits::default(),
             max_instances: 2,
             restart_policy: RestartPolicy::Always,
             health_check: Some(HealthCheck {
@@ -482,7 +900,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
             env: HashMap::new(),
             working_dir: None,
             capabilities: vec!["monitor".to_string()],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 1,
             restart_policy: RestartPolicy::Always,
             health_check: Some(HealthCheck {
@@ -505,7 +925,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "remove".to_string(),
                 "update".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: true,
+            limits: AgentLimits::default(),
             max_instances: 2,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -528,7 +950,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "analyze".to_string(),
                 "format".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -551,7 +975,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "check".to_string(),
                 "test".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -574,7 +1000,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "debug".to_string(),
                 "analyze".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -597,7 +1025,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "debug".to_string(),
                 "analyze".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -620,7 +1050,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "test".to_string(),
                 "build".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -643,7 +1075,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "format".to_string(),
                 "lint".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -666,7 +1100,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "lint".to_string(),
                 "analyze".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -689,7 +1125,9 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
                 "optimize".to_string(),
                 "analyze".to_string(),
             ],
+            allowed_commands: vec![],
             requires_root: false,
+            limits: AgentLimits::default(),
             max_instances: 5,
             restart_policy: RestartPolicy::OnFailure { max_retries: 3 },
             health_check: Some(HealthCheck {
@@ -707,3 +1145,101 @@ pub async fn load_default_specs(registry: &AgentRegistry) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(command: &str, args: &[&str], restart_policy: RestartPolicy) -> AgentSpec {
+        AgentSpec {
+            agent_type: "test".to_string(),
+            name: "Test".to_string(),
+            description: String::new(),
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: HashMap::new(),
+            working_dir: None,
+            capabilities: vec![],
+            allowed_commands: vec![],
+            requires_root: false,
+            limits: AgentLimits::default(),
+            max_instances: 1,
+            restart_policy,
+            health_check: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_agent_restarted_until_retries_run_out() {
+        assert_eq!(
+            restart_backoff(Duration::from_secs(1), 3),
+            Duration::from_secs(8)
+        );
+        assert_eq!(
+            restart_backoff(Duration::from_secs(1), 10),
+            MAX_RESTART_BACKOFF
+        );
+
+        let registry = AgentRegistry::new().with_restart_backoff(Duration::from_millis(10));
+        registry
+            .register_spec(spec(
+                "sh",
+                &["-c", "exit 3"],
+                RestartPolicy::OnFailure { max_retries: 2 },
+            ))
+            .await
+            .unwrap();
+        let mut events = registry.subscribe();
+        let id = registry.spawn_agent("test", None).await.unwrap();
+
+        let mut restarts = 0;
+        let mut deaths = 0;
+        while deaths < 3 {
+            let event = tokio::time::timeout(Duration::from_secs(10), events.recv())
+                .await
+                .expect("supervisor event")
+                .unwrap();
+            match event {
+                AgentEvent::Died { reason, .. } => {
+                    assert!(reason.contains("exit status: 3"), "{}", reason);
+                    deaths += 1;
+                }
+                AgentEvent::Restarted { restart_count, .. } => {
+                    restarts += 1;
+                    assert_eq!(restart_count, restarts);
+                }
+                AgentEvent::HealthChanged { .. } => unreachable!("no health check"),
+            }
+        }
+        assert_eq!(restarts, 2);
+
+        // The final status is set right after the last death is announced
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let instance = registry.get_instance_status(&id).await.unwrap();
+        assert!(matches!(instance.status, AgentStatus::Failed { .. }));
+        assert_eq!(instance.restart_count, 2);
+
+        // A dead instance no longer counts against max_instances
+        registry.spawn_agent("test", None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_kill_stops_supervised_agent() {
+        let registry = AgentRegistry::new();
+        registry
+            .register_spec(spec("sleep", &["30"], RestartPolicy::Always))
+            .await
+            .unwrap();
+        let id = registry.spawn_agent("test", None).await.unwrap();
+        assert!(matches!(
+            registry.get_instance_status(&id).await.unwrap().status,
+            AgentStatus::Starting | AgentStatus::Running
+        ));
+
+        registry.kill_agent(&id).await.unwrap();
+        let instance = registry.get_instance_status(&id).await.unwrap();
+        assert!(matches!(instance.status, AgentStatus::Stopped));
+        assert_eq!(instance.restart_count, 0);
+        assert!(registry.kill_agent(&id).await.is_err());
+    }
+}
@@ -0,0 +1,467 @@
+//! Agents run as systemd services
+//!
+//! `SystemdAgentFactory` starts every agent instance as a transient service
+//! `op-dbus-agent@<type>-<id>.service` through systemd's D-Bus API, so agents
+//! outlive the orchestrator and run with the resource limits of their spec.
+//! Agents that do not require root run as the `op-dbus-agent` system user,
+//! which the bus policy in org.dbusmcp.Agent.conf lets own agent names.
+//! Whether an agent is still running comes from its unit's state, and agents
+//! whose units are still active when the orchestrator starts again are
+//! adopted. When systemd refuses to start units for the caller, agents are
+//! spawned as child processes instead.
+
+use super::agent_registry::{
+    AgentExit, AgentFactory, AgentHandle, AgentProcess, AgentSpec, ProcessAgentFactory,
+};
+use crate::state::plugins::systemd_transient::{parse_bytes, parse_cpu_quota};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use zbus::zvariant::{OwnedObjectPath, Value};
+use zbus::{Connection, Proxy};
+
+/// Template-style prefix of agent unit names
+pub const UNIT_PREFIX: &str = "op-dbus-agent@";
+
+const SYSTEMD_DESTINATION: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const SYSTEMD_MANAGER: &str = "org.freedesktop.systemd1.Manager";
+const NO_SUCH_UNIT: &str = "org.freedesktop.systemd1.NoSuchUnit";
+
+/// Errors of systemd refusing to manage units for the caller
+const ACCESS_DENIED: &[&str] = &[
+    "org.freedesktop.DBus.Error.AccessDenied",
+    "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired",
+];
+
+/// System user running agents that do not require root, created by
+/// systemd/op-dbus-agent.sysusers.conf
+pub const AGENT_USER: &str = "op-dbus-agent";
+
+/// How often a unit's state is polled while waiting for it to exit
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Starts agents as transient systemd services
+pub struct SystemdAgentFactory {
+    connection: Connection,
+    /// Spawns agents once systemd denied starting units
+    fallback: ProcessAgentFactory,
+    denied: AtomicBool,
+}
+
+impl SystemdAgentFactory {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            fallback: ProcessAgentFactory,
+            denied: AtomicBool::new(false),
+        }
+    }
+
+    /// Factory using the system bus
+    pub async fn system() -> Result<Self> {
+        let connection = Connection::system()
+            .await
+            .context("Failed to connect to the system bus")?;
+        Ok(Self::new(connection))
+    }
+}
+
+/// Unit running an instance; unit names only take a few characters
+pub fn unit_name(instance_id: &str) -> String {
+    let instance: String = instance_id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | ':' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{}{}.service", UNIT_PREFIX, instance)
+}
+
+/// Absolute path of a program, as systemd needs it for `ExecStart`
+fn resolve_program(command: &str) -> Result<PathBuf> {
+    if command.starts_with('/') {
+        return Ok(PathBuf::from(command));
+    }
+    let path = std::env::var_os("PATH").unwrap_or_else(|| "/usr/bin:/bin".into());
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(command))
+        .find(|candidate| candidate.is_file())
+        .with_context(|| format!("Agent command '{}' not found in PATH", command))
+}
+
+/// Properties of the service running `spec` as `instance_id`
+pub fn unit_properties(
+    spec: &AgentSpec,
+    instance_id: &str,
+    program: &Path,
+) -> Result<Vec<(&'static str, Value<'static>)>> {
+    let program = program.to_string_lossy().into_owned();
+    let mut argv = vec![spec.command.clone()];
+    argv.extend(spec.args.iter().cloned());
+
+    let mut properties: Vec<(&'static str, Value<'static>)> = vec![
+        (
+            "Description",
+            Value::from(format!("op-dbus agent {} ({})", spec.name, instance_id)),
+        ),
+        ("ExecStart", Value::from(vec![(program, argv, false)])),
+    ];
+
+    // Same environment as agents spawned as child processes
+    let mut environment = vec![
+        format!("AGENT_ID={}", instance_id),
+        format!("AGENT_TYPE={}", spec.agent_type),
+    ];
+    if !spec.allowed_commands.is_empty() {
+        environment.push(format!(
+            "AGENT_ALLOWED_COMMANDS={}",
+            spec.allowed_commands.join(",")
+        ));
+    }
+    let mut custom: Vec<_> = spec.env.iter().collect();
+    custom.sort();
+    environment.extend(
+        custom
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value)),
+    );
+    properties.push(("Environment", Value::from(environment)));
+
+    if let Some(dir) = &spec.working_dir {
+        properties.push((
+            "WorkingDirectory",
+            Value::from(dir.to_string_lossy().into_owned()),
+        ));
+    }
+    if !spec.requires_root {
+        properties.push(("User", Value::from(AGENT_USER)));
+        properties.push(("Group", Value::from(AGENT_USER)));
+        properties.push(("NoNewPrivileges", Value::from(true)));
+    }
+
+    if let Some(memory_max) = &spec.limits.memory_max {
+        properties.push(("MemoryMax", Value::from(parse_bytes(memory_max)?)));
+    }
+    if let Some(cpu_quota) = &spec.limits.cpu_quota {
+        properties.push((
+            "CPUQuotaPerSecUSec",
+            Value::from(parse_cpu_quota(cpu_quota)?),
+        ));
+    }
+    if let Some(tasks_max) = spec.limits.tasks_max {
+        properties.push(("TasksMax", Value::from(tasks_max)));
+    }
+    Ok(properties)
+}
+
+async fn manager(connection: &Connection) -> Result<Proxy<'static>> {
+    Ok(Proxy::new(
+        connection,
+        SYSTEMD_DESTINATION,
+        SYSTEMD_PATH,
+        SYSTEMD_MANAGER,
+    )
+    .await?)
+}
+
+async fn unit_proxy(
+    connection: &Connection,
+    path: OwnedObjectPath,
+    interface: &'static str,
+) -> Result<Proxy<'static>> {
+    Ok(Proxy::new(connection, SYSTEMD_DESTINATION, path, interface).await?)
+}
+
+#[async_trait]
+impl AgentFactory for SystemdAgentFactory {
+    async fn create_agent(&self, spec: &AgentSpec, instance_id: &str) -> Result<AgentHandle> {
+        if self.denied.load(Ordering::Relaxed) {
+            return self.fallback.create_agent(spec, instance_id).await;
+        }
+
+        let unit = unit_name(instance_id);
+        let program = resolve_program(&spec.command)?;
+        let properties = unit_properties(spec, instance_id, &program)?;
+        let aux: Vec<(&str, Vec<(&str, Value)>)> = Vec::new();
+
+        let started: zbus::Result<OwnedObjectPath> = manager(&self.connection)
+            .await?
+            .call(
+                "StartTransientUnit",
+                &(unit.as_str(), "fail", properties, aux),
+            )
+            .await;
+        match started {
+            Ok(_job) => {}
+            Err(zbus::Error::MethodError(name, message, _))
+                if ACCESS_DENIED.contains(&name.as_str()) =>
+            {
+                log::warn!(
+                    "Not allowed to start agent units ({}), running agents as child processes",
+                    message.unwrap_or_else(|| name.to_string())
+                );
+                self.denied.store(true, Ordering::Relaxed);
+                return self.fallback.create_agent(spec, instance_id).await;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to start agent unit {}", unit))
+            }
+        }
+
+        let mut process = AgentUnit::new(self.connection.clone(), unit);
+        process.refresh().await?;
+        Ok(AgentHandle {
+            id: instance_id.to_string(),
+            process: Box::new(process),
+            spec: spec.clone(),
+        })
+    }
+
+    fn supports(&self, _agent_type: &str) -> bool {
+        true
+    }
+
+    async fn running_agents(&self, specs: &HashMap<String, AgentSpec>) -> Result<Vec<AgentHandle>> {
+        type UnitEntry = (
+            String,
+            String,
+            String,
+            String,
+            String,
+            String,
+            OwnedObjectPath,
+            u32,
+            String,
+            OwnedObjectPath,
+        );
+        let units: Vec<UnitEntry> = manager(&self.connection)
+            .await?
+            .call(
+                "ListUnitsByPatterns",
+                &(
+                    vec!["active", "activating", "reloading"],
+                    vec![format!("{}*.service", UNIT_PREFIX)],
+                ),
+            )
+            .await
+            .context("Failed to list agent units")?;
+
+        let mut handles = Vec::new();
+        for (unit, .., path, _, _, _) in units {
+            // The instance and type are in the environment the unit was started with
+            let service =
+                unit_proxy(&self.connection, path, "org.freedesktop.systemd1.Service").await?;
+            let environment: Vec<String> = service.get_property("Environment").await?;
+            let variable = |name: &str| {
+                environment
+                    .iter()
+                    .find_map(|assignment| assignment.strip_prefix(name)?.strip_prefix('='))
+                    .map(str::to_string)
+            };
+            let (Some(instance_id), Some(agent_type)) =
+                (variable("AGENT_ID"), variable("AGENT_TYPE"))
+            else {
+                log::warn!("Agent unit {} has no AGENT_ID or AGENT_TYPE", unit);
+                continue;
+            };
+            let Some(spec) = specs.get(&agent_type) else {
+                log::warn!("Agent unit {} runs unknown agent type {}", unit, agent_type);
+                continue;
+            };
+
+            let mut process = AgentUnit::new(self.connection.clone(), unit);
+            process.refresh().await?;
+            handles.push(AgentHandle {
+                id: instance_id,
+                process: Box::new(process),
+                spec: spec.clone(),
+            });
+        }
+        Ok(handles)
+    }
+}
+
+/// State of an agent's unit
+enum UnitState {
+    Running,
+    Exited(AgentExit),
+}
+
+/// Agent running as a systemd unit
+struct AgentUnit {
+    connection: Connection,
+    unit: String,
+    main_pid: Option<u32>,
+}
+
+impl AgentUnit {
+    fn new(connection: Connection, unit: String) -> Self {
+        Self {
+            connection,
+            unit,
+            main_pid: None,
+        }
+    }
+
+    /// Read the unit's state, remembering its main process
+    async fn refresh(&mut self) -> Result<UnitState> {
+        let path: OwnedObjectPath = match manager(&self.connection)
+            .await?
+            .call("GetUnit", &(self.unit.as_str(),))
+            .await
+        {
+            Ok(path) => path,
+            // Transient units are unloaded once they stopped cleanly
+            Err(zbus::Error::MethodError(name, ..)) if name.as_str() == NO_SUCH_UNIT => {
+                self.main_pid = None;
+                return Ok(UnitState::Exited(AgentExit {
+                    success: true,
+                    reason: "unit finished".to_string(),
+                }));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let unit = unit_proxy(
+            &self.connection,
+            path.clone(),
+            "org.freedesktop.systemd1.Unit",
+        )
+        .await?;
+        let service =
+            unit_proxy(&self.connection, path, "org.freedesktop.systemd1.Service").await?;
+        let main_pid: u32 = service.get_property("MainPID").await?;
+        self.main_pid = Some(main_pid).filter(|pid| *pid != 0);
+
+        let active_state: String = unit.get_property("ActiveState").await?;
+        match active_state.as_str() {
+            "inactive" => Ok(UnitState::Exited(AgentExit {
+                success: true,
+                reason: "unit stopped".to_string(),
+            })),
+            "failed" => {
+                let result: String = service.get_property("Result").await?;
+                let status: i32 = service.get_property("ExecMainStatus").await?;
+                // Forget the failure so the instance's unit name can be reused
+                let _: zbus::Result<()> = manager(&self.connection)
+                    .await?
+                    .call("ResetFailedUnit", &(self.unit.as_str(),))
+                    .await;
+                Ok(UnitState::Exited(AgentExit {
+                    success: false,
+                    reason: format!("unit failed with result '{}' (status {})", result, status),
+                }))
+            }
+            _ => Ok(UnitState::Running),
+        }
+    }
+}
+
+#[async_trait]
+impl AgentProcess for AgentUnit {
+    fn id(&self) -> Option<u32> {
+        self.main_pid
+    }
+
+    async fn wait(&mut self) -> Result<AgentExit> {
+        loop {
+            if let UnitState::Exited(exit) = self.refresh().await? {
+                return Ok(exit);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn kill(&mut self) -> Result<()> {
+        let stopped: zbus::Result<OwnedObjectPath> = manager(&self.connection)
+            .await?
+            .call("StopUnit", &(self.unit.as_str(), "replace"))
+            .await;
+        match stopped {
+            Ok(_) => Ok(()),
+            Err(zbus::Error::MethodError(name, ..)) if name.as_str() == NO_SUCH_UNIT => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Failed to stop {}", self.unit)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::agent_registry::{AgentLimits, RestartPolicy};
+
+    #[test]
+    fn test_unit_properties() {
+        let spec = AgentSpec {
+            agent_type: "executor".to_string(),
+            name: "Command Executor".to_string(),
+            description: String::new(),
+            command: "dbus-agent-executor".to_string(),
+            args: vec!["--verbose".to_string()],
+            env: HashMap::from([("RUST_LOG".to_string(), "debug".to_string())]),
+            working_dir: None,
+            capabilities: vec![],
+            allowed_commands: vec!["ls".to_string(), "df".to_string()],
+            requires_root: false,
+            limits: AgentLimits {
+                memory_max: Some("128M".to_string()),
+                cpu_quota: None,
+                tasks_max: Some(32),
+            },
+            max_instances: 1,
+            restart_policy: RestartPolicy::Never,
+            health_check: None,
+        };
+        let instance_id = "executor-1b4e28ba";
+        assert_eq!(
+            unit_name(instance_id),
+            "op-dbus-agent@executor-1b4e28ba.service"
+        );
+        assert_eq!(unit_name("odd/type-1"), "op-dbus-agent@odd_type-1.service");
+
+        let properties = unit_properties(
+            &spec,
+            instance_id,
+            Path::new("/usr/bin/dbus-agent-executor"),
+        )
+        .unwrap();
+        let names: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "Description",
+                "ExecStart",
+                "Environment",
+                "User",
+                "Group",
+                "NoNewPrivileges",
+                "MemoryMax",
+                "TasksMax"
+            ]
+        );
+        assert_eq!(
+            properties[2].1,
+            Value::from(vec![
+                "AGENT_ID=executor-1b4e28ba".to_string(),
+                "AGENT_TYPE=executor".to_string(),
+                "AGENT_ALLOWED_COMMANDS=ls,df".to_string(),
+                "RUST_LOG=debug".to_string(),
+            ])
+        );
+        assert_eq!(properties[3].1, Value::from(AGENT_USER));
+        assert_eq!(properties[6].1, Value::from(128u64 << 20));
+
+        let root = AgentSpec {
+            requires_root: true,
+            limits: AgentLimits::default(),
+            ..spec
+        };
+        let properties = unit_properties(&root, instance_id, Path::new("/bin/true")).unwrap();
+        assert!(properties.iter().all(|(name, _)| *name != "User"));
+    }
+}
@@ -241,7 +241,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("c-pro-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("c-pro-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting C Pro Agent: {}", agent_id);
@@ -1,12 +1,30 @@
+//! Executor agent running commands in sandboxed transient units
+//!
+//! Every command runs as a transient systemd service started through D-Bus,
+//! under a dynamic user (with a private /tmp) and a read-only file system, no network, memory and
+//! CPU limits and a wall-clock timeout. Stdout and stderr come back through
+//! pipes handed to systemd as file descriptors.
+//!
+//! Starting the units needs root, so the executor's agent spec sets
+//! `requires_root`. The executor is the only component starting these units
+//! and always sets the sandbox itself; nothing grants other users the right
+//! to start them, which would let them leave the sandbox out.
+//!
+//! The allowed commands come from the agent spec (`AGENT_ALLOWED_COMMANDS`,
+//! comma-separated), defaulting to a small set of read-only tools.
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::process::Command;
+use std::io::Read;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use uuid::Uuid;
-use zbus::{connection::Builder, interface, object_server::SignalEmitter};
+use zbus::zvariant::{Fd, OwnedObjectPath, Value};
+use zbus::{connection::Builder, interface, object_server::SignalEmitter, Connection, Proxy};
 
 // Security configuration
-const ALLOWED_COMMANDS: &[&str] = &[
+const DEFAULT_ALLOWED_COMMANDS: &[&str] = &[
     "ls", "cat", "grep", "ps", "top", "df", "du", "free", "uptime", "whoami", "date", "hostname",
     "pwd", "echo", "wc", "sort", "head", "tail",
 ];
@@ -18,6 +36,22 @@ const MAX_COMMAND_LENGTH: usize = 1024;
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
 const MAX_TIMEOUT_SECS: u64 = 300;
 
+// Sandbox limits
+const MEMORY_MAX_BYTES: u64 = 256 * 1024 * 1024;
+const CPU_QUOTA_USEC_PER_SEC: u64 = 500_000; // 50% of one CPU
+const TASKS_MAX: u64 = 64;
+const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+/// Time past the unit's own timeout before the agent stops it
+const STOP_GRACE: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const SYSTEMD_DESTINATION: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const SYSTEMD_MANAGER: &str = "org.freedesktop.systemd1.Manager";
+
+/// Prefix of the sandboxed units
+const UNIT_PREFIX: &str = "op-dbus-exec-";
+
 #[derive(Debug, Deserialize)]
 struct ExecuteTask {
     #[serde(rename = "type")]
@@ -41,19 +75,140 @@ struct ExecuteResult {
 struct ExecutorAgent {
     agent_id: String,
     allowed_commands: HashSet<String>,
+    /// System bus connection for starting the transient units
+    systemd: Connection,
 }
 
-impl ExecutorAgent {
-    fn new(agent_id: String) -> Self {
-        let allowed_commands: HashSet<String> =
-            ALLOWED_COMMANDS.iter().map(|s| s.to_string()).collect();
+/// How a sandboxed command ended, from the unit's service properties
+struct UnitExit {
+    /// `Result` of the service: "success", "exit-code", "timeout", ...
+    result: String,
+    exit_status: i32,
+}
+
+/// Commands from the agent spec, or the defaults without one
+fn allowed_commands_from_env() -> HashSet<String> {
+    match std::env::var("AGENT_ALLOWED_COMMANDS") {
+        Ok(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => DEFAULT_ALLOWED_COMMANDS
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+    }
+}
 
+/// Absolute path of a command, as systemd needs it for `ExecStart`
+fn resolve_program(command: &str) -> Option<PathBuf> {
+    if command.starts_with('/') {
+        return Some(PathBuf::from(command));
+    }
+    let path = std::env::var_os("PATH").unwrap_or_else(|| "/usr/bin:/bin".into());
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(command))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Properties of the transient service running `argv`
+fn sandbox_properties<'a>(
+    description: String,
+    program: &Path,
+    argv: Vec<String>,
+    working_dir: Option<String>,
+    timeout_secs: u64,
+    stdout: &'a OwnedFd,
+    stderr: &'a OwnedFd,
+) -> Vec<(&'static str, Value<'a>)> {
+    let program = program.to_string_lossy().into_owned();
+    let mut properties = vec![
+        ("Description", Value::from(description)),
+        ("ExecStart", Value::from(vec![(program, argv, false)])),
+        ("DynamicUser", Value::from(true)),
+        ("ProtectSystem", Value::from("strict")),
+        ("ProtectHome", Value::from("read-only")),
+        ("PrivateNetwork", Value::from(true)),
+        ("PrivateDevices", Value::from(true)),
+        ("NoNewPrivileges", Value::from(true)),
+        ("MemoryMax", Value::from(MEMORY_MAX_BYTES)),
+        ("CPUQuotaPerSecUSec", Value::from(CPU_QUOTA_USEC_PER_SEC)),
+        ("TasksMax", Value::from(TASKS_MAX)),
+        ("RuntimeMaxUSec", Value::from(timeout_secs * 1_000_000)),
+        // Keep the unit after the command exits so its status can be read
+        ("RemainAfterExit", Value::from(true)),
+        (
+            "StandardOutputFileDescriptor",
+            Value::from(Fd::from(stdout)),
+        ),
+        ("StandardErrorFileDescriptor", Value::from(Fd::from(stderr))),
+    ];
+    if let Some(dir) = working_dir {
+        properties.push(("WorkingDirectory", Value::from(dir)));
+    }
+    properties
+}
+
+/// Why a sandboxed unit could not be started, pointing out that the
+/// executor must run as root when systemd refused to start units for it
+fn start_error(error: &zbus::Error) -> String {
+    let denied = match error {
+        zbus::Error::MethodError(name, ..) => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+                | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired"
+        ),
+        zbus::Error::FDO(error) => matches!(
+            **error,
+            zbus::fdo::Error::AccessDenied(_)
+                | zbus::fdo::Error::InteractiveAuthorizationRequired(_)
+        ),
+        _ => false,
+    };
+    if denied {
+        format!(
+            "Not allowed to start sandboxed units: the executor must run as root ({})",
+            error
+        )
+    } else {
+        format!("Failed to start sandboxed unit: {}", error)
+    }
+}
+
+/// Read a pipe to its end, keeping the first `MAX_OUTPUT_BYTES`
+fn read_pipe(reader: std::io::PipeReader) -> tokio::task::JoinHandle<String> {
+    tokio::task::spawn_blocking(move || {
+        let mut reader = reader;
+        let mut output = Vec::new();
+        let _ = (&mut reader)
+            .take(MAX_OUTPUT_BYTES)
+            .read_to_end(&mut output);
+        let _ = std::io::copy(&mut reader, &mut std::io::sink());
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+impl ExecutorAgent {
+    fn new(agent_id: String, allowed_commands: HashSet<String>, systemd: Connection) -> Self {
         Self {
             agent_id,
             allowed_commands,
+            systemd,
         }
     }
 
+    async fn manager(&self) -> zbus::Result<Proxy<'_>> {
+        Proxy::new(
+            &self.systemd,
+            SYSTEMD_DESTINATION,
+            SYSTEMD_PATH,
+            SYSTEMD_MANAGER,
+        )
+        .await
+    }
+
     fn validate_command(&self, command: &str) -> Result<(), String> {
         // Check command length
         if command.len() > MAX_COMMAND_LENGTH {
@@ -124,10 +279,10 @@ impl ExecutorAgent {
             return Err("Directory traversal not allowed".to_string());
         }
 
-        // Check if path is absolute and within allowed directories
-        if !dir.starts_with("/home/") && !dir.starts_with("/tmp/") && !dir.starts_with("/var/log/")
-        {
-            return Err("Working directory must be within /home/, /tmp/, or /var/log/".to_string());
+        // Check if path is absolute and within allowed directories; the
+        // sandbox's /tmp is private, so it cannot be a working directory
+        if !dir.starts_with("/home/") && !dir.starts_with("/var/log/") {
+            return Err("Working directory must be within /home/ or /var/log/".to_string());
         }
 
         Ok(())
@@ -162,47 +317,151 @@ impl ExecutorAgent {
         // Validate all arguments
         self.validate_args(&all_args)?;
 
-        // Setup command
-        let mut cmd = Command::new(base_command);
-        cmd.args(&all_args);
+        let program = resolve_program(base_command)
+            .ok_or_else(|| format!("Command '{}' was not found", base_command))?;
 
-        // Set working directory if provided
-        if let Some(dir) = task.working_dir {
-            self.validate_working_dir(&dir)?;
-            cmd.current_dir(dir);
+        if let Some(dir) = &task.working_dir {
+            self.validate_working_dir(dir)?;
         }
 
         // Set timeout
         let timeout_secs = task
             .timeout
             .unwrap_or(DEFAULT_TIMEOUT_SECS)
-            .min(MAX_TIMEOUT_SECS);
+            .clamp(1, MAX_TIMEOUT_SECS);
+
+        let (stdout_reader, stdout_writer) =
+            std::io::pipe().map_err(|e| format!("Failed to create pipe: {}", e))?;
+        let (stderr_reader, stderr_writer) =
+            std::io::pipe().map_err(|e| format!("Failed to create pipe: {}", e))?;
+        let stdout_writer = OwnedFd::from(stdout_writer);
+        let stderr_writer = OwnedFd::from(stderr_writer);
+        let stdout = read_pipe(stdout_reader);
+        let stderr = read_pipe(stderr_reader);
+
+        let mut argv = vec![base_command.to_string()];
+        argv.extend(all_args);
+        let unit = format!(
+            "{}{}-{}.service",
+            UNIT_PREFIX,
+            self.agent_id
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+            &Uuid::new_v4().simple().to_string()[..8]
+        );
+        let properties = sandbox_properties(
+            format!("op-dbus executor {}: {}", self.agent_id, base_command),
+            &program,
+            argv,
+            task.working_dir,
+            timeout_secs,
+            &stdout_writer,
+            &stderr_writer,
+        );
 
-        // Execute with timeout
-        let output = tokio::time::timeout(
-            Duration::from_secs(timeout_secs),
-            tokio::task::spawn_blocking(move || cmd.output()),
+        let started = self.start_unit(&unit, properties).await;
+        // systemd holds its own copies for the service
+        drop(stdout_writer);
+        drop(stderr_writer);
+        if let Err(e) = started {
+            return Err(start_error(&e));
+        }
+
+        let exited = tokio::time::timeout(
+            Duration::from_secs(timeout_secs) + STOP_GRACE,
+            self.wait_for_exit(&unit),
         )
         .await;
+        // Stopping releases the unit's descriptors, ending the pipes
+        self.cleanup_unit(&unit).await;
+        let stdout = collect_output(stdout).await;
+        let stderr = collect_output(stderr).await;
+
+        let exit = match exited {
+            Ok(Ok(exit)) => exit,
+            Ok(Err(e)) => return Err(format!("Failed to follow unit {}: {}", unit, e)),
+            Err(_) => {
+                return Err(format!("Command timed out after {} seconds", timeout_secs));
+            }
+        };
+        match exit.result.as_str() {
+            "success" | "exit-code" => Ok(ExecuteResult {
+                success: exit.exit_status == 0,
+                exit_code: exit.exit_status,
+                stdout,
+                stderr,
+                error: None,
+            }),
+            "timeout" => Err(format!("Command timed out after {} seconds", timeout_secs)),
+            result => Ok(ExecuteResult {
+                success: false,
+                exit_code: -1,
+                stdout,
+                stderr,
+                error: Some(format!("Command ended with result '{}'", result)),
+            }),
+        }
+    }
 
-        match output {
-            Ok(Ok(Ok(output))) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code().unwrap_or(-1);
-
-                Ok(ExecuteResult {
-                    success: output.status.success(),
-                    exit_code,
-                    stdout,
-                    stderr,
-                    error: None,
-                })
+    async fn start_unit(&self, unit: &str, properties: Vec<(&str, Value<'_>)>) -> zbus::Result<()> {
+        let aux: Vec<(&str, Vec<(&str, Value)>)> = Vec::new();
+        let _job: OwnedObjectPath = self
+            .manager()
+            .await?
+            .call("StartTransientUnit", &(unit, "fail", properties, aux))
+            .await?;
+        Ok(())
+    }
+
+    /// Wait until the unit's command has exited
+    async fn wait_for_exit(&self, unit: &str) -> zbus::Result<UnitExit> {
+        let path: OwnedObjectPath = self.manager().await?.call("GetUnit", &(unit,)).await?;
+        let unit_proxy = Proxy::new(
+            &self.systemd,
+            SYSTEMD_DESTINATION,
+            path.clone(),
+            "org.freedesktop.systemd1.Unit",
+        )
+        .await?;
+        let service = Proxy::new(
+            &self.systemd,
+            SYSTEMD_DESTINATION,
+            path,
+            "org.freedesktop.systemd1.Service",
+        )
+        .await?;
+
+        loop {
+            let active_state: String = unit_proxy.get_property("ActiveState").await?;
+            let sub_state: String = unit_proxy.get_property("SubState").await?;
+            if sub_state == "exited" || active_state == "failed" || active_state == "inactive" {
+                return Ok(UnitExit {
+                    result: service.get_property("Result").await?,
+                    exit_status: service.get_property("ExecMainStatus").await?,
+                });
             }
-            Ok(Ok(Err(e))) => Err(format!("Failed to execute command: {}", e)),
-            Ok(Err(e)) => Err(format!("Task panic: {}", e)),
-            Err(_) => Err(format!("Command timed out after {} seconds", timeout_secs)),
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Stop the unit and forget a failure, so it is garbage-collected
+    async fn cleanup_unit(&self, unit: &str) {
+        let Ok(manager) = self.manager().await else {
+            return;
+        };
+        let stopped: zbus::Result<OwnedObjectPath> =
+            manager.call("StopUnit", &(unit, "replace")).await;
+        if let Err(e) = stopped {
+            eprintln!("[{}] Failed to stop {}: {}", self.agent_id, unit, e);
         }
+        let _: zbus::Result<()> = manager.call("ResetFailedUnit", &(unit,)).await;
+    }
+}
+
+/// Output of a pipe reader, giving up if the pipe is still held open
+async fn collect_output(reader: tokio::task::JoinHandle<String>) -> String {
+    match tokio::time::timeout(STOP_GRACE, reader).await {
+        Ok(Ok(output)) => output,
+        _ => String::new(),
     }
 }
 
@@ -259,6 +518,13 @@ impl ExecutorAgent {
             "status": "running",
             "allowed_commands": self.allowed_commands.iter().collect::<Vec<_>>(),
             "max_timeout": MAX_TIMEOUT_SECS,
+            "sandbox": {
+                "dynamic_user": true,
+                "protect_system": "strict",
+                "private_network": true,
+                "memory_max": MEMORY_MAX_BYTES,
+                "cpu_quota_usec_per_sec": CPU_QUOTA_USEC_PER_SEC,
+            },
         });
         Ok(status.to_string())
     }
@@ -276,13 +542,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("executor-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("executor-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Secure Executor Agent: {}", agent_id);
-    println!("Allowed commands: {:?}", ALLOWED_COMMANDS);
+    let allowed_commands = allowed_commands_from_env();
+    println!("Allowed commands: {:?}", allowed_commands);
 
-    let agent = ExecutorAgent::new(agent_id.clone());
+    let systemd = Connection::system().await?;
+    let agent = ExecutorAgent::new(agent_id.clone(), allowed_commands, systemd);
 
     let path = format!("/org/dbusmcp/Agent/Executor/{}", agent_id.replace('-', "_"));
     let service_name = format!("org.dbusmcp.Agent.Executor.{}", agent_id.replace('-', "_"));
@@ -302,3 +570,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_error() {
+        let denied = zbus::Error::FDO(Box::new(
+            zbus::fdo::Error::InteractiveAuthorizationRequired(
+                "Interactive authentication required.".to_string(),
+            ),
+        ));
+        let message = start_error(&denied);
+        assert!(message.starts_with("Not allowed to start sandboxed units"));
+        assert!(message.contains("must run as root"));
+
+        let other = zbus::Error::FDO(Box::new(zbus::fdo::Error::Failed("boom".to_string())));
+        assert!(start_error(&other).starts_with("Failed to start sandboxed unit"));
+    }
+}
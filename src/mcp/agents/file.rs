@@ -260,13 +260,11 @@ impl FileAgent {
             fs::read_dir(validated_path).map_err(|e| format!("Failed to read directory: {}", e))?;
 
         let mut files = Vec::new();
-        for entry in entries {
-            if let Ok(entry) = entry {
-                if let Some(name) = entry.file_name().to_str() {
-                    // Skip hidden files unless explicitly requested
-                    if !name.starts_with('.') {
-                        files.push(name.to_string());
-                    }
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str() {
+                // Skip hidden files unless explicitly requested
+                if !name.starts_with('.') {
+                    files.push(name.to_string());
                 }
             }
         }
@@ -373,7 +371,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("file-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("file-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Secure File Agent: {}", agent_id);
@@ -215,7 +215,7 @@ impl GolangProAgent {
         cmd.arg("build");
 
         if let Some(p) = path {
-            let validated_path = self.validate_path(p)?;
+            let _validated_path = self.validate_path(p)?;
             cmd.arg("-o").arg("/tmp/go_output").arg(p);
         }
 
@@ -252,7 +252,7 @@ impl GolangProAgent {
             .map_err(|e| format!("Failed to run gofmt: {}", e))?;
 
         let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let _stderr = String::from_utf8_lossy(&output.stderr);
 
         if stdout.is_empty() {
             Ok("Code is properly formatted".to_string())
@@ -328,7 +328,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("golang-pro-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("golang-pro-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Golang Pro Agent: {}", agent_id);
@@ -241,7 +241,7 @@ impl JavascriptProAgent {
         cmd.arg("prettier");
 
         if let Some(p) = path {
-            let validated_path = self.validate_path(p)?;
+            let _validated_path = self.validate_path(p)?;
             cmd.arg("--check").arg("--write").arg(p);
         }
 
@@ -275,7 +275,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     } else {
         format!(
             "javascript-pro-{}",
-            Uuid::new_v4().to_string()[..8].to_string()
+            &Uuid::new_v4().to_string()[..8]
         )
     };
 
@@ -163,7 +163,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("monitor-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("monitor-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Monitor Agent: {}", agent_id);
@@ -3,7 +3,7 @@ use serde_json::{json, Value};
 use std::process::Command;
 use uuid::Uuid;
 use zbus::{connection::Builder, interface, object_server::SignalEmitter};
-use std::io::{self, BufRead, Write};
+use std::io::{self, Write};
 
 // Security configuration
 const FORBIDDEN_CHARS: &[char] = &[
@@ -481,7 +481,7 @@ async fn run_dbus_service(args: Vec<String>) -> Result<(), Box<dyn std::error::E
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("network-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("network-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Network Agent: {}", agent_id);
@@ -212,7 +212,7 @@ impl PhpProAgent {
         cmd.arg("vendor/bin/phpcs");
 
         if let Some(p) = path {
-            let validated_path = self.validate_path(p)?;
+            let _validated_path = self.validate_path(p)?;
             cmd.arg(p);
         }
 
@@ -241,7 +241,7 @@ impl PhpProAgent {
         cmd.arg("vendor/bin/phpstan");
 
         if let Some(p) = path {
-            let validated_path = self.validate_path(p)?;
+            let _validated_path = self.validate_path(p)?;
             cmd.arg("analyse").arg(p);
         }
 
@@ -273,7 +273,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("php-pro-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("php-pro-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting PHP Pro Agent: {}", agent_id);
@@ -303,7 +303,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("python-pro-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("python-pro-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Python Pro Agent: {}", agent_id);
@@ -308,7 +308,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("rust-pro-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("rust-pro-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Rust Pro Agent: {}", agent_id);
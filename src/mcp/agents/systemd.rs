@@ -131,7 +131,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let agent_id = if args.len() > 1 {
         args[1].clone()
     } else {
-        format!("systemd-{}", Uuid::new_v4().to_string()[..8].to_string())
+        format!("systemd-{}", &Uuid::new_v4().to_string()[..8])
     };
 
     println!("Starting Systemd Agent: {}", agent_id);
@@ -17,12 +17,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
-            "--service" => {
-                if i + 1 < args.len() {
+            "--service"
+                if i + 1 < args.len() => {
                     service_name = args[i + 1].clone();
                     i += 1;
                 }
-            }
             "--system" => {
                 use_system_bus = true;
             }
@@ -7,7 +7,6 @@ use crate::mcp::introspection_parser;
 use introspection_parser::{IntrospectionParser, MethodInfo};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::io::{self, BufRead, Write};
 use zbus::Connection;
 
 #[derive(Debug, Deserialize)]
@@ -36,6 +35,7 @@ pub struct McpError {
 
 pub struct DbusMcpBridge {
     service_name: String,
+    #[allow(dead_code)] // Held so the bridge keeps its bus connection
     connection: Connection,
     methods: Vec<MethodInfo>,
     mcp_name: String,
@@ -368,7 +368,7 @@ impl DbusMcpBridge {
             }
             if !stderr.is_empty() {
                 if !result.is_empty() {
-                    result.push_str("\n");
+                    result.push('\n');
                 }
                 result.push_str("Info:\n");
                 result.push_str(&stderr);
@@ -1,47 +1,33 @@
 //! Refactored orchestrator using agent registry for loose coupling
 //! Moved to chat module for tighter integration
 
-use crate::mcp::agent_registry::{load_default_specs, AgentRegistry};
+use crate::mcp::agent_registry::{load_default_specs, AgentEvent, AgentRegistry};
+use crate::mcp::agent_units::SystemdAgentFactory;
+use crate::task_queue::{NewTask, TaskQuery, TaskStore};
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
 use zbus::{interface, object_server::SignalEmitter};
 
+pub use crate::task_queue::{Task, TaskStatus};
+
 /// Orchestrator for managing agents without tight coupling
 pub struct Orchestrator {
     /// Agent registry for dynamic agent management
     registry: Arc<AgentRegistry>,
 
-    /// Task queue for each agent
-    task_queues: Arc<RwLock<HashMap<String, Vec<Task>>>>,
+    /// Persistent task queue of all agents
+    tasks: Arc<TaskStore>,
 
     /// Event listeners
     event_listeners: Arc<RwLock<Vec<Box<dyn EventListener>>>>,
 }
 
-/// Task to be executed by an agent
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Task {
-    pub id: String,
-    pub agent_id: String,
-    pub task_type: String,
-    pub payload: Value,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-    pub status: TaskStatus,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum TaskStatus {
-    Pending,
-    Running,
-    Completed { result: Value },
-    Failed { error: String },
-}
+/// How often timed out tasks are failed and old ones purged
+const TASK_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(30);
 
 /// Event listener trait for extensibility
 #[async_trait::async_trait]
@@ -70,19 +56,59 @@ impl Orchestrator {
             }
         }
 
-        Ok(Self {
-            registry,
-            task_queues: Arc::new(RwLock::new(HashMap::new())),
-            event_listeners: Arc::new(RwLock::new(Vec::new())),
-        })
+        // Agents run as systemd units unless OPDBUS_AGENT_RUNNER=process;
+        // those still running from before a restart are supervised again
+        if std::env::var("OPDBUS_AGENT_RUNNER").as_deref() != Ok("process") {
+            match SystemdAgentFactory::system().await {
+                Ok(factory) => {
+                    registry.register_factory(Box::new(factory)).await;
+                    match registry.adopt_running_agents().await {
+                        Ok(adopted) if !adopted.is_empty() => {
+                            log::info!("Adopted {} running agents", adopted.len())
+                        }
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Failed to adopt running agents: {}", e),
+                    }
+                }
+                Err(e) => log::warn!("Running agents as child processes: {}", e),
+            }
+        }
+
+        let tasks = TaskStore::from_env().context("Failed to open task database")?;
+
+        // Tasks of agents that did not survive the restart never finish
+        let live: Vec<String> = registry
+            .list_instances()
+            .await
+            .into_iter()
+            .filter(|instance| instance.status.is_live())
+            .map(|instance| instance.id)
+            .collect();
+        match tasks.fail_orphaned(&live, "Agent no longer running") {
+            Ok(orphaned) if !orphaned.is_empty() => {
+                log::info!("Failed {} tasks of agents that are gone", orphaned.len())
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Failed to recover running tasks: {}", e),
+        }
+
+        Ok(Self::with_registry(registry, Arc::new(tasks)))
     }
 
-    /// Create with a custom agent registry
-    pub fn with_registry(registry: Arc<AgentRegistry>) -> Self {
+    /// Create with a custom agent registry and task store
+    pub fn with_registry(registry: Arc<AgentRegistry>, tasks: Arc<TaskStore>) -> Self {
+        let event_listeners = Arc::new(RwLock::new(Vec::new()));
+        tokio::spawn(forward_agent_events(
+            registry.subscribe(),
+            tasks.clone(),
+            event_listeners.clone(),
+        ));
+        tokio::spawn(maintain_tasks(tasks.clone(), event_listeners.clone()));
+
         Self {
             registry,
-            task_queues: Arc::new(RwLock::new(HashMap::new())),
-            event_listeners: Arc::new(RwLock::new(Vec::new())),
+            tasks,
+            event_listeners,
         }
     }
 
@@ -122,6 +148,102 @@ impl Orchestrator {
     }
 }
 
+/// Pass the agent supervisors' events on to the listeners, failing the
+/// running tasks of agents that died
+async fn forward_agent_events(
+    mut events: broadcast::Receiver<AgentEvent>,
+    tasks: Arc<TaskStore>,
+    listeners: Arc<RwLock<Vec<Box<dyn EventListener>>>>,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                log::warn!("Missed {} agent events", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        if let AgentEvent::Died {
+            instance_id,
+            reason,
+        } = &event
+        {
+            let reason = format!("Agent died: {}", reason);
+            if let Err(e) = tasks.fail_running_for_agent(instance_id, &reason) {
+                log::warn!("Failed to fail tasks of {}: {}", instance_id, e);
+            }
+        }
+
+        let listeners = listeners.read().await;
+        for listener in listeners.iter() {
+            match &event {
+                AgentEvent::Died {
+                    instance_id,
+                    reason,
+                } => listener.on_agent_died(instance_id, reason).await,
+                AgentEvent::Restarted {
+                    instance_id,
+                    agent_type,
+                    ..
+                } => listener.on_agent_spawned(instance_id, agent_type).await,
+                AgentEvent::HealthChanged {
+                    instance_id,
+                    healthy: false,
+                } => {
+                    listener
+                        .on_error(
+                            "health_check",
+                            &format!("Agent {} is unhealthy", instance_id),
+                        )
+                        .await
+                }
+                AgentEvent::HealthChanged { healthy: true, .. } => {}
+            }
+        }
+    }
+}
+
+/// Fail timed out tasks and purge those past the retention period
+async fn maintain_tasks(
+    tasks: Arc<TaskStore>,
+    listeners: Arc<RwLock<Vec<Box<dyn EventListener>>>>,
+) {
+    let mut interval = tokio::time::interval(TASK_MAINTENANCE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        match tasks.expire_timed_out() {
+            Ok(expired) => {
+                let listeners = listeners.read().await;
+                for task_id in expired {
+                    for listener in listeners.iter() {
+                        listener
+                            .on_error("task_timeout", &format!("Task {} timed out", task_id))
+                            .await;
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to expire timed out tasks: {}", e),
+        }
+        match tasks.purge_expired() {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {} finished tasks", purged),
+            Err(e) => log::warn!("Failed to purge finished tasks: {}", e),
+        }
+    }
+}
+
+fn task_error(e: anyhow::Error) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(format!("Task queue error: {}", e))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> zbus::fdo::Result<String> {
+    serde_json::to_string(value)
+        .map_err(|e| zbus::fdo::Error::Failed(format!("Serialization error: {}", e)))
+}
+
 #[interface(name = "org.dbusmcp.Orchestrator")]
 impl Orchestrator {
     /// Spawn a new agent instance dynamically
@@ -145,10 +267,6 @@ impl Orchestrator {
                 // Notify listeners
                 self.notify_agent_spawned(&agent_id, &agent_type).await;
 
-                // Initialize task queue for this agent
-                let mut queues = self.task_queues.write().await;
-                queues.insert(agent_id.clone(), Vec::new());
-
                 Ok(agent_id)
             }
             Err(e) => {
@@ -161,29 +279,26 @@ impl Orchestrator {
         }
     }
 
-    /// Send a task to an agent
+    /// Queue a task for an agent
+    ///
+    /// Optional `priority`, `max_retries` and `timeout_secs` fields of the
+    /// task set how it is scheduled.
     async fn send_task(&self, agent_id: String, task_json: String) -> zbus::fdo::Result<String> {
         let task_data: Value = serde_json::from_str(&task_json)
             .map_err(|e| zbus::fdo::Error::InvalidArgs(format!("Invalid task: {}", e)))?;
 
-        let task = Task {
-            id: uuid::Uuid::new_v4().to_string(),
-            agent_id: agent_id.clone(),
-            task_type: task_data["type"].as_str().unwrap_or("unknown").to_string(),
-            payload: task_data,
-            created_at: chrono::Utc::now(),
-            status: TaskStatus::Pending,
-        };
-
-        // Add to task queue
-        let mut queues = self.task_queues.write().await;
-        queues
-            .entry(agent_id.clone())
-            .or_insert_with(Vec::new)
-            .push(task.clone());
+        let mut task = NewTask::new(agent_id, task_data.clone());
+        if let Some(priority) = task_data["priority"].as_i64() {
+            task = task.with_priority(priority);
+        }
+        if let Some(max_retries) = task_data["max_retries"].as_u64() {
+            task = task.with_max_retries(max_retries as u32);
+        }
+        if let Some(timeout_secs) = task_data["timeout_secs"].as_u64() {
+            task = task.with_timeout(timeout_secs);
+        }
 
-        // In a real implementation, this would send to the agent via D-Bus
-        // For now, return the task ID
+        let task = self.tasks.enqueue(task).map_err(task_error)?;
         Ok(task.id)
     }
 
@@ -234,9 +349,14 @@ impl Orchestrator {
             Ok(()) => {
                 self.notify_agent_died(&agent_id, "Killed by user").await;
 
-                // Remove task queue
-                let mut queues = self.task_queues.write().await;
-                queues.remove(&agent_id);
+                // Nothing will run the agent's queued tasks any more
+                if let Err(e) = self
+                    .tasks
+                    .fail_running_for_agent(&agent_id, "Agent killed")
+                    .and_then(|_| self.tasks.cancel_pending_for_agent(&agent_id))
+                {
+                    log::warn!("Failed to cancel tasks of {}: {}", agent_id, e);
+                }
 
                 Ok(true)
             }
@@ -252,16 +372,23 @@ impl Orchestrator {
 
     /// Get pending tasks for an agent
     async fn get_pending_tasks(&self, agent_id: String) -> zbus::fdo::Result<String> {
-        let queues = self.task_queues.read().await;
-        let tasks = queues
-            .get(&agent_id)
-            .map(|tasks| tasks.clone())
-            .unwrap_or_default();
-
-        let tasks_json = serde_json::to_string(&tasks)
-            .map_err(|e| zbus::fdo::Error::Failed(format!("Serialization error: {}", e)))?;
+        let tasks = self
+            .tasks
+            .list(&TaskQuery {
+                agent_id: Some(agent_id),
+                status: Some(TaskStatus::Pending),
+                limit: None,
+            })
+            .map_err(task_error)?;
+        to_json(&tasks)
+    }
 
-        Ok(tasks_json)
+    /// Claim an agent's next task, or an empty string if it has none it may run
+    async fn claim_task(&self, agent_id: String) -> zbus::fdo::Result<String> {
+        match self.tasks.claim_next(&agent_id).map_err(task_error)? {
+            Some(task) => to_json(&task),
+            None => Ok(String::new()),
+        }
     }
 
     /// Mark a task as completed
@@ -269,22 +396,69 @@ impl Orchestrator {
         let result: Value = serde_json::from_str(&result_json)
             .map_err(|e| zbus::fdo::Error::InvalidArgs(format!("Invalid result: {}", e)))?;
 
-        // Find and update the task
-        let mut queues = self.task_queues.write().await;
-        for tasks in queues.values_mut() {
-            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
-                task.status = TaskStatus::Completed {
-                    result: result.clone(),
-                };
-
-                // Notify listeners
-                self.notify_task_completed(&task_id, &result).await;
+        if !self.tasks.complete(&task_id, &result).map_err(task_error)? {
+            return Ok(false);
+        }
+        self.notify_task_completed(&task_id, &result).await;
+        Ok(true)
+    }
 
-                return Ok(true);
+    /// Mark a task as failed, returning its new status
+    ///
+    /// A task with retries left is queued again as `pending`.
+    async fn fail_task(&self, task_id: String, error: String) -> zbus::fdo::Result<String> {
+        match self.tasks.fail(&task_id, &error).map_err(task_error)? {
+            Some(status) => {
+                if status == TaskStatus::Failed {
+                    self.notify_error("task_failed", &format!("Task {}: {}", task_id, error))
+                        .await;
+                }
+                Ok(status.as_str().to_string())
             }
+            None => Err(zbus::fdo::Error::Failed(format!(
+                "Task {} is not running",
+                task_id
+            ))),
+        }
+    }
+
+    /// List tasks matching a JSON filter (`agent_id`, `status`, `limit`)
+    async fn list_tasks(&self, filter_json: String) -> zbus::fdo::Result<String> {
+        let query: TaskQuery = if filter_json.is_empty() {
+            TaskQuery::default()
+        } else {
+            serde_json::from_str(&filter_json)
+                .map_err(|e| zbus::fdo::Error::InvalidArgs(format!("Invalid filter: {}", e)))?
+        };
+        to_json(&self.tasks.list(&query).map_err(task_error)?)
+    }
+
+    /// Get a task with its result or error
+    async fn get_task(&self, task_id: String) -> zbus::fdo::Result<String> {
+        match self.tasks.get(&task_id).map_err(task_error)? {
+            Some(task) => to_json(&task),
+            None => Err(zbus::fdo::Error::Failed(format!(
+                "Unknown task: {}",
+                task_id
+            ))),
         }
+    }
+
+    /// Cancel a pending or running task
+    async fn cancel_task(&self, task_id: String) -> zbus::fdo::Result<bool> {
+        self.tasks.cancel(&task_id).map_err(task_error)
+    }
+
+    /// Queue a finished task again, returning the new task's ID
+    async fn replay_task(&self, task_id: String) -> zbus::fdo::Result<String> {
+        let task = self.tasks.replay(&task_id).map_err(task_error)?;
+        Ok(task.id)
+    }
 
-        Ok(false)
+    /// Set how many tasks an agent runs at once
+    async fn set_task_concurrency(&self, agent_id: String, limit: u32) -> zbus::fdo::Result<()> {
+        self.tasks.set_concurrency_limit(&agent_id, limit as usize);
+        Ok(())
     }
 
     /// Signals
@@ -1,32 +1,32 @@
 //! Standalone MCP Chat Server with Unified Introspection Support
 //! Moved to chat module for tighter integration
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use axum::{
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
     extract::State,
+    http::HeaderMap,
     response::IntoResponse,
     routing::{get, post},
-    Json, Router,
+    Json,
 };
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
-use tower_http::{
-    cors::CorsLayer,
-    services::ServeDir,
-    trace::TraceLayer,
-};
 use tracing::{error, info};
 
+use crate::audit::{AuditRecord, Frontend};
 use crate::mcp::ollama::{self, OllamaClient};
 use crate::http_tls_server::*;
+use crate::mcp::plugin_tool_bridge::PluginToolBridge;
+use crate::mcp::tool_policy::ToolPolicy;
+use crate::mcp::tool_registry::{PolkitMiddleware, ToolRegistry};
 use crate::mcp::workflow_plugin_introspection;
-use crate::mcp::introspection_cache;
+use crate::mcp::streamable_http::{McpHandler, StreamableHttp};
 use super::orchestrator;
-use super::introspection::{self, introspect_server_config, ServerConfig};
+use super::introspection::introspect_server_config;
 use crate::plugin_system::{Plugin, PluginRegistry};
 use crate::plugins::network::NetworkPlugin;
 use crate::plugins::systemd::SystemdPlugin;
@@ -81,13 +81,17 @@ struct ChatState {
     // Enhanced capabilities: orchestrator for system orchestration
     _orchestrator: Arc<orchestrator::Orchestrator>,
     plugin_registry: Arc<PluginRegistry>,
+    // Core plugins as <plugin>_query/_plan/_apply tools
+    tool_registry: Arc<ToolRegistry>,
     start_time: std::time::SystemTime,
     // Model selection support
     available_models: Arc<Vec<String>>,
+    #[allow(dead_code)]
     conversation_models: Arc<RwLock<HashMap<String, String>>>, // conversation_id -> model_name
     // External MCP server integration
     mcp_registry: Arc<crate::mcp::external_mcp_client::McpServerRegistry>,
     // SSE event broadcaster
+    #[allow(dead_code)]
     sse_broadcaster: Arc<RwLock<crate::mcp::sse_streaming::SseEventBroadcaster>>,
 }
 
@@ -146,6 +150,25 @@ pub async fn run() -> Result<()> {
     // Auto-discover D-Bus plugins
     discover_dbus_plugins(&plugin_registry).await;
 
+    // Core plugins as tools. Remote chat users have no polkit subject, so
+    // their _apply tools are refused
+    let tool_registry = Arc::new(ToolRegistry::new());
+    let policy = ToolPolicy::load()?;
+    tool_registry
+        .set_approver(Arc::new(policy.unattended_approver("chat")))
+        .await;
+    tool_registry.set_policy(policy).await;
+    tool_registry
+        .add_middleware(Box::new(PolkitMiddleware::refusing(
+            "the chat server cannot identify its remote caller for polkit",
+        )))
+        .await;
+    PluginToolBridge::builtin(tool_registry.clone())
+        .await
+        .register_plugins_as_tools()
+        .await?;
+    info!("✅ Plugin tools registered");
+
     // Build unified tool introspection
     // This consolidates plugins (via PluginToolBridge) and native tools into one registry
     // Note: We use this instead of IntrospectionCache which has rusqlite Send+Sync issues
@@ -186,13 +209,10 @@ pub async fn run() -> Result<()> {
                             if server.enabled {
                                 let client = crate::mcp::external_mcp_client::McpClient::new(server.clone()).await;
                                 match client {
-                                    Ok(mut c) => {
-                                        if let Err(e) = c.connect().await {
-                                            error!("Failed to connect to MCP server {}: {}", server.name, e);
-                                        } else {
-                                            if let Err(e) = mcp_registry.register(c).await {
-                                                error!("Failed to register MCP server {}: {}", server.name, e);
-                                            }
+                                    Ok(c) => {
+                                        // Registering connects to the server
+                                        if let Err(e) = mcp_registry.register(c).await {
+                                            error!("Failed to register MCP server {}: {}", server.name, e);
                                         }
                                     }
                                     Err(e) => error!("Failed to create MCP client for {}: {}", server.name, e),
@@ -219,6 +239,7 @@ pub async fn run() -> Result<()> {
         tool_introspection: Arc::new(RwLock::new(tool_introspection)),
         _orchestrator: orchestrator,
         plugin_registry,
+        tool_registry,
         start_time: std::time::SystemTime::now(),
         available_models: Arc::new(available_models),
         conversation_models: Arc::new(RwLock::new(HashMap::new())),
@@ -231,7 +252,7 @@ pub async fn run() -> Result<()> {
     info!("   - Workflows: available");
 
     // Setup static file serving for the web UI
-    let web_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    let _web_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("src")
         .join("mcp")
         .join("web");
@@ -241,12 +262,36 @@ pub async fn run() -> Result<()> {
 
     // Create MCP chat service router with state
     let chat_state_arc = Arc::new(chat_state);
+
+    // JSON-RPC on /mcp speaks the streamable-HTTP transport; bodies in the
+    // legacy action protocol still go straight to mcp_handler
+    let mcp_handler_fn: McpHandler = {
+        let state = chat_state_arc.clone();
+        Arc::new(move |request: Value| {
+            let state = state.clone();
+            Box::pin(async move { handle_jsonrpc(&state, &request).await })
+        })
+    };
+    let mcp_transport = Arc::new(StreamableHttp::new(mcp_handler_fn));
     let chat_router = ServiceRouter::new("/api/chat")
         .route("/mcp", post({
             let state = chat_state_arc.clone();
-            move |Json(payload): Json<Value>| async move {
-                mcp_handler(State((*state).clone()), Json(payload)).await
+            let transport = mcp_transport.clone();
+            move |headers: HeaderMap, Json(payload): Json<Value>| async move {
+                if payload.is_array() || payload.get("jsonrpc").is_some() {
+                    transport.post(&headers, payload).await
+                } else {
+                    mcp_handler(State((*state).clone()), Json(payload)).await.into_response()
+                }
             }
+        })
+        .get({
+            let transport = mcp_transport.clone();
+            move |headers: HeaderMap| async move { transport.get(&headers) }
+        })
+        .delete({
+            let transport = mcp_transport.clone();
+            move |headers: HeaderMap| async move { transport.delete(&headers) }
         }))
         .route("/health", get(health_handler))
         .route("/status", get({
@@ -289,25 +334,18 @@ pub async fn run() -> Result<()> {
     let web_router = ServiceRouter::new("/")
         .static_dir("/", "chat-ui/build");
 
-    // Placeholder router for external MCP servers (dynamic forwarding)
-    let external_mcp_router = ServiceRouter::new("/api/mcp/:server")
-        .route("/", post(external_mcp_handler))
-        .route("/events", get(external_mcp_sse_handler));
-
-    // Register external MCP router
-    let server = ServerBuilder::new()
+    let mut builder = ServerBuilder::new()
         .bind_addr(format!("{}:{}", config.bind_host, config.http_port))
         .public_host(&config.public_host)
         .https_auto()
+        .auth(HttpAuth::from_env())
         .service_router(chat_router)
         .service_router(mcp_discover_router)
-        .service_router(external_mcp_router)
-        .service_router(web_router)
-        .build()
-        .await?;
-
-        .build()
-        .await?;
+        .service_router(web_router);
+    if let Some(client_auth) = ClientAuthConfig::from_env()? {
+        builder = builder.client_auth(client_auth);
+    }
+    let server = builder.build().await?;
 
     info!("🚀 MCP Chat Server starting...");
     server.serve().await?;
@@ -318,18 +356,33 @@ pub async fn run() -> Result<()> {
 // Enhanced MCP helper functions with orchestrator integration
 
 async fn get_available_tools(state: &ChatState) -> Vec<Value> {
+    // Plugin tools of the tool registry
+    let mut tools: Vec<Value> = state
+        .tool_registry
+        .list_tools()
+        .await
+        .into_iter()
+        .map(|tool| {
+            json!({
+                "name": tool.name,
+                "description": tool.description,
+                "inputSchema": tool.input_schema
+            })
+        })
+        .collect();
+
     // Get tools from unified introspection
     let tool_introspection = state.tool_introspection.read().await;
     if let Some(introspection) = tool_introspection.as_ref() {
-        if let Some(tools) = introspection.get("tools") {
-            if let Some(tools_array) = tools.as_array() {
-                return tools_array.clone();
-            }
+        if let Some(tools_array) = introspection.get("tools").and_then(|t| t.as_array()) {
+            tools.extend(tools_array.iter().cloned());
+            return tools;
         }
     }
 
     // Fallback: get orchestrator capabilities
-    get_orchestrator_tools(state).await
+    tools.extend(get_orchestrator_tools(state).await);
+    tools
 }
 
 async fn get_orchestrator_tools(_state: &ChatState) -> Vec<Value> {
@@ -422,7 +475,7 @@ async fn execute_tool_with_orchestration(
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
     info!("Executing tool with orchestration: {} with params {:?}", tool_name, parameters);
 
-    match tool_name {
+    let result = match tool_name {
         "orchestrate_system_task" => {
             orchestrate_system_task(state, parameters).await
         }
@@ -439,11 +492,17 @@ async fn execute_tool_with_orchestration(
             // Try to execute as regular tool
             execute_regular_tool(state, tool_name, parameters).await
         }
-    }
+    };
+
+    AuditRecord::new(Frontend::Chat, tool_name)
+        .with_params(parameters)
+        .with_result(&result)
+        .record();
+    result
 }
 
 async fn orchestrate_system_task(
-    state: &ChatState,
+    _state: &ChatState,
     parameters: &Value,
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
     let task_type = parameters.get("task_type")
@@ -503,11 +562,11 @@ async fn dbus_discovery(
 
     // Apply filters to placeholder data
     let filtered_services: Vec<_> = services_data.into_iter()
-        .filter(|s| service_filter.map_or(true, |f| s.contains(f)))
+        .filter(|s| service_filter.is_none_or(|f| s.contains(f)))
         .collect();
 
     let filtered_interfaces: Vec<_> = interfaces_data.into_iter()
-        .filter(|i| interface_filter.map_or(true, |f| i.contains(f)))
+        .filter(|i| interface_filter.is_none_or(|f| i.contains(f)))
         .collect();
 
     Ok(json!({
@@ -537,7 +596,7 @@ async fn system_introspect(
             "physical" => {
                 results["physical"] = json!({
                     "cpu_count": num_cpus::get(),
-                    "memory": sys_info::mem_info().map(|m| m.total as u64).unwrap_or(0),
+                    "memory": sys_info::mem_info().map(|m| m.total).unwrap_or(0),
                     "hostname": gethostname::gethostname().to_string_lossy()
                 });
             }
@@ -614,6 +673,14 @@ async fn execute_regular_tool(
     tool_name: &str,
     parameters: &Value,
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    if state.tool_registry.get_tool(tool_name).await.is_some() {
+        let result = state
+            .tool_registry
+            .execute_tool(tool_name, parameters.clone())
+            .await?;
+        return Ok(serde_json::to_value(result)?);
+    }
+
     // Check if this is a plugin tool
     if tool_name.starts_with("plugin_") {
         return execute_plugin_tool(state, tool_name, parameters).await;
@@ -663,15 +730,12 @@ async fn execute_plugin_tool(
             }))
         }
         "apply" => {
-            // The parameters should contain the desired state
-            // If parameters has a "state" field, use that, otherwise use parameters as state
-            let desired_state = parameters.get("state").unwrap_or(parameters).clone();
-            plugin.apply_state(desired_state).await?;
-            Ok(json!({
-                "status": "success",
-                "plugin": plugin_name,
-                "message": "State applied successfully"
-            }))
+            // Changes only go through the authorized <plugin>_apply tool
+            let result = state
+                .tool_registry
+                .execute_tool(&format!("{}_apply", plugin_name), parameters.clone())
+                .await?;
+            Ok(serde_json::to_value(result)?)
         }
         "diff" => {
             let current = plugin.get_state().await?;
@@ -764,105 +828,122 @@ async fn send_chat_message_with_orchestration(
     Ok(response)
 }
 
-// MCP handler for proxy server - enhanced chatbot with orchestration capabilities
-async fn mcp_handler(
-    State(state): State<ChatState>,
-    Json(request): Json<Value>,
-) -> impl IntoResponse {
-    info!("MCP request received: {:?}", request);
+/// Protocol versions the chat server speaks; the first is preferred
+const MCP_PROTOCOL_VERSIONS: [&str; 2] = ["2025-03-26", "2024-11-05"];
+
+/// Answer one JSON-RPC 2.0 MCP request
+async fn handle_jsonrpc(state: &ChatState, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request.get("method").and_then(|v| v.as_str()).unwrap_or_default();
+
+    if request.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        return json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": -32600,
+                "message": "Invalid JSON-RPC version"
+            }
+        });
+    }
 
-    // Check if this is a JSON-RPC 2.0 MCP request
-    if let (Some(jsonrpc), Some(method), Some(id)) = (
-        request.get("jsonrpc").and_then(|v| v.as_str()),
-        request.get("method").and_then(|v| v.as_str()),
-        request.get("id")
-    ) {
-        if jsonrpc != "2.0" {
-            return Json(json!({
+    let params = request.get("params").cloned().unwrap_or(json!({}));
+
+    match method {
+        "initialize" => {
+            let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+            let version = requested
+                .filter(|v| MCP_PROTOCOL_VERSIONS.contains(v))
+                .unwrap_or(MCP_PROTOCOL_VERSIONS[0]);
+            json!({
                 "jsonrpc": "2.0",
                 "id": id,
-                "error": {
-                    "code": -32600,
-                    "message": "Invalid JSON-RPC version"
+                "result": {
+                    "protocolVersion": version,
+                    "serverInfo": {
+                        "name": "op-dbus-mcp-server",
+                        "version": "1.0.0"
+                    },
+                    "capabilities": {
+                        "tools": {
+                            "listChanged": true
+                        }
+                    }
                 }
-            }));
+            })
         }
 
-        let params = request.get("params").cloned().unwrap_or(json!({}));
+        "ping" => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {}
+        }),
 
-        return match method {
-            "initialize" => {
-                Json(json!({
-                    "jsonrpc": "2.0",
-                    "id": id,
-                    "result": {
-                        "protocolVersion": "2024-11-05",
-                        "serverInfo": {
-                            "name": "op-dbus-mcp-server",
-                            "version": "1.0.0"
-                        },
-                        "capabilities": {
-                            "tools": {
-                                "listChanged": true
-                            }
-                        }
-                    }
-                }))
-            }
+        "tools/list" => {
+            let tools = get_available_tools(state).await;
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "tools": tools
+                }
+            })
+        }
 
-            "tools/list" => {
-                let tools = get_available_tools(&state).await;
-                Json(json!({
-                    "jsonrpc": "2.0",
-                    "id": id,
-                    "result": {
-                        "tools": tools
-                    }
-                }))
-            }
+        "tools/call" => {
+            let tool_name = params.get("name").and_then(|v| v.as_str());
+            let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
 
-            "tools/call" => {
-                let tool_name = params.get("name").and_then(|v| v.as_str());
-                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
-
-                if let Some(tool_name) = tool_name {
-                    let result = execute_tool_with_orchestration(&state, tool_name, &arguments).await;
-                    match result {
-                        Ok(response) => Json(json!({
-                            "jsonrpc": "2.0",
-                            "id": id,
-                            "result": response
-                        })),
-                        Err(error) => Json(json!({
-                            "jsonrpc": "2.0",
-                            "id": id,
-                            "error": {
-                                "code": -32603,
-                                "message": error.to_string()
-                            }
-                        }))
-                    }
-                } else {
-                    Json(json!({
+            if let Some(tool_name) = tool_name {
+                let result = execute_tool_with_orchestration(state, tool_name, &arguments).await;
+                match result {
+                    Ok(response) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": response
+                    }),
+                    Err(error) => json!({
                         "jsonrpc": "2.0",
                         "id": id,
                         "error": {
-                            "code": -32602,
-                            "message": "Missing tool name"
+                            "code": -32603,
+                            "message": error.to_string()
                         }
-                    }))
+                    })
                 }
+            } else {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": -32602,
+                        "message": "Missing tool name"
+                    }
+                })
             }
+        }
 
-            _ => Json(json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "error": {
-                    "code": -32601,
-                    "message": format!("Method not found: {}", method)
-                }
-            }))
-        };
+        _ => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": -32601,
+                "message": format!("Method not found: {}", method)
+            }
+        })
+    }
+}
+
+// MCP handler for proxy server - enhanced chatbot with orchestration capabilities
+async fn mcp_handler(
+    State(state): State<ChatState>,
+    Json(request): Json<Value>,
+) -> impl IntoResponse {
+    info!("MCP request received: {:?}", request);
+
+    // Check if this is a JSON-RPC 2.0 MCP request
+    if request.get("jsonrpc").is_some() && request.get("method").is_some() && request.get("id").is_some() {
+        return Json(handle_jsonrpc(&state, &request).await);
     }
 
     // Fallback to legacy action-based protocol
@@ -945,7 +1026,8 @@ async fn mcp_handler(
     }
 }
 
-// WebSocket handler for chat
+// WebSocket handler for chat, not routed yet
+#[allow(dead_code)]
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<ChatState>,
@@ -953,7 +1035,8 @@ async fn websocket_handler(
     ws.on_upgrade(|socket| handle_socket(socket, state))
 }
 
-async fn handle_socket(mut socket: WebSocket, state: ChatState) {
+#[allow(dead_code)]
+async fn handle_socket(socket: WebSocket, state: ChatState) {
     let (mut sender, mut receiver) = socket.split();
 
     // Generate a simple conversation ID
@@ -967,88 +1050,83 @@ async fn handle_socket(mut socket: WebSocket, state: ChatState) {
 
     while let Some(Ok(message)) = receiver.next().await {
         if let Message::Text(text) = message {
-            // Parse incoming message
-            if let Ok(chat_msg) = serde_json::from_str::<ChatMessage>(&text) {
-                match chat_msg {
-                    ChatMessage::User { content, .. } => {
-                        // Get system context for enhanced AI awareness
-                        let system_context = get_system_context(&state).await;
-
-                        // Store user message
-                        let timestamp = std::time::SystemTime::now()
-                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs();
-
-                        let user_msg = ChatMessage::User {
-                            content: content.clone(),
-                            timestamp,
-                            context: system_context.clone(),
-                        };
-
-                        // Add to conversation
-                        {
-                            let mut conversations = state.conversations.write().await;
-                            conversations
-                                .entry(conversation_id.clone())
-                                .or_insert_with(Vec::new)
-                                .push(user_msg.clone());
-                        }
+            // Parse incoming message; only user messages are answered
+            if let Ok(ChatMessage::User { content, .. }) = serde_json::from_str::<ChatMessage>(&text) {
+                // Get system context for enhanced AI awareness
+                let system_context = get_system_context(&state).await;
+
+                // Store user message
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+
+                let user_msg = ChatMessage::User {
+                    content: content.clone(),
+                    timestamp,
+                    context: system_context.clone(),
+                };
+
+                // Add to conversation
+                {
+                    let mut conversations = state.conversations.write().await;
+                    conversations
+                        .entry(conversation_id.clone())
+                        .or_insert_with(Vec::new)
+                        .push(user_msg.clone());
+                }
 
-                        // Send back the user message for UI update
-                        if let Ok(response) = serde_json::to_string(&user_msg) {
-                            let _ = sender.send(Message::Text(response)).await;
-                        }
+                // Send back the user message for UI update
+                if let Ok(response) = serde_json::to_string(&user_msg) {
+                    let _ = sender.send(Message::Text(response)).await;
+                }
 
-                        // Generate AI response using the AI brain
-                        {
-                            // Build context-aware prompt with system information
-                            let enhanced_prompt = build_enhanced_prompt(&content, &system_context);
-                            let model = state.ollama_client.default_model();
-
-                            match state.ollama_client.simple_chat(&model, &enhanced_prompt).await {
-                                Ok(ai_response) => {
-                                    let ai_msg = ChatMessage::Assistant {
-                                        content: ai_response,
-                                        timestamp: std::time::SystemTime::now()
-                                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                                            .unwrap()
-                                            .as_secs(),
-                                        tools_used: None,
-                                    };
-
-                                    // Add to conversation
-                                    {
-                                        let mut conversations = state.conversations.write().await;
-                                        conversations
-                                            .entry(conversation_id.clone())
-                                            .or_insert_with(Vec::new)
-                                            .push(ai_msg.clone());
-                                    }
+                // Generate AI response using the AI brain
+                {
+                    // Build context-aware prompt with system information
+                    let enhanced_prompt = build_enhanced_prompt(&content, &system_context);
+                    let model = state.ollama_client.default_model();
+
+                    match state.ollama_client.simple_chat(&model, &enhanced_prompt).await {
+                        Ok(ai_response) => {
+                            let ai_msg = ChatMessage::Assistant {
+                                content: ai_response,
+                                timestamp: std::time::SystemTime::now()
+                                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                                    .unwrap()
+                                    .as_secs(),
+                                tools_used: None,
+                            };
+
+                            // Add to conversation
+                            {
+                                let mut conversations = state.conversations.write().await;
+                                conversations
+                                    .entry(conversation_id.clone())
+                                    .or_insert_with(Vec::new)
+                                    .push(ai_msg.clone());
+                            }
 
-                                    // Send AI response
-                                    if let Ok(response) = serde_json::to_string(&ai_msg) {
-                                        let _ = sender.send(Message::Text(response)).await;
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("AI chat error: {}", e);
-                                    let error_msg = ChatMessage::Error {
-                                        content: format!("AI chat failed: {}", e),
-                                        timestamp: std::time::SystemTime::now()
-                                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                                            .unwrap()
-                                            .as_secs(),
-                                    };
-
-                                    if let Ok(response) = serde_json::to_string(&error_msg) {
-                                        let _ = sender.send(Message::Text(response)).await;
-                                    }
-                                }
+                            // Send AI response
+                            if let Ok(response) = serde_json::to_string(&ai_msg) {
+                                let _ = sender.send(Message::Text(response)).await;
+                            }
+                        }
+                        Err(e) => {
+                            error!("AI chat error: {}", e);
+                            let error_msg = ChatMessage::Error {
+                                content: format!("AI chat failed: {}", e),
+                                timestamp: std::time::SystemTime::now()
+                                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                                    .unwrap()
+                                    .as_secs(),
+                            };
+
+                            if let Ok(response) = serde_json::to_string(&error_msg) {
+                                let _ = sender.send(Message::Text(response)).await;
                             }
                         }
                     }
-                    _ => {} // Ignore other message types
                 }
             }
         }
@@ -1099,6 +1177,7 @@ async fn get_system_context(state: &ChatState) -> Option<SystemContext> {
 }
 
 /// Build enhanced prompt with unified system context
+#[allow(dead_code)] // Used by the WebSocket chat
 fn build_enhanced_prompt(user_input: &str, context: &Option<SystemContext>) -> String {
     // Get workflow and plugin information
     let wp_introspection = workflow_plugin_introspection::WorkflowPluginIntrospection::new();
@@ -1168,15 +1247,7 @@ async fn status_handler(State(state): State<ChatState>) -> impl IntoResponse {
         "status": "active",
         "tool_count": tool_count,
         "ollama_available": state.ollama_client.is_available().await,
-        "uptime_seconds": std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() - state.start_time
-            .duration_since(std::time::UNIX_EPOCH)
-                            .as_secs() - state.start_time
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs()
+        "uptime_seconds": state.start_time.elapsed().map(|d| d.as_secs()).unwrap_or(0)
     }))
 }
 
@@ -1201,7 +1272,7 @@ async fn mcp_config_handler(State(state): State<ChatState>) -> impl IntoResponse
 }
 
 /// MCP config for Claude Desktop – uses client_config_generator for formatting
-async fn mcp_claude_config_handler(State(state): State<ChatState>) -> impl IntoResponse {
+async fn mcp_claude_config_handler(State(_state): State<ChatState>) -> impl IntoResponse {
     // Use the generator module to produce Claude‑compatible config
     match crate::mcp::client_config_generator::generate_claude_config("http://localhost:8080") {
         Ok(cfg) => {
@@ -1219,11 +1290,10 @@ async fn models_handler(State(state): State<ChatState>) -> impl IntoResponse {
     // Build models list from config + dynamic discovery
     let models: Vec<Value> = state.available_models.iter().map(|model_name| {
         // Determine provider from model name
-        let (provider, display_name) = if model_name.contains("meta-llama") {
-            ("huggingface", format!("🤗 {}", model_name))
-        } else if model_name.contains("mistralai") {
-            ("huggingface", format!("🤗 {}", model_name))  
-        } else if model_name.contains("google") {
+        let (provider, display_name) = if model_name.contains("meta-llama")
+            || model_name.contains("mistralai")
+            || model_name.contains("google")
+        {
             ("huggingface", format!("🤗 {}", model_name))
         } else {
             ("ollama", model_name.clone())
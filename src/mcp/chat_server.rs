@@ -13,6 +13,7 @@ use tower_http::cors::CorsLayer;
 use super::agent_registry::AgentRegistry;
 use super::ollama::OllamaClient;
 use super::tool_registry::{ToolRegistry, ToolRegistryService};
+use crate::audit::{AuditRecord, Frontend};
 
 // Chat message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -385,14 +386,14 @@ impl ChatServerState {
         tool_name: &str,
         params: HashMap<String, serde_json::Value>,
     ) -> ChatMessage {
-        match self
-            .tool_registry
-            .execute_tool(
-                tool_name,
-                serde_json::Value::Object(params.into_iter().collect()),
-            )
-            .await
-        {
+        let params = serde_json::Value::Object(params.into_iter().collect());
+        let result = self.tool_registry.execute_tool(tool_name, params.clone()).await;
+        AuditRecord::new(Frontend::Chat, tool_name)
+            .with_params(&params)
+            .with_result(&result)
+            .record();
+
+        match result {
             Ok(result) => {
                 // Extract natural language response from tool result
                 let content_str = if result.content.is_empty() {
@@ -914,7 +915,7 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: ChatServerSt
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(Message::Text(text))) = receiver.next().await {
             // Process the message
-            let response = state.process_message(&conversation_id, &text).await;
+            let _response = state.process_message(&conversation_id, &text).await;
 
             // Response is sent via broadcast, so no need to send directly
         }
@@ -933,7 +934,7 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: ChatServerSt
 
 // REST API endpoints
 pub async fn get_suggestions(
-    State(state): State<ChatServerState>,
+    State(_state): State<ChatServerState>,
     Json(payload): Json<HashMap<String, String>>,
 ) -> Json<Vec<String>> {
     let partial = payload.get("partial").map(|s| s.as_str()).unwrap_or("");
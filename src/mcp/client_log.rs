@@ -0,0 +1,102 @@
+//! Log records forwarded to the MCP client as `notifications/message`
+//!
+//! Records still go to stderr through env_logger. Once the client sends
+//! `logging/setLevel`, records at or above that level are also sent to it.
+
+use anyhow::{bail, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Value};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::mpsc;
+
+static LOGGER: OnceLock<ClientLogger> = OnceLock::new();
+
+/// Logger writing to stderr and, once asked to, to the client
+pub struct ClientLogger {
+    stderr: env_logger::Logger,
+    client_level: Mutex<LevelFilter>,
+    notifications: mpsc::UnboundedSender<Value>,
+}
+
+/// Install the logger; `notifications` carries messages to the client
+pub fn init(notifications: mpsc::UnboundedSender<Value>) -> Result<()> {
+    let stderr = env_logger::Builder::from_default_env().build();
+    let max_level = stderr.filter();
+    let logger = LOGGER.get_or_init(|| ClientLogger {
+        stderr,
+        client_level: Mutex::new(LevelFilter::Off),
+        notifications,
+    });
+    log::set_logger(logger)?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Apply `logging/setLevel` with an MCP (syslog) level name
+pub fn set_level(level: &str) -> Result<()> {
+    let filter = level_filter(level)?;
+    if let Some(logger) = LOGGER.get() {
+        *logger.client_level.lock().unwrap() = filter;
+        log::set_max_level(filter.max(logger.stderr.filter()));
+    }
+    Ok(())
+}
+
+fn level_filter(level: &str) -> Result<LevelFilter> {
+    Ok(match level {
+        "debug" => LevelFilter::Debug,
+        "info" | "notice" => LevelFilter::Info,
+        "warning" => LevelFilter::Warn,
+        "error" | "critical" | "alert" | "emergency" => LevelFilter::Error,
+        _ => bail!("Unknown log level '{}'", level),
+    })
+}
+
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warning",
+        Level::Info => "info",
+        Level::Debug | Level::Trace => "debug",
+    }
+}
+
+impl Log for ClientLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || metadata.level() <= *self.client_level.lock().unwrap()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+        if record.level() <= *self.client_level.lock().unwrap() {
+            let _ = self.notifications.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {
+                    "level": level_name(record.level()),
+                    "logger": record.target(),
+                    "data": record.args().to_string(),
+                }
+            }));
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_mapping() {
+        assert_eq!(level_filter("notice").unwrap(), LevelFilter::Info);
+        assert_eq!(level_filter("critical").unwrap(), LevelFilter::Error);
+        assert!(level_filter("verbose").is_err());
+        assert_eq!(level_name(Level::Warn), "warning");
+    }
+}
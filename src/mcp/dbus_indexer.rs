@@ -309,11 +309,7 @@ impl DbusIndexer {
         )
         .await?;
 
-        // Call GetManagedObjects with proper zbus 3.14.1 API
-        let result: Result<HashMap<String, HashMap<String, HashMap<String, OwnedValue>>>, _> =
-            proxy.call("GetManagedObjects", &()).await;
-
-        Ok(result?)
+        Ok(proxy.call("GetManagedObjects", &()).await?)
     }
 
     /// Build ServiceIndex from ObjectManager data
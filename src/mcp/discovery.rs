@@ -187,6 +187,8 @@ impl DbusDiscovery {
     }
 }
 
+/// Entry point of the dbus-mcp-discovery binary
+#[allow(dead_code)] // unused when built into the library
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== D-Bus MCP Discovery Service ===\n");
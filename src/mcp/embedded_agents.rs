@@ -34,8 +34,7 @@ pub fn load_comprehensive_agents() -> HashMap<String, Resource> {
 
                     // Generate human-readable name and description
                     let display_name = agent_name
-                        .replace('-', " ")
-                        .replace('_', " ")
+                        .replace(['-', '_'], " ")
                         .split_whitespace()
                         .map(|word| {
                             let mut chars = word.chars();
@@ -2,17 +2,27 @@
 //!
 //! Connects to external MCP servers via stdio, HTTP, or SSE
 //! Aggregates tools from multiple MCP servers
+//!
+//! Progress the server reports for a tool call is passed on to the caller's
+//! `ProgressReporter`, and dropping a call before it finished cancels it on
+//! the server. `notifications/tools/list_changed` and the end of a stdio
+//! server are published as [`ClientEvent`]s.
 
+use super::tool_registry::{current_progress, ProgressReporter, SecurityLevel};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio::sync::RwLock;
-use std::sync::Arc;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
 
 /// MCP server transport type
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,236 +53,550 @@ pub struct McpServerConfig {
     pub description: String,
     pub transport: McpTransport,
     pub enabled: bool,
+    /// Security level of the server's tools when re-exported; High if not set
+    #[serde(default)]
+    pub security_level: Option<SecurityLevel>,
+}
+
+/// External MCP servers, as read from `mcp-servers.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpServersConfig {
+    #[serde(default)]
+    pub servers: Vec<McpServerConfig>,
 }
 
 /// Tool definition from external MCP server
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpTool {
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(rename = "inputSchema", alias = "input_schema", default)]
     pub input_schema: Value,
 }
 
+/// Something that happened on a server's connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// The server announced `notifications/tools/list_changed`
+    ToolsChanged,
+    /// The connection ended; calls fail until the client connects again
+    Disconnected,
+}
+
+/// State shared by a client and the tasks reading from its server
+struct Shared {
+    name: String,
+    /// Calls waiting for their response, by request id
+    pending: Mutex<HashMap<u64, oneshot::Sender<Value>>>,
+    /// Where progress of running calls goes, by progress token
+    progress: Mutex<HashMap<u64, ProgressReporter>>,
+    events: broadcast::Sender<ClientEvent>,
+}
+
+impl Shared {
+    /// Handle a message from the server, returning the reply it needs
+    fn dispatch(&self, message: Value) -> Option<Value> {
+        let Some(method) = message["method"].as_str() else {
+            // A response to one of our requests
+            let id = message["id"].as_u64()?;
+            if let Some(waiter) = self.pending.lock().unwrap().remove(&id) {
+                let _ = waiter.send(message);
+            }
+            return None;
+        };
+
+        match (method, message.get("id")) {
+            ("notifications/progress", None) => {
+                let params = &message["params"];
+                let reporter = params["progressToken"]
+                    .as_u64()
+                    .and_then(|token| self.progress.lock().unwrap().get(&token).cloned());
+                if let Some(reporter) = reporter {
+                    let text = match params["message"].as_str() {
+                        Some(text) => text.to_string(),
+                        None => match params["total"].as_f64() {
+                            Some(total) => format!("{}/{}", params["progress"], total),
+                            None => params["progress"].to_string(),
+                        },
+                    };
+                    reporter.report(&text);
+                }
+                None
+            }
+            ("notifications/tools/list_changed", None) => {
+                let _ = self.events.send(ClientEvent::ToolsChanged);
+                None
+            }
+            (method, None) => {
+                debug!("Ignoring notification {} from {}", method, self.name);
+                None
+            }
+            ("ping", Some(id)) => Some(json!({"jsonrpc": "2.0", "id": id, "result": {}})),
+            (method, Some(id)) => Some(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": -32601, "message": format!("Method not found: {}", method)}
+            })),
+        }
+    }
+}
+
+/// Connection to a server started as a child process
+struct StdioConnection {
+    /// Messages for the server's stdin
+    outgoing: mpsc::UnboundedSender<Value>,
+    closed: Arc<AtomicBool>,
+    tasks: Vec<JoinHandle<()>>,
+    _process: Child,
+}
+
+impl Drop for StdioConnection {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Connection to a server over streamable HTTP
+#[derive(Clone)]
+struct HttpConnection {
+    client: reqwest::Client,
+    url: String,
+    headers: HashMap<String, String>,
+    session: Arc<Mutex<Option<String>>>,
+}
+
+impl HttpConnection {
+    /// POST a message, returning the response to it if it has an id
+    async fn post(&self, message: &Value, shared: &Shared) -> Result<Option<Value>> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(session) = self.session.lock().unwrap().clone() {
+            request = request.header("Mcp-Session-Id", session);
+        }
+
+        let response = request.send().await.context("HTTP request failed")?;
+        if !response.status().is_success() {
+            anyhow::bail!("HTTP MCP server returned error: {}", response.status());
+        }
+        if let Some(session) = response.headers().get("Mcp-Session-Id") {
+            *self.session.lock().unwrap() = session.to_str().ok().map(str::to_string);
+        }
+        if message.get("id").is_none() {
+            return Ok(None);
+        }
+
+        let is_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_stream {
+            return Ok(Some(response.json().await?));
+        }
+
+        // Notifications such as progress may come before the response
+        let body = response.text().await?;
+        let mut result = None;
+        for data in body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        {
+            if data.get("method").is_none() && data["id"] == message["id"] {
+                result = Some(data);
+            } else {
+                shared.dispatch(data);
+            }
+        }
+        result.map(Some).context("No response in event stream")
+    }
+}
+
+enum Connection {
+    Stdio(StdioConnection),
+    Http(HttpConnection),
+}
+
+impl Connection {
+    fn is_open(&self) -> bool {
+        match self {
+            Connection::Stdio(stdio) => !stdio.closed.load(Ordering::SeqCst),
+            Connection::Http(_) => true,
+        }
+    }
+
+    /// Send a notification without waiting for it to be delivered
+    fn notify(&self, message: Value, shared: &Arc<Shared>) {
+        match self {
+            Connection::Stdio(stdio) => {
+                let _ = stdio.outgoing.send(message);
+            }
+            Connection::Http(http) => {
+                let http = http.clone();
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = http.post(&message, &shared).await {
+                        debug!("Failed to notify {}: {}", shared.name, e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Forgets a call when it ends, and cancels it on the server if it did not
+/// end with a response
+struct CallGuard<'a> {
+    client: &'a McpClient,
+    connection: &'a Arc<Connection>,
+    id: u64,
+    answered: bool,
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.client.shared.pending.lock().unwrap().remove(&self.id);
+        self.client.shared.progress.lock().unwrap().remove(&self.id);
+        if !self.answered && self.connection.is_open() {
+            debug!("Cancelling request {} on {}", self.id, self.client.name);
+            self.connection.notify(
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/cancelled",
+                    "params": {"requestId": self.id, "reason": "Cancelled by the client"}
+                }),
+                &self.client.shared,
+            );
+        }
+    }
+}
+
 /// External MCP client
 pub struct McpClient {
     pub name: String,
     config: McpServerConfig,
-    process: Option<Child>,
+    connection: RwLock<Option<Arc<Connection>>>,
+    shared: Arc<Shared>,
     tools: Arc<RwLock<Vec<McpTool>>>,
-    initialized: Arc<RwLock<bool>>,
+    next_id: AtomicU64,
 }
 
 impl McpClient {
     /// Create a new MCP client
     pub async fn new(config: McpServerConfig) -> Result<Self> {
+        let (events, _) = broadcast::channel(16);
         let client = Self {
             name: config.name.clone(),
+            shared: Arc::new(Shared {
+                name: config.name.clone(),
+                pending: Mutex::new(HashMap::new()),
+                progress: Mutex::new(HashMap::new()),
+                events,
+            }),
             config,
-            process: None,
+            connection: RwLock::new(None),
             tools: Arc::new(RwLock::new(Vec::new())),
-            initialized: Arc::new(RwLock::new(false)),
+            next_id: AtomicU64::new(1),
         };
 
         Ok(client)
     }
 
-    /// Connect to the MCP server
-    pub async fn connect(&mut self) -> Result<()> {
-        match &self.config.transport {
+    pub fn config(&self) -> &McpServerConfig {
+        &self.config
+    }
+
+    /// Events of this server's connections
+    pub fn subscribe(&self) -> broadcast::Receiver<ClientEvent> {
+        self.shared.events.subscribe()
+    }
+
+    /// Whether the client is connected and the server still running
+    pub async fn is_connected(&self) -> bool {
+        self.connection
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|connection| connection.is_open())
+    }
+
+    /// Connect to the MCP server, replacing an earlier connection
+    pub async fn connect(&self) -> Result<()> {
+        self.disconnect().await;
+
+        let connection = match &self.config.transport {
             McpTransport::Stdio { command, args, env } => {
-                self.connect_stdio(command, args, env.as_ref()).await
-            }
-            McpTransport::Http { url, headers } => {
-                self.connect_http(url, headers.as_ref()).await
+                self.connect_stdio(command, args, env.as_ref())?
             }
+            McpTransport::Http { url, headers } => self.connect_http(url, headers.as_ref()),
             McpTransport::Sse { url, headers } => {
-                self.connect_sse(url, headers.as_ref()).await
+                // SSE servers answer POSTed requests on an event stream
+                self.connect_http(url, headers.as_ref())
             }
+        };
+        *self.connection.write().await = Some(Arc::new(connection));
+
+        if let Err(e) = self.initialize().await {
+            self.disconnect().await;
+            return Err(e);
         }
+        let tools = self.refresh_tools().await?;
+        info!(
+            "✅ Connected to MCP server: {} ({} tools)",
+            self.name,
+            tools.len()
+        );
+        Ok(())
+    }
+
+    /// Close the connection; a stdio server is stopped
+    pub async fn disconnect(&self) {
+        self.connection.write().await.take();
     }
 
     /// Connect via stdio
-    async fn connect_stdio(
-        &mut self,
+    fn connect_stdio(
+        &self,
         command: &str,
         args: &[String],
         env: Option<&HashMap<String, String>>,
-    ) -> Result<()> {
+    ) -> Result<Connection> {
         info!("Connecting to MCP server: {} via stdio", self.name);
 
         let mut cmd = Command::new(command);
         cmd.args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         if let Some(env_vars) = env {
             cmd.envs(env_vars);
         }
 
-        let child = cmd.spawn()
+        let mut process = cmd
+            .spawn()
             .context(format!("Failed to spawn MCP server: {}", command))?;
+        let mut stdin = process.stdin.take().context("No stdin for MCP server")?;
+        let stdout = process.stdout.take().context("No stdout for MCP server")?;
+        let stderr = process.stderr.take().context("No stderr for MCP server")?;
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Value>();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let writer = tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                let mut line = message.to_string();
+                line.push('\n');
+                if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
 
-        self.process = Some(child);
+        let reader = {
+            let shared = self.shared.clone();
+            let outgoing = outgoing.clone();
+            let closed = closed.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    match serde_json::from_str::<Value>(&line) {
+                        Ok(message) => {
+                            if let Some(reply) = shared.dispatch(message) {
+                                let _ = outgoing.send(reply);
+                            }
+                        }
+                        Err(e) => debug!("Invalid message from {}: {}", shared.name, e),
+                    }
+                }
 
-        // Initialize MCP connection
-        self.send_initialize().await?;
+                // Waiting calls fail once their senders are gone
+                closed.store(true, Ordering::SeqCst);
+                shared.pending.lock().unwrap().clear();
+                warn!("MCP server {} closed its connection", shared.name);
+                let _ = shared.events.send(ClientEvent::Disconnected);
+            })
+        };
 
-        info!("✅ Connected to MCP server: {}", self.name);
-        Ok(())
+        let logger = {
+            let name = self.name.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    debug!("{}: {}", name, line);
+                }
+            })
+        };
+
+        Ok(Connection::Stdio(StdioConnection {
+            outgoing,
+            closed,
+            tasks: vec![writer, reader, logger],
+            _process: process,
+        }))
     }
 
     /// Connect via HTTP
-    async fn connect_http(
-        &mut self,
-        url: &str,
-        _headers: Option<&HashMap<String, String>>,
-    ) -> Result<()> {
-        info!("Connecting to MCP server: {} via HTTP at {}", self.name, url);
-        
-        // Send initialize request
-        let client = reqwest::Client::new();
-        let init_request = json!({
-            "jsonrpc": "2.0",
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
-                "clientInfo": {
-                    "name": "op-dbus",
-                    "version": env!("CARGO_PKG_VERSION")
-                }
-            },
-            "id": 1
-        });
+    fn connect_http(&self, url: &str, headers: Option<&HashMap<String, String>>) -> Connection {
+        info!(
+            "Connecting to MCP server: {} via HTTP at {}",
+            self.name, url
+        );
+        Connection::Http(HttpConnection {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            headers: headers.cloned().unwrap_or_default(),
+            session: Arc::new(Mutex::new(None)),
+        })
+    }
 
-        let response = client.post(url)
-            .json(&init_request)
-            .send()
-            .await
-            .context("Failed to connect to HTTP MCP server")?;
+    /// Send initialize request (MCP protocol)
+    async fn initialize(&self) -> Result<()> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "op-dbus",
+                        "version": env!("CARGO_PKG_VERSION")
+                    }
+                }),
+            )
+            .await?;
+        debug!("MCP initialize response from {}: {}", self.name, result);
+
+        self.notify("notifications/initialized", json!({})).await
+    }
 
-        if response.status().is_success() {
-            info!("✅ Connected to HTTP MCP server: {}", self.name);
-            self.fetch_tools().await?;
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("HTTP MCP server returned error: {}", response.status()))
-        }
+    async fn connection(&self) -> Result<Arc<Connection>> {
+        self.connection
+            .read()
+            .await
+            .clone()
+            .filter(|connection| connection.is_open())
+            .with_context(|| format!("MCP server {} is not connected", self.name))
     }
 
-    /// Connect via SSE
-    async fn connect_sse(
-        &mut self,
-        url: &str,
-        _headers: Option<&HashMap<String, String>>,
-    ) -> Result<()> {
-        info!("Connecting to MCP server: {} via SSE at {}", self.name, url);
-        // SSE client implementation would go here
-        // For now, fall back to HTTP
-        self.connect_http(url, None).await
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        let connection = self.connection().await?;
+        connection.notify(
+            json!({"jsonrpc": "2.0", "method": method, "params": params}),
+            &self.shared,
+        );
+        Ok(())
     }
 
-    /// Send initialize request (MCP protocol)
-    async fn send_initialize(&mut self) -> Result<()> {
-        let init_request = json!({
-            "jsonrpc": "2.0",
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
-                "clientInfo": {
-                    "name": "op-dbus",
-                    "version": env!("CARGO_PKG_VERSION")
-                }
-            },
-            "id": 1
-        });
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let connection = self.connection().await?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.send_request(&connection, id, method, params).await
+    }
 
-        if let Some(process) = &mut self.process {
-            if let Some(stdin) = process.stdin.as_mut() {
-                let request_str = serde_json::to_string(&init_request)?;
-                stdin.write_all(request_str.as_bytes()).await?;
-                stdin.write_all(b"\n").await?;
-                stdin.flush().await?;
-
-                // Read response
-                if let Some(stdout) = process.stdout.as_mut() {
-                    let mut reader = BufReader::new(stdout);
-                    let mut response_line = String::new();
-                    reader.read_line(&mut response_line).await?;
-                    
-                    debug!("MCP initialize response: {}", response_line);
-                }
+    /// Send a request and wait for the server's result
+    ///
+    /// Dropped before the response came, the request is cancelled on the
+    /// server.
+    async fn send_request(
+        &self,
+        connection: &Arc<Connection>,
+        id: u64,
+        method: &str,
+        params: Value,
+    ) -> Result<Value> {
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let mut guard = CallGuard {
+            client: self,
+            connection,
+            id,
+            answered: false,
+        };
 
-                *self.initialized.write().await = true;
-                self.fetch_tools().await?;
+        let response = match connection.as_ref() {
+            Connection::Stdio(stdio) => {
+                let (tx, rx) = oneshot::channel();
+                self.shared.pending.lock().unwrap().insert(id, tx);
+                stdio
+                    .outgoing
+                    .send(message)
+                    .ok()
+                    .with_context(|| format!("MCP server {} is not connected", self.name))?;
+                rx.await
+                    .with_context(|| format!("MCP server {} closed the connection", self.name))?
             }
+            Connection::Http(http) => http
+                .post(&message, &self.shared)
+                .await?
+                .context("Empty response")?,
+        };
+        guard.answered = true;
+
+        if let Some(error) = response.get("error") {
+            anyhow::bail!(
+                "{} failed on {}: {}",
+                method,
+                self.name,
+                error["message"].as_str().unwrap_or("unknown error")
+            );
         }
-
-        Ok(())
+        Ok(response["result"].clone())
     }
 
     /// Fetch available tools from the MCP server
-    async fn fetch_tools(&self) -> Result<()> {
-        let tools_request = json!({
-            "jsonrpc": "2.0",
-            "method": "tools/list",
-            "params": {},
-            "id": 2
-        });
-
-        // For stdio
-        if let Some(_process) = &self.process {
-            // Would send request and parse response
-            // For now, return placeholder
-            debug!("Fetching tools from {} (stdio)", self.name);
+    pub async fn refresh_tools(&self) -> Result<Vec<McpTool>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            let page: Vec<McpTool> = serde_json::from_value(result["tools"].clone())
+                .with_context(|| format!("Invalid tool list from {}", self.name))?;
+            tools.extend(page);
+
+            match result["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
         }
 
-        Ok(())
+        debug!("Fetched {} tools from {}", tools.len(), self.name);
+        *self.tools.write().await = tools.clone();
+        Ok(tools)
     }
 
     /// Call a tool on this MCP server
+    ///
+    /// Returns the server's `tools/call` result. Progress goes to the current
+    /// call's `ProgressReporter`, if there is one.
     pub async fn call_tool(&self, tool_name: &str, arguments: Value) -> Result<Value> {
-        let call_request = json!({
-            "jsonrpc": "2.0",
-            "method": "tools/call",
-            "params": {
-                "name": tool_name,
-                "arguments": arguments
-            },
-            "id": 3
-        });
+        let connection = self.connection().await?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
 
-        // Implementation depends on transport
-        match &self.config.transport {
-            McpTransport::Stdio { .. } => {
-                // Send via stdin, read from stdout
-                Ok(json!({
-                    "result": "Tool execution via stdio (placeholder)"
-                }))
-            }
-            McpTransport::Http { url, .. } => {
-                let client = reqwest::Client::new();
-                let response = client.post(url)
-                    .json(&call_request)
-                    .send()
-                    .await?
-                    .json::<Value>()
-                    .await?;
-                
-                Ok(response)
-            }
-            McpTransport::Sse { .. } => {
-                Ok(json!({
-                    "result": "Tool execution via SSE (placeholder)"
-                }))
-            }
+        let mut params = json!({
+            "name": tool_name,
+            "arguments": arguments
+        });
+        if let Some(reporter) = current_progress() {
+            self.shared.progress.lock().unwrap().insert(id, reporter);
+            params["_meta"] = json!({"progressToken": id});
         }
+
+        self.send_request(&connection, id, "tools/call", params)
+            .await
     }
 
     /// Get list of tools from this server
@@ -286,18 +610,10 @@ impl McpClient {
     }
 }
 
-impl Drop for McpClient {
-    fn drop(&mut self) {
-        if let Some(mut process) = self.process.take() {
-            let _ = process.start_kill();
-        }
-    }
-}
-
 /// MCP server registry - manages multiple external MCP servers
 /// Each server gets its own endpoint
 pub struct McpServerRegistry {
-    servers: Arc<RwLock<HashMap<String, Arc<RwLock<McpClient>>>>>,
+    servers: Arc<RwLock<HashMap<String, Arc<McpClient>>>>,
 }
 
 impl McpServerRegistry {
@@ -308,22 +624,27 @@ impl McpServerRegistry {
     }
 
     /// Register an MCP server
-    pub async fn register(&self, mut client: McpClient) -> Result<()> {
-        let name = client.name.clone();
-        
+    pub async fn register(&self, client: McpClient) -> Result<()> {
         // Connect to the server
         client.connect().await?;
-        
-        let servers = self.servers.clone();
-        let mut servers_write = servers.write().await;
-        servers_write.insert(name.clone(), Arc::new(RwLock::new(client)));
-        
-        info!("Registered MCP server: {} (available at /api/mcp/{})", name, name);
+        self.insert(Arc::new(client)).await;
         Ok(())
     }
 
+    /// Register a client without connecting it
+    pub async fn insert(&self, client: Arc<McpClient>) {
+        let name = client.name.clone();
+        let mut servers = self.servers.write().await;
+        servers.insert(name.clone(), client);
+
+        info!(
+            "Registered MCP server: {} (available at /api/mcp/{})",
+            name, name
+        );
+    }
+
     /// Get a specific MCP server by name
-    pub async fn get_server(&self, name: &str) -> Option<Arc<RwLock<McpClient>>> {
+    pub async fn get_server(&self, name: &str) -> Option<Arc<McpClient>> {
         let servers = self.servers.read().await;
         servers.get(name).cloned()
     }
@@ -340,10 +661,7 @@ impl McpServerRegistry {
         let mut all_tools = Vec::new();
 
         for (server_name, client) in servers.iter() {
-            let client_guard = client.read().await;
-            let tools = client_guard.get_tools().await;
-            
-            for tool in tools {
+            for tool in client.get_tools().await {
                 all_tools.push((server_name.clone(), tool));
             }
         }
@@ -354,25 +672,26 @@ impl McpServerRegistry {
     /// Get tools from a specific server
     pub async fn get_server_tools(&self, server_name: &str) -> Result<Vec<McpTool>> {
         let servers = self.servers.read().await;
-        
+
         if let Some(client) = servers.get(server_name) {
-            let client_guard = client.read().await;
-            Ok(client_guard.get_tools().await)
+            Ok(client.get_tools().await)
         } else {
             Err(anyhow::anyhow!("MCP server not found: {}", server_name))
         }
     }
 
     /// Call a tool on a specific server
-    pub async fn call_tool(&self, server_name: &str, tool_name: &str, arguments: Value) -> Result<Value> {
-        let servers = self.servers.read().await;
-        
-        if let Some(client) = servers.get(server_name) {
-            let client_guard = client.read().await;
-            client_guard.call_tool(tool_name, arguments).await
-        } else {
-            Err(anyhow::anyhow!("MCP server not found: {}", server_name))
-        }
+    pub async fn call_tool(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        arguments: Value,
+    ) -> Result<Value> {
+        let client = self
+            .get_server(server_name)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("MCP server not found: {}", server_name))?;
+        client.call_tool(tool_name, arguments).await
     }
 
     /// Get metadata for all servers (for discovery)
@@ -380,12 +699,13 @@ impl McpServerRegistry {
         let servers = self.servers.read().await;
         let mut metadata = Vec::new();
 
-        for (name, _client) in servers.iter() {
+        for (name, client) in servers.iter() {
             metadata.push(json!({
                 "name": name,
                 "endpoint": format!("/api/mcp/{}", name),
                 "protocol": "MCP JSON-RPC 2.0",
-                "sse_endpoint": format!("/api/mcp/{}/events", name)
+                "sse_endpoint": format!("/api/mcp/{}/events", name),
+                "connected": client.is_connected().await
             }));
         }
 
@@ -207,6 +207,7 @@ pub async fn start_hybrid_bridge() -> Result<()> {
 }
 
 /// CLI tool for testing the hybrid bridge
+#[allow(dead_code)] // unused when built into the library
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
@@ -7,13 +7,13 @@ use anyhow::{Context, Result};
 use rusqlite::{params, Connection};
 use serde_json::Value as JsonValue;
 use std::path::Path;
-use std::sync::RwLock;
+use std::sync::Mutex;
 use zbus_xml::Node as XmlNode;
 
 /// Introspection cache storing JSON representations
-/// Uses RwLock to make Connection Send+Sync for async contexts
+/// Uses a Mutex to make Connection Send+Sync for async contexts
 pub struct IntrospectionCache {
-    conn: RwLock<Connection>,
+    conn: Mutex<Connection>,
 }
 
 impl IntrospectionCache {
@@ -67,7 +67,7 @@ impl IntrospectionCache {
         )?;
 
         Ok(Self {
-            conn: RwLock::new(conn),
+            conn: Mutex::new(conn),
         })
     }
 
@@ -94,7 +94,7 @@ impl IntrospectionCache {
         for interface in node.interfaces() {
             let iface_name = interface.name().as_ref().to_string();
 
-            self.conn.write().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
+            self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
                 "INSERT OR REPLACE INTO introspection_cache
                  (service_name, object_path, interface_name, cached_at, introspection_json)
                  VALUES (?1, ?2, ?3, ?4, ?5)",
@@ -107,7 +107,8 @@ impl IntrospectionCache {
                 let method_json = serde_json::json!({
                     "name": method.name().as_ref(),
                     "in_args": method.args().iter()
-                        .filter(|a| matches!(a.direction(), Some(zbus_xml::ArgDirection::In)))
+                        // Method args without a direction are inputs
+                        .filter(|a| !matches!(a.direction(), Some(zbus_xml::ArgDirection::Out)))
                         .map(|a| {
                             serde_json::json!({
                                 "name": a.name(),
@@ -126,7 +127,7 @@ impl IntrospectionCache {
                         .collect::<Vec<_>>(),
                 });
 
-                self.conn.write().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
+                self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
                     "INSERT OR REPLACE INTO service_methods
                      (service_name, interface_name, method_name, signature_json)
                      VALUES (?1, ?2, ?3, ?4)",
@@ -148,7 +149,7 @@ impl IntrospectionCache {
                     zbus_xml::PropertyAccess::ReadWrite => "readwrite",
                 };
 
-                self.conn.write().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
+                self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
                     "INSERT OR REPLACE INTO service_properties
                      (service_name, interface_name, property_name, type_signature, access)
                      VALUES (?1, ?2, ?3, ?4, ?5)",
@@ -177,7 +178,7 @@ impl IntrospectionCache {
                         .collect::<Vec<_>>(),
                 });
 
-                self.conn.write().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
+                self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
                     "INSERT OR REPLACE INTO service_signals
                      (service_name, interface_name, signal_name, signature_json)
                      VALUES (?1, ?2, ?3, ?4)",
@@ -202,14 +203,14 @@ impl IntrospectionCache {
         interface_name: Option<&str>,
     ) -> Result<Option<JsonValue>> {
         let query = if let Some(iface) = interface_name {
-            self.conn.read().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
+            self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
                 "SELECT introspection_json FROM introspection_cache
                  WHERE service_name = ?1 AND object_path = ?2 AND interface_name = ?3",
                 params![service_name, object_path, iface],
                 |row| row.get::<_, String>(0),
             )
         } else {
-            self.conn.read().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
+            self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
                 "SELECT introspection_json FROM introspection_cache
                  WHERE service_name = ?1 AND object_path = ?2
                  LIMIT 1",
@@ -227,7 +228,7 @@ impl IntrospectionCache {
 
     /// Get all methods for a service interface
     pub fn get_methods_json(&self, service_name: &str, interface_name: &str) -> Result<JsonValue> {
-        let conn = self.conn.read().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?;
         let mut stmt = conn.prepare(
             "SELECT method_name, signature_json FROM service_methods
              WHERE service_name = ?1 AND interface_name = ?2",
@@ -246,9 +247,62 @@ impl IntrospectionCache {
         Ok(serde_json::json!({ "methods": methods }))
     }
 
+    /// Input and output argument types of a method, if it is cached
+    pub fn get_method_signature(
+        &self,
+        service_name: &str,
+        interface_name: &str,
+        method_name: &str,
+    ) -> Result<Option<(Vec<String>, Vec<String>)>> {
+        let query = self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
+            "SELECT signature_json FROM service_methods
+             WHERE service_name = ?1 AND interface_name = ?2 AND method_name = ?3",
+            params![service_name, interface_name, method_name],
+            |row| row.get::<_, String>(0),
+        );
+        let json_str = match query {
+            Ok(json_str) => json_str,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let method: JsonValue = serde_json::from_str(&json_str)?;
+        let types = |key: &str| {
+            method[key]
+                .as_array()
+                .map(|args| {
+                    args.iter()
+                        .filter_map(|a| a["type"].as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Ok(Some((types("in_args"), types("out_args"))))
+    }
+
+    /// Type signature of a property, if it is cached
+    pub fn get_property_signature(
+        &self,
+        service_name: &str,
+        interface_name: &str,
+        property_name: &str,
+    ) -> Result<Option<String>> {
+        let query = self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
+            "SELECT type_signature FROM service_properties
+             WHERE service_name = ?1 AND interface_name = ?2 AND property_name = ?3",
+            params![service_name, interface_name, property_name],
+            |row| row.get::<_, String>(0),
+        );
+        match query {
+            Ok(signature) => Ok(Some(signature)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Search for methods by name pattern
     pub fn search_methods(&self, pattern: &str) -> Result<Vec<JsonValue>> {
-        let conn = self.conn.read().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let conn = self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?;
         let mut stmt = conn.prepare(
             "SELECT service_name, interface_name, signature_json
              FROM service_methods
@@ -344,8 +398,8 @@ impl IntrospectionCache {
             .as_secs() as i64
             - (days * 86400) as i64;
 
-        let count = self.conn.write().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
-            "DELETE FROM introspection_cache WHERE cached_at < ?1",
+        let count = self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.execute(
+            "DELETE FROM introspection_cache WHERE cached_at <= ?1",
             params![cutoff],
         )?;
 
@@ -354,7 +408,7 @@ impl IntrospectionCache {
 
     /// Get cache statistics
     pub fn get_stats(&self) -> Result<JsonValue> {
-        let total_services: i64 = self.conn.read().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
+        let total_services: i64 = self.conn.lock().map_err(|e| anyhow::anyhow!("{}", e))?.query_row(
             "SELECT COUNT(DISTINCT service_name) FROM introspection_cache",
             [],
             |row| row.get(0),
@@ -362,17 +416,17 @@ impl IntrospectionCache {
 
         let total_interfaces: i64 =
             self.conn
-                .read().map_err(|e| anyhow::anyhow!("{}", e))?
+                .lock().map_err(|e| anyhow::anyhow!("{}", e))?
                 .query_row("SELECT COUNT(*) FROM introspection_cache", [], |row| {
                     row.get(0)
                 })?;
 
         let total_methods: i64 =
             self.conn
-                .read().map_err(|e| anyhow::anyhow!("{}", e))?
+                .lock().map_err(|e| anyhow::anyhow!("{}", e))?
                 .query_row("SELECT COUNT(*) FROM service_methods", [], |row| row.get(0))?;
 
-        let db_size = std::fs::metadata(self.conn.read().unwrap().path().unwrap())?.len();
+        let db_size = std::fs::metadata(self.conn.lock().unwrap().path().unwrap())?.len();
 
         Ok(serde_json::json!({
             "services": total_services,
@@ -411,7 +465,17 @@ mod tests {
         assert!(json.is_some());
 
         let methods = cache.get_methods_json("org.freedesktop.DBus", "org.freedesktop.DBus")?;
-        assert!(methods["methods"].as_array().unwrap().len() > 0);
+        assert!(!methods["methods"].as_array().unwrap().is_empty());
+
+        let (in_args, out_args) = cache
+            .get_method_signature("org.freedesktop.DBus", "org.freedesktop.DBus", "Hello")?
+            .unwrap();
+        assert!(in_args.is_empty());
+        assert_eq!(out_args, vec!["s"]);
+        assert_eq!(
+            cache.get_property_signature("org.freedesktop.DBus", "org.freedesktop.DBus", "Features")?,
+            Some("as".to_string())
+        );
 
         Ok(())
     }
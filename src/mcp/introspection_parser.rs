@@ -292,43 +292,8 @@ impl IntrospectionParser {
     }
 }
 
-// Example usage demo
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_example() {
-        let xml = r#"
-<node>
-  <interface name="org.freedesktop.systemd1.Manager">
-    <method name="StartUnit">
-      <arg name="name" type="s" direction="in"/>
-      <arg name="mode" type="s" direction="in"/>
-      <arg name="job" type="o" direction="out"/>
-    </method>
-    <method name="StopUnit">
-      <arg name="name" type="s" direction="in"/>
-      <arg name="mode" type="s" direction="in"/>
-      <arg name="job" type="o" direction="out"/>
-    </method>
-    <property name="Version" type="s" access="read"/>
-  </interface>
-</node>
-        "#;
-
-        let data = IntrospectionParser::parse_xml(xml);
-        let json = IntrospectionParser::to_json(&data);
-
-        println!("JSON output:\n{}", json);
-
-        assert_eq!(data.interfaces.len(), 1);
-        assert_eq!(data.interfaces[0].methods.len(), 2);
-        assert_eq!(data.interfaces[0].properties.len(), 1);
-    }
-}
-
 // CLI tool to convert XML → JSON
+#[allow(dead_code)] // unused when built into the library
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     use std::io::Read;
 
@@ -373,3 +338,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+// Example usage demo
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_example() {
+        let xml = r#"
+<node>
+  <interface name="org.freedesktop.systemd1.Manager">
+    <method name="StartUnit">
+      <arg name="name" type="s" direction="in"/>
+      <arg name="mode" type="s" direction="in"/>
+      <arg name="job" type="o" direction="out"/>
+    </method>
+    <method name="StopUnit">
+      <arg name="name" type="s" direction="in"/>
+      <arg name="mode" type="s" direction="in"/>
+      <arg name="job" type="o" direction="out"/>
+    </method>
+    <property name="Version" type="s" access="read"/>
+  </interface>
+</node>
+        "#;
+
+        let data = IntrospectionParser::parse_xml(xml);
+        let json = IntrospectionParser::to_json(&data);
+
+        println!("JSON output:\n{}", json);
+
+        assert_eq!(data.interfaces.len(), 1);
+        assert_eq!(data.interfaces[0].methods.len(), 2);
+        assert_eq!(data.interfaces[0].properties.len(), 1);
+    }
+}
@@ -21,12 +21,12 @@
 //! 6. Create templates for similar objects
 
 use anyhow::{Context, Result};
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::Path;
 use regex::Regex;
-use chrono::{DateTime, Utc};
 
 // ============================================================================
 // INTROSPECTIVE GADGET - THE OBJECT INSPECTOR
@@ -36,7 +36,7 @@ use chrono::{DateTime, Utc};
 #[derive(Clone)]
 pub struct IntrospectiveGadget {
     knowledge_base: std::sync::Arc<tokio::sync::RwLock<crate::mcp::native_introspection::KnowledgeBase>>,
-    parsers: std::sync::Arc<std::sync::RwLock<HashMap<String, Box<dyn ObjectParser + Send + Sync>>>>,
+    parsers: std::sync::Arc<tokio::sync::RwLock<HashMap<String, Box<dyn ObjectParser + Send + Sync>>>>,
 }
 
 impl IntrospectiveGadget {
@@ -55,7 +55,7 @@ impl IntrospectiveGadget {
 
         Ok(Self {
             knowledge_base,
-            parsers: std::sync::Arc::new(std::sync::RwLock::new(parsers)),
+            parsers: std::sync::Arc::new(tokio::sync::RwLock::new(parsers)),
         })
     }
 
@@ -73,7 +73,7 @@ impl IntrospectiveGadget {
         let mut errors = Vec::new();
 
         // Try the detected format first
-        if let Some(parser) = self.parsers.read().unwrap().get(&detected_format) {
+        if let Some(parser) = self.parsers.read().await.get(&detected_format) {
             match parser.parse(&input).await {
                 Ok(result) => results.push(result),
                 Err(e) => errors.push(format!("{} parser failed: {}", detected_format, e)),
@@ -82,7 +82,7 @@ impl IntrospectiveGadget {
 
         // If that didn't work, try auto-detection
         if results.is_empty() {
-            if let Some(auto_parser) = self.parsers.read().unwrap().get("auto") {
+            if let Some(auto_parser) = self.parsers.read().await.get("auto") {
                 match auto_parser.parse(&input).await {
                     Ok(result) => results.push(result),
                     Err(e) => errors.push(format!("Auto parser failed: {}", e)),
@@ -92,11 +92,11 @@ impl IntrospectiveGadget {
 
         // Try all parsers if still no results
         if results.is_empty() {
-            for (format_name, parser) in self.parsers.read().unwrap().iter() {
+            for (format_name, parser) in self.parsers.read().await.iter() {
                 if format_name != &detected_format && format_name != "auto" {
-                    match parser.parse(&input).await {
-                        Ok(result) => results.push(result),
-                        Err(_) => {} // Don't log errors for fallback attempts
+                    // Don't log errors for fallback attempts
+                    if let Ok(result) = parser.parse(&input).await {
+                        results.push(result);
                     }
                 }
             }
@@ -137,7 +137,7 @@ impl IntrospectiveGadget {
     pub async fn inspect_docker_container(&self, container_name: &str) -> Result<ContainerInspectionWithKnowledge> {
         // Get container info
         let inspect_output = tokio::process::Command::new("docker")
-            .args(&["inspect", container_name])
+            .args(["inspect", container_name])
             .output()
             .await
             .context("Failed to run docker inspect")?;
@@ -164,7 +164,7 @@ impl IntrospectiveGadget {
 
         // Get running processes
         let top_output = tokio::process::Command::new("docker")
-            .args(&["top", container_name])
+            .args(["top", container_name])
             .output()
             .await;
 
@@ -435,7 +435,7 @@ impl IntrospectiveGadget {
             }
         }
 
-        patterns.sort_by(|a, b| b.count.cmp(&a.count));
+        patterns.sort_by_key(|p| std::cmp::Reverse(p.count));
         patterns.truncate(10); // Top 10 patterns
 
         patterns
@@ -607,7 +607,7 @@ impl ObjectSchema {
         for (prop_name, prop) in &self.properties {
             match prop.data_type.as_str() {
                 "string" => {
-                    if let Some(pattern) = &prop.pattern {
+                    if let Some(_pattern) = &prop.pattern {
                         rules.push(format!("{}_format", prop_name));
                     }
                 }
@@ -810,11 +810,7 @@ impl JsonParser {
                 }
             }
             Value::Array(arr) => {
-                let item_schema = if let Some(first) = arr.first() {
-                    Some(Box::new(self.analyze_json_schema(first)))
-                } else {
-                    None
-                };
+                let item_schema = arr.first().map(|first| Box::new(self.analyze_json_schema(first)));
 
                 ObjectSchema {
                     schema_type: "array".to_string(),
@@ -902,7 +898,7 @@ impl ObjectParser for DockerParser {
         if let InspectionSource::DockerContainer(name) = &input.source {
             // Run docker inspect
             let output = tokio::process::Command::new("docker")
-                .args(&["inspect", name])
+                .args(["inspect", name])
                 .output()
                 .await?;
 
@@ -938,7 +934,7 @@ impl ObjectParser for BinaryParser {
 
         Ok(ParsedObject {
             data: json!({
-                "binary_data": base64::encode(bytes),
+                "binary_data": base64::engine::general_purpose::STANDARD.encode(bytes),
                 "size": bytes.len(),
                 "entropy": calculate_entropy(bytes),
             }),
@@ -1000,7 +996,7 @@ struct AutoParser;
 #[async_trait::async_trait]
 impl ObjectParser for AutoParser {
     async fn parse(&self, input: &InspectionInput) -> Result<ParsedObject> {
-        let data = input.data.as_ref()
+        let _data = input.data.as_ref()
             .ok_or_else(|| anyhow::anyhow!("No data provided for auto parsing"))?;
 
         // Try JSON first
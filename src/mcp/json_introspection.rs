@@ -63,7 +63,7 @@ impl JsonIntrospectionParser {
     }
 
     /// Fetch JSON introspection from HTTP endpoint
-    pub async fn fetch_http(url: &str) -> Result<JsonIntrospection, Box<dyn std::error::Error>> {
+    pub async fn fetch_http(_url: &str) -> Result<JsonIntrospection, Box<dyn std::error::Error>> {
         // Would use reqwest in production
         // For now, return error
         Err("HTTP fetching not yet implemented".into())
@@ -1,35 +1,37 @@
 //! Refactored MCP server using tool registry for loose coupling
 
-#[path = "../mcp/introspection_cache.rs"]
-mod introspection_cache;
-#[path = "../native/mod.rs"]
-mod native;
-#[path = "../mcp/resources_enhanced.rs"]
-mod resources;
-#[path = "../mcp/tool_registry.rs"]
-mod tool_registry;
-#[path = "../mcp/tools/agents.rs"]
-mod agents;
-#[path = "../mcp/tools/dbus_granular.rs"]
-mod dbus_granular;
-#[path = "../mcp/introspection_tools.rs"]
-mod introspection_tools;
+use op_dbus::mcp::resources_enhanced as resources;
+use op_dbus::mcp::tools::{agents, dbus_granular};
+use op_dbus::mcp::{
+    client_log, introspection_cache, introspection_tools, mcp_gateway,
+    plugin_tool_bridge::PluginToolBridge, prompts, state_resources, tool_policy, tool_registry,
+};
+use op_dbus::native;
 
 use anyhow::{Context, Result};
-use reqwest;
+use prompts::PromptRegistry;
 use resources::ResourceRegistry;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::io::{self, BufRead, Write};
-use std::sync::Arc;
+use state_resources::{StateResources, STATE_URI_PREFIX};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+use tool_policy::{Approver, PolicyDenial, ToolPolicy, POLICY_DENIED};
 use tool_registry::{
-    AuditMiddleware, DynamicToolBuilder, LoggingMiddleware, SecurityMiddleware, Tool, ToolContent, ToolRegistry,
-    ToolRegistryService, ToolResult,
+    with_progress, AuditMiddleware, DynamicToolBuilder, LoggingMiddleware, PolkitMiddleware,
+    ProgressReporter, SecurityContext, SecurityLevel, SecurityMiddleware, ToolContent,
+    ToolMetadata, ToolRegistry, ToolRegistryService, ToolResult,
 };
 use zbus::Connection;
 
 // Import introspection components
 use introspection_cache::IntrospectionCache;
+use mcp_gateway::McpGateway;
 use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,17 +60,138 @@ struct McpError {
     data: Option<Value>,
 }
 
+impl McpResponse {
+    fn result(id: Option<Value>, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Option<Value>, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(McpError {
+                code,
+                message: message.into(),
+                data: None,
+            }),
+        }
+    }
+}
+
+/// How long the operator has to answer an approval request
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Asks the operator to approve tool calls through the client, with
+/// `elicitation/create` requests
+struct ElicitationApprover {
+    outgoing: mpsc::UnboundedSender<Value>,
+    /// Requests awaiting the client's response, by JSON-encoded id
+    pending: Mutex<HashMap<String, oneshot::Sender<Value>>>,
+    next_id: AtomicU64,
+    /// Whether the client declared the elicitation capability
+    supported: AtomicBool,
+}
+
+impl ElicitationApprover {
+    fn new(outgoing: mpsc::UnboundedSender<Value>) -> Self {
+        Self {
+            outgoing,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            supported: AtomicBool::new(false),
+        }
+    }
+
+    /// Hand a response from the client to the request waiting for it
+    fn resolve(&self, response: Value) {
+        let key = response["id"].to_string();
+        match self.pending.lock().unwrap().remove(&key) {
+            Some(waiting) => {
+                let _ = waiting.send(response);
+            }
+            None => log::debug!("Ignoring response to unknown request {}", key),
+        }
+    }
+
+    /// Fail the requests still waiting, once the client is gone
+    fn close(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+#[async_trait::async_trait]
+impl Approver for ElicitationApprover {
+    async fn approve(&self, metadata: &ToolMetadata, params: &Value) -> Result<bool> {
+        if !self.supported.load(Ordering::SeqCst) {
+            anyhow::bail!("the client does not support elicitation");
+        }
+
+        let id = json!(format!("approval-{}", self.next_id.fetch_add(1, Ordering::SeqCst)));
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.to_string(), tx);
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "elicitation/create",
+            "params": {
+                "message": format!(
+                    "Allow {:?} security tool '{}' to run with arguments {}?",
+                    metadata.security_level, metadata.name, params
+                ),
+                "requestedSchema": {
+                    "type": "object",
+                    "properties": {
+                        "approve": {
+                            "type": "boolean",
+                            "title": "Approve",
+                            "description": metadata.description,
+                        }
+                    },
+                    "required": ["approve"]
+                }
+            }
+        }));
+
+        let response = match tokio::time::timeout(APPROVAL_TIMEOUT, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => anyhow::bail!("the client went away"),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id.to_string());
+                return Ok(false);
+            }
+        };
+        if let Some(error) = response.get("error") {
+            anyhow::bail!("elicitation failed: {}", error);
+        }
+        let result = &response["result"];
+        Ok(result["action"] == "accept" && result["content"]["approve"] == true)
+    }
+}
+
 /// Refactored MCP server with tool registry service and embedded resources
 struct McpServer {
     registry_service: Arc<ToolRegistryService>,
     resources: Arc<ResourceRegistry>,
-    orchestrator: Option<zbus::Proxy<'static>>,
+    prompts: PromptRegistry,
+    state: StateResources,
+    _orchestrator: Option<zbus::Proxy<'static>>,
+    /// Responses and notifications to the client, written to stdout
+    outgoing: mpsc::UnboundedSender<Value>,
+    /// Requests still being handled, by JSON-encoded id, for cancellation
+    in_flight: Mutex<HashMap<String, AbortHandle>>,
+    approver: Arc<ElicitationApprover>,
 }
 
 // Orchestrator proxy will be created manually
 
 impl McpServer {
-    async fn new() -> Result<Self> {
+    async fn new(outgoing: mpsc::UnboundedSender<Value>) -> Result<Self> {
         // Create tool registry
         let registry = Arc::new(ToolRegistry::new());
 
@@ -78,12 +201,52 @@ impl McpServer {
             .add_middleware(Box::new(AuditMiddleware::new()))
             .await;
 
-        // Add security middleware (must be added last to run after other middleware)
-        registry.add_middleware(Box::new(SecurityMiddleware::new())).await;
+        // The client on the other end of stdio is identified by its peer
+        // credentials; calls are checked against the tool policy as its session
+        let subject = native::polkit::Subject::stdio_peer()?;
+        let security = SecurityMiddleware::new();
+        security
+            .set_security_context(SecurityContext {
+                user_id: Some(subject.to_string()),
+                authenticated: true,
+                ..Default::default()
+            })
+            .await;
+        registry.add_middleware(Box::new(security)).await;
+        registry.set_policy(ToolPolicy::load()?).await;
+
+        // High and Critical tools run once the operator approved them
+        let approver = Arc::new(ElicitationApprover::new(outgoing.clone()));
+        registry.set_approver(approver.clone()).await;
+
+        // Live plugin state from the op-dbus service
+        let state = StateResources::connect(outgoing.clone()).await;
+
+        // Plugin changes are authorized for the client on the other end of stdio
+        let authority = native::polkit::PolkitAuthority::system().await?;
+        eprintln!("Authorizing plugin changes for {}", subject);
+        registry
+            .add_middleware(Box::new(PolkitMiddleware::new(authority, subject)))
+            .await;
 
         // Register default tools
         Self::register_default_tools(&registry).await?;
 
+        // Plugins as <plugin>_query, _plan and _apply tools, behind the polkit
+        // middleware above
+        PluginToolBridge::builtin(registry.clone())
+            .await
+            .register_plugins_as_tools()
+            .await?;
+
+        // Tools of external MCP servers, re-exported as <server>__<tool>
+        let gateway = McpGateway::new(registry.clone(), outgoing.clone());
+        match gateway.load_config().await {
+            Ok(0) => {}
+            Ok(count) => eprintln!("Re-exporting tools of {} external MCP servers", count),
+            Err(e) => eprintln!("Failed to load external MCP servers: {}", e),
+        }
+
         // Create resource registry with embedded documentation
         let resources = Arc::new(ResourceRegistry::new());
         eprintln!(
@@ -91,9 +254,13 @@ impl McpServer {
             resources.list_resources().len()
         );
 
+        // Prompts from the embedded agent definitions
+        let prompts = PromptRegistry::embedded();
+        eprintln!("Loaded {} agent prompts", prompts.len());
+
         // Initialize D-Bus introspection cache
         let cache_path = PathBuf::from("/var/cache/dbus-introspection.db");
-        let cache =
+        let _cache =
             IntrospectionCache::new(&cache_path).context("Failed to create introspection cache")?;
         eprintln!("✅ D-Bus introspection cache ready at {:?}", cache_path);
 
@@ -131,12 +298,15 @@ impl McpServer {
         Ok(Self {
             registry_service,
             resources,
-            orchestrator,
+            prompts,
+            state,
+            _orchestrator: orchestrator,
+            outgoing,
+            in_flight: Mutex::new(HashMap::new()),
+            approver,
         })
     }
 
-    /// Initialize D-Bus introspection cache
-
     /// Register default tools dynamically
     async fn register_default_tools(registry: &ToolRegistry) -> Result<()> {
         // Systemd status tool
@@ -469,18 +639,19 @@ impl McpServer {
         registry.register_tool(Box::new(create_ovs_bridge)).await?;
 
         // Register agent management tools (control MCP server functionality)
-        agents::register_agent_tools(&registry).await?;
+        agents::register_agent_tools(registry).await?;
 
         // Register granular D-Bus tools
-        dbus_granular::register_dbus_granular_tools(&registry).await?;
+        dbus_granular::register_dbus_granular_tools(registry).await?;
 
         // Register introspection tools
-        introspection_tools::register_introspection_tools(&registry).await?;
+        introspection_tools::register_introspection_tools(registry).await?;
 
         Ok(())
     }
 
     /// Register agents as tools (fetches from chat-server)
+    #[allow(dead_code)]
     async fn register_agent_tools(registry: &ToolRegistry) -> Result<()> {
         // Try to fetch agents from localhost:8080/api/agents
         let client = match reqwest::Client::builder()
@@ -510,7 +681,7 @@ impl McpServer {
             eprintln!("Registering {} agent tools", agents.len());
 
             for agent in agents {
-                if let (Some(agent_type), Some(name), Some(description), Some(capabilities)) = (
+                if let (Some(agent_type), Some(_name), Some(description), Some(capabilities)) = (
                     agent.get("agent_type").and_then(|v| v.as_str()),
                     agent.get("name").and_then(|v| v.as_str()),
                     agent.get("description").and_then(|v| v.as_str()),
@@ -572,13 +743,68 @@ impl McpServer {
         Ok(())
     }
 
+    /// Handle one message from the client
+    ///
+    /// Requests run as their own tasks so that long tool calls neither block
+    /// later requests nor escape `notifications/cancelled`.
+    fn dispatch(self: &Arc<Self>, request: McpRequest) {
+        let Some(id) = request.id.clone() else {
+            self.handle_notification(request);
+            return;
+        };
+        let key = id.to_string();
+
+        // Hold the lock until the handle is stored so a fast request cannot
+        // try to remove its entry first
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let server = Arc::clone(self);
+        let task_key = key.clone();
+        let task = tokio::spawn(async move {
+            let response = server.handle_request(request).await;
+            server.in_flight.lock().unwrap().remove(&task_key);
+            match serde_json::to_value(&response) {
+                Ok(response) => {
+                    let _ = server.outgoing.send(response);
+                }
+                Err(e) => eprintln!("Failed to serialize response: {}", e),
+            }
+        });
+        in_flight.insert(key, task.abort_handle());
+    }
+
+    fn handle_notification(&self, notification: McpRequest) {
+        match notification.method.as_str() {
+            "notifications/cancelled" => {
+                let params = notification.params.unwrap_or_default();
+                let key = params["requestId"].to_string();
+                // The cancelled request gets no response
+                if let Some(task) = self.in_flight.lock().unwrap().remove(&key) {
+                    task.abort();
+                    log::info!(
+                        "Cancelled request {}: {}",
+                        key,
+                        params["reason"].as_str().unwrap_or("no reason given")
+                    );
+                }
+            }
+            "notifications/initialized" => {}
+            method => log::debug!("Ignoring notification {}", method),
+        }
+    }
+
     async fn handle_request(&self, request: McpRequest) -> McpResponse {
         match request.method.as_str() {
-            "initialize" => self.handle_initialize(request.id),
+            "initialize" => self.handle_initialize(request.id, request.params),
+            "ping" => McpResponse::result(request.id, json!({})),
             "tools/list" => self.handle_tools_list(request.id).await,
             "tools/call" => self.handle_tools_call(request.id, request.params).await,
-            "resources/list" => self.handle_resources_list(request.id),
-            "resources/read" => self.handle_resources_read(request.id, request.params),
+            "resources/list" => self.handle_resources_list(request.id).await,
+            "resources/read" => self.handle_resources_read(request.id, request.params).await,
+            "resources/subscribe" => self.handle_resources_subscribe(request.id, request.params).await,
+            "resources/unsubscribe" => self.handle_resources_unsubscribe(request.id, request.params),
+            "prompts/list" => McpResponse::result(request.id, json!({ "prompts": self.prompts.list() })),
+            "prompts/get" => self.handle_prompts_get(request.id, request.params),
+            "logging/setLevel" => self.handle_logging_set_level(request.id, request.params),
             _ => McpResponse {
                 jsonrpc: "2.0".to_string(),
                 id: request.id,
@@ -592,7 +818,11 @@ impl McpServer {
         }
     }
 
-    fn handle_initialize(&self, id: Option<Value>) -> McpResponse {
+    fn handle_initialize(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let params = params.unwrap_or_default();
+        let elicitation = !params["capabilities"]["elicitation"].is_null();
+        self.approver.supported.store(elicitation, Ordering::SeqCst);
+
         McpResponse {
             jsonrpc: "2.0".to_string(),
             id,
@@ -601,12 +831,16 @@ impl McpServer {
                 "capabilities": {
                     "tools": {
                         "list": true,
-                        "call": true
+                        "call": true,
+                        "listChanged": true
                     },
                     "resources": {
                         "list": true,
-                        "read": true
-                    }
+                        "read": true,
+                        "subscribe": true
+                    },
+                    "prompts": {},
+                    "logging": {}
                 },
                 "serverInfo": {
                     "name": "dbus-mcp-refactored",
@@ -689,31 +923,51 @@ impl McpServer {
             };
         }
 
-        // Execute tool through registry service
-        match self.registry_service.registry().execute_tool(tool_name, arguments).await {
+        // Execute tool through registry service, reporting progress if asked to
+        let execution = self.registry_service.registry().execute_tool(tool_name, arguments);
+        let result = match params["_meta"].get("progressToken") {
+            Some(token) => {
+                let reporter = ProgressReporter::new(token.clone(), self.outgoing.clone());
+                with_progress(reporter, execution).await
+            }
+            None => execution.await,
+        };
+        match result {
             Ok(result) => McpResponse {
                 jsonrpc: "2.0".to_string(),
                 id,
                 result: Some(json!(result)),
                 error: None,
             },
-            Err(e) => McpResponse {
-                jsonrpc: "2.0".to_string(),
-                id,
-                result: None,
-                error: Some(McpError {
-                    code: -32603,
-                    message: format!("Tool execution failed: {}", e),
-                    data: None,
-                }),
+            Err(e) => match e.downcast_ref::<PolicyDenial>() {
+                Some(denial) => McpResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result: None,
+                    error: Some(McpError {
+                        code: POLICY_DENIED,
+                        message: denial.to_string(),
+                        data: Some(json!(denial)),
+                    }),
+                },
+                None => McpResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result: None,
+                    error: Some(McpError {
+                        code: -32603,
+                        message: format!("Tool execution failed: {}", e),
+                        data: None,
+                    }),
+                },
             },
         }
     }
 
-    fn handle_resources_list(&self, id: Option<Value>) -> McpResponse {
+    async fn handle_resources_list(&self, id: Option<Value>) -> McpResponse {
         let resources = self.resources.list_resources();
 
-        let resource_list: Vec<Value> = resources
+        let mut resource_list: Vec<Value> = resources
             .into_iter()
             .map(|resource| {
                 json!({
@@ -724,6 +978,7 @@ impl McpServer {
                 })
             })
             .collect();
+        resource_list.extend(self.state.list().await);
 
         McpResponse {
             jsonrpc: "2.0".to_string(),
@@ -735,7 +990,7 @@ impl McpServer {
         }
     }
 
-    fn handle_resources_read(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+    async fn handle_resources_read(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
         let params = match params {
             Some(p) => p,
             None => {
@@ -768,6 +1023,22 @@ impl McpServer {
             }
         };
 
+        if uri.starts_with(STATE_URI_PREFIX) {
+            return match self.state.read(uri).await {
+                Ok(state) => McpResponse::result(
+                    id,
+                    json!({
+                        "contents": [{
+                            "uri": uri,
+                            "mimeType": "application/json",
+                            "text": serde_json::to_string_pretty(&state).unwrap_or_default()
+                        }]
+                    }),
+                ),
+                Err(e) => McpResponse::error(id, -32603, format!("Failed to read {}: {:#}", uri, e)),
+            };
+        }
+
         match self.resources.get_resource(uri) {
             Some(resource) => McpResponse {
                 jsonrpc: "2.0".to_string(),
@@ -793,25 +1064,87 @@ impl McpServer {
             },
         }
     }
+
+    async fn handle_resources_subscribe(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let params = params.unwrap_or_default();
+        let Some(uri) = params["uri"].as_str() else {
+            return McpResponse::error(id, -32602, "Missing resource URI");
+        };
+        if !uri.starts_with(STATE_URI_PREFIX) {
+            // Embedded documentation never changes
+            return McpResponse::error(id, -32602, format!("Resource does not support subscriptions: {}", uri));
+        }
+        match self.state.subscribe(uri).await {
+            Ok(()) => McpResponse::result(id, json!({})),
+            Err(e) => McpResponse::error(id, -32603, format!("Failed to subscribe to {}: {:#}", uri, e)),
+        }
+    }
+
+    fn handle_resources_unsubscribe(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let params = params.unwrap_or_default();
+        let Some(uri) = params["uri"].as_str() else {
+            return McpResponse::error(id, -32602, "Missing resource URI");
+        };
+        self.state.unsubscribe(uri);
+        McpResponse::result(id, json!({}))
+    }
+
+    fn handle_prompts_get(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let params = params.unwrap_or_default();
+        let Some(name) = params["name"].as_str() else {
+            return McpResponse::error(id, -32602, "Missing prompt name");
+        };
+        match self.prompts.get(name, &params["arguments"]) {
+            Some(prompt) => McpResponse::result(id, prompt),
+            None => McpResponse::error(id, -32602, format!("Prompt not found: {}", name)),
+        }
+    }
+
+    fn handle_logging_set_level(&self, id: Option<Value>, params: Option<Value>) -> McpResponse {
+        let params = params.unwrap_or_default();
+        let Some(level) = params["level"].as_str() else {
+            return McpResponse::error(id, -32602, "Missing log level");
+        };
+        match client_log::set_level(level) {
+            Ok(()) => McpResponse::result(id, json!({})),
+            Err(e) => McpResponse::error(id, -32602, e.to_string()),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Everything for the client goes through one channel so that responses
+    // and notifications from concurrent tasks are written whole
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Value>();
+
     // Initialize logging
-    env_logger::init();
+    client_log::init(outgoing.clone())?;
 
     eprintln!("Starting refactored MCP server with tool registry...");
 
-    let server = McpServer::new().await?;
+    let server = Arc::new(McpServer::new(outgoing).await?);
+
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        // Null marks the end of output; the logger keeps a sender forever
+        while let Some(message) = outgoing_rx.recv().await.filter(|m| !m.is_null()) {
+            let mut line = message.to_string();
+            line.push('\n');
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
 
     eprintln!("MCP server ready. Reading from stdin...");
 
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
 
-    for line in stdin.lock().lines() {
-        let line = match line {
-            Ok(l) => l,
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(l)) => l,
+            Ok(None) => break,
             Err(e) => {
                 eprintln!("Failed to read line: {}", e);
                 continue;
@@ -822,20 +1155,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             continue;
         }
 
-        let request: McpRequest = match serde_json::from_str(&line) {
-            Ok(r) => r,
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(m) => m,
             Err(e) => {
                 eprintln!("Failed to parse request: {}", e);
                 continue;
             }
         };
 
-        let response = server.handle_request(request).await;
-        let response_json = serde_json::to_string(&response)?;
+        // Responses answer our own requests to the client
+        if message.get("method").is_none() {
+            server.approver.resolve(message);
+            continue;
+        }
+
+        match serde_json::from_value::<McpRequest>(message) {
+            Ok(request) => server.dispatch(request),
+            Err(e) => eprintln!("Failed to parse request: {}", e),
+        }
+    }
+    server.approver.close();
 
-        writeln!(stdout, "{}", response_json)?;
-        stdout.flush()?;
+    // Let requests still running answer before exiting
+    let pending: Vec<AbortHandle> = server.in_flight.lock().unwrap().values().cloned().collect();
+    while pending.iter().any(|task| !task.is_finished()) {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
     }
+    let _ = server.outgoing.send(Value::Null);
+    let _ = writer.await;
 
     Ok(())
 }
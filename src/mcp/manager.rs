@@ -13,6 +13,7 @@ use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 use tower_http::services::ServeDir;
 
+use super::tool_policy::ToolPolicy;
 use super::tool_registry::{Tool, ToolRegistry};
 
 /// MCP Manager state
@@ -307,6 +308,11 @@ pub async fn start_manager(
 ) -> anyhow::Result<()> {
     // Create tool registry and register introspection tools
     let tool_registry = ToolRegistry::new();
+    let policy = ToolPolicy::load()?;
+    tool_registry
+        .set_approver(Arc::new(policy.unattended_approver("manager")))
+        .await;
+    tool_registry.set_policy(policy).await;
     super::introspection_tools::register_introspection_tools(&tool_registry).await?;
 
     let state = McpManagerState::new(tool_registry).await;
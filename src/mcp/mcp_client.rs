@@ -69,6 +69,7 @@ pub struct McpClient {
     servers: Arc<RwLock<HashMap<String, HostedMcpServer>>>,
     remote_configs: Arc<RwLock<HashMap<String, RemoteMcpConfig>>>,
     remote_servers: Arc<RwLock<HashMap<String, RemoteMcpServer>>>,
+    #[allow(dead_code)]
     http_client: Client,
 }
 
@@ -145,7 +146,7 @@ impl HostedMcpServer {
         *req_id += 1;
         drop(req_id);
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
+        let (tx, _rx) = tokio::sync::oneshot::channel();
         {
             let mut pending = self.pending_requests.lock().await;
             pending.insert(id, tx);
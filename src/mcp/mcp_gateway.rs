@@ -0,0 +1,459 @@
+//! MCP gateway re-exporting the tools of external MCP servers
+//!
+//! Every server in `OPDBUS_MCP_SERVERS` (default
+//! /etc/op-dbus/mcp-servers.toml) is connected and its tools are registered
+//! in the `ToolRegistry` as `<server>__<tool>`. Calls go through the
+//! registry like those of native tools, so the tool policy, approval and
+//! audit middleware apply to them, and are forwarded with their progress
+//! and cancellation.
+//!
+//! The tool list is refreshed when a server sends
+//! `notifications/tools/list_changed`. A stdio server that exits has its
+//! tools removed and is started again with backoff. Clients of the gateway
+//! are told with `notifications/tools/list_changed` whenever the re-exported
+//! tools change.
+
+use super::external_mcp_client::{
+    ClientEvent, McpClient, McpServerConfig, McpServerRegistry, McpServersConfig, McpTool,
+};
+use super::tool_registry::{
+    SecurityLevel, Tool, ToolContent, ToolMetadata, ToolRegistry, ToolResult,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tracing::{info, warn};
+
+pub const DEFAULT_SERVERS_FILE: &str = "/etc/op-dbus/mcp-servers.toml";
+
+/// Separates the server name from the tool name in re-exported tools
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// First delay before reconnecting to a server; doubled after each failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Name a server's tool is re-exported under
+pub fn namespaced_name(server: &str, tool: &str) -> String {
+    format!("{}{}{}", server, NAMESPACE_SEPARATOR, tool)
+}
+
+/// A tool of an external server, called through its client
+struct ProxiedTool {
+    name: String,
+    server: String,
+    tool: McpTool,
+    security_level: SecurityLevel,
+    client: Arc<McpClient>,
+}
+
+#[async_trait]
+impl Tool for ProxiedTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.tool.description
+    }
+
+    fn input_schema(&self) -> Value {
+        if self.tool.input_schema.is_null() {
+            json!({"type": "object"})
+        } else {
+            self.tool.input_schema.clone()
+        }
+    }
+
+    fn metadata(&self) -> ToolMetadata {
+        ToolMetadata {
+            name: self.name.clone(),
+            description: self.tool.description.clone(),
+            category: format!("mcp:{}", self.server),
+            tags: vec!["external".to_string(), self.server.clone()],
+            author: None,
+            version: "1.0.0".to_string(),
+            security_level: self.security_level.clone(),
+            requires_auth: true,
+        }
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let result = self.client.call_tool(&self.tool.name, params).await?;
+        proxied_result(&result)
+    }
+}
+
+/// Convert a `tools/call` result of an external server
+fn proxied_result(result: &Value) -> Result<ToolResult> {
+    let content: Vec<ToolContent> = result["content"]
+        .as_array()
+        .map(|items| items.iter().map(proxied_content).collect())
+        .unwrap_or_default();
+
+    if result["isError"] == true {
+        let message: Vec<&str> = content
+            .iter()
+            .filter_map(|item| item.text.as_deref())
+            .collect();
+        anyhow::bail!("{}", message.join("\n"));
+    }
+    Ok(ToolResult::success_multi(content))
+}
+
+fn proxied_content(item: &Value) -> ToolContent {
+    match (item["type"].as_str(), item["text"].as_str()) {
+        (Some("text"), Some(text)) => ToolContent::text(text),
+        (content_type, text) => ToolContent {
+            content_type: content_type.unwrap_or("json").to_string(),
+            text: text.map(str::to_string),
+            data: Some(item.clone()),
+        },
+    }
+}
+
+/// Re-exports the tools of external MCP servers in a tool registry
+pub struct McpGateway {
+    registry: Arc<ToolRegistry>,
+    servers: McpServerRegistry,
+    /// Re-exported tool names by server
+    exported: Mutex<HashMap<String, Vec<String>>>,
+    /// Notifications for the gateway's own clients
+    notifications: mpsc::UnboundedSender<Value>,
+}
+
+impl McpGateway {
+    pub fn new(
+        registry: Arc<ToolRegistry>,
+        notifications: mpsc::UnboundedSender<Value>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            registry,
+            servers: McpServerRegistry::new(),
+            exported: Mutex::new(HashMap::new()),
+            notifications,
+        })
+    }
+
+    /// The external servers
+    pub fn servers(&self) -> &McpServerRegistry {
+        &self.servers
+    }
+
+    /// Add the enabled servers from `OPDBUS_MCP_SERVERS`, returning how many
+    pub async fn load_config(self: &Arc<Self>) -> Result<usize> {
+        let path = std::env::var("OPDBUS_MCP_SERVERS")
+            .unwrap_or_else(|_| DEFAULT_SERVERS_FILE.to_string());
+        let path = Path::new(&path);
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: McpServersConfig = toml::from_str(&content)
+            .with_context(|| format!("Invalid MCP server configuration {}", path.display()))?;
+
+        let mut added = 0;
+        for server in config.servers.into_iter().filter(|server| server.enabled) {
+            self.add_server(server).await?;
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Connect to a server and keep its tools re-exported
+    pub async fn add_server(self: &Arc<Self>, config: McpServerConfig) -> Result<()> {
+        if config.name.contains(NAMESPACE_SEPARATOR) {
+            anyhow::bail!(
+                "MCP server name '{}' must not contain '{}'",
+                config.name,
+                NAMESPACE_SEPARATOR
+            );
+        }
+        if self.servers.get_server(&config.name).await.is_some() {
+            anyhow::bail!("MCP server '{}' is already added", config.name);
+        }
+
+        let client = Arc::new(McpClient::new(config).await?);
+        self.servers.insert(client.clone()).await;
+        tokio::spawn(Arc::clone(self).serve(client));
+        Ok(())
+    }
+
+    /// Connect to a server, reconnecting with backoff whenever it goes away
+    async fn serve(self: Arc<Self>, client: Arc<McpClient>) {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            // Events of earlier connections do not count
+            let mut events = client.subscribe();
+            match client.connect().await {
+                Ok(()) => {
+                    backoff = INITIAL_BACKOFF;
+                    self.export_tools(&client).await;
+
+                    loop {
+                        match events.recv().await {
+                            Ok(ClientEvent::ToolsChanged)
+                            | Err(broadcast::error::RecvError::Lagged(_)) => {
+                                match client.refresh_tools().await {
+                                    Ok(_) => self.export_tools(&client).await,
+                                    Err(e) => {
+                                        warn!("Failed to refresh tools of {}: {}", client.name, e)
+                                    }
+                                }
+                            }
+                            Ok(ClientEvent::Disconnected) => break,
+                            Err(broadcast::error::RecvError::Closed) => return,
+                        }
+                    }
+                    self.withdraw_tools(&client.name).await;
+                }
+                Err(e) => warn!("Failed to connect to MCP server {}: {}", client.name, e),
+            }
+
+            info!(
+                "Reconnecting to MCP server {} in {:?}",
+                client.name, backoff
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Register the server's current tools in place of those exported before
+    async fn export_tools(&self, client: &Arc<McpClient>) {
+        let mut exported = self.exported.lock().await;
+        let names = exported.entry(client.name.clone()).or_default();
+        for name in names.drain(..) {
+            self.registry.unregister_tool(&name).await;
+        }
+
+        let security_level = client
+            .config()
+            .security_level
+            .clone()
+            .unwrap_or(SecurityLevel::High);
+        for tool in client.get_tools().await {
+            let name = namespaced_name(&client.name, &tool.name);
+            let proxied = ProxiedTool {
+                name: name.clone(),
+                server: client.name.clone(),
+                tool,
+                security_level: security_level.clone(),
+                client: client.clone(),
+            };
+            match self.registry.register_tool(Box::new(proxied)).await {
+                Ok(()) => names.push(name),
+                Err(e) => warn!("Not re-exporting {}: {}", name, e),
+            }
+        }
+        info!("Re-exported {} tools of {}", names.len(), client.name);
+        drop(exported);
+
+        self.notify_tools_changed();
+    }
+
+    /// Remove the tools of a server that went away
+    async fn withdraw_tools(&self, server: &str) {
+        let names = self
+            .exported
+            .lock()
+            .await
+            .remove(server)
+            .unwrap_or_default();
+        for name in &names {
+            self.registry.unregister_tool(name).await;
+        }
+        if !names.is_empty() {
+            self.notify_tools_changed();
+        }
+    }
+
+    fn notify_tools_changed(&self) {
+        let _ = self.notifications.send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/tools/list_changed"
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::external_mcp_client::McpTransport;
+    use super::super::tool_policy::{PolicyDenial, ToolPolicy};
+    use super::super::tool_registry::{
+        with_progress, with_security_context, ProgressReporter, SecurityContext,
+    };
+    use super::*;
+
+    /// MCP server on stdio with tools `echo` (reports progress), `slow`
+    /// (never answers), `grow` (adds the tool `extra`) and `exit`;
+    /// cancellations are written to $MOCK_LOG
+    const MOCK_SERVER: &str = r#"
+reply() { printf '{"jsonrpc":"2.0","id":%s,"result":%s}\n' "$id" "$1"; }
+tools='{"name":"echo","description":"Echo","inputSchema":{"type":"object"}},{"name":"slow"},{"name":"grow"},{"name":"exit"}'
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      reply '{"protocolVersion":"2024-11-05","capabilities":{"tools":{"listChanged":true}},"serverInfo":{"name":"mock","version":"1.0"}}' ;;
+    *'"method":"tools/list"'*) reply "{\"tools\":[$tools]}" ;;
+    *'"method":"notifications/cancelled"'*)
+      printf '%s\n' "$line" | sed -n 's/.*"requestId":\([0-9]*\).*/cancelled \1/p' >> "$MOCK_LOG" ;;
+    *'"name":"echo"'*)
+      token=$(printf '%s' "$line" | sed -n 's/.*"progressToken":\([0-9]*\).*/\1/p')
+      [ -n "$token" ] && printf '{"jsonrpc":"2.0","method":"notifications/progress","params":{"progressToken":%s,"progress":1,"total":2,"message":"halfway"}}\n' "$token"
+      reply '{"content":[{"type":"text","text":"hello from mock"}]}' ;;
+    *'"name":"grow"'*)
+      tools="$tools,{\"name\":\"extra\"}"
+      printf '{"jsonrpc":"2.0","method":"notifications/tools/list_changed"}\n'
+      reply '{"content":[]}' ;;
+    *'"name":"exit"'*) exit 0 ;;
+  esac
+done
+"#;
+
+    /// Wait until the tool is registered, or no longer is
+    async fn wait_for_tool(registry: &ToolRegistry, name: &str, registered: bool) {
+        for _ in 0..500 {
+            if registry.get_tool(name).await.is_some() == registered {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{} registered: {} expected", name, registered);
+    }
+
+    #[test]
+    fn test_proxied_result() {
+        let result = proxied_result(&json!({
+            "content": [{"type": "text", "text": "ok"}, {"type": "image", "data": "aGk=", "mimeType": "image/png"}]
+        }))
+        .unwrap();
+        assert_eq!(result.content[0].text.as_deref(), Some("ok"));
+        assert_eq!(result.content[1].content_type, "image");
+        assert_eq!(
+            result.content[1].data.as_ref().unwrap()["mimeType"],
+            "image/png"
+        );
+
+        let error = proxied_result(&json!({
+            "content": [{"type": "text", "text": "no such file"}],
+            "isError": true
+        }));
+        assert_eq!(error.unwrap_err().to_string(), "no such file");
+    }
+
+    #[tokio::test]
+    async fn test_gateway_proxies_stdio_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("mock.log");
+        std::fs::write(&log, "").unwrap();
+
+        let registry = Arc::new(ToolRegistry::new());
+        registry
+            .set_policy(ToolPolicy {
+                approval_level: None,
+                ..ToolPolicy::default()
+            })
+            .await;
+        let (notifications, mut notifications_rx) = mpsc::unbounded_channel();
+        let gateway = McpGateway::new(registry.clone(), notifications);
+        gateway
+            .add_server(McpServerConfig {
+                name: "mock".to_string(),
+                description: "Mock server".to_string(),
+                transport: McpTransport::Stdio {
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), MOCK_SERVER.to_string()],
+                    env: Some(HashMap::from([(
+                        "MOCK_LOG".to_string(),
+                        log.display().to_string(),
+                    )])),
+                },
+                enabled: true,
+                security_level: None,
+            })
+            .await
+            .unwrap();
+
+        wait_for_tool(&registry, "mock__echo", true).await;
+        let changed = notifications_rx.recv().await.unwrap();
+        assert_eq!(changed["method"], "notifications/tools/list_changed");
+        let metadata = registry.get_tool("mock__echo").await.unwrap().metadata();
+        assert_eq!(metadata.security_level, SecurityLevel::High);
+
+        // Proxied tools are subject to the same policy as native ones
+        let anonymous = registry.execute_tool("mock__echo", json!({})).await;
+        assert!(matches!(
+            anonymous.unwrap_err().downcast_ref::<PolicyDenial>(),
+            Some(PolicyDenial::AuthenticationRequired { .. })
+        ));
+
+        let session = SecurityContext {
+            authenticated: true,
+            ..Default::default()
+        };
+        let (progress, mut progress_rx) = mpsc::unbounded_channel();
+        let reporter = ProgressReporter::new(json!("call-1"), progress);
+        let result = with_security_context(
+            session.clone(),
+            with_progress(reporter, registry.execute_tool("mock__echo", json!({}))),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.content[0].text.as_deref(), Some("hello from mock"));
+        let reported = progress_rx.recv().await.unwrap();
+        assert_eq!(reported["params"]["progressToken"], "call-1");
+        assert_eq!(reported["params"]["message"], "halfway");
+
+        // Dropping a call cancels it on the server
+        let slow = tokio::spawn({
+            let registry = registry.clone();
+            let session = session.clone();
+            async move {
+                with_security_context(session, registry.execute_tool("mock__slow", json!({}))).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        slow.abort();
+        let mut cancelled = String::new();
+        for _ in 0..250 {
+            cancelled = std::fs::read_to_string(&log).unwrap();
+            if !cancelled.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(cancelled.starts_with("cancelled "), "{:?}", cancelled);
+
+        // The tool list follows the server's
+        with_security_context(
+            session.clone(),
+            registry.execute_tool("mock__grow", json!({})),
+        )
+        .await
+        .unwrap();
+        wait_for_tool(&registry, "mock__extra", true).await;
+
+        // A server that exits is started again with its original tools
+        let exited = with_security_context(
+            session.clone(),
+            registry.execute_tool("mock__exit", json!({})),
+        )
+        .await;
+        assert!(exited.is_err());
+        wait_for_tool(&registry, "mock__extra", false).await;
+        wait_for_tool(&registry, "mock__echo", true).await;
+        with_security_context(session, registry.execute_tool("mock__echo", json!({})))
+            .await
+            .unwrap();
+    }
+}
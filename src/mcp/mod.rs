@@ -3,14 +3,7 @@
 //! This module provides MCP server functionality with D-Bus orchestration
 //! for Linux system automation.
 
-pub mod agents {
-    pub mod executor;
-    pub mod file;
-    pub mod monitor;
-    pub mod network;
-    pub mod packagekit;
-    pub mod systemd;
-}
+// Agents are binaries of their own (dbus-agent-*), not library modules
 
 // Core MCP modules
 // pub mod bridge; // Binary
@@ -25,15 +18,25 @@ pub mod system_introspection;
 
 // Refactored modules for loose coupling
 pub mod agent_registry;
+pub mod agent_units;  // Agents run as transient systemd services
 pub mod tool_registry;
+pub mod tool_policy;  // Which tools a session may run, and approval of sensitive ones
 pub mod external_mcp_client;  // External MCP server integration
+#[cfg(feature = "mcp")]
+pub mod mcp_gateway;  // External servers' tools re-exported through the tool registry
 pub mod sse_streaming;  // SSE support for long-running operations
+pub mod streamable_http;  // MCP streamable-HTTP transport with sessions
 pub mod client_config_generator;  // Auto-generate client configs
+pub mod client_log;  // Log records forwarded to the client
+pub mod prompts;  // Agent definitions as MCP prompts
+pub mod state_resources;  // Live plugin state resources and subscriptions
 
 
 
 // MCP tools
 pub mod tools {
+    pub mod agents;
+    pub mod dbus_granular;
     pub mod introspection;
 }
 
@@ -69,6 +72,7 @@ pub mod workflow_plugin_introspection;
 
 // Embedded resources for MCP
 pub mod resources;
+pub mod resources_enhanced;  // Embedded docs plus markdown scanned at runtime
 
 // Comprehensive native introspection (no wrappers)
 pub mod comprehensive_introspection;
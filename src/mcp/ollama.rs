@@ -33,6 +33,7 @@ struct ChatOptions {
 }
 
 /// Ollama chat response
+#[allow(dead_code)] // Only the message is used; the rest shows in debug output
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     message: ChatMessage,
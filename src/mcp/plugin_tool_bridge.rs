@@ -1,11 +1,16 @@
 //! Bridge between Plugin Registry and Tool Registry
-//! Auto-creates plugins from introspection and exposes them as MCP tools
-
-use crate::mcp::tool_registry::{DynamicToolBuilder, ToolContent, ToolRegistry, ToolResult};
-use crate::state::manager::StateManager;
-use crate::state::plugin::StatePlugin;
-use anyhow::{Context, Result};
+//! Auto-creates plugins from introspection and exposes them as MCP tools:
+//! `<plugin>_query`, `<plugin>_plan` and `<plugin>_apply`
+
+use crate::mcp::tool_registry::{
+    report_progress, DynamicToolBuilder, SecurityLevel, ToolContent, ToolRegistry, ToolResult,
+};
+use crate::state::manager::{ApplyEvent, DesiredState, StateManager};
+use crate::state::plan::{infer_schema, plan_hash};
+use crate::state::plugin::{PluginCapabilities, StateAction, StatePlugin};
+use anyhow::Result;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Bridge that connects plugin registry to tool registry
@@ -22,6 +27,19 @@ impl PluginToolBridge {
         }
     }
 
+    /// Bridge of the core plugins into `tool_registry`
+    ///
+    /// Their `_apply` tools change the system from this process; add a
+    /// `PolkitMiddleware` to the registry before registering them.
+    pub async fn builtin(tool_registry: Arc<ToolRegistry>) -> Self {
+        let state_manager = Arc::new(StateManager::new());
+        for (name, plugin) in crate::state::plugins::builtin() {
+            state_manager.register_plugin(plugin.clone()).await;
+            state_manager.register_plugin_as_workflow_node(name, plugin);
+        }
+        Self::new(state_manager, tool_registry)
+    }
+
     /// Auto-create plugins from introspection and register them as tools
     pub async fn auto_discover_and_register(&self) -> Result<()> {
         log::info!("Auto-discovering plugins from D-Bus introspection...");
@@ -29,6 +47,7 @@ impl PluginToolBridge {
         // Discover and register auto-generated plugins
         #[cfg(feature = "mcp")]
         {
+            use anyhow::Context;
             self.state_manager
                 .discover_and_register_auto_plugins()
                 .await
@@ -60,16 +79,27 @@ impl PluginToolBridge {
         Ok(())
     }
 
-    /// Register a single plugin as an MCP tool
+    /// Register a plugin as its `_query`, `_plan` and `_apply` tools
+    ///
+    /// The desired-state schema is inferred from the plugin's current state.
+    /// `_apply` only applies the plan whose `plan_hash` `_plan` returned, so
+    /// a change has to be planned (and shown) before it can be made.
     async fn register_plugin_as_tool(
         &self,
         plugin_name: &str,
         plugin: Arc<dyn StatePlugin>,
     ) -> Result<()> {
-        let tool_name = format!("plugin_{}", plugin_name);
+        let mut state_schema = match plugin.query_current_state().await {
+            Ok(current) => infer_schema([&current]),
+            Err(e) => {
+                log::debug!("No state schema for plugin '{}': {}", plugin_name, e);
+                infer_schema([])
+            }
+        };
+        state_schema["description"] = json!(format!("Desired state of the {} plugin", plugin_name));
 
         // Create tool for querying plugin state
-        let query_tool = DynamicToolBuilder::new(&format!("{}_query", tool_name))
+        let query_tool = DynamicToolBuilder::new(format!("{}_query", plugin_name))
             .description(format!("Query current state from {} plugin", plugin_name))
             .schema(json!({
                 "type": "object",
@@ -77,13 +107,13 @@ impl PluginToolBridge {
                 "required": []
             }))
             .handler({
-                let plugin = plugin.clone();
+                let state_manager = self.state_manager.clone();
                 let plugin_name = plugin_name.to_string();
                 move |_params| {
-                    let plugin = plugin.clone();
+                    let state_manager = state_manager.clone();
                     let plugin_name = plugin_name.clone();
                     Box::pin(async move {
-                        match plugin.query_current_state().await {
+                        match state_manager.query_plugin_state(&plugin_name).await {
                             Ok(state) => Ok(ToolResult {
                                 content: vec![ToolContent::json(state)],
                                 metadata: Some(json!({
@@ -92,7 +122,7 @@ impl PluginToolBridge {
                                 })),
                             }),
                             Err(e) => Ok(ToolResult {
-                                content: vec![ToolContent::error(&format!(
+                                content: vec![ToolContent::error(format!(
                                     "Failed to query plugin state: {}",
                                     e
                                 ))],
@@ -108,24 +138,25 @@ impl PluginToolBridge {
             .register_tool(Box::new(query_tool))
             .await?;
 
-        // Create tool for calculating diff
-        let diff_tool = DynamicToolBuilder::new(&format!("{}_diff", tool_name))
-            .description(format!("Calculate state diff for {} plugin", plugin_name))
+        // Create tool for planning (dry-run) a change
+        let plan_tool = DynamicToolBuilder::new(format!("{}_plan", plugin_name))
+            .description(format!(
+                "Plan a change to the {} plugin without applying it. Show the actions to the \
+                 operator; {}_apply needs the returned plan_hash",
+                plugin_name, plugin_name
+            ))
             .schema(json!({
                 "type": "object",
                 "properties": {
-                    "desired_state": {
-                        "type": "object",
-                        "description": "Desired state configuration"
-                    }
+                    "desired_state": state_schema
                 },
                 "required": ["desired_state"]
             }))
             .handler({
-                let plugin = plugin.clone();
+                let state_manager = self.state_manager.clone();
                 let plugin_name = plugin_name.to_string();
                 move |params| {
-                    let plugin = plugin.clone();
+                    let state_manager = state_manager.clone();
                     let plugin_name = plugin_name.clone();
                     Box::pin(async move {
                         let desired = params
@@ -133,18 +164,16 @@ impl PluginToolBridge {
                             .ok_or_else(|| anyhow::anyhow!("Missing desired_state"))?
                             .clone();
 
-                        let current = plugin.query_current_state().await?;
-                        let diff = plugin.calculate_diff(&current, &desired).await?;
+                        let actions = plan(&state_manager, &plugin_name, desired).await?;
 
                         Ok(ToolResult {
                             content: vec![ToolContent::json(json!({
-                                "diff": diff,
-                                "current": current,
-                                "desired": desired
+                                "plan_hash": plan_hash(&actions),
+                                "actions": actions
                             }))],
                             metadata: Some(json!({
                                 "plugin": plugin_name,
-                                "operation": "diff"
+                                "operation": "plan"
                             })),
                         })
                     })
@@ -153,42 +182,74 @@ impl PluginToolBridge {
             .build();
 
         self.tool_registry
-            .register_tool(Box::new(diff_tool))
+            .register_tool(Box::new(plan_tool))
             .await?;
 
-        // Create tool for applying state
-        let apply_tool = DynamicToolBuilder::new(&format!("{}_apply", tool_name))
-            .description(format!("Apply state changes for {} plugin", plugin_name))
+        // Create tool for applying a planned change
+        let apply_tool = DynamicToolBuilder::new(format!("{}_apply", plugin_name))
+            .description(format!(
+                "Apply a change to the {} plugin that {}_plan planned and the operator reviewed",
+                plugin_name, plugin_name
+            ))
             .schema(json!({
                 "type": "object",
                 "properties": {
-                    "desired_state": {
-                        "type": "object",
-                        "description": "Desired state configuration"
+                    "desired_state": state_schema,
+                    "plan_hash": {
+                        "type": "string",
+                        "description": format!("plan_hash {}_plan returned for this desired_state", plugin_name)
                     }
                 },
-                "required": ["desired_state"]
+                "required": ["desired_state", "plan_hash"]
             }))
+            .security_level(apply_security_level(&plugin.capabilities()))
+            .requires_auth(true)
             .handler({
-                let plugin = plugin.clone();
+                let state_manager = self.state_manager.clone();
                 let plugin_name = plugin_name.to_string();
                 move |params| {
-                    let plugin = plugin.clone();
+                    let state_manager = state_manager.clone();
                     let plugin_name = plugin_name.clone();
                     Box::pin(async move {
                         let desired = params
                             .get("desired_state")
                             .ok_or_else(|| anyhow::anyhow!("Missing desired_state"))?
                             .clone();
+                        let confirmed = params
+                            .get("plan_hash")
+                            .and_then(|h| h.as_str())
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                    "Missing plan_hash: call {}_plan and show the plan first",
+                                    plugin_name
+                                )
+                            })?;
+
+                        let actions = plan(&state_manager, &plugin_name, desired.clone()).await?;
+                        if plan_hash(&actions) != confirmed {
+                            anyhow::bail!(
+                                "The plan changed since it was reviewed; call {}_plan again",
+                                plugin_name
+                            );
+                        }
 
-                        let current = plugin.query_current_state().await?;
-                        let diff = plugin.calculate_diff(&current, &desired).await?;
-                        let result = plugin.apply_state(&diff).await?;
+                        let report = state_manager
+                            .apply_state_single_plugin_with_progress(
+                                DesiredState {
+                                    version: 1,
+                                    plugins: HashMap::from([(plugin_name.clone(), desired)]),
+                                },
+                                &plugin_name,
+                                |event| report_progress(&progress_message(&event)),
+                            )
+                            .await?;
 
                         Ok(ToolResult {
                             content: vec![ToolContent::json(json!({
-                                "result": result,
-                                "diff": diff
+                                "success": report.success,
+                                "results": report.results,
+                                "footprint": report.footprint,
+                                "actions": actions
                             }))],
                             metadata: Some(json!({
                                 "plugin": plugin_name,
@@ -255,7 +316,58 @@ impl PluginToolBridge {
     }
 
     fn service_to_plugin_name(service: &str) -> String {
-        service.split('.').last().unwrap_or(service).to_lowercase()
+        service.split('.').next_back().unwrap_or(service).to_lowercase()
+    }
+}
+
+/// Actions applying `desired` to `plugin` would take
+async fn plan(
+    state_manager: &StateManager,
+    plugin: &str,
+    desired: Value,
+) -> Result<Vec<StateAction>> {
+    if state_manager.get_plugin(plugin).await.is_none() {
+        anyhow::bail!("Plugin '{}' not registered", plugin);
+    }
+    let diffs = state_manager
+        .show_diff(DesiredState {
+            version: 1,
+            plugins: HashMap::from([(plugin.to_string(), desired)]),
+        })
+        .await?;
+    Ok(diffs.into_iter().flat_map(|diff| diff.actions).collect())
+}
+
+/// Security level of a plugin's `_apply` tool, by whether it can be undone
+fn apply_security_level(capabilities: &PluginCapabilities) -> SecurityLevel {
+    if capabilities.supports_checkpoints && capabilities.supports_rollback {
+        SecurityLevel::High
+    } else {
+        SecurityLevel::Critical
+    }
+}
+
+fn progress_message(event: &ApplyEvent) -> String {
+    match event {
+        ApplyEvent::CheckpointCreated { checkpoint, .. } => {
+            format!("Created checkpoint {}", checkpoint)
+        }
+        ApplyEvent::DiffCalculated { actions, .. } => format!("{} actions to apply", actions),
+        ApplyEvent::ActionStarted { index, .. } => format!("Applying action {}", index + 1),
+        ApplyEvent::ActionFinished { index, success, .. } => format!(
+            "Action {} {}",
+            index + 1,
+            if *success { "succeeded" } else { "failed" }
+        ),
+        ApplyEvent::Verified { matches, .. } => if *matches {
+            "Verified the applied state"
+        } else {
+            "Applied state does not match the desired state"
+        }
+        .to_string(),
+        ApplyEvent::RolledBack { checkpoint, .. } => {
+            format!("Rolled back to checkpoint {}", checkpoint)
+        }
     }
 }
 
@@ -293,3 +405,208 @@ impl crate::mcp::chat::orchestrator::EventListener for IntrospectionPluginListen
         log::error!("Error in {}: {}", context, error);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::tool_policy::{PolicyDenial, ToolPolicy};
+    use crate::mcp::tool_registry::{with_security_context, PolkitMiddleware, SecurityContext};
+    use crate::state::plugin::{ApplyResult, Checkpoint, DiffMetadata, StateDiff};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Plugin holding a JSON object, without rollback
+    struct KeysPlugin {
+        state: Mutex<serde_json::Map<String, Value>>,
+    }
+
+    #[async_trait]
+    impl StatePlugin for KeysPlugin {
+        fn name(&self) -> &str {
+            "keys"
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        async fn query_current_state(&self) -> Result<Value> {
+            Ok(Value::Object(self.state.lock().unwrap().clone()))
+        }
+
+        async fn calculate_diff(&self, current: &Value, desired: &Value) -> Result<StateDiff> {
+            let actions = desired
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(key, value)| current.get(key.as_str()) != Some(value))
+                .map(|(key, value)| StateAction::Create {
+                    resource: key.clone(),
+                    config: value.clone(),
+                })
+                .collect();
+            Ok(StateDiff {
+                plugin: self.name().to_string(),
+                actions,
+                metadata: DiffMetadata {
+                    timestamp: 0,
+                    current_hash: String::new(),
+                    desired_hash: String::new(),
+                },
+            })
+        }
+
+        async fn apply_state(&self, diff: &StateDiff) -> Result<ApplyResult> {
+            for action in &diff.actions {
+                if let StateAction::Create { resource, config } = action {
+                    self.state
+                        .lock()
+                        .unwrap()
+                        .insert(resource.clone(), config.clone());
+                }
+            }
+            Ok(ApplyResult {
+                success: true,
+                changes_applied: Vec::new(),
+                errors: Vec::new(),
+                checkpoint: None,
+            })
+        }
+
+        async fn verify_state(&self, _desired: &Value) -> Result<bool> {
+            Ok(true)
+        }
+
+        async fn create_checkpoint(&self) -> Result<Checkpoint> {
+            Ok(Checkpoint {
+                id: "keys-1".to_string(),
+                plugin: self.name().to_string(),
+                timestamp: 0,
+                state_snapshot: json!({}),
+                backend_checkpoint: None,
+            })
+        }
+
+        async fn rollback(&self, _checkpoint: &Checkpoint) -> Result<()> {
+            anyhow::bail!("rollback not supported")
+        }
+
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities {
+                supports_rollback: false,
+                supports_checkpoints: true,
+                supports_verification: true,
+                atomic_operations: false,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_requires_reviewed_plan() {
+        let state_manager = Arc::new(StateManager::new());
+        let mut initial = serde_json::Map::new();
+        initial.insert("mtu".to_string(), json!(1500));
+        state_manager
+            .register_plugin(Arc::new(KeysPlugin {
+                state: Mutex::new(initial),
+            }))
+            .await;
+        let registry = Arc::new(ToolRegistry::new());
+        PluginToolBridge::new(state_manager.clone(), registry.clone())
+            .register_plugins_as_tools()
+            .await
+            .unwrap();
+        // Approval is covered by the registry's tests
+        registry
+            .set_policy(ToolPolicy {
+                approval_level: None,
+                ..ToolPolicy::default()
+            })
+            .await;
+
+        let apply = registry.get_tool("keys_apply").await.unwrap();
+        assert_eq!(apply.metadata().security_level, SecurityLevel::Critical);
+        let schema = apply.input_schema();
+        assert_eq!(
+            schema["properties"]["desired_state"]["properties"]["mtu"]["type"],
+            "integer"
+        );
+
+        let desired = json!({"mtu": 9000});
+        let planned = registry
+            .execute_tool("keys_plan", json!({ "desired_state": desired }))
+            .await
+            .unwrap();
+        let plan = planned.content[0].data.as_ref().unwrap();
+        assert_eq!(plan["actions"].as_array().unwrap().len(), 1);
+        let hash = plan["plan_hash"].as_str().unwrap().to_string();
+
+        // Applying needs an authenticated session
+        let anonymous = registry
+            .execute_tool(
+                "keys_apply",
+                json!({ "desired_state": desired, "plan_hash": hash }),
+            )
+            .await;
+        assert!(anonymous
+            .unwrap_err()
+            .downcast_ref::<PolicyDenial>()
+            .is_some());
+
+        let operator = SecurityContext {
+            authenticated: true,
+            ..Default::default()
+        };
+        with_security_context(operator, async {
+            let unreviewed = registry
+                .execute_tool(
+                    "keys_apply",
+                    json!({ "desired_state": {"mtu": 1400}, "plan_hash": hash }),
+                )
+                .await;
+            assert!(unreviewed
+                .unwrap_err()
+                .to_string()
+                .contains("keys_plan again"));
+            assert!(registry
+                .execute_tool("keys_apply", json!({ "desired_state": desired }))
+                .await
+                .is_err());
+
+            registry
+                .execute_tool(
+                    "keys_apply",
+                    json!({ "desired_state": desired, "plan_hash": hash }),
+                )
+                .await
+                .unwrap();
+        })
+        .await;
+        assert_eq!(
+            state_manager.query_plugin_state("keys").await.unwrap(),
+            json!({"mtu": 9000})
+        );
+
+        // Front-ends that cannot identify their caller refuse every apply
+        registry
+            .add_middleware(Box::new(PolkitMiddleware::refusing("unknown caller")))
+            .await;
+        let operator = SecurityContext {
+            authenticated: true,
+            ..Default::default()
+        };
+        let refused = with_security_context(
+            operator,
+            registry.execute_tool(
+                "keys_apply",
+                json!({ "desired_state": {"mtu": 1500}, "plan_hash": hash }),
+            ),
+        )
+        .await;
+        assert!(refused.unwrap_err().to_string().contains("unknown caller"));
+        assert_eq!(
+            state_manager.query_plugin_state("keys").await.unwrap(),
+            json!({"mtu": 9000})
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! MCP prompts built from the agent definitions in `comprehensive-agents`
+//!
+//! Every `plugins/<plugin>/agents/<agent>.md` becomes a prompt named after
+//! the agent: its front-matter `name`, or the file name. The front-matter
+//! `description` describes the prompt, and `prompts/get` returns the
+//! markdown body followed by the optional `task` argument.
+
+use rust_embed::RustEmbed;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Agent definitions embedded at build time
+#[derive(RustEmbed)]
+#[folder = "comprehensive-agents"]
+struct AgentFiles;
+
+/// A prompt made from one agent definition
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub name: String,
+    pub description: String,
+    pub plugin: String,
+    pub body: String,
+}
+
+/// Prompts by name, for `prompts/list` and `prompts/get`
+#[derive(Debug, Default)]
+pub struct PromptRegistry {
+    prompts: BTreeMap<String, Prompt>,
+}
+
+impl PromptRegistry {
+    /// Prompts from the embedded `comprehensive-agents` directory
+    pub fn embedded() -> Self {
+        Self::from_files(AgentFiles::iter().filter_map(|path| {
+            let file = AgentFiles::get(&path)?;
+            Some((
+                path.to_string(),
+                String::from_utf8_lossy(&file.data).into_owned(),
+            ))
+        }))
+    }
+
+    /// Prompts from `(path, content)` pairs relative to `comprehensive-agents`
+    ///
+    /// An agent whose name is already taken by another plugin is named
+    /// `<plugin>-<agent>`.
+    pub fn from_files(files: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut files: Vec<(String, String)> = files.into_iter().collect();
+        files.sort();
+
+        let mut prompts = BTreeMap::new();
+        for (path, content) in files {
+            let parts: Vec<&str> = path.split('/').collect();
+            let ["plugins", plugin, "agents", file] = parts.as_slice() else {
+                continue;
+            };
+            let Some(stem) = file.strip_suffix(".md") else {
+                continue;
+            };
+
+            let (front_matter, body) = parse_front_matter(&content);
+            let mut name = front_matter
+                .get("name")
+                .cloned()
+                .unwrap_or_else(|| stem.to_string());
+            if prompts.contains_key(&name) {
+                name = format!("{}-{}", plugin, name);
+            }
+            let description = front_matter
+                .get("description")
+                .cloned()
+                .unwrap_or_else(|| format!("{} agent from the {} plugin", stem, plugin));
+
+            prompts.insert(
+                name.clone(),
+                Prompt {
+                    name,
+                    description,
+                    plugin: plugin.to_string(),
+                    body: body.trim().to_string(),
+                },
+            );
+        }
+        Self { prompts }
+    }
+
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+
+    /// Prompt descriptors for `prompts/list`
+    pub fn list(&self) -> Vec<Value> {
+        self.prompts
+            .values()
+            .map(|prompt| {
+                json!({
+                    "name": prompt.name,
+                    "description": prompt.description,
+                    "arguments": [{
+                        "name": "task",
+                        "description": "Task for the agent",
+                        "required": false
+                    }]
+                })
+            })
+            .collect()
+    }
+
+    /// Result of `prompts/get`, or `None` for an unknown prompt
+    pub fn get(&self, name: &str, arguments: &Value) -> Option<Value> {
+        let prompt = self.prompts.get(name)?;
+        let mut text = prompt.body.clone();
+        if let Some(task) = arguments["task"].as_str().filter(|t| !t.trim().is_empty()) {
+            text.push_str("\n\n## Task\n\n");
+            text.push_str(task);
+        }
+        Some(json!({
+            "description": prompt.description,
+            "messages": [{
+                "role": "user",
+                "content": {"type": "text", "text": text}
+            }]
+        }))
+    }
+}
+
+/// Split `---` delimited `key: value` front matter from the markdown body
+fn parse_front_matter(content: &str) -> (HashMap<String, String>, &str) {
+    let mut fields = HashMap::new();
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return (fields, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (fields, content);
+    };
+
+    for line in rest[..end].lines() {
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim().trim_matches('"');
+            if !value.is_empty() {
+                fields.insert(key.trim().to_string(), value.to_string());
+            }
+        }
+    }
+    let body = rest[end + 4..].trim_start_matches(['-', '\n']);
+    (fields, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompts_from_agent_markdown() {
+        let registry = PromptRegistry::from_files([
+            (
+                "plugins/observability-monitoring/agents/network-engineer.md".to_string(),
+                "---\nname: network-engineer\ndescription: Debugs network connectivity\nmodel: sonnet\n---\n\nYou are a network engineer.\n".to_string(),
+            ),
+            (
+                "plugins/cloud-infrastructure/agents/network-engineer.md".to_string(),
+                "You are a cloud network engineer.".to_string(),
+            ),
+            (
+                "plugins/cloud-infrastructure/commands/deploy.md".to_string(),
+                "Not an agent".to_string(),
+            ),
+        ]);
+        assert_eq!(registry.len(), 2);
+
+        let names: Vec<Value> = registry.list().iter().map(|p| p["name"].clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                json!("network-engineer"),
+                json!("observability-monitoring-network-engineer")
+            ]
+        );
+
+        let prompt = registry
+            .get(
+                "observability-monitoring-network-engineer",
+                &json!({"task": "Why is br0 down?"}),
+            )
+            .unwrap();
+        assert_eq!(prompt["description"], "Debugs network connectivity");
+        assert_eq!(
+            prompt["messages"][0]["content"]["text"],
+            "You are a network engineer.\n\n## Task\n\nWhy is br0 down?"
+        );
+
+        let prompt = registry.get("network-engineer", &json!({})).unwrap();
+        assert_eq!(
+            prompt["messages"][0]["content"]["text"],
+            "You are a cloud network engineer."
+        );
+        assert!(registry.get("missing", &json!({})).is_none());
+    }
+}
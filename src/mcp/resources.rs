@@ -28,19 +28,6 @@ impl ResourceRegistry {
     pub fn new() -> Self {
         let mut resources = HashMap::new();
 
-        // Embed agent documentation and specifications
-        resources.insert(
-            "agent://agents/overview".to_string(),
-            Resource {
-                uri: "agent://agents/overview".to_string(),
-                name: "Agent System Overview".to_string(),
-                description: "Complete overview of the agent-based architecture and guidelines"
-                    .to_string(),
-                mime_type: "text/markdown".to_string(),
-                content: include_str!("../../AGENTS.md").to_string(),
-            },
-        );
-
         // Individual agent specifications
         resources.insert(
             "agent://spec/executor".to_string(),
@@ -40,19 +40,6 @@ impl ResourceRegistry {
 
     /// Load all embedded resources (existing functionality)
     fn load_embedded_resources(&mut self) {
-        // Embed agent documentation and specifications
-        self.resources.insert(
-            "agent://agents/overview".to_string(),
-            Resource {
-                uri: "agent://agents/overview".to_string(),
-                name: "Agent System Overview".to_string(),
-                description: "Complete overview of the agent-based architecture and guidelines"
-                    .to_string(),
-                mime_type: "text/markdown".to_string(),
-                content: include_str!("../../AGENTS.md").to_string(),
-            },
-        );
-
         // Individual agent specifications
         self.add_embedded_resource(
             "agent://spec/executor",
@@ -167,21 +154,21 @@ impl ResourceRegistry {
             "dbus://design/hierarchical",
             "Hierarchical D-Bus Design",
             "Design document for the hierarchical D-Bus abstraction layer",
-            include_str!("../../HIERARCHICAL_DBUS_DESIGN.md"),
+            include_str!("../../docs/HIERARCHICAL_DBUS_DESIGN.md"),
         );
 
         self.add_embedded_resource(
             "dbus://guide/introspection",
             "D-Bus Introspection with zbus",
             "Comprehensive guide to D-Bus introspection using Rust zbus",
-            include_str!("../../d_bus_introspection_with_zbus.md"),
+            include_str!("../../docs/d_bus_introspection_with_zbus.md"),
         );
 
         self.add_embedded_resource(
             "dbus://guide/indexer-implementation",
             "D-Bus Indexer Implementation Guide",
             "Implementation guide for the D-Bus indexer based on zbus patterns",
-            include_str!("../../DBUS_INDEXER_IMPLEMENTATION_GUIDE.md"),
+            include_str!("../../docs/DBUS_INDEXER_IMPLEMENTATION_GUIDE.md"),
         );
 
         // Other documentation
@@ -189,14 +176,14 @@ impl ResourceRegistry {
             "snapshot://automation",
             "Snapshot Automation Guide",
             "Guide to BTRFS snapshot automation for D-Bus index",
-            include_str!("../../SNAPSHOT_AUTOMATION.md"),
+            include_str!("../../docs/SNAPSHOT_AUTOMATION.md"),
         );
 
         self.add_embedded_resource(
             "plugin://development-guide",
             "Plugin Development Guide",
             "Complete guide to developing plugins for op-dbus",
-            include_str!("../../PLUGIN-DEVELOPMENT-GUIDE.md"),
+            include_str!("../../docs/PLUGIN-DEVELOPMENT-GUIDE.md"),
         );
 
         self.add_embedded_resource(
@@ -383,8 +370,8 @@ mod tests {
         let resources = registry.list_resources();
         assert!(!resources.is_empty(), "Should have resources");
 
-        // Test agents category
-        let agents = registry.get_by_category("agents");
+        // Test agent specifications
+        let agents = registry.get_by_category("agent");
         assert!(!agents.is_empty(), "Should have agent resources");
 
         // Test commands category
@@ -2,13 +2,10 @@
 //!
 //! Provides SSE endpoints for long-running MCP operations
 
-use axum::{
-    response::{
+use axum::response::{
         sse::{Event, KeepAlive},
         Sse,
-    },
-    extract::State,
-};
+    };
 use futures::stream::{self, Stream};
 use serde_json::{json, Value};
 use std::convert::Infallible;
@@ -160,7 +157,7 @@ impl Default for SseEventBroadcaster {
 
 /// Create SSE stream from event receiver
 pub async fn create_sse_stream(
-    mut rx: mpsc::UnboundedReceiver<McpEvent>,
+    rx: mpsc::UnboundedReceiver<McpEvent>,
 ) -> impl Stream<Item = Result<Event, Infallible>> {
     stream::unfold(rx, |mut rx| async move {
         match rx.recv().await {
@@ -0,0 +1,194 @@
+//! Live system-state resources and their subscriptions
+//!
+//! `opdbus://state/<plugin>` is the current state of a plugin, read with
+//! `Query` on `/org/opdbus/state/<plugin>` of the op-dbus service on the
+//! system bus. `resources/subscribe` listens for the object's `StateChanged`
+//! and `DriftDetected` signals and sends `notifications/resources/updated`
+//! for the URI each time one arrives.
+
+use crate::native::dbus_marshal::value_to_json;
+use anyhow::{anyhow, Context, Result};
+use futures::StreamExt;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, Proxy};
+
+/// URI prefix of plugin state resources
+pub const STATE_URI_PREFIX: &str = "opdbus://state/";
+
+const SERVICE: &str = "org.opdbus";
+const STATE_PATH: &str = "/org/opdbus/state";
+const PLUGIN_INTERFACE: &str = "org.opdbus.StatePlugin";
+
+/// Plugin named by a state resource URI
+pub fn plugin_of(uri: &str) -> Option<&str> {
+    uri.strip_prefix(STATE_URI_PREFIX)
+        .filter(|plugin| !plugin.is_empty() && !plugin.contains('/'))
+}
+
+/// State resources of the op-dbus service and the client's subscriptions
+pub struct StateResources {
+    connection: Option<Connection>,
+    notifications: mpsc::UnboundedSender<Value>,
+    subscriptions: Mutex<HashMap<String, AbortHandle>>,
+}
+
+impl StateResources {
+    /// Resources on the system bus; without a bus there are none
+    pub async fn connect(notifications: mpsc::UnboundedSender<Value>) -> Self {
+        let connection = match Connection::system().await {
+            Ok(connection) => Some(connection),
+            Err(e) => {
+                log::warn!("State resources unavailable: {}", e);
+                None
+            }
+        };
+        Self {
+            connection,
+            notifications,
+            subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn connection(&self) -> Result<&Connection> {
+        self.connection
+            .as_ref()
+            .ok_or_else(|| anyhow!("Not connected to the system bus"))
+    }
+
+    async fn plugin_proxy(&self, plugin: &str) -> Result<Proxy<'static>> {
+        Proxy::new(
+            self.connection()?,
+            SERVICE,
+            format!("{}/{}", STATE_PATH, plugin),
+            PLUGIN_INTERFACE,
+        )
+        .await
+        .context("Failed to create plugin proxy")
+    }
+
+    /// Names of the plugins exported by the service, sorted
+    ///
+    /// Empty if op-dbus is not running.
+    pub async fn plugin_names(&self) -> Vec<String> {
+        let Ok(connection) = self.connection() else {
+            return Vec::new();
+        };
+        let objects = async {
+            zbus::fdo::ObjectManagerProxy::builder(connection)
+                .destination(SERVICE)?
+                .path(STATE_PATH)?
+                .build()
+                .await?
+                .get_managed_objects()
+                .await
+        };
+        let objects = match objects.await {
+            Ok(objects) => objects,
+            Err(e) => {
+                log::debug!("Failed to list op-dbus plugins: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut plugins: Vec<String> = objects
+            .keys()
+            .filter_map(|path| path.as_str().rsplit('/').next().map(String::from))
+            .collect();
+        plugins.sort();
+        plugins
+    }
+
+    /// Resource descriptors for every plugin exported by the service
+    ///
+    /// Empty if op-dbus is not running.
+    pub async fn list(&self) -> Vec<Value> {
+        self.plugin_names()
+            .await
+            .into_iter()
+            .map(|plugin| {
+                json!({
+                    "uri": format!("{}{}", STATE_URI_PREFIX, plugin),
+                    "name": format!("{} state", plugin),
+                    "description": format!("Current state of the {} plugin", plugin),
+                    "mimeType": "application/json"
+                })
+            })
+            .collect()
+    }
+
+    /// Current state of the plugin named by `uri`
+    pub async fn read(&self, uri: &str) -> Result<Value> {
+        let plugin = plugin_of(uri).ok_or_else(|| anyhow!("Not a state resource: {}", uri))?;
+        let state: HashMap<String, OwnedValue> = self
+            .plugin_proxy(plugin)
+            .await?
+            .call("Query", &())
+            .await
+            .with_context(|| format!("Failed to query {} state", plugin))?;
+
+        let mut json = Map::new();
+        for (key, value) in &state {
+            json.insert(key.clone(), value_to_json(value)?);
+        }
+        Ok(Value::Object(json))
+    }
+
+    /// Notify the client whenever the resource at `uri` changes
+    pub async fn subscribe(&self, uri: &str) -> Result<()> {
+        let plugin = plugin_of(uri).ok_or_else(|| anyhow!("Not a state resource: {}", uri))?;
+        if self.subscriptions.lock().unwrap().contains_key(uri) {
+            return Ok(());
+        }
+
+        let proxy = self.plugin_proxy(plugin).await?;
+        let changed = proxy.receive_signal("StateChanged").await?;
+        let drifted = proxy.receive_signal("DriftDetected").await?;
+        let mut signals = futures::stream::select(changed, drifted);
+
+        let notifications = self.notifications.clone();
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/resources/updated",
+            "params": {"uri": uri}
+        });
+        let task = tokio::spawn(async move {
+            // Keep the proxy, and with it the signal match rules, alive
+            let _proxy = proxy;
+            while signals.next().await.is_some() {
+                if notifications.send(notification.clone()).is_err() {
+                    break;
+                }
+            }
+        });
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .insert(uri.to_string(), task.abort_handle());
+        Ok(())
+    }
+
+    /// Stop notifying the client about `uri`
+    pub fn unsubscribe(&self, uri: &str) {
+        if let Some(task) = self.subscriptions.lock().unwrap().remove(uri) {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_of() {
+        assert_eq!(plugin_of("opdbus://state/net"), Some("net"));
+        assert_eq!(plugin_of("opdbus://state/"), None);
+        assert_eq!(plugin_of("opdbus://state/net/extra"), None);
+        assert_eq!(plugin_of("agent://agents/overview"), None);
+    }
+}
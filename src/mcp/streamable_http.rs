@@ -0,0 +1,613 @@
+//! MCP streamable-HTTP transport
+//!
+//! One endpoint serves the whole protocol:
+//! - `POST` carries JSON-RPC messages. `initialize` starts a session whose id
+//!   comes back in the `Mcp-Session-Id` header, which every later message
+//!   must carry. A request is answered with plain JSON unless it sends
+//!   notifications or is still running after [`SSE_AFTER`]; then the response
+//!   becomes an SSE stream of its notifications followed by its result.
+//! - `GET` opens an SSE stream of server-initiated messages, or with
+//!   `Last-Event-ID` resumes the stream that event belonged to.
+//! - `DELETE` ends the session and aborts its running requests.
+//!
+//! Event ids are `<stream>-<sequence>`, so a resumed stream replays only the
+//! events of the stream the client lost.
+
+use crate::mcp::tool_registry::{with_progress, ProgressReporter};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{post, MethodRouter};
+use axum::Json;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::{AbortHandle, JoinHandle};
+
+/// Header carrying the session id
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Header a client sends to resume a stream
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// A request still running after this long is answered with an SSE stream
+pub const SSE_AFTER: Duration = Duration::from_secs(2);
+
+/// Sessions unused for this long are dropped when the next one starts
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Events kept per session for resumption
+const HISTORY_LIMIT: usize = 1024;
+
+/// Stream of server-initiated messages, opened with `GET`
+const STANDALONE_STREAM: u64 = 0;
+
+/// Handles one JSON-RPC request and returns its response
+pub type McpHandler = Arc<dyn Fn(Value) -> BoxFuture<'static, Value> + Send + Sync>;
+
+#[derive(Debug)]
+struct StoredEvent {
+    seq: u64,
+    stream: u64,
+    /// `None` closes a request stream without a response (cancelled)
+    message: Option<Value>,
+    /// Last event of a request stream
+    last: bool,
+}
+
+impl StoredEvent {
+    fn id(&self) -> String {
+        format!("{}-{}", self.stream, self.seq)
+    }
+}
+
+/// `(stream, sequence)` of an event id
+fn parse_event_id(id: &str) -> Option<(u64, u64)> {
+    let (stream, seq) = id.split_once('-')?;
+    Some((stream.parse().ok()?, seq.parse().ok()?))
+}
+
+struct Session {
+    next_seq: AtomicU64,
+    next_stream: AtomicU64,
+    history: Mutex<VecDeque<Arc<StoredEvent>>>,
+    live: broadcast::Sender<Arc<StoredEvent>>,
+    /// Running requests by JSON-encoded id
+    requests: Mutex<HashMap<String, AbortHandle>>,
+    last_used: Mutex<Instant>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            next_seq: AtomicU64::new(1),
+            next_stream: AtomicU64::new(STANDALONE_STREAM + 1),
+            history: Mutex::new(VecDeque::new()),
+            live: broadcast::channel(256).0,
+            requests: Mutex::new(HashMap::new()),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_used.lock().unwrap().elapsed()
+    }
+
+    fn open_stream(&self) -> u64 {
+        self.next_stream.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn publish(&self, stream: u64, message: Option<Value>, last: bool) {
+        let mut history = self.history.lock().unwrap();
+        let event = Arc::new(StoredEvent {
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            stream,
+            message,
+            last,
+        });
+        history.push_back(event.clone());
+        if history.len() > HISTORY_LIMIT {
+            history.pop_front();
+        }
+        let _ = self.live.send(event);
+    }
+
+    fn replay(&self, stream: u64, after: u64) -> VecDeque<Arc<StoredEvent>> {
+        self.history
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.stream == stream && event.seq > after)
+            .cloned()
+            .collect()
+    }
+
+    fn cancel(&self, request_id: &Value) {
+        if let Some(task) = self
+            .requests
+            .lock()
+            .unwrap()
+            .remove(&request_id.to_string())
+        {
+            task.abort();
+        }
+    }
+
+    fn abort_all(&self) {
+        for (_, task) in self.requests.lock().unwrap().drain() {
+            task.abort();
+        }
+    }
+}
+
+/// Events of one stream: stored ones first, then live ones
+struct Subscription {
+    session: Weak<Session>,
+    stream: u64,
+    after: u64,
+    pending: VecDeque<Arc<StoredEvent>>,
+    live: broadcast::Receiver<Arc<StoredEvent>>,
+    done: bool,
+}
+
+impl Subscription {
+    /// Events of `stream` after sequence `after`, or only new ones for `None`
+    fn new(session: &Arc<Session>, stream: u64, after: Option<u64>) -> Self {
+        // Subscribe under the history lock so no event is missed or repeated
+        let history = session.history.lock().unwrap();
+        let live = session.live.subscribe();
+        let after = after.unwrap_or_else(|| history.back().map_or(0, |event| event.seq));
+        let pending = history
+            .iter()
+            .filter(|event| event.stream == stream && event.seq > after)
+            .cloned()
+            .collect();
+        Self {
+            session: Arc::downgrade(session),
+            stream,
+            after,
+            pending,
+            live,
+            done: false,
+        }
+    }
+
+    async fn next(&mut self) -> Option<Arc<StoredEvent>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let event = match self.pending.pop_front() {
+                Some(event) => event,
+                None => match self.live.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        self.pending = self.session.upgrade()?.replay(self.stream, self.after);
+                        continue;
+                    }
+                    // The session ended
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+            };
+            if event.stream != self.stream || event.seq <= self.after {
+                continue;
+            }
+            self.after = event.seq;
+            self.done = event.last;
+            return Some(event);
+        }
+    }
+
+    fn into_response(self) -> Response {
+        let events = futures::stream::unfold(self, |mut subscription| async move {
+            let event = subscription.next().await?;
+            Some((event, subscription))
+        })
+        .filter_map(|event| async move {
+            let message = event.message.as_ref()?;
+            Some(Ok::<_, Infallible>(
+                Event::default().id(event.id()).data(message.to_string()),
+            ))
+        });
+        Sse::new(events)
+            .keep_alive(KeepAlive::default())
+            .into_response()
+    }
+}
+
+fn accepts(headers: &HeaderMap, mime: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains(mime) || value.contains("*/*"))
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {"code": -32000, "message": message}
+    });
+    (status, Json(body)).into_response()
+}
+
+/// MCP streamable-HTTP endpoint in front of a JSON-RPC handler
+pub struct StreamableHttp {
+    handler: McpHandler,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    sse_after: Duration,
+}
+
+impl StreamableHttp {
+    pub fn new(handler: McpHandler) -> Self {
+        Self {
+            handler,
+            sessions: Mutex::new(HashMap::new()),
+            sse_after: SSE_AFTER,
+        }
+    }
+
+    /// Answer requests running longer than `sse_after` with an SSE stream
+    pub fn with_sse_after(mut self, sse_after: Duration) -> Self {
+        self.sse_after = sse_after;
+        self
+    }
+
+    /// `POST`, `GET` and `DELETE` handlers for the endpoint
+    pub fn router(self: Arc<Self>) -> MethodRouter {
+        let on_post = self.clone();
+        let on_get = self.clone();
+        let on_delete = self;
+        post(
+            move |headers: HeaderMap, Json(body): Json<Value>| async move {
+                on_post.post(&headers, body).await
+            },
+        )
+        .get(move |headers: HeaderMap| async move { on_get.get(&headers) })
+        .delete(move |headers: HeaderMap| async move { on_delete.delete(&headers) })
+    }
+
+    /// Number of open sessions
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Send a server-initiated message to every session's `GET` stream
+    pub fn notify(&self, message: Value) {
+        for session in self.sessions.lock().unwrap().values() {
+            session.publish(STANDALONE_STREAM, Some(message.clone()), false);
+        }
+    }
+
+    fn session(&self, headers: &HeaderMap) -> Result<Arc<Session>, (StatusCode, &'static str)> {
+        let Some(id) = headers.get(SESSION_HEADER).and_then(|id| id.to_str().ok()) else {
+            return Err((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"));
+        };
+        match self.sessions.lock().unwrap().get(id) {
+            Some(session) => {
+                session.touch();
+                Ok(session.clone())
+            }
+            None => Err((StatusCode::NOT_FOUND, "Unknown session")),
+        }
+    }
+
+    /// Run `request` as a task `notifications/cancelled` and `DELETE` can abort
+    ///
+    /// Its progress notifications go to `notifications` if given.
+    fn spawn(
+        &self,
+        session: &Arc<Session>,
+        request: Value,
+        notifications: Option<mpsc::UnboundedSender<Value>>,
+    ) -> JoinHandle<Value> {
+        let key = request["id"].to_string();
+        let reporter = notifications.zip(request["params"]["_meta"].get("progressToken").cloned());
+        let handler = self.handler.clone();
+        let task_session = session.clone();
+        let task_key = key.clone();
+
+        // Hold the lock until the handle is stored so a fast request cannot
+        // try to remove its entry first
+        let mut requests = session.requests.lock().unwrap();
+        let task = tokio::spawn(async move {
+            let response = match reporter {
+                Some((tx, token)) => {
+                    with_progress(ProgressReporter::new(token, tx), handler(request)).await
+                }
+                None => handler(request).await,
+            };
+            task_session.requests.lock().unwrap().remove(&task_key);
+            response
+        });
+        requests.insert(key, task.abort_handle());
+        task
+    }
+
+    /// Handle a `POST` of one message or a batch
+    pub async fn post(&self, headers: &HeaderMap, body: Value) -> Response {
+        let batch = body.is_array();
+        let messages = match body {
+            Value::Array(messages) => messages,
+            message => vec![message],
+        };
+
+        if messages
+            .iter()
+            .any(|message| message["method"] == "initialize")
+        {
+            if messages.len() != 1 {
+                return error_response(StatusCode::BAD_REQUEST, "initialize must be sent alone");
+            }
+            return self.initialize(messages.into_iter().next().unwrap()).await;
+        }
+
+        let session = match self.session(headers) {
+            Ok(session) => session,
+            Err((status, message)) => return error_response(status, message),
+        };
+
+        let mut requests = Vec::new();
+        for message in messages {
+            if message.get("method").is_some() && message.get("id").is_some() {
+                requests.push(message);
+            } else if message["method"] == "notifications/cancelled" {
+                session.cancel(&message["params"]["requestId"]);
+            }
+            // Other notifications and responses to the server need no answer
+        }
+
+        if requests.is_empty() {
+            return StatusCode::ACCEPTED.into_response();
+        }
+        if batch {
+            let tasks: Vec<_> = requests
+                .into_iter()
+                .map(|request| self.spawn(&session, request, None))
+                .collect();
+            let responses: Vec<Value> = futures::future::join_all(tasks)
+                .await
+                .into_iter()
+                .filter_map(Result::ok)
+                .collect();
+            return Json(Value::Array(responses)).into_response();
+        }
+
+        let request = requests.pop().unwrap();
+        if !accepts(headers, "text/event-stream") {
+            return match self.spawn(&session, request, None).await {
+                Ok(response) => Json(response).into_response(),
+                Err(_) => StatusCode::ACCEPTED.into_response(),
+            };
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut task = self.spawn(&session, request, Some(tx));
+        let first = tokio::select! {
+            result = &mut task => {
+                return match result {
+                    Ok(response) => Json(response).into_response(),
+                    // Cancelled requests are not answered
+                    Err(_) => StatusCode::ACCEPTED.into_response(),
+                };
+            }
+            Some(notification) = rx.recv() => Some(notification),
+            _ = tokio::time::sleep(self.sse_after) => None,
+        };
+
+        // Still running or reporting progress: answer with a stream
+        let stream = session.open_stream();
+        let subscription = Subscription::new(&session, stream, Some(0));
+        tokio::spawn(async move {
+            if first.is_some() {
+                session.publish(stream, first, false);
+            }
+            loop {
+                tokio::select! {
+                    biased;
+                    Some(notification) = rx.recv() => {
+                        session.publish(stream, Some(notification), false);
+                    }
+                    result = &mut task => {
+                        while let Ok(notification) = rx.try_recv() {
+                            session.publish(stream, Some(notification), false);
+                        }
+                        session.publish(stream, result.ok(), true);
+                        break;
+                    }
+                }
+            }
+        });
+        subscription.into_response()
+    }
+
+    async fn initialize(&self, request: Value) -> Response {
+        let response = (self.handler)(request).await;
+        if response.get("error").is_some() {
+            return Json(response).into_response();
+        }
+
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.retain(|_, session| session.idle_for() < SESSION_IDLE_TIMEOUT);
+            sessions.insert(id.clone(), Arc::new(Session::new()));
+        }
+        log::info!("Started MCP session {}", id);
+
+        let mut response = Json(response).into_response();
+        response
+            .headers_mut()
+            .insert(SESSION_HEADER, HeaderValue::from_str(&id).unwrap());
+        response
+    }
+
+    /// Handle a `GET`: open the session's stream or resume a lost one
+    pub fn get(&self, headers: &HeaderMap) -> Response {
+        if !accepts(headers, "text/event-stream") {
+            return StatusCode::NOT_ACCEPTABLE.into_response();
+        }
+        let session = match self.session(headers) {
+            Ok(session) => session,
+            Err((status, message)) => return error_response(status, message),
+        };
+
+        let subscription = match headers.get(LAST_EVENT_ID_HEADER) {
+            Some(id) => match id.to_str().ok().and_then(parse_event_id) {
+                Some((stream, seq)) => Subscription::new(&session, stream, Some(seq)),
+                None => return error_response(StatusCode::BAD_REQUEST, "Invalid Last-Event-ID"),
+            },
+            None => Subscription::new(&session, STANDALONE_STREAM, None),
+        };
+        subscription.into_response()
+    }
+
+    /// Handle a `DELETE`: end the session
+    pub fn delete(&self, headers: &HeaderMap) -> Response {
+        let session = match self.session(headers) {
+            Ok(session) => session,
+            Err((status, message)) => return error_response(status, message),
+        };
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, other| !Arc::ptr_eq(other, &session));
+        session.abort_all();
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::tool_registry::report_progress;
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let handler: McpHandler = Arc::new(|request: Value| {
+            Box::pin(async move {
+                if request["method"] == "tools/call" {
+                    report_progress("working");
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                json!({"jsonrpc": "2.0", "id": request["id"], "result": {"method": request["method"]}})
+            })
+        });
+        let transport = StreamableHttp::new(handler).with_sse_after(Duration::from_millis(10));
+        Router::new().route("/mcp", Arc::new(transport).router())
+    }
+
+    async fn send(
+        app: &Router,
+        method: Method,
+        session: Option<&str>,
+        body: Option<Value>,
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri("/mcp")
+            .header(header::ACCEPT, "application/json, text/event-stream")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(session) = session {
+            request = request.header(SESSION_HEADER, session);
+        }
+        let body = body.map_or(Body::empty(), |body| Body::from(body.to_string()));
+        app.clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn text(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let app = app();
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let response = send(&app, Method::POST, None, Some(initialize)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+        let missing = send(&app, Method::POST, None, Some(list.clone())).await;
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+        let response = send(&app, Method::POST, Some(&session), Some(list.clone())).await;
+        assert_eq!(
+            text(response).await,
+            r#"{"id":2,"jsonrpc":"2.0","result":{"method":"tools/list"}}"#
+        );
+
+        let initialized = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        let response = send(&app, Method::POST, Some(&session), Some(initialized)).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // A slow call with progress becomes a stream ending in its response
+        let call = json!({
+            "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+            "params": {"name": "slow", "_meta": {"progressToken": "p"}}
+        });
+        let response = send(&app, Method::POST, Some(&session), Some(call)).await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let stream = text(response).await;
+        let ids: Vec<&str> = stream
+            .lines()
+            .filter_map(|line| line.strip_prefix("id: "))
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(stream.contains("notifications/progress"));
+        assert!(stream.contains(r#""id":3"#));
+
+        // The stream can be resumed after its first event
+        let resume = Request::builder()
+            .method(Method::GET)
+            .uri("/mcp")
+            .header(header::ACCEPT, "text/event-stream")
+            .header(SESSION_HEADER, &session)
+            .header(LAST_EVENT_ID_HEADER, ids[0]);
+        let response = app
+            .clone()
+            .oneshot(resume.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let resumed = text(response).await;
+        assert!(!resumed.contains("notifications/progress"));
+        assert!(resumed.contains(&format!("id: {}", ids[1])));
+
+        let response = send(&app, Method::DELETE, Some(&session), None).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send(&app, Method::POST, Some(&session), Some(list)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_parse_event_id() {
+        assert_eq!(parse_event_id("3-17"), Some((3, 17)));
+        assert_eq!(parse_event_id("17"), None);
+        assert_eq!(parse_event_id("a-1"), None);
+    }
+}
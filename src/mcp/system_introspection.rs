@@ -507,6 +507,7 @@ impl SystemIntrospector {
 }
 
 /// CLI tool for system introspection
+#[allow(dead_code)] // unused when built into the library
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
@@ -0,0 +1,336 @@
+//! Policy deciding which tools a session may run
+//!
+//! `ToolRegistry::execute_tool` checks every call against the tool's
+//! metadata and the caller's `SecurityContext`:
+//! - tools on the deny list, or missing from a non-empty allow list, are refused
+//! - tools above the session's maximum security level are refused
+//! - `requires_auth` tools need an authenticated session
+//! - tools with a configured permission need a session holding it
+//! - tools at or above the approval level (High by default) run only after
+//!   the operator approved the call, asked through the client
+//!
+//! Front-ends that cannot ask, such as the chat server, answer approvals with
+//! an [`UnattendedApprover`] as `without_approver` says for them. Every
+//! front-end refuses such calls unless the policy file sets it to `allow`.
+//!
+//! The policy is read from `OPDBUS_TOOL_POLICY` (default
+//! /etc/op-dbus/tool-policy.json). Without the file every level may run and
+//! High and Critical tools need approval. Refusals are [`PolicyDenial`]s,
+//! which MCP servers return as structured errors with code [`POLICY_DENIED`].
+
+use super::tool_registry::{SecurityContext, SecurityLevel, ToolMetadata};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const DEFAULT_POLICY_FILE: &str = "/etc/op-dbus/tool-policy.json";
+
+/// JSON-RPC error code of a call refused by the policy
+pub const POLICY_DENIED: i32 = -32001;
+
+/// Tool execution policy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolPolicy {
+    /// Highest level a session may run, unless its context lowers it
+    pub max_level: SecurityLevel,
+    /// Level from which calls need operator approval; `null` never asks
+    pub approval_level: Option<SecurityLevel>,
+    /// If not empty, only matching tools may run; a trailing `*` matches a prefix
+    pub allow: Vec<String>,
+    /// Matching tools never run
+    pub deny: Vec<String>,
+    /// Permission a session needs, by tool pattern
+    pub permissions: BTreeMap<String, String>,
+    /// What front-ends that cannot ask for approval do with calls needing
+    /// it, by front-end name; unlisted ones refuse them
+    pub without_approver: BTreeMap<String, WithoutApprover>,
+}
+
+/// Answer to approvals on a front-end that cannot ask the operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithoutApprover {
+    /// Run the call, relying on the front-end's own authentication
+    Allow,
+    Refuse,
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        Self {
+            max_level: SecurityLevel::Critical,
+            approval_level: Some(SecurityLevel::High),
+            allow: Vec::new(),
+            deny: Vec::new(),
+            permissions: BTreeMap::new(),
+            without_approver: BTreeMap::new(),
+        }
+    }
+}
+
+/// What a call needs besides the policy allowing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    NeedsApproval,
+}
+
+/// Why the policy refused a tool call
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum PolicyDenial {
+    #[error("Tool '{tool}' is denied by policy")]
+    Denied { tool: String },
+    #[error("Tool '{tool}' is not in the allowed tools")]
+    NotAllowed { tool: String },
+    #[error("Tool '{tool}' is {level:?} security, above the session's maximum of {max_level:?}")]
+    LevelTooHigh {
+        tool: String,
+        level: SecurityLevel,
+        max_level: SecurityLevel,
+    },
+    #[error("Tool '{tool}' requires an authenticated session")]
+    AuthenticationRequired { tool: String },
+    #[error("Tool '{tool}' requires permission '{permission}'")]
+    PermissionRequired { tool: String, permission: String },
+    #[error("Calling tool '{tool}' was not approved")]
+    NotApproved { tool: String },
+    #[error("Tool '{tool}' needs approval, but it cannot be asked for: {detail}")]
+    ApprovalUnavailable { tool: String, detail: String },
+}
+
+/// Asks the operator to approve a tool call
+#[async_trait]
+pub trait Approver: Send + Sync {
+    /// Whether the operator approved calling the tool with `params`
+    async fn approve(&self, metadata: &ToolMetadata, params: &Value) -> Result<bool>;
+}
+
+/// Approver of a front-end that cannot ask, answering as the policy says
+pub struct UnattendedApprover {
+    frontend: String,
+    answer: WithoutApprover,
+}
+
+#[async_trait]
+impl Approver for UnattendedApprover {
+    async fn approve(&self, metadata: &ToolMetadata, _params: &Value) -> Result<bool> {
+        match self.answer {
+            WithoutApprover::Allow => {
+                log::info!(
+                    "Running {:?} security tool '{}' without approval on the {} front-end",
+                    metadata.security_level,
+                    metadata.name,
+                    self.frontend
+                );
+                Ok(true)
+            }
+            WithoutApprover::Refuse => anyhow::bail!(
+                "the {} front-end cannot ask for approval (see without_approver in the tool policy)",
+                self.frontend
+            ),
+        }
+    }
+}
+
+fn matches(pattern: &str, tool: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => tool.starts_with(prefix),
+        None => pattern == tool,
+    }
+}
+
+impl ToolPolicy {
+    /// Policy from `OPDBUS_TOOL_POLICY`, or the default without the file
+    pub fn load() -> Result<Self> {
+        let path =
+            std::env::var("OPDBUS_TOOL_POLICY").unwrap_or_else(|_| DEFAULT_POLICY_FILE.to_string());
+        let path = Path::new(&path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_file(path)
+    }
+
+    /// What `frontend` does with calls needing approval
+    pub fn without_approver(&self, frontend: &str) -> WithoutApprover {
+        self.without_approver
+            .get(frontend)
+            .copied()
+            .unwrap_or(WithoutApprover::Refuse)
+    }
+
+    /// Approver for `frontend`, which cannot ask the operator
+    pub fn unattended_approver(&self, frontend: &str) -> UnattendedApprover {
+        UnattendedApprover {
+            frontend: frontend.to_string(),
+            answer: self.without_approver(frontend),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tool policy {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid tool policy {}", path.display()))
+    }
+
+    /// Check a call of the tool described by `metadata` from `context`
+    pub fn check(
+        &self,
+        metadata: &ToolMetadata,
+        context: &SecurityContext,
+    ) -> std::result::Result<Verdict, PolicyDenial> {
+        let tool = metadata.name.clone();
+        if self.deny.iter().any(|pattern| matches(pattern, &tool)) {
+            return Err(PolicyDenial::Denied { tool });
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|pattern| matches(pattern, &tool)) {
+            return Err(PolicyDenial::NotAllowed { tool });
+        }
+
+        let max_level = match &context.max_level {
+            Some(level) => level.clone().min(self.max_level.clone()),
+            None => self.max_level.clone(),
+        };
+        if metadata.security_level > max_level {
+            return Err(PolicyDenial::LevelTooHigh {
+                tool,
+                level: metadata.security_level.clone(),
+                max_level,
+            });
+        }
+
+        if metadata.requires_auth && !context.authenticated {
+            return Err(PolicyDenial::AuthenticationRequired { tool });
+        }
+        for (pattern, permission) in &self.permissions {
+            if matches(pattern, &tool) && !context.permissions.contains(permission) {
+                return Err(PolicyDenial::PermissionRequired {
+                    tool,
+                    permission: permission.clone(),
+                });
+            }
+        }
+
+        match &self.approval_level {
+            Some(level) if metadata.security_level >= *level => Ok(Verdict::NeedsApproval),
+            _ => Ok(Verdict::Allow),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str, security_level: SecurityLevel, requires_auth: bool) -> ToolMetadata {
+        ToolMetadata {
+            name: name.to_string(),
+            description: String::new(),
+            category: "test".to_string(),
+            tags: vec![],
+            author: None,
+            version: "1.0.0".to_string(),
+            security_level,
+            requires_auth,
+        }
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let policy: ToolPolicy = serde_json::from_value(serde_json::json!({
+            "max_level": "High",
+            "deny": ["exec_*"],
+            "permissions": {"net_*": "network-admin"}
+        }))
+        .unwrap();
+        assert_eq!(policy.approval_level, Some(SecurityLevel::High));
+
+        let anonymous = SecurityContext::default();
+        let operator = SecurityContext {
+            authenticated: true,
+            permissions: vec!["network-admin".to_string()],
+            ..Default::default()
+        };
+
+        let status = tool("systemd_status", SecurityLevel::Low, false);
+        assert_eq!(policy.check(&status, &anonymous), Ok(Verdict::Allow));
+        assert!(matches!(
+            policy.check(&tool("exec_command", SecurityLevel::Low, false), &operator),
+            Err(PolicyDenial::Denied { .. })
+        ));
+        assert!(matches!(
+            policy.check(
+                &tool("firewall_rules", SecurityLevel::Critical, false),
+                &operator
+            ),
+            Err(PolicyDenial::LevelTooHigh {
+                max_level: SecurityLevel::High,
+                ..
+            })
+        ));
+
+        let apply = tool("net_apply", SecurityLevel::High, true);
+        assert!(matches!(
+            policy.check(&apply, &anonymous),
+            Err(PolicyDenial::AuthenticationRequired { .. })
+        ));
+        let without_permission = SecurityContext {
+            authenticated: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            policy.check(&apply, &without_permission),
+            Err(PolicyDenial::PermissionRequired { .. })
+        ));
+        assert_eq!(policy.check(&apply, &operator), Ok(Verdict::NeedsApproval));
+
+        // A session can be held below the policy's maximum
+        let limited = SecurityContext {
+            max_level: Some(SecurityLevel::Medium),
+            ..operator.clone()
+        };
+        assert!(policy.check(&apply, &limited).is_err());
+
+        let allow_list = ToolPolicy {
+            allow: vec!["systemd_*".to_string()],
+            ..ToolPolicy::default()
+        };
+        assert_eq!(allow_list.check(&status, &anonymous), Ok(Verdict::Allow));
+        assert!(matches!(
+            allow_list.check(&tool("net_query", SecurityLevel::Low, false), &anonymous),
+            Err(PolicyDenial::NotAllowed { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unattended_approver() {
+        let policy = ToolPolicy::default();
+        let apply = tool("net_apply", SecurityLevel::High, false);
+        for frontend in ["chat", "manager", "kiosk"] {
+            assert_eq!(policy.without_approver(frontend), WithoutApprover::Refuse);
+            let refused = policy
+                .unattended_approver(frontend)
+                .approve(&apply, &Value::Null)
+                .await
+                .unwrap_err();
+            assert!(refused.to_string().contains("without_approver"));
+        }
+
+        let opted_in: ToolPolicy =
+            serde_json::from_value(serde_json::json!({"without_approver": {"chat": "allow"}}))
+                .unwrap();
+        assert_eq!(opted_in.without_approver("chat"), WithoutApprover::Allow);
+        assert!(opted_in
+            .unattended_approver("chat")
+            .approve(&apply, &Value::Null)
+            .await
+            .unwrap());
+        assert_eq!(opted_in.without_approver("manager"), WithoutApprover::Refuse);
+    }
+}
@@ -17,7 +17,7 @@
 //! - **State Plugins**: Metadata about plugin capabilities (rollback, checkpoints, etc.)
 //!
 //! This consolidation means AI agents and web UIs only need to call:
-//! ```no_run
+//! ```ignore
 //! let introspection = tool_registry.get_introspection().await;
 //! // Contains everything needed to understand system capabilities
 //! ```
@@ -29,13 +29,17 @@
 //!
 //! Now it's all in one place.
 
+use crate::audit::{self, AuditRecord, Frontend};
+use crate::native::polkit::{AuthorizationError, PolkitAuthority, Subject};
+use super::tool_policy::{Approver, PolicyDenial, ToolPolicy, Verdict};
 use anyhow::{bail, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 
 /// Tool trait that all MCP tools must implement
 #[async_trait]
@@ -241,21 +245,93 @@ pub trait ToolFactory: Send + Sync {
     fn tool_name(&self) -> &str;
 }
 
+/// Registered tools by name
+type ToolMap = HashMap<String, Arc<Box<dyn Tool>>>;
+
 /// Tool registry for managing all tools
 pub struct ToolRegistry {
-    tools: Arc<RwLock<HashMap<String, Arc<Box<dyn Tool>>>>>,
+    tools: Arc<RwLock<ToolMap>>,
     factories: Arc<RwLock<HashMap<String, Box<dyn ToolFactory>>>>,
     categories: Arc<RwLock<HashMap<String, Vec<String>>>>,
     middleware: Arc<RwLock<Vec<Box<dyn ToolMiddleware>>>>,
+    policy: Arc<RwLock<ToolPolicy>>,
+    approver: Arc<RwLock<Option<Arc<dyn Approver>>>>,
 }
 
 /// Security context for authentication
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct SecurityContext {
     pub user_id: Option<String>,
     pub session_id: Option<String>,
     pub authenticated: bool,
     pub permissions: Vec<String>,
+    /// Highest security level this session may run, below the policy's
+    pub max_level: Option<SecurityLevel>,
+}
+
+tokio::task_local! {
+    static PROGRESS: ProgressReporter;
+    static SESSION: SecurityContext;
+}
+
+/// Run tool calls of a session with its own security context
+///
+/// Outside of this the context of the registry's `SecurityMiddleware` applies.
+pub async fn with_security_context<F: std::future::Future>(
+    context: SecurityContext,
+    fut: F,
+) -> F::Output {
+    SESSION.scope(context, fut).await
+}
+
+/// Sends `notifications/progress` for a tool call whose client asked for it
+/// with a `progressToken`
+#[derive(Clone)]
+pub struct ProgressReporter {
+    token: Value,
+    step: Arc<AtomicU64>,
+    notifications: mpsc::UnboundedSender<Value>,
+}
+
+impl ProgressReporter {
+    pub fn new(token: Value, notifications: mpsc::UnboundedSender<Value>) -> Self {
+        Self {
+            token,
+            step: Arc::new(AtomicU64::new(0)),
+            notifications,
+        }
+    }
+
+    /// Send the next step; progress counts the steps reported so far
+    pub fn report(&self, message: &str) {
+        let step = self.step.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = self.notifications.send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": {
+                "progressToken": self.token,
+                "progress": step,
+                "message": message,
+            }
+        }));
+    }
+}
+
+/// Run a tool call with `reporter` receiving its progress
+pub async fn with_progress<F: std::future::Future>(reporter: ProgressReporter, fut: F) -> F::Output {
+    PROGRESS.scope(reporter, fut).await
+}
+
+/// Report progress of the current tool call
+///
+/// Does nothing unless the client asked for progress.
+pub fn report_progress(message: &str) {
+    let _ = PROGRESS.try_with(|reporter| reporter.report(message));
+}
+
+/// Reporter of the current tool call, for passing its progress on
+pub fn current_progress() -> Option<ProgressReporter> {
+    PROGRESS.try_with(ProgressReporter::clone).ok()
 }
 
 /// Middleware for tool execution
@@ -266,6 +342,11 @@ pub trait ToolMiddleware: Send + Sync {
 
     /// Called after tool execution
     async fn after_execute(&self, tool_name: &str, params: &Value, result: &Result<ToolResult>);
+
+    /// Security context the registry checks calls against, if this middleware holds one
+    async fn security_context(&self) -> Option<SecurityContext> {
+        None
+    }
 }
 
 /// Security validation middleware
@@ -273,15 +354,16 @@ pub struct SecurityMiddleware {
     security_context: Arc<RwLock<SecurityContext>>,
 }
 
+impl Default for SecurityMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SecurityMiddleware {
     pub fn new() -> Self {
         Self {
-            security_context: Arc::new(RwLock::new(SecurityContext {
-                user_id: None,
-                session_id: None,
-                authenticated: false,
-                permissions: vec![],
-            })),
+            security_context: Arc::new(RwLock::new(SecurityContext::default())),
         }
     }
 
@@ -295,88 +377,31 @@ impl SecurityMiddleware {
     }
 }
 
+/// Levels, authentication and permissions are enforced by the registry's
+/// `ToolPolicy` from the tool metadata; this validates the parameters of
+/// critical operations and logs who ran sensitive tools.
 #[async_trait]
 impl ToolMiddleware for SecurityMiddleware {
     async fn before_execute(&self, tool_name: &str, params: &Value) -> Result<()> {
-        let ctx = self.security_context.read().await;
-
-        // Get tool metadata (we need to find the tool to get its metadata)
-        // For now, use basic security checks based on tool name
-        let security_level = self.infer_security_level(tool_name);
-
-        // Check authentication requirements
-        match security_level {
-            SecurityLevel::Low => {
-                // No special requirements for low security operations
-            }
-            SecurityLevel::Medium => {
-                if !ctx.authenticated {
-                    return Err(anyhow::anyhow!("Authentication required for medium security operation: {}", tool_name));
-                }
-            }
-            SecurityLevel::High => {
-                if !ctx.authenticated {
-                    return Err(anyhow::anyhow!("Authentication required for high security operation: {}", tool_name));
-                }
-                // Check for admin permission
-                if !ctx.permissions.contains(&"admin".to_string()) {
-                    return Err(anyhow::anyhow!("Admin permission required for high security operation: {}", tool_name));
-                }
-            }
-            SecurityLevel::Critical => {
-                if !ctx.authenticated {
-                    return Err(anyhow::anyhow!("Authentication required for critical security operation: {}", tool_name));
-                }
-                // Check for super-admin permission
-                if !ctx.permissions.contains(&"super-admin".to_string()) {
-                    return Err(anyhow::anyhow!("Super-admin permission required for critical operation: {}", tool_name));
-                }
-                // Additional validation for critical operations
-                self.validate_critical_operation(tool_name, params)?;
-            }
-        }
-
-        log::info!("Security check passed for tool '{}' at level {:?}", tool_name, security_level);
-        Ok(())
+        self.validate_critical_operation(tool_name, params)
     }
 
     async fn after_execute(&self, tool_name: &str, _params: &Value, result: &Result<ToolResult>) {
-        let security_level = self.infer_security_level(tool_name);
-
-        // Enhanced audit logging for sensitive operations
-        if security_level >= SecurityLevel::High {
-            let ctx = self.security_context.read().await;
-            let success = result.is_ok();
-            let error_msg = result.as_ref().err().map(|e| e.to_string());
+        let ctx = self.security_context.read().await;
+        log::info!(
+            "Tool '{}' executed by user {:?}, success: {}",
+            tool_name,
+            ctx.user_id,
+            result.is_ok()
+        );
+    }
 
-            log::warn!(
-                "AUDIT: Tool '{}' executed by user {:?}, level {:?}, success: {}, error: {:?}",
-                tool_name, ctx.user_id, security_level, success, error_msg
-            );
-        }
+    async fn security_context(&self) -> Option<SecurityContext> {
+        Some(self.get_security_context().await)
     }
 }
 
 impl SecurityMiddleware {
-    fn infer_security_level(&self, tool_name: &str) -> SecurityLevel {
-        match tool_name {
-            // Low security - read-only operations
-            "file_read" | "network_interfaces" | "process_list" => SecurityLevel::Low,
-
-            // Medium security - status checks
-            "systemd_status" => SecurityLevel::Medium,
-
-            // High security - system management
-            "create_ovs_bridge" | "systemd_control" | "network_config" => SecurityLevel::High,
-
-            // Critical security - system-altering operations
-            "exec_command" | "system_shutdown" | "firewall_rules" => SecurityLevel::Critical,
-
-            // Default to medium for unknown tools
-            _ => SecurityLevel::Medium,
-        }
-    }
-
     fn validate_critical_operation(&self, tool_name: &str, params: &Value) -> Result<()> {
         match tool_name {
             "exec_command" => {
@@ -388,18 +413,23 @@ impl SecurityMiddleware {
                     }
                 }
             }
-            "system_shutdown" => {
+            "system_shutdown"
                 // Require explicit confirmation parameter
-                if !params.get("confirmed").and_then(|c| c.as_bool()).unwrap_or(false) {
+                if !params.get("confirmed").and_then(|c| c.as_bool()).unwrap_or(false) => {
                     return Err(anyhow::anyhow!("System shutdown requires explicit confirmation"));
                 }
-            }
             _ => {}
         }
         Ok(())
     }
 }
 
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
@@ -407,9 +437,28 @@ impl ToolRegistry {
             factories: Arc::new(RwLock::new(HashMap::new())),
             categories: Arc::new(RwLock::new(HashMap::new())),
             middleware: Arc::new(RwLock::new(Vec::new())),
+            policy: Arc::new(RwLock::new(ToolPolicy::default())),
+            approver: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Replace the policy tool calls are checked against
+    pub async fn set_policy(&self, policy: ToolPolicy) {
+        *self.policy.write().await = policy;
+    }
+
+    pub async fn policy(&self) -> ToolPolicy {
+        self.policy.read().await.clone()
+    }
+
+    /// Set who approves calls of High and Critical tools
+    ///
+    /// Without an approver such calls are refused; front-ends that cannot
+    /// ask set the policy's `unattended_approver` instead.
+    pub async fn set_approver(&self, approver: Arc<dyn Approver>) {
+        *self.approver.write().await = Some(approver);
+    }
+
     /// Register a tool instance
     pub async fn register_tool(&self, tool: Box<dyn Tool>) -> Result<()> {
         let name = tool.name().to_string();
@@ -432,6 +481,23 @@ impl ToolRegistry {
         Ok(())
     }
 
+    /// Remove a tool instance, returning whether it was registered
+    pub async fn unregister_tool(&self, name: &str) -> bool {
+        let Some(tool) = self.tools.write().await.remove(name) else {
+            return false;
+        };
+
+        let mut categories = self.categories.write().await;
+        let category = tool.metadata().category;
+        if let Some(names) = categories.get_mut(&category) {
+            names.retain(|registered| registered != name);
+            if names.is_empty() {
+                categories.remove(&category);
+            }
+        }
+        true
+    }
+
     /// Register a tool factory
     pub async fn register_factory(&self, factory: Box<dyn ToolFactory>) -> Result<()> {
         let name = factory.tool_name().to_string();
@@ -479,8 +545,18 @@ impl ToolRegistry {
             }
         };
 
-        // Call before middleware
         let middlewares = self.middleware.read().await;
+        if let Err(denial) = self.authorize(&tool.metadata(), &params, &middlewares).await {
+            let record = tool_audit_record(name, &params);
+            match denial {
+                PolicyDenial::ApprovalUnavailable { .. } => record.failed(denial.to_string()),
+                _ => record.denied(denial.to_string()),
+            }
+            .record();
+            return Err(denial.into());
+        }
+
+        // Call before middleware
         for mw in middlewares.iter() {
             mw.before_execute(name, &params).await?;
         }
@@ -499,6 +575,50 @@ impl ToolRegistry {
         result
     }
 
+    /// Check a call against the policy and the caller's security context,
+    /// asking for approval where the policy wants it
+    async fn authorize(
+        &self,
+        metadata: &ToolMetadata,
+        params: &Value,
+        middlewares: &[Box<dyn ToolMiddleware>],
+    ) -> std::result::Result<(), PolicyDenial> {
+        let context = match SESSION.try_with(SecurityContext::clone) {
+            Ok(context) => context,
+            Err(_) => {
+                let mut context = SecurityContext::default();
+                for mw in middlewares {
+                    if let Some(held) = mw.security_context().await {
+                        context = held;
+                        break;
+                    }
+                }
+                context
+            }
+        };
+
+        let verdict = self.policy.read().await.check(metadata, &context)?;
+        if verdict == Verdict::Allow {
+            return Ok(());
+        }
+
+        let tool = metadata.name.clone();
+        let Some(approver) = self.approver.read().await.clone() else {
+            return Err(PolicyDenial::ApprovalUnavailable {
+                tool,
+                detail: "no approver configured".to_string(),
+            });
+        };
+        match approver.approve(metadata, params).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(PolicyDenial::NotApproved { tool }),
+            Err(e) => Err(PolicyDenial::ApprovalUnavailable {
+                tool,
+                detail: e.to_string(),
+            }),
+        }
+    }
+
     /// List all registered tools
     pub async fn list_tools(&self) -> Vec<ToolInfo> {
         let tools = self.tools.read().await;
@@ -614,7 +734,10 @@ impl ToolMiddleware for LoggingMiddleware {
     }
 }
 
-/// Example audit middleware
+/// Audit middleware
+///
+/// Every call is written to the persistent audit log (see [`crate::audit`]);
+/// the most recent entries are also kept in memory.
 pub struct AuditMiddleware {
     audit_log: Arc<RwLock<Vec<AuditEntry>>>,
 }
@@ -628,6 +751,12 @@ pub struct AuditEntry {
     pub error: Option<String>,
 }
 
+impl Default for AuditMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AuditMiddleware {
     pub fn new() -> Self {
         Self {
@@ -648,6 +777,10 @@ impl ToolMiddleware for AuditMiddleware {
     }
 
     async fn after_execute(&self, tool_name: &str, params: &Value, result: &Result<ToolResult>) {
+        tool_audit_record(tool_name, params)
+            .with_result(result)
+            .record();
+
         let entry = AuditEntry {
             timestamp: chrono::Utc::now(),
             tool_name: tool_name.to_string(),
@@ -667,6 +800,94 @@ impl ToolMiddleware for AuditMiddleware {
     }
 }
 
+/// polkit authorization of apply tools for the MCP client process
+///
+/// `plugin_<name>_apply` and `<name>_apply` need the
+/// `org.opdbus.apply.<name>` action, like `Apply` on the plugin's D-Bus
+/// object. Every tool ending in `_apply` is checked, whether or not a plugin
+/// of that name is known: for unknown names, including re-exported
+/// `<server>__<tool>_apply`, polkit falls back to `org.opdbus.apply`.
+pub struct PolkitMiddleware {
+    caller: PolkitCaller,
+}
+
+enum PolkitCaller {
+    Subject(PolkitAuthority, Subject),
+    /// The caller cannot be identified; every apply is refused with the reason
+    Unidentified(String),
+}
+
+impl PolkitMiddleware {
+    pub fn new(authority: PolkitAuthority, subject: Subject) -> Self {
+        Self {
+            caller: PolkitCaller::Subject(authority, subject),
+        }
+    }
+
+    /// Refuse every apply tool, for front-ends whose callers have no polkit
+    /// subject
+    pub fn refusing(reason: impl Into<String>) -> Self {
+        Self {
+            caller: PolkitCaller::Unidentified(reason.into()),
+        }
+    }
+
+    async fn authorize(&self, plugin: &str) -> std::result::Result<(), AuthorizationError> {
+        match &self.caller {
+            PolkitCaller::Subject(authority, subject) => {
+                authority.authorize_apply(subject, plugin).await
+            }
+            PolkitCaller::Unidentified(reason) => {
+                Err(AuthorizationError::Unavailable(reason.clone()))
+            }
+        }
+    }
+}
+
+/// Plugin changed by a tool, for `<name>_apply` and `plugin_<name>_apply`
+fn applied_plugin(tool_name: &str) -> Option<&str> {
+    let plugin = tool_name.strip_suffix("_apply")?;
+    Some(plugin.strip_prefix("plugin_").unwrap_or(plugin)).filter(|p| !p.is_empty())
+}
+
+/// Audit record for a tool call, attributed to the client process's user
+fn tool_audit_record(tool_name: &str, params: &Value) -> AuditRecord {
+    let mut record = AuditRecord::new(Frontend::Mcp, tool_name).with_params(params);
+    if record.actor.is_none() {
+        record = record.with_actor(audit::process_actor());
+    }
+    if let Some(plugin) = applied_plugin(tool_name) {
+        record = record.with_plugin(plugin);
+    }
+    record
+}
+
+#[async_trait]
+impl ToolMiddleware for PolkitMiddleware {
+    async fn before_execute(&self, tool_name: &str, params: &Value) -> Result<()> {
+        if let Some(plugin) = applied_plugin(tool_name) {
+            if let Err(e) = self.authorize(plugin).await {
+                let record = tool_audit_record(tool_name, params);
+                match e {
+                    AuthorizationError::Denied { .. } => record.denied(e.to_string()),
+                    AuthorizationError::Unavailable(_) => record.failed(e.to_string()),
+                }
+                .record();
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    async fn after_execute(
+        &self,
+        _tool_name: &str,
+        _params: &Value,
+        _result: &Result<ToolResult>,
+    ) {
+    }
+}
+
 /// Helper macro to implement tools
 #[macro_export]
 macro_rules! impl_tool {
@@ -728,18 +949,20 @@ impl_tool!(
 );
 
 use std::future::Future;
-/// Dynamic tool builder for runtime tool creation
 use std::pin::Pin;
 
+/// Handler of a dynamic tool
+type DynamicHandler =
+    Arc<dyn Fn(Value) -> Pin<Box<dyn Future<Output = Result<ToolResult>> + Send>> + Send + Sync>;
+
+/// Dynamic tool builder for runtime tool creation
 pub struct DynamicToolBuilder {
     name: String,
     description: String,
     schema: Value,
     security_level: SecurityLevel,
     requires_auth: bool,
-    handler: Arc<
-        dyn Fn(Value) -> Pin<Box<dyn Future<Output = Result<ToolResult>> + Send>> + Send + Sync,
-    >,
+    handler: DynamicHandler,
 }
 
 impl DynamicToolBuilder {
@@ -817,9 +1040,7 @@ pub struct DynamicTool {
     description: String,
     schema: Value,
     metadata: ToolMetadata,
-    handler: Arc<
-        dyn Fn(Value) -> Pin<Box<dyn Future<Output = Result<ToolResult>> + Send>> + Send + Sync,
-    >,
+    handler: DynamicHandler,
 }
 
 #[async_trait]
@@ -985,3 +1206,188 @@ impl ToolRegistryService {
         &self.registry
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::native::polkit::tests::{serve_mock_authority, TestBus};
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_applied_plugin() {
+        assert_eq!(applied_plugin("plugin_net_apply"), Some("net"));
+        assert_eq!(applied_plugin("net_apply"), Some("net"));
+        assert_eq!(applied_plugin("privacy_router_apply"), Some("privacy_router"));
+        assert_eq!(applied_plugin("net_plan"), None);
+        assert_eq!(applied_plugin("systemd_status"), None);
+    }
+
+    #[tokio::test]
+    async fn test_progress_reported_within_call() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        report_progress("outside a call");
+
+        let reporter = ProgressReporter::new(json!("call-1"), tx);
+        with_progress(reporter, async {
+            report_progress("introspecting");
+            report_progress("done");
+        })
+        .await;
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first["method"], "notifications/progress");
+        assert_eq!(first["params"]["progressToken"], "call-1");
+        assert_eq!(first["params"]["progress"], 1);
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second["params"]["progress"], 2);
+        assert_eq!(second["params"]["message"], "done");
+        assert!(rx.try_recv().is_err());
+    }
+
+    /// Approver giving the same answer to every call
+    struct FixedApprover {
+        approve: bool,
+        asked: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Approver for FixedApprover {
+        async fn approve(&self, _metadata: &ToolMetadata, _params: &Value) -> Result<bool> {
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            Ok(self.approve)
+        }
+    }
+
+    fn denial(result: Result<ToolResult>) -> PolicyDenial {
+        result
+            .unwrap_err()
+            .downcast_ref::<PolicyDenial>()
+            .expect("call refused by policy")
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_policy_enforced_from_metadata() {
+        let registry = ToolRegistry::new();
+        let tool = DynamicToolBuilder::new("firewall_rules")
+            .security_level(SecurityLevel::Critical)
+            .requires_auth(true)
+            .handler(|_params| async { Ok(ToolResult::success(ToolContent::text("done"))) })
+            .build();
+        registry.register_tool(Box::new(tool)).await.unwrap();
+
+        let call = || registry.execute_tool("firewall_rules", json!({}));
+        assert!(matches!(
+            denial(call().await),
+            PolicyDenial::AuthenticationRequired { .. }
+        ));
+
+        let security = SecurityMiddleware::new();
+        security
+            .set_security_context(SecurityContext {
+                authenticated: true,
+                ..Default::default()
+            })
+            .await;
+        registry.add_middleware(Box::new(security)).await;
+        assert!(matches!(
+            denial(call().await),
+            PolicyDenial::ApprovalUnavailable { .. }
+        ));
+
+        let declining = Arc::new(FixedApprover {
+            approve: false,
+            asked: AtomicU64::new(0),
+        });
+        registry.set_approver(declining.clone()).await;
+        assert!(matches!(denial(call().await), PolicyDenial::NotApproved { .. }));
+        assert_eq!(declining.asked.load(Ordering::SeqCst), 1);
+
+        registry
+            .set_approver(Arc::new(FixedApprover {
+                approve: true,
+                asked: AtomicU64::new(0),
+            }))
+            .await;
+        call().await.unwrap();
+
+        // A session of its own replaces the middleware's context
+        let limited = SecurityContext {
+            authenticated: true,
+            max_level: Some(SecurityLevel::High),
+            ..Default::default()
+        };
+        let refused = with_security_context(limited, call()).await;
+        assert!(matches!(denial(refused), PolicyDenial::LevelTooHigh { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_polkit_middleware_blocks_apply() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_polkit, checks) = serve_mock_authority(
+            &bus,
+            &["org.opdbus.apply.net", "org.opdbus.apply"],
+            &["org.opdbus.apply"],
+        )
+        .await;
+
+        let applied = Arc::new(AtomicBool::new(false));
+        let registry = ToolRegistry::new();
+        for name in ["net_apply", "net_query", "remote__net_apply"] {
+            let applied = applied.clone();
+            let tool = DynamicToolBuilder::new(name)
+                .handler(move |_params| {
+                    let applied = applied.clone();
+                    async move {
+                        applied.store(true, Ordering::SeqCst);
+                        Ok(ToolResult::success(ToolContent::text("done")))
+                    }
+                })
+                .build();
+            registry.register_tool(Box::new(tool)).await.unwrap();
+        }
+
+        let subject = Subject::UnixProcess {
+            pid: 4242,
+            start_time: 1,
+            uid: 1000,
+        };
+        let authority = PolkitAuthority::new(bus.connect().await);
+        registry
+            .add_middleware(Box::new(PolkitMiddleware::new(authority, subject)))
+            .await;
+
+        let denied = registry.execute_tool("net_apply", json!({})).await;
+        assert!(denied.unwrap_err().to_string().contains("org.opdbus.apply.net"));
+        assert!(!applied.load(Ordering::SeqCst));
+
+        registry.execute_tool("net_query", json!({})).await.unwrap();
+        assert!(applied.load(Ordering::SeqCst));
+        // Not a known plugin: still checked, against the generic action
+        registry.execute_tool("remote__net_apply", json!({})).await.unwrap();
+        let checks = checks.lock().unwrap();
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[1].1, "org.opdbus.apply");
+    }
+
+    #[tokio::test]
+    async fn test_polkit_middleware_refusing() {
+        let registry = ToolRegistry::new();
+        for name in ["plugin_net_apply", "net_query"] {
+            let tool = DynamicToolBuilder::new(name)
+                .handler(|_params| async { Ok(ToolResult::success(ToolContent::text("done"))) })
+                .build();
+            registry.register_tool(Box::new(tool)).await.unwrap();
+        }
+        registry
+            .add_middleware(Box::new(PolkitMiddleware::refusing("remote caller")))
+            .await;
+
+        let refused = registry.execute_tool("plugin_net_apply", json!({})).await;
+        assert!(refused.unwrap_err().to_string().contains("remote caller"));
+        registry.execute_tool("net_query", json!({})).await.unwrap();
+    }
+}
@@ -4,7 +4,6 @@
 use crate::mcp::tool_registry::{DynamicToolBuilder, ToolContent, ToolResult};
 use anyhow::Result;
 use serde_json::{json, Value};
-use std::collections::HashMap;
 use zbus::Connection;
 
 /// Register all agent tools with the tool registry
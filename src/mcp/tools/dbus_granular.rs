@@ -3,12 +3,12 @@
 //! All introspection is converted to JSON immediately via SQLite cache
 
 use crate::mcp::introspection_cache::IntrospectionCache;
-use crate::mcp::tool_registry::{DynamicToolBuilder, ToolContent, ToolResult};
+use crate::mcp::tool_registry::{report_progress, DynamicToolBuilder, ToolContent, ToolResult};
+use crate::native::dbus_marshal;
 use anyhow::{Context, Result};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
 use zbus::{Connection, Proxy};
 
 /// Get introspection cache path
@@ -198,7 +198,7 @@ async fn register_introspect_service_tool(
                     
                     match proxy_result {
                         Ok(proxy) => {
-                            match proxy.call::<_, String>("Introspect", &()).await {
+                            match proxy.call::<_, _, String>("Introspect", &()).await {
                                 Ok(xml) => {
                                     // Store in cache (converts XML → JSON automatically)
                                     cache.store_introspection(service, path, &xml)?;
@@ -313,15 +313,18 @@ async fn register_list_objects_tool(
                     if let Ok(proxy) = Proxy::new(
                         &connection,
                         service,
-                        &om_path,
+                        om_path.as_str(),
                         "org.freedesktop.DBus.ObjectManager",
                     ).await {
-                        if let Ok(managed) = proxy.call::<_, HashMap<String, HashMap<String, Value>>>(
+                        if let Ok(managed) = proxy.call::<_, _, HashMap<
+                            zbus::zvariant::OwnedObjectPath,
+                            HashMap<String, HashMap<String, zbus::zvariant::OwnedValue>>,
+                        >>(
                             "GetManagedObjects",
                             &(),
                         ).await {
                             // Extract all object paths from ObjectManager
-                            objects.extend(managed.keys().cloned());
+                            objects.extend(managed.keys().map(|path| path.to_string()));
                             break; // Success, no need to try other paths
                         }
                     }
@@ -424,7 +427,7 @@ async fn register_introspect_object_tool(
                     
                     match proxy_result {
                         Ok(proxy) => {
-                            match proxy.call::<_, String>("Introspect", &()).await {
+                            match proxy.call::<_, _, String>("Introspect", &()).await {
                                 Ok(xml) => {
                                     cache.store_introspection(service, path, &xml)?;
                                     
@@ -599,7 +602,7 @@ async fn register_list_methods_tool(
                 let interface = params["interface"].as_str().unwrap();
                 let bus_type = params["bus"].as_str().unwrap_or("system");
                 
-                let connection = if bus_type == "system" {
+                let _connection = if bus_type == "system" {
                     Connection::system().await?
                 } else {
                     Connection::session().await?
@@ -800,7 +803,11 @@ async fn register_call_method_tool(
                 "method": {"type": "string"},
                 "args": {
                     "type": "array",
-                    "description": "Method arguments (as JSON values)"
+                    "description": "Method arguments (as JSON values), coerced to the introspected signature"
+                },
+                "signature": {
+                    "type": "string",
+                    "description": "Input signature such as \"sa{sv}\"; read from introspection if omitted"
                 },
                 "bus": {
                     "type": "string",
@@ -837,14 +844,32 @@ async fn register_call_method_tool(
                     .cloned()
                     .unwrap_or_default();
                 
-                // Convert JSON values to zbus::Value
-                // This is simplified - in production would need proper type conversion
-                let zbus_args: Vec<zbus::zvariant::Value> = args.iter()
-                    .map(|v| zbus::zvariant::to_value(v).unwrap())
-                    .collect();
+                // Coerce JSON values to the method's D-Bus types
+                let signatures = match params["signature"].as_str() {
+                    Some(signature) => dbus_marshal::split_signature(signature)?,
+                    None => {
+                        let cache = IntrospectionCache::new(get_cache_path())?;
+                        ensure_cached(&cache, &connection, service, path, interface).await?;
+                        let (in_args, _) = cache
+                            .get_method_signature(service, interface, method)?
+                            .ok_or_else(|| anyhow::anyhow!(
+                                "Method {}.{} not found in the introspection of {}; pass `signature` to call it anyway",
+                                interface, method, path
+                            ))?;
+                        in_args
+                            .iter()
+                            .map(|ty| dbus_marshal::parse_signature(ty))
+                            .collect::<Result<Vec<_>>>()?
+                    }
+                };
+                let body = dbus_marshal::json_to_body(&args, &signatures)
+                    .with_context(|| format!("Invalid arguments for {}.{}", interface, method))?;
                 
-                let result: zbus::zvariant::Value = proxy.call(method, &zbus_args).await?;
-                let result_json: Value = zbus::zvariant::from_value(result)?;
+                let reply = match body {
+                    Some(body) => proxy.call_method(method, &body).await?,
+                    None => proxy.call_method(method, &()).await?,
+                };
+                let result_json = dbus_marshal::body_to_json(&reply.body())?;
                 
                 Ok(ToolResult {
                     content: vec![ToolContent::json(json!({
@@ -899,7 +924,7 @@ async fn register_get_property_tool(
                     Connection::session().await?
                 };
                 
-                let proxy = Proxy::new(
+                let _proxy = Proxy::new(
                     &connection,
                     service,
                     path,
@@ -913,11 +938,11 @@ async fn register_get_property_tool(
                     .build()
                     .await?;
                 
-                let value: zbus::zvariant::Value = properties_proxy
-                    .get(interface, property)
+                let value = properties_proxy
+                    .get(interface.try_into()?, property)
                     .await?;
                 
-                let value_json: Value = zbus::zvariant::from_value(value)?;
+                let value_json = dbus_marshal::value_to_json(&value)?;
                 
                 Ok(ToolResult {
                     content: vec![ToolContent::json(json!({
@@ -982,10 +1007,23 @@ async fn register_set_property_tool(
                     .build()
                     .await?;
                 
-                let zbus_value: zbus::zvariant::Value = zbus::zvariant::to_value(value)?;
+                // Coerce the value to the property's introspected type
+                let cache = IntrospectionCache::new(get_cache_path())?;
+                ensure_cached(&cache, &connection, service, path, interface).await?;
+                let signature = cache
+                    .get_property_signature(service, interface, property)?
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Property {}.{} not found in the introspection of {}",
+                        interface, property, path
+                    ))?;
+                let zbus_value = dbus_marshal::json_to_value(
+                    value,
+                    &dbus_marshal::parse_signature(&signature)?,
+                )
+                .with_context(|| format!("Invalid value for {}.{}", interface, property))?;
                 
                 properties_proxy
-                    .set(interface, property, zbus_value)
+                    .set(interface.try_into()?, property, zbus_value)
                     .await?;
                 
                 Ok(ToolResult {
@@ -1084,15 +1122,14 @@ async fn register_get_all_properties_tool(
                         }
                         
                         // Get all properties for this interface
-                        let props: HashMap<String, zbus::zvariant::Value> = properties_proxy
-                            .get_all(iface_name)
+                        let props = properties_proxy
+                            .get_all(iface_name.try_into()?)
                             .await
                             .unwrap_or_default();
                         
                         let mut iface_props = json!({});
                         for (prop_name, prop_value) in props {
-                            let value_json: Value = zbus::zvariant::from_value(prop_value)?;
-                            iface_props[prop_name] = value_json;
+                            iface_props[prop_name] = dbus_marshal::value_to_json(&prop_value)?;
                         }
                         
                         all_properties[iface_name] = iface_props;
@@ -1115,6 +1152,27 @@ async fn register_get_all_properties_tool(
     Ok(())
 }
 
+/// Helper: Introspect an object into the cache unless `interface` is cached
+async fn ensure_cached(
+    cache: &IntrospectionCache,
+    connection: &Connection,
+    service: &str,
+    path: &str,
+    interface: &str,
+) -> Result<()> {
+    if cache.get_introspection_json(service, path, Some(interface))?.is_none() {
+        let proxy = Proxy::new(
+            connection,
+            service,
+            path,
+            "org.freedesktop.DBus.Introspectable",
+        ).await?;
+        let xml: String = proxy.call("Introspect", &()).await?;
+        cache.store_introspection(service, path, &xml)?;
+    }
+    Ok(())
+}
+
 /// Helper: Recursively introspect object paths with error handling
 /// Returns (successful objects, non-introspectable objects with errors)
 async fn recursive_introspect_with_errors(
@@ -1132,18 +1190,19 @@ async fn recursive_introspect_with_errors(
             continue;
         }
         processed.insert(current_path.clone());
+        report_progress(&format!("Introspecting {}", current_path));
         
         // Try to introspect this object
         let proxy_result = Proxy::new(
             connection,
             service,
-            &current_path,
+            current_path.as_str(),
             "org.freedesktop.DBus.Introspectable",
         ).await;
         
         match proxy_result {
             Ok(proxy) => {
-                match proxy.call::<_, String>("Introspect", &()).await {
+                match proxy.call::<_, _, String>("Introspect", &()).await {
                     Ok(xml) => {
                         // Parse XML to find child nodes using zbus_xml
                         match zbus_xml::Node::from_reader(xml.as_bytes()) {
@@ -26,6 +26,12 @@ pub struct ToolParameter {
     pub required: bool,
 }
 
+impl Default for DiscoverSystemTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DiscoverSystemTool {
     pub fn new() -> Self {
         Self {
@@ -87,6 +93,12 @@ pub struct AnalyzeCpuFeaturesTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for AnalyzeCpuFeaturesTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AnalyzeCpuFeaturesTool {
     pub fn new() -> Self {
         Self {
@@ -113,6 +125,12 @@ pub struct AnalyzeIspTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for AnalyzeIspTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AnalyzeIspTool {
     pub fn new() -> Self {
         Self {
@@ -172,6 +190,12 @@ pub struct GenerateIspRequestTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for GenerateIspRequestTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GenerateIspRequestTool {
     pub fn new() -> Self {
         Self {
@@ -234,6 +258,12 @@ pub struct CompareHardwareTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for CompareHardwareTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CompareHardwareTool {
     pub fn new() -> Self {
         Self {
@@ -339,6 +369,12 @@ pub struct QueryCachedDbusMethodsTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for QueryCachedDbusMethodsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QueryCachedDbusMethodsTool {
     pub fn new() -> Self {
         Self {
@@ -402,6 +438,12 @@ pub struct SearchDbusMethodsTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for SearchDbusMethodsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SearchDbusMethodsTool {
     pub fn new() -> Self {
         Self {
@@ -442,6 +484,12 @@ pub struct GetCacheStatsTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for GetCacheStatsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GetCacheStatsTool {
     pub fn new() -> Self {
         Self {
@@ -472,6 +520,12 @@ pub struct WarmCacheTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for WarmCacheTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WarmCacheTool {
     pub fn new() -> Self {
         Self {
@@ -541,6 +595,12 @@ pub struct ListDbusServicesTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for ListDbusServicesTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ListDbusServicesTool {
     pub fn new() -> Self {
         Self {
@@ -606,6 +666,12 @@ pub struct ListDbusObjectPathsTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for ListDbusObjectPathsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ListDbusObjectPathsTool {
     pub fn new() -> Self {
         Self {
@@ -663,6 +729,12 @@ pub struct IntrospectDbusObjectTool {
     pub parameters: Vec<ToolParameter>,
 }
 
+impl Default for IntrospectDbusObjectTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IntrospectDbusObjectTool {
     pub fn new() -> Self {
         Self {
@@ -42,7 +42,7 @@ class MCPControlCenter {
             const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
             const wsUrl = `${protocol}//${window.location.host}/ws`;
 
-            this.ws = new WebSocket(wsUrl);
+            this.ws = new WebSocket(opdbusAuth.url(wsUrl));
 
             this.ws.onopen = () => {
                 console.log('WebSocket connected - real-time updates enabled');
@@ -197,7 +197,7 @@ class MCPControlCenter {
 
     async loadStatus() {
         try {
-            const response = await fetch('/api/status');
+            const response = await opdbusAuth.fetch('/api/status');
             const data = await response.json();
             if (data.success) {
                 this.updateStats(data.data);
@@ -209,7 +209,7 @@ class MCPControlCenter {
 
     async loadTools() {
         try {
-            const response = await fetch('/api/tools');
+            const response = await opdbusAuth.fetch('/api/tools');
             const data = await response.json();
             if (data.success) {
                 this.tools = data.data.tools || [];
@@ -224,7 +224,7 @@ class MCPControlCenter {
 
     async loadAgents() {
         try {
-            const response = await fetch('/api/agents');
+            const response = await opdbusAuth.fetch('/api/agents');
             const data = await response.json();
             if (data.success) {
                 this.agents = data.data || [];
@@ -240,7 +240,7 @@ class MCPControlCenter {
     async loadServices() {
         try {
             console.log('📡 Loading services from /api/discovery/services');
-            const response = await fetch('/api/discovery/services');
+            const response = await opdbusAuth.fetch('/api/discovery/services');
             console.log('📥 Services response received:', response.status);
             const data = await response.json();
             console.log('📄 Services data:', data);
@@ -261,7 +261,7 @@ class MCPControlCenter {
 
     async loadLogs() {
         try {
-            const response = await fetch('/api/logs');
+            const response = await opdbusAuth.fetch('/api/logs');
             const data = await response.json();
             if (data.success) {
                 this.logs = data.data || [];
@@ -566,7 +566,7 @@ class MCPControlCenter {
         resultEl.textContent = 'Executing...';
 
         try {
-            const response = await fetch(`/api/tools/${this.currentTool.name}`, {
+            const response = await opdbusAuth.fetch(`/api/tools/${this.currentTool.name}`, {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify(params)
@@ -609,7 +609,7 @@ class MCPControlCenter {
         }
 
         try {
-            const response = await fetch('/api/agents', {
+            const response = await opdbusAuth.fetch('/api/agents', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ type: agentType, config })
@@ -633,7 +633,7 @@ class MCPControlCenter {
         if (!confirm('Are you sure you want to kill this agent?')) return;
 
         try {
-            const response = await fetch(`/api/agents/${agentId}`, {
+            const response = await opdbusAuth.fetch(`/api/agents/${agentId}`, {
                 method: 'DELETE'
             });
             
@@ -676,7 +676,7 @@ class MCPControlCenter {
 
         try {
             console.log('📡 Making API call to /api/discovery/run');
-            const response = await fetch('/api/discovery/run', {
+            const response = await opdbusAuth.fetch('/api/discovery/run', {
                 method: 'POST'
             });
 
@@ -973,7 +973,7 @@ class MCPControlCenter {
 
         try {
             // Send to AI chat server
-            const response = await fetch('/api/chat', {
+            const response = await opdbusAuth.fetch('/api/chat', {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
@@ -1075,7 +1075,7 @@ class MCPClient {
         };
 
         try {
-            const response = await fetch('/api/mcp/remote/configure', {
+            const response = await opdbusAuth.fetch('/api/mcp/remote/configure', {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json'
@@ -1112,7 +1112,7 @@ class MCPClient {
         }
 
         // All requests go to Rust backend - Rust handles everything
-        const response = await fetch('/api/mcp', {
+        const response = await opdbusAuth.fetch('/api/mcp', {
             method: 'POST',
             headers: headers,
             body: JSON.stringify({
@@ -1192,7 +1192,7 @@ async function loadMCPConfig() {
     
     // Load configurations from Rust backend (Rust is the source of truth)
     try {
-        const response = await fetch('/api/mcp/remote/configs');
+        const response = await opdbusAuth.fetch('/api/mcp/remote/configs');
         const data = await response.json();
         
         if (data.success && data.data && data.data.length > 0) {
@@ -1764,7 +1764,7 @@ function renderWorkflow() {
 async function loadProvidersAndModels() {
     try {
         // First, get current provider and models
-        const response = await fetch('/api/models');
+        const response = await opdbusAuth.fetch('/api/models');
         if (response.ok) {
             const data = await response.json();
             let currentProvider = data.provider;
@@ -1775,13 +1775,13 @@ async function loadProvidersAndModels() {
                 currentProvider = 'huggingface';
                 // Switch to default provider if needed
                 try {
-                    await fetch('/api/provider/select', {
+                    await opdbusAuth.fetch('/api/provider/select', {
                         method: 'POST',
                         headers: { 'Content-Type': 'application/json' },
                         body: JSON.stringify({ provider: currentProvider })
                     });
                     // Reload models after switching provider
-                    const modelsResponse = await fetch('/api/models');
+                    const modelsResponse = await opdbusAuth.fetch('/api/models');
                     if (modelsResponse.ok) {
                         const modelsData = await modelsResponse.json();
                         populateModelDropdown(modelsData);
@@ -1796,7 +1796,7 @@ async function loadProvidersAndModels() {
             
             // Then fetch and populate models for current provider dynamically
             if (currentProvider) {
-                const modelsResponse = await fetch(`/api/models/${currentProvider}`);
+                const modelsResponse = await opdbusAuth.fetch(`/api/models/${currentProvider}`);
                 if (modelsResponse.ok) {
                     const modelsData = await modelsResponse.json();
                     populateModelDropdownFromProvider(modelsData);
@@ -1847,7 +1847,7 @@ async function handleProviderChange(provider) {
 
     try {
         // Switch provider
-        const response = await fetch('/api/provider/select', {
+        const response = await opdbusAuth.fetch('/api/provider/select', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ provider })
@@ -1857,7 +1857,7 @@ async function handleProviderChange(provider) {
             console.log(`Switched to provider: ${provider}`);
 
             // Fetch models dynamically for the selected provider
-            const modelsResponse = await fetch(`/api/models/${provider}`);
+            const modelsResponse = await opdbusAuth.fetch(`/api/models/${provider}`);
             if (modelsResponse.ok) {
                 const modelsData = await modelsResponse.json();
                 populateModelDropdownFromProvider(modelsData);
@@ -1894,7 +1894,7 @@ async function handleProviderChange(provider) {
 
 async function loadModels() {
     try {
-        const response = await fetch('/api/models');
+        const response = await opdbusAuth.fetch('/api/models');
         if (response.ok) {
             const data = await response.json();
             populateModelDropdown(data);
@@ -1969,7 +1969,7 @@ function populateModelDropdownFromProvider(data) {
 
 async function handleModelChange(modelId) {
     try {
-        const response = await fetch('/api/models/select', {
+        const response = await opdbusAuth.fetch('/api/models/select', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ model: modelId })
@@ -2012,7 +2012,7 @@ if (document.readyState === 'loading') {
 
 async function handleModelChange(modelId) {
     try {
-        const response = await fetch('/api/models/select', {
+        const response = await opdbusAuth.fetch('/api/models/select', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ model: modelId })
@@ -0,0 +1,58 @@
+// API token for the op-dbus HTTP servers
+//
+// Over TCP, requests under /api, /mcp and /ws need a token unless the server
+// runs with OPDBUS_HTTP_AUTH=off. The token (from `op-dbus token create`) is
+// kept in localStorage and sent as a bearer token, or as the access_token
+// query parameter of WebSocket URLs, where headers cannot be set. Servers
+// accept the query parameter only on WebSocket upgrades. A 401 asks for a
+// token and retries once.
+
+const opdbusAuth = {
+    storageKey: 'opdbus_token',
+
+    token() {
+        return localStorage.getItem(this.storageKey);
+    },
+
+    setToken(token) {
+        if (token) {
+            localStorage.setItem(this.storageKey, token.trim());
+        } else {
+            localStorage.removeItem(this.storageKey);
+        }
+    },
+
+    // Ask for a token; false when the prompt was dismissed
+    promptToken() {
+        const token = window.prompt('API token (create one with `op-dbus token create`):', '');
+        if (!token) return false;
+        this.setToken(token);
+        return true;
+    },
+
+    // WebSocket `url` with the token as access_token
+    url(url) {
+        const token = this.token();
+        if (!token) return url;
+        const withToken = new URL(url, window.location.href);
+        withToken.searchParams.set('access_token', token);
+        return withToken.toString();
+    },
+
+    // fetch() sending the token, asking for one on 401
+    async fetch(url, init = {}) {
+        const send = () => {
+            const headers = new Headers(init.headers);
+            const token = this.token();
+            if (token) headers.set('Authorization', `Bearer ${token}`);
+            return fetch(url, { ...init, headers });
+        };
+        const response = await send();
+        if (response.status === 401 && this.promptToken()) {
+            return send();
+        }
+        return response;
+    },
+};
+
+window.opdbusAuth = opdbusAuth;
@@ -48,7 +48,7 @@ class MCPChat {
         const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
         const wsUrl = `${protocol}//${window.location.host}/ws`;
         
-        this.ws = new WebSocket(wsUrl);
+        this.ws = new WebSocket(opdbusAuth.url(wsUrl));
         
         this.ws.onopen = () => {
             this.setConnectionStatus('connected');
@@ -158,7 +158,7 @@ class MCPChat {
     
     async fetchSuggestions(partial) {
         try {
-            const response = await fetch('/chat/api/suggestions', {
+            const response = await opdbusAuth.fetch('/chat/api/suggestions', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ partial })
@@ -550,7 +550,7 @@ class MCPChat {
     
     async loadModels() {
         try {
-            const response = await fetch('/api/models');
+            const response = await opdbusAuth.fetch('/api/models');
             if (response.ok) {
                 const data = await response.json();
                 this.populateProviderDropdown(data.provider);
@@ -610,7 +610,7 @@ class MCPChat {
 
     async handleProviderChange(provider) {
         try {
-            const response = await fetch('/api/provider/select', {
+            const response = await opdbusAuth.fetch('/api/provider/select', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ provider })
@@ -646,7 +646,7 @@ class MCPChat {
 
     async handleModelChange(modelId) {
         try {
-            const response = await fetch('/api/models/select', {
+            const response = await opdbusAuth.fetch('/api/models/select', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ modelId })
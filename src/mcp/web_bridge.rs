@@ -603,22 +603,20 @@ async fn ws_mcp_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
 
 async fn handle_mcp_socket(mut socket: WebSocket) {
     while let Some(msg) = socket.recv().await {
-        if let Ok(msg) = msg {
-            if let axum::extract::ws::Message::Text(text) = msg {
-                // Forward to MCP server via stdio
-                // For now, echo back
-                let response = serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "result": {"status": "ok"}
-                });
-
-                let _ = socket
-                    .send(axum::extract::ws::Message::Text(
-                        serde_json::to_string(&response).unwrap(),
-                    ))
-                    .await;
-            }
+        if let Ok(axum::extract::ws::Message::Text(_text)) = msg {
+            // Forward to MCP server via stdio
+            // For now, echo back
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {"status": "ok"}
+            });
+
+            let _ = socket
+                .send(axum::extract::ws::Message::Text(
+                    serde_json::to_string(&response).unwrap(),
+                ))
+                .await;
         }
     }
 }
@@ -1,17 +1,15 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, Query, State,
+        Path, State,
     },
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{Html, IntoResponse},
     routing::{delete, get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
     net::SocketAddr,
     path::PathBuf,
     sync::Arc,
@@ -23,10 +21,10 @@ use tokio::{
 };
 use tower_http::{
     cors::{Any, CorsLayer},
-    services::{ServeDir, ServeFile},
+    services::ServeDir,
     trace::TraceLayer,
 };
-use tracing::{error, info, warn};
+use tracing::{info, warn};
 use zbus::Connection;
 
 // Orchestrator proxy will be created manually
@@ -295,7 +293,7 @@ async fn api_list_agents(State(state): State<AppState>) -> impl IntoResponse {
                     if let Ok(status_json) = result {
                         if let Ok(status) = serde_json::from_str::<Value>(&status_json) {
                             agents.push(AgentInfo {
-                                id: id,
+                                id,
                                 agent_type: status["type"]
                                     .as_str()
                                     .unwrap_or("unknown")
@@ -536,7 +534,7 @@ async fn monitor_agents_task(state: AppState) {
                     if let Ok(status_json) = result {
                         if let Ok(status) = serde_json::from_str::<Value>(&status_json) {
                             agents.push(AgentInfo {
-                                id: id,
+                                id,
                                 agent_type: status["type"]
                                     .as_str()
                                     .unwrap_or("unknown")
@@ -9,8 +9,10 @@ use std::sync::Arc;
 
 /// Workflow states for MCP operations
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Default)]
 pub enum McpWorkflowState {
     /// Initial state
+    #[default]
     Start,
     /// Code analysis completed
     CodeAnalyzed,
@@ -28,11 +30,6 @@ pub enum McpWorkflowState {
     AwaitingInput,
 }
 
-impl Default for McpWorkflowState {
-    fn default() -> Self {
-        McpWorkflowState::Start
-    }
-}
 
 impl ProcessState for McpWorkflowState {
     fn is_default(&self) -> bool {
@@ -327,6 +324,12 @@ pub struct McpWorkflowManager {
     flows: std::collections::HashMap<String, Flow<McpWorkflowState>>,
 }
 
+impl Default for McpWorkflowManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl McpWorkflowManager {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,436 @@
+//! JSON ↔ D-Bus value marshalling driven by type signatures
+//!
+//! `json_to_value` coerces JSON into exactly the type of an introspected
+//! signature, so methods taking `u`, `o`, `a{sv}` or `(ss)` can be called
+//! from JSON:
+//! - integers accept JSON numbers or numeric strings, range-checked
+//! - `o` and `g` must be valid object paths and signatures
+//! - `ay` accepts a string (its UTF-8 bytes) or an array of numbers
+//! - dicts take JSON objects, arrays and structs take JSON arrays
+//! - `v` infers the type from the JSON (see `infer_value`) unless it is given
+//!   explicitly as `{"signature": "u", "value": 5}`
+//!
+//! Replies convert back with `value_to_json`; structs become arrays.
+
+#![allow(dead_code)] // Method calls are marshalled by the MCP D-Bus tools
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Value as Json};
+use std::os::fd::AsRawFd;
+use zbus::message::Body;
+use zbus::zvariant::{Array, Dict, ObjectPath, Signature, Structure, StructureBuilder, Value};
+
+/// Parse a single complete type such as `"a{sv}"` or `"(ss)"`
+pub fn parse_signature(signature: &str) -> Result<Signature> {
+    signature
+        .parse()
+        .map_err(|e| anyhow!("Invalid D-Bus signature '{}': {}", signature, e))
+}
+
+/// Split an argument list signature such as `"sa{sv}"` into one type per argument
+pub fn split_signature(signature: &str) -> Result<Vec<Signature>> {
+    let parsed = parse_signature(signature)?;
+    Ok(match parsed {
+        Signature::Unit => Vec::new(),
+        // "ss" parses as a structure too; only "(ss)" is a single argument
+        Signature::Structure(ref fields) if parsed.to_string() != signature => {
+            fields.iter().cloned().collect()
+        }
+        single => vec![single],
+    })
+}
+
+/// Coerce `json` into a D-Bus value of type `signature`
+pub fn json_to_value(json: &Json, signature: &Signature) -> Result<Value<'static>> {
+    coerce(json, signature, "value")
+}
+
+/// Message body for a method call with one JSON value per argument type
+///
+/// `None` for methods without arguments.
+pub fn json_to_body(args: &[Json], signatures: &[Signature]) -> Result<Option<Structure<'static>>> {
+    if args.len() != signatures.len() {
+        bail!(
+            "Expected {} argument(s) of type '{}', got {}",
+            signatures.len(),
+            signatures.iter().map(|s| s.to_string()).collect::<String>(),
+            args.len()
+        );
+    }
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    let mut body = StructureBuilder::new();
+    for (i, (arg, signature)) in args.iter().zip(signatures).enumerate() {
+        body = body.append_field(coerce(arg, signature, &format!("args[{}]", i))?);
+    }
+    Ok(Some(body.build()?))
+}
+
+/// JSON for a message body: null if empty, the value of a single argument,
+/// or an array with one entry per argument
+pub fn body_to_json(body: &Body) -> Result<Json> {
+    if matches!(body.signature(), Signature::Unit) {
+        return Ok(Json::Null);
+    }
+    let fields = body
+        .deserialize::<Structure>()
+        .context("Failed to decode message body")?
+        .into_fields();
+    if fields.len() == 1 {
+        value_to_json(&fields[0])
+    } else {
+        fields.iter().map(value_to_json).collect()
+    }
+}
+
+/// D-Bus value for JSON without a known signature
+///
+/// Objects become `a{sv}` and arrays `av`. D-Bus has no null, so JSON null
+/// is sent as an empty string.
+pub fn infer_value(json: &Json) -> Result<Value<'static>> {
+    if let Some(typed) = explicit_variant(json, "value")? {
+        return Ok(typed);
+    }
+    Ok(match json {
+        Json::Null => Value::from(""),
+        Json::Bool(b) => Value::from(*b),
+        Json::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                Value::from(n.as_f64().unwrap_or_default())
+            }
+        }
+        Json::String(s) => Value::from(s.clone()),
+        Json::Array(items) => {
+            let mut array = Array::new(&Signature::Variant);
+            for item in items {
+                array.append(Value::Value(Box::new(infer_value(item)?)))?;
+            }
+            Value::Array(array)
+        }
+        Json::Object(map) => {
+            let mut dict = Dict::new(&Signature::Str, &Signature::Variant);
+            for (key, value) in map {
+                dict.append(
+                    Value::from(key.clone()),
+                    Value::Value(Box::new(infer_value(value)?)),
+                )?;
+            }
+            Value::Dict(dict)
+        }
+    })
+}
+
+/// Convert a D-Bus value to JSON
+pub fn value_to_json(value: &Value) -> Result<Json> {
+    Ok(match value {
+        Value::U8(n) => Json::from(*n),
+        Value::Bool(b) => Json::Bool(*b),
+        Value::I16(n) => Json::from(*n),
+        Value::U16(n) => Json::from(*n),
+        Value::I32(n) => Json::from(*n),
+        Value::U32(n) => Json::from(*n),
+        Value::I64(n) => Json::from(*n),
+        Value::U64(n) => Json::from(*n),
+        Value::F64(f) => serde_json::Number::from_f64(*f)
+            .map(Json::Number)
+            .unwrap_or(Json::Null),
+        Value::Str(s) => Json::String(s.to_string()),
+        Value::Signature(s) => Json::String(s.to_string()),
+        Value::ObjectPath(p) => Json::String(p.to_string()),
+        Value::Value(inner) => value_to_json(inner)?,
+        Value::Array(items) => items.iter().map(value_to_json).collect::<Result<_>>()?,
+        Value::Dict(dict) => {
+            let mut map = Map::new();
+            for (key, value) in dict.iter() {
+                let key = match value_to_json(key)? {
+                    Json::String(s) => s,
+                    other => other.to_string(),
+                };
+                map.insert(key, value_to_json(value)?);
+            }
+            Json::Object(map)
+        }
+        Value::Structure(fields) => fields
+            .fields()
+            .iter()
+            .map(value_to_json)
+            .collect::<Result<_>>()?,
+        Value::Fd(fd) => Json::from(fd.as_raw_fd()),
+    })
+}
+
+fn coerce(json: &Json, signature: &Signature, at: &str) -> Result<Value<'static>> {
+    Ok(match signature {
+        Signature::U8 => Value::U8(integer(json, signature, at)?),
+        Signature::I16 => Value::I16(integer(json, signature, at)?),
+        Signature::U16 => Value::U16(integer(json, signature, at)?),
+        Signature::I32 => Value::I32(integer(json, signature, at)?),
+        Signature::U32 => Value::U32(integer(json, signature, at)?),
+        Signature::I64 => Value::I64(integer(json, signature, at)?),
+        Signature::U64 => Value::U64(integer(json, signature, at)?),
+        Signature::F64 => {
+            let number = match json {
+                Json::Number(n) => n.as_f64(),
+                Json::String(s) => s.trim().parse().ok(),
+                _ => None,
+            };
+            Value::F64(number.ok_or_else(|| mismatch(json, signature, at))?)
+        }
+        Signature::Bool => match json {
+            Json::Bool(b) => Value::Bool(*b),
+            Json::String(s) if s == "true" || s == "false" => Value::Bool(s == "true"),
+            _ => return Err(mismatch(json, signature, at)),
+        },
+        Signature::Str => match json {
+            Json::String(s) => Value::from(s.clone()),
+            _ => return Err(mismatch(json, signature, at)),
+        },
+        Signature::ObjectPath => match json {
+            Json::String(s) => Value::ObjectPath(
+                ObjectPath::try_from(s.clone())
+                    .map_err(|e| anyhow!("{}: invalid object path '{}': {}", at, s, e))?,
+            ),
+            _ => return Err(mismatch(json, signature, at)),
+        },
+        Signature::Signature => match json {
+            Json::String(s) => Value::Signature(
+                parse_signature(s).with_context(|| format!("{}: invalid signature", at))?,
+            ),
+            _ => return Err(mismatch(json, signature, at)),
+        },
+        Signature::Variant => Value::Value(Box::new(match explicit_variant(json, at)? {
+            Some(typed) => typed,
+            None => infer_value(json).with_context(|| format!("{}: invalid variant", at))?,
+        })),
+        Signature::Array(child) => {
+            let element = child.signature();
+            let mut array = Array::new(element);
+            match (json, element) {
+                (Json::String(s), Signature::U8) => {
+                    for byte in s.bytes() {
+                        array.append(Value::U8(byte))?;
+                    }
+                }
+                (Json::Array(items), _) => {
+                    for (i, item) in items.iter().enumerate() {
+                        array.append(coerce(item, element, &format!("{}[{}]", at, i))?)?;
+                    }
+                }
+                _ => return Err(mismatch(json, signature, at)),
+            }
+            Value::Array(array)
+        }
+        Signature::Dict { key, value } => {
+            let Json::Object(map) = json else {
+                return Err(mismatch(json, signature, at));
+            };
+            let mut dict = Dict::new(key.signature(), value.signature());
+            for (name, item) in map {
+                let at = format!("{}.{}", at, name);
+                dict.append(
+                    coerce(&Json::String(name.clone()), key.signature(), &at)?,
+                    coerce(item, value.signature(), &at)?,
+                )?;
+            }
+            Value::Dict(dict)
+        }
+        Signature::Structure(fields) => {
+            let Json::Array(items) = json else {
+                return Err(mismatch(json, signature, at));
+            };
+            if items.len() != fields.len() {
+                bail!(
+                    "{}: expected {} with {} fields, got {} values",
+                    at,
+                    signature,
+                    fields.len(),
+                    items.len()
+                );
+            }
+            let mut structure = StructureBuilder::new();
+            for (i, (item, field)) in items.iter().zip(fields.iter()).enumerate() {
+                structure = structure.append_field(coerce(item, field, &format!("{}[{}]", at, i))?);
+            }
+            Value::Structure(structure.build()?)
+        }
+        Signature::Fd => bail!("{}: file descriptors cannot be passed as JSON", at),
+        Signature::Unit => bail!("{}: no value expected", at),
+    })
+}
+
+/// `{"signature": ..., "value": ...}`, the explicit form of a variant
+fn explicit_variant(json: &Json, at: &str) -> Result<Option<Value<'static>>> {
+    let Json::Object(map) = json else {
+        return Ok(None);
+    };
+    match (map.len(), map.get("signature"), map.get("value")) {
+        (2, Some(Json::String(signature)), Some(value)) => {
+            let signature =
+                parse_signature(signature).with_context(|| format!("{}: invalid variant", at))?;
+            coerce(value, &signature, at).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn integer<T: TryFrom<i128>>(json: &Json, signature: &Signature, at: &str) -> Result<T> {
+    let number: Option<i128> = match json {
+        Json::Number(n) => n
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| n.as_u64().map(i128::from)),
+        Json::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    let number = number.ok_or_else(|| mismatch(json, signature, at))?;
+    T::try_from(number).map_err(|_| {
+        anyhow!(
+            "{}: {} is out of range for {} ('{}')",
+            at,
+            number,
+            type_name(signature),
+            signature
+        )
+    })
+}
+
+fn mismatch(json: &Json, signature: &Signature, at: &str) -> anyhow::Error {
+    let mut found = json.to_string();
+    if found.len() > 40 {
+        found = format!("{}...", found.chars().take(40).collect::<String>());
+    }
+    anyhow!(
+        "{}: expected {} ('{}'), got {} {}",
+        at,
+        type_name(signature),
+        signature,
+        json_type(json),
+        found
+    )
+}
+
+fn type_name(signature: &Signature) -> &'static str {
+    match signature {
+        Signature::Unit => "nothing",
+        Signature::U8 => "byte",
+        Signature::Bool => "boolean",
+        Signature::I16 => "int16",
+        Signature::U16 => "uint16",
+        Signature::I32 => "int32",
+        Signature::U32 => "uint32",
+        Signature::I64 => "int64",
+        Signature::U64 => "uint64",
+        Signature::F64 => "double",
+        Signature::Str => "string",
+        Signature::Signature => "signature",
+        Signature::ObjectPath => "object path",
+        Signature::Variant => "variant",
+        Signature::Fd => "file descriptor",
+        Signature::Array(_) => "array",
+        Signature::Dict { .. } => "dict",
+        Signature::Structure(_) => "struct",
+    }
+}
+
+fn json_type(json: &Json) -> &'static str {
+    match json {
+        Json::Null => "null",
+        Json::Bool(_) => "boolean",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn typed(json: Json, signature: &str) -> Result<Value<'static>> {
+        json_to_value(&json, &parse_signature(signature)?)
+    }
+
+    #[test]
+    fn test_coerce_to_signature() {
+        let value = typed(json!("42"), "u").unwrap();
+        assert_eq!(value, Value::U32(42));
+
+        let value = typed(json!("/org/freedesktop/systemd1"), "o").unwrap();
+        assert_eq!(value.value_signature().to_string(), "o");
+
+        let value = typed(json!({"Description": "web", "CPUWeight": 100}), "a{sv}").unwrap();
+        assert_eq!(value.value_signature().to_string(), "a{sv}");
+        assert_eq!(
+            value_to_json(&value).unwrap(),
+            json!({"Description": "web", "CPUWeight": 100})
+        );
+
+        let value = typed(
+            json!([["CPUWeight", {"signature": "t", "value": 100}]]),
+            "a(sv)",
+        )
+        .unwrap();
+        let Value::Array(properties) = &value else {
+            panic!("expected an array");
+        };
+        let Value::Structure(property) = &properties.inner()[0] else {
+            panic!("expected a struct");
+        };
+        assert_eq!(
+            property.fields()[1],
+            Value::Value(Box::new(Value::U64(100)))
+        );
+
+        let value = typed(json!("abc"), "ay").unwrap();
+        assert_eq!(value_to_json(&value).unwrap(), json!([97, 98, 99]));
+    }
+
+    #[test]
+    fn test_type_mismatch_errors() {
+        let err = typed(json!("web"), "u").unwrap_err().to_string();
+        assert_eq!(err, "value: expected uint32 ('u'), got string \"web\"");
+
+        let err = typed(json!(-1), "u").unwrap_err().to_string();
+        assert!(err.contains("out of range for uint32"), "{}", err);
+
+        let err = typed(json!("not a path"), "o").unwrap_err().to_string();
+        assert!(err.starts_with("value: invalid object path"), "{}", err);
+
+        let err = typed(json!({"a": "x"}), "a{su}").unwrap_err().to_string();
+        assert!(err.starts_with("value.a: expected uint32"), "{}", err);
+
+        let err = typed(json!(["a"]), "(ss)").unwrap_err().to_string();
+        assert!(err.contains("expected (ss) with 2 fields"), "{}", err);
+    }
+
+    #[test]
+    fn test_method_body() {
+        assert!(split_signature("").unwrap().is_empty());
+        assert_eq!(split_signature("sa{sv}").unwrap().len(), 2);
+        assert_eq!(split_signature("(ss)").unwrap().len(), 1);
+
+        let signatures = split_signature("ssb").unwrap();
+        let body = json_to_body(
+            &[json!("web.service"), json!("replace"), json!(true)],
+            &signatures,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(body.signature().to_string_no_parens(), "ssb");
+
+        let err = json_to_body(&[json!("web.service")], &signatures).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected 3 argument(s) of type 'ssb', got 1"
+        );
+        assert!(json_to_body(&[], &[]).unwrap().is_none());
+    }
+}
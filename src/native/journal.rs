@@ -0,0 +1,419 @@
+// src/native/journal.rs - systemd journal file reader and writer
+//
+// Reads recent messages of a unit through `journalctl -o json`, which covers
+// rotated files and compressed fields. Without journalctl, the active journal
+// files are read directly: a forward scan of the object area of files up to
+// a size bound, enough for failure reports; it does not use the hash tables
+// or entry arrays and skips compressed fields.
+//
+// Entries are written with journald's native datagram protocol.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+const SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+const INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_COMPRESSED: u8 = 1 | 2 | 4;
+const OBJECT_HEADER_SIZE: usize = 16;
+
+/// Directories journald writes to (persistent, then volatile)
+const JOURNAL_DIRS: [&str; 2] = ["/var/log/journal", "/run/log/journal"];
+
+/// Largest journal file scanned without journalctl
+const MAX_SCAN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Socket journald receives native protocol datagrams on
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// A journal entry's message
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalLine {
+    pub realtime_usec: u64,
+    pub message: String,
+}
+
+/// Recent messages logged by or about `unit`, oldest first
+pub fn unit_messages(unit: &str, limit: usize) -> Result<Vec<JournalLine>> {
+    match journalctl_messages(unit, limit) {
+        Ok(lines) => return Ok(lines),
+        Err(e) => tracing::debug!("Reading journal files, journalctl failed: {:#}", e),
+    }
+
+    let mut lines = Vec::new();
+    for path in active_journal_files() {
+        match read_unit_messages(&path, unit, limit) {
+            Ok(found) => lines.extend(found),
+            Err(e) => tracing::debug!("Skipping journal {}: {}", path.display(), e),
+        }
+    }
+    lines.sort_by_key(|line| line.realtime_usec);
+    let skip = lines.len().saturating_sub(limit);
+    Ok(lines.split_off(skip))
+}
+
+/// Last `limit` messages of `unit` from `journalctl -o json`
+fn journalctl_messages(unit: &str, limit: usize) -> Result<Vec<JournalLine>> {
+    let output = std::process::Command::new("journalctl")
+        .args(["--no-pager", "--quiet", "--output=json"])
+        .args(["--lines", &limit.to_string(), "--unit", unit])
+        .output()
+        .context("Failed to run journalctl")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "journalctl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_json_lines(&output.stdout))
+}
+
+/// Entries of `journalctl -o json` output, one object per line
+///
+/// Messages that are not valid UTF-8 come as arrays of bytes.
+fn parse_json_lines(output: &[u8]) -> Vec<JournalLine> {
+    output
+        .split(|b| *b == b'\n')
+        .filter_map(|line| {
+            let entry: Value = serde_json::from_slice(line).ok()?;
+            let realtime_usec = entry["__REALTIME_TIMESTAMP"].as_str()?.parse().ok()?;
+            let message = match &entry["MESSAGE"] {
+                Value::String(message) => message.clone(),
+                Value::Array(bytes) => {
+                    let bytes: Vec<u8> = bytes
+                        .iter()
+                        .filter_map(|b| b.as_u64().map(|b| b as u8))
+                        .collect();
+                    String::from_utf8_lossy(&bytes).to_string()
+                }
+                _ => return None,
+            };
+            Some(JournalLine {
+                realtime_usec,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// The `system.journal` file of each machine directory
+pub fn active_journal_files() -> Vec<PathBuf> {
+    JOURNAL_DIRS
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path().join("system.journal"))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Last `limit` messages in one journal file matching `_SYSTEMD_UNIT=` or `UNIT=`
+///
+/// Files larger than `MAX_SCAN_BYTES` are refused rather than read whole.
+pub fn read_unit_messages(path: &Path, unit: &str, limit: usize) -> Result<Vec<JournalLine>> {
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_SCAN_BYTES {
+        anyhow::bail!("{} bytes is too large to scan", size);
+    }
+    let bytes = std::fs::read(path)?;
+    let journal = JournalFile::parse(&bytes)?;
+
+    let wanted = [
+        format!("_SYSTEMD_UNIT={}", unit).into_bytes(),
+        format!("UNIT={}", unit).into_bytes(),
+    ];
+
+    // Data objects are deduplicated and always written before the entries
+    // referencing them, so one pass is enough
+    let mut matching = HashSet::new();
+    let mut recent = VecDeque::with_capacity(limit + 1);
+    for object in journal.objects() {
+        match object.kind {
+            OBJECT_DATA => {
+                if let Some(payload) = journal.data_payload(object.offset) {
+                    if wanted.iter().any(|w| w.as_slice() == payload) {
+                        matching.insert(object.offset as u64);
+                    }
+                }
+            }
+            OBJECT_ENTRY
+                if journal
+                    .entry_items(object.offset, object.size)
+                    .any(|item| matching.contains(&item)) =>
+            {
+                recent.push_back(object);
+                if recent.len() > limit {
+                    recent.pop_front();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(recent
+        .into_iter()
+        .filter_map(|entry| {
+            let message = journal
+                .entry_items(entry.offset, entry.size)
+                .filter_map(|item| journal.data_payload(usize::try_from(item).ok()?))
+                .find_map(|payload| payload.strip_prefix(b"MESSAGE="))?;
+            Some(JournalLine {
+                realtime_usec: read_u64(journal.bytes, entry.offset + 24)?,
+                message: String::from_utf8_lossy(message).to_string(),
+            })
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Object {
+    offset: usize,
+    kind: u8,
+    size: usize,
+}
+
+struct JournalFile<'a> {
+    bytes: &'a [u8],
+    compact: bool,
+    header_size: usize,
+    tail_object: usize,
+}
+
+impl<'a> JournalFile<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() < 144 || &bytes[..8] != SIGNATURE {
+            anyhow::bail!("Not a journal file");
+        }
+        let incompatible = u32::from_le_bytes(bytes[12..16].try_into()?);
+        let header_size = read_u64(bytes, 88).unwrap_or(0) as usize;
+        let tail_object = read_u64(bytes, 136).unwrap_or(0) as usize;
+        if header_size < 144 {
+            anyhow::bail!("Invalid journal header size {}", header_size);
+        }
+
+        Ok(Self {
+            bytes,
+            compact: incompatible & INCOMPATIBLE_COMPACT != 0,
+            header_size,
+            tail_object,
+        })
+    }
+
+    /// Objects in file order, stopping at the first one that does not fit
+    fn objects(&self) -> impl Iterator<Item = Object> + '_ {
+        let mut offset = self.header_size;
+        std::iter::from_fn(move || {
+            if offset > self.tail_object || offset + OBJECT_HEADER_SIZE > self.bytes.len() {
+                return None;
+            }
+            let size = read_u64(self.bytes, offset + 8)? as usize;
+            if size < OBJECT_HEADER_SIZE || offset.checked_add(size)? > self.bytes.len() {
+                return None;
+            }
+            let object = Object {
+                offset,
+                kind: self.bytes[offset],
+                size,
+            };
+            offset = (offset + size + 7) & !7;
+            Some(object)
+        })
+    }
+
+    /// `FIELD=value` payload of an uncompressed data object
+    fn data_payload(&self, offset: usize) -> Option<&'a [u8]> {
+        let header = self.bytes.get(offset..offset + OBJECT_HEADER_SIZE)?;
+        if header[0] != OBJECT_DATA || header[1] & OBJECT_COMPRESSED != 0 {
+            return None;
+        }
+        let size = read_u64(self.bytes, offset + 8)? as usize;
+        let start = offset + if self.compact { 72 } else { 64 };
+        self.bytes.get(start..offset.checked_add(size)?)
+    }
+
+    /// Offsets of the data objects an entry references
+    fn entry_items(&self, offset: usize, size: usize) -> impl Iterator<Item = u64> + '_ {
+        let item_size = if self.compact { 4 } else { 16 };
+        let start = offset + 64;
+        let end = offset + size;
+        (start..end)
+            .step_by(item_size)
+            .take_while(move |pos| pos + item_size <= end)
+            .filter_map(move |pos| {
+                if self.compact {
+                    let raw = self.bytes.get(pos..pos + 4)?;
+                    Some(u32::from_le_bytes(raw.try_into().ok()?) as u64)
+                } else {
+                    read_u64(self.bytes, pos)
+                }
+            })
+    }
+}
+
+/// Send one entry with the given fields to journald
+///
+/// Field names must be upper case; values containing newlines are sent in
+/// the length-prefixed binary form.
+pub fn send(fields: &[(&str, &str)]) -> Result<()> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    socket.send_to(&encode_fields(fields), JOURNAL_SOCKET)?;
+    Ok(())
+}
+
+fn encode_fields(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    for (name, value) in fields {
+        datagram.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            datagram.push(b'\n');
+            datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            datagram.push(b'=');
+        }
+        datagram.extend_from_slice(value.as_bytes());
+        datagram.push(b'\n');
+    }
+    datagram
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let raw = bytes.get(offset..offset + 8)?;
+    Some(u64::from_le_bytes(raw.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal journal file with the given fields per entry
+    fn build_journal(compact: bool, entries: &[&[&str]]) -> Vec<u8> {
+        let header_size = 256usize;
+        let mut bytes = vec![0u8; header_size];
+        bytes[..8].copy_from_slice(SIGNATURE);
+        if compact {
+            bytes[12..16].copy_from_slice(&INCOMPATIBLE_COMPACT.to_le_bytes());
+        }
+        bytes[88..96].copy_from_slice(&(header_size as u64).to_le_bytes());
+
+        let mut data_offsets = std::collections::HashMap::new();
+        let mut tail = 0usize;
+        let append = |bytes: &mut Vec<u8>, kind: u8, body: &[u8]| {
+            let offset = bytes.len();
+            let size = OBJECT_HEADER_SIZE + body.len();
+            bytes.push(kind);
+            bytes.extend_from_slice(&[0u8; 7]);
+            bytes.extend_from_slice(&(size as u64).to_le_bytes());
+            bytes.extend_from_slice(body);
+            bytes.resize((bytes.len() + 7) & !7, 0);
+            offset
+        };
+
+        for (seq, fields) in entries.iter().enumerate() {
+            let mut items = Vec::new();
+            for field in *fields {
+                let offset = *data_offsets.entry(field.to_string()).or_insert_with(|| {
+                    let mut body = vec![0u8; if compact { 56 } else { 48 }];
+                    body.extend_from_slice(field.as_bytes());
+                    append(&mut bytes, OBJECT_DATA, &body)
+                });
+                items.push(offset as u64);
+            }
+
+            let mut body = vec![0u8; 48];
+            body[8..16].copy_from_slice(&(1_000 + seq as u64).to_le_bytes());
+            for item in items {
+                if compact {
+                    body.extend_from_slice(&(item as u32).to_le_bytes());
+                } else {
+                    body.extend_from_slice(&item.to_le_bytes());
+                    body.extend_from_slice(&[0u8; 8]);
+                }
+            }
+            tail = append(&mut bytes, OBJECT_ENTRY, &body);
+        }
+        bytes[136..144].copy_from_slice(&(tail as u64).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_encode_fields() {
+        let datagram = encode_fields(&[("MESSAGE", "applied"), ("DETAIL", "a\nb")]);
+        let mut expected = b"MESSAGE=applied\nDETAIL\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(datagram, expected);
+    }
+
+    fn messages(bytes: &[u8], unit: &str, limit: usize) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.journal");
+        std::fs::write(&path, bytes).unwrap();
+        read_unit_messages(&path, unit, limit)
+            .unwrap()
+            .into_iter()
+            .map(|line| line.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_unit_messages() {
+        for compact in [false, true] {
+            let bytes = build_journal(
+                compact,
+                &[
+                    &["_SYSTEMD_UNIT=backup.service", "MESSAGE=starting"],
+                    &["_SYSTEMD_UNIT=cron.service", "MESSAGE=tick"],
+                    &["_SYSTEMD_UNIT=backup.service", "MESSAGE=disk full"],
+                    &[
+                        "MESSAGE=backup.service: Failed with result 'exit-code'.",
+                        "UNIT=backup.service",
+                    ],
+                ],
+            );
+
+            assert_eq!(
+                messages(&bytes, "backup.service", 10),
+                [
+                    "starting",
+                    "disk full",
+                    "backup.service: Failed with result 'exit-code'."
+                ]
+            );
+            assert_eq!(
+                messages(&bytes, "backup.service", 1),
+                ["backup.service: Failed with result 'exit-code'."]
+            );
+            assert!(messages(&bytes, "ssh.service", 10).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_json_lines() {
+        let output = concat!(
+            r#"{"__REALTIME_TIMESTAMP":"1700000000000001","MESSAGE":"Started backup"}"#,
+            "\n",
+            r#"{"__REALTIME_TIMESTAMP":"1700000000000002","MESSAGE":[100,105,115,107,255]}"#,
+            "\n",
+            r#"{"__REALTIME_TIMESTAMP":"1700000000000003"}"#,
+            "\n",
+        );
+        let lines = parse_json_lines(output.as_bytes());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].realtime_usec, 1_700_000_000_000_001);
+        assert_eq!(lines[0].message, "Started backup");
+        assert_eq!(lines[1].message, "disk\u{fffd}");
+    }
+
+    #[test]
+    fn test_truncated_journal() {
+        let mut bytes = build_journal(false, &[&["_SYSTEMD_UNIT=a.service", "MESSAGE=one"]]);
+        bytes.truncate(bytes.len() - 4);
+        assert!(messages(&bytes, "a.service", 10).is_empty());
+        assert!(JournalFile::parse(b"not a journal").is_err());
+    }
+}
@@ -3,6 +3,9 @@ pub mod openflow;
 pub mod ovsdb_jsonrpc;
 pub mod rtnetlink_helpers;
 pub mod btrfs;
+pub mod dbus_marshal;
+pub mod journal;
+pub mod polkit;
 
 pub use ovsdb_jsonrpc::OvsdbClient;
 // rtnetlink_helpers functions accessed via rtnetlink_helpers::function_name
@@ -258,7 +258,7 @@ impl OpenFlowClient {
 }
 
 /// OpenFlow message trait
-trait OpenFlowMessage {
+trait OpenFlowMessage: Sync {
     fn message_type(&self) -> OpenFlowMessageType;
     fn xid(&self) -> u32;
     fn to_bytes(&self) -> Vec<u8>;
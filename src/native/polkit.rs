@@ -0,0 +1,502 @@
+// src/native/polkit.rs - polkit authorization client
+//
+// Mutating calls are checked with org.freedesktop.PolicyKit1.Authority
+// CheckAuthorization. Every plugin has its own action (org.opdbus.apply.net,
+// org.opdbus.apply.systemd, ...); plugins without an action registered in
+// org.opdbus.policy fall back to the generic org.opdbus.apply.
+//
+// OPDBUS_POLKIT=off disables the checks, for hosts without polkitd.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use zbus::proxy;
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+/// Action for applying state to plugins without their own action
+pub const APPLY_ACTION: &str = "org.opdbus.apply";
+
+/// CheckAuthorization flag letting polkit ask the caller's agent for a password
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+#[proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait Authority {
+    /// Returns (is_authorized, is_challenge, details)
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// polkit action for applying state with `plugin`
+pub fn apply_action(plugin: &str) -> String {
+    let element: String = plugin
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("{}.{}", APPLY_ACTION, element)
+}
+
+/// The caller an authorization is checked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subject {
+    /// Unique name of a D-Bus caller; polkitd resolves its credentials
+    BusName(String),
+    /// Local process, identified by pid and start time against pid reuse
+    UnixProcess { pid: u32, start_time: u64, uid: u32 },
+}
+
+impl Subject {
+    /// Subject for a process known from socket peer credentials
+    pub fn unix_process(pid: u32, uid: u32) -> Result<Self> {
+        Ok(Self::UnixProcess {
+            pid,
+            start_time: process_start_time(pid)?,
+            uid,
+        })
+    }
+
+    /// Process on the other end of stdin: the socket peer if stdin is a
+    /// unix socket, the parent process otherwise
+    pub fn stdio_peer() -> Result<Self> {
+        use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+        use std::os::fd::AsRawFd;
+
+        if let Ok(creds) = getsockopt(std::io::stdin().as_raw_fd(), PeerCredentials) {
+            return Self::unix_process(creds.pid() as u32, creds.uid());
+        }
+
+        let ppid = std::os::unix::process::parent_id();
+        let uid = std::fs::read_to_string(format!("/proc/{}/status", ppid))
+            .ok()
+            .and_then(|status| parse_status_uid(&status))
+            .with_context(|| format!("Failed to read credentials of process {}", ppid))?;
+        Self::unix_process(ppid, uid)
+    }
+
+    fn to_dbus(&self) -> (&'static str, HashMap<&'static str, Value<'_>>) {
+        match self {
+            Self::BusName(name) => (
+                "system-bus-name",
+                HashMap::from([("name", Value::from(name.as_str()))]),
+            ),
+            Self::UnixProcess {
+                pid,
+                start_time,
+                uid,
+            } => (
+                "unix-process",
+                HashMap::from([
+                    ("pid", Value::from(*pid)),
+                    ("start-time", Value::from(*start_time)),
+                    ("uid", Value::from(*uid as i32)),
+                ]),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BusName(name) => write!(f, "bus name {}", name),
+            Self::UnixProcess { pid, uid, .. } => write!(f, "pid {} (uid {})", pid, uid),
+        }
+    }
+}
+
+/// Why a mutating call was refused
+#[derive(Debug, thiserror::Error)]
+pub enum AuthorizationError {
+    #[error("{subject} is not authorized for {action}")]
+    Denied {
+        action: String,
+        subject: String,
+        /// Authentication would have granted it
+        challenge: bool,
+    },
+    #[error("Authorization check failed: {0}")]
+    Unavailable(String),
+}
+
+/// Client for the polkit authority
+#[derive(Clone)]
+pub struct PolkitAuthority {
+    /// `None` when authorization is disabled or polkit is unreachable
+    connection: Option<Connection>,
+    /// Why polkit is unreachable; every check fails with it
+    unavailable: Option<String>,
+    allow_interaction: bool,
+}
+
+impl PolkitAuthority {
+    /// Authority on the system bus, unless `OPDBUS_POLKIT=off`
+    pub async fn system() -> Result<Self> {
+        let disabled = std::env::var("OPDBUS_POLKIT")
+            .map(|v| matches!(v.as_str(), "off" | "0" | "false" | "disabled"))
+            .unwrap_or(false);
+        if disabled {
+            log::warn!("polkit authorization disabled by OPDBUS_POLKIT");
+            return Ok(Self::disabled());
+        }
+
+        let connection = Connection::system()
+            .await
+            .context("Failed to connect to system bus for polkit")?;
+        Ok(Self::new(connection))
+    }
+
+    /// `system()`, or an authority refusing every call when the system bus
+    /// cannot be reached
+    pub async fn system_or_unavailable() -> Self {
+        match Self::system().await {
+            Ok(authority) => authority,
+            Err(e) => {
+                log::warn!("polkit unavailable, refusing changes: {:#}", e);
+                Self::unavailable(format!("{:#}", e))
+            }
+        }
+    }
+
+    /// Authority reached through `connection`
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection: Some(connection),
+            unavailable: None,
+            allow_interaction: true,
+        }
+    }
+
+    /// Allow every call, without asking polkit
+    pub fn disabled() -> Self {
+        Self {
+            connection: None,
+            unavailable: None,
+            allow_interaction: false,
+        }
+    }
+
+    /// Refuse every call with `reason`
+    pub fn unavailable(reason: impl Into<String>) -> Self {
+        Self {
+            connection: None,
+            unavailable: Some(reason.into()),
+            allow_interaction: false,
+        }
+    }
+
+    /// Whether polkit may prompt the caller's authentication agent
+    pub fn with_interaction(mut self, allow: bool) -> Self {
+        self.allow_interaction = allow;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.connection.is_some() || self.unavailable.is_some()
+    }
+
+    /// Check `action` for `subject`
+    pub async fn authorize(
+        &self,
+        subject: &Subject,
+        action: &str,
+    ) -> std::result::Result<(), AuthorizationError> {
+        self.check(subject, action, HashMap::new()).await
+    }
+
+    /// Check the apply action of `plugin` for `subject`
+    pub async fn authorize_apply(
+        &self,
+        subject: &Subject,
+        plugin: &str,
+    ) -> std::result::Result<(), AuthorizationError> {
+        let details = HashMap::from([("plugin", plugin)]);
+        match self
+            .check(subject, &apply_action(plugin), details.clone())
+            .await
+        {
+            Err(AuthorizationError::Unavailable(e)) if e.contains("not registered") => {
+                self.check(subject, APPLY_ACTION, details).await
+            }
+            result => result,
+        }
+    }
+
+    async fn check(
+        &self,
+        subject: &Subject,
+        action: &str,
+        details: HashMap<&str, &str>,
+    ) -> std::result::Result<(), AuthorizationError> {
+        let Some(connection) = &self.connection else {
+            return match &self.unavailable {
+                Some(reason) => Err(AuthorizationError::Unavailable(reason.clone())),
+                None => Ok(()),
+            };
+        };
+
+        let unavailable = |e: zbus::Error| AuthorizationError::Unavailable(e.to_string());
+        let proxy = AuthorityProxy::new(connection).await.map_err(unavailable)?;
+        let flags = if self.allow_interaction {
+            ALLOW_USER_INTERACTION
+        } else {
+            0
+        };
+        let (authorized, challenge, _) = proxy
+            .check_authorization(&subject.to_dbus(), action, details, flags, "")
+            .await
+            .map_err(unavailable)?;
+
+        if authorized {
+            log::debug!("polkit authorized {} for {}", action, subject);
+            Ok(())
+        } else {
+            log::warn!("polkit denied {} for {}", action, subject);
+            Err(AuthorizationError::Denied {
+                action: action.to_string(),
+                subject: subject.to_string(),
+                challenge,
+            })
+        }
+    }
+}
+
+/// Start time of a process in clock ticks since boot (field 22 of /proc/<pid>/stat)
+fn process_start_time(pid: u32) -> Result<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .with_context(|| format!("Failed to read /proc/{}/stat", pid))?;
+    parse_stat_start_time(&stat).with_context(|| format!("Malformed /proc/{}/stat", pid))
+}
+
+fn parse_stat_start_time(stat: &str) -> Option<u64> {
+    // The command name may contain spaces and parentheses, fields resume
+    // after the last ')' starting with field 3
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+fn parse_status_uid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use zbus::interface;
+    use zbus::zvariant::OwnedValue;
+
+    /// Subject as received by an authority
+    pub(crate) type SubjectDict = (String, HashMap<String, OwnedValue>);
+
+    /// Private dbus-daemon, stopped on drop
+    pub(crate) struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        /// `None` if dbus-daemon is not installed
+        pub(crate) fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        pub(crate) fn address(&self) -> &str {
+            &self.address
+        }
+
+        pub(crate) async fn connect(&self) -> Connection {
+            zbus::connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .await
+                .unwrap()
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// polkit authority granting a fixed set of actions
+    pub(crate) struct MockAuthority {
+        registered: Vec<String>,
+        allowed: Vec<String>,
+        pub(crate) checks: Arc<Mutex<Vec<(SubjectDict, String)>>>,
+    }
+
+    #[interface(name = "org.freedesktop.PolicyKit1.Authority")]
+    impl MockAuthority {
+        async fn check_authorization(
+            &self,
+            subject: SubjectDict,
+            action_id: String,
+            _details: HashMap<String, String>,
+            _flags: u32,
+            _cancellation_id: String,
+        ) -> zbus::fdo::Result<(bool, bool, HashMap<String, String>)> {
+            if !self.registered.contains(&action_id) {
+                return Err(zbus::fdo::Error::Failed(format!(
+                    "Action {} is not registered",
+                    action_id
+                )));
+            }
+            let allowed = self.allowed.contains(&action_id);
+            self.checks.lock().unwrap().push((subject, action_id));
+            Ok((allowed, false, HashMap::new()))
+        }
+    }
+
+    /// Serve a mock authority on `bus`; returns the connection and the
+    /// checks it received
+    pub(crate) async fn serve_mock_authority(
+        bus: &TestBus,
+        registered: &[&str],
+        allowed: &[&str],
+    ) -> (Connection, Arc<Mutex<Vec<(SubjectDict, String)>>>) {
+        let checks = Arc::new(Mutex::new(Vec::new()));
+        let mock = MockAuthority {
+            registered: registered.iter().map(|a| a.to_string()).collect(),
+            allowed: allowed.iter().map(|a| a.to_string()).collect(),
+            checks: checks.clone(),
+        };
+        let connection = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name("org.freedesktop.PolicyKit1")
+            .unwrap()
+            .serve_at("/org/freedesktop/PolicyKit1/Authority", mock)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        (connection, checks)
+    }
+
+    #[test]
+    fn test_action_ids_and_proc_parsing() {
+        assert_eq!(apply_action("net"), "org.opdbus.apply.net");
+        assert_eq!(
+            apply_action("privacy_router"),
+            "org.opdbus.apply.privacy-router"
+        );
+
+        let stat = "4242 (my (odd) cmd) S 1 4242 4242 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 987654 1000 100";
+        assert_eq!(parse_stat_start_time(stat), Some(987654));
+        assert_eq!(
+            parse_status_uid("Name:\tbash\nUid:\t1000\t1000\t1000\t1000\n"),
+            Some(1000)
+        );
+
+        // Every built-in plugin has its own action
+        let policy = include_str!("../../org.opdbus.policy");
+        for plugin in ["net", "systemd", "lxc", "openflow", "privacy_router"] {
+            assert!(policy.contains(&format!("\"{}\"", apply_action(plugin))));
+        }
+
+        let own = Subject::unix_process(std::process::id(), 0).unwrap();
+        assert!(matches!(own, Subject::UnixProcess { start_time, .. } if start_time > 0));
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_refuses() {
+        let own = Subject::unix_process(std::process::id(), 0).unwrap();
+        assert!(PolkitAuthority::disabled()
+            .authorize(&own, "x")
+            .await
+            .is_ok());
+
+        let authority = PolkitAuthority::unavailable("no system bus");
+        assert!(authority.is_enabled());
+        assert!(matches!(
+            authority.authorize_apply(&own, "net").await,
+            Err(AuthorizationError::Unavailable(reason)) if reason == "no system bus"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_with_mock_polkit() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_mock, checks) = serve_mock_authority(
+            &bus,
+            &["org.opdbus.apply", "org.opdbus.apply.net"],
+            &["org.opdbus.apply"],
+        )
+        .await;
+        let authority = PolkitAuthority::new(bus.connect().await);
+        let subject = Subject::UnixProcess {
+            pid: 4242,
+            start_time: 987654,
+            uid: 1000,
+        };
+
+        // Registered and denied
+        let denied = authority.authorize_apply(&subject, "net").await;
+        assert!(matches!(
+            denied,
+            Err(AuthorizationError::Denied { ref action, .. }) if action == "org.opdbus.apply.net"
+        ));
+
+        // No action of its own, falls back to the generic one
+        authority
+            .authorize_apply(&subject, "keyring")
+            .await
+            .unwrap();
+
+        {
+            let checks = checks.lock().unwrap();
+            assert_eq!(checks.len(), 2);
+            let (kind, details) = &checks[0].0;
+            assert_eq!(kind, "unix-process");
+            assert_eq!(u32::try_from(&details["pid"]).unwrap(), 4242);
+            assert_eq!(i32::try_from(&details["uid"]).unwrap(), 1000);
+            assert_eq!(checks[1].1, "org.opdbus.apply");
+        }
+
+        assert!(PolkitAuthority::disabled()
+            .authorize(&subject, "org.opdbus.apply.net")
+            .await
+            .is_ok());
+    }
+}
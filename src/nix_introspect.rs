@@ -7,7 +7,7 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use futures::stream::TryStreamExt;
-use netlink_packet_route::address::AddressAttribute;
+use netlink_packet_route::address::Nla as AddressNla;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
@@ -301,7 +301,7 @@ impl SystemState {
             for service in &self.dbus_services {
                 config.push_str(&format!("  #   - {} ({})\n", service.name, service.bus));
             }
-            config.push_str("\n");
+            config.push('\n');
         }
 
         // Containers
@@ -318,7 +318,7 @@ impl SystemState {
                     container.name, container.container_type, container.state
                 ));
             }
-            config.push_str("\n");
+            config.push('\n');
         }
 
         config.push_str("  # System state version\n");
@@ -408,6 +408,21 @@ fn parse_interfaces_from_xml(xml: &str) -> Vec<String> {
 }
 
 /// Scan systemd units
+/// Entry of systemd's `ListUnits`: name, description, load, active and sub
+/// state, followed unit, unit path, job id, type and path
+type ListedUnit = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    zbus::zvariant::OwnedObjectPath,
+    u32,
+    String,
+    zbus::zvariant::OwnedObjectPath,
+);
+
 async fn scan_systemd_units() -> Result<Vec<SystemdUnitInfo>> {
     info!("Scanning systemd units...");
 
@@ -421,18 +436,7 @@ async fn scan_systemd_units() -> Result<Vec<SystemdUnitInfo>> {
     .await?;
 
     // List all units
-    let units: Vec<(
-        String,
-        String,
-        String,
-        String,
-        String,
-        String,
-        zbus::zvariant::OwnedObjectPath,
-        u32,
-        String,
-        zbus::zvariant::OwnedObjectPath,
-    )> = proxy.call("ListUnits", &()).await?;
+    let units: Vec<ListedUnit> = proxy.call("ListUnits", &()).await?;
 
     let mut unit_infos = Vec::new();
 
@@ -451,7 +455,7 @@ async fn scan_systemd_units() -> Result<Vec<SystemdUnitInfo>> {
     {
         if load_state == "loaded" {
             // Determine unit type from name
-            let unit_type = name.split('.').last().unwrap_or("unknown").to_string();
+            let unit_type = name.split('.').next_back().unwrap_or("unknown").to_string();
 
             // Check if unit is enabled
             let enabled = check_unit_enabled(&proxy, &name).await.unwrap_or(false);
@@ -533,7 +537,7 @@ async fn scan_network_interfaces() -> Result<Vec<NetworkInterfaceInfo>> {
 
 /// Get IP addresses for an interface using rtnetlink
 async fn get_interface_ips(iface_name: &str) -> Result<Vec<String>> {
-    use rtnetlink::{new_connection, IpVersion};
+    use rtnetlink::new_connection;
 
     let (connection, handle, _) = new_connection()?;
     tokio::spawn(connection);
@@ -557,9 +561,21 @@ async fn get_interface_ips(iface_name: &str) -> Result<Vec<String>> {
             .execute();
 
         while let Some(addr) = addr_handle.try_next().await? {
-            for nla in addr.attributes {
-                if let AddressAttribute::Address(ip) = nla {
-                    addresses.push(format!("{}", std::net::IpAddr::from(ip)));
+            for nla in addr.nlas {
+                let AddressNla::Address(bytes) = nla else {
+                    continue;
+                };
+                let ip = match bytes.len() {
+                    4 => <[u8; 4]>::try_from(bytes.as_slice())
+                        .ok()
+                        .map(std::net::IpAddr::from),
+                    16 => <[u8; 16]>::try_from(bytes.as_slice())
+                        .ok()
+                        .map(std::net::IpAddr::from),
+                    _ => None,
+                };
+                if let Some(ip) = ip {
+                    addresses.push(ip.to_string());
                 }
             }
         }
@@ -3,13 +3,12 @@
 // This plugin is automatically created from D-Bus introspection data.
 // It maps D-Bus properties to plugin state.
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use zbus::{Connection, Proxy};
 use zbus::zvariant::Value as ZValue;
-use crate::plugin_system::{Plugin, Change, ValidationResult, PluginCapabilities, PluginMetadata, PluginContext};
-use crate::mcp::chat::introspection_parser::IntrospectionParser;
+use crate::plugin_system::{Plugin, Change, ValidationResult, PluginCapabilities, PluginContext};
 
 pub struct DbusAutoPlugin {
     name: String,
@@ -62,7 +61,7 @@ impl Plugin for DbusAutoPlugin {
 
     async fn get_state(&self) -> Result<Value> {
         // Create a generic proxy to access properties
-        let proxy = Proxy::new(
+        let _proxy = Proxy::new(
             &self.connection,
             self.service_name.as_str(),
             self.object_path.as_str(),
@@ -89,7 +88,7 @@ impl Plugin for DbusAutoPlugin {
                     // This is a simplification. zbus::zvariant::Value to serde_json::Value 
                     // conversion is non-trivial for complex types.
                     // For now, we'll just convert basic types and stringify others.
-                    let json_val = match value.as_ref() {
+                    let json_val = match &*value {
                         ZValue::Str(s) => json!(s.as_str()),
                         ZValue::Bool(b) => json!(b),
                         ZValue::U8(i) => json!(i),
@@ -117,14 +116,14 @@ impl Plugin for DbusAutoPlugin {
         // For auto-plugins, applying state is risky without a schema.
         // We will attempt to set writable properties if they exist in the desired state.
         
-        let props_proxy = zbus::fdo::PropertiesProxy::builder(&self.connection)
+        let _props_proxy = zbus::fdo::PropertiesProxy::builder(&self.connection)
             .destination(self.service_name.as_str())?
             .path(self.object_path.as_str())?
             .build()
             .await?;
 
         if let Some(obj) = desired.as_object() {
-            for (key, value) in obj {
+            for (_key, _value) in obj {
                 // Attempt to set property
                 // We need to convert serde_json::Value back to zvariant::Value
                 // This is hard without knowing the expected type.
@@ -136,7 +135,7 @@ impl Plugin for DbusAutoPlugin {
         Ok(())
     }
 
-    async fn diff(&self, current: Value, desired: Value) -> Result<Vec<Change>> {
+    async fn diff(&self, _current: Value, _desired: Value) -> Result<Vec<Change>> {
         // Simple JSON diff
         Ok(vec![])
     }
@@ -11,7 +11,7 @@ use tracing::{info, warn};
 
 // Import OVSDB client
 use crate::native::ovsdb_jsonrpc::OvsdbClient;
-use crate::plugin_system::{Plugin, Change, ValidationResult, PluginCapabilities, PluginMetadata, PluginContext};
+use crate::plugin_system::{Plugin, Change, ValidationResult, PluginCapabilities, PluginContext};
 use async_trait::async_trait;
 use serde_json::Value;
 
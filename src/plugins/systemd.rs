@@ -6,8 +6,7 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::process::Command;
-use crate::plugin_system::{Plugin, Change, ValidationResult, PluginCapabilities, PluginMetadata, PluginContext};
+use crate::plugin_system::{Plugin, Change, ValidationResult, PluginCapabilities, PluginContext};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemdPlugin {
@@ -123,7 +122,7 @@ impl Plugin for SystemdPlugin {
         Ok(())
     }
 
-    async fn diff(&self, current: Value, desired: Value) -> Result<Vec<Change>> {
+    async fn diff(&self, _current: Value, _desired: Value) -> Result<Vec<Change>> {
         // TODO: Implement diff logic
         Ok(vec![])
     }
@@ -0,0 +1,255 @@
+//! Native snapshot replication between op-dbus instances
+//!
+//! Replaces `btrfs send | ssh host btrfs receive` shell pipelines with a
+//! framed streaming protocol:
+//! - Incremental sends via `btrfs send -p <parent>`
+//! - Tar streams for snapshots made by the directory storage backend
+//! - Shared-secret challenge/response authentication
+//! - Unix socket, TCP and TLS transports
+//! - Per-replica tracking of acknowledged snapshots
+//! - Resumable transfers and SHA-256 verification of every stream
+//!
+//! The receiving end is `op-dbus replica serve`.
+
+pub mod protocol;
+pub mod receiver;
+pub mod sender;
+pub mod tracker;
+pub mod transport;
+
+pub use receiver::{BtrfsReceiveSink, DirectorySink, ReplicaReceiver, StreamSink, TarReceiveSink};
+pub use sender::{
+    BtrfsSendSource, FileSource, ReplicationReport, ReplicationSender, SourceStream, StreamSource,
+    TarSource,
+};
+pub use tracker::{AckedSnapshot, ReplicaRecord, ReplicaTracker};
+pub use transport::{ReplicaEndpoint, ReplicaListener};
+
+use anyhow::{Context, Result};
+
+/// Default location of the shared replication token
+pub const DEFAULT_TOKEN_PATH: &str = "/etc/op-dbus/replica.token";
+
+/// Load the shared replication token
+///
+/// Taken from `OPDBUS_REPLICA_TOKEN`, or from the file named by
+/// `OPDBUS_REPLICA_TOKEN_FILE` (default `/etc/op-dbus/replica.token`).
+pub fn load_token() -> Result<Vec<u8>> {
+    if let Ok(token) = std::env::var("OPDBUS_REPLICA_TOKEN") {
+        if !token.is_empty() {
+            return Ok(token.into_bytes());
+        }
+    }
+
+    let path = std::env::var("OPDBUS_REPLICA_TOKEN_FILE")
+        .unwrap_or_else(|_| DEFAULT_TOKEN_PATH.to_string());
+    let token = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "No replication token (set OPDBUS_REPLICA_TOKEN or create {})",
+            path
+        )
+    })?;
+    let token = token.trim();
+    if token.is_empty() {
+        anyhow::bail!("Replication token file {} is empty", path);
+    }
+    Ok(token.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const TOKEN: &[u8] = b"test-token";
+
+    struct Loopback {
+        _dir: tempfile::TempDir,
+        endpoint: ReplicaEndpoint,
+        snapshots: std::path::PathBuf,
+        spool: std::path::PathBuf,
+        target: std::path::PathBuf,
+        tracker: Arc<ReplicaTracker>,
+    }
+
+    async fn loopback() -> Loopback {
+        loopback_with(|target| Arc::new(DirectorySink::new(target))).await
+    }
+
+    async fn loopback_with(sink: impl FnOnce(&Path) -> Arc<dyn StreamSink>) -> Loopback {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = ReplicaEndpoint::Unix(dir.path().join("replica.sock"));
+        let snapshots = dir.path().join("snapshots");
+        let spool = dir.path().join("spool");
+        let target = dir.path().join("target");
+        std::fs::create_dir_all(&snapshots).unwrap();
+
+        let receiver = Arc::new(ReplicaReceiver::new(&spool, sink(&target), TOKEN.to_vec()));
+        let listener = ReplicaListener::bind(&endpoint, None).await.unwrap();
+        tokio::spawn(receiver.serve(listener));
+
+        let tracker = Arc::new(ReplicaTracker::load(dir.path().join("replicas.json")).unwrap());
+        Loopback {
+            _dir: dir,
+            endpoint,
+            snapshots,
+            spool,
+            target,
+            tracker,
+        }
+    }
+
+    fn sender(lb: &Loopback, token: &[u8]) -> ReplicationSender {
+        ReplicationSender::new(
+            Arc::new(FileSource),
+            Arc::clone(&lb.tracker),
+            token.to_vec(),
+        )
+        .with_node("test")
+        .with_max_attempts(2)
+    }
+
+    fn write_snapshot(dir: &Path, name: &str, len: usize) -> std::path::PathBuf {
+        let path = dir.join(name);
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_loopback_full_and_incremental() {
+        let lb = loopback().await;
+        let sender = sender(&lb, TOKEN);
+        let replica = lb.endpoint.to_string();
+
+        let first = write_snapshot(&lb.snapshots, "vectors-a", 600_000);
+        let report = sender.replicate(&lb.endpoint, &first).await.unwrap();
+        assert_eq!(report.bytes_sent, 600_000);
+        assert_eq!(report.parent, None);
+        assert_eq!(
+            std::fs::read(lb.target.join("vectors-a")).unwrap(),
+            std::fs::read(&first).unwrap()
+        );
+        assert!(lb.tracker.is_acked(&replica, "vectors-a"));
+
+        // Second snapshot of the same family is sent relative to the acked one
+        let second = write_snapshot(&lb.snapshots, "vectors-b", 1000);
+        let report = sender.replicate(&lb.endpoint, &second).await.unwrap();
+        assert_eq!(report.parent.as_deref(), Some("vectors-a"));
+
+        // Re-sending an existing snapshot is acknowledged without data
+        let report = sender.replicate(&lb.endpoint, &first).await.unwrap();
+        assert!(report.already_present);
+        assert_eq!(report.bytes_sent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_missing_parent_falls_back_to_full_send() {
+        let lb = loopback().await;
+        let sender = sender(&lb, TOKEN);
+
+        let first = write_snapshot(&lb.snapshots, "state-1", 100);
+        sender.replicate(&lb.endpoint, &first).await.unwrap();
+        std::fs::remove_file(lb.target.join("state-1")).unwrap();
+
+        let second = write_snapshot(&lb.snapshots, "state-2", 100);
+        let report = sender.replicate(&lb.endpoint, &second).await.unwrap();
+        assert_eq!(report.parent, None);
+        assert!(lb.target.join("state-2").exists());
+    }
+
+    #[tokio::test]
+    async fn test_loopback_resume_and_verification() {
+        let lb = loopback().await;
+        let sender = sender(&lb, TOKEN);
+        let snapshot = write_snapshot(&lb.snapshots, "cache@1", 300_000);
+        let data = std::fs::read(&snapshot).unwrap();
+
+        // Simulate an interrupted transfer: the replica already spooled a prefix
+        std::fs::create_dir_all(&lb.spool).unwrap();
+        let spool = lb
+            .spool
+            .join(format!("{}.part", protocol::stream_id("cache@1", None)));
+        std::fs::write(&spool, &data[..100_000]).unwrap();
+
+        let report = sender.replicate(&lb.endpoint, &snapshot).await.unwrap();
+        assert_eq!(report.resumed_from, 100_000);
+        assert_eq!(report.bytes_sent, 200_000);
+        assert_eq!(std::fs::read(lb.target.join("cache@1")).unwrap(), data);
+        assert!(!spool.exists());
+
+        // A corrupt spool fails verification, then the retry starts from scratch
+        let snapshot = write_snapshot(&lb.snapshots, "cache@2", 50_000);
+        let spool = lb.spool.join(format!(
+            "{}.part",
+            protocol::stream_id("cache@2", Some("cache@1"))
+        ));
+        std::fs::write(&spool, vec![0xffu8; 10_000]).unwrap();
+
+        let report = sender.replicate(&lb.endpoint, &snapshot).await.unwrap();
+        assert_eq!(report.resumed_from, 0);
+        assert_eq!(
+            std::fs::read(lb.target.join("cache@2")).unwrap(),
+            std::fs::read(&snapshot).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_loopback_rejects_bad_token() {
+        let lb = loopback().await;
+        let sender = sender(&lb, b"wrong-token");
+        let snapshot = write_snapshot(&lb.snapshots, "vectors-x", 10);
+
+        assert!(sender.replicate(&lb.endpoint, &snapshot).await.is_err());
+        assert!(!lb.target.join("vectors-x").exists());
+
+        let record = lb
+            .tracker
+            .records()
+            .remove(&lb.endpoint.to_string())
+            .unwrap();
+        assert!(record.acked.is_empty());
+        assert!(record.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_loopback_directory_backend_snapshots() {
+        use crate::storage::{CopyMode, DirectoryBackend, SnapshotBackend};
+
+        let lb = loopback_with(|target| Arc::new(TarReceiveSink::new(target))).await;
+        let backend = DirectoryBackend::with_mode(CopyMode::Hardlink);
+        let live = lb.snapshots.join("live");
+        backend.create_volume(&live).await.unwrap();
+        std::fs::write(live.join("a.vec"), b"vector").unwrap();
+
+        let snapshot = lb.snapshots.join("vectors-1");
+        backend.snapshot(&live, &snapshot).await.unwrap();
+
+        let tar_sender = ReplicationSender::new(
+            backend.stream_source(),
+            Arc::clone(&lb.tracker),
+            TOKEN.to_vec(),
+        )
+        .with_max_attempts(1);
+        tar_sender.replicate(&lb.endpoint, &snapshot).await.unwrap();
+        assert_eq!(
+            std::fs::read(lb.target.join("vectors-1/a.vec")).unwrap(),
+            b"vector"
+        );
+
+        // Tar streams are always full, even once a sibling has been acked
+        let second = lb.snapshots.join("vectors-2");
+        backend.snapshot(&live, &second).await.unwrap();
+        let report = tar_sender.replicate(&lb.endpoint, &second).await.unwrap();
+        assert_eq!(report.parent, None);
+        assert!(lb.target.join("vectors-2/a.vec").exists());
+
+        // A tar replica refuses btrfs send streams
+        let stream = write_snapshot(&lb.snapshots, "vectors-3", 10);
+        assert!(sender(&lb, TOKEN)
+            .replicate(&lb.endpoint, &stream)
+            .await
+            .is_err());
+    }
+}
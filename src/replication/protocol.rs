@@ -0,0 +1,330 @@
+//! Wire protocol for snapshot replication
+//!
+//! Every frame is `[kind: u8][len: u32 BE][payload]`. Control frames carry a
+//! JSON encoded [`Message`], data frames carry raw snapshot stream bytes
+//! (`btrfs send` streams, or tar archives from directory storage backends).
+//!
+//! Session flow:
+//! 1. sender -> `Hello`, receiver -> `Challenge`
+//! 2. sender -> `Auth` (HMAC over both nonces), receiver -> `AuthOk` (its own HMAC)
+//! 3. sender -> `Offer`, receiver -> `Accept { offset }` / `AlreadyHave` / `MissingParent`
+//! 4. sender -> data frames from `offset`, then `End { total_bytes, sha256 }`
+//! 5. receiver verifies the full stream and applies it, then -> `Ack`
+
+use anyhow::{Context, Result};
+use crate::encoding::{constant_time_eq, to_hex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Protocol version announced in `Hello`
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Size of data frames produced by the sender
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Upper bound for a single frame, protects the receiver from bogus lengths
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Stream produced by `btrfs send`
+pub const FORMAT_BTRFS_SEND: &str = "btrfs-send";
+
+/// Tar archive of a directory snapshot
+pub const FORMAT_TAR: &str = "tar";
+
+const FRAME_CONTROL: u8 = 0;
+const FRAME_DATA: u8 = 1;
+
+/// Control messages exchanged between sender and receiver
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Hello {
+        version: u32,
+        node: String,
+        nonce: String,
+    },
+    Challenge {
+        nonce: String,
+    },
+    Auth {
+        mac: String,
+    },
+    AuthOk {
+        mac: String,
+    },
+    Offer {
+        snapshot: String,
+        parent: Option<String>,
+        stream_id: String,
+        #[serde(default = "default_format")]
+        format: String,
+    },
+    Accept {
+        offset: u64,
+    },
+    AlreadyHave {
+        snapshot: String,
+    },
+    MissingParent {
+        parent: String,
+    },
+    End {
+        total_bytes: u64,
+        sha256: String,
+    },
+    Ack {
+        snapshot: String,
+        sha256: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+fn default_format() -> String {
+    FORMAT_BTRFS_SEND.to_string()
+}
+
+/// A decoded frame
+#[derive(Debug)]
+pub enum Frame {
+    Control(Message),
+    Data(Vec<u8>),
+}
+
+/// Write a control message
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Message) -> Result<()> {
+    let payload = serde_json::to_vec(message)?;
+    write_frame(writer, FRAME_CONTROL, &payload).await
+}
+
+/// Write a chunk of stream data
+pub async fn write_data<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> Result<()> {
+    write_frame(writer, FRAME_DATA, data).await
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    kind: u8,
+    payload: &[u8],
+) -> Result<()> {
+    if payload.len() > MAX_FRAME_LEN {
+        anyhow::bail!("Frame too large: {} bytes", payload.len());
+    }
+    writer.write_u8(kind).await?;
+    writer.write_u32(payload.len() as u32).await?;
+    writer.write_all(payload).await?;
+    if kind == FRAME_CONTROL {
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// Read the next frame
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Frame> {
+    let kind = reader.read_u8().await.context("Connection closed")?;
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_LEN {
+        anyhow::bail!("Frame too large: {} bytes", len);
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+
+    match kind {
+        FRAME_CONTROL => Ok(Frame::Control(
+            serde_json::from_slice(&payload).context("Invalid control message")?,
+        )),
+        FRAME_DATA => Ok(Frame::Data(payload)),
+        other => anyhow::bail!("Unknown frame kind: {}", other),
+    }
+}
+
+/// Read the next control message, turning a remote `Error` into an `Err`
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message> {
+    match read_frame(reader).await? {
+        Frame::Control(Message::Error { message }) => anyhow::bail!("Remote error: {}", message),
+        Frame::Control(message) => Ok(message),
+        Frame::Data(_) => anyhow::bail!("Unexpected data frame"),
+    }
+}
+
+/// Client side of the challenge/response handshake
+pub async fn handshake_client<S>(stream: &mut S, node: &str, token: &[u8]) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let client_nonce = random_nonce();
+    write_message(
+        stream,
+        &Message::Hello {
+            version: PROTOCOL_VERSION,
+            node: node.to_string(),
+            nonce: client_nonce.clone(),
+        },
+    )
+    .await?;
+
+    let server_nonce = match read_message(stream).await? {
+        Message::Challenge { nonce } => nonce,
+        other => anyhow::bail!("Expected challenge, got {:?}", other),
+    };
+
+    let mac = auth_mac(token, "client", &server_nonce, &client_nonce);
+    write_message(stream, &Message::Auth { mac }).await?;
+
+    match read_message(stream).await? {
+        Message::AuthOk { mac } => {
+            let expected = auth_mac(token, "server", &client_nonce, &server_nonce);
+            if !constant_time_eq(mac.as_bytes(), expected.as_bytes()) {
+                anyhow::bail!("Replica failed to authenticate");
+            }
+            Ok(())
+        }
+        other => anyhow::bail!("Expected auth confirmation, got {:?}", other),
+    }
+}
+
+/// Server side of the challenge/response handshake, returns the peer node name
+pub async fn handshake_server<S>(stream: &mut S, token: &[u8]) -> Result<String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (node, client_nonce) = match read_message(stream).await? {
+        Message::Hello {
+            version,
+            node,
+            nonce,
+        } => {
+            if version != PROTOCOL_VERSION {
+                let message = format!("Unsupported protocol version {}", version);
+                write_message(
+                    stream,
+                    &Message::Error {
+                        message: message.clone(),
+                    },
+                )
+                .await?;
+                anyhow::bail!(message);
+            }
+            (node, nonce)
+        }
+        other => anyhow::bail!("Expected hello, got {:?}", other),
+    };
+
+    let server_nonce = random_nonce();
+    write_message(
+        stream,
+        &Message::Challenge {
+            nonce: server_nonce.clone(),
+        },
+    )
+    .await?;
+
+    let mac = match read_message(stream).await? {
+        Message::Auth { mac } => mac,
+        other => anyhow::bail!("Expected auth, got {:?}", other),
+    };
+
+    let expected = auth_mac(token, "client", &server_nonce, &client_nonce);
+    if !constant_time_eq(mac.as_bytes(), expected.as_bytes()) {
+        write_message(
+            stream,
+            &Message::Error {
+                message: "Authentication failed".to_string(),
+            },
+        )
+        .await?;
+        anyhow::bail!("Authentication failed for node {}", node);
+    }
+
+    let mac = auth_mac(token, "server", &client_nonce, &server_nonce);
+    write_message(stream, &Message::AuthOk { mac }).await?;
+    Ok(node)
+}
+
+/// Stable identifier for a (snapshot, parent) stream, used to key resumable spools
+pub fn stream_id(snapshot: &str, parent: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(snapshot.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(parent.unwrap_or("").as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+fn auth_mac(token: &[u8], role: &str, first: &str, second: &str) -> String {
+    to_hex(&hmac_sha256(
+        token,
+        &[role.as_bytes(), first.as_bytes(), second.as_bytes()],
+    ))
+}
+
+fn random_nonce() -> String {
+    to_hex(&rand::random::<[u8; 32]>())
+}
+
+/// HMAC-SHA256 (RFC 2104) over the concatenation of `parts`
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    for part in parts {
+        inner.update(part);
+    }
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        // RFC 4231 test case 2
+        let mac = hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(
+            to_hex(&mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_stream_id_depends_on_parent() {
+        assert_ne!(
+            stream_id("vectors-b", None),
+            stream_id("vectors-b", Some("vectors-a"))
+        );
+        assert_eq!(stream_id("vectors-b", None), stream_id("vectors-b", None));
+    }
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        write_message(&mut a, &Message::Accept { offset: 42 })
+            .await
+            .unwrap();
+        write_data(&mut a, b"stream").await.unwrap();
+
+        match read_frame(&mut b).await.unwrap() {
+            Frame::Control(Message::Accept { offset }) => assert_eq!(offset, 42),
+            other => panic!("unexpected frame {:?}", other),
+        }
+        match read_frame(&mut b).await.unwrap() {
+            Frame::Data(data) => assert_eq!(data, b"stream"),
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+}
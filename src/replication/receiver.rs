@@ -0,0 +1,377 @@
+//! Receiving side: the `op-dbus replica` server and snapshot sinks
+
+use super::protocol::{self, Frame, Message};
+use super::transport::{ReplicaListener, ReplicaStream};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use crate::encoding::to_hex;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tracing::{debug, error, info, warn};
+
+/// Applies a fully received and verified stream
+#[async_trait]
+pub trait StreamSink: Send + Sync {
+    /// Whether the snapshot already exists on this replica
+    async fn has_snapshot(&self, name: &str) -> bool;
+
+    /// Apply the spooled stream for `snapshot`
+    async fn receive(&self, stream_file: &Path, snapshot: &str) -> Result<()>;
+
+    /// Whether streams in `format` can be applied
+    fn accepts(&self, format: &str) -> bool {
+        format == protocol::FORMAT_BTRFS_SEND
+    }
+}
+
+/// Applies streams with `btrfs receive -f <stream> <target_dir>`
+pub struct BtrfsReceiveSink {
+    target_dir: PathBuf,
+}
+
+impl BtrfsReceiveSink {
+    pub fn new(target_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            target_dir: target_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamSink for BtrfsReceiveSink {
+    async fn has_snapshot(&self, name: &str) -> bool {
+        self.target_dir.join(name).exists()
+    }
+
+    async fn receive(&self, stream_file: &Path, snapshot: &str) -> Result<()> {
+        tokio::fs::create_dir_all(&self.target_dir).await?;
+
+        let output = Command::new("btrfs")
+            .arg("receive")
+            .arg("-f")
+            .arg(stream_file)
+            .arg(&self.target_dir)
+            .output()
+            .await
+            .context("Failed to execute btrfs receive")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "btrfs receive of {} failed: {}",
+                snapshot,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Extracts tar streams from directory storage backends into `<target_dir>/<snapshot>`
+pub struct TarReceiveSink {
+    target_dir: PathBuf,
+}
+
+impl TarReceiveSink {
+    pub fn new(target_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            target_dir: target_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamSink for TarReceiveSink {
+    async fn has_snapshot(&self, name: &str) -> bool {
+        self.target_dir.join(name).exists()
+    }
+
+    async fn receive(&self, stream_file: &Path, snapshot: &str) -> Result<()> {
+        let partial = self.target_dir.join(format!(".{}.partial", snapshot));
+        if partial.exists() {
+            tokio::fs::remove_dir_all(&partial).await?;
+        }
+        tokio::fs::create_dir_all(&partial).await?;
+
+        let output = Command::new("tar")
+            .args(["--extract", "--no-same-owner", "--file"])
+            .arg(stream_file)
+            .arg("--directory")
+            .arg(&partial)
+            .output()
+            .await
+            .context("Failed to execute tar")?;
+
+        if !output.status.success() {
+            let _ = tokio::fs::remove_dir_all(&partial).await;
+            anyhow::bail!(
+                "Extracting {} failed: {}",
+                snapshot,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        tokio::fs::rename(&partial, self.target_dir.join(snapshot)).await?;
+        Ok(())
+    }
+
+    fn accepts(&self, format: &str) -> bool {
+        format == protocol::FORMAT_TAR
+    }
+}
+
+/// Stores verified streams as files in a directory instead of applying them
+///
+/// Useful on replicas without BTRFS: streams can later be applied with
+/// `btrfs receive -f`.
+pub struct DirectorySink {
+    target_dir: PathBuf,
+}
+
+impl DirectorySink {
+    pub fn new(target_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            target_dir: target_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamSink for DirectorySink {
+    async fn has_snapshot(&self, name: &str) -> bool {
+        self.target_dir.join(name).exists()
+    }
+
+    async fn receive(&self, stream_file: &Path, snapshot: &str) -> Result<()> {
+        tokio::fs::create_dir_all(&self.target_dir).await?;
+        tokio::fs::rename(stream_file, self.target_dir.join(snapshot)).await?;
+        Ok(())
+    }
+
+    fn accepts(&self, _format: &str) -> bool {
+        true
+    }
+}
+
+/// Replica receiver: authenticates senders, spools streams and applies them
+pub struct ReplicaReceiver {
+    spool_dir: PathBuf,
+    sink: Arc<dyn StreamSink>,
+    token: Vec<u8>,
+}
+
+impl ReplicaReceiver {
+    pub fn new(spool_dir: impl Into<PathBuf>, sink: Arc<dyn StreamSink>, token: Vec<u8>) -> Self {
+        Self {
+            spool_dir: spool_dir.into(),
+            sink,
+            token,
+        }
+    }
+
+    /// Accept connections forever, handling each on its own task
+    pub async fn serve(self: Arc<Self>, listener: ReplicaListener) -> Result<()> {
+        tokio::fs::create_dir_all(&self.spool_dir).await?;
+
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("Replica accept failed: {:#}", e);
+                    continue;
+                }
+            };
+
+            let receiver = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = receiver.handle_connection(stream).await {
+                    warn!(
+                        "Replication session from {} ended with error: {:#}",
+                        peer, e
+                    );
+                }
+            });
+        }
+    }
+
+    /// Run one replication session over `stream`
+    pub async fn handle_connection<S: ReplicaStream>(&self, mut stream: S) -> Result<()> {
+        let node = protocol::handshake_server(&mut stream, &self.token).await?;
+        debug!("Replication session authenticated for node {}", node);
+
+        loop {
+            let frame = match protocol::read_frame(&mut stream).await {
+                Ok(frame) => frame,
+                // Sender closed the session between offers
+                Err(_) => return Ok(()),
+            };
+
+            match frame {
+                Frame::Control(Message::Offer {
+                    snapshot,
+                    parent,
+                    stream_id,
+                    format,
+                }) => {
+                    if !self.sink.accepts(&format) {
+                        reject(
+                            &mut stream,
+                            format!("Unsupported stream format '{}' for {}", format, snapshot),
+                        )
+                        .await?;
+                        continue;
+                    }
+                    self.handle_offer(&mut stream, &node, &snapshot, parent.as_deref(), &stream_id)
+                        .await?
+                }
+                Frame::Control(other) => anyhow::bail!("Expected offer, got {:?}", other),
+                Frame::Data(_) => anyhow::bail!("Unexpected data frame"),
+            }
+        }
+    }
+
+    async fn handle_offer<S: ReplicaStream>(
+        &self,
+        stream: &mut S,
+        node: &str,
+        snapshot: &str,
+        parent: Option<&str>,
+        stream_id: &str,
+    ) -> Result<()> {
+        if let Some(bad) = std::iter::once(snapshot)
+            .chain(parent)
+            .find(|n| !valid_name(n))
+        {
+            return reject(stream, format!("Invalid snapshot name '{}'", bad)).await;
+        }
+        if stream_id != protocol::stream_id(snapshot, parent) {
+            return reject(stream, "Stream id does not match offer".to_string()).await;
+        }
+
+        if self.sink.has_snapshot(snapshot).await {
+            protocol::write_message(
+                stream,
+                &Message::AlreadyHave {
+                    snapshot: snapshot.to_string(),
+                },
+            )
+            .await?;
+            return Ok(());
+        }
+
+        if let Some(parent) = parent {
+            if !self.sink.has_snapshot(parent).await {
+                protocol::write_message(
+                    stream,
+                    &Message::MissingParent {
+                        parent: parent.to_string(),
+                    },
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+
+        let spool = self.spool_dir.join(format!("{}.part", stream_id));
+        let offset = match tokio::fs::metadata(&spool).await {
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        };
+        if offset > 0 {
+            info!("Resuming {} from {} at byte {}", snapshot, node, offset);
+        }
+        protocol::write_message(stream, &Message::Accept { offset }).await?;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&spool)
+            .await
+            .with_context(|| format!("Failed to open spool {}", spool.display()))?;
+
+        // A dropped connection leaves the spool in place so the sender can resume
+        let (total_bytes, sha256) = loop {
+            let frame = match protocol::read_frame(stream).await {
+                Ok(frame) => frame,
+                Err(e) => {
+                    file.flush().await?;
+                    return Err(e.context(format!("Transfer of {} interrupted", snapshot)));
+                }
+            };
+            match frame {
+                Frame::Data(data) => file.write_all(&data).await?,
+                Frame::Control(Message::End {
+                    total_bytes,
+                    sha256,
+                }) => break (total_bytes, sha256),
+                Frame::Control(Message::Error { message }) => {
+                    drop(file);
+                    let _ = tokio::fs::remove_file(&spool).await;
+                    anyhow::bail!("Sender aborted {}: {}", snapshot, message);
+                }
+                Frame::Control(other) => anyhow::bail!("Unexpected message {:?}", other),
+            }
+        };
+        file.sync_all().await?;
+        drop(file);
+
+        let (len, digest) = hash_file(&spool).await?;
+        if len != total_bytes || digest != sha256 {
+            let _ = tokio::fs::remove_file(&spool).await;
+            return reject(
+                stream,
+                format!(
+                    "Verification of {} failed: got {} bytes/{}, expected {} bytes/{}",
+                    snapshot, len, digest, total_bytes, sha256
+                ),
+            )
+            .await;
+        }
+
+        if let Err(e) = self.sink.receive(&spool, snapshot).await {
+            let _ = tokio::fs::remove_file(&spool).await;
+            error!("Failed to apply {}: {:#}", snapshot, e);
+            return reject(stream, format!("{:#}", e)).await;
+        }
+        let _ = tokio::fs::remove_file(&spool).await;
+
+        info!(
+            "Received {} from {} ({} bytes)",
+            snapshot, node, total_bytes
+        );
+        protocol::write_message(
+            stream,
+            &Message::Ack {
+                snapshot: snapshot.to_string(),
+                sha256,
+            },
+        )
+        .await
+    }
+}
+
+async fn reject<S: ReplicaStream>(stream: &mut S, message: String) -> Result<()> {
+    warn!("{}", message);
+    protocol::write_message(stream, &Message::Error { message }).await
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/') && !name.contains('\0')
+}
+
+async fn hash_file(path: &Path) -> Result<(u64, String)> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; protocol::CHUNK_SIZE];
+    let mut len = 0u64;
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        len += n as u64;
+    }
+    Ok((len, to_hex(&hasher.finalize())))
+}
@@ -0,0 +1,418 @@
+//! Sending side: snapshot stream sources and the replication client
+
+use super::protocol::{self, Message};
+use super::tracker::{AckedSnapshot, ReplicaTracker};
+use super::transport::ReplicaEndpoint;
+use crate::storage::snapshot_family;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use crate::encoding::to_hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, Duration};
+use tokio_rustls::TlsConnector;
+use tracing::{debug, info, warn};
+
+/// Produces the byte stream for a snapshot, optionally relative to a parent
+#[async_trait]
+pub trait StreamSource: Send + Sync {
+    async fn open(&self, snapshot: &Path, parent: Option<&Path>) -> Result<SourceStream>;
+
+    /// Stream format announced to the replica
+    fn format(&self) -> &'static str {
+        protocol::FORMAT_BTRFS_SEND
+    }
+
+    /// Whether streams can be produced relative to a parent snapshot
+    fn supports_incremental(&self) -> bool {
+        true
+    }
+}
+
+/// An open snapshot stream; `finish` reports whether the producer succeeded
+pub struct SourceStream {
+    reader: Box<dyn AsyncRead + Unpin + Send>,
+    child: Option<(&'static str, Child)>,
+}
+
+impl SourceStream {
+    pub fn from_reader(reader: impl AsyncRead + Unpin + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            child: None,
+        }
+    }
+
+    /// Stream the stdout of a spawned producer process
+    fn from_child(program: &'static str, mut child: Child) -> Result<Self> {
+        let stdout = child
+            .stdout
+            .take()
+            .with_context(|| format!("{} has no stdout", program))?;
+        Ok(Self {
+            reader: Box::new(stdout),
+            child: Some((program, child)),
+        })
+    }
+
+    /// Wait for the producing process and fail if it did not exit cleanly
+    pub async fn finish(self) -> Result<()> {
+        drop(self.reader);
+        let Some((program, child)) = self.child else {
+            return Ok(());
+        };
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed ({}): {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Streams snapshots with `btrfs send [-p parent] <snapshot>`
+pub struct BtrfsSendSource;
+
+#[async_trait]
+impl StreamSource for BtrfsSendSource {
+    async fn open(&self, snapshot: &Path, parent: Option<&Path>) -> Result<SourceStream> {
+        let mut cmd = Command::new("btrfs");
+        cmd.arg("send");
+        if let Some(parent) = parent {
+            cmd.arg("-p").arg(parent);
+        }
+        cmd.arg(snapshot)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let child = cmd.spawn().context("Failed to execute btrfs send")?;
+        SourceStream::from_child("btrfs send", child)
+    }
+}
+
+/// Streams directory snapshots as tar archives (always full streams)
+pub struct TarSource;
+
+#[async_trait]
+impl StreamSource for TarSource {
+    async fn open(&self, snapshot: &Path, _parent: Option<&Path>) -> Result<SourceStream> {
+        let mut cmd = Command::new("tar");
+        cmd.args(["--create", "--file=-", "--directory"])
+            .arg(snapshot)
+            .arg(".")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let child = cmd.spawn().context("Failed to execute tar")?;
+        SourceStream::from_child("tar", child)
+    }
+
+    fn format(&self) -> &'static str {
+        protocol::FORMAT_TAR
+    }
+
+    fn supports_incremental(&self) -> bool {
+        false
+    }
+}
+
+/// Streams a pre-generated send stream file (e.g. from `btrfs send -f`)
+pub struct FileSource;
+
+#[async_trait]
+impl StreamSource for FileSource {
+    async fn open(&self, snapshot: &Path, _parent: Option<&Path>) -> Result<SourceStream> {
+        let file = tokio::fs::File::open(snapshot)
+            .await
+            .with_context(|| format!("Failed to open {}", snapshot.display()))?;
+        Ok(SourceStream::from_reader(file))
+    }
+}
+
+/// Outcome of replicating one snapshot to one replica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationReport {
+    pub replica: String,
+    pub snapshot: String,
+    pub parent: Option<String>,
+    pub bytes_sent: u64,
+    pub resumed_from: u64,
+    pub sha256: String,
+    pub already_present: bool,
+}
+
+/// Raised when the replica lacks the parent of an incremental stream
+#[derive(Debug, thiserror::Error)]
+#[error("Replica is missing parent snapshot {0}")]
+struct MissingParent(String);
+
+pub struct ReplicationSender {
+    node: String,
+    token: Vec<u8>,
+    source: Arc<dyn StreamSource>,
+    tracker: Arc<ReplicaTracker>,
+    tls: Option<TlsConnector>,
+    max_attempts: u32,
+}
+
+impl ReplicationSender {
+    pub fn new(
+        source: Arc<dyn StreamSource>,
+        tracker: Arc<ReplicaTracker>,
+        token: Vec<u8>,
+    ) -> Self {
+        Self {
+            node: gethostname::gethostname().to_string_lossy().to_string(),
+            token,
+            source,
+            tracker,
+            tls: None,
+            max_attempts: 3,
+        }
+    }
+
+    /// Create a sender using the token and CA configured in the environment
+    pub fn from_env(source: Arc<dyn StreamSource>, tracker: Arc<ReplicaTracker>) -> Result<Self> {
+        let mut sender = Self::new(source, tracker, super::load_token()?);
+        if let Ok(ca) = std::env::var("OPDBUS_REPLICA_CA") {
+            sender = sender.with_tls(super::transport::tls_connector(Path::new(&ca))?);
+        }
+        Ok(sender)
+    }
+
+    pub fn with_tls(mut self, connector: TlsConnector) -> Self {
+        self.tls = Some(connector);
+        self
+    }
+
+    pub fn with_node(mut self, node: impl Into<String>) -> Self {
+        self.node = node.into();
+        self
+    }
+
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn tracker(&self) -> &Arc<ReplicaTracker> {
+        &self.tracker
+    }
+
+    /// Replicate `snapshot` to `endpoint`
+    ///
+    /// Uses the newest snapshot of the same family already acknowledged by the
+    /// replica as parent, falls back to a full send if the replica lost it, and
+    /// resumes interrupted transfers from the replica's spooled offset.
+    pub async fn replicate(
+        &self,
+        endpoint: &ReplicaEndpoint,
+        snapshot: &Path,
+    ) -> Result<ReplicationReport> {
+        let replica = endpoint.to_string();
+        let name = snapshot_name(snapshot)?;
+        let mut parent = self.select_parent(&replica, snapshot, &name);
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            match self
+                .attempt(endpoint, snapshot, &name, parent.as_deref())
+                .await
+            {
+                Ok(report) => {
+                    if !report.already_present || !self.tracker.is_acked(&replica, &name) {
+                        self.tracker.record_ack(
+                            &replica,
+                            AckedSnapshot {
+                                snapshot: name.clone(),
+                                parent: report.parent.clone(),
+                                sha256: report.sha256.clone(),
+                                bytes: report.bytes_sent + report.resumed_from,
+                                acked_at: chrono::Utc::now().timestamp(),
+                            },
+                        )?;
+                    }
+                    info!(
+                        "Replicated {} to {} ({} bytes, parent: {:?})",
+                        name, replica, report.bytes_sent, report.parent
+                    );
+                    return Ok(report);
+                }
+                Err(e) if e.downcast_ref::<MissingParent>().is_some() && parent.is_some() => {
+                    warn!("{}: {}, falling back to full send", replica, e);
+                    parent = None;
+                    attempt -= 1;
+                }
+                Err(e) if attempt < self.max_attempts => {
+                    warn!(
+                        "Replication of {} to {} failed (attempt {}/{}): {:#}",
+                        name, replica, attempt, self.max_attempts, e
+                    );
+                    sleep(Duration::from_millis(500 * 2u64.pow(attempt - 1))).await;
+                }
+                Err(e) => {
+                    let message = format!("{:#}", e);
+                    if let Err(track_err) = self.tracker.record_failure(&replica, &message) {
+                        warn!("Failed to record replication failure: {}", track_err);
+                    }
+                    return Err(e.context(format!("Replication of {} to {} failed", name, replica)));
+                }
+            }
+        }
+    }
+
+    fn select_parent(&self, replica: &str, snapshot: &Path, name: &str) -> Option<String> {
+        if !self.source.supports_incremental() {
+            return None;
+        }
+        let dir = snapshot.parent()?;
+        let family = snapshot_family(name);
+        self.tracker.latest_acked(replica, |candidate| {
+            candidate != name
+                && snapshot_family(candidate) == family
+                && dir.join(candidate).exists()
+        })
+    }
+
+    async fn attempt(
+        &self,
+        endpoint: &ReplicaEndpoint,
+        snapshot: &Path,
+        name: &str,
+        parent: Option<&str>,
+    ) -> Result<ReplicationReport> {
+        let mut conn = endpoint.connect(self.tls.as_ref()).await?;
+        protocol::handshake_client(&mut conn, &self.node, &self.token).await?;
+
+        protocol::write_message(
+            &mut conn,
+            &Message::Offer {
+                snapshot: name.to_string(),
+                parent: parent.map(str::to_string),
+                stream_id: protocol::stream_id(name, parent),
+                format: self.source.format().to_string(),
+            },
+        )
+        .await?;
+
+        let offset = match protocol::read_message(&mut conn).await? {
+            Message::Accept { offset } => offset,
+            Message::AlreadyHave { .. } => {
+                debug!("{} already present on {}", name, endpoint);
+                return Ok(ReplicationReport {
+                    replica: endpoint.to_string(),
+                    snapshot: name.to_string(),
+                    parent: parent.map(str::to_string),
+                    bytes_sent: 0,
+                    resumed_from: 0,
+                    sha256: String::new(),
+                    already_present: true,
+                });
+            }
+            Message::MissingParent { parent } => return Err(MissingParent(parent).into()),
+            other => anyhow::bail!("Unexpected reply to offer: {:?}", other),
+        };
+
+        if offset > 0 {
+            info!("Resuming {} to {} at byte {}", name, endpoint, offset);
+        }
+
+        let parent_path = parent.and_then(|p| snapshot.parent().map(|dir| dir.join(p)));
+        let mut source = self.source.open(snapshot, parent_path.as_deref()).await?;
+
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; protocol::CHUNK_SIZE];
+        let mut total = 0u64;
+        let mut sent = 0u64;
+
+        loop {
+            let n = source.reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+
+            // Skip the prefix the replica already spooled, but keep hashing it
+            let start = offset.saturating_sub(total).min(n as u64) as usize;
+            if start < n {
+                protocol::write_data(&mut conn, &buf[start..n]).await?;
+                sent += (n - start) as u64;
+            }
+            total += n as u64;
+        }
+
+        if let Err(e) = source.finish().await {
+            let _ = protocol::write_message(
+                &mut conn,
+                &Message::Error {
+                    message: e.to_string(),
+                },
+            )
+            .await;
+            return Err(e);
+        }
+
+        if total < offset {
+            let message = format!(
+                "Stream for {} is {} bytes but replica holds {} bytes",
+                name, total, offset
+            );
+            let _ = protocol::write_message(
+                &mut conn,
+                &Message::Error {
+                    message: message.clone(),
+                },
+            )
+            .await;
+            anyhow::bail!(message);
+        }
+
+        let sha256 = to_hex(&hasher.finalize());
+        protocol::write_message(
+            &mut conn,
+            &Message::End {
+                total_bytes: total,
+                sha256: sha256.clone(),
+            },
+        )
+        .await?;
+
+        match protocol::read_message(&mut conn).await? {
+            Message::Ack {
+                snapshot: acked,
+                sha256: acked_sha,
+            } if acked == name && acked_sha == sha256 => Ok(ReplicationReport {
+                replica: endpoint.to_string(),
+                snapshot: name.to_string(),
+                parent: parent.map(str::to_string),
+                bytes_sent: sent,
+                resumed_from: offset,
+                sha256,
+                already_present: false,
+            }),
+            other => anyhow::bail!("Unexpected acknowledgement: {:?}", other),
+        }
+    }
+}
+
+fn snapshot_name(snapshot: &Path) -> Result<String> {
+    snapshot
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .with_context(|| format!("Invalid snapshot path {}", snapshot.display()))
+}
@@ -0,0 +1,151 @@
+//! Per-replica record of acknowledged snapshots
+//!
+//! Persisted as JSON next to the data being replicated so incremental sends
+//! can pick a parent the replica is known to have.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Maximum number of acknowledged snapshots remembered per replica
+const MAX_ACKED_PER_REPLICA: usize = 64;
+
+/// A snapshot the replica confirmed as received and verified
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AckedSnapshot {
+    pub snapshot: String,
+    pub parent: Option<String>,
+    pub sha256: String,
+    pub bytes: u64,
+    pub acked_at: i64,
+}
+
+/// Replication state for a single replica
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplicaRecord {
+    /// Acknowledged snapshots, oldest first
+    pub acked: Vec<AckedSnapshot>,
+    pub last_attempt: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+pub struct ReplicaTracker {
+    path: PathBuf,
+    records: Mutex<HashMap<String, ReplicaRecord>>,
+}
+
+impl ReplicaTracker {
+    /// Load tracker state from `path`, starting empty if it does not exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let records = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Invalid replica tracker file {}", path.display()))?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path,
+            records: Mutex::new(records),
+        })
+    }
+
+    /// Record a verified acknowledgement from `replica`
+    pub fn record_ack(&self, replica: &str, ack: AckedSnapshot) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.entry(replica.to_string()).or_default();
+        record.acked.retain(|a| a.snapshot != ack.snapshot);
+        record.last_attempt = Some(ack.acked_at);
+        record.last_error = None;
+        record.acked.push(ack);
+        if record.acked.len() > MAX_ACKED_PER_REPLICA {
+            let excess = record.acked.len() - MAX_ACKED_PER_REPLICA;
+            record.acked.drain(..excess);
+        }
+        self.persist(&records)
+    }
+
+    /// Record a failed replication attempt
+    pub fn record_failure(&self, replica: &str, error: &str) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.entry(replica.to_string()).or_default();
+        record.last_attempt = Some(chrono::Utc::now().timestamp());
+        record.last_error = Some(error.to_string());
+        self.persist(&records)
+    }
+
+    /// Whether `replica` acknowledged `snapshot`
+    pub fn is_acked(&self, replica: &str, snapshot: &str) -> bool {
+        self.records
+            .lock()
+            .unwrap()
+            .get(replica)
+            .map(|r| r.acked.iter().any(|a| a.snapshot == snapshot))
+            .unwrap_or(false)
+    }
+
+    /// Most recently acknowledged snapshot for `replica` matching `filter`
+    pub fn latest_acked(&self, replica: &str, filter: impl Fn(&str) -> bool) -> Option<String> {
+        self.records.lock().unwrap().get(replica).and_then(|r| {
+            r.acked
+                .iter()
+                .rev()
+                .find(|a| filter(&a.snapshot))
+                .map(|a| a.snapshot.clone())
+        })
+    }
+
+    /// Snapshot of all replica records
+    pub fn records(&self) -> HashMap<String, ReplicaRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    fn persist(&self, records: &HashMap<String, ReplicaRecord>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write atomically: write to temp file, then rename
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(records)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(name: &str) -> AckedSnapshot {
+        AckedSnapshot {
+            snapshot: name.to_string(),
+            parent: None,
+            sha256: "00".to_string(),
+            bytes: 1,
+            acked_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_tracker_persists_acks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replicas.json");
+
+        let tracker = ReplicaTracker::load(&path).unwrap();
+        tracker.record_ack("tcp://a:1", ack("vectors-1")).unwrap();
+        tracker.record_ack("tcp://a:1", ack("state-1")).unwrap();
+
+        let reloaded = ReplicaTracker::load(&path).unwrap();
+        assert!(reloaded.is_acked("tcp://a:1", "vectors-1"));
+        assert!(!reloaded.is_acked("tcp://b:1", "vectors-1"));
+        assert_eq!(
+            reloaded.latest_acked("tcp://a:1", |s| s.starts_with("vectors-")),
+            Some("vectors-1".to_string())
+        );
+    }
+}
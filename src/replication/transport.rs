@@ -0,0 +1,256 @@
+//! Replica endpoints and socket transports (unix, TCP, TLS)
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Any bidirectional byte stream a replication session can run over
+pub trait ReplicaStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ReplicaStream for T {}
+
+pub type BoxedStream = Box<dyn ReplicaStream>;
+
+/// Address of a replica receiver
+///
+/// Accepted forms: `unix:/run/op-dbus/replica.sock`, `tcp://host:port`,
+/// `tls://host:port` and bare `host:port` (plain TCP).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReplicaEndpoint {
+    Unix(PathBuf),
+    Tcp(String),
+    Tls { addr: String, server_name: String },
+}
+
+impl FromStr for ReplicaEndpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if path.is_empty() {
+                anyhow::bail!("Empty unix socket path in endpoint '{}'", s);
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        if let Some(addr) = s.strip_prefix("tls://") {
+            let server_name = host_part(addr)
+                .with_context(|| format!("Invalid TLS endpoint '{}'", s))?
+                .to_string();
+            return Ok(Self::Tls {
+                addr: addr.to_string(),
+                server_name,
+            });
+        }
+        let addr = s.strip_prefix("tcp://").unwrap_or(s);
+        host_part(addr).with_context(|| format!("Invalid TCP endpoint '{}'", s))?;
+        Ok(Self::Tcp(addr.to_string()))
+    }
+}
+
+impl fmt::Display for ReplicaEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+            Self::Tcp(addr) => write!(f, "tcp://{}", addr),
+            Self::Tls { addr, .. } => write!(f, "tls://{}", addr),
+        }
+    }
+}
+
+fn host_part(addr: &str) -> Option<&str> {
+    let (host, port) = addr.rsplit_once(':')?;
+    port.parse::<u16>().ok()?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    (!host.is_empty()).then_some(host)
+}
+
+impl ReplicaEndpoint {
+    /// Open a connection to this endpoint
+    pub async fn connect(&self, tls: Option<&TlsConnector>) -> Result<BoxedStream> {
+        match self {
+            Self::Unix(path) => {
+                let stream = UnixStream::connect(path)
+                    .await
+                    .with_context(|| format!("Failed to connect to {}", path.display()))?;
+                Ok(Box::new(stream))
+            }
+            Self::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)
+                    .await
+                    .with_context(|| format!("Failed to connect to {}", addr))?;
+                stream.set_nodelay(true)?;
+                Ok(Box::new(stream))
+            }
+            Self::Tls { addr, server_name } => {
+                let connector = tls.context("TLS endpoint requires a CA certificate")?;
+                let stream = TcpStream::connect(addr)
+                    .await
+                    .with_context(|| format!("Failed to connect to {}", addr))?;
+                stream.set_nodelay(true)?;
+                let name = rustls::ServerName::try_from(server_name.as_str())
+                    .with_context(|| format!("Invalid TLS server name '{}'", server_name))?;
+                let stream = connector
+                    .connect(name, stream)
+                    .await
+                    .with_context(|| format!("TLS handshake with {} failed", addr))?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+/// Listening socket for a replica receiver
+pub enum ReplicaListener {
+    Unix(UnixListener),
+    Tcp(TcpListener, Option<TlsAcceptor>),
+}
+
+impl ReplicaListener {
+    /// Bind a listener for `endpoint`; TLS endpoints require an acceptor
+    pub async fn bind(endpoint: &ReplicaEndpoint, tls: Option<TlsAcceptor>) -> Result<Self> {
+        match endpoint {
+            ReplicaEndpoint::Unix(path) => {
+                if path.exists() {
+                    tokio::fs::remove_file(path).await?;
+                }
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let listener = UnixListener::bind(path)
+                    .with_context(|| format!("Failed to bind {}", path.display()))?;
+                Ok(Self::Unix(listener))
+            }
+            ReplicaEndpoint::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Failed to bind {}", addr))?;
+                Ok(Self::Tcp(listener, None))
+            }
+            ReplicaEndpoint::Tls { addr, .. } => {
+                let acceptor = tls.context("TLS endpoint requires a certificate and key")?;
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Failed to bind {}", addr))?;
+                Ok(Self::Tcp(listener, Some(acceptor)))
+            }
+        }
+    }
+
+    /// Accept the next connection, returning the stream and a peer description
+    pub async fn accept(&self) -> Result<(BoxedStream, String)> {
+        match self {
+            Self::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Box::new(stream), "unix".to_string()))
+            }
+            Self::Tcp(listener, acceptor) => {
+                let (stream, peer) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                match acceptor {
+                    Some(acceptor) => {
+                        let stream = acceptor
+                            .accept(stream)
+                            .await
+                            .with_context(|| format!("TLS handshake with {} failed", peer))?;
+                        Ok((Box::new(stream), peer.to_string()))
+                    }
+                    None => Ok((Box::new(stream), peer.to_string())),
+                }
+            }
+        }
+    }
+}
+
+/// Build a TLS connector trusting the CA certificates in `ca_path` (PEM)
+pub fn tls_connector(ca_path: &Path) -> Result<TlsConnector> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in read_pem_certs(ca_path)? {
+        roots
+            .add(&cert)
+            .with_context(|| format!("Invalid CA certificate in {}", ca_path.display()))?;
+    }
+
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Build a TLS acceptor from a PEM certificate chain and private key
+pub fn tls_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    let certs = read_pem_certs(cert_path)?;
+    let key = read_pem_key(key_path)?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("Invalid TLS certificate/key pair")?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn read_pem_certs(path: &Path) -> Result<Vec<rustls::Certificate>> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut data.as_slice())
+        .with_context(|| format!("Invalid PEM in {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path.display());
+    }
+    Ok(certs.into_iter().map(rustls::Certificate).collect())
+}
+
+fn read_pem_key(path: &Path) -> Result<rustls::PrivateKey> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut reader = data.as_slice();
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)
+        .with_context(|| format!("Invalid PEM in {}", path.display()))?
+    {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(rustls::PrivateKey(key)),
+            _ => continue,
+        }
+    }
+    anyhow::bail!("No private key found in {}", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_parsing() {
+        assert_eq!(
+            "unix:/run/op-dbus/replica.sock"
+                .parse::<ReplicaEndpoint>()
+                .unwrap(),
+            ReplicaEndpoint::Unix(PathBuf::from("/run/op-dbus/replica.sock"))
+        );
+        assert_eq!(
+            "tcp://10.0.0.2:9574".parse::<ReplicaEndpoint>().unwrap(),
+            ReplicaEndpoint::Tcp("10.0.0.2:9574".to_string())
+        );
+        assert_eq!(
+            "replica.local:9574".parse::<ReplicaEndpoint>().unwrap(),
+            ReplicaEndpoint::Tcp("replica.local:9574".to_string())
+        );
+        assert_eq!(
+            "tls://replica.local:9574"
+                .parse::<ReplicaEndpoint>()
+                .unwrap(),
+            ReplicaEndpoint::Tls {
+                addr: "replica.local:9574".to_string(),
+                server_name: "replica.local".to_string(),
+            }
+        );
+        assert!("replica.local".parse::<ReplicaEndpoint>().is_err());
+        assert!("unix:".parse::<ReplicaEndpoint>().is_err());
+    }
+}
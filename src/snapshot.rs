@@ -1,15 +1,18 @@
-//! BTRFS Snapshot Management
+//! Snapshot Management
 //!
-//! Provides rolling snapshot retention policies for BTRFS subvolumes.
+//! Provides rolling snapshot retention policies for BTRFS subvolumes, or for
+//! plain directories when the storage backend is not BTRFS.
 //! Supports multiple retention strategies:
 //! - Rolling N: Keep last N snapshots, delete older ones
 //! - Time-based: Keep snapshots from last N days/hours
 //! - Tagged: Keep specific snapshots forever (golden masters)
 
+use crate::storage::{self, SnapshotBackend};
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 
 /// Snapshot retention policy
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,19 +44,17 @@ pub struct SnapshotInfo {
     pub size_bytes: u64,
 }
 
-/// BTRFS snapshot manager
+/// Snapshot manager with retention policies
 pub struct SnapshotManager {
     snapshots_dir: PathBuf,
     policy: RetentionPolicy,
+    backend: Arc<dyn SnapshotBackend>,
 }
 
 impl SnapshotManager {
     /// Create new snapshot manager
     pub fn new(snapshots_dir: impl AsRef<Path>) -> Self {
-        Self {
-            snapshots_dir: snapshots_dir.as_ref().to_path_buf(),
-            policy: RetentionPolicy::default(),
-        }
+        Self::with_policy(snapshots_dir, RetentionPolicy::default())
     }
 
     /// Create with custom retention policy
@@ -61,11 +62,22 @@ impl SnapshotManager {
         Self {
             snapshots_dir: snapshots_dir.as_ref().to_path_buf(),
             policy,
+            backend: storage::detect(snapshots_dir.as_ref()),
         }
     }
 
+    /// Use an explicit storage backend
+    pub fn with_backend(mut self, backend: Arc<dyn SnapshotBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
     /// Create a new snapshot with rolling retention
-    pub fn create_snapshot(&self, source: impl AsRef<Path>, name: Option<&str>) -> Result<PathBuf> {
+    pub async fn create_snapshot(
+        &self,
+        source: impl AsRef<Path>,
+        name: Option<&str>,
+    ) -> Result<PathBuf> {
         let source = source.as_ref();
 
         // Ensure snapshots directory exists
@@ -81,47 +93,35 @@ impl SnapshotManager {
 
         let snapshot_path = self.snapshots_dir.join(&snapshot_name);
 
-        // Create BTRFS snapshot
+        // Create read-only snapshot
         log::info!("📸 Creating snapshot: {}", snapshot_name);
-        let output = Command::new("btrfs")
-            .args(&[
-                "subvolume",
-                "snapshot",
-                "-r", // read-only
-                source.to_str().unwrap(),
-                snapshot_path.to_str().unwrap(),
-            ])
-            .output()
-            .context("Failed to execute btrfs snapshot command")?;
-
-        if !output.status.success() {
-            bail!(
-                "Failed to create snapshot: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
+        self.backend
+            .snapshot(source, &snapshot_path)
+            .await
+            .context("Failed to create snapshot")?;
 
         log::info!("   Created: {}", snapshot_path.display());
 
         // Apply retention policy
-        self.apply_retention_policy()?;
+        self.apply_retention_policy().await?;
 
         Ok(snapshot_path)
     }
 
     /// Apply retention policy (delete old snapshots)
-    pub fn apply_retention_policy(&self) -> Result<()> {
-        let snapshots = self.list_snapshots()?;
+    pub async fn apply_retention_policy(&self) -> Result<()> {
+        let snapshots = self.list_snapshots().await?;
 
         match &self.policy {
             RetentionPolicy::Rolling { keep } => {
-                self.apply_rolling_retention(&snapshots, *keep)?;
+                self.apply_rolling_retention(&snapshots, *keep).await?;
             }
             RetentionPolicy::TimeBased { days } => {
-                self.apply_time_based_retention(&snapshots, *days)?;
+                self.apply_time_based_retention(&snapshots, *days).await?;
             }
             RetentionPolicy::Tagged { keep_untagged } => {
-                self.apply_tagged_retention(&snapshots, *keep_untagged)?;
+                self.apply_tagged_retention(&snapshots, *keep_untagged)
+                    .await?;
             }
         }
 
@@ -129,7 +129,7 @@ impl SnapshotManager {
     }
 
     /// Apply rolling N retention (keep last N snapshots)
-    fn apply_rolling_retention(&self, snapshots: &[SnapshotInfo], keep: usize) -> Result<()> {
+    async fn apply_rolling_retention(&self, snapshots: &[SnapshotInfo], keep: usize) -> Result<()> {
         if snapshots.len() <= keep {
             return Ok(()); // Nothing to delete
         }
@@ -142,7 +142,7 @@ impl SnapshotManager {
         let to_delete = sorted.len() - keep;
         for snapshot in sorted.iter().take(to_delete) {
             log::info!("🗑️  Deleting old snapshot: {}", snapshot.name);
-            self.delete_snapshot(&snapshot.path)?;
+            self.delete_snapshot(&snapshot.path).await?;
         }
 
         if to_delete > 0 {
@@ -153,13 +153,17 @@ impl SnapshotManager {
     }
 
     /// Apply time-based retention
-    fn apply_time_based_retention(&self, snapshots: &[SnapshotInfo], days: usize) -> Result<()> {
+    async fn apply_time_based_retention(
+        &self,
+        snapshots: &[SnapshotInfo],
+        days: usize,
+    ) -> Result<()> {
         let cutoff = chrono::Utc::now().timestamp() - (days as i64 * 86400);
 
         for snapshot in snapshots {
             if snapshot.created < cutoff {
                 log::info!("🗑️  Deleting expired snapshot: {}", snapshot.name);
-                self.delete_snapshot(&snapshot.path)?;
+                self.delete_snapshot(&snapshot.path).await?;
             }
         }
 
@@ -167,7 +171,7 @@ impl SnapshotManager {
     }
 
     /// Apply tagged retention (keep all tagged, rolling for untagged)
-    fn apply_tagged_retention(
+    async fn apply_tagged_retention(
         &self,
         snapshots: &[SnapshotInfo],
         keep_untagged: usize,
@@ -179,7 +183,7 @@ impl SnapshotManager {
             let to_delete = untagged.len() - keep_untagged;
             for snapshot in untagged.iter().take(to_delete) {
                 log::info!("🗑️  Deleting old untagged snapshot: {}", snapshot.name);
-                self.delete_snapshot(&snapshot.path)?;
+                self.delete_snapshot(&snapshot.path).await?;
             }
         }
 
@@ -187,24 +191,15 @@ impl SnapshotManager {
     }
 
     /// Delete a snapshot
-    pub fn delete_snapshot(&self, snapshot_path: &Path) -> Result<()> {
-        let output = Command::new("btrfs")
-            .args(&["subvolume", "delete", snapshot_path.to_str().unwrap()])
-            .output()
-            .context("Failed to execute btrfs delete command")?;
-
-        if !output.status.success() {
-            bail!(
-                "Failed to delete snapshot: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-
-        Ok(())
+    pub async fn delete_snapshot(&self, snapshot_path: &Path) -> Result<()> {
+        self.backend
+            .delete(snapshot_path)
+            .await
+            .context("Failed to delete snapshot")
     }
 
     /// List all snapshots
-    pub fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>> {
+    pub async fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>> {
         if !self.snapshots_dir.exists() {
             return Ok(Vec::new());
         }
@@ -215,17 +210,14 @@ impl SnapshotManager {
             let entry = entry?;
             let path = entry.path();
 
-            if !path.is_dir() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !path.is_dir() || name.starts_with('.') {
                 continue;
             }
 
-            // Check if it's a BTRFS subvolume
-            let output = Command::new("btrfs")
-                .args(&["subvolume", "show", path.to_str().unwrap()])
-                .output()?;
-
-            if !output.status.success() {
-                continue; // Not a subvolume
+            // Only count volumes the backend manages (subvolumes on BTRFS)
+            if !self.backend.is_volume(&path).await {
+                continue;
             }
 
             // Get creation time and size
@@ -246,7 +238,7 @@ impl SnapshotManager {
             };
 
             snapshots.push(SnapshotInfo {
-                name: path.file_name().unwrap().to_string_lossy().to_string(),
+                name,
                 path,
                 created,
                 tagged,
@@ -273,13 +265,13 @@ impl SnapshotManager {
     }
 
     /// Get total size of all snapshots
-    pub fn total_size(&self) -> Result<u64> {
+    pub async fn total_size(&self) -> Result<u64> {
         // TODO: Use `btrfs qgroup show` for accurate size
         let mut total = 0u64;
-        for snapshot in self.list_snapshots()? {
+        for snapshot in self.list_snapshots().await? {
             // Rough estimate using du
             let output = Command::new("du")
-                .args(&["-sb", snapshot.path.to_str().unwrap()])
+                .args(["-sb", snapshot.path.to_str().unwrap()])
                 .output()?;
 
             if output.status.success() {
@@ -293,13 +285,13 @@ impl SnapshotManager {
     }
 
     /// Clean up all snapshots (dangerous!)
-    pub fn delete_all(&self) -> Result<usize> {
-        let snapshots = self.list_snapshots()?;
+    pub async fn delete_all(&self) -> Result<usize> {
+        let snapshots = self.list_snapshots().await?;
         let count = snapshots.len();
 
         for snapshot in snapshots {
             if !snapshot.tagged {
-                self.delete_snapshot(&snapshot.path)?;
+                self.delete_snapshot(&snapshot.path).await?;
             }
         }
 
@@ -392,4 +384,34 @@ mod tests {
         assert_eq!(sorted[0].name, "2025-01-01");
         assert_eq!(sorted[2].name, "2025-01-03");
     }
+
+    #[tokio::test]
+    async fn test_tagged_retention_on_directory_backend() {
+        use crate::storage::{CopyMode, DirectoryBackend};
+
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("@dbus-index");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("index.json"), b"{}").unwrap();
+
+        let manager = SnapshotManager::with_policy(
+            temp.path().join("snapshots"),
+            RetentionPolicy::Tagged { keep_untagged: 1 },
+        )
+        .with_backend(Arc::new(DirectoryBackend::with_mode(CopyMode::Hardlink)));
+
+        manager
+            .create_snapshot(&source, Some("golden"))
+            .await
+            .unwrap();
+        manager.tag_snapshot("golden", "release").unwrap();
+        manager.create_snapshot(&source, Some("a")).await.unwrap();
+        manager.create_snapshot(&source, Some("b")).await.unwrap();
+
+        let snapshots = manager.list_snapshots().await.unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots
+            .iter()
+            .any(|s| s.name == "golden" && s.tag.as_deref() == Some("release")));
+    }
 }
@@ -12,20 +12,20 @@ impl NetworkAuthority {
     pub fn enforce_authority() -> Result<()> {
         // Disable NetworkManager if running
         let _ = Command::new("systemctl")
-            .args(&["stop", "NetworkManager"])
+            .args(["stop", "NetworkManager"])
             .output();
 
         let _ = Command::new("systemctl")
-            .args(&["disable", "NetworkManager"])
+            .args(["disable", "NetworkManager"])
             .output();
 
         // Disable systemd-networkd if running
         let _ = Command::new("systemctl")
-            .args(&["stop", "systemd-networkd"])
+            .args(["stop", "systemd-networkd"])
             .output();
 
         let _ = Command::new("systemctl")
-            .args(&["disable", "systemd-networkd"])
+            .args(["disable", "systemd-networkd"])
             .output();
 
         log::info!("Network authority enforced - plugin system is sole controller");
@@ -38,7 +38,7 @@ impl NetworkAuthority {
 
         // Check if NetworkManager is active
         if let Ok(output) = Command::new("systemctl")
-            .args(&["is-active", "NetworkManager"])
+            .args(["is-active", "NetworkManager"])
             .output()
         {
             if output.stdout == b"active\n" {
@@ -48,7 +48,7 @@ impl NetworkAuthority {
 
         // Check if systemd-networkd is active
         if let Ok(output) = Command::new("systemctl")
-            .args(&["is-active", "systemd-networkd"])
+            .args(["is-active", "systemd-networkd"])
             .output()
         {
             if output.stdout == b"active\n" {
@@ -1,7 +1,6 @@
 // Auto-generated D-Bus plugin system
 // Discovers D-Bus services and creates plugins dynamically
 
-// use op_dbus::mcp::introspection_parser;
 use crate::state::plugin::{
     ApplyResult, Checkpoint, PluginCapabilities, StateAction, StateDiff, StatePlugin,
 };
@@ -25,7 +24,7 @@ pub struct AutoGeneratedPlugin {
     base_path: String,
 
     /// Parsed introspection data (interfaces, methods, properties)
-    introspection: crate::mcp::bridge::introspection_parser::IntrospectionData,
+    introspection: crate::mcp::introspection_parser::IntrospectionData,
 
     /// Cached connection (reused for performance)
     connection: Option<Connection>,
@@ -59,7 +58,7 @@ impl AutoGeneratedPlugin {
     /// Convert D-Bus service name to plugin name
     /// org.freedesktop.NetworkManager → networkmanager
     fn service_to_plugin_name(service: &str) -> String {
-        service.split('.').last().unwrap_or(service).to_lowercase()
+        service.split('.').next_back().unwrap_or(service).to_lowercase()
     }
 
     /// Introspect a D-Bus service and parse its schema
@@ -67,7 +66,7 @@ impl AutoGeneratedPlugin {
         conn: &Connection,
         service_name: &str,
         path: &str,
-    ) -> Result<crate::mcp::bridge::introspection_parser::IntrospectionData> {
+    ) -> Result<crate::mcp::introspection_parser::IntrospectionData> {
         // Create proxy for introspection
         let proxy = Proxy::new(
             conn,
@@ -84,7 +83,7 @@ impl AutoGeneratedPlugin {
             .context("Failed to call Introspect")?;
 
         // Parse XML to structured data
-        Ok(crate::mcp::bridge::introspection_parser::IntrospectionParser::parse_xml(&xml))
+        Ok(crate::mcp::introspection_parser::IntrospectionParser::parse_xml(&xml))
     }
 
     /// Read all properties from all interfaces
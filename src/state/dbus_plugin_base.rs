@@ -3,6 +3,7 @@
 //! Provides common D-Bus operations, hash footprints, and blockchain integration
 
 use crate::blockchain::PluginFootprint;
+use crate::native::dbus_marshal;
 use crate::state::plugin::StatePlugin;
 use anyhow::{Context, Result};
 use serde_json::Value;
@@ -52,28 +53,19 @@ pub trait DbusStatePluginBase: StatePlugin {
             .await
             .context(format!("Failed to get property {}", property))?;
 
-        // Convert zbus::zvariant::Value to serde_json::Value
-        let json_str = format!("{:?}", value); // Simplified - would need proper conversion
-        Ok(serde_json::from_str(&json_str).unwrap_or(Value::Null))
+        dbus_marshal::value_to_json(&value)
     }
 
     /// Set a D-Bus property value
+    ///
+    /// The value is coerced to the type of the property's current value.
     async fn set_property(&self, proxy: &Proxy<'_>, property: &str, value: &Value) -> Result<()> {
-        // Convert serde_json::Value to zbus::zvariant::Value (simplified)
-        let zbus_value: zbus::zvariant::Value = match value {
-            Value::String(s) => s.as_str().into(),
-            Value::Bool(b) => (*b).into(),
-            Value::Number(n) => {
-                if let Some(i) = n.as_i64() {
-                    i.into()
-                } else if let Some(f) = n.as_f64() {
-                    f.into()
-                } else {
-                    return Err(anyhow::anyhow!("Unsupported number type"));
-                }
-            }
-            _ => return Err(anyhow::anyhow!("Unsupported value type for D-Bus property")),
-        };
+        let current: zbus::zvariant::OwnedValue = proxy
+            .get_property(property)
+            .await
+            .context(format!("Failed to get property {}", property))?;
+        let zbus_value = dbus_marshal::json_to_value(value, current.value_signature())
+            .context(format!("Invalid value for property {}", property))?;
 
         proxy
             .set_property(property, zbus_value)
@@ -99,14 +91,10 @@ pub trait DbusStatePluginBase: StatePlugin {
             .await
             .context("Failed to get all properties")?;
 
-        // Convert to serde_json::Value HashMap
-        let mut result = HashMap::new();
-        for (key, _value) in all_props {
-            // Simplified conversion - would need proper zvariant to serde_json conversion
-            result.insert(key, Value::Null);
-        }
-
-        Ok(result)
+        all_props
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), dbus_marshal::value_to_json(value)?)))
+            .collect()
     }
 
     /// Call a D-Bus method (no-arg version - for methods with args, use proxy.call directly)
@@ -225,77 +213,3 @@ pub trait DbusStatePluginBase: StatePlugin {
         Ok(Vec::new())
     }
 }
-
-/// Helper functions for D-Bus value conversion
-pub mod conversion {
-    use serde_json::Value;
-    use zbus::zvariant;
-
-    /// Convert serde_json::Value to zbus::zvariant::Value
-    #[allow(dead_code)]
-    pub fn json_to_zvariant(value: &Value) -> Result<zvariant::Value<'_>, anyhow::Error> {
-        match value {
-            Value::Null => Ok(zvariant::Value::from("")),
-            Value::Bool(b) => Ok(zvariant::Value::from(*b)),
-            Value::Number(n) => {
-                if let Some(i) = n.as_i64() {
-                    Ok(zvariant::Value::from(i))
-                } else if let Some(u) = n.as_u64() {
-                    Ok(zvariant::Value::from(u))
-                } else if let Some(f) = n.as_f64() {
-                    Ok(zvariant::Value::from(f))
-                } else {
-                    Err(anyhow::anyhow!("Unsupported number format"))
-                }
-            }
-            Value::String(s) => Ok(zvariant::Value::from(s.as_str())),
-            Value::Array(_) => Err(anyhow::anyhow!("Array conversion not yet implemented")),
-            Value::Object(_) => Err(anyhow::anyhow!("Object conversion not yet implemented")),
-        }
-    }
-
-    /// Convert zbus::zvariant::Value to serde_json::Value
-    #[allow(dead_code)]
-    pub fn zvariant_to_json(value: &zvariant::Value) -> Result<Value, anyhow::Error> {
-        // Simplified - full implementation would handle all zvariant types
-        match value.value_signature().to_string().as_str() {
-            "s" => {
-                if let Ok(s) = <&str>::try_from(value) {
-                    Ok(Value::String(s.to_string()))
-                } else {
-                    Ok(Value::Null)
-                }
-            }
-            "b" => {
-                if let Ok(b) = bool::try_from(value) {
-                    Ok(Value::Bool(b))
-                } else {
-                    Ok(Value::Null)
-                }
-            }
-            "i" | "u" | "x" | "t" => {
-                // Try various integer types
-                if let Ok(i) = i64::try_from(value) {
-                    Ok(Value::Number(i.into()))
-                } else if let Ok(u) = u64::try_from(value) {
-                    Ok(Value::Number(u.into()))
-                } else {
-                    Ok(Value::Null)
-                }
-            }
-            "d" => {
-                if let Ok(f) = f64::try_from(value) {
-                    Ok(serde_json::Number::from_f64(f)
-                        .map(Value::Number)
-                        .unwrap_or(Value::Null))
-                } else {
-                    Ok(Value::Null)
-                }
-            }
-            _ => {
-                // For complex types, use debug representation
-                Ok(Value::String(format!("{:?}", value)))
-            }
-        }
-    }
-}
@@ -1,32 +1,69 @@
 //! D-Bus server for system bus integration
+//!
+//! `org.opdbus` on the system bus exposes:
+//! - `/org/opdbus/state`: `org.opdbus.StateManager` (JSON string calls) and
+//!   `org.freedesktop.DBus.ObjectManager` for the plugin objects
+//! - `/org/opdbus/state/<plugin>`: `org.opdbus.StatePlugin` with typed
+//!   `a{sv}` state, plus the `StateChanged`, `ApplyProgress` and
+//!   `DriftDetected` signals
+//!
+//! Drift is checked every `OPDBUS_DRIFT_INTERVAL` seconds (default 300,
+//! 0 disables) against the last state applied over D-Bus.
+//!
+//! Mutating methods are authorized through polkit for the calling bus name,
+//! with the `org.opdbus.apply.<plugin>` action of each plugin touched, and
+//! recorded in the audit log with the caller's uid.
 
+use crate::audit::{AuditRecord, Frontend};
+use crate::native::dbus_marshal::{infer_value, value_to_json};
+use crate::native::polkit::{AuthorizationError, PolkitAuthority, Subject};
 use crate::state::{
-    plugin::{StateAction, StateDiff},
+    manager::{ApplyEvent, DesiredState},
+    plugin::{ApplyResult, Checkpoint, StateAction, StateDiff, StatePlugin},
     StateManager,
 };
 use anyhow::Result;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use zbus::{connection::Builder, interface};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::OwnedValue;
+use zbus::{connection::Builder, interface, Connection};
+
+/// Object path of the state manager and parent of the plugin objects
+pub const STATE_PATH: &str = "/org/opdbus/state";
+
+/// Checkpoints kept per plugin object for `Rollback`
+const MAX_CHECKPOINTS: usize = 16;
+
+const DEFAULT_DRIFT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Typed state dictionary (`a{sv}`)
+pub type StateDict = HashMap<String, OwnedValue>;
 
 /// D-Bus interface for the state manager
 pub struct StateManagerDBus {
     state_manager: Arc<StateManager>,
+    authority: PolkitAuthority,
 }
 
 #[interface(name = "org.opdbus.StateManager")]
 impl StateManagerDBus {
     /// Apply state from JSON string
-    async fn apply_state(&self, state_json: String) -> zbus::fdo::Result<String> {
-        match serde_json::from_str(&state_json) {
-            Ok(desired_state) => match self.state_manager.apply_state(desired_state).await {
-                Ok(report) => Ok(format!("Applied successfully: {}", report.success)),
-                Err(e) => Err(zbus::fdo::Error::Failed(format!("Apply failed: {}", e))),
-            },
-            Err(e) => Err(zbus::fdo::Error::InvalidArgs(format!(
-                "Invalid JSON: {}",
-                e
-            ))),
-        }
+    async fn apply_state(
+        &self,
+        state_json: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<String> {
+        let params = serde_json::from_str(&state_json).unwrap_or(Value::Null);
+        let record = audit_record(connection, &header, "apply")
+            .await
+            .with_params(&params);
+        audited(record, self.apply_state_json(&state_json, &header).await)
     }
 
     /// Query current state
@@ -55,9 +92,63 @@ impl StateManagerDBus {
         &self,
         state_file_path: String,
         bridge_name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<String> {
+        let record = audit_record(connection, &header, "restore_flows")
+            .await
+            .with_plugin("openflow")
+            .with_params(&serde_json::json!({
+                "state_file": state_file_path,
+                "bridge": bridge_name,
+            }));
+        let result = self
+            .restore_flows_from(state_file_path, bridge_name, &header)
+            .await;
+        audited(record, result)
+    }
+}
+
+impl StateManagerDBus {
+    async fn apply_state_json(
+        &self,
+        state_json: &str,
+        header: &Header<'_>,
+    ) -> zbus::fdo::Result<String> {
+        match serde_json::from_str::<DesiredState>(state_json) {
+            Ok(desired_state) => {
+                let subject = caller(header)?;
+                for plugin in desired_state.plugins.keys() {
+                    self.authority
+                        .authorize_apply(&subject, plugin)
+                        .await
+                        .map_err(access_denied)?;
+                }
+                match self.state_manager.apply_state(desired_state).await {
+                    Ok(report) => Ok(format!("Applied successfully: {}", report.success)),
+                    Err(e) => Err(zbus::fdo::Error::Failed(format!("Apply failed: {}", e))),
+                }
+            }
+            Err(e) => Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Invalid JSON: {}",
+                e
+            ))),
+        }
+    }
+
+    async fn restore_flows_from(
+        &self,
+        state_file_path: String,
+        bridge_name: String,
+        header: &Header<'_>,
     ) -> zbus::fdo::Result<String> {
         use std::path::PathBuf;
 
+        self.authority
+            .authorize_apply(&caller(header)?, "openflow")
+            .await
+            .map_err(access_denied)?;
+
         // Handle default state file path
         let state_path = if state_file_path.is_empty() {
             PathBuf::from("/etc/op-dbus/state.json")
@@ -185,18 +276,730 @@ impl StateManagerDBus {
     }
 }
 
+/// Object path of a plugin (`/org/opdbus/state/<plugin>`)
+///
+/// Characters not allowed in object paths are replaced with `_`.
+pub fn plugin_object_path(plugin_name: &str) -> String {
+    let element: String = plugin_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}/{}", STATE_PATH, element)
+}
+
+/// Convert a JSON object to `a{sv}`; other values are wrapped as `{"value": ...}`
+pub fn json_to_dict(value: &Value) -> Result<StateDict> {
+    let wrapped;
+    let map = match value {
+        Value::Object(map) => map,
+        other => {
+            wrapped = serde_json::json!({ "value": other });
+            wrapped.as_object().expect("object literal")
+        }
+    };
+
+    map.iter()
+        .map(|(key, value)| {
+            let value = OwnedValue::try_from(infer_value(value)?)?;
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Convert `a{sv}` to a JSON object
+pub fn dict_to_json(dict: &StateDict) -> Result<Value> {
+    let map = dict
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), value_to_json(value)?)))
+        .collect::<Result<serde_json::Map<String, Value>>>()?;
+    Ok(Value::Object(map))
+}
+
+fn failed(e: impl std::fmt::Display) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(e.to_string())
+}
+
+fn invalid_args(e: impl std::fmt::Display) -> zbus::fdo::Error {
+    zbus::fdo::Error::InvalidArgs(e.to_string())
+}
+
+fn access_denied(e: AuthorizationError) -> zbus::fdo::Error {
+    match e {
+        AuthorizationError::Denied { .. } => zbus::fdo::Error::AccessDenied(e.to_string()),
+        AuthorizationError::Unavailable(_) => failed(e),
+    }
+}
+
+/// polkit subject for the sender of a method call
+fn caller(header: &Header<'_>) -> zbus::fdo::Result<Subject> {
+    header
+        .sender()
+        .map(|sender| Subject::BusName(sender.to_string()))
+        .ok_or_else(|| zbus::fdo::Error::AccessDenied("Caller has no bus name".to_string()))
+}
+
+/// Audit record for a method call, attributed to the caller's uid
+async fn audit_record(
+    connection: &Connection,
+    header: &Header<'_>,
+    operation: &str,
+) -> AuditRecord {
+    let record = AuditRecord::new(Frontend::Dbus, operation);
+    let Some(sender) = header.sender() else {
+        return record;
+    };
+    let uid = match zbus::fdo::DBusProxy::new(connection).await {
+        Ok(proxy) => proxy
+            .get_connection_unix_user(sender.clone().into())
+            .await
+            .ok(),
+        Err(_) => None,
+    };
+    match uid {
+        Some(uid) => record.with_actor(format!("uid:{}", uid)),
+        None => record.with_actor(format!("dbus:{}", sender)),
+    }
+}
+
+/// Record the outcome of a method call in the audit log and pass it through
+fn audited<T>(record: AuditRecord, result: zbus::fdo::Result<T>) -> zbus::fdo::Result<T> {
+    match &result {
+        Ok(_) => record.record(),
+        Err(zbus::fdo::Error::AccessDenied(e)) => record.denied(e.clone()).record(),
+        Err(e) => record.failed(e.to_string()).record(),
+    }
+    result
+}
+
+/// D-Bus object for one state plugin
+#[derive(Clone)]
+pub struct PluginObject {
+    state_manager: Arc<StateManager>,
+    plugin: Arc<dyn StatePlugin>,
+    checkpoints: Arc<Mutex<VecDeque<Checkpoint>>>,
+    last_applied: Arc<Mutex<Option<Value>>>,
+    authority: PolkitAuthority,
+}
+
+impl PluginObject {
+    pub fn new(
+        state_manager: Arc<StateManager>,
+        plugin: Arc<dyn StatePlugin>,
+        authority: PolkitAuthority,
+    ) -> Self {
+        Self {
+            state_manager,
+            plugin,
+            checkpoints: Arc::new(Mutex::new(VecDeque::new())),
+            last_applied: Arc::new(Mutex::new(None)),
+            authority,
+        }
+    }
+
+    async fn authorize(&self, header: &Header<'_>) -> zbus::fdo::Result<()> {
+        self.authority
+            .authorize_apply(&caller(header)?, self.plugin.name())
+            .await
+            .map_err(access_denied)
+    }
+
+    async fn plan(&self, desired: &Value) -> Result<StateDiff> {
+        let current = self.plugin.query_current_state().await?;
+        self.plugin.calculate_diff(&current, desired).await
+    }
+
+    async fn store_checkpoint(&self, checkpoint: Checkpoint) {
+        let mut checkpoints = self.checkpoints.lock().await;
+        checkpoints.retain(|c| c.id != checkpoint.id);
+        checkpoints.push_back(checkpoint);
+        while checkpoints.len() > MAX_CHECKPOINTS {
+            checkpoints.pop_front();
+        }
+    }
+
+    /// Roll back to a stored checkpoint and announce it
+    async fn rollback_to(
+        &self,
+        checkpoint_id: &str,
+        header: &Header<'_>,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::fdo::Result<()> {
+        self.authorize(header).await?;
+        let checkpoint = self
+            .checkpoints
+            .lock()
+            .await
+            .iter()
+            .find(|c| c.id == checkpoint_id)
+            .cloned()
+            .ok_or_else(|| invalid_args(format!("Unknown checkpoint {}", checkpoint_id)))?;
+
+        self.plugin.rollback(&checkpoint).await.map_err(failed)?;
+        *self.last_applied.lock().await = None;
+
+        let _ = Self::state_changed(
+            emitter,
+            vec![format!("Rolled back to checkpoint {}", checkpoint_id)],
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Apply `desired` through the state manager, reporting each phase
+    ///
+    /// Going through `StateManager` records metrics, readiness and the
+    /// blockchain footprint like every other front-end. Returns the result
+    /// and the id of the checkpoint to roll back to.
+    async fn apply_desired(
+        &self,
+        desired: Value,
+        emitter: Option<&SignalEmitter<'_>>,
+    ) -> Result<(ApplyResult, Option<String>)> {
+        const TOTAL: u32 = 4;
+        let name = self.plugin.name().to_string();
+        let desired_state = DesiredState {
+            version: 1,
+            plugins: HashMap::from([(name.clone(), desired.clone())]),
+        };
+
+        // Progress arrives synchronously; signals are emitted as it comes
+        let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let apply = self.state_manager.apply_state_single_plugin_with_progress(
+            desired_state,
+            &name,
+            move |event| {
+                let _ = events.send(event);
+            },
+        );
+        let forward = async {
+            while let Some(event) = received.recv().await {
+                let (phase, step) = match event {
+                    ApplyEvent::CheckpointCreated { .. } => ("checkpoint", 1),
+                    ApplyEvent::DiffCalculated { .. } => ("diff", 2),
+                    ApplyEvent::ActionStarted { index: 0, .. } => ("apply", 3),
+                    ApplyEvent::RolledBack { .. } => ("rollback", TOTAL),
+                    _ => continue,
+                };
+                Self::progress(emitter, phase, step, TOTAL).await;
+            }
+        };
+        let (report, ()) = tokio::join!(apply, forward);
+        let report = report?;
+
+        for (_, checkpoint) in &report.checkpoints {
+            self.store_checkpoint(checkpoint.clone()).await;
+        }
+        // A checkpoint from the plugin itself describes exactly what changed
+        let mut checkpoint_id = report.checkpoints.last().map(|(_, c)| c.id.clone());
+        for checkpoint in report.results.iter().filter_map(|r| r.checkpoint.clone()) {
+            checkpoint_id = Some(checkpoint.id.clone());
+            self.store_checkpoint(checkpoint).await;
+        }
+        let result = ApplyResult {
+            success: report.success,
+            changes_applied: report
+                .results
+                .iter()
+                .flat_map(|r| r.changes_applied.clone())
+                .collect(),
+            errors: report
+                .results
+                .iter()
+                .flat_map(|r| r.errors.clone())
+                .collect(),
+            checkpoint: None,
+        };
+        if result.success {
+            *self.last_applied.lock().await = Some(desired);
+        }
+
+        Self::progress(emitter, "done", TOTAL, TOTAL).await;
+        if let Some(emitter) = emitter {
+            if !result.changes_applied.is_empty() {
+                let _ = Self::state_changed(emitter, result.changes_applied.clone()).await;
+            }
+        }
+
+        Ok((result, checkpoint_id))
+    }
+
+    /// Diff against the last applied state, if the plugin no longer verifies
+    async fn check_drift(&self) -> Result<Option<StateDiff>> {
+        let Some(desired) = self.last_applied.lock().await.clone() else {
+            return Ok(None);
+        };
+        if self.plugin.verify_state(&desired).await? {
+            return Ok(None);
+        }
+        Ok(Some(self.plan(&desired).await?))
+    }
+
+    async fn emit_drift(emitter: &SignalEmitter<'_>, diff: &StateDiff) -> Result<()> {
+        let dict = json_to_dict(&serde_json::to_value(diff)?)?;
+        Self::drift_detected(emitter, dict).await?;
+        Ok(())
+    }
+
+    async fn progress(emitter: Option<&SignalEmitter<'_>>, phase: &str, step: u32, total: u32) {
+        if let Some(emitter) = emitter {
+            if let Err(e) = Self::apply_progress(emitter, phase, step, total).await {
+                log::debug!("Failed to emit ApplyProgress: {}", e);
+            }
+        }
+    }
+}
+
+#[interface(name = "org.opdbus.StatePlugin")]
+impl PluginObject {
+    /// Current state of the plugin
+    async fn query(&self) -> zbus::fdo::Result<StateDict> {
+        let state = self.plugin.query_current_state().await.map_err(failed)?;
+        json_to_dict(&state).map_err(failed)
+    }
+
+    /// Actions needed to reach `desired`
+    async fn diff(&self, desired: StateDict) -> zbus::fdo::Result<StateDict> {
+        let desired = dict_to_json(&desired).map_err(invalid_args)?;
+        let diff = self.plan(&desired).await.map_err(failed)?;
+        json_to_dict(&serde_json::to_value(diff).map_err(failed)?).map_err(failed)
+    }
+
+    /// Apply `desired`; returns success, changes_applied, errors and checkpoint
+    async fn apply(
+        &self,
+        desired: StateDict,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<StateDict> {
+        let desired = dict_to_json(&desired).map_err(invalid_args);
+        let mut record = audit_record(connection, &header, "apply")
+            .await
+            .with_plugin(self.plugin.name())
+            .with_params(desired.as_ref().unwrap_or(&Value::Null));
+        if let Err(e) = self.authorize(&header).await {
+            return audited(record, Err(e));
+        }
+        let applied = match desired {
+            Ok(desired) => self
+                .apply_desired(desired, Some(&emitter))
+                .await
+                .map_err(failed),
+            Err(e) => Err(e),
+        };
+        if let Ok((result, _)) = &applied {
+            record = record.with_actions(&result.changes_applied);
+            if !result.success {
+                record = record.failed(result.errors.join("; "));
+            }
+        }
+        let (result, checkpoint) = audited(record, applied)?;
+
+        json_to_dict(&serde_json::json!({
+            "success": result.success,
+            "changes_applied": result.changes_applied,
+            "errors": result.errors,
+            "checkpoint": checkpoint.unwrap_or_default(),
+        }))
+        .map_err(failed)
+    }
+
+    /// Whether the current state matches `desired`; emits DriftDetected if not
+    async fn verify(
+        &self,
+        desired: StateDict,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> zbus::fdo::Result<bool> {
+        let desired = dict_to_json(&desired).map_err(invalid_args)?;
+        let verified = self.plugin.verify_state(&desired).await.map_err(failed)?;
+
+        if !verified {
+            if let Ok(diff) = self.plan(&desired).await {
+                let _ = Self::emit_drift(&emitter, &diff).await;
+            }
+        }
+        Ok(verified)
+    }
+
+    /// Create a checkpoint; its `id` can be passed to Rollback
+    async fn checkpoint(&self, #[zbus(header)] header: Header<'_>) -> zbus::fdo::Result<StateDict> {
+        self.authorize(&header).await?;
+        let checkpoint = self.plugin.create_checkpoint().await.map_err(failed)?;
+        let dict =
+            json_to_dict(&serde_json::to_value(&checkpoint).map_err(failed)?).map_err(failed)?;
+        self.store_checkpoint(checkpoint).await;
+        Ok(dict)
+    }
+
+    /// Roll back to a checkpoint created by Checkpoint or Apply
+    async fn rollback(
+        &self,
+        checkpoint_id: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        let record = audit_record(connection, &header, "rollback")
+            .await
+            .with_plugin(self.plugin.name())
+            .with_params(&serde_json::json!({ "checkpoint": checkpoint_id }));
+        let result = self.rollback_to(&checkpoint_id, &header, &emitter).await;
+        audited(record, result)
+    }
+
+    #[zbus(property)]
+    fn name(&self) -> String {
+        self.plugin.name().to_string()
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> String {
+        self.plugin.version().to_string()
+    }
+
+    #[zbus(property)]
+    fn available(&self) -> bool {
+        self.plugin.is_available()
+    }
+
+    #[zbus(property)]
+    fn capabilities(&self) -> HashMap<String, bool> {
+        let caps = self.plugin.capabilities();
+        HashMap::from([
+            ("supports_rollback".to_string(), caps.supports_rollback),
+            (
+                "supports_checkpoints".to_string(),
+                caps.supports_checkpoints,
+            ),
+            (
+                "supports_verification".to_string(),
+                caps.supports_verification,
+            ),
+            ("atomic_operations".to_string(), caps.atomic_operations),
+        ])
+    }
+
+    /// Changes made by Apply or Rollback
+    #[zbus(signal)]
+    async fn state_changed(emitter: &SignalEmitter<'_>, changes: Vec<String>) -> zbus::Result<()>;
+
+    /// Apply phase: checkpoint, diff, apply, done
+    #[zbus(signal)]
+    async fn apply_progress(
+        emitter: &SignalEmitter<'_>,
+        phase: &str,
+        step: u32,
+        total: u32,
+    ) -> zbus::Result<()>;
+
+    /// The system no longer matches the last applied state
+    #[zbus(signal)]
+    async fn drift_detected(emitter: &SignalEmitter<'_>, diff: StateDict) -> zbus::Result<()>;
+}
+
+fn drift_interval() -> Option<Duration> {
+    match std::env::var("OPDBUS_DRIFT_INTERVAL") {
+        Ok(secs) => match secs.parse::<u64>() {
+            Ok(0) => None,
+            Ok(secs) => Some(Duration::from_secs(secs)),
+            Err(_) => Some(DEFAULT_DRIFT_INTERVAL),
+        },
+        Err(_) => Some(DEFAULT_DRIFT_INTERVAL),
+    }
+}
+
+/// Periodically verify the last applied state and signal drift
+async fn monitor_drift(
+    connection: Connection,
+    path: String,
+    object: PluginObject,
+    every: Duration,
+) {
+    let emitter = match SignalEmitter::new(&connection, path.as_str()) {
+        Ok(emitter) => emitter,
+        Err(e) => {
+            log::warn!("Drift monitoring disabled for {}: {}", path, e);
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(every);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match object.check_drift().await {
+            Ok(Some(diff)) => {
+                log::warn!("Drift detected for plugin {}", diff.plugin);
+                if let Err(e) = PluginObject::emit_drift(&emitter, &diff).await {
+                    log::debug!("Failed to emit DriftDetected: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::debug!("Drift check failed for {}: {}", path, e),
+        }
+    }
+}
+
 /// Start the system bus D-Bus service
-pub async fn start_system_bus(state_manager: Arc<StateManager>) -> Result<()> {
-    let interface = StateManagerDBus { state_manager };
+///
+/// Returns once `org.opdbus` is owned and every object is served; they stay
+/// served as long as the returned connection lives.
+pub async fn start_system_bus(state_manager: Arc<StateManager>) -> Result<Connection> {
+    let plugins = state_manager.list_plugins().await;
+    let authority = PolkitAuthority::system().await?;
+    let interface = StateManagerDBus {
+        state_manager: state_manager.clone(),
+        authority: authority.clone(),
+    };
 
-    let _connection = Builder::system()?
+    let mut builder = Builder::system()?
         .name("org.opdbus")?
-        .serve_at("/org/opdbus/state", interface)?
-        .build()
-        .await?;
+        .serve_at(STATE_PATH, interface)?
+        .serve_at(STATE_PATH, zbus::fdo::ObjectManager)?;
+
+    let mut objects = Vec::new();
+    for plugin in plugins {
+        let path = plugin_object_path(plugin.name());
+        let object = PluginObject::new(state_manager.clone(), plugin, authority.clone());
+        builder = builder.serve_at(path.clone(), object.clone())?;
+        objects.push((path, object));
+    }
 
-    // Keep the connection alive
-    std::future::pending::<()>().await;
+    let connection = builder.build().await?;
+    log::info!(
+        "Serving {} plugin objects under {}",
+        objects.len(),
+        STATE_PATH
+    );
 
-    Ok(())
+    if let Some(every) = drift_interval() {
+        for (path, object) in objects {
+            tokio::spawn(monitor_drift(connection.clone(), path, object, every));
+        }
+    }
+
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::plugin::{DiffMetadata, PluginCapabilities};
+    use async_trait::async_trait;
+
+    /// Plugin whose state is a JSON value in memory
+    struct MemoryPlugin {
+        state: std::sync::Mutex<Value>,
+    }
+
+    #[async_trait]
+    impl StatePlugin for MemoryPlugin {
+        fn name(&self) -> &str {
+            "memory-test"
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        async fn query_current_state(&self) -> Result<Value> {
+            Ok(self.state.lock().unwrap().clone())
+        }
+
+        async fn calculate_diff(&self, current: &Value, desired: &Value) -> Result<StateDiff> {
+            let actions = if current == desired {
+                Vec::new()
+            } else {
+                vec![StateAction::Modify {
+                    resource: "state".to_string(),
+                    changes: desired.clone(),
+                }]
+            };
+            Ok(StateDiff {
+                plugin: self.name().to_string(),
+                actions,
+                metadata: DiffMetadata {
+                    timestamp: 0,
+                    current_hash: String::new(),
+                    desired_hash: String::new(),
+                },
+            })
+        }
+
+        async fn apply_state(&self, diff: &StateDiff) -> Result<ApplyResult> {
+            let mut changes_applied = Vec::new();
+            for action in &diff.actions {
+                if let StateAction::Modify { changes, .. } = action {
+                    *self.state.lock().unwrap() = changes.clone();
+                    changes_applied.push("Replaced state".to_string());
+                }
+            }
+            Ok(ApplyResult {
+                success: true,
+                changes_applied,
+                errors: Vec::new(),
+                checkpoint: None,
+            })
+        }
+
+        async fn verify_state(&self, desired: &Value) -> Result<bool> {
+            Ok(*self.state.lock().unwrap() == *desired)
+        }
+
+        async fn create_checkpoint(&self) -> Result<Checkpoint> {
+            Ok(Checkpoint {
+                id: "memory-1".to_string(),
+                plugin: self.name().to_string(),
+                timestamp: 0,
+                state_snapshot: self.state.lock().unwrap().clone(),
+                backend_checkpoint: None,
+            })
+        }
+
+        async fn rollback(&self, checkpoint: &Checkpoint) -> Result<()> {
+            *self.state.lock().unwrap() = checkpoint.state_snapshot.clone();
+            Ok(())
+        }
+
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities {
+                supports_rollback: true,
+                supports_checkpoints: true,
+                supports_verification: true,
+                atomic_operations: true,
+            }
+        }
+    }
+
+    #[test]
+    fn test_plugin_object_path() {
+        assert_eq!(plugin_object_path("systemd"), "/org/opdbus/state/systemd");
+        assert_eq!(
+            plugin_object_path("systemd-networkd"),
+            "/org/opdbus/state/systemd_networkd"
+        );
+    }
+
+    #[test]
+    fn test_dict_roundtrip() {
+        let state = serde_json::json!({
+            "units": { "ssh.service": { "enabled": true, "restarts": 3 } },
+            "list": ["a", 1, 2.5, false],
+            "empty": {}
+        });
+        let dict = json_to_dict(&state).unwrap();
+        assert_eq!(dict_to_json(&dict).unwrap(), state);
+
+        let wrapped = json_to_dict(&serde_json::json!([1, 2])).unwrap();
+        assert_eq!(
+            dict_to_json(&wrapped).unwrap(),
+            serde_json::json!({ "value": [1, 2] })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_checkpoint_and_drift() {
+        let plugin = Arc::new(MemoryPlugin {
+            state: std::sync::Mutex::new(serde_json::json!({ "mode": "old" })),
+        });
+        let manager = Arc::new(StateManager::new());
+        manager.register_plugin(plugin.clone()).await;
+        let object =
+            PluginObject::new(manager.clone(), plugin.clone(), PolkitAuthority::disabled());
+        let desired = serde_json::json!({ "mode": "new" });
+
+        let (result, checkpoint) = object.apply_desired(desired.clone(), None).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.changes_applied, ["Replaced state"]);
+        assert_eq!(checkpoint.as_deref(), Some("memory-1"));
+        // Applied through the state manager, so readiness sees it
+        assert!(manager.last_reconciles()["memory-test"].success);
+        assert!(object.check_drift().await.unwrap().is_none());
+
+        // Someone changes the state behind op-dbus' back
+        *plugin.state.lock().unwrap() = serde_json::json!({ "mode": "manual" });
+        let drift = object.check_drift().await.unwrap().unwrap();
+        assert_eq!(drift.actions.len(), 1);
+
+        let stored = object.checkpoints.lock().await.back().cloned().unwrap();
+        plugin.rollback(&stored).await.unwrap();
+        assert_eq!(
+            *plugin.state.lock().unwrap(),
+            serde_json::json!({ "mode": "old" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_requires_polkit_authorization() {
+        use crate::native::polkit::tests::{serve_mock_authority, TestBus};
+
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_polkit, checks) =
+            serve_mock_authority(&bus, &["org.opdbus.apply.memory-test"], &[]).await;
+
+        let plugin = Arc::new(MemoryPlugin {
+            state: std::sync::Mutex::new(serde_json::json!({ "mode": "old" })),
+        });
+        let authority = PolkitAuthority::new(bus.connect().await);
+        let path = plugin_object_path(plugin.name());
+        let server = zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .serve_at(
+                path.as_str(),
+                PluginObject::new(Arc::new(StateManager::new()), plugin.clone(), authority),
+            )
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let client = bus.connect().await;
+        let destination = server.unique_name().unwrap().to_owned();
+        let desired = json_to_dict(&serde_json::json!({ "mode": "new" })).unwrap();
+
+        let denied = client
+            .call_method(
+                Some(destination.clone()),
+                path.as_str(),
+                Some("org.opdbus.StatePlugin"),
+                "Apply",
+                &(desired,),
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&denied, zbus::Error::MethodError(name, _, _)
+                if name.as_str() == "org.freedesktop.DBus.Error.AccessDenied"),
+            "{}",
+            denied
+        );
+        assert_eq!(
+            *plugin.state.lock().unwrap(),
+            serde_json::json!({ "mode": "old" })
+        );
+
+        // Reads stay open
+        client
+            .call_method(
+                Some(destination),
+                path.as_str(),
+                Some("org.opdbus.StatePlugin"),
+                "Query",
+                &(),
+            )
+            .await
+            .unwrap();
+
+        let checks = checks.lock().unwrap();
+        assert_eq!(checks.len(), 1);
+        let ((kind, details), action) = &checks[0];
+        assert_eq!(kind, "system-bus-name");
+        assert_eq!(
+            <&str>::try_from(&details["name"]).unwrap(),
+            client.unique_name().unwrap().as_str()
+        );
+        assert_eq!(action, "org.opdbus.apply.memory-test");
+    }
 }
@@ -0,0 +1,500 @@
+//! Readiness checks and systemd watchdog integration
+//!
+//! A `ReadinessChecker` probes every component the daemon depends on: each
+//! registered plugin, the system bus, OVSDB, the blockchain store and the
+//! outcome of the last reconcile. The checks back `/health/ready` and the
+//! systemd STATUS= line. The `sd_notify` watchdog is only fed from liveness:
+//! the event loop still running the watchdog task and the system bus
+//! answering. A failed apply or an unavailable plugin makes the daemon not
+//! ready, but restarting it would not fix the desired state. While the daemon
+//! is busy, such as applying the desired state at startup, STATUS= reports
+//! that activity instead.
+
+use crate::state::StateManager;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default OVSDB socket, as used by `OvsdbClient`
+pub const DEFAULT_OVSDB_SOCKET: &str = "/var/run/openvswitch/db.sock";
+
+/// How long a single probe may take before it counts as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Components whose failure means the daemon is wedged rather than not ready
+const LIVENESS_COMPONENTS: &[&str] = &["dbus"];
+
+/// Health of one component
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub healthy: bool,
+    /// Whether the component counts towards readiness
+    pub required: bool,
+    pub message: Option<String>,
+}
+
+impl ComponentHealth {
+    fn ok(name: impl Into<String>, required: bool, message: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            healthy: true,
+            required,
+            message,
+        }
+    }
+
+    fn failed(name: impl Into<String>, required: bool, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            healthy: false,
+            required,
+            message: Some(message.into()),
+        }
+    }
+}
+
+/// Result of a readiness check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checked_at: i64,
+    pub components: Vec<ComponentHealth>,
+}
+
+impl ReadinessReport {
+    fn new(components: Vec<ComponentHealth>) -> Self {
+        Self {
+            ready: components.iter().all(|c| c.healthy || !c.required),
+            checked_at: chrono::Utc::now().timestamp(),
+            components,
+        }
+    }
+
+    /// Required components that are not healthy
+    pub fn failures(&self) -> impl Iterator<Item = &ComponentHealth> {
+        self.components.iter().filter(|c| c.required && !c.healthy)
+    }
+
+    /// Whether the liveness components are healthy, whatever the rest reports
+    pub fn is_live(&self) -> bool {
+        self.components
+            .iter()
+            .filter(|c| LIVENESS_COMPONENTS.contains(&c.name.as_str()))
+            .all(|c| c.healthy)
+    }
+
+    /// One-line summary, suitable for systemd's STATUS=
+    pub fn summary(&self) -> String {
+        if self.ready {
+            return "Ready".to_string();
+        }
+        let failures: Vec<String> = self
+            .failures()
+            .map(|c| match &c.message {
+                Some(message) => format!("{}: {}", c.name, message),
+                None => c.name.clone(),
+            })
+            .collect();
+        format!("Not ready: {}", failures.join("; "))
+    }
+}
+
+impl IntoResponse for ReadinessReport {
+    fn into_response(self) -> Response {
+        let status = if self.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status, axum::Json(self)).into_response()
+    }
+}
+
+/// Probes the components the daemon depends on
+pub struct ReadinessChecker {
+    state_manager: Arc<StateManager>,
+    state_file: Option<PathBuf>,
+    ovsdb_socket: PathBuf,
+    blockchain_dir: PathBuf,
+    check_dbus: bool,
+    /// Reported as STATUS= instead of the readiness summary while set
+    activity: std::sync::Mutex<Option<String>>,
+}
+
+#[allow(dead_code)] // Builder options are only used by the library
+impl ReadinessChecker {
+    /// Create a checker with the default socket and store locations
+    pub fn new(state_manager: Arc<StateManager>) -> Self {
+        Self {
+            state_manager,
+            state_file: None,
+            ovsdb_socket: PathBuf::from(DEFAULT_OVSDB_SOCKET),
+            blockchain_dir: crate::state::metrics::blockchain_dir(),
+            check_dbus: true,
+            activity: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Only plugins named in this desired-state file are required to be available
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    pub fn with_ovsdb_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ovsdb_socket = path.into();
+        self
+    }
+
+    pub fn with_blockchain_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.blockchain_dir = path.into();
+        self
+    }
+
+    /// Report what the daemon is busy with as STATUS=; `None` goes back to
+    /// reporting readiness
+    pub async fn set_activity(&self, activity: Option<String>) {
+        *self.activity.lock().unwrap() = activity.clone();
+        let status = match activity {
+            Some(activity) => activity,
+            None => self.check().await.summary(),
+        };
+        if let Err(e) = sd_notify(&format!("STATUS={}", status)) {
+            log::warn!("sd_notify failed: {}", e);
+        }
+    }
+
+    fn activity(&self) -> Option<String> {
+        self.activity.lock().unwrap().clone()
+    }
+
+    /// Skip the system bus probe (for tests and containers without a bus)
+    pub fn without_dbus(mut self) -> Self {
+        self.check_dbus = false;
+        self
+    }
+
+    /// Run every probe
+    pub async fn check(&self) -> ReadinessReport {
+        let mut components = self.check_plugins().await;
+        if self.check_dbus {
+            components.push(check_dbus().await);
+        }
+        components.push(check_ovsdb(&self.ovsdb_socket).await);
+        components.push(check_blockchain(&self.blockchain_dir).await);
+        components.push(self.check_reconcile());
+        ReadinessReport::new(components)
+    }
+
+    /// Plugins named in the desired state, or None when there is no state file
+    async fn managed_plugins(&self) -> Option<HashSet<String>> {
+        let path = self.state_file.as_ref()?;
+        match self.state_manager.load_desired_state(path).await {
+            Ok(desired) => Some(desired.plugins.into_keys().collect()),
+            Err(e) => {
+                log::debug!("Readiness: cannot read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    async fn check_plugins(&self) -> Vec<ComponentHealth> {
+        let managed = self.managed_plugins().await;
+        let mut plugins = self.state_manager.list_plugins().await;
+        plugins.sort_by(|a, b| a.name().cmp(b.name()));
+
+        plugins
+            .iter()
+            .map(|plugin| {
+                let name = format!("plugin:{}", plugin.name());
+                let required = managed
+                    .as_ref()
+                    .is_some_and(|managed| managed.contains(plugin.name()));
+                if plugin.is_available() {
+                    ComponentHealth::ok(name, required, None)
+                } else {
+                    ComponentHealth::failed(name, required, plugin.unavailable_reason())
+                }
+            })
+            .collect()
+    }
+
+    fn check_reconcile(&self) -> ComponentHealth {
+        let reconciles = self.state_manager.last_reconciles();
+        if reconciles.is_empty() {
+            return ComponentHealth::ok("reconcile", true, Some("no apply yet".to_string()));
+        }
+        let mut failed: Vec<&String> = reconciles
+            .iter()
+            .filter(|(_, status)| !status.success)
+            .map(|(plugin, _)| plugin)
+            .collect();
+        if failed.is_empty() {
+            return ComponentHealth::ok("reconcile", true, None);
+        }
+        failed.sort();
+        let failed: Vec<&str> = failed.into_iter().map(String::as_str).collect();
+        ComponentHealth::failed(
+            "reconcile",
+            true,
+            format!("last apply failed for {}", failed.join(", ")),
+        )
+    }
+}
+
+async fn probe<F, T>(future: F) -> Result<T, String>
+where
+    F: std::future::Future<Output = anyhow::Result<T>>,
+{
+    match tokio::time::timeout(PROBE_TIMEOUT, future).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", PROBE_TIMEOUT.as_secs())),
+    }
+}
+
+/// The system bus answers a call to the bus daemon
+async fn check_dbus() -> ComponentHealth {
+    let result = probe(async {
+        let conn = zbus::Connection::system().await?;
+        let id = zbus::fdo::DBusProxy::new(&conn).await?.get_id().await?;
+        Ok(id)
+    })
+    .await;
+    match result {
+        Ok(_) => ComponentHealth::ok("dbus", true, None),
+        Err(e) => ComponentHealth::failed("dbus", true, e),
+    }
+}
+
+/// OVSDB answers list_dbs; only required when its socket exists
+async fn check_ovsdb(socket: &Path) -> ComponentHealth {
+    if !socket.exists() {
+        return ComponentHealth::ok("ovsdb", false, Some("socket not present".to_string()));
+    }
+    let socket = socket.to_path_buf();
+    let result = probe(async move {
+        let mut stream = tokio::net::UnixStream::connect(&socket).await?;
+        let request = serde_json::json!({"method": "list_dbs", "params": [], "id": 0});
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+        stream.write_all(request.to_string().as_bytes()).await?;
+        let mut line = String::new();
+        tokio::io::BufReader::new(stream)
+            .read_line(&mut line)
+            .await?;
+        let response: serde_json::Value = serde_json::from_str(&line)?;
+        if !response["error"].is_null() {
+            anyhow::bail!("list_dbs failed: {}", response["error"]);
+        }
+        Ok(())
+    })
+    .await;
+    match result {
+        Ok(()) => ComponentHealth::ok("ovsdb", true, None),
+        Err(e) => ComponentHealth::failed("ovsdb", true, e),
+    }
+}
+
+/// A probe file can be written to the blockchain store; only required when it exists
+async fn check_blockchain(dir: &Path) -> ComponentHealth {
+    if !dir.exists() {
+        return ComponentHealth::ok("blockchain", false, Some("store not present".to_string()));
+    }
+    let probe_file = dir.join(format!(".health-{}", std::process::id()));
+    let result = probe(async {
+        tokio::fs::write(&probe_file, b"ok").await?;
+        tokio::fs::remove_file(&probe_file).await?;
+        Ok(())
+    })
+    .await;
+    match result {
+        Ok(()) => ComponentHealth::ok("blockchain", true, None),
+        Err(e) => ComponentHealth::failed("blockchain", true, format!("not writable: {}", e)),
+    }
+}
+
+/// Send a state string to systemd's notification socket
+///
+/// Returns false when not running under systemd (`NOTIFY_SOCKET` unset).
+pub fn sd_notify(state: &str) -> std::io::Result<bool> {
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let path = path.to_string_lossy().into_owned();
+    let addr = match path.strip_prefix('@') {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name.as_bytes())?
+        }
+        None => SocketAddr::from_pathname(&path)?,
+    };
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+/// Watchdog interval requested by systemd, if it is meant for this process
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Tell systemd the daemon is up and keep its watchdog fed while alive
+///
+/// The watchdog is pinged at half the configured interval as long as the
+/// event loop runs this task and the system bus answers. Readiness failures
+/// are only reported as STATUS=; systemd restarts the unit when the
+/// watchdog expires, which a bad desired state would not fix.
+pub fn spawn_watchdog(checker: Arc<ReadinessChecker>) {
+    if let Err(e) = sd_notify("READY=1") {
+        log::warn!("sd_notify failed: {}", e);
+    }
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+    log::info!("systemd watchdog enabled, interval {:?}", interval);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval / 2);
+        loop {
+            ticker.tick().await;
+            let report = checker.check().await;
+            if !report.ready {
+                log::warn!("{}", report.summary());
+            }
+            let status = checker.activity().unwrap_or_else(|| report.summary());
+            let state = if report.is_live() {
+                format!("WATCHDOG=1\nSTATUS={}", status)
+            } else {
+                log::error!("Liveness check failed, withholding the watchdog ping");
+                format!("STATUS={}", status)
+            };
+            if let Err(e) = sd_notify(&state) {
+                log::warn!("sd_notify failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::plugin::{
+        ApplyResult, Checkpoint, PluginCapabilities, StateDiff, StatePlugin,
+    };
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+
+    struct ProbePlugin {
+        name: &'static str,
+        available: bool,
+    }
+
+    #[async_trait]
+    impl StatePlugin for ProbePlugin {
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn is_available(&self) -> bool {
+            self.available
+        }
+        async fn query_current_state(&self) -> anyhow::Result<Value> {
+            Ok(json!({}))
+        }
+        async fn calculate_diff(
+            &self,
+            _current: &Value,
+            _desired: &Value,
+        ) -> anyhow::Result<StateDiff> {
+            anyhow::bail!("not used")
+        }
+        async fn apply_state(&self, _diff: &StateDiff) -> anyhow::Result<ApplyResult> {
+            anyhow::bail!("not used")
+        }
+        async fn verify_state(&self, _desired: &Value) -> anyhow::Result<bool> {
+            Ok(true)
+        }
+        async fn create_checkpoint(&self) -> anyhow::Result<Checkpoint> {
+            anyhow::bail!("not used")
+        }
+        async fn rollback(&self, _checkpoint: &Checkpoint) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities {
+                supports_rollback: false,
+                supports_checkpoints: false,
+                supports_verification: false,
+                atomic_operations: false,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness_requires_managed_plugins() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        std::fs::write(
+            &state_file,
+            json!({"version": 1, "plugins": {"managed": {}}}).to_string(),
+        )
+        .unwrap();
+
+        let manager = Arc::new(StateManager::new());
+        for (name, available) in [("managed", true), ("optional", false)] {
+            manager
+                .register_plugin(Arc::new(ProbePlugin { name, available }))
+                .await;
+        }
+        let checker = ReadinessChecker::new(manager.clone())
+            .with_state_file(&state_file)
+            .with_ovsdb_socket(dir.path().join("missing.sock"))
+            .with_blockchain_dir(dir.path())
+            .without_dbus();
+
+        let report = checker.check().await;
+        assert!(report.ready, "{}", report.summary());
+        let optional = report
+            .components
+            .iter()
+            .find(|c| c.name == "plugin:optional")
+            .unwrap();
+        assert!(!optional.healthy && !optional.required);
+
+        manager
+            .register_plugin(Arc::new(ProbePlugin {
+                name: "managed",
+                available: false,
+            }))
+            .await;
+        let report = checker.check().await;
+        assert!(!report.ready);
+        // Not ready, but the watchdog keeps being fed
+        assert!(report.is_live());
+        assert_eq!(
+            report.summary(),
+            "Not ready: plugin:managed: Plugin 'managed' is not available"
+        );
+
+        // An activity is reported instead of readiness until cleared
+        checker.set_activity(Some("Applying".to_string())).await;
+        assert_eq!(checker.activity().as_deref(), Some("Applying"));
+        checker.set_activity(None).await;
+        assert_eq!(checker.activity(), None);
+    }
+}
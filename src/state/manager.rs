@@ -2,13 +2,15 @@
 // ULTIMATE AUTHORITY: This plugin system is the sole authoritative source for network configuration
 // All external systems (NetworkManager, systemd-networkd, etc.) are subordinate data sources only
 // Note: Ledger functionality has been replaced with streaming blockchain
-use crate::state::plugin::{ApplyResult, Checkpoint, StateDiff, StatePlugin};
+use crate::state::metrics;
+use crate::state::plugin::{ApplyResult, Checkpoint, StateAction, StateDiff, StatePlugin};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::RwLock;
 
 #[cfg(feature = "streaming-blockchain")]
@@ -35,12 +37,59 @@ pub struct ApplyReport {
     pub success: bool,
     pub results: Vec<ApplyResult>,
     pub checkpoints: Vec<(String, Checkpoint)>,
+    /// Content hash of the blockchain footprint recorded for the apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub footprint: Option<String>,
+}
+
+/// Progress of an apply, reported as each step happens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ApplyEvent {
+    CheckpointCreated {
+        plugin: String,
+        checkpoint: String,
+    },
+    DiffCalculated {
+        plugin: String,
+        actions: usize,
+    },
+    ActionStarted {
+        plugin: String,
+        index: usize,
+        action: StateAction,
+    },
+    ActionFinished {
+        plugin: String,
+        index: usize,
+        success: bool,
+        changes: Vec<String>,
+        errors: Vec<String>,
+    },
+    Verified {
+        plugin: String,
+        matches: bool,
+    },
+    RolledBack {
+        plugin: String,
+        checkpoint: String,
+        error: Option<String>,
+    },
+}
+
+/// Outcome of the most recent apply of a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileStatus {
+    pub success: bool,
+    /// Unix time the apply finished
+    pub finished_at: i64,
 }
 
 /// State manager coordinates all plugins and provides atomic operations
 pub struct StateManager {
     plugins: Arc<RwLock<HashMap<String, Arc<dyn StatePlugin>>>>,
     workflows: std::sync::Mutex<crate::state::plugin_workflow::PluginWorkflowManager>,
+    reconciles: std::sync::Mutex<HashMap<String, ReconcileStatus>>,
     #[cfg(feature = "streaming-blockchain")]
     blockchain_sender: Option<FootprintSender>,
 }
@@ -59,6 +108,7 @@ impl StateManager {
             workflows: std::sync::Mutex::new(
                 crate::state::plugin_workflow::PluginWorkflowManager::new(),
             ),
+            reconciles: std::sync::Mutex::new(HashMap::new()),
             #[cfg(feature = "streaming-blockchain")]
             blockchain_sender: None,
         }
@@ -71,17 +121,25 @@ impl StateManager {
     }
 
     /// Record a hashed footprint for a plugin operation (best-effort)
+    ///
+    /// Returns the footprint's content hash once it has been sent.
     #[cfg(feature = "streaming-blockchain")]
-    fn record_footprint(&self, plugin: &str, operation: &str, data: serde_json::Value) {
-        if let Some(tx) = &self.blockchain_sender {
-            let gen = FootprintGenerator::new(plugin);
-            match gen.create_footprint(operation, &data, None) {
-                Ok(fp) => {
-                    let _ = tx.send(fp);
-                }
-                Err(e) => {
-                    log::debug!("Failed to create footprint for {}: {}", plugin, e);
-                }
+    fn record_footprint(
+        &self,
+        plugin: &str,
+        operation: &str,
+        data: serde_json::Value,
+    ) -> Option<String> {
+        let tx = self.blockchain_sender.as_ref()?;
+        let gen = FootprintGenerator::new(plugin);
+        match gen.create_footprint(operation, &data, None) {
+            Ok(fp) => {
+                let hash = fp.content_hash.clone();
+                tx.send(fp).ok().map(|_| hash)
+            }
+            Err(e) => {
+                log::debug!("Failed to create footprint for {}: {}", plugin, e);
+                None
             }
         }
     }
@@ -190,7 +248,7 @@ impl StateManager {
         let mut state = HashMap::new();
 
         for (name, plugin) in plugins.iter() {
-            match plugin.query_current_state().await {
+            match Self::query_timed(name, plugin.as_ref()).await {
                 Ok(plugin_state) => {
                     state.insert(name.clone(), plugin_state);
                 }
@@ -209,11 +267,19 @@ impl StateManager {
         let plugins = self.plugins.read().await;
 
         match plugins.get(plugin_name) {
-            Some(plugin) => plugin.query_current_state().await,
+            Some(plugin) => Self::query_timed(plugin_name, plugin.as_ref()).await,
             None => Err(anyhow!("Plugin not found: {}", plugin_name)),
         }
     }
 
+    /// Query a plugin's current state, recording the latency
+    async fn query_timed(plugin_name: &str, plugin: &dyn StatePlugin) -> Result<Value> {
+        let started = Instant::now();
+        let result = plugin.query_current_state().await;
+        metrics::observe_query(plugin_name, started.elapsed());
+        result
+    }
+
     /// Calculate diffs for all plugins
     async fn calculate_all_diffs(&self, desired: &DesiredState) -> Result<Vec<StateDiff>> {
         let plugins = self.plugins.read().await;
@@ -221,8 +287,9 @@ impl StateManager {
 
         for (plugin_name, desired_state) in &desired.plugins {
             if let Some(plugin) = plugins.get(plugin_name) {
-                let current_state = plugin.query_current_state().await?;
+                let current_state = Self::query_timed(plugin_name, plugin.as_ref()).await?;
                 let diff = plugin.calculate_diff(&current_state, desired_state).await?;
+                metrics::observe_drift(&diff.plugin, diff.drifted_resources());
 
                 // Only include diffs that have actual actions
                 if !diff.actions.is_empty() {
@@ -238,6 +305,7 @@ impl StateManager {
 
     /// Apply desired state atomically across all plugins
     pub async fn apply_state(&self, desired: DesiredState) -> Result<ApplyReport> {
+        let started = Instant::now();
         let mut checkpoints = Vec::new();
         let mut results = Vec::new();
 
@@ -282,15 +350,18 @@ impl StateManager {
 
         if diffs.is_empty() {
             log::info!("No changes needed - current state matches desired state");
+            self.observe_applies(&desired, &[], started).await;
             return Ok(ApplyReport {
                 success: true,
                 results,
                 checkpoints,
+                footprint: None,
             });
         }
 
         // Phase 3: Apply changes in dependency order
         log::info!("Phase 3: Applying changes ({} plugins)", diffs.len());
+        let mut failed = Vec::new();
         for diff in diffs {
             // Acquire lock, check if plugin exists, and apply state
             let apply_result = {
@@ -329,6 +400,7 @@ impl StateManager {
                     // Check if result indicates failure
                     if !result.success {
                         log::error!("Plugin {} returned success=false, but not triggering rollback (treating as warning)", diff.plugin);
+                        failed.push(diff.plugin.clone());
                     }
 
                     // State changes are automatically logged to streaming blockchain via plugin footprints
@@ -348,6 +420,7 @@ impl StateManager {
                     // return Err(e);
 
                     // Continue anyway
+                    failed.push(diff.plugin.clone());
                     results.push(ApplyResult {
                         success: false,
                         changes_applied: vec![],
@@ -395,14 +468,42 @@ impl StateManager {
         //     return Err(anyhow!("State verification failed"));
         // }
 
+        self.observe_applies(&desired, &failed, started).await;
         log::info!("State apply completed successfully");
         Ok(ApplyReport {
             success: true,
             results,
             checkpoints,
+            footprint: None,
         })
     }
 
+    /// Record the outcome of an apply for every registered plugin in the desired state
+    async fn observe_applies(&self, desired: &DesiredState, failed: &[String], started: Instant) {
+        let plugins = self.plugins.read().await;
+        for plugin_name in desired.plugins.keys().filter(|p| plugins.contains_key(*p)) {
+            self.finish_apply(plugin_name, !failed.contains(plugin_name), started);
+        }
+    }
+
+    /// Record the outcome of an apply in the metrics and the reconcile status
+    fn finish_apply(&self, plugin_name: &str, success: bool, started: Instant) {
+        metrics::observe_apply(plugin_name, success, started.elapsed());
+        let status = ReconcileStatus {
+            success,
+            finished_at: chrono::Utc::now().timestamp(),
+        };
+        self.reconciles
+            .lock()
+            .unwrap()
+            .insert(plugin_name.to_string(), status);
+    }
+
+    /// Outcome of the most recent apply of each plugin
+    pub fn last_reconciles(&self) -> HashMap<String, ReconcileStatus> {
+        self.reconciles.lock().unwrap().clone()
+    }
+
     /// Show diff between current and desired state
     pub async fn show_diff(&self, desired: DesiredState) -> Result<Vec<StateDiff>> {
         self.calculate_all_diffs(&desired).await
@@ -414,6 +515,7 @@ impl StateManager {
         desired: DesiredState,
         plugin_name: &str,
     ) -> Result<ApplyReport> {
+        let started = Instant::now();
         let mut checkpoints = Vec::new();
         let mut results = Vec::new();
 
@@ -452,7 +554,7 @@ impl StateManager {
         let diff = {
             let plugins = self.plugins.read().await;
             if let Some(plugin) = plugins.get(plugin_name) {
-                let current_state = plugin.query_current_state().await?;
+                let current_state = Self::query_timed(plugin_name, plugin.as_ref()).await?;
                 plugin
                     .calculate_diff(&current_state, plugin_desired_state)
                     .await?
@@ -460,13 +562,16 @@ impl StateManager {
                 return Err(anyhow!("Plugin '{}' not registered", plugin_name));
             }
         };
+        metrics::observe_drift(&diff.plugin, diff.drifted_resources());
 
         if diff.actions.is_empty() {
             log::info!("No changes needed for {}", plugin_name);
+            self.finish_apply(plugin_name, true, started);
             return Ok(ApplyReport {
                 success: true,
                 results,
                 checkpoints,
+                footprint: None,
             });
         }
 
@@ -481,6 +586,11 @@ impl StateManager {
             }
         };
 
+        self.finish_apply(
+            plugin_name,
+            apply_result.as_ref().is_ok_and(|result| result.success),
+            started,
+        );
         match apply_result {
             Ok(result) => {
                 log::info!(
@@ -525,6 +635,174 @@ impl StateManager {
             success: true,
             results,
             checkpoints,
+            footprint: None,
+        })
+    }
+
+    /// Apply state for a single plugin one action at a time, reporting progress
+    ///
+    /// When an action fails the plugin is rolled back to its checkpoint, if
+    /// it supports rollback, and the report is returned with success=false.
+    /// Either way the apply is recorded as a footprint.
+    pub async fn apply_state_single_plugin_with_progress<F>(
+        &self,
+        desired: DesiredState,
+        plugin_name: &str,
+        progress: F,
+    ) -> Result<ApplyReport>
+    where
+        F: Fn(ApplyEvent) + Send + Sync,
+    {
+        let started = Instant::now();
+        let mut checkpoints = Vec::new();
+        let mut results = Vec::new();
+
+        let plugin_desired_state = desired
+            .plugins
+            .get(plugin_name)
+            .ok_or_else(|| anyhow!("Plugin '{}' not found in state file", plugin_name))?;
+        let plugin = self
+            .get_plugin(plugin_name)
+            .await
+            .ok_or_else(|| anyhow!("Plugin '{}' not registered", plugin_name))?;
+        let capabilities = plugin.capabilities();
+
+        // Phase 1: Create checkpoint
+        let checkpoint = match plugin.create_checkpoint().await {
+            Ok(checkpoint) => {
+                progress(ApplyEvent::CheckpointCreated {
+                    plugin: plugin_name.to_string(),
+                    checkpoint: checkpoint.id.clone(),
+                });
+                checkpoints.push((plugin_name.to_string(), checkpoint.clone()));
+                Some(checkpoint)
+            }
+            Err(e) => {
+                log::error!("Failed to create checkpoint for {}: {}", plugin_name, e);
+                None
+            }
+        };
+
+        // Phase 2: Calculate diff
+        let current_state = Self::query_timed(plugin_name, plugin.as_ref()).await?;
+        let diff = plugin
+            .calculate_diff(&current_state, plugin_desired_state)
+            .await?;
+        metrics::observe_drift(&diff.plugin, diff.drifted_resources());
+        progress(ApplyEvent::DiffCalculated {
+            plugin: plugin_name.to_string(),
+            actions: diff.actions.len(),
+        });
+        if diff.actions.is_empty() {
+            self.finish_apply(plugin_name, true, started);
+            return Ok(ApplyReport {
+                success: true,
+                results,
+                checkpoints,
+                footprint: None,
+            });
+        }
+
+        // Phase 3: Apply each action on its own
+        let mut failure = None;
+        for (index, action) in diff.actions.iter().enumerate() {
+            progress(ApplyEvent::ActionStarted {
+                plugin: plugin_name.to_string(),
+                index,
+                action: action.clone(),
+            });
+            let step = StateDiff {
+                plugin: diff.plugin.clone(),
+                actions: vec![action.clone()],
+                metadata: diff.metadata.clone(),
+            };
+            let (success, changes, errors) = match plugin.apply_state(&step).await {
+                Ok(result) => {
+                    let outcome = (
+                        result.success,
+                        result.changes_applied.clone(),
+                        result.errors.clone(),
+                    );
+                    results.push(result);
+                    outcome
+                }
+                Err(e) => (false, Vec::new(), vec![e.to_string()]),
+            };
+            progress(ApplyEvent::ActionFinished {
+                plugin: plugin_name.to_string(),
+                index,
+                success,
+                changes,
+                errors: errors.clone(),
+            });
+            if !success {
+                failure = Some(errors.join("; "));
+                break;
+            }
+        }
+
+        let success = match failure {
+            Some(error) => {
+                log::error!("Failed to apply state for {}: {}", plugin_name, error);
+                if let (Some(checkpoint), true) = (&checkpoint, capabilities.supports_rollback) {
+                    let rollback = plugin.rollback(checkpoint).await;
+                    metrics::observe_rollback(plugin_name);
+                    progress(ApplyEvent::RolledBack {
+                        plugin: plugin_name.to_string(),
+                        checkpoint: checkpoint.id.clone(),
+                        error: rollback.err().map(|e| e.to_string()),
+                    });
+                }
+                false
+            }
+            // Phase 4: Verify
+            None if capabilities.supports_verification => {
+                let matches = match plugin.verify_state(plugin_desired_state).await {
+                    Ok(matches) => matches,
+                    Err(e) => {
+                        log::error!("Failed to verify state for {}: {}", plugin_name, e);
+                        false
+                    }
+                };
+                progress(ApplyEvent::Verified {
+                    plugin: plugin_name.to_string(),
+                    matches,
+                });
+                matches
+            }
+            None => true,
+        };
+
+        #[cfg(feature = "streaming-blockchain")]
+        let footprint = {
+            let changes: Vec<&String> = results.iter().flat_map(|r| &r.changes_applied).collect();
+            let errors: Vec<&String> = results.iter().flat_map(|r| &r.errors).collect();
+            self.record_footprint(
+                plugin_name,
+                "apply",
+                serde_json::json!({
+                    "actions": diff.actions,
+                    "desired_hash": diff.metadata.desired_hash,
+                    "success": success,
+                    "changes": changes,
+                    "errors": errors,
+                }),
+            )
+        };
+        #[cfg(not(feature = "streaming-blockchain"))]
+        let footprint = None;
+
+        self.finish_apply(plugin_name, success, started);
+        log::info!(
+            "State apply finished for plugin {}: success={}",
+            plugin_name,
+            success
+        );
+        Ok(ApplyReport {
+            success,
+            results,
+            checkpoints,
+            footprint,
         })
     }
 }
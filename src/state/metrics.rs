@@ -0,0 +1,305 @@
+//! Prometheus metrics for state-manager operations
+//!
+//! Metrics are registered in the default prometheus registry, so they are
+//! exported by anything that serves `prometheus::gather()`, such as the
+//! `/metrics` route of the shared HTTP server.
+
+use crate::cache::{btrfs_cache::CacheStats, BtrfsCache};
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use prometheus::{CounterVec, Gauge, GaugeVec, HistogramOpts, HistogramVec, Opts};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default location of the streaming blockchain
+pub const DEFAULT_BLOCKCHAIN_DIR: &str = "/var/lib/op-dbus/blockchain";
+
+lazy_static! {
+    static ref APPLY_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "opdbus_apply_duration_seconds",
+                "Duration of state applies per plugin",
+            ),
+            &["plugin", "outcome"],
+        )
+        .unwrap(),
+    );
+    static ref APPLIES: CounterVec = register(
+        CounterVec::new(
+            Opts::new(
+                "opdbus_applies_total",
+                "State applies per plugin and outcome"
+            ),
+            &["plugin", "outcome"],
+        )
+        .unwrap(),
+    );
+    static ref DRIFTED_RESOURCES: GaugeVec = register(
+        GaugeVec::new(
+            Opts::new(
+                "opdbus_drifted_resources",
+                "Resources whose current state differs from the desired state",
+            ),
+            &["plugin"],
+        )
+        .unwrap(),
+    );
+    static ref LAST_SUCCESSFUL_APPLY: GaugeVec = register(
+        GaugeVec::new(
+            Opts::new(
+                "opdbus_last_successful_apply_timestamp_seconds",
+                "Unix time of the last successful apply per plugin",
+            ),
+            &["plugin"],
+        )
+        .unwrap(),
+    );
+    static ref ROLLBACKS: CounterVec = register(
+        CounterVec::new(
+            Opts::new(
+                "opdbus_rollbacks_total",
+                "Rollbacks to a checkpoint per plugin"
+            ),
+            &["plugin"],
+        )
+        .unwrap(),
+    );
+    static ref QUERY_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "opdbus_query_state_duration_seconds",
+                "Latency of query_current_state per plugin",
+            ),
+            &["plugin"],
+        )
+        .unwrap(),
+    );
+    static ref BLOCKCHAIN_HEIGHT: Gauge = register(
+        Gauge::new(
+            "opdbus_blockchain_height",
+            "Number of footprints recorded in the streaming blockchain",
+        )
+        .unwrap(),
+    );
+    static ref BLOCKCHAIN_SNAPSHOT_AGE: Gauge = register(
+        Gauge::new(
+            "opdbus_blockchain_last_snapshot_age_seconds",
+            "Seconds since the newest blockchain snapshot was taken",
+        )
+        .unwrap(),
+    );
+    static ref CACHE_HIT_RATIO: Gauge = register(
+        Gauge::new(
+            "opdbus_cache_hit_ratio",
+            "Embedding cache hits divided by lookups",
+        )
+        .unwrap(),
+    );
+    static ref CACHE_ENTRIES: Gauge =
+        register(Gauge::new("opdbus_cache_entries", "Entries in the embedding cache").unwrap(),);
+}
+
+static CACHE: OnceLock<Arc<BtrfsCache>> = OnceLock::new();
+
+/// Register a collector in the default registry
+fn register<C: Collector + Clone + 'static>(collector: C) -> C {
+    prometheus::register(Box::new(collector.clone())).expect("Metric registered twice");
+    collector
+}
+
+fn outcome(success: bool) -> &'static str {
+    if success {
+        "success"
+    } else {
+        "failure"
+    }
+}
+
+fn now_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Record a finished apply for a plugin
+pub fn observe_apply(plugin: &str, success: bool, duration: Duration) {
+    let labels = [plugin, outcome(success)];
+    APPLY_DURATION
+        .with_label_values(&labels)
+        .observe(duration.as_secs_f64());
+    APPLIES.with_label_values(&labels).inc();
+    if success {
+        LAST_SUCCESSFUL_APPLY
+            .with_label_values(&[plugin])
+            .set(now_seconds());
+        DRIFTED_RESOURCES.with_label_values(&[plugin]).set(0.0);
+    }
+}
+
+/// Record a rollback to a checkpoint
+pub fn observe_rollback(plugin: &str) {
+    ROLLBACKS.with_label_values(&[plugin]).inc();
+}
+
+/// Record the latency of querying a plugin's current state
+pub fn observe_query(plugin: &str, duration: Duration) {
+    QUERY_DURATION
+        .with_label_values(&[plugin])
+        .observe(duration.as_secs_f64());
+}
+
+/// Set the drift gauge from a freshly calculated diff's drifted resources
+pub fn observe_drift(plugin: &str, drifted: usize) {
+    DRIFTED_RESOURCES
+        .with_label_values(&[plugin])
+        .set(drifted as f64);
+}
+
+/// Register the cache whose hit ratio is exported
+///
+/// Only the first registered cache is kept.
+pub fn register_cache(cache: Arc<BtrfsCache>) {
+    let _ = CACHE.set(cache);
+}
+
+/// Update the cache gauges from a stats snapshot
+pub fn observe_cache(stats: &CacheStats) {
+    CACHE_HIT_RATIO.set(stats.hit_ratio());
+    CACHE_ENTRIES.set(stats.total_entries as f64);
+}
+
+/// Update the blockchain gauges from the files under `base`
+///
+/// Height is the number of footprints in `timing/`, and snapshot age is
+/// taken from the newest entry in `snapshots/`.
+pub fn observe_blockchain(base: &Path) {
+    let height = std::fs::read_dir(base.join("timing"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                .count()
+        })
+        .unwrap_or(0);
+    BLOCKCHAIN_HEIGHT.set(height as f64);
+
+    let newest = std::fs::read_dir(base.join("snapshots"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.metadata().and_then(|m| m.modified()).ok())
+        .max();
+    if let Some(newest) = newest {
+        let age = SystemTime::now().duration_since(newest).unwrap_or_default();
+        BLOCKCHAIN_SNAPSHOT_AGE.set(age.as_secs_f64());
+    }
+}
+
+/// Directory of the streaming blockchain (`OPDBUS_BLOCKCHAIN_DIR`)
+pub fn blockchain_dir() -> PathBuf {
+    std::env::var("OPDBUS_BLOCKCHAIN_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_BLOCKCHAIN_DIR))
+}
+
+/// Refresh gauges that are sampled rather than updated as events happen
+///
+/// Called before metrics are gathered.
+pub fn refresh() {
+    observe_blockchain(&blockchain_dir());
+    if let Some(cache) = CACHE.get() {
+        match cache.stats() {
+            Ok(stats) => observe_cache(&stats),
+            Err(e) => log::debug!("Failed to read cache stats: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::plugin::{DiffMetadata, StateAction, StateDiff};
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_and_drift_metrics() {
+        let diff = StateDiff {
+            plugin: "metrics-test".to_string(),
+            actions: vec![
+                StateAction::Create {
+                    resource: "a".to_string(),
+                    config: json!({}),
+                },
+                StateAction::NoOp {
+                    resource: "b".to_string(),
+                },
+                StateAction::Delete {
+                    resource: "c".to_string(),
+                },
+            ],
+            metadata: DiffMetadata {
+                timestamp: 0,
+                current_hash: String::new(),
+                desired_hash: String::new(),
+            },
+        };
+        assert_eq!(diff.drifted_resources(), 2);
+        observe_drift(&diff.plugin, diff.drifted_resources());
+        assert_eq!(
+            DRIFTED_RESOURCES.with_label_values(&["metrics-test"]).get(),
+            2.0
+        );
+
+        observe_apply("metrics-test", false, Duration::from_millis(5));
+        observe_rollback("metrics-test");
+        assert_eq!(
+            LAST_SUCCESSFUL_APPLY
+                .with_label_values(&["metrics-test"])
+                .get(),
+            0.0
+        );
+        assert_eq!(ROLLBACKS.with_label_values(&["metrics-test"]).get(), 1.0);
+
+        observe_apply("metrics-test", true, Duration::from_millis(5));
+        assert_eq!(
+            APPLIES
+                .with_label_values(&["metrics-test", "success"])
+                .get(),
+            1.0
+        );
+        assert_eq!(
+            DRIFTED_RESOURCES.with_label_values(&["metrics-test"]).get(),
+            0.0
+        );
+        assert!(
+            LAST_SUCCESSFUL_APPLY
+                .with_label_values(&["metrics-test"])
+                .get()
+                > 0.0
+        );
+
+        let text = prometheus::TextEncoder::new()
+            .encode_to_string(&prometheus::gather())
+            .unwrap();
+        assert!(
+            text.contains("opdbus_applies_total{outcome=\"failure\",plugin=\"metrics-test\"} 1")
+        );
+    }
+
+    #[test]
+    fn test_blockchain_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("timing")).unwrap();
+        std::fs::create_dir_all(dir.path().join("snapshots/state-1")).unwrap();
+        for hash in ["a", "b", "c"] {
+            std::fs::write(dir.path().join(format!("timing/{hash}.json")), "{}").unwrap();
+        }
+
+        observe_blockchain(dir.path());
+        assert_eq!(BLOCKCHAIN_HEIGHT.get(), 3.0);
+        assert!(BLOCKCHAIN_SNAPSHOT_AGE.get() < 60.0);
+    }
+}
@@ -1,11 +1,15 @@
 //! State management - declarative plugin system
 #[cfg(any(feature = "mcp", feature = "web"))]
 pub mod authority;
+#[cfg(feature = "mcp")]
 pub mod auto_plugin;
 pub mod crypto;
 pub mod dbus_plugin_base;
 pub mod dbus_server;
+pub mod health;
 pub mod manager;
+pub mod metrics;
+pub mod plan;
 pub mod plugin;
 pub mod plugin_workflow;
 pub mod plugins;
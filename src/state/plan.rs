@@ -0,0 +1,101 @@
+//! Plans of state changes and the schemas used to describe them
+//!
+//! Front-ends that let an operator review a plan before applying it (the web
+//! UI, the MCP plugin tools) identify the plan by [`plan_hash`] and refuse an
+//! apply whose plan no longer hashes the same.
+
+#![allow(dead_code)] // Plans are reviewed through the web UI and MCP front-ends
+
+use crate::state::plugin::StateAction;
+use crate::encoding::to_hex;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Hash identifying a plan, for confirming exactly what was reviewed
+pub fn plan_hash(actions: &[StateAction]) -> String {
+    let content = serde_json::to_vec(actions).unwrap_or_default();
+    to_hex(&Sha256::digest(&content))
+}
+
+/// JSON Schema describing the shape of `values`, for the editing forms
+///
+/// Plugins do not publish schemas, so this is inferred from their current
+/// and desired state; properties seen in any value are included.
+pub fn infer_schema<'a>(values: impl IntoIterator<Item = &'a Value>) -> Value {
+    let mut schema = Value::Null;
+    for value in values {
+        schema = merge_schema(schema, schema_of(value));
+    }
+    if schema.is_null() {
+        json!({ "type": "object", "properties": {} })
+    } else {
+        schema
+    }
+}
+
+fn schema_of(value: &Value) -> Value {
+    match value {
+        Value::Null => Value::Null,
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "type": "integer" }),
+        Value::Number(_) => json!({ "type": "number" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => json!({ "type": "array", "items": infer_schema(items) }),
+        Value::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), schema_of(value)))
+                .collect();
+            json!({ "type": "object", "properties": properties })
+        }
+    }
+}
+
+fn merge_schema(a: Value, b: Value) -> Value {
+    match (a, b) {
+        (Value::Null, other) | (other, Value::Null) => other,
+        (mut a, b) if a["type"] == "object" && b["type"] == "object" => {
+            if let (Some(into), Some(from)) = (
+                a["properties"].as_object_mut(),
+                b["properties"].as_object().cloned(),
+            ) {
+                for (name, schema) in from {
+                    let merged = merge_schema(into.remove(&name).unwrap_or(Value::Null), schema);
+                    into.insert(name, merged);
+                }
+            }
+            a
+        }
+        (a, b) if a["type"] == b["type"] => a,
+        (a, b) if is_numeric(&a) && is_numeric(&b) => json!({ "type": "number" }),
+        // Mixed types are edited as raw JSON
+        _ => json!({}),
+    }
+}
+
+fn is_numeric(schema: &Value) -> bool {
+    matches!(schema["type"].as_str(), Some("integer" | "number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_schema() {
+        let current = json!({"name": "br0", "mtu": 1500, "ports": ["eth0"]});
+        let desired = json!({"name": "br0", "mtu": 9000.5, "stp": true});
+        let schema = infer_schema([&current, &desired]);
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["name"]["type"], "string");
+        assert_eq!(schema["properties"]["mtu"]["type"], "number");
+        assert_eq!(schema["properties"]["stp"]["type"], "boolean");
+        assert_eq!(schema["properties"]["ports"]["items"]["type"], "string");
+
+        let a = [StateAction::Delete {
+            resource: "x".into(),
+        }];
+        assert_eq!(plan_hash(&a), plan_hash(&a.clone()));
+        assert_ne!(plan_hash(&a), plan_hash(&[]));
+    }
+}
@@ -60,6 +60,16 @@ pub struct StateDiff {
     pub metadata: DiffMetadata,
 }
 
+impl StateDiff {
+    /// Number of actions that would change something
+    pub fn drifted_resources(&self) -> usize {
+        self.actions
+            .iter()
+            .filter(|action| !matches!(action, StateAction::NoOp { .. }))
+            .count()
+    }
+}
+
 /// Metadata about the diff calculation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiffMetadata {
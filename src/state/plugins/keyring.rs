@@ -236,9 +236,9 @@ mod tests {
     async fn test_capabilities() {
         let plugin = KeyringPlugin::new();
         let caps = plugin.capabilities();
-        assert!(caps.can_read);
-        assert!(!caps.can_apply); // Should be read-only
-        assert!(caps.can_diff);
-        assert!(!caps.requires_root);
+        assert!(!caps.supports_rollback); // Secrets are never restored
+        assert!(caps.supports_checkpoints);
+        assert!(caps.supports_verification);
+        assert!(!caps.atomic_operations);
     }
 }
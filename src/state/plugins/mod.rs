@@ -11,6 +11,9 @@ pub mod packagekit;
 pub mod sessdecl;
 pub mod systemd;
 pub mod systemd_networkd;
+pub mod systemd_timer;
+pub mod systemd_transient;
+pub mod systemd_unit_file;
 
 pub mod dnsresolver;
 pub mod pcidecl;
@@ -33,3 +36,31 @@ pub use privacy::PrivacyPlugin;
 pub mod privacy_router;
 #[cfg(feature = "openflow")]
 pub use privacy_router::PrivacyRouterPlugin;
+
+use super::plugin::StatePlugin;
+use std::sync::Arc;
+
+/// Core plugins, by workflow node name
+pub fn builtin() -> Vec<(&'static str, Arc<dyn StatePlugin>)> {
+    vec![
+        ("net", Arc::new(NetStatePlugin::new())),
+        ("systemd", Arc::new(SystemdStatePlugin::new())),
+        ("login1", Arc::new(Login1Plugin::new())),
+        ("lxc", Arc::new(LxcPlugin::new())),
+        ("sessdecl", Arc::new(SessDeclPlugin::new())),
+        ("dns", Arc::new(DnsResolverPlugin::new())),
+        ("pcidecl", Arc::new(PciDeclPlugin::new())),
+        ("packagekit", Arc::new(PackageKitPlugin::new())),
+        #[cfg(feature = "openflow")]
+        ("openflow", Arc::new(OpenFlowPlugin::new())),
+        #[cfg(feature = "openflow")]
+        ("privacy", Arc::new(PrivacyPlugin::new(Default::default()))),
+        #[cfg(feature = "openflow")]
+        ("netmaker", Arc::new(NetmakerPlugin::new(Default::default()))),
+        #[cfg(feature = "openflow")]
+        (
+            "privacy_router",
+            Arc::new(PrivacyRouterPlugin::new(Default::default())),
+        ),
+    ]
+}
@@ -447,7 +447,7 @@ impl NetStatePlugin {
 
     /// Apply OVS internal port configuration
     pub async fn apply_ovs_port_config(&self, config: &InterfaceConfig) -> Result<()> {
-        let client = crate::native::OvsdbClient::new();
+        let _client = crate::native::OvsdbClient::new();
         log::info!("Starting apply_ovs_port_config for {}", config.name);
 
         // Internal ports are created as part of their parent bridge
@@ -308,16 +308,14 @@ impl StatePlugin for PackageKitPlugin {
             let is_installed = self.package_installed(package_name).await?;
 
             match package_config.ensure.as_str() {
-                "installed" => {
-                    if !is_installed {
+                "installed"
+                    if !is_installed => {
                         return Ok(false);
                     }
-                }
-                "removed" => {
-                    if is_installed {
+                "removed"
+                    if is_installed => {
                         return Ok(false);
                     }
-                }
                 _ => {}
             }
         }
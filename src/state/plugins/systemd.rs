@@ -1,26 +1,75 @@
 //! Systemd state plugin - manages systemd via org.freedesktop.systemd1 D-Bus
 //! Maps D-Bus object tree to declarative state
-
+//!
+//! Unit files and drop-ins are written under `/etc/systemd/system`
+//! (override with `OPDBUS_SYSTEMD_UNIT_DIR`), see [`super::systemd_unit_file`].
+//! Timers expand to timer and service units ([`super::systemd_timer`]);
+//! transient units run one-off jobs ([`super::systemd_transient`]).
+
+use super::systemd_timer::{self as timer, TimerConfig, TimerStatus};
+use super::systemd_transient::{transient_unit_name, TransientUnit};
+use super::systemd_unit_file::{self as unit_file, FileSnapshot, UnitFile};
+use crate::native::journal;
 use crate::state::plugin::{
     ApplyResult, Checkpoint, DiffMetadata, PluginCapabilities, StateAction, StateDiff, StatePlugin,
 };
 use crate::state::plugtree::PlugTree;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zbus::zvariant::OwnedObjectPath;
 use zbus::{Connection, Proxy};
 
+/// How long to wait for a job when `OPDBUS_SYSTEMD_JOB_TIMEOUT` is unset
+const DEFAULT_JOB_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// How long a started unit is watched for failing right after its job
+const DEFAULT_SETTLE_WINDOW: Duration = Duration::from_secs(3);
+
+/// How often the unit's state is checked while it settles
+const SETTLE_POLL: Duration = Duration::from_millis(250);
+
+/// Journal lines attached to failed jobs
+const JOURNAL_LINES: usize = 10;
+
 /// Systemd configuration schema - mirrors D-Bus object tree
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SystemdConfig {
     /// Units indexed by name (e.g., "ssh.service")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub units: Option<HashMap<String, UnitConfig>>,
+
+    /// Timers indexed by name (e.g., "backup" for backup.timer)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timers: Option<HashMap<String, TimerConfig>>,
+
+    /// One-off transient jobs indexed by unit name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transient: Option<HashMap<String, TransientUnit>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl SystemdConfig {
+    /// Declared units, including those generated for timers
+    pub fn desired_units(&self) -> Result<Vec<(String, UnitConfig)>> {
+        let mut units: Vec<(String, UnitConfig)> = self
+            .units
+            .iter()
+            .flatten()
+            .map(|(name, config)| (name.clone(), config.clone()))
+            .collect();
+        for (name, timer) in self.timers.iter().flatten() {
+            units.extend(timer.to_units(name)?);
+        }
+        Ok(units)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct UnitConfig {
     /// Desired state: "active", "inactive", "failed", etc.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,14 +86,94 @@ pub struct UnitConfig {
     /// Additional D-Bus properties (dynamic)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub properties: Option<HashMap<String, Value>>,
+
+    /// Contents of the unit file itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_file: Option<UnitFile>,
+
+    /// Drop-ins by name (`<unit>.d/<name>.conf`); `null` removes the drop-in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dropins: Option<BTreeMap<String, Option<UnitFile>>>,
+}
+
+/// Result of a systemd job, as reported by `JobRemoved`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobOutcome {
+    pub unit: String,
+
+    /// "start", "stop" or "restart"
+    pub operation: String,
+
+    /// Job object path
+    pub job: String,
+
+    /// "done", "canceled", "timeout", "failed", "dependency" or "skipped"
+    pub result: String,
+
+    /// Recent journal lines of the unit, for failed jobs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub journal: Vec<String>,
+}
+
+impl JobOutcome {
+    pub fn succeeded(&self) -> bool {
+        matches!(self.result.as_str(), "done" | "skipped")
+    }
+
+    /// One line per job, followed by journal lines if any
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} {}: {}", self.operation, self.unit, self.result);
+        for line in &self.journal {
+            summary.push_str("\n    ");
+            summary.push_str(line);
+        }
+        summary
+    }
 }
 
 /// Systemd state plugin
-pub struct SystemdStatePlugin;
+pub struct SystemdStatePlugin {
+    unit_dir: PathBuf,
+    job_timeout: Duration,
+    settle_window: Duration,
+}
 
 impl SystemdStatePlugin {
     pub fn new() -> Self {
-        Self
+        let unit_dir = std::env::var("OPDBUS_SYSTEMD_UNIT_DIR")
+            .unwrap_or_else(|_| unit_file::DEFAULT_UNIT_DIR.to_string());
+        let job_timeout = std::env::var("OPDBUS_SYSTEMD_JOB_TIMEOUT")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_JOB_TIMEOUT);
+        Self {
+            unit_dir: PathBuf::from(unit_dir),
+            job_timeout,
+            settle_window: DEFAULT_SETTLE_WINDOW,
+        }
+    }
+
+    /// How long to wait for each start/stop/restart job
+    pub fn with_job_timeout(mut self, timeout: Duration) -> Self {
+        self.job_timeout = timeout;
+        self
+    }
+
+    /// How long a unit must keep running after its start job to count as started
+    pub fn with_settle_window(mut self, window: Duration) -> Self {
+        self.settle_window = window;
+        self
+    }
+
+    /// Use a different directory for unit files and drop-ins
+    pub fn with_unit_dir(mut self, unit_dir: impl Into<PathBuf>) -> Self {
+        self.unit_dir = unit_dir.into();
+        self
+    }
+
+    pub fn unit_dir(&self) -> &Path {
+        &self.unit_dir
     }
 
     /// Connect to systemd via D-Bus
@@ -63,10 +192,10 @@ impl SystemdStatePlugin {
         .context("Failed to create systemd D-Bus proxy")
     }
 
-    /// Get unit object path from unit name
+    /// Get unit object path from unit name, loading the unit if needed
     async fn get_unit_path(&self, proxy: &Proxy<'_>, unit_name: &str) -> Result<String> {
         let path: zbus::zvariant::OwnedObjectPath = proxy
-            .call("GetUnit", &(unit_name,))
+            .call("LoadUnit", &(unit_name,))
             .await
             .context(format!("Failed to get unit path for {}", unit_name))?;
 
@@ -95,49 +224,552 @@ impl SystemdStatePlugin {
 
         // Check if enabled (this is a UnitFile property)
         let enabled = self.check_unit_enabled(&proxy, unit_name).await.ok();
+        let (unit_file, dropins) = self.read_unit_files(unit_name)?;
 
         Ok(UnitConfig {
             active_state: Some(active_state),
             enabled,
             masked: None, // TODO: Query mask state
             properties: None,
+            unit_file,
+            dropins,
         })
     }
 
-    /// Check if unit is enabled
-    async fn check_unit_enabled(&self, proxy: &Proxy<'_>, unit_name: &str) -> Result<bool> {
-        let state: String = proxy
-            .call("GetUnitFileState", &(unit_name,))
-            .await
-            .context("Failed to get unit file state")?;
+    /// Read the unit file and drop-ins of a unit from the unit directory
+    #[allow(clippy::type_complexity)]
+    fn read_unit_files(
+        &self,
+        unit_name: &str,
+    ) -> Result<(Option<UnitFile>, Option<BTreeMap<String, Option<UnitFile>>>)> {
+        let unit_path = unit_file::unit_path(&self.unit_dir, unit_name);
+        let file = match std::fs::symlink_metadata(&unit_path) {
+            Ok(metadata) if metadata.is_file() => {
+                unit_file::read_optional(&unit_path)?.map(|text| UnitFile::parse(&text))
+            }
+            _ => None,
+        };
+
+        let mut dropins = BTreeMap::new();
+        let dropin_dir = self.unit_dir.join(format!("{}.d", unit_name));
+        if let Ok(entries) = std::fs::read_dir(&dropin_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "conf") {
+                    if let (Some(stem), Some(text)) =
+                        (path.file_stem(), unit_file::read_optional(&path)?)
+                    {
+                        dropins.insert(
+                            stem.to_string_lossy().to_string(),
+                            Some(UnitFile::parse(&text)),
+                        );
+                    }
+                }
+            }
+        }
 
-        Ok(state == "enabled")
+        Ok((file, (!dropins.is_empty()).then_some(dropins)))
+    }
+
+    /// Write the declared unit file and drop-ins of a unit
+    ///
+    /// Files whose rendered contents already match are left alone. The prior
+    /// contents of every file written are recorded in `prior`. Returns
+    /// whether anything changed.
+    fn write_unit_files(
+        &self,
+        unit_name: &str,
+        config: &UnitConfig,
+        prior: &mut FileSnapshot,
+    ) -> Result<bool> {
+        if !unit_file::valid_file_name(unit_name) {
+            anyhow::bail!("Invalid unit name '{}'", unit_name);
+        }
+
+        let mut files: Vec<(PathBuf, Option<String>)> = Vec::new();
+        if let Some(file) = &config.unit_file {
+            if config.masked == Some(true) {
+                // Writing over the /dev/null symlink would unmask the unit
+                log::warn!("Not writing unit file for masked unit {}", unit_name);
+            } else {
+                files.push((
+                    unit_file::unit_path(&self.unit_dir, unit_name),
+                    Some(file.render()?),
+                ));
+            }
+        }
+        for (name, dropin) in config.dropins.iter().flatten() {
+            if !unit_file::valid_file_name(name) {
+                anyhow::bail!("Invalid drop-in name '{}' for {}", name, unit_name);
+            }
+            let contents = dropin.as_ref().map(|d| d.render()).transpose()?;
+            files.push((
+                unit_file::dropin_path(&self.unit_dir, unit_name, name),
+                contents,
+            ));
+        }
+
+        let mut changed = false;
+        for (path, contents) in files {
+            let existing = unit_file::read_optional(&path)?;
+            if existing == contents {
+                continue;
+            }
+            prior.entry(path.clone()).or_insert(existing);
+            match contents {
+                Some(contents) => unit_file::write_atomic(&path, &contents)?,
+                None => unit_file::remove_file(&path)?,
+            }
+            log::info!("Updated {}", path.display());
+            changed = true;
+        }
+        Ok(changed)
     }
 
-    /// Start a systemd unit
-    async fn start_unit(&self, unit_name: &str) -> Result<()> {
+    /// Whether the declared unit file and drop-ins match the files on disk
+    fn unit_files_match(&self, unit_name: &str, config: &UnitConfig) -> Result<bool> {
+        let (current_file, current_dropins) = self.read_unit_files(unit_name)?;
+
+        if let Some(file) = &config.unit_file {
+            if config.masked != Some(true) && current_file != Some(file.normalized()?) {
+                return Ok(false);
+            }
+        }
+        for (name, dropin) in config.dropins.iter().flatten() {
+            let name = name.strip_suffix(".conf").unwrap_or(name);
+            let current = current_dropins.as_ref().and_then(|d| d.get(name)).cloned();
+            let desired = dropin.as_ref().map(|d| d.normalized()).transpose()?;
+            if current.flatten() != desired {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reload systemd so it picks up changed unit files
+    async fn daemon_reload(&self) -> Result<()> {
         let proxy = self.connect_systemd().await?;
 
-        let _job: zbus::zvariant::OwnedObjectPath = proxy
-            .call("StartUnit", &(unit_name, "replace"))
+        let _: () = proxy
+            .call("Reload", &())
             .await
-            .context(format!("Failed to start unit {}", unit_name))?;
+            .context("Failed to reload systemd")?;
 
-        log::info!("Started systemd unit: {}", unit_name);
+        log::info!("Reloaded systemd manager configuration");
         Ok(())
     }
 
-    /// Stop a systemd unit
-    async fn stop_unit(&self, unit_name: &str) -> Result<()> {
+    /// Restart a unit whose files changed, if it is running and meant to stay so
+    async fn restart_if_required(
+        &self,
+        unit_name: &str,
+        config: &UnitConfig,
+    ) -> Result<Option<JobOutcome>> {
+        let current = self.query_unit(unit_name).await?;
+        let current_state = current.active_state.as_deref().unwrap_or("unknown");
+
+        if !restart_required(config, current_state) {
+            return Ok(None);
+        }
+
+        self.run_job("restart", unit_name, "RestartUnit", &(unit_name, "replace"))
+            .await
+            .map(Some)
+    }
+
+    /// Queue a job and wait for systemd to report its result
+    async fn run_job<B>(
+        &self,
+        operation: &str,
+        unit_name: &str,
+        method: &str,
+        body: &B,
+    ) -> Result<JobOutcome>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
         let proxy = self.connect_systemd().await?;
 
-        let _job: zbus::zvariant::OwnedObjectPath = proxy
-            .call("StopUnit", &(unit_name, "replace"))
+        // systemd only emits job signals while someone is subscribed, and
+        // the stream must exist before the job is queued
+        if let Err(e) = proxy.call::<_, _, ()>("Subscribe", &()).await {
+            log::debug!("systemd Subscribe failed: {}", e);
+        }
+        let mut removed = proxy
+            .receive_signal("JobRemoved")
             .await
-            .context(format!("Failed to stop unit {}", unit_name))?;
+            .context("Failed to subscribe to JobRemoved")?;
 
-        log::info!("Stopped systemd unit: {}", unit_name);
-        Ok(())
+        let job: OwnedObjectPath = proxy
+            .call(method, body)
+            .await
+            .context(format!("Failed to {} unit {}", operation, unit_name))?;
+
+        let wait = async {
+            while let Some(message) = removed.next().await {
+                let (_id, path, _unit, result): (u32, OwnedObjectPath, String, String) =
+                    message.body().deserialize()?;
+                if path == job {
+                    return Ok(result);
+                }
+            }
+            anyhow::bail!("JobRemoved signal stream ended")
+        };
+        let mut result = match tokio::time::timeout(self.job_timeout, wait).await {
+            Ok(result) => result?,
+            Err(_) => {
+                log::warn!(
+                    "Timed out after {:?} waiting for {} job of {}",
+                    self.job_timeout,
+                    operation,
+                    unit_name
+                );
+                "timeout".to_string()
+            }
+        };
+
+        // Simple services finish their start job before they can fail
+        if result == "done" && operation != "stop" {
+            match self.failed_while_settling(&proxy, unit_name).await {
+                Ok(true) => result = "failed".to_string(),
+                Ok(false) => {}
+                Err(e) => log::debug!("Failed to watch {} settle: {}", unit_name, e),
+            }
+        }
+        let _ = proxy.call::<_, _, ()>("Unsubscribe", &()).await;
+
+        let mut outcome = JobOutcome {
+            unit: unit_name.to_string(),
+            operation: operation.to_string(),
+            job: job.to_string(),
+            result,
+            journal: Vec::new(),
+        };
+        if outcome.succeeded() {
+            log::info!("{} job for {}: {}", operation, unit_name, outcome.result);
+        } else {
+            outcome.journal = recent_journal(unit_name).await;
+            log::warn!("{} job for {}: {}", operation, unit_name, outcome.result);
+        }
+        Ok(outcome)
+    }
+
+    /// Watch a unit whose start job is done until the settle window ends
+    ///
+    /// Returns true as soon as the unit fails, or crashes and waits to be
+    /// restarted (SubState `auto-restart`). Only simple and exec services
+    /// finish their start job before the process got anywhere; other units
+    /// are checked once.
+    async fn failed_while_settling(&self, proxy: &Proxy<'_>, unit_name: &str) -> Result<bool> {
+        let unit_path = self.get_unit_path(proxy, unit_name).await?;
+        // Uncached, every read asks systemd
+        let unit_proxy = |interface: &'static str| {
+            zbus::proxy::Builder::<Proxy<'_>>::new(proxy.connection())
+                .destination("org.freedesktop.systemd1")
+                .and_then(|b| b.path(unit_path.clone()))
+                .and_then(|b| b.interface(interface))
+                .map(|b| b.cache_properties(zbus::proxy::CacheProperties::No))
+        };
+        let unit = unit_proxy("org.freedesktop.systemd1.Unit")?.build().await?;
+
+        let service_type: Option<String> = if unit_name.ends_with(".service") {
+            let service = unit_proxy("org.freedesktop.systemd1.Service")?
+                .build()
+                .await?;
+            service.get_property("Type").await.ok()
+        } else {
+            None
+        };
+        let window = match service_type.as_deref() {
+            Some("simple" | "exec" | "idle") => self.settle_window,
+            _ => Duration::ZERO,
+        };
+
+        let deadline = tokio::time::Instant::now() + window;
+        loop {
+            let active_state: String = unit.get_property("ActiveState").await?;
+            let sub_state: String = unit.get_property("SubState").await?;
+            if active_state == "failed" || sub_state == "auto-restart" {
+                return Ok(true);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(SETTLE_POLL).await;
+        }
+    }
+
+    /// Write files for all units, reload once, then bring units to their state
+    async fn apply_units(&self, units: &[(String, UnitConfig)]) -> ApplyResult {
+        let mut changes_applied = Vec::new();
+        let mut errors = Vec::new();
+        let mut prior = FileSnapshot::new();
+        let mut changed_units = Vec::new();
+
+        for (unit_name, config) in units {
+            match self.write_unit_files(unit_name, config, &mut prior) {
+                Ok(true) => {
+                    changes_applied.push(format!("Updated unit files for: {}", unit_name));
+                    changed_units.push(unit_name.as_str());
+                }
+                Ok(false) => {}
+                Err(e) => errors.push(format!(
+                    "Failed to write unit files for {}: {}",
+                    unit_name, e
+                )),
+            }
+        }
+
+        if !prior.is_empty() {
+            if let Err(e) = self.daemon_reload().await {
+                errors.push(format!("Failed to reload systemd: {}", e));
+            }
+        }
+
+        for (unit_name, config) in units {
+            if changed_units.contains(&unit_name.as_str()) {
+                match self.restart_if_required(unit_name, config).await {
+                    Ok(job) => record_jobs(job, &mut changes_applied, &mut errors),
+                    Err(e) => errors.push(format!("Failed to restart {}: {}", unit_name, e)),
+                }
+            }
+
+            match self.apply_unit_config(unit_name, config).await {
+                Ok(jobs) => {
+                    record_jobs(jobs, &mut changes_applied, &mut errors);
+                    changes_applied.push(format!("Applied systemd config for: {}", unit_name));
+                }
+                Err(e) => {
+                    errors.push(format!("Failed to apply config for {}: {}", unit_name, e));
+                }
+            }
+        }
+
+        // Exact prior contents of the files touched, for rolling this apply back
+        let checkpoint = (!prior.is_empty()).then(|| self.file_checkpoint(&prior, false));
+
+        ApplyResult {
+            success: errors.is_empty(),
+            changes_applied,
+            errors,
+            checkpoint,
+        }
+    }
+
+    fn file_checkpoint(&self, files: &FileSnapshot, managed_scan: bool) -> Checkpoint {
+        let timestamp = chrono::Utc::now().timestamp();
+        Checkpoint {
+            id: format!("systemd-{}", timestamp),
+            plugin: "systemd".to_string(),
+            timestamp,
+            state_snapshot: serde_json::to_value(SystemdConfig::default()).unwrap_or(Value::Null),
+            backend_checkpoint: Some(serde_json::json!({
+                "unit_dir": self.unit_dir,
+                "files": files,
+                "managed_scan": managed_scan,
+            })),
+        }
+    }
+
+    /// Restore unit files recorded in a checkpoint and reload systemd
+    async fn restore_unit_files(&self, backend_checkpoint: &Value) -> Result<()> {
+        let Some(files) = backend_checkpoint.get("files") else {
+            return Ok(());
+        };
+        let files: FileSnapshot = serde_json::from_value(files.clone())?;
+        let mut restore = files.clone();
+
+        // Managed files created after a full scan did not exist back then
+        if backend_checkpoint.get("managed_scan") == Some(&Value::Bool(true)) {
+            for path in unit_file::scan_managed(&self.unit_dir)?.into_keys() {
+                restore.entry(path).or_insert(None);
+            }
+        }
+
+        let changed = restore
+            .iter()
+            .filter(|(path, contents)| {
+                unit_file::read_optional(path).ok().as_ref() != Some(*contents)
+            })
+            .count();
+        if changed == 0 {
+            return Ok(());
+        }
+
+        unit_file::restore_files(&restore)?;
+        log::info!("Restored {} unit file(s)", changed);
+        self.daemon_reload().await
+    }
+
+    /// Unit D-Bus proxy for `interface` on a loaded unit
+    async fn unit_proxy(&self, unit_name: &str, interface: &'static str) -> Result<Proxy<'static>> {
+        let proxy = self.connect_systemd().await?;
+        let unit_path = self.get_unit_path(&proxy, unit_name).await?;
+
+        Proxy::new(
+            proxy.connection(),
+            "org.freedesktop.systemd1",
+            unit_path,
+            interface,
+        )
+        .await
+        .context(format!(
+            "Failed to create {} proxy for {}",
+            interface, unit_name
+        ))
+    }
+
+    /// Query state and trigger times of a timer
+    pub async fn query_timer(&self, timer_name: &str) -> Result<TimerStatus> {
+        let timer_name = format!("{}.timer", timer::timer_base(timer_name));
+        let unit = self
+            .unit_proxy(&timer_name, "org.freedesktop.systemd1.Unit")
+            .await?;
+        let timer_proxy = self
+            .unit_proxy(&timer_name, "org.freedesktop.systemd1.Timer")
+            .await?;
+
+        let active_state: String = unit
+            .get_property("ActiveState")
+            .await
+            .unwrap_or_else(|_| "unknown".to_string());
+        let last: u64 = timer_proxy
+            .get_property("LastTriggerUSec")
+            .await
+            .unwrap_or(0);
+        let next_realtime: u64 = timer_proxy
+            .get_property("NextElapseUSecRealtime")
+            .await
+            .unwrap_or(0);
+        let next_monotonic: u64 = timer_proxy
+            .get_property("NextElapseUSecMonotonic")
+            .await
+            .unwrap_or(0);
+        let result: Option<String> = timer_proxy.get_property("Result").await.ok();
+
+        // Monotonic timers (OnBootSec, ...) report time since boot
+        let now = chrono::Utc::now().timestamp_micros() as u64;
+        let next_from_monotonic = timer::uptime_usec()
+            .and_then(|uptime| timer::monotonic_to_realtime_usec(next_monotonic, uptime, now));
+        let next = [
+            timer::format_realtime_usec(next_realtime).map(|_| next_realtime),
+            next_from_monotonic,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        Ok(TimerStatus {
+            active_state,
+            last_trigger: timer::format_realtime_usec(last),
+            next_elapse: next.and_then(timer::format_realtime_usec),
+            result,
+        })
+    }
+
+    /// All loaded timers with their trigger times
+    async fn list_timers(&self) -> Result<HashMap<String, TimerConfig>> {
+        let proxy = self.connect_systemd().await?;
+
+        #[allow(clippy::type_complexity)]
+        let units: Vec<(
+            String,
+            String,
+            String,
+            String,
+            String,
+            String,
+            zbus::zvariant::OwnedObjectPath,
+            u32,
+            String,
+            zbus::zvariant::OwnedObjectPath,
+        )> = proxy
+            .call(
+                "ListUnitsByPatterns",
+                &(Vec::<&str>::new(), vec!["*.timer"]),
+            )
+            .await
+            .context("Failed to list timers")?;
+
+        let mut timers = HashMap::new();
+        for (name, ..) in units {
+            match self.query_timer(&name).await {
+                Ok(status) => {
+                    timers.insert(
+                        timer::timer_base(&name).to_string(),
+                        TimerConfig {
+                            status: Some(status),
+                            ..Default::default()
+                        },
+                    );
+                }
+                Err(e) => log::debug!("Failed to query timer {}: {}", name, e),
+            }
+        }
+        Ok(timers)
+    }
+
+    /// Start a transient service running `job` and wait for its start job
+    pub async fn start_transient_unit(
+        &self,
+        unit_name: &str,
+        job: &TransientUnit,
+    ) -> Result<JobOutcome> {
+        let unit_name = transient_unit_name(unit_name)?;
+        let properties = job.properties()?;
+        let aux: Vec<(&str, Vec<(&str, zbus::zvariant::Value)>)> = Vec::new();
+
+        self.run_job(
+            "start",
+            &unit_name,
+            "StartTransientUnit",
+            &(unit_name.as_str(), "fail", properties, aux),
+        )
+        .await
+    }
+
+    /// Start a transient job unless a unit of that name is already running
+    async fn ensure_transient_unit(
+        &self,
+        unit_name: &str,
+        job: &TransientUnit,
+    ) -> Result<Option<JobOutcome>> {
+        if let Some(state) = self.running_state(unit_name).await? {
+            log::debug!("Transient unit {} is already {}", unit_name, state);
+            return Ok(None);
+        }
+
+        self.start_transient_unit(unit_name, job).await.map(Some)
+    }
+
+    /// ActiveState of a unit that is running or about to, `None` otherwise
+    async fn running_state(&self, unit_name: &str) -> Result<Option<String>> {
+        let current = self.query_unit(unit_name).await?;
+        Ok(current
+            .active_state
+            .filter(|state| matches!(state.as_str(), "active" | "activating" | "reloading")))
+    }
+
+    /// Check if unit is enabled
+    async fn check_unit_enabled(&self, proxy: &Proxy<'_>, unit_name: &str) -> Result<bool> {
+        let state: String = proxy
+            .call("GetUnitFileState", &(unit_name,))
+            .await
+            .context("Failed to get unit file state")?;
+
+        Ok(state == "enabled")
+    }
+
+    /// Start a systemd unit and wait for the job to finish
+    async fn start_unit(&self, unit_name: &str) -> Result<JobOutcome> {
+        self.run_job("start", unit_name, "StartUnit", &(unit_name, "replace"))
+            .await
+    }
+
+    /// Stop a systemd unit and wait for the job to finish
+    async fn stop_unit(&self, unit_name: &str) -> Result<JobOutcome> {
+        self.run_job("stop", unit_name, "StopUnit", &(unit_name, "replace"))
+            .await
     }
 
     /// Enable a systemd unit
@@ -166,8 +798,14 @@ impl SystemdStatePlugin {
         Ok(())
     }
 
-    /// Apply desired unit configuration
-    async fn apply_unit_config(&self, unit_name: &str, config: &UnitConfig) -> Result<()> {
+    /// Apply desired unit configuration, returning the jobs it ran
+    async fn apply_unit_config(
+        &self,
+        unit_name: &str,
+        config: &UnitConfig,
+    ) -> Result<Vec<JobOutcome>> {
+        let mut jobs = Vec::new();
+
         // Apply masked state first (prevents other operations)
         if let Some(desired_masked) = config.masked {
             // Check current mask state via GetUnitFileState
@@ -194,9 +832,9 @@ impl SystemdStatePlugin {
                 .unwrap_or_else(|| "unknown".to_string());
 
             if desired_state == "active" && current_state != "active" {
-                self.start_unit(unit_name).await?;
+                jobs.push(self.start_unit(unit_name).await?);
             } else if desired_state == "inactive" && current_state == "active" {
-                self.stop_unit(unit_name).await?;
+                jobs.push(self.stop_unit(unit_name).await?);
             }
         }
 
@@ -212,7 +850,7 @@ impl SystemdStatePlugin {
             }
         }
 
-        Ok(())
+        Ok(jobs)
     }
 }
 
@@ -230,24 +868,9 @@ impl SystemdStatePlugin {
         unit_name: &str,
         unit_config: &UnitConfig,
     ) -> Result<ApplyResult> {
-        let mut changes_applied = Vec::new();
-        let mut errors = Vec::new();
-
-        match self.apply_unit_config(unit_name, unit_config).await {
-            Ok(_) => {
-                changes_applied.push(format!("Applied config for unit: {}", unit_name));
-            }
-            Err(e) => {
-                errors.push(format!("Failed to apply unit {}: {}", unit_name, e));
-            }
-        }
-
-        Ok(ApplyResult {
-            success: errors.is_empty(),
-            changes_applied,
-            errors,
-            checkpoint: None,
-        })
+        Ok(self
+            .apply_units(&[(unit_name.to_string(), unit_config.clone())])
+            .await)
     }
 
     /// Mask a systemd unit
@@ -325,8 +948,18 @@ impl StatePlugin for SystemdStatePlugin {
     }
 
     async fn query_current_state(&self) -> Result<Value> {
-        // For now, return empty state - full implementation would list all units
-        let config = SystemdConfig { units: None };
+        // Units are not listed yet; timers are, with their trigger times
+        let timers = match self.list_timers().await {
+            Ok(timers) => Some(timers),
+            Err(e) => {
+                log::debug!("Could not list systemd timers: {}", e);
+                None
+            }
+        };
+        let config = SystemdConfig {
+            timers,
+            ..Default::default()
+        };
         Ok(serde_json::to_value(config)?)
     }
 
@@ -336,14 +969,32 @@ impl StatePlugin for SystemdStatePlugin {
 
         let mut actions = Vec::new();
 
-        if let Some(desired_units) = &desired_config.units {
-            for (unit_name, desired_unit) in desired_units {
-                let current_unit = current_config.units.as_ref().and_then(|u| u.get(unit_name));
+        for (unit_name, desired_unit) in desired_config.desired_units()? {
+            let current_unit = current_config
+                .units
+                .as_ref()
+                .and_then(|u| u.get(&unit_name));
+
+            if current_unit != Some(&desired_unit) {
+                actions.push(StateAction::Modify {
+                    resource: unit_name,
+                    changes: serde_json::to_value(&desired_unit)?,
+                });
+            }
+        }
 
-                if current_unit != Some(desired_unit) {
-                    actions.push(StateAction::Modify {
-                        resource: unit_name.clone(),
-                        changes: serde_json::to_value(desired_unit)?,
+        // Transient jobs are started unless already running
+        for (name, job) in desired_config.transient.iter().flatten() {
+            let resource = transient_unit_name(name)?;
+            match self.running_state(&resource).await {
+                Ok(Some(_)) => actions.push(StateAction::NoOp { resource }),
+                running => {
+                    if let Err(e) = running {
+                        log::debug!("Could not query {}: {}", resource, e);
+                    }
+                    actions.push(StateAction::Create {
+                        resource,
+                        config: serde_json::to_value(job)?,
                     });
                 }
             }
@@ -361,49 +1012,58 @@ impl StatePlugin for SystemdStatePlugin {
     }
 
     async fn apply_state(&self, diff: &StateDiff) -> Result<ApplyResult> {
-        let mut changes_applied = Vec::new();
-        let mut errors = Vec::new();
+        let mut units = Vec::new();
+        let mut jobs = Vec::new();
 
         for action in &diff.actions {
-            if let StateAction::Modify { resource, changes } = action {
-                let unit_config: UnitConfig = serde_json::from_value(changes.clone())?;
-
-                match self.apply_unit_config(resource, &unit_config).await {
-                    Ok(_) => {
-                        changes_applied.push(format!("Applied systemd config for: {}", resource));
-                    }
-                    Err(e) => {
-                        errors.push(format!("Failed to apply config for {}: {}", resource, e));
-                    }
+            match action {
+                StateAction::Modify { resource, changes } => {
+                    let unit_config: UnitConfig = serde_json::from_value(changes.clone())?;
+                    units.push((resource.clone(), unit_config));
                 }
+                StateAction::Create { resource, config } => {
+                    let job: TransientUnit = serde_json::from_value(config.clone())?;
+                    jobs.push((resource.clone(), job));
+                }
+                _ => {}
             }
         }
 
-        Ok(ApplyResult {
-            success: errors.is_empty(),
-            changes_applied,
-            errors,
-            checkpoint: None,
-        })
+        let mut result = self.apply_units(&units).await;
+
+        for (unit_name, job) in jobs {
+            match self.ensure_transient_unit(&unit_name, &job).await {
+                Ok(job) => record_jobs(job, &mut result.changes_applied, &mut result.errors),
+                Err(e) => result.errors.push(format!(
+                    "Failed to start transient unit {}: {}",
+                    unit_name, e
+                )),
+            }
+        }
+        result.success = result.errors.is_empty();
+
+        Ok(result)
     }
 
     async fn verify_state(&self, desired: &Value) -> Result<bool> {
         let desired_config: SystemdConfig = serde_json::from_value(desired.clone())?;
 
-        if let Some(units) = &desired_config.units {
-            for (unit_name, desired_unit) in units {
-                let current = self.query_unit(unit_name).await?;
+        for (unit_name, desired_unit) in desired_config.desired_units()? {
+            if !self.unit_files_match(&unit_name, &desired_unit)? {
+                return Ok(false);
+            }
 
-                if let Some(ref desired_state) = desired_unit.active_state {
-                    if current.active_state.as_ref() != Some(desired_state) {
-                        return Ok(false);
-                    }
+            let current = self.query_unit(&unit_name).await?;
+
+            if let Some(ref desired_state) = desired_unit.active_state {
+                if current.active_state.as_ref() != Some(desired_state) {
+                    return Ok(false);
                 }
+            }
 
-                if let Some(desired_enabled) = desired_unit.enabled {
-                    if current.enabled != Some(desired_enabled) {
-                        return Ok(false);
-                    }
+            if let Some(desired_enabled) = desired_unit.enabled {
+                if current.enabled != Some(desired_enabled) {
+                    return Ok(false);
                 }
             }
         }
@@ -413,16 +1073,19 @@ impl StatePlugin for SystemdStatePlugin {
 
     async fn create_checkpoint(&self) -> Result<Checkpoint> {
         let current = self.query_current_state().await?;
+        // Every file op-dbus manages; rollback also removes managed files created later
+        let managed = unit_file::scan_managed(&self.unit_dir)?;
         Ok(Checkpoint {
-            id: format!("systemd-{}", chrono::Utc::now().timestamp()),
-            plugin: self.name().to_string(),
-            timestamp: chrono::Utc::now().timestamp(),
             state_snapshot: current,
-            backend_checkpoint: None,
+            ..self.file_checkpoint(&managed, true)
         })
     }
 
     async fn rollback(&self, checkpoint: &Checkpoint) -> Result<()> {
+        if let Some(backend_checkpoint) = &checkpoint.backend_checkpoint {
+            self.restore_unit_files(backend_checkpoint).await?;
+        }
+
         let old_config: SystemdConfig = serde_json::from_value(checkpoint.state_snapshot.clone())?;
 
         if let Some(units) = old_config.units {
@@ -444,6 +1107,43 @@ impl StatePlugin for SystemdStatePlugin {
     }
 }
 
+/// Add job results to an apply's changes, or its errors if the job failed
+fn record_jobs(
+    jobs: impl IntoIterator<Item = JobOutcome>,
+    changes_applied: &mut Vec<String>,
+    errors: &mut Vec<String>,
+) {
+    for job in jobs {
+        if job.succeeded() {
+            changes_applied.push(job.summary());
+        } else {
+            errors.push(job.summary());
+        }
+    }
+}
+
+/// Recent journal messages of a unit, best effort
+async fn recent_journal(unit_name: &str) -> Vec<String> {
+    let unit = unit_name.to_string();
+    match tokio::task::spawn_blocking(move || journal::unit_messages(&unit, JOURNAL_LINES)).await {
+        Ok(Ok(lines)) => lines.into_iter().map(|line| line.message).collect(),
+        Ok(Err(e)) => {
+            log::debug!("Could not read journal for {}: {}", unit_name, e);
+            Vec::new()
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether a unit whose files changed must be restarted to pick them up
+fn restart_required(desired: &UnitConfig, current_state: &str) -> bool {
+    if desired.masked == Some(true) || desired.active_state.as_deref() == Some("inactive") {
+        // It is about to be stopped anyway
+        return false;
+    }
+    matches!(current_state, "active" | "activating" | "reloading")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -466,4 +1166,131 @@ mod tests {
         }
         // If none succeed, we at least reached D-Bus paths without panicking.
     }
+
+    fn unit(value: Value) -> UnitConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_restart_required() {
+        let running = unit(serde_json::json!({ "active_state": "active" }));
+        let stopping = unit(serde_json::json!({ "active_state": "inactive" }));
+        let unspecified = unit(serde_json::json!({}));
+
+        assert!(restart_required(&running, "active"));
+        assert!(restart_required(&unspecified, "activating"));
+        assert!(!restart_required(&unspecified, "inactive"));
+        assert!(!restart_required(&running, "failed"));
+        assert!(!restart_required(&stopping, "active"));
+    }
+
+    #[tokio::test]
+    async fn test_unit_files_written_and_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = SystemdStatePlugin::new().with_unit_dir(dir.path());
+        let existing_dropin = unit_file::dropin_path(dir.path(), "ssh.service", "20-local");
+        unit_file::write_atomic(&existing_dropin, "[Service]\nNice=5\n").unwrap();
+
+        let checkpoint = plugin.create_checkpoint().await.unwrap();
+        let config = unit(serde_json::json!({
+            "unit_file": {
+                "Unit": { "Description": "Backup agent" },
+                "Service": { "ExecStart": "/usr/bin/backup", "NoNewPrivileges": true }
+            },
+            "dropins": { "20-local": null, "10-limits": { "Service": { "MemoryMax": "512M" } } }
+        }));
+
+        let mut prior = FileSnapshot::new();
+        assert!(plugin
+            .write_unit_files("ssh.service", &config, &mut prior)
+            .unwrap());
+        assert!(plugin.unit_files_match("ssh.service", &config).unwrap());
+        assert_eq!(prior.len(), 3);
+        assert!(!existing_dropin.exists());
+        assert_eq!(
+            prior.get(&existing_dropin),
+            Some(&Some("[Service]\nNice=5\n".to_string()))
+        );
+
+        // Unchanged files are not rewritten
+        let mut again = FileSnapshot::new();
+        assert!(!plugin
+            .write_unit_files("ssh.service", &config, &mut again)
+            .unwrap());
+        assert!(again.is_empty());
+
+        let (file, dropins) = plugin.read_unit_files("ssh.service").unwrap();
+        assert_eq!(
+            file,
+            config.unit_file.as_ref().map(|f| f.normalized().unwrap())
+        );
+        assert!(dropins.unwrap().contains_key("10-limits"));
+
+        // The file snapshot taken during apply restores the unmanaged drop-in;
+        // the full checkpoint removes the managed files created since. Reload
+        // may fail without a system bus, the files are restored first.
+        let _ = plugin
+            .restore_unit_files(
+                &plugin
+                    .file_checkpoint(&prior, false)
+                    .backend_checkpoint
+                    .unwrap(),
+            )
+            .await;
+        assert!(existing_dropin.exists());
+        assert!(!unit_file::unit_path(dir.path(), "ssh.service").exists());
+
+        plugin
+            .write_unit_files("ssh.service", &config, &mut FileSnapshot::new())
+            .unwrap();
+        let _ = plugin
+            .restore_unit_files(checkpoint.backend_checkpoint.as_ref().unwrap())
+            .await;
+        assert!(!unit_file::unit_path(dir.path(), "ssh.service").exists());
+        assert!(!unit_file::dropin_path(dir.path(), "ssh.service", "10-limits").exists());
+    }
+
+    #[test]
+    fn test_job_results_recorded() {
+        let job = |result: &str, journal: &[&str]| JobOutcome {
+            unit: "backup.service".to_string(),
+            operation: "start".to_string(),
+            job: "/org/freedesktop/systemd1/job/42".to_string(),
+            result: result.to_string(),
+            journal: journal.iter().map(|l| l.to_string()).collect(),
+        };
+
+        let mut changes = Vec::new();
+        let mut errors = Vec::new();
+        record_jobs(
+            [
+                job("done", &[]),
+                job("failed", &["disk full", "Main process exited, code=exited"]),
+                job("dependency", &[]),
+            ],
+            &mut changes,
+            &mut errors,
+        );
+
+        assert_eq!(changes, ["start backup.service: done"]);
+        assert_eq!(
+            errors,
+            [
+                "start backup.service: failed\n    disk full\n    Main process exited, code=exited",
+                "start backup.service: dependency"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_unit_names_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = SystemdStatePlugin::new().with_unit_dir(dir.path());
+        let config =
+            unit(serde_json::json!({ "unit_file": { "Service": { "ExecStart": "/bin/true" } } }));
+
+        assert!(plugin
+            .write_unit_files("../escape.service", &config, &mut FileSnapshot::new())
+            .is_err());
+    }
 }
@@ -0,0 +1,246 @@
+//! Timers managed by the systemd plugin
+//!
+//! A timer declaration expands to a `<name>.timer` unit and, optionally, the
+//! `<name>.service` unit it triggers:
+//!
+//! ```json
+//! {
+//!   "timers": {
+//!     "backup": {
+//!       "on_calendar": "*-*-* 02:00:00",
+//!       "persistent": true,
+//!       "service": { "Service": { "Type": "oneshot", "ExecStart": "/usr/bin/backup" } }
+//!     }
+//!   }
+//! }
+//! ```
+
+use super::systemd::UnitConfig;
+use super::systemd_unit_file::{Directive, UnitFile};
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TimerConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Calendar expressions (`OnCalendar=`), a string or a list
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "one_or_many"
+    )]
+    pub on_calendar: Vec<String>,
+
+    /// Delay after boot (`OnBootSec=`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_boot_sec: Option<String>,
+
+    /// Interval after the last activation (`OnUnitActiveSec=`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_unit_active_sec: Option<String>,
+
+    /// Catch up on runs missed while powered off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub randomized_delay_sec: Option<String>,
+
+    /// Unit to trigger, defaults to the service of the same name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+
+    /// Contents of the triggered `<name>.service`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<UnitFile>,
+
+    /// Whether the timer is enabled and running (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Trigger times, reported by queries and ignored on apply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TimerStatus>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TimerStatus {
+    pub active_state: String,
+
+    /// Last time the timer elapsed (RFC 3339)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_trigger: Option<String>,
+
+    /// Next time the timer elapses (RFC 3339)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_elapse: Option<String>,
+
+    /// Result of the last run: "success", "resources", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Timer name without the `.timer` suffix
+pub fn timer_base(name: &str) -> &str {
+    name.strip_suffix(".timer").unwrap_or(name)
+}
+
+impl TimerConfig {
+    /// Units making up this timer, service first
+    pub fn to_units(&self, name: &str) -> Result<Vec<(String, UnitConfig)>> {
+        let base = timer_base(name);
+        if self.on_calendar.is_empty()
+            && self.on_boot_sec.is_none()
+            && self.on_unit_active_sec.is_none()
+        {
+            anyhow::bail!(
+                "Timer {} needs on_calendar, on_boot_sec or on_unit_active_sec",
+                base
+            );
+        }
+
+        let mut file = UnitFile::default();
+        let description = self
+            .description
+            .clone()
+            .unwrap_or_else(|| format!("{} timer", base));
+        file.unit
+            .insert("Description".to_string(), Directive::Text(description));
+
+        let timer = &mut file.timer;
+        if !self.on_calendar.is_empty() {
+            timer.insert(
+                "OnCalendar".to_string(),
+                Directive::List(self.on_calendar.clone()),
+            );
+        }
+        let optional = [
+            ("OnBootSec", &self.on_boot_sec),
+            ("OnUnitActiveSec", &self.on_unit_active_sec),
+            ("RandomizedDelaySec", &self.randomized_delay_sec),
+            ("Unit", &self.unit),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                timer.insert(key.to_string(), Directive::Text(value.clone()));
+            }
+        }
+        if let Some(persistent) = self.persistent {
+            timer.insert("Persistent".to_string(), Directive::Bool(persistent));
+        }
+        file.install.insert(
+            "WantedBy".to_string(),
+            Directive::Text("timers.target".to_string()),
+        );
+
+        let enabled = self.enabled.unwrap_or(true);
+        let mut units = Vec::new();
+        if let Some(service) = &self.service {
+            units.push((
+                format!("{}.service", base),
+                UnitConfig {
+                    unit_file: Some(service.clone()),
+                    ..Default::default()
+                },
+            ));
+        }
+        units.push((
+            format!("{}.timer", base),
+            UnitConfig {
+                active_state: Some(if enabled { "active" } else { "inactive" }.to_string()),
+                enabled: Some(enabled),
+                unit_file: Some(file),
+                ..Default::default()
+            },
+        ));
+        Ok(units)
+    }
+}
+
+/// Format a CLOCK_REALTIME timestamp in microseconds; 0 and `u64::MAX` mean unset
+pub fn format_realtime_usec(usec: u64) -> Option<String> {
+    if usec == 0 || usec == u64::MAX {
+        return None;
+    }
+    chrono::DateTime::from_timestamp_micros(i64::try_from(usec).ok()?).map(|t| t.to_rfc3339())
+}
+
+/// Convert a CLOCK_MONOTONIC timestamp to wall-clock time given the current uptime
+pub fn monotonic_to_realtime_usec(usec: u64, uptime_usec: u64, now_usec: u64) -> Option<u64> {
+    if usec == 0 || usec == u64::MAX {
+        return None;
+    }
+    (now_usec + usec).checked_sub(uptime_usec)
+}
+
+/// Current CLOCK_MONOTONIC-ish uptime from /proc/uptime
+pub fn uptime_usec() -> Option<u64> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some((seconds * 1_000_000.0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_expands_to_units() {
+        let timer: TimerConfig = serde_json::from_value(serde_json::json!({
+            "on_calendar": "*-*-* 02:00:00",
+            "on_boot_sec": "15min",
+            "persistent": true,
+            "service": { "Service": { "Type": "oneshot", "ExecStart": "/usr/bin/backup" } }
+        }))
+        .unwrap();
+
+        let units = timer.to_units("backup.timer").unwrap();
+        assert_eq!(units[0].0, "backup.service");
+        assert_eq!(units[0].1.enabled, None);
+        assert_eq!(units[1].0, "backup.timer");
+        assert_eq!(units[1].1.active_state.as_deref(), Some("active"));
+
+        let rendered = units[1].1.unit_file.as_ref().unwrap().render().unwrap();
+        assert!(rendered
+            .contains("[Timer]\nOnBootSec=15min\nOnCalendar=*-*-* 02:00:00\nPersistent=yes\n"));
+        assert!(rendered.contains("[Install]\nWantedBy=timers.target\n"));
+
+        assert!(TimerConfig::default().to_units("empty").is_err());
+    }
+
+    #[test]
+    fn test_trigger_time_conversion() {
+        assert_eq!(format_realtime_usec(0), None);
+        assert_eq!(format_realtime_usec(u64::MAX), None);
+        assert_eq!(
+            format_realtime_usec(1_700_000_000_000_000).as_deref(),
+            Some("2023-11-14T22:13:20+00:00")
+        );
+
+        // Elapses 60s after boot, 10s of uptime so far
+        assert_eq!(
+            monotonic_to_realtime_usec(60_000_000, 10_000_000, 1_700_000_000_000_000),
+            Some(1_700_000_050_000_000)
+        );
+        assert_eq!(monotonic_to_realtime_usec(u64::MAX, 0, 0), None);
+    }
+}
@@ -0,0 +1,203 @@
+//! Transient units started through `StartTransientUnit`
+//!
+//! One-off jobs run as transient services with resource limits passed as
+//! D-Bus properties, without writing unit files:
+//!
+//! ```json
+//! {
+//!   "transient": {
+//!     "reindex.service": {
+//!       "exec_start": ["/usr/bin/op-dbus", "index", "rebuild"],
+//!       "memory_max": "512M",
+//!       "cpu_quota": "50%"
+//!     }
+//!   }
+//! }
+//! ```
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zbus::zvariant::Value;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TransientUnit {
+    /// Command line; the first element must be an absolute path
+    pub exec_start: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Memory limit in bytes or with a K/M/G/T suffix, or "infinity"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<String>,
+
+    /// CPU time limit as a percentage of one CPU ("50%", "200%")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks_max: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<BTreeMap<String, String>>,
+
+    /// Keep the unit active after the command exits, so later applies
+    /// do not run it again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remain_after_exit: Option<bool>,
+}
+
+/// Unit name for a transient job; bare names become services
+pub fn transient_unit_name(name: &str) -> Result<String> {
+    if name.ends_with(".scope") {
+        anyhow::bail!(
+            "Transient scopes need existing processes, use a .service for {}",
+            name
+        );
+    }
+    if name.ends_with(".service") {
+        Ok(name.to_string())
+    } else {
+        Ok(format!("{}.service", name))
+    }
+}
+
+impl TransientUnit {
+    /// Properties for `StartTransientUnit` (signature `a(sv)`)
+    pub fn properties(&self) -> Result<Vec<(&'static str, Value<'static>)>> {
+        let program = self
+            .exec_start
+            .first()
+            .context("Transient unit needs exec_start")?;
+        if !program.starts_with('/') {
+            anyhow::bail!("exec_start must use an absolute path, got '{}'", program);
+        }
+
+        let mut properties: Vec<(&'static str, Value<'static>)> = vec![(
+            "ExecStart",
+            Value::from(vec![(program.clone(), self.exec_start.clone(), false)]),
+        )];
+
+        if let Some(description) = &self.description {
+            properties.push(("Description", Value::from(description.clone())));
+        }
+        if let Some(memory_max) = &self.memory_max {
+            properties.push(("MemoryMax", Value::from(parse_bytes(memory_max)?)));
+        }
+        if let Some(cpu_quota) = &self.cpu_quota {
+            properties.push((
+                "CPUQuotaPerSecUSec",
+                Value::from(parse_cpu_quota(cpu_quota)?),
+            ));
+        }
+        if let Some(tasks_max) = self.tasks_max {
+            properties.push(("TasksMax", Value::from(tasks_max)));
+        }
+        if let Some(user) = &self.user {
+            properties.push(("User", Value::from(user.clone())));
+        }
+        if let Some(dir) = &self.working_directory {
+            properties.push(("WorkingDirectory", Value::from(dir.clone())));
+        }
+        if let Some(environment) = &self.environment {
+            let assignments: Vec<String> = environment
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            properties.push(("Environment", Value::from(assignments)));
+        }
+        if let Some(remain) = self.remain_after_exit {
+            properties.push(("RemainAfterExit", Value::from(remain)));
+        }
+        Ok(properties)
+    }
+}
+
+/// Parse a systemd byte size ("512M", "1G", "4096", "infinity")
+pub fn parse_bytes(value: &str) -> Result<u64> {
+    let value = value.trim();
+    if value == "infinity" {
+        return Ok(u64::MAX);
+    }
+    let (digits, multiplier) = match value.char_indices().last() {
+        Some((idx, suffix)) if suffix.is_ascii_alphabetic() => {
+            let multiplier: u64 = match suffix.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => anyhow::bail!("Unknown size suffix in '{}'", value),
+            };
+            (&value[..idx], multiplier)
+        }
+        _ => (value, 1),
+    };
+    let number: u64 = digits
+        .parse()
+        .with_context(|| format!("Invalid size '{}'", value))?;
+    number
+        .checked_mul(multiplier)
+        .with_context(|| format!("Size '{}' is too large", value))
+}
+
+/// Parse a CPU quota percentage into CPU microseconds per second
+pub fn parse_cpu_quota(value: &str) -> Result<u64> {
+    let percent: u64 = value
+        .trim()
+        .strip_suffix('%')
+        .with_context(|| format!("CPU quota '{}' must be a percentage", value))?
+        .parse()
+        .with_context(|| format!("Invalid CPU quota '{}'", value))?;
+    if percent == 0 {
+        anyhow::bail!("CPU quota must be above 0%");
+    }
+    Ok(percent * 10_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_limits() {
+        assert_eq!(parse_bytes("512M").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_bytes("4096").unwrap(), 4096);
+        assert_eq!(parse_bytes("infinity").unwrap(), u64::MAX);
+        assert!(parse_bytes("12Q").is_err());
+
+        assert_eq!(parse_cpu_quota("50%").unwrap(), 500_000);
+        assert_eq!(parse_cpu_quota("200%").unwrap(), 2_000_000);
+        assert!(parse_cpu_quota("0.5").is_err());
+    }
+
+    #[test]
+    fn test_transient_properties() {
+        let unit: TransientUnit = serde_json::from_value(serde_json::json!({
+            "exec_start": ["/usr/bin/reindex", "--full"],
+            "memory_max": "1G",
+            "cpu_quota": "25%"
+        }))
+        .unwrap();
+
+        let properties = unit.properties().unwrap();
+        let names: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["ExecStart", "MemoryMax", "CPUQuotaPerSecUSec"]);
+        assert_eq!(properties[1].1, Value::from(1u64 << 30));
+        assert_eq!(properties[0].1.value_signature().to_string(), "a(sasb)");
+
+        let relative = TransientUnit {
+            exec_start: vec!["reindex".to_string()],
+            ..Default::default()
+        };
+        assert!(relative.properties().is_err());
+        assert_eq!(transient_unit_name("reindex").unwrap(), "reindex.service");
+        assert!(transient_unit_name("job.scope").is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Unit files and drop-ins managed by the systemd plugin
+//!
+//! Units are declared as typed sections and rendered to systemd's INI format:
+//!
+//! ```json
+//! {
+//!   "unit_file": {
+//!     "Unit": { "Description": "Backup agent" },
+//!     "Service": { "ExecStart": "/usr/bin/backup", "Restart": "on-failure" },
+//!     "Install": { "WantedBy": "multi-user.target" }
+//!   },
+//!   "dropins": { "10-limits": { "Service": { "MemoryMax": "512M" } } }
+//! }
+//! ```
+//!
+//! Booleans render as `yes`/`no`, lists render as repeated directives (an
+//! empty first element emits the `Key=` reset drop-ins need).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// First line of every file written by op-dbus
+pub const MANAGED_HEADER: &str = "# Managed by op-dbus - local changes will be overwritten";
+
+/// Default directory for administrator unit files
+pub const DEFAULT_UNIT_DIR: &str = "/etc/systemd/system";
+
+/// Value of a single directive
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Directive {
+    Bool(bool),
+    Integer(i64),
+    Text(String),
+    List(Vec<String>),
+}
+
+impl Directive {
+    fn values(&self) -> Vec<String> {
+        match self {
+            Directive::Bool(true) => vec!["yes".to_string()],
+            Directive::Bool(false) => vec!["no".to_string()],
+            Directive::Integer(n) => vec![n.to_string()],
+            Directive::Text(s) => vec![s.clone()],
+            Directive::List(items) => items.clone(),
+        }
+    }
+}
+
+/// Directives of one section, keyed by name
+pub type Section = BTreeMap<String, Directive>;
+
+/// Contents of a unit file or drop-in
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UnitFile {
+    #[serde(rename = "Unit", default, skip_serializing_if = "Section::is_empty")]
+    pub unit: Section,
+
+    #[serde(rename = "Service", default, skip_serializing_if = "Section::is_empty")]
+    pub service: Section,
+
+    #[serde(rename = "Timer", default, skip_serializing_if = "Section::is_empty")]
+    pub timer: Section,
+
+    #[serde(rename = "Install", default, skip_serializing_if = "Section::is_empty")]
+    pub install: Section,
+
+    /// Any other section (Socket, Path, Mount, ...)
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Section>,
+}
+
+impl UnitFile {
+    /// Render to unit file syntax, starting with [`MANAGED_HEADER`]
+    pub fn render(&self) -> Result<String> {
+        let mut out = String::from(MANAGED_HEADER);
+        out.push('\n');
+
+        let mut sections: Vec<(&str, &Section)> = vec![
+            ("Unit", &self.unit),
+            ("Service", &self.service),
+            ("Timer", &self.timer),
+        ];
+        sections.extend(
+            self.other
+                .iter()
+                .map(|(name, section)| (name.as_str(), section)),
+        );
+        sections.push(("Install", &self.install));
+
+        for (name, section) in sections {
+            if section.is_empty() {
+                continue;
+            }
+            if !valid_identifier(name) {
+                anyhow::bail!("Invalid section name '{}'", name);
+            }
+            out.push_str(&format!("\n[{}]\n", name));
+            for (key, directive) in section {
+                if !valid_identifier(key) {
+                    anyhow::bail!("Invalid directive name '{}' in [{}]", key, name);
+                }
+                for value in directive.values() {
+                    if value.contains('\n') || value.contains('\r') {
+                        anyhow::bail!("Value of {} in [{}] contains a newline", key, name);
+                    }
+                    out.push_str(&format!("{}={}\n", key, value));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Parse unit file syntax; repeated directives become lists
+    pub fn parse(text: &str) -> Self {
+        let mut file = UnitFile::default();
+        let mut current: Option<String> = None;
+        let mut pending = String::new();
+
+        for raw in text.lines() {
+            // Backslash continues a directive on the next line
+            if let Some(stripped) = raw.trim_end().strip_suffix('\\') {
+                pending.push_str(stripped);
+                pending.push(' ');
+                continue;
+            }
+            let line = format!("{}{}", pending, raw);
+            pending.clear();
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                current = Some(name.trim().to_string());
+                continue;
+            }
+            let (Some(section), Some((key, value))) = (&current, line.split_once('=')) else {
+                continue;
+            };
+
+            let entry = file.section_mut(section).entry(key.trim().to_string());
+            let value = value.trim().to_string();
+            match entry {
+                std::collections::btree_map::Entry::Vacant(slot) => {
+                    slot.insert(Directive::Text(value));
+                }
+                std::collections::btree_map::Entry::Occupied(mut slot) => {
+                    let mut values = slot.get().values();
+                    values.push(value);
+                    slot.insert(Directive::List(values));
+                }
+            }
+        }
+        file
+    }
+
+    /// Canonical form for comparisons (`true` and `"yes"` compare equal)
+    pub fn normalized(&self) -> Result<Self> {
+        Ok(Self::parse(&self.render()?))
+    }
+
+    fn section_mut(&mut self, name: &str) -> &mut Section {
+        match name {
+            "Unit" => &mut self.unit,
+            "Service" => &mut self.service,
+            "Timer" => &mut self.timer,
+            "Install" => &mut self.install,
+            other => self.other.entry(other.to_string()).or_default(),
+        }
+    }
+}
+
+fn valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ')
+}
+
+/// Whether `name` is safe to use as a unit or drop-in file name
+pub fn valid_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains('/')
+        && !name.contains('\0')
+        && !name.starts_with('.')
+}
+
+/// Path of a unit file
+pub fn unit_path(unit_dir: &Path, unit: &str) -> PathBuf {
+    unit_dir.join(unit)
+}
+
+/// Path of a drop-in (`<unit>.d/<name>.conf`)
+pub fn dropin_path(unit_dir: &Path, unit: &str, name: &str) -> PathBuf {
+    let name = name.strip_suffix(".conf").unwrap_or(name);
+    unit_dir
+        .join(format!("{}.d", unit))
+        .join(format!("{}.conf", name))
+}
+
+/// Read a file, returning `None` if it does not exist
+pub fn read_optional(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Write `contents` to `path` via a temporary file and rename
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let parent = path
+        .parent()
+        .with_context(|| format!("Invalid path {}", path.display()))?;
+    std::fs::create_dir_all(parent)?;
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid path {}", path.display()))?
+        .to_string_lossy();
+    let tmp = parent.join(format!(".{}.op-dbus-tmp", file_name));
+
+    let mut file = std::fs::File::create(&tmp)
+        .with_context(|| format!("Failed to create {}", tmp.display()))?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::set_permissions(&tmp, std::os::unix::fs::PermissionsExt::from_mode(0o644))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    // Persist the rename itself
+    if let Ok(dir) = std::fs::File::open(parent) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Remove a file if present, and its drop-in directory once empty
+pub fn remove_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+    }
+    if let Some(parent) = path.parent() {
+        if parent.extension().is_some_and(|ext| ext == "d") {
+            // Only succeeds when empty
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+    Ok(())
+}
+
+/// Prior contents of files about to be changed (`None` = did not exist)
+pub type FileSnapshot = BTreeMap<PathBuf, Option<String>>;
+
+/// Restore files to the contents recorded in `snapshot`
+pub fn restore_files(snapshot: &FileSnapshot) -> Result<()> {
+    for (path, contents) in snapshot {
+        match contents {
+            Some(contents) => write_atomic(path, contents)?,
+            None => remove_file(path)?,
+        }
+    }
+    Ok(())
+}
+
+/// All unit files and drop-ins in `unit_dir` written by op-dbus
+pub fn scan_managed(unit_dir: &Path) -> Result<FileSnapshot> {
+    let mut managed = FileSnapshot::new();
+    let Ok(entries) = std::fs::read_dir(unit_dir) else {
+        return Ok(managed);
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_file() {
+            record_if_managed(&path, &mut managed)?;
+        } else if file_type.is_dir() && path.extension().is_some_and(|ext| ext == "d") {
+            for dropin in std::fs::read_dir(&path)?.flatten() {
+                let dropin = dropin.path();
+                if dropin.extension().is_some_and(|ext| ext == "conf") {
+                    record_if_managed(&dropin, &mut managed)?;
+                }
+            }
+        }
+    }
+    Ok(managed)
+}
+
+/// Record `path` if it starts with [`MANAGED_HEADER`]
+///
+/// Files are checked as bytes: other files in the unit directory need not
+/// be UTF-8, and those op-dbus cannot read are not its own.
+fn record_if_managed(path: &Path, managed: &mut FileSnapshot) -> Result<()> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            log::debug!("Skipping unreadable {}: {}", path.display(), e);
+            return Ok(());
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    if !bytes.starts_with(MANAGED_HEADER.as_bytes()) {
+        return Ok(());
+    }
+    let contents = String::from_utf8(bytes)
+        .with_context(|| format!("Managed file {} is not UTF-8", path.display()))?;
+    managed.insert(path.to_path_buf(), Some(contents));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> UnitFile {
+        serde_json::from_value(serde_json::json!({
+            "Unit": { "Description": "Backup agent", "After": ["network-online.target", "local-fs.target"] },
+            "Service": { "ExecStart": ["", "/usr/bin/backup --daily"], "NoNewPrivileges": true, "Nice": 10 },
+            "Socket": { "ListenStream": "/run/backup.sock" },
+            "Install": { "WantedBy": "multi-user.target" }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_sections_in_order() {
+        let rendered = sample().render().unwrap();
+        assert_eq!(
+            rendered,
+            format!(
+                "{}\n\n[Unit]\nAfter=network-online.target\nAfter=local-fs.target\nDescription=Backup agent\n\n\
+                 [Service]\nExecStart=\nExecStart=/usr/bin/backup --daily\nNice=10\nNoNewPrivileges=yes\n\n\
+                 [Socket]\nListenStream=/run/backup.sock\n\n[Install]\nWantedBy=multi-user.target\n",
+                MANAGED_HEADER
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_roundtrip_normalizes_values() {
+        let file = sample();
+        let parsed = UnitFile::parse(&file.render().unwrap());
+        assert_eq!(parsed, file.normalized().unwrap());
+        assert_eq!(
+            parsed.service.get("NoNewPrivileges"),
+            Some(&Directive::Text("yes".to_string()))
+        );
+        assert_eq!(
+            parsed.other["Socket"].get("ListenStream"),
+            Some(&Directive::Text("/run/backup.sock".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_injection() {
+        let mut file = UnitFile::default();
+        file.service.insert(
+            "ExecStart".to_string(),
+            Directive::Text("/bin/true\nUser=root".to_string()),
+        );
+        assert!(file.render().is_err());
+        assert!(!valid_file_name("../evil.service"));
+    }
+
+    #[test]
+    fn test_scan_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let managed = unit_path(dir.path(), "backup.service");
+        let dropin = dropin_path(dir.path(), "ssh.service", "10-op-dbus");
+        let foreign = unit_path(dir.path(), "vendor.service");
+
+        write_atomic(&managed, &sample().render().unwrap()).unwrap();
+        std::fs::write(&foreign, "[Unit]\n").unwrap();
+        // Unmanaged files need not be text
+        std::fs::write(unit_path(dir.path(), "blob.service"), [0xff, 0xfe, 0x00, 0x80]).unwrap();
+        let blob_dropin = dropin_path(dir.path(), "vendor.service", "blob");
+        std::fs::create_dir_all(blob_dropin.parent().unwrap()).unwrap();
+        std::fs::write(&blob_dropin, [0xc3, 0x28]).unwrap();
+        let snapshot = scan_managed(dir.path()).unwrap();
+        assert_eq!(snapshot.len(), 1);
+        assert!(snapshot.contains_key(&managed));
+
+        let mut prior = FileSnapshot::new();
+        prior.insert(dropin.clone(), None);
+        prior.insert(managed.clone(), read_optional(&managed).unwrap());
+        write_atomic(&dropin, "# drop-in\n").unwrap();
+        write_atomic(&managed, "changed\n").unwrap();
+
+        restore_files(&prior).unwrap();
+        assert!(!dropin.exists());
+        assert!(!dir.path().join("ssh.service.d").exists());
+        assert_eq!(
+            std::fs::read_to_string(&managed).unwrap(),
+            sample().render().unwrap()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! BTRFS backend: volumes are subvolumes, snapshots are read-only subvolume snapshots
+
+use super::{BackendKind, SnapshotBackend};
+use crate::native::btrfs;
+use crate::replication::{BtrfsSendSource, StreamSource};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::process::Command;
+use tracing::debug;
+
+pub struct BtrfsBackend;
+
+#[async_trait]
+impl SnapshotBackend for BtrfsBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Btrfs
+    }
+
+    async fn create_volume(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            if !self.is_volume(path).await {
+                debug!(
+                    "{} exists but is not a subvolume, leaving it in place",
+                    path.display()
+                );
+            }
+            return Ok(());
+        }
+        btrfs::create_subvolume(path).await
+    }
+
+    async fn snapshot(&self, source: &Path, dest: &Path) -> Result<()> {
+        btrfs::create_snapshot(source, dest).await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        btrfs::delete_subvolume(path).await
+    }
+
+    async fn is_volume(&self, path: &Path) -> bool {
+        Command::new("btrfs")
+            .args(["subvolume", "show"])
+            .arg(path)
+            .output()
+            .await
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    fn stream_source(&self) -> Arc<dyn StreamSource> {
+        Arc::new(BtrfsSendSource)
+    }
+}
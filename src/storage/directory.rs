@@ -0,0 +1,354 @@
+//! Plain-directory backend for filesystems without subvolumes
+//!
+//! Snapshots are full directory trees, built next to their destination and
+//! published with a rename. File data is shared instead of duplicated:
+//! - `Reflink`: `cp --reflink=always` clones extents (XFS, bcachefs, ...)
+//! - `Hardlink`: files unchanged since the previous snapshot of the same
+//!   family are hardlinked to it, changed files are copied. The live volume
+//!   is never linked, so later writes to it cannot leak into snapshots.
+//!
+//! Snapshot files are made read-only; the directories stay writable so
+//! snapshots can be deleted without special privileges.
+
+use super::{snapshot_family, BackendKind, SnapshotBackend};
+use crate::replication::{StreamSource, TarSource};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::fs::{self, File, Metadata};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tracing::{debug, info};
+
+/// How snapshot files share data with their origin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMode {
+    Reflink,
+    Hardlink,
+}
+
+pub struct DirectoryBackend {
+    mode: OnceLock<CopyMode>,
+}
+
+impl Default for DirectoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectoryBackend {
+    /// Backend that probes reflink support on the first snapshot
+    pub fn new() -> Self {
+        Self {
+            mode: OnceLock::new(),
+        }
+    }
+
+    /// Backend with a fixed copy mode
+    pub fn with_mode(mode: CopyMode) -> Self {
+        let backend = Self::new();
+        let _ = backend.mode.set(mode);
+        backend
+    }
+
+    /// Copy mode in use, once known
+    pub fn mode(&self) -> Option<CopyMode> {
+        self.mode.get().copied()
+    }
+}
+
+#[async_trait]
+impl SnapshotBackend for DirectoryBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Directory
+    }
+
+    async fn create_volume(&self, path: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))
+    }
+
+    async fn snapshot(&self, source: &Path, dest: &Path) -> Result<()> {
+        if !source.is_dir() {
+            anyhow::bail!("Snapshot source {} is not a directory", source.display());
+        }
+        if dest.exists() {
+            anyhow::bail!("Snapshot {} already exists", dest.display());
+        }
+
+        let parent = dest
+            .parent()
+            .with_context(|| format!("Invalid snapshot path {}", dest.display()))?
+            .to_path_buf();
+        let name = dest
+            .file_name()
+            .with_context(|| format!("Invalid snapshot path {}", dest.display()))?
+            .to_string_lossy()
+            .to_string();
+        tokio::fs::create_dir_all(&parent).await?;
+
+        let partial = parent.join(format!(".{}.partial", name));
+        if partial.exists() {
+            tokio::fs::remove_dir_all(&partial).await?;
+        }
+
+        let mode = match self.mode.get() {
+            Some(mode) => *mode,
+            None => {
+                let dir = parent.clone();
+                let probed = tokio::task::spawn_blocking(move || probe_reflink(&dir)).await?;
+                info!(
+                    "Directory snapshots in {} use {:?}",
+                    parent.display(),
+                    probed
+                );
+                *self.mode.get_or_init(|| probed)
+            }
+        };
+
+        let source = source.to_path_buf();
+        let staged = partial.clone();
+        let result = tokio::task::spawn_blocking(move || match mode {
+            CopyMode::Reflink => reflink_tree(&source, &staged),
+            CopyMode::Hardlink => {
+                let previous = previous_snapshot(&parent, &name);
+                if let Some(previous) = &previous {
+                    debug!("Linking unchanged files from {}", previous.display());
+                }
+                link_tree(&source, &staged, previous.as_deref())
+            }
+        })
+        .await?;
+
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_dir_all(&partial).await;
+            return Err(e.context(format!("Failed to snapshot into {}", dest.display())));
+        }
+
+        tokio::fs::rename(&partial, dest).await?;
+        debug!("Created directory snapshot {} ({:?})", dest.display(), mode);
+        Ok(())
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+        tokio::fs::remove_dir_all(path)
+            .await
+            .with_context(|| format!("Failed to delete {}", path.display()))
+    }
+
+    async fn is_volume(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn stream_source(&self) -> Arc<dyn StreamSource> {
+        Arc::new(TarSource)
+    }
+}
+
+/// Check whether `dir` supports reflinks by cloning a probe file
+fn probe_reflink(dir: &Path) -> CopyMode {
+    let probe = dir.join(format!(".reflink-probe-{}", std::process::id()));
+    let clone = dir.join(format!(".reflink-probe-{}.clone", std::process::id()));
+
+    let supported = fs::write(&probe, b"op-dbus").is_ok()
+        && std::process::Command::new("cp")
+            .arg("--reflink=always")
+            .arg(&probe)
+            .arg(&clone)
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+
+    let _ = fs::remove_file(&probe);
+    let _ = fs::remove_file(&clone);
+
+    if supported {
+        CopyMode::Reflink
+    } else {
+        CopyMode::Hardlink
+    }
+}
+
+fn reflink_tree(source: &Path, dest: &Path) -> Result<()> {
+    let output = std::process::Command::new("cp")
+        .args(["-a", "--reflink=always", "--no-target-directory"])
+        .arg(source)
+        .arg(dest)
+        .output()
+        .context("Failed to execute cp")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "cp --reflink failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    make_read_only(dest)
+}
+
+/// Newest sibling snapshot of the same family, used as hardlink origin
+fn previous_snapshot(parent: &Path, name: &str) -> Option<PathBuf> {
+    let family = snapshot_family(name);
+    fs::read_dir(parent)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let candidate = entry.file_name().to_string_lossy().to_string();
+            !candidate.starts_with('.')
+                && candidate != name
+                && snapshot_family(&candidate) == family
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata
+                .is_dir()
+                .then(|| (metadata.modified().ok(), entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Copy `source` to `dest`, hardlinking files unchanged since `previous`
+fn link_tree(source: &Path, dest: &Path, previous: Option<&Path>) -> Result<()> {
+    fs::create_dir(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dest.join(entry.file_name());
+        let origin = previous.map(|p| p.join(entry.file_name()));
+        let metadata = fs::symlink_metadata(&from)?;
+        let file_type = metadata.file_type();
+
+        if file_type.is_dir() {
+            link_tree(&from, &to, origin.as_deref())?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(&from)?, &to)?;
+        } else if file_type.is_file() {
+            match origin.filter(|origin| unchanged(&metadata, origin)) {
+                Some(origin) => fs::hard_link(&origin, &to)?,
+                None => copy_file(&from, &to, &metadata)?,
+            }
+        } else {
+            debug!("Skipping special file {}", from.display());
+        }
+    }
+    Ok(())
+}
+
+fn unchanged(metadata: &Metadata, origin: &Path) -> bool {
+    let Ok(existing) = fs::symlink_metadata(origin) else {
+        return false;
+    };
+    existing.is_file()
+        && existing.len() == metadata.len()
+        && metadata.modified().is_ok()
+        && existing.modified().ok() == metadata.modified().ok()
+}
+
+/// Copy a file, keeping its modification time and dropping write permission
+fn copy_file(from: &Path, to: &Path, metadata: &Metadata) -> Result<()> {
+    let mut reader =
+        File::open(from).with_context(|| format!("Failed to open {}", from.display()))?;
+    let mut writer =
+        File::create(to).with_context(|| format!("Failed to create {}", to.display()))?;
+    std::io::copy(&mut reader, &mut writer)?;
+    writer.set_modified(metadata.modified()?)?;
+    writer.set_permissions(read_only(metadata.permissions()))?;
+    Ok(())
+}
+
+fn make_read_only(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = fs::symlink_metadata(entry.path())?;
+        if metadata.is_dir() {
+            make_read_only(&entry.path())?;
+        } else if metadata.is_file() {
+            fs::set_permissions(entry.path(), read_only(metadata.permissions()))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_only(permissions: fs::Permissions) -> fs::Permissions {
+    fs::Permissions::from_mode(permissions.mode() & !0o222)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[tokio::test]
+    async fn test_hardlink_snapshots_share_unchanged_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let live = temp.path().join("vectors");
+        let snapshots = temp.path().join("snapshots");
+        let backend = DirectoryBackend::with_mode(CopyMode::Hardlink);
+
+        backend.create_volume(&live).await.unwrap();
+        fs::create_dir(live.join("nested")).unwrap();
+        fs::write(live.join("a.vec"), b"first").unwrap();
+        fs::write(live.join("nested/b.vec"), b"second").unwrap();
+
+        let first = snapshots.join("vectors-1");
+        backend.snapshot(&live, &first).await.unwrap();
+
+        fs::write(live.join("a.vec"), b"changed").unwrap();
+        let second = snapshots.join("vectors-2");
+        backend.snapshot(&live, &second).await.unwrap();
+
+        // Writes to the live volume never reach earlier snapshots
+        assert_eq!(fs::read(first.join("a.vec")).unwrap(), b"first");
+        assert_eq!(fs::read(second.join("a.vec")).unwrap(), b"changed");
+
+        // The unchanged file is shared between snapshots, but not with the live volume
+        let inode = |p: &Path| fs::metadata(p).unwrap().ino();
+        assert_eq!(
+            inode(&first.join("nested/b.vec")),
+            inode(&second.join("nested/b.vec"))
+        );
+        assert_ne!(
+            inode(&live.join("nested/b.vec")),
+            inode(&first.join("nested/b.vec"))
+        );
+        assert!(fs::metadata(second.join("a.vec"))
+            .unwrap()
+            .permissions()
+            .readonly());
+
+        assert!(backend.snapshot(&live, &second).await.is_err());
+
+        backend.delete(&first).await.unwrap();
+        assert!(!first.exists());
+        assert_eq!(fs::read(second.join("nested/b.vec")).unwrap(), b"second");
+        backend.delete(&first).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_probed_snapshot() {
+        let temp = tempfile::tempdir().unwrap();
+        let live = temp.path().join("state");
+        let backend = DirectoryBackend::new();
+
+        backend.create_volume(&live).await.unwrap();
+        fs::write(live.join("current.json"), b"{}").unwrap();
+        std::os::unix::fs::symlink("current.json", live.join("latest.json")).unwrap();
+
+        let snapshot = temp.path().join("snapshots/state-20250101-000000");
+        backend.snapshot(&live, &snapshot).await.unwrap();
+
+        assert!(backend.mode().is_some());
+        assert_eq!(fs::read(snapshot.join("latest.json")).unwrap(), b"{}");
+        assert!(!temp
+            .path()
+            .join("snapshots/.state-20250101-000000.partial")
+            .exists());
+    }
+}
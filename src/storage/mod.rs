@@ -0,0 +1,221 @@
+//! Snapshot storage backends
+//!
+//! The blockchain, the cache, the snapshot managers and deployment images keep
+//! their data in volumes that can be snapshotted. On BTRFS a volume is a
+//! subvolume; on other filesystems it is a plain directory whose snapshots
+//! share unchanged file data through reflinks or hardlinks.
+//!
+//! The backend is picked at runtime from the filesystem holding the data and
+//! can be forced with `OPDBUS_STORAGE_BACKEND=btrfs|directory|auto`.
+
+pub mod btrfs;
+pub mod directory;
+
+pub use btrfs::BtrfsBackend;
+pub use directory::{CopyMode, DirectoryBackend};
+
+use crate::replication::StreamSource;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Storage backend implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Btrfs,
+    Directory,
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Btrfs => write!(f, "btrfs"),
+            Self::Directory => write!(f, "directory"),
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "btrfs" => Ok(Self::Btrfs),
+            "directory" | "dir" => Ok(Self::Directory),
+            other => anyhow::bail!("Unknown storage backend '{}'", other),
+        }
+    }
+}
+
+/// Creates volumes and read-only snapshots of them
+#[async_trait]
+pub trait SnapshotBackend: Send + Sync {
+    fn kind(&self) -> BackendKind;
+
+    /// Create a writable volume at `path`; existing paths are left untouched
+    async fn create_volume(&self, path: &Path) -> Result<()>;
+
+    /// Create a read-only snapshot of `source` at `dest`
+    async fn snapshot(&self, source: &Path, dest: &Path) -> Result<()>;
+
+    /// Delete a volume or snapshot; missing paths are not an error
+    async fn delete(&self, path: &Path) -> Result<()>;
+
+    /// Whether `path` is a volume or snapshot this backend can manage
+    async fn is_volume(&self, path: &Path) -> bool;
+
+    /// Stream source for replicating snapshots made by this backend
+    fn stream_source(&self) -> Arc<dyn StreamSource>;
+}
+
+/// Pick the backend for data stored at `path`
+///
+/// Honours `OPDBUS_STORAGE_BACKEND`; otherwise BTRFS is used when `path` lives
+/// on a BTRFS filesystem and the `btrfs` tool is installed.
+pub fn detect(path: &Path) -> Arc<dyn SnapshotBackend> {
+    let requested = std::env::var("OPDBUS_STORAGE_BACKEND").unwrap_or_default();
+    let kind = match requested.as_str() {
+        "" | "auto" => None,
+        value => match value.parse::<BackendKind>() {
+            Ok(kind) => Some(kind),
+            Err(e) => {
+                warn!("{}, detecting from filesystem", e);
+                None
+            }
+        },
+    };
+
+    let kind = kind.unwrap_or_else(|| {
+        let fstype = filesystem_type(path);
+        debug!("{} is on {:?}", path.display(), fstype);
+        if fstype.as_deref() == Some("btrfs") && command_available("btrfs") {
+            BackendKind::Btrfs
+        } else {
+            BackendKind::Directory
+        }
+    });
+
+    debug!("Using {} storage backend for {}", kind, path.display());
+    from_kind(kind)
+}
+
+/// Instantiate a backend of the given kind
+pub fn from_kind(kind: BackendKind) -> Arc<dyn SnapshotBackend> {
+    match kind {
+        BackendKind::Btrfs => Arc::new(BtrfsBackend),
+        BackendKind::Directory => Arc::new(DirectoryBackend::new()),
+    }
+}
+
+/// Filesystem type of the mount containing `path` (from /proc/self/mountinfo)
+///
+/// `path` does not need to exist yet; its nearest existing ancestor is used.
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let path = existing_ancestor(path)?.canonicalize().ok()?;
+
+    let mut best: Option<(usize, String)> = None;
+    for line in mountinfo.lines() {
+        let Some((mount, fstype)) = parse_mountinfo_line(line) else {
+            continue;
+        };
+        if !path.starts_with(&mount) {
+            continue;
+        }
+        // Later entries over-mount earlier ones at the same point
+        let depth = mount.components().count();
+        if best.as_ref().is_none_or(|(d, _)| depth >= *d) {
+            best = Some((depth, fstype));
+        }
+    }
+    best.map(|(_, fstype)| fstype)
+}
+
+fn parse_mountinfo_line(line: &str) -> Option<(PathBuf, String)> {
+    // <id> <parent> <major:minor> <root> <mount point> <options> [optional...] - <fstype> ...
+    let (fields, rest) = line.split_once(" - ")?;
+    let mount = fields.split(' ').nth(4)?;
+    let fstype = rest.split(' ').next()?;
+    Some((PathBuf::from(unescape_mount(mount)), fstype.to_string()))
+}
+
+/// Decode the octal escapes (`\040` etc.) used for mount points
+fn unescape_mount(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("");
+            if let Ok(value) = u8::from_str_radix(digits, 8) {
+                out.push(value);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.exists())
+}
+
+/// Whether an executable named `name` is on `PATH`
+pub fn command_available(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+        .unwrap_or(false)
+}
+
+/// Family prefix of a snapshot name (`vectors-<hash>` -> `vectors-`, `cache@<ts>` -> `cache@`)
+pub fn snapshot_family(name: &str) -> &str {
+    match name.find(['-', '@']) {
+        Some(idx) => &name[..=idx],
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_kind_parsing() {
+        assert_eq!("btrfs".parse::<BackendKind>().unwrap(), BackendKind::Btrfs);
+        assert_eq!(
+            "Directory".parse::<BackendKind>().unwrap(),
+            BackendKind::Directory
+        );
+        assert!("zfs".parse::<BackendKind>().is_err());
+    }
+
+    #[test]
+    fn test_parse_mountinfo_line() {
+        let line = "36 35 98:0 / /var/lib/op\\040dbus rw,noatime master:1 - btrfs /dev/sda2 rw,space_cache";
+        let (mount, fstype) = parse_mountinfo_line(line).unwrap();
+        assert_eq!(mount, PathBuf::from("/var/lib/op dbus"));
+        assert_eq!(fstype, "btrfs");
+    }
+
+    #[test]
+    fn test_filesystem_type_of_missing_path() {
+        let temp = tempfile::tempdir().unwrap();
+        let missing = temp.path().join("not/yet/created");
+        assert_eq!(filesystem_type(&missing), filesystem_type(temp.path()));
+    }
+
+    #[test]
+    fn test_snapshot_family() {
+        assert_eq!(snapshot_family("vectors-abc"), "vectors-");
+        assert_eq!(snapshot_family("state-20250106-143022"), "state-");
+        assert_eq!(snapshot_family("cache@2025-01-06-14:30:22"), "cache@");
+        assert_eq!(snapshot_family("plain"), "plain");
+    }
+}
@@ -0,0 +1,720 @@
+//! Persistent task queue for agents
+//!
+//! Tasks sent to agents through the orchestrator are stored in SQLite, so
+//! pending and running tasks survive a restart. Each task moves from
+//! `pending` to `running` when an agent claims it, and ends `completed`,
+//! `failed` or `cancelled`. Failed or timed out tasks, and those whose agent
+//! died while running them, go back to `pending` until their retries are
+//! used up. Agents get higher priority tasks first and run at most a limited
+//! number at once.
+//!
+//! Finished tasks are kept for the retention period and can be listed,
+//! inspected and replayed with `op-dbus task`.
+
+use crate::http_tls_server::tokens::parse_ttl;
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Default task database
+pub const DEFAULT_TASK_DB: &str = "/var/lib/op-dbus/tasks.db";
+
+/// Default time finished tasks are kept
+pub const DEFAULT_RETENTION: &str = "7d";
+
+/// Default number of tasks an agent runs at once
+pub const DEFAULT_CONCURRENCY: usize = 1;
+
+const TASK_COLUMNS: &str = "id, agent_id, task_type, payload, priority, status, attempts, \
+     max_retries, timeout_secs, result, error, created_at, started_at, finished_at, replay_of";
+
+/// Where a task is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Running => "running",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether the task will not change any more
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled
+        )
+    }
+}
+
+impl std::str::FromStr for TaskStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(TaskStatus::Pending),
+            "running" => Ok(TaskStatus::Running),
+            "completed" => Ok(TaskStatus::Completed),
+            "failed" => Ok(TaskStatus::Failed),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            _ => anyhow::bail!(
+                "Unknown task status '{}' (pending, running, completed, failed or cancelled)",
+                s
+            ),
+        }
+    }
+}
+
+/// Task to be executed by an agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    pub id: String,
+    pub agent_id: String,
+    pub task_type: String,
+    pub payload: Value,
+    /// Higher priorities are claimed first
+    pub priority: i64,
+    pub status: TaskStatus,
+    /// Times the task was claimed
+    pub attempts: u32,
+    /// Times a failed task is queued again
+    pub max_retries: u32,
+    /// Running time after which the task counts as failed
+    pub timeout_secs: Option<u64>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Task this one replays
+    pub replay_of: Option<String>,
+}
+
+impl Task {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let payload: String = row.get(3)?;
+        let status: String = row.get(5)?;
+        let result: Option<String> = row.get(9)?;
+        Ok(Self {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            task_type: row.get(2)?,
+            payload: serde_json::from_str(&payload).unwrap_or(Value::Null),
+            priority: row.get(4)?,
+            status: status.parse().unwrap_or(TaskStatus::Failed),
+            attempts: row.get(6)?,
+            max_retries: row.get(7)?,
+            timeout_secs: row.get(8)?,
+            result: result.and_then(|result| serde_json::from_str(&result).ok()),
+            error: row.get(10)?,
+            created_at: from_millis(row.get(11)?),
+            started_at: row.get::<_, Option<i64>>(12)?.map(from_millis),
+            finished_at: row.get::<_, Option<i64>>(13)?.map(from_millis),
+            replay_of: row.get(14)?,
+        })
+    }
+}
+
+/// A task to add to the queue
+#[derive(Debug, Clone)]
+pub struct NewTask {
+    pub agent_id: String,
+    pub task_type: String,
+    pub payload: Value,
+    pub priority: i64,
+    pub max_retries: u32,
+    pub timeout_secs: Option<u64>,
+}
+
+impl NewTask {
+    /// Task of the payload's `type` for an agent
+    pub fn new(agent_id: impl Into<String>, payload: Value) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            task_type: payload["type"].as_str().unwrap_or("unknown").to_string(),
+            payload,
+            priority: 0,
+            max_retries: 0,
+            timeout_secs: None,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i64) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = Some(timeout_secs);
+        self
+    }
+}
+
+/// Filter for listing tasks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskQuery {
+    pub agent_id: Option<String>,
+    pub status: Option<TaskStatus>,
+    /// Return at most this many of the newest matches
+    pub limit: Option<usize>,
+}
+
+/// SQLite-backed task queue
+pub struct TaskStore {
+    conn: Mutex<Connection>,
+    path: PathBuf,
+    retention: chrono::Duration,
+    concurrency: usize,
+    agent_concurrency: Mutex<HashMap<String, usize>>,
+}
+
+fn now_millis() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+fn from_millis(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .unwrap_or(DateTime::UNIX_EPOCH)
+}
+
+impl TaskStore {
+    /// Create or open the task database
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let conn = Connection::open(&path)
+            .with_context(|| format!("Failed to open task database {}", path.display()))?;
+
+        // The orchestrator and the CLI use the database at the same time
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS tasks (
+                id TEXT PRIMARY KEY,
+                agent_id TEXT NOT NULL,
+                task_type TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                priority INTEGER NOT NULL DEFAULT 0,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                max_retries INTEGER NOT NULL DEFAULT 0,
+                timeout_secs INTEGER,
+                result TEXT,
+                error TEXT,
+                created_at INTEGER NOT NULL,
+                started_at INTEGER,
+                finished_at INTEGER,
+                replay_of TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_tasks_agent_status
+                ON tasks(agent_id, status);
+
+            CREATE INDEX IF NOT EXISTS idx_tasks_finished_at
+                ON tasks(finished_at);
+            "#,
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path,
+            retention: parse_ttl(DEFAULT_RETENTION)?,
+            concurrency: DEFAULT_CONCURRENCY,
+            agent_concurrency: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Store at `OPDBUS_TASK_DB`, keeping finished tasks for
+    /// `OPDBUS_TASK_RETENTION` and running `OPDBUS_TASK_CONCURRENCY` tasks
+    /// per agent
+    pub fn from_env() -> Result<Self> {
+        let path = std::env::var_os("OPDBUS_TASK_DB")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_TASK_DB));
+        let mut store = Self::open(path)?;
+        if let Ok(retention) = std::env::var("OPDBUS_TASK_RETENTION") {
+            store = store
+                .with_retention(parse_ttl(&retention).context("Invalid OPDBUS_TASK_RETENTION")?);
+        }
+        if let Ok(concurrency) = std::env::var("OPDBUS_TASK_CONCURRENCY") {
+            store = store.with_concurrency(
+                concurrency
+                    .parse()
+                    .context("Invalid OPDBUS_TASK_CONCURRENCY")?,
+            );
+        }
+        Ok(store)
+    }
+
+    pub fn with_retention(mut self, retention: chrono::Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Number of tasks each agent runs at once, unless set for the agent
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn retention(&self) -> chrono::Duration {
+        self.retention
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Task database lock poisoned: {}", e))
+    }
+
+    /// Set the number of tasks an agent runs at once
+    pub fn set_concurrency_limit(&self, agent_id: &str, limit: usize) {
+        if let Ok(mut limits) = self.agent_concurrency.lock() {
+            limits.insert(agent_id.to_string(), limit.max(1));
+        }
+    }
+
+    fn concurrency_limit(&self, agent_id: &str) -> usize {
+        self.agent_concurrency
+            .lock()
+            .ok()
+            .and_then(|limits| limits.get(agent_id).copied())
+            .unwrap_or(self.concurrency)
+    }
+
+    /// Add a task to the queue
+    pub fn enqueue(&self, task: NewTask) -> Result<Task> {
+        self.insert(task, None)
+    }
+
+    fn insert(&self, task: NewTask, replay_of: Option<&str>) -> Result<Task> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.conn()?.execute(
+            "INSERT INTO tasks (id, agent_id, task_type, payload, priority, status,
+                                max_retries, timeout_secs, created_at, replay_of)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'pending', ?6, ?7, ?8, ?9)",
+            params![
+                id,
+                task.agent_id,
+                task.task_type,
+                serde_json::to_string(&task.payload)?,
+                task.priority,
+                task.max_retries,
+                task.timeout_secs,
+                now_millis(),
+                replay_of,
+            ],
+        )?;
+        self.get(&id)?
+            .context("Task disappeared right after it was queued")
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Task>> {
+        let conn = self.conn()?;
+        let task = conn
+            .query_row(
+                &format!("SELECT {} FROM tasks WHERE id = ?1", TASK_COLUMNS),
+                params![id],
+                Task::from_row,
+            )
+            .optional()?;
+        Ok(task)
+    }
+
+    /// Tasks matching `query`, newest first
+    pub fn list(&self, query: &TaskQuery) -> Result<Vec<Task>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE (?1 IS NULL OR agent_id = ?1) AND (?2 IS NULL OR status = ?2)
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?3",
+            TASK_COLUMNS
+        ))?;
+        let limit = query.limit.map(|limit| limit as i64).unwrap_or(-1);
+        let tasks = stmt
+            .query_map(
+                params![
+                    query.agent_id,
+                    query.status.map(|status| status.as_str()),
+                    limit
+                ],
+                Task::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tasks)
+    }
+
+    /// Claim the agent's next pending task, highest priority first
+    ///
+    /// Returns `None` when nothing is pending or the agent already runs as
+    /// many tasks as it may.
+    pub fn claim_next(&self, agent_id: &str) -> Result<Option<Task>> {
+        let limit = self.concurrency_limit(agent_id);
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let running: usize = tx.query_row(
+            "SELECT COUNT(*) FROM tasks WHERE agent_id = ?1 AND status = 'running'",
+            params![agent_id],
+            |row| row.get(0),
+        )?;
+        if running >= limit {
+            return Ok(None);
+        }
+
+        let id: Option<String> = tx
+            .query_row(
+                "SELECT id FROM tasks WHERE agent_id = ?1 AND status = 'pending'
+                 ORDER BY priority DESC, created_at, rowid LIMIT 1",
+                params![agent_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        tx.execute(
+            "UPDATE tasks SET status = 'running', attempts = attempts + 1, started_at = ?2
+             WHERE id = ?1",
+            params![id, now_millis()],
+        )?;
+        tx.commit()?;
+        drop(conn);
+
+        self.get(&id)
+    }
+
+    /// Record the result of a running task
+    ///
+    /// Returns false if the task is unknown or not running.
+    pub fn complete(&self, id: &str, result: &Value) -> Result<bool> {
+        let updated = self.conn()?.execute(
+            "UPDATE tasks SET status = 'completed', result = ?2, error = NULL, finished_at = ?3
+             WHERE id = ?1 AND status = 'running'",
+            params![id, serde_json::to_string(result)?, now_millis()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Record the failure of a running task
+    ///
+    /// The task is queued again while it has retries left. Returns its new
+    /// status, or `None` if the task is unknown or not running.
+    pub fn fail(&self, id: &str, error: &str) -> Result<Option<TaskStatus>> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE tasks SET
+                 error = ?2,
+                 status = CASE WHEN attempts <= max_retries THEN 'pending' ELSE 'failed' END,
+                 started_at = CASE WHEN attempts <= max_retries THEN NULL ELSE started_at END,
+                 finished_at = CASE WHEN attempts <= max_retries THEN NULL ELSE ?3 END
+             WHERE id = ?1 AND status = 'running'",
+            params![id, error, now_millis()],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        let status: String = conn.query_row(
+            "SELECT status FROM tasks WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(Some(status.parse()?))
+    }
+
+    /// Fail running tasks that exceeded their timeout, returning their ids
+    pub fn expire_timed_out(&self) -> Result<Vec<String>> {
+        let now = now_millis();
+        let expired: Vec<(String, u64)> = {
+            let conn = self.conn()?;
+            let mut stmt = conn.prepare(
+                "SELECT id, timeout_secs FROM tasks
+                 WHERE status = 'running' AND timeout_secs IS NOT NULL
+                   AND started_at + timeout_secs * 1000 <= ?1",
+            )?;
+            let rows = stmt
+                .query_map(params![now], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+
+        let mut ids = Vec::new();
+        for (id, timeout_secs) in expired {
+            if self
+                .fail(&id, &format!("Timed out after {}s", timeout_secs))?
+                .is_some()
+            {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Fail the running tasks of an agent that died, returning their ids
+    pub fn fail_running_for_agent(&self, agent_id: &str, reason: &str) -> Result<Vec<String>> {
+        let running = self.running()?;
+        self.fail_all(
+            running.into_iter().filter(|(_, agent)| agent == agent_id),
+            reason,
+        )
+    }
+
+    /// Fail running tasks of agents not in `live_agents`, returning their ids
+    ///
+    /// Used at startup: agents that did not survive the restart never finish
+    /// their tasks.
+    pub fn fail_orphaned(&self, live_agents: &[String], reason: &str) -> Result<Vec<String>> {
+        let running = self.running()?;
+        self.fail_all(
+            running
+                .into_iter()
+                .filter(|(_, agent)| !live_agents.contains(agent)),
+            reason,
+        )
+    }
+
+    /// Ids and agents of the running tasks
+    fn running(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT id, agent_id FROM tasks WHERE status = 'running'")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    fn fail_all(
+        &self,
+        tasks: impl Iterator<Item = (String, String)>,
+        reason: &str,
+    ) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for (id, _) in tasks {
+            if self.fail(&id, reason)?.is_some() {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Cancel a pending or running task
+    ///
+    /// Returns false if the task is unknown or already finished.
+    pub fn cancel(&self, id: &str) -> Result<bool> {
+        let updated = self.conn()?.execute(
+            "UPDATE tasks SET status = 'cancelled', finished_at = ?2
+             WHERE id = ?1 AND status IN ('pending', 'running')",
+            params![id, now_millis()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Cancel every pending task of an agent, returning how many there were
+    pub fn cancel_pending_for_agent(&self, agent_id: &str) -> Result<usize> {
+        let updated = self.conn()?.execute(
+            "UPDATE tasks SET status = 'cancelled', finished_at = ?2
+             WHERE agent_id = ?1 AND status = 'pending'",
+            params![agent_id, now_millis()],
+        )?;
+        Ok(updated)
+    }
+
+    /// Queue a finished task again as a new task
+    pub fn replay(&self, id: &str) -> Result<Task> {
+        let task = self
+            .get(id)?
+            .with_context(|| format!("Unknown task {}", id))?;
+        if !task.status.is_finished() {
+            anyhow::bail!("Task {} is still {}", id, task.status.as_str());
+        }
+        let replay = NewTask {
+            agent_id: task.agent_id,
+            task_type: task.task_type,
+            payload: task.payload,
+            priority: task.priority,
+            max_retries: task.max_retries,
+            timeout_secs: task.timeout_secs,
+        };
+        self.insert(replay, Some(id))
+    }
+
+    /// Delete tasks that finished longer ago than the retention period
+    pub fn purge_expired(&self) -> Result<usize> {
+        let cutoff = (Utc::now() - self.retention).timestamp_millis();
+        let deleted = self.conn()?.execute(
+            "DELETE FROM tasks WHERE finished_at IS NOT NULL AND finished_at < ?1",
+            params![cutoff],
+        )?;
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_priority_concurrency_and_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TaskStore::open(dir.path().join("tasks.db")).unwrap();
+
+        let low = store
+            .enqueue(NewTask::new("agent-1", json!({"type": "scan"})).with_max_retries(1))
+            .unwrap();
+        let high = store
+            .enqueue(NewTask::new("agent-1", json!({"type": "fix"})).with_priority(10))
+            .unwrap();
+        assert_eq!(low.task_type, "scan");
+        assert_eq!(low.status, TaskStatus::Pending);
+
+        // Highest priority first, one at a time
+        let claimed = store.claim_next("agent-1").unwrap().unwrap();
+        assert_eq!(claimed.id, high.id);
+        assert_eq!(claimed.status, TaskStatus::Running);
+        assert_eq!(claimed.attempts, 1);
+        assert!(store.claim_next("agent-1").unwrap().is_none());
+
+        assert!(store.complete(&high.id, &json!({"ok": true})).unwrap());
+        assert!(!store.complete(&high.id, &json!({"ok": true})).unwrap());
+        let done = store.get(&high.id).unwrap().unwrap();
+        assert_eq!(done.status, TaskStatus::Completed);
+        assert_eq!(done.result, Some(json!({"ok": true})));
+
+        // One retry, then the failure is final
+        store.claim_next("agent-1").unwrap().unwrap();
+        assert_eq!(
+            store.fail(&low.id, "boom").unwrap(),
+            Some(TaskStatus::Pending)
+        );
+        store.claim_next("agent-1").unwrap().unwrap();
+        assert_eq!(
+            store.fail(&low.id, "boom").unwrap(),
+            Some(TaskStatus::Failed)
+        );
+        let failed = store.get(&low.id).unwrap().unwrap();
+        assert_eq!(failed.attempts, 2);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+
+        // Tasks survive reopening the database
+        drop(store);
+        let store = TaskStore::open(dir.path().join("tasks.db"))
+            .unwrap()
+            .with_concurrency(2);
+        let replay = store.replay(&low.id).unwrap();
+        assert_eq!(replay.replay_of.as_deref(), Some(low.id.as_str()));
+        assert_eq!(replay.status, TaskStatus::Pending);
+
+        let query = TaskQuery {
+            agent_id: Some("agent-1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.list(&query).unwrap().len(), 3);
+        assert!(store.cancel(&replay.id).unwrap());
+        assert!(!store.cancel(&replay.id).unwrap());
+    }
+
+    #[test]
+    fn test_timeout_and_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TaskStore::open(dir.path().join("tasks.db"))
+            .unwrap()
+            .with_retention(chrono::Duration::zero());
+
+        let task = store
+            .enqueue(NewTask::new("agent-1", json!({"type": "slow"})).with_timeout(0))
+            .unwrap();
+        store.claim_next("agent-1").unwrap().unwrap();
+        assert_eq!(store.expire_timed_out().unwrap(), vec![task.id.clone()]);
+        let expired = store.get(&task.id).unwrap().unwrap();
+        assert_eq!(expired.status, TaskStatus::Failed);
+        assert_eq!(expired.error.as_deref(), Some("Timed out after 0s"));
+
+        let pending = store
+            .enqueue(NewTask::new("agent-1", json!({"type": "later"})))
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(store.purge_expired().unwrap(), 1);
+        assert!(store.get(&task.id).unwrap().is_none());
+        assert!(store.get(&pending.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_tasks_of_dead_agents() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TaskStore::open(dir.path().join("tasks.db")).unwrap();
+
+        let retried = store
+            .enqueue(NewTask::new("agent-1", json!({"type": "scan"})).with_max_retries(1))
+            .unwrap();
+        let other = store
+            .enqueue(NewTask::new("agent-2", json!({"type": "scan"})))
+            .unwrap();
+        store.claim_next("agent-1").unwrap().unwrap();
+        store.claim_next("agent-2").unwrap().unwrap();
+
+        // The dead agent's task is queued again, the other keeps running
+        assert_eq!(
+            store
+                .fail_running_for_agent("agent-1", "Agent died")
+                .unwrap(),
+            vec![retried.id.clone()]
+        );
+        let requeued = store.get(&retried.id).unwrap().unwrap();
+        assert_eq!(requeued.status, TaskStatus::Pending);
+        assert_eq!(requeued.error.as_deref(), Some("Agent died"));
+        assert_eq!(
+            store.get(&other.id).unwrap().unwrap().status,
+            TaskStatus::Running
+        );
+
+        // After a restart only agent-1 is back
+        store.claim_next("agent-1").unwrap().unwrap();
+        let live = vec!["agent-1".to_string()];
+        assert_eq!(
+            store.fail_orphaned(&live, "Agent gone").unwrap(),
+            vec![other.id.clone()]
+        );
+        assert_eq!(
+            store.get(&other.id).unwrap().unwrap().status,
+            TaskStatus::Failed
+        );
+        assert_eq!(
+            store.get(&retried.id).unwrap().unwrap().status,
+            TaskStatus::Running
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Desired-state file editing for the web UI
+//!
+//! The web UI edits the same state file `op-dbus run` applies
+//! (`OPDBUS_STATE_FILE`, default /etc/op-dbus/state.json). Every save keeps
+//! a copy of the whole file under `<OPDBUS_STATE_DIR>/history` (default
+//! /var/lib/op-dbus/state/history) and appends to `history.jsonl` there.
+//! Applies started from a saved revision are appended too, with the job and
+//! the content hash of the blockchain footprint the apply recorded.
+//!
+//! Plans are identified by a hash of their actions; an apply must name the
+//! hash of the plan that was reviewed and is refused if the plan changed.
+
+use crate::blockchain::plugin_footprint::current_actor;
+use crate::state::manager::DesiredState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::encoding::to_hex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+pub const DEFAULT_STATE_FILE: &str = "/etc/op-dbus/state.json";
+pub const DEFAULT_STATE_DIR: &str = "/var/lib/op-dbus/state";
+
+const HISTORY_INDEX: &str = "history.jsonl";
+
+/// An entry of the desired-state history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HistoryEntry {
+    /// The state file was saved as `revision`
+    Saved {
+        revision: String,
+        /// SHA-256 of the saved file
+        hash: String,
+        /// Plugin whose section was edited, if only one was
+        plugin: Option<String>,
+        actor: Option<String>,
+        message: Option<String>,
+        at: DateTime<Utc>,
+    },
+    /// `plugin` was applied from `revision`
+    Applied {
+        revision: String,
+        plugin: String,
+        job_id: String,
+        success: bool,
+        /// Content hash of the apply's blockchain footprint
+        footprint: Option<String>,
+        actor: Option<String>,
+        at: DateTime<Utc>,
+    },
+}
+
+impl HistoryEntry {
+    pub fn revision(&self) -> &str {
+        match self {
+            Self::Saved { revision, .. } | Self::Applied { revision, .. } => revision,
+        }
+    }
+}
+
+/// The desired-state file and its history
+pub struct DesiredStateStore {
+    path: PathBuf,
+    history_dir: PathBuf,
+    /// Serializes saves so revisions and the index stay in order
+    lock: Mutex<()>,
+}
+
+impl DesiredStateStore {
+    pub fn new(path: impl Into<PathBuf>, state_dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.into(),
+            history_dir: state_dir.as_ref().join("history"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Default state directory, or `OPDBUS_STATE_DIR`
+    pub fn default_state_dir() -> PathBuf {
+        std::env::var_os("OPDBUS_STATE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR))
+    }
+
+    /// Default state file, or `OPDBUS_STATE_FILE`
+    pub fn default_state_file() -> PathBuf {
+        std::env::var_os("OPDBUS_STATE_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_FILE))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The state file, empty if it does not exist yet
+    pub async fn load(&self) -> Result<DesiredState> {
+        match tokio::fs::read(&self.path).await {
+            Ok(content) => parse_state(&content)
+                .with_context(|| format!("Invalid state file {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DesiredState {
+                version: 1,
+                plugins: HashMap::new(),
+            }),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", self.path.display())),
+        }
+    }
+
+    /// Revision the state file is at, None if it was changed outside the UI
+    pub async fn current_revision(&self) -> Result<Option<String>> {
+        let hash = match tokio::fs::read(&self.path).await {
+            Ok(content) => to_hex(&Sha256::digest(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(self
+            .history()
+            .await?
+            .into_iter()
+            .find_map(|entry| match entry {
+                HistoryEntry::Saved {
+                    revision, hash: h, ..
+                } if h == hash => Some(revision),
+                _ => None,
+            }))
+    }
+
+    /// Replace `plugin`'s section of the state file and record a revision
+    pub async fn save_plugin(
+        &self,
+        plugin: &str,
+        state: Value,
+        message: Option<String>,
+    ) -> Result<HistoryEntry> {
+        let _guard = self.lock.lock().await;
+        let mut desired = self.load().await?;
+        desired.plugins.insert(plugin.to_string(), state);
+        self.write(&desired, Some(plugin.to_string()), message)
+            .await
+    }
+
+    /// Record that `plugin` was applied from `revision`
+    pub async fn record_apply(
+        &self,
+        revision: &str,
+        plugin: &str,
+        job_id: &str,
+        success: bool,
+        footprint: Option<String>,
+        actor: Option<String>,
+    ) -> Result<HistoryEntry> {
+        let _guard = self.lock.lock().await;
+        let entry = HistoryEntry::Applied {
+            revision: revision.to_string(),
+            plugin: plugin.to_string(),
+            job_id: job_id.to_string(),
+            success,
+            footprint,
+            actor,
+            at: Utc::now(),
+        };
+        self.append(&entry).await?;
+        Ok(entry)
+    }
+
+    /// History entries, newest first
+    pub async fn history(&self) -> Result<Vec<HistoryEntry>> {
+        let index = self.history_dir.join(HISTORY_INDEX);
+        let content = match tokio::fs::read_to_string(&index).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping bad line in {}: {}", index.display(), e),
+            }
+        }
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// The state file as saved in `revision`
+    pub async fn revision(&self, revision: &str) -> Result<DesiredState> {
+        if revision.is_empty()
+            || !revision
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            anyhow::bail!("Invalid revision '{}'", revision);
+        }
+        let path = self.history_dir.join(format!("{}.json", revision));
+        let content = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("Unknown revision {}", revision))?;
+        parse_state(&content)
+    }
+
+    async fn write(
+        &self,
+        desired: &DesiredState,
+        plugin: Option<String>,
+        message: Option<String>,
+    ) -> Result<HistoryEntry> {
+        // Through Value, so plugins are written in a stable order
+        let content = serde_json::to_vec_pretty(&serde_json::to_value(desired)?)?;
+        let hash = to_hex(&Sha256::digest(&content));
+        let at = Utc::now();
+        let revision = format!("{}-{}", at.format("%Y%m%dT%H%M%S%3fZ"), &hash[..12]);
+
+        tokio::fs::create_dir_all(&self.history_dir).await?;
+        tokio::fs::write(
+            self.history_dir.join(format!("{}.json", revision)),
+            &content,
+        )
+        .await?;
+        write_atomic(&self.path, &content)?;
+
+        let entry = HistoryEntry::Saved {
+            revision,
+            hash,
+            plugin,
+            actor: current_actor(),
+            message,
+            at,
+        };
+        self.append(&entry).await?;
+        Ok(entry)
+    }
+
+    async fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        tokio::fs::create_dir_all(&self.history_dir).await?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.history_dir.join(HISTORY_INDEX))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn parse_state(content: &[u8]) -> Result<DesiredState> {
+    Ok(serde_json::from_slice(content)?)
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_save_history_and_apply_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DesiredStateStore::new(dir.path().join("state.json"), dir.path().join("state"));
+        assert!(store.load().await.unwrap().plugins.is_empty());
+        assert_eq!(store.current_revision().await.unwrap(), None);
+
+        let first = store
+            .save_plugin("systemd", json!({"units": {}}), Some("initial".into()))
+            .await
+            .unwrap();
+        let second = store
+            .save_plugin("net", json!({"interfaces": []}), None)
+            .await
+            .unwrap();
+        assert_ne!(first.revision(), second.revision());
+        assert_eq!(
+            store.current_revision().await.unwrap().as_deref(),
+            Some(second.revision())
+        );
+
+        // Old revisions keep the file as it was
+        let old = store.revision(first.revision()).await.unwrap();
+        assert!(old.plugins.contains_key("systemd") && !old.plugins.contains_key("net"));
+        assert_eq!(store.load().await.unwrap().plugins.len(), 2);
+        assert!(store.revision("../state").await.is_err());
+
+        store
+            .record_apply(
+                second.revision(),
+                "net",
+                "job-1",
+                true,
+                Some("abc".into()),
+                None,
+            )
+            .await
+            .unwrap();
+        let history = store.history().await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(
+            matches!(&history[0], HistoryEntry::Applied { footprint: Some(f), .. } if f == "abc")
+        );
+        assert_eq!(history[2], first);
+
+        // Edited by hand: no longer at a known revision
+        std::fs::write(store.path(), b"{\"version\":1,\"plugins\":{}}").unwrap();
+        assert_eq!(store.current_revision().await.unwrap(), None);
+    }
+}
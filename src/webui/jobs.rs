@@ -0,0 +1,608 @@
+//! Background apply jobs for the web UI
+//!
+//! `POST /api/plugins/:plugin/apply` starts a job and returns its id right
+//! away. Each `ApplyEvent` of the apply is kept on the job and broadcast as a
+//! `JobEvent`, which `/api/jobs/:id/events` (SSE) and `/ws/jobs/:id`
+//! (WebSocket) stream to the browser. Like `mcp::sse_streaming`, but over a
+//! broadcast channel so every open page receives every event.
+
+use crate::audit::{AuditRecord, Frontend};
+use crate::blockchain::plugin_footprint::{current_actor, with_actor};
+use crate::state::manager::{ApplyEvent, ApplyReport, DesiredState};
+use crate::state::StateManager;
+use axum::response::sse::Event;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Finished jobs kept for `GET /api/jobs/:id`
+const MAX_FINISHED_JOBS: usize = 100;
+
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// An apply running, or run, in the background
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub plugin: String,
+    /// Who started the job, as recorded in footprints
+    pub actor: Option<String>,
+    pub status: JobStatus,
+    pub events: Vec<ApplyEvent>,
+    /// The `ApplyReport` once the apply has finished
+    pub report: Option<Value>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Job update sent to subscribers
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobEvent {
+    Started {
+        job_id: String,
+        plugin: String,
+    },
+    Progress {
+        job_id: String,
+        event: ApplyEvent,
+    },
+    Finished {
+        job_id: String,
+        status: JobStatus,
+        error: Option<String>,
+    },
+}
+
+impl JobEvent {
+    pub fn job_id(&self) -> &str {
+        match self {
+            Self::Started { job_id, .. }
+            | Self::Progress { job_id, .. }
+            | Self::Finished { job_id, .. } => job_id,
+        }
+    }
+
+    /// Convert to SSE event
+    pub fn to_sse_event(&self) -> Result<Event, Infallible> {
+        let event_type = match self {
+            Self::Started { .. } => "job_started",
+            Self::Progress { .. } => "job_progress",
+            Self::Finished { .. } => "job_finished",
+        };
+        Ok(Event::default()
+            .event(event_type)
+            .json_data(self)
+            .unwrap_or_default())
+    }
+}
+
+/// Jobs of this server and the channel their events go out on
+#[derive(Clone)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+    events: broadcast::Sender<JobEvent>,
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    /// All jobs, newest first
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.jobs.lock().unwrap().values().cloned().collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+        jobs
+    }
+
+    /// A job's events so far, then its live events until it finishes
+    ///
+    /// None if there is no such job.
+    pub fn follow(
+        &self,
+        id: &str,
+    ) -> Option<(Vec<JobEvent>, Option<broadcast::Receiver<JobEvent>>)> {
+        // Events are sent with the lock held, so each one is either in the
+        // snapshot or received, never both
+        let jobs = self.jobs.lock().unwrap();
+        let rx = self.events.subscribe();
+        let job = jobs.get(id).cloned()?;
+        drop(jobs);
+
+        let mut history = vec![JobEvent::Started {
+            job_id: job.id.clone(),
+            plugin: job.plugin.clone(),
+        }];
+        history.extend(job.events.iter().map(|event| JobEvent::Progress {
+            job_id: job.id.clone(),
+            event: event.clone(),
+        }));
+        if job.status == JobStatus::Running {
+            return Some((history, Some(rx)));
+        }
+        history.push(JobEvent::Finished {
+            job_id: job.id,
+            status: job.status,
+            error: job.error,
+        });
+        Some((history, None))
+    }
+
+    /// Stream of a job's events, ending when it finishes
+    ///
+    /// A subscriber that falls behind the broadcast channel catches up from
+    /// the events kept on the job.
+    pub fn events(&self, id: &str) -> Option<impl Stream<Item = JobEvent>> {
+        let (history, rx) = self.follow(id)?;
+        let follower = Follower {
+            registry: self.clone(),
+            id: id.to_string(),
+            pending: history.into(),
+            rx,
+            delivered: 0,
+        };
+        Some(stream::unfold(follower, |mut follower| async move {
+            let event = follower.next().await?;
+            follower.delivered += 1;
+            Some((event, follower))
+        }))
+    }
+
+    /// Start applying `state` to `plugin` in the background
+    ///
+    /// The job runs as the actor of the calling task.
+    pub fn start_apply(
+        &self,
+        state_manager: Arc<StateManager>,
+        plugin: String,
+        state: Value,
+    ) -> Job {
+        self.start_apply_then(state_manager, plugin, state, |_| async {})
+    }
+
+    /// Like `start_apply`, running `then` with the finished job
+    pub fn start_apply_then<F, Fut>(
+        &self,
+        state_manager: Arc<StateManager>,
+        plugin: String,
+        state: Value,
+        then: F,
+    ) -> Job
+    where
+        F: FnOnce(Job) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            plugin: plugin.clone(),
+            actor: current_actor(),
+            status: JobStatus::Running,
+            events: Vec::new(),
+            report: None,
+            error: None,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.insert(job.id.clone(), job.clone());
+        self.send(JobEvent::Started {
+            job_id: job.id.clone(),
+            plugin: plugin.clone(),
+        });
+        drop(jobs);
+
+        let registry = self.clone();
+        let id = job.id.clone();
+        let audit = AuditRecord::new(Frontend::Web, "apply")
+            .with_plugin(plugin.clone())
+            .with_params(&state);
+        let desired = DesiredState {
+            version: 1,
+            plugins: HashMap::from([(plugin.clone(), state)]),
+        };
+        let apply = async move {
+            let result = state_manager
+                .apply_state_single_plugin_with_progress(desired, &plugin, |event| {
+                    registry.record(&id, event)
+                })
+                .await;
+            audit_apply(audit, &result);
+            if let Some(job) = registry.finish(&id, result) {
+                then(job).await;
+            }
+        };
+        match job.actor.clone() {
+            Some(actor) => tokio::spawn(with_actor(actor, apply)),
+            None => tokio::spawn(apply),
+        };
+        job
+    }
+
+    fn record(&self, id: &str, event: ApplyEvent) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(id) {
+            job.events.push(event.clone());
+        }
+        self.send(JobEvent::Progress {
+            job_id: id.to_string(),
+            event,
+        });
+    }
+
+    fn finish(&self, id: &str, result: anyhow::Result<ApplyReport>) -> Option<Job> {
+        let (status, report, error) = match result {
+            Ok(report) if report.success => (JobStatus::Succeeded, Some(report), None),
+            Ok(report) => (
+                JobStatus::Failed,
+                Some(report),
+                Some("Apply failed".to_string()),
+            ),
+            Err(e) => (JobStatus::Failed, None, Some(e.to_string())),
+        };
+
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(id).map(|job| {
+            job.status = status;
+            job.report = report.map(|r| serde_json::to_value(r).unwrap_or_default());
+            job.error = error.clone();
+            job.finished_at = Some(Utc::now());
+            job.clone()
+        });
+        self.send(JobEvent::Finished {
+            job_id: id.to_string(),
+            status,
+            error,
+        });
+        prune(&mut jobs);
+        job
+    }
+
+    fn send(&self, event: JobEvent) {
+        // No subscribers is fine; the job keeps its own events
+        let _ = self.events.send(event);
+    }
+}
+
+/// State of one `JobRegistry::events` stream
+struct Follower {
+    registry: JobRegistry,
+    id: String,
+    /// Events to send before receiving more
+    pending: VecDeque<JobEvent>,
+    /// None once the job has finished
+    rx: Option<broadcast::Receiver<JobEvent>>,
+    /// Events sent so far, the job's history included
+    delivered: usize,
+}
+
+impl Follower {
+    async fn next(&mut self) -> Option<JobEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.rx.as_mut()?.recv().await {
+                Ok(event) if event.job_id() == self.id => {
+                    if matches!(event, JobEvent::Finished { .. }) {
+                        self.rx = None;
+                    }
+                    return Some(event);
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Job {} subscriber missed {} events", self.id, missed);
+                    // The job keeps every event, resume after those already sent
+                    let (history, rx) = self.registry.follow(&self.id)?;
+                    self.pending = history.into_iter().skip(self.delivered).collect();
+                    self.rx = rx;
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Record a finished apply, with the changes it made, in the audit log
+fn audit_apply(record: AuditRecord, result: &anyhow::Result<ApplyReport>) {
+    let record = match result {
+        Ok(report) => {
+            let changes: Vec<&String> = report
+                .results
+                .iter()
+                .flat_map(|r| &r.changes_applied)
+                .collect();
+            let record = record.with_actions(&changes);
+            if report.success {
+                record
+            } else {
+                let errors: Vec<&str> = report
+                    .results
+                    .iter()
+                    .flat_map(|r| r.errors.iter().map(String::as_str))
+                    .collect();
+                record.failed(errors.join("; "))
+            }
+        }
+        Err(e) => record.failed(e.to_string()),
+    };
+    record.record();
+}
+
+/// Drop the oldest finished jobs beyond MAX_FINISHED_JOBS
+fn prune(jobs: &mut HashMap<String, Job>) {
+    let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+        .values()
+        .filter_map(|job| job.finished_at.map(|at| (at, job.id.clone())))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+        jobs.remove(id);
+    }
+}
+
+/// Summary of a job for the job list, without its events and report
+pub fn job_summary(job: &Job) -> Value {
+    json!({
+        "id": job.id,
+        "plugin": job.plugin,
+        "status": job.status,
+        "actor": job.actor,
+        "started_at": job.started_at,
+        "finished_at": job.finished_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::plugin::{
+        ApplyResult, Checkpoint, DiffMetadata, PluginCapabilities, StateAction, StateDiff,
+        StatePlugin,
+    };
+    use anyhow::Result;
+    use futures::StreamExt;
+    use async_trait::async_trait;
+
+    /// Plugin holding a JSON object; keys named "bad" fail to apply
+    struct KeysPlugin {
+        state: Mutex<serde_json::Map<String, Value>>,
+    }
+
+    #[async_trait]
+    impl StatePlugin for KeysPlugin {
+        fn name(&self) -> &str {
+            "keys"
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        async fn query_current_state(&self) -> Result<Value> {
+            Ok(Value::Object(self.state.lock().unwrap().clone()))
+        }
+
+        async fn calculate_diff(&self, current: &Value, desired: &Value) -> Result<StateDiff> {
+            let actions = desired
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(key, value)| current.get(key.as_str()) != Some(value))
+                .map(|(key, value)| StateAction::Create {
+                    resource: key.clone(),
+                    config: value.clone(),
+                })
+                .collect();
+            Ok(StateDiff {
+                plugin: self.name().to_string(),
+                actions,
+                metadata: DiffMetadata {
+                    timestamp: 0,
+                    current_hash: String::new(),
+                    desired_hash: String::new(),
+                },
+            })
+        }
+
+        async fn apply_state(&self, diff: &StateDiff) -> Result<ApplyResult> {
+            let mut changes_applied = Vec::new();
+            for action in &diff.actions {
+                if let StateAction::Create { resource, config } = action {
+                    if resource == "bad" {
+                        anyhow::bail!("cannot create {}", resource);
+                    }
+                    self.state
+                        .lock()
+                        .unwrap()
+                        .insert(resource.clone(), config.clone());
+                    changes_applied.push(format!("Created {}", resource));
+                }
+            }
+            Ok(ApplyResult {
+                success: true,
+                changes_applied,
+                errors: Vec::new(),
+                checkpoint: None,
+            })
+        }
+
+        async fn verify_state(&self, desired: &Value) -> Result<bool> {
+            Ok(Value::Object(self.state.lock().unwrap().clone()) == *desired)
+        }
+
+        async fn create_checkpoint(&self) -> Result<Checkpoint> {
+            Ok(Checkpoint {
+                id: "keys-1".to_string(),
+                plugin: self.name().to_string(),
+                timestamp: 0,
+                state_snapshot: Value::Object(self.state.lock().unwrap().clone()),
+                backend_checkpoint: None,
+            })
+        }
+
+        async fn rollback(&self, checkpoint: &Checkpoint) -> Result<()> {
+            if let Value::Object(snapshot) = &checkpoint.state_snapshot {
+                *self.state.lock().unwrap() = snapshot.clone();
+            }
+            Ok(())
+        }
+
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities {
+                supports_rollback: true,
+                supports_checkpoints: true,
+                supports_verification: true,
+                atomic_operations: false,
+            }
+        }
+    }
+
+    async fn run(registry: &JobRegistry, manager: &Arc<StateManager>, state: Value) -> Job {
+        let job = with_actor("uid:1000", async {
+            registry.start_apply(manager.clone(), "keys".to_string(), state)
+        })
+        .await;
+        let events: Vec<JobEvent> = registry.events(&job.id).unwrap().collect().await;
+        let job = registry.get(&job.id).unwrap();
+        // Streamed events match the job's record, whenever they were joined
+        assert_eq!(events.len(), job.events.len() + 2);
+        job
+    }
+
+    #[tokio::test]
+    async fn test_apply_job_progress_and_rollback() {
+        let manager = Arc::new(StateManager::new());
+        manager
+            .register_plugin(Arc::new(KeysPlugin {
+                state: Mutex::new(serde_json::Map::new()),
+            }))
+            .await;
+        let registry = JobRegistry::new();
+
+        let job = run(&registry, &manager, json!({"a": 1, "b": 2})).await;
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.actor.as_deref(), Some("uid:1000"));
+        let started = job
+            .events
+            .iter()
+            .filter(|e| matches!(e, ApplyEvent::ActionStarted { .. }))
+            .count();
+        assert_eq!(started, 2);
+        assert!(matches!(
+            job.events[0],
+            ApplyEvent::CheckpointCreated { .. }
+        ));
+        assert!(matches!(
+            job.events.last(),
+            Some(ApplyEvent::Verified { matches: true, .. })
+        ));
+
+        let job = run(&registry, &manager, json!({"a": 5, "b": 2, "bad": 3})).await;
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(matches!(
+            job.events.last(),
+            Some(ApplyEvent::RolledBack { error: None, .. })
+        ));
+        assert_eq!(
+            manager.query_plugin_state("keys").await.unwrap(),
+            json!({"a": 1, "b": 2})
+        );
+
+        // A finished job replays its whole history
+        let (history, live) = registry.follow(&job.id).unwrap();
+        assert!(live.is_none());
+        assert!(matches!(history[0], JobEvent::Started { .. }));
+        assert!(matches!(
+            history.last(),
+            Some(JobEvent::Finished {
+                status: JobStatus::Failed,
+                ..
+            })
+        ));
+        assert_eq!(registry.list().len(), 2);
+        assert!(registry.follow("missing").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_catches_up() {
+        let registry = JobRegistry::new();
+        let job = Job {
+            id: "job-1".to_string(),
+            plugin: "keys".to_string(),
+            actor: None,
+            status: JobStatus::Running,
+            events: Vec::new(),
+            report: None,
+            error: None,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        registry.jobs.lock().unwrap().insert(job.id.clone(), job);
+        let events = registry.events("job-1").unwrap();
+
+        // More events than the channel holds, Finished among the missed ones
+        let count = EVENT_CAPACITY * 2;
+        for actions in 0..count {
+            registry.record(
+                "job-1",
+                ApplyEvent::DiffCalculated {
+                    plugin: "keys".to_string(),
+                    actions,
+                },
+            );
+        }
+        registry.finish("job-1", Err(anyhow::anyhow!("stopped")));
+
+        let events: Vec<JobEvent> = events.collect().await;
+        assert_eq!(events.len(), count + 2);
+        assert!(events
+            .iter()
+            .skip(1)
+            .take(count)
+            .enumerate()
+            .all(|(i, e)| matches!(
+                e,
+                JobEvent::Progress { event: ApplyEvent::DiffCalculated { actions, .. }, .. }
+                    if *actions == i
+            )));
+        assert!(matches!(
+            events.last(),
+            Some(JobEvent::Finished {
+                status: JobStatus::Failed,
+                ..
+            })
+        ));
+    }
+}
@@ -1,6 +1,10 @@
 //! Web UI for op-dbus state management
 //! Provides a REST API and web interface for managing system state
 
+#[cfg(feature = "web")]
+pub mod desired_state;
+#[cfg(feature = "web")]
+pub mod jobs;
 #[cfg(feature = "web")]
 pub mod server;
 
@@ -9,7 +13,14 @@ pub mod server;
 pub use server::{start_web_server, WebConfig};
 
 #[cfg(not(feature = "web"))]
-pub struct WebConfig;
+#[derive(Clone, Debug, Default)]
+pub struct WebConfig {
+    pub bind_addr: String,
+    pub port: u16,
+    pub unix_socket: Option<std::path::PathBuf>,
+    pub unix_socket_group: Option<String>,
+    pub state_file: std::path::PathBuf,
+}
 
 #[cfg(not(feature = "web"))]
 pub async fn start_web_server(
@@ -1,30 +1,82 @@
 //! Web server for op-dbus UI
+//!
+//! Mutating routes are authorized for the caller (see `Caller`):
+//! - over the unix socket, through polkit with the peer's credentials. The
+//!   socket is opt-in: set `OPDBUS_WEB_SOCKET` (e.g. /run/op-dbus/web.sock)
+//!   and `OPDBUS_WEB_SOCKET_GROUP` for the group allowed to connect. Without
+//!   polkit these changes are refused.
+//! - over TCP, by an API token with the `apply` scope (created with
+//!   `op-dbus token create`) or a client certificate whose role allows
+//!   `apply`. The token or certificate is the authorization; polkit is not
+//!   asked. The pages send the token through `/auth.js`.
+//!
+//! Any other caller is refused, unless polkit checks are disabled.
+//!
+//! Applies run as background jobs (see `jobs`); their progress streams over
+//! `/api/jobs/:id/events` (SSE, for clients sending the token as a header)
+//! and `/ws/jobs/:id` (WebSocket).
+//!
+//! `/desired` edits the desired-state file (see `desired_state`): a plan is
+//! reviewed first and the apply must confirm that plan's hash.
+//!
+//! Saves, finished applies and refused changes are written to the audit log.
 
 use anyhow::Result;
-use crate::http_tls_server::{ServerBuilder, ServiceRouter};
+use crate::audit::{AuditRecord, Frontend};
+use crate::http_tls_server::tokens::{Scope, TokenRecord};
+use crate::http_tls_server::{
+    ClientAuthConfig, HttpAuth, PeerCredentials, PeerIdentity, ServerBuilder, ServiceRouter,
+};
+use crate::native::polkit::{AuthorizationError, PolkitAuthority, Subject};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Json},
+    async_trait,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        FromRequestParts, Path, State,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Json,
+    },
     routing::{delete, get, post},
-    Router,
 };
+use futures::{Stream, StreamExt};
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
+use std::time::Duration;
 
+use super::desired_state::{DesiredStateStore, HistoryEntry};
+use super::jobs::{job_summary, JobEvent, JobRegistry, JobStatus};
+use crate::state::manager::DesiredState;
+use crate::state::plan::{infer_schema, plan_hash};
+use crate::state::plugin::StateAction;
+use crate::state::health::{ReadinessChecker, ReadinessReport};
 use crate::state::StateManager;
 
 #[derive(Clone)]
 pub struct AppState {
     state_manager: Arc<StateManager>,
+    authority: PolkitAuthority,
+    jobs: JobRegistry,
+    desired: Arc<DesiredStateStore>,
+    readiness: Arc<ReadinessChecker>,
 }
 
 #[derive(Clone, Debug)]
 pub struct WebConfig {
     pub bind_addr: String,
     pub port: u16,
+    /// Unix socket for local clients, whose peer credentials are authorized
+    pub unix_socket: Option<PathBuf>,
+    /// Group allowed to connect to `unix_socket`
+    pub unix_socket_group: Option<String>,
+    /// Desired-state file edited on the `/desired` page
+    pub state_file: PathBuf,
 }
 
 impl Default for WebConfig {
@@ -32,18 +84,64 @@ impl Default for WebConfig {
         Self {
             bind_addr: "0.0.0.0".to_string(),
             port: 9573, // OPDBUS on phone keypad: 6-7-3-2-8-7 (compressed)
+            unix_socket: std::env::var_os("OPDBUS_WEB_SOCKET").map(PathBuf::from),
+            unix_socket_group: std::env::var("OPDBUS_WEB_SOCKET_GROUP").ok(),
+            state_file: DesiredStateStore::default_state_file(),
         }
     }
 }
 
 /// Start web server
 pub async fn start_web_server(state_manager: Arc<StateManager>, config: WebConfig) -> Result<()> {
-    let app_state = AppState { state_manager };
-    let state = Arc::new(app_state);
+    let authority = PolkitAuthority::system_or_unavailable().await;
+    let readiness = Arc::new(
+        ReadinessChecker::new(state_manager.clone()).with_state_file(&config.state_file),
+    );
+    let app_state = AppState {
+        state_manager,
+        authority,
+        jobs: JobRegistry::new(),
+        desired: Arc::new(DesiredStateStore::new(
+            config.state_file.clone(),
+            DesiredStateStore::default_state_dir(),
+        )),
+        readiness,
+    };
+    let router = service_router(Arc::new(app_state));
+
+    let addr = format!("{}:{}", config.bind_addr, config.port);
+    
+    tracing::info!("Web UI available at http://{}", addr);
+    tracing::info!("  Dashboard: http://{}/", addr);
+    tracing::info!("  API docs: http://{}/api", addr);
+
+    // Build and start the shared server
+    let mut builder = ServerBuilder::new()
+        .bind_addr(addr)
+        .https_auto() // Auto-detect HTTPS certificates
+        .auth(HttpAuth::from_env())
+        .service_router(router);
+    if let Some(path) = config.unix_socket {
+        tracing::info!("  Local API: {}", path.display());
+        builder = builder.unix_socket(path);
+    }
+    if let Some(group) = config.unix_socket_group {
+        builder = builder.unix_socket_group(group);
+    }
+    if let Some(client_auth) = ClientAuthConfig::from_env()? {
+        builder = builder.client_auth(client_auth);
+    }
+    let server = builder.build().await?;
+
+    server.serve().await?;
 
-    // Create service router
+    Ok(())
+}
+
+/// Routes of the API and the pages
+fn service_router(state: Arc<AppState>) -> ServiceRouter {
     // Note: We capture state in closures because ServiceRouter builds a Router<()>
-    let router = ServiceRouter::new("/")
+    ServiceRouter::new("/")
         // API routes
         .route("/api/plugins", get({
             let s = state.clone();
@@ -59,7 +157,52 @@ pub async fn start_web_server(state_manager: Arc<StateManager>, config: WebConfi
         }))
         .route("/api/plugins/:plugin/apply", post({
             let s = state.clone();
-            move |path, json| apply_plugin_state(State((*s).clone()), path, json)
+            move |caller, path, json| apply_plugin_state(State((*s).clone()), caller, path, json)
+        }))
+        .route("/api/jobs", get({
+            let s = state.clone();
+            move || list_jobs(State((*s).clone()))
+        }))
+        .route("/api/jobs/:id", get({
+            let s = state.clone();
+            move |path| get_job(State((*s).clone()), path)
+        }))
+        .route("/api/jobs/:id/events", get({
+            let s = state.clone();
+            move |path| job_events_sse(State((*s).clone()), path)
+        }))
+        .route("/ws/jobs/:id", get({
+            let s = state.clone();
+            move |path, ws| job_events_ws(State((*s).clone()), path, ws)
+        }))
+        // Desired-state editing
+        .route("/api/desired", get({
+            let s = state.clone();
+            move || get_desired(State((*s).clone()))
+        }))
+        .route("/api/desired/history", get({
+            let s = state.clone();
+            move || desired_history(State((*s).clone()))
+        }))
+        .route("/api/desired/history/:revision", get({
+            let s = state.clone();
+            move |path| desired_revision(State((*s).clone()), path)
+        }))
+        .route("/api/desired/:plugin", axum::routing::put({
+            let s = state.clone();
+            move |caller, path, json| save_desired(State((*s).clone()), caller, path, json)
+        }))
+        .route("/api/desired/:plugin/schema", get({
+            let s = state.clone();
+            move |path| desired_schema(State((*s).clone()), path)
+        }))
+        .route("/api/desired/:plugin/plan", post({
+            let s = state.clone();
+            move |path, json| plan_desired(State((*s).clone()), path, json)
+        }))
+        .route("/api/desired/:plugin/apply", post({
+            let s = state.clone();
+            move |caller, path, json| apply_desired(State((*s).clone()), caller, path, json)
         }))
         // PlugTree routes (per-resource)
         .route("/api/containers", get({
@@ -72,11 +215,11 @@ pub async fn start_web_server(state_manager: Arc<StateManager>, config: WebConfi
         }))
         .route("/api/containers/:id", post({
             let s = state.clone();
-            move |path, json| apply_container(State((*s).clone()), path, json)
+            move |caller, path, json| apply_container(State((*s).clone()), caller, path, json)
         }))
         .route("/api/containers/:id", delete({
             let s = state.clone();
-            move |path| delete_container(State((*s).clone()), path)
+            move |caller, path| delete_container(State((*s).clone()), caller, path)
         }))
         .route("/api/units", get({
             let s = state.clone();
@@ -88,7 +231,7 @@ pub async fn start_web_server(state_manager: Arc<StateManager>, config: WebConfi
         }))
         .route("/api/units/:name", post({
             let s = state.clone();
-            move |path, json| apply_unit(State((*s).clone()), path, json)
+            move |caller, path, json| apply_unit(State((*s).clone()), caller, path, json)
         }))
         // System-wide
         .route("/api/query", get({
@@ -99,29 +242,107 @@ pub async fn start_web_server(state_manager: Arc<StateManager>, config: WebConfi
             let s = state.clone();
             move || introspect_databases(State((*s).clone()))
         }))
+        .route("/health/ready", get({
+            let s = state.clone();
+            move || readiness_check(State((*s).clone()))
+        }))
         // UI
         .route("/", get(index_handler))
+        .route("/auth.js", get(auth_script))
+        .route("/desired", get(desired_page))
         .route("/containers", get(containers_page))
         .route("/network", get(network_page))
-        .route("/systemd", get(systemd_page));
+        .route("/systemd", get(systemd_page))
+}
 
-    let addr = format!("{}:{}", config.bind_addr, config.port);
-    
-    tracing::info!("Web UI available at http://{}", addr);
-    tracing::info!("  Dashboard: http://{}/", addr);
-    tracing::info!("  API docs: http://{}/api", addr);
+/// Who asks for a change
+///
+/// Taken from the request extensions: unix socket connections carry
+/// PeerCredentials, and `HttpAuth` adds the client certificate or API token
+/// it authenticated, checked in that order like `HttpAuth` does.
+#[derive(Debug, Clone)]
+enum Caller {
+    Local(PeerCredentials),
+    Certificate(PeerIdentity),
+    Token(TokenRecord),
+    Anonymous,
+}
 
-    // Build and start the shared server
-    let server = ServerBuilder::new()
-        .bind_addr(addr)
-        .https_auto() // Auto-detect HTTPS certificates
-        .service_router(router)
-        .build()
-        .await?;
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
 
-    server.serve().await?;
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        let extensions = &parts.extensions;
+        Ok(if let Some(peer) = extensions.get::<PeerCredentials>() {
+            Self::Local(*peer)
+        } else if let Some(identity) = extensions.get::<PeerIdentity>() {
+            Self::Certificate(identity.clone())
+        } else if let Some(record) = extensions.get::<TokenRecord>() {
+            Self::Token(record.clone())
+        } else {
+            Self::Anonymous
+        })
+    }
+}
 
-    Ok(())
+/// Authorize `operation` on `plugin` for the caller of a request
+///
+/// Refusals are recorded in the audit log.
+async fn authorize_apply(
+    state: &AppState,
+    caller: Caller,
+    operation: &str,
+    plugin: &str,
+) -> Result<(), (StatusCode, String)> {
+    let result = check_apply(state, caller, plugin).await;
+    if let Err((status, message)) = &result {
+        let record = AuditRecord::new(Frontend::Web, operation).with_plugin(plugin);
+        match *status {
+            StatusCode::FORBIDDEN => record.denied(message.clone()),
+            _ => record.failed(message.clone()),
+        }
+        .record();
+    }
+    result
+}
+
+async fn check_apply(
+    state: &AppState,
+    caller: Caller,
+    plugin: &str,
+) -> Result<(), (StatusCode, String)> {
+    if !state.authority.is_enabled() {
+        return Ok(());
+    }
+    let (pid, uid) = match caller {
+        Caller::Local(PeerCredentials { pid: Some(pid), uid, .. }) => (pid, uid),
+        Caller::Certificate(PeerIdentity { role: Some(role), .. }) if role.allows(Scope::Apply) => {
+            return Ok(());
+        }
+        Caller::Token(record) if record.allows(Scope::Apply) => return Ok(()),
+        _ => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Changes need the unix socket, an API token with the apply scope or a client \
+                 certificate with the apply role"
+                    .to_string(),
+            ))
+        }
+    };
+    let subject =
+        Subject::unix_process(pid, uid).map_err(|e| (StatusCode::FORBIDDEN, e.to_string()))?;
+
+    state
+        .authority
+        .authorize_apply(&subject, plugin)
+        .await
+        .map_err(|e| match e {
+            AuthorizationError::Denied { .. } => (StatusCode::FORBIDDEN, e.to_string()),
+            AuthorizationError::Unavailable(_) => {
+                (StatusCode::SERVICE_UNAVAILABLE, e.to_string())
+            }
+        })
 }
 
 // API Handlers
@@ -154,17 +375,78 @@ async fn query_plugin_state(
 
 #[derive(Deserialize)]
 struct ApplyRequest {
-    #[allow(dead_code)]
     state: Value,
 }
 
+/// Start an apply job; responds 202 with the job and its URL
 async fn apply_plugin_state(
-    State(_state): State<AppState>,
-    Path(_plugin): Path<String>,
-    Json(_req): Json<ApplyRequest>,
-) -> impl IntoResponse {
-    // TODO: Implement apply
-    StatusCode::NOT_IMPLEMENTED
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(plugin): Path<String>,
+    Json(req): Json<ApplyRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    authorize_apply(&state, caller, "apply", &plugin).await?;
+    if state.state_manager.get_plugin(&plugin).await.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Plugin '{}' not registered", plugin),
+        ));
+    }
+
+    let job = state
+        .jobs
+        .start_apply(state.state_manager.clone(), plugin, req.state);
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/api/jobs/{}", job.id))],
+        Json(job),
+    ))
+}
+
+// Job handlers
+
+async fn list_jobs(State(state): State<AppState>) -> impl IntoResponse {
+    let jobs: Vec<Value> = state.jobs.list().iter().map(job_summary).collect();
+    Json(serde_json::json!({ "jobs": jobs }))
+}
+
+async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    state.jobs.get(&id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn job_events_sse(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let events = state.jobs.events(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Sse::new(events.map(|event| event.to_sse_event()))
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
+}
+
+async fn job_events_ws(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, StatusCode> {
+    let events = state.jobs.events(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(ws.on_upgrade(move |socket| forward_job_events(socket, events)))
+}
+
+/// Send job events as JSON text messages, then close
+async fn forward_job_events(mut socket: WebSocket, events: impl Stream<Item = JobEvent>) {
+    let mut events = std::pin::pin!(events);
+    while let Some(event) = events.next().await {
+        let Ok(text) = serde_json::to_string(&event) else {
+            continue;
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
 }
 
 async fn query_all(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
@@ -174,6 +456,11 @@ async fn query_all(State(state): State<AppState>) -> Result<Json<Value>, StatusC
     }
 }
 
+/// Per-component readiness, 503 when a required component is failing
+async fn readiness_check(State(state): State<AppState>) -> ReadinessReport {
+    state.readiness.check().await
+}
+
 // Container (PlugTree) handlers
 
 async fn list_containers(State(_state): State<AppState>) -> impl IntoResponse {
@@ -190,20 +477,230 @@ async fn get_container(
 }
 
 async fn apply_container(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    caller: Caller,
     Path(_id): Path<String>,
     Json(_req): Json<Value>,
 ) -> impl IntoResponse {
+    if let Err(denied) = authorize_apply(&state, caller, "apply_container", "lxc").await {
+        return denied.into_response();
+    }
     // TODO: Apply container state
-    StatusCode::NOT_IMPLEMENTED
+    StatusCode::NOT_IMPLEMENTED.into_response()
 }
 
 async fn delete_container(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    caller: Caller,
     Path(_id): Path<String>,
 ) -> impl IntoResponse {
+    if let Err(denied) = authorize_apply(&state, caller, "delete_container", "lxc").await {
+        return denied.into_response();
+    }
     // TODO: Delete container
-    StatusCode::NOT_IMPLEMENTED
+    StatusCode::NOT_IMPLEMENTED.into_response()
+}
+
+// Desired-state handlers
+
+fn internal_error(e: anyhow::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+async fn get_desired(State(state): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
+    let desired = state.desired.load().await.map_err(internal_error)?;
+    let revision = state.desired.current_revision().await.map_err(internal_error)?;
+    Ok(Json(json!({
+        "path": state.desired.path(),
+        "revision": revision,
+        "state": desired,
+    })))
+}
+
+async fn desired_history(
+    State(state): State<AppState>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let history = state.desired.history().await.map_err(internal_error)?;
+    Ok(Json(json!({ "history": history })))
+}
+
+async fn desired_revision(
+    State(state): State<AppState>,
+    Path(revision): Path<String>,
+) -> Result<Json<DesiredState>, (StatusCode, String)> {
+    state
+        .desired
+        .revision(&revision)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))
+}
+
+/// Form schema inferred from the plugin's current and desired state
+async fn desired_schema(
+    State(state): State<AppState>,
+    Path(plugin): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let current = state
+        .state_manager
+        .query_plugin_state(&plugin)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+    let mut desired = state.desired.load().await.map_err(internal_error)?;
+    let section = desired.plugins.remove(&plugin);
+    Ok(Json(infer_schema(std::iter::once(&current).chain(section.as_ref()))))
+}
+
+/// Actions that applying `desired` to `plugin` would take
+async fn plugin_plan(
+    state: &AppState,
+    plugin: &str,
+    desired: Value,
+) -> Result<Vec<StateAction>, (StatusCode, String)> {
+    if state.state_manager.get_plugin(plugin).await.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Plugin '{}' not registered", plugin),
+        ));
+    }
+    let diffs = state
+        .state_manager
+        .show_diff(DesiredState {
+            version: 1,
+            plugins: HashMap::from([(plugin.to_string(), desired)]),
+        })
+        .await
+        .map_err(internal_error)?;
+    Ok(diffs.into_iter().flat_map(|diff| diff.actions).collect())
+}
+
+#[derive(Deserialize)]
+struct PlanRequest {
+    /// Draft to plan; the saved desired state if absent
+    state: Option<Value>,
+}
+
+async fn plan_desired(
+    State(state): State<AppState>,
+    Path(plugin): Path<String>,
+    Json(req): Json<PlanRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let desired = match req.state {
+        Some(draft) => draft,
+        None => saved_section(&state, &plugin).await?,
+    };
+    let actions = plugin_plan(&state, &plugin, desired.clone()).await?;
+    let current = state
+        .state_manager
+        .query_plugin_state(&plugin)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(json!({
+        "plugin": plugin,
+        "plan_hash": plan_hash(&actions),
+        "actions": actions,
+        "current": current,
+        "desired": desired,
+    })))
+}
+
+async fn saved_section(state: &AppState, plugin: &str) -> Result<Value, (StatusCode, String)> {
+    let mut desired = state.desired.load().await.map_err(internal_error)?;
+    desired.plugins.remove(plugin).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("No desired state for '{}' in {}", plugin, state.desired.path().display()),
+        )
+    })
+}
+
+#[derive(Deserialize)]
+struct SaveRequest {
+    state: Value,
+    message: Option<String>,
+}
+
+async fn save_desired(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(plugin): Path<String>,
+    Json(req): Json<SaveRequest>,
+) -> Result<Json<HistoryEntry>, (StatusCode, String)> {
+    authorize_apply(&state, caller, "save_desired", &plugin).await?;
+    let params = json!({ "state": req.state, "message": req.message });
+    let result = state
+        .desired
+        .save_plugin(&plugin, req.state, req.message)
+        .await;
+    AuditRecord::new(Frontend::Web, "save_desired")
+        .with_plugin(plugin)
+        .with_params(&params)
+        .with_result(&result)
+        .record();
+    result.map(Json).map_err(internal_error)
+}
+
+#[derive(Deserialize)]
+struct ConfirmedApply {
+    /// `plan_hash` of the plan that was reviewed
+    plan_hash: String,
+}
+
+/// Apply the saved desired state of a plugin, if its plan is unchanged
+async fn apply_desired(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(plugin): Path<String>,
+    Json(req): Json<ConfirmedApply>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    authorize_apply(&state, caller, "apply", &plugin).await?;
+
+    let revision = state
+        .desired
+        .current_revision()
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::CONFLICT,
+                "The state file was changed outside the web UI; save it here first".to_string(),
+            )
+        })?;
+    let desired = saved_section(&state, &plugin).await?;
+    let actions = plugin_plan(&state, &plugin, desired.clone()).await?;
+    if plan_hash(&actions) != req.plan_hash {
+        return Err((
+            StatusCode::CONFLICT,
+            "The plan changed since it was reviewed; review it again".to_string(),
+        ));
+    }
+
+    let store = state.desired.clone();
+    let applied_revision = revision.clone();
+    let job = state.jobs.start_apply_then(
+        state.state_manager.clone(),
+        plugin,
+        desired,
+        move |job| async move {
+            let footprint = job
+                .report
+                .as_ref()
+                .and_then(|report| report["footprint"].as_str())
+                .map(String::from);
+            let success = job.status == JobStatus::Succeeded;
+            if let Err(e) = store
+                .record_apply(&applied_revision, &job.plugin, &job.id, success, footprint, job.actor)
+                .await
+            {
+                tracing::warn!("Failed to record apply of job {}: {}", job.id, e);
+            }
+        },
+    );
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/api/jobs/{}", job.id))],
+        Json(json!({ "revision": revision, "job": job })),
+    ))
 }
 
 // Unit (PlugTree) handlers
@@ -217,11 +714,15 @@ async fn get_unit(State(_state): State<AppState>, Path(_name): Path<String>) ->
 }
 
 async fn apply_unit(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    caller: Caller,
     Path(_name): Path<String>,
     Json(_req): Json<Value>,
 ) -> impl IntoResponse {
-    StatusCode::NOT_IMPLEMENTED
+    if let Err(denied) = authorize_apply(&state, caller, "apply_unit", "systemd").await {
+        return denied.into_response();
+    }
+    StatusCode::NOT_IMPLEMENTED.into_response()
 }
 
 async fn introspect_databases(State(_state): State<AppState>) -> impl IntoResponse {
@@ -285,6 +786,7 @@ async fn index_handler() -> Html<&'static str> {
         <a href="/network">🌐 Network (OVS)</a>
         <a href="/systemd">⚙️ Systemd Units</a>
         <a href="/api/query">🔍 Query All State</a>
+        <a href="/desired">📝 Edit Desired State</a>
     </div>
 
     <div class="card">
@@ -297,6 +799,13 @@ async fn index_handler() -> Html<&'static str> {
         <p><code>GET /api/units</code> - List systemd units</p>
         <p><code>POST /api/units/:name</code> - Control unit (start/stop/enable/disable/mask)</p>
         <p><code>GET /api/introspect</code> - Introspect OVSDB + NonNet databases</p>
+        <p><code>GET /api/desired</code> - Desired-state file and its revision</p>
+        <p><code>POST /api/desired/:plugin/plan</code> - Plan a draft, returns <code>plan_hash</code></p>
+        <p><code>PUT /api/desired/:plugin</code> - Save a plugin's desired state</p>
+        <p><code>POST /api/desired/:plugin/apply</code> - Apply the saved state, confirming <code>plan_hash</code></p>
+        <p><code>GET /api/desired/history</code> - Saves and applies, with footprints</p>
+        <p><code>GET /api/jobs/:id</code> - Apply job status</p>
+        <p><code>GET /health/ready</code> - Readiness of plugins, D-Bus, OVSDB, blockchain and last apply</p>
     </div>
 
     <div class="card">
@@ -309,6 +818,14 @@ async fn index_handler() -> Html<&'static str> {
     )
 }
 
+/// Token handling shared with the chat UI, see src/mcp/web/auth.js
+async fn auth_script() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/javascript")],
+        include_str!("../mcp/web/auth.js"),
+    )
+}
+
 async fn containers_page() -> Html<&'static str> {
     Html(
         r#"
@@ -358,9 +875,10 @@ async fn containers_page() -> Html<&'static str> {
         <p>Loading containers...</p>
     </div>
 
+    <script src="/auth.js"></script>
     <script>
         async function loadContainers() {
-            const res = await fetch('/api/containers');
+            const res = await opdbusAuth.fetch('/api/containers');
             const data = await res.json();
             const container_html = data.containers.map(c => `
                 <div class="container-card">
@@ -393,6 +911,320 @@ async fn containers_page() -> Html<&'static str> {
     )
 }
 
+async fn desired_page() -> Html<&'static str> {
+    Html(
+        r##"
+<!DOCTYPE html>
+<html>
+<head>
+    <title>Desired State - op-dbus</title>
+    <style>
+        body {
+            font-family: system-ui, -apple-system, sans-serif;
+            max-width: 1200px;
+            margin: 0 auto;
+            padding: 20px;
+            background: #1a1a1a;
+            color: #e0e0e0;
+        }
+        h1 { color: #4fc3f7; }
+        h2 { color: #81c784; margin-top: 0; }
+        a { color: #4fc3f7; text-decoration: none; }
+        .layout { display: grid; grid-template-columns: 2fr 1fr; gap: 20px; }
+        .card {
+            background: #2a2a2a;
+            border-radius: 8px;
+            padding: 20px;
+            margin: 10px 0;
+            border-left: 4px solid #4fc3f7;
+        }
+        fieldset { border: 1px solid #444; border-radius: 4px; margin: 8px 0; }
+        legend { color: #81c784; }
+        label { display: block; margin: 6px 0; }
+        input, select, textarea {
+            background: #1a1a1a;
+            color: #e0e0e0;
+            border: 1px solid #555;
+            border-radius: 3px;
+            padding: 4px;
+            font-family: 'Courier New', monospace;
+        }
+        textarea { width: 100%; min-height: 80px; }
+        button {
+            background: #4fc3f7;
+            color: #1a1a1a;
+            border: none;
+            padding: 8px 16px;
+            border-radius: 4px;
+            cursor: pointer;
+            margin: 5px 5px 5px 0;
+        }
+        button:hover { background: #81c784; }
+        button:disabled { background: #555; cursor: default; }
+        .action { font-family: 'Courier New', monospace; margin: 4px 0; }
+        .create { color: #81c784; }
+        .modify { color: #ffb74d; }
+        .delete { color: #e57373; }
+        .muted { color: #888; font-size: 0.9em; }
+        pre { white-space: pre-wrap; }
+    </style>
+</head>
+<body>
+    <h1>📝 Desired State</h1>
+    <p><a href="/">← Back to Dashboard</a></p>
+    <p class="muted" id="file"></p>
+
+    <div class="layout">
+        <div>
+            <div class="card">
+                <h2>Edit</h2>
+                <label>Plugin <select id="plugin" onchange="selectPlugin()"></select></label>
+                <form id="form" oninput="draftChanged()"></form>
+                <button onclick="reviewPlan()">Review plan</button>
+            </div>
+            <div class="card">
+                <h2>Plan</h2>
+                <div id="plan"><p class="muted">Review a plan before applying.</p></div>
+                <label>Message <input id="message" size="50" placeholder="Why this change"></label>
+                <button id="apply" disabled onclick="saveAndApply()">Save and apply</button>
+                <pre id="progress"></pre>
+            </div>
+        </div>
+        <div class="card">
+            <h2>History</h2>
+            <div id="history"></div>
+            <pre id="detail"></pre>
+        </div>
+    </div>
+
+    <script src="/auth.js"></script>
+    <script>
+        let desired = {};
+        let reviewed = null;
+
+        function escapeHtml(text) {
+            const div = document.createElement('div');
+            div.textContent = String(text);
+            return div.innerHTML;
+        }
+
+        async function api(method, url, body) {
+            const res = await opdbusAuth.fetch(url, {
+                method,
+                headers: { 'Content-Type': 'application/json' },
+                body: body === undefined ? undefined : JSON.stringify(body),
+            });
+            if (!res.ok) {
+                throw new Error(`${res.status}: ${await res.text()}`);
+            }
+            return res.json();
+        }
+
+        async function load() {
+            const [file, plugins] = await Promise.all([
+                api('GET', '/api/desired'),
+                api('GET', '/api/plugins'),
+            ]);
+            desired = file.state.plugins || {};
+            document.getElementById('file').textContent =
+                `${file.path} — ${file.revision ? 'revision ' + file.revision : 'not saved from the web UI'}`;
+            const names = [...new Set([...Object.keys(desired), ...plugins.plugins])].sort();
+            document.getElementById('plugin').innerHTML =
+                names.map(n => `<option>${escapeHtml(n)}</option>`).join('');
+            await selectPlugin();
+            await loadHistory();
+        }
+
+        async function selectPlugin() {
+            const plugin = document.getElementById('plugin').value;
+            const form = document.getElementById('form');
+            try {
+                const schema = await api('GET', `/api/desired/${plugin}/schema`);
+                // Start from the current state when nothing is saved yet
+                const value = plugin in desired ? desired[plugin] : await api('GET', `/api/plugins/${plugin}`);
+                form.innerHTML = '';
+                form.appendChild(renderField(plugin, schema, value));
+            } catch (e) {
+                form.textContent = e.message;
+            }
+            draftChanged();
+        }
+
+        // Form element for a schema; arrays and untyped values are edited as JSON
+        function renderField(name, schema, value) {
+            if (schema.type === 'object') {
+                const set = document.createElement('fieldset');
+                set.dataset.kind = 'object';
+                set.dataset.name = name;
+                set.innerHTML = `<legend>${escapeHtml(name)}</legend>`;
+                const props = schema.properties || {};
+                const keys = new Set([...Object.keys(props), ...Object.keys(value || {})]);
+                for (const key of keys) {
+                    set.appendChild(renderField(key, props[key] || {}, (value || {})[key]));
+                }
+                return set;
+            }
+            const label = document.createElement('label');
+            label.dataset.name = name;
+            label.append(`${name} `);
+            let input;
+            if (schema.type === 'boolean') {
+                input = document.createElement('input');
+                input.type = 'checkbox';
+                input.checked = !!value;
+            } else if (schema.type === 'string' || schema.type === 'integer' || schema.type === 'number') {
+                input = document.createElement('input');
+                input.type = schema.type === 'string' ? 'text' : 'number';
+                if (schema.type === 'number') input.step = 'any';
+                input.value = value ?? '';
+            } else {
+                input = document.createElement('textarea');
+                input.value = JSON.stringify(value ?? null, null, 2);
+            }
+            input.dataset.kind = schema.type || 'json';
+            label.appendChild(input);
+            return label;
+        }
+
+        function readField(element) {
+            if (element.dataset.kind === 'object') {
+                const out = {};
+                for (const child of element.children) {
+                    if (child.dataset.name !== undefined) out[child.dataset.name] = readField(child);
+                }
+                return out;
+            }
+            const input = element.querySelector('input, textarea');
+            switch (input.dataset.kind) {
+                case 'boolean': return input.checked;
+                case 'integer': return parseInt(input.value, 10);
+                case 'number': return parseFloat(input.value);
+                case 'string': return input.value;
+                default: return JSON.parse(input.value);
+            }
+        }
+
+        function draft() {
+            return readField(document.getElementById('form').firstElementChild);
+        }
+
+        // Any edit invalidates the reviewed plan
+        function draftChanged() {
+            reviewed = null;
+            document.getElementById('apply').disabled = true;
+        }
+
+        async function reviewPlan() {
+            const plugin = document.getElementById('plugin').value;
+            const planDiv = document.getElementById('plan');
+            try {
+                const state = draft();
+                const plan = await api('POST', `/api/desired/${plugin}/plan`, { state });
+                const actions = plan.actions.map(a => {
+                    const [kind, body] = Object.entries(a)[0];
+                    const detail = body.config ?? body.changes;
+                    return `<div class="action ${kind.toLowerCase()}">${escapeHtml(kind)} ${escapeHtml(body.resource)}` +
+                        (detail === undefined ? '' : `<pre>${escapeHtml(JSON.stringify(detail, null, 2))}</pre>`) +
+                        '</div>';
+                }).join('');
+                planDiv.innerHTML = (actions || '<p>No changes: the system already matches.</p>') +
+                    `<p class="muted">Plan ${plan.plan_hash.slice(0, 16)}</p>`;
+                reviewed = { plugin, state, hash: plan.plan_hash, count: plan.actions.length };
+                document.getElementById('apply').disabled = false;
+            } catch (e) {
+                planDiv.textContent = e.message;
+            }
+        }
+
+        async function saveAndApply() {
+            if (!reviewed) return;
+            const { plugin, state, hash, count } = reviewed;
+            if (!confirm(`Save and apply ${count} change(s) to ${plugin}?`)) return;
+            const progress = document.getElementById('progress');
+            document.getElementById('apply').disabled = true;
+            try {
+                const message = document.getElementById('message').value || null;
+                const saved = await api('PUT', `/api/desired/${plugin}`, { state, message });
+                desired[plugin] = state;
+                progress.textContent = `Saved revision ${saved.revision}\n`;
+                const started = await api('POST', `/api/desired/${plugin}/apply`, { plan_hash: hash });
+                followJob(started.job.id);
+            } catch (e) {
+                progress.textContent += e.message + '\n';
+            }
+        }
+
+        function followJob(id) {
+            const progress = document.getElementById('progress');
+            const scheme = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+            const events = new WebSocket(opdbusAuth.url(`${scheme}//${window.location.host}/ws/jobs/${id}`));
+            events.onmessage = async e => {
+                const data = JSON.parse(e.data);
+                if (data.type === 'progress') {
+                    progress.textContent += describe(data.event) + '\n';
+                }
+                if (data.type !== 'finished') return;
+                events.close();
+                const job = await api('GET', `/api/jobs/${id}`);
+                const footprint = job.report?.footprint;
+                progress.textContent += `Job ${data.status}${data.error ? ': ' + data.error : ''}` +
+                    (footprint ? `, footprint ${footprint.slice(0, 16)}` : '') + '\n';
+                draftChanged();
+                loadHistory();
+            };
+        }
+
+        function describe(event) {
+            switch (event.event) {
+                case 'checkpoint_created': return `Checkpoint ${event.checkpoint}`;
+                case 'diff_calculated': return `${event.actions} action(s) to apply`;
+                case 'action_started': {
+                    const [kind, body] = Object.entries(event.action)[0];
+                    return `[${event.index + 1}] ${kind} ${body.resource}...`;
+                }
+                case 'action_finished':
+                    return `[${event.index + 1}] ${event.success ? 'done' : 'failed: ' + event.errors.join('; ')}`;
+                case 'verified': return event.matches ? 'Verified' : 'Verification failed';
+                case 'rolled_back':
+                    return `Rolled back to ${event.checkpoint}${event.error ? ' (failed: ' + event.error + ')' : ''}`;
+                default: return JSON.stringify(event);
+            }
+        }
+
+        async function loadHistory() {
+            const { history } = await api('GET', '/api/desired/history');
+            document.getElementById('history').innerHTML = history.map(h => {
+                const when = new Date(h.at).toLocaleString();
+                if (h.kind === 'saved') {
+                    return `<p>💾 <a href="#" onclick="showDetail('/api/desired/history/${encodeURIComponent(h.revision)}'); return false">${escapeHtml(h.revision)}</a>` +
+                        `<br><span class="muted">${escapeHtml(when)} ${escapeHtml(h.plugin || '')} ${escapeHtml(h.actor || '')}` +
+                        `${h.message ? ' — ' + escapeHtml(h.message) : ''}</span></p>`;
+                }
+                return `<p>${h.success ? '✅' : '❌'} applied ${escapeHtml(h.plugin)} from ${escapeHtml(h.revision)}` +
+                    `<br><span class="muted">${escapeHtml(when)} job <a href="#" onclick="showDetail('/api/jobs/${encodeURIComponent(h.job_id)}'); return false">` +
+                    `${escapeHtml(h.job_id.slice(0, 8))}</a>` +
+                    `${h.footprint ? ' footprint ' + escapeHtml(h.footprint.slice(0, 16)) : ''}</span></p>`;
+            }).join('') || '<p class="muted">No saves yet</p>';
+        }
+
+        // API links cannot carry the token, so their JSON is shown here
+        async function showDetail(url) {
+            const detail = document.getElementById('detail');
+            try {
+                detail.textContent = JSON.stringify(await api('GET', url), null, 2);
+            } catch (e) {
+                detail.textContent = e.message;
+            }
+        }
+
+        load().catch(e => { document.getElementById('file').textContent = e.message; });
+    </script>
+</body>
+</html>
+    "##,
+    )
+}
+
 async fn network_page() -> Html<&'static str> {
     Html("<h1>Network Configuration</h1><p>Coming soon</p>")
 }
@@ -400,3 +1232,126 @@ async fn network_page() -> Html<&'static str> {
 async fn systemd_page() -> Html<&'static str> {
     Html("<h1>Systemd Units</h1><p>Coming soon</p>")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_tls_server::tokens::{Scope, TokenStore};
+    use axum::{body::Body, extract::Request, http::Method, Router};
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, method: Method, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = Body::from(r#"{"state": {}}"#);
+        app.clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn app_state(dir: &std::path::Path, authority: PolkitAuthority) -> AppState {
+        let state_manager = Arc::new(StateManager::new());
+        AppState {
+            readiness: Arc::new(ReadinessChecker::new(state_manager.clone()).without_dbus()),
+            state_manager,
+            authority,
+            jobs: JobRegistry::new(),
+            desired: Arc::new(DesiredStateStore::new(
+                dir.join("state.json"),
+                dir.join("state"),
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_desired_page_endpoints_need_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = app_state(dir.path(), PolkitAuthority::disabled());
+        let tokens = TokenStore::open(dir.path().join("tokens.json"));
+        let (_, reader) = tokens.create("reader", vec![Scope::Read], None).unwrap();
+        let (_, applier) = tokens.create("applier", vec![Scope::Apply], None).unwrap();
+        let app = HttpAuth::new(tokens).apply(service_router(Arc::new(state)).build());
+
+        // The page and its token script load without a token
+        assert_eq!(send(&app, Method::GET, "/desired", None).await, StatusCode::OK);
+        assert_eq!(send(&app, Method::GET, "/auth.js", None).await, StatusCode::OK);
+        let page = desired_page().await.0;
+        assert!(page.contains(r#"<script src="/auth.js">"#) && page.contains("opdbusAuth.fetch"));
+
+        for uri in ["/api/desired", "/api/desired/history"] {
+            assert_eq!(send(&app, Method::GET, uri, None).await, StatusCode::UNAUTHORIZED);
+            assert_eq!(send(&app, Method::GET, uri, Some(&reader)).await, StatusCode::OK);
+        }
+
+        let plan = "/api/desired/missing/plan";
+        assert_eq!(send(&app, Method::POST, plan, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&app, Method::POST, plan, Some(&reader)).await, StatusCode::FORBIDDEN);
+        // Past authentication, the handler reports the unknown plugin
+        assert_eq!(send(&app, Method::POST, plan, Some(&applier)).await, StatusCode::NOT_FOUND);
+
+        let save = "/api/desired/missing";
+        assert_eq!(send(&app, Method::PUT, save, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&app, Method::PUT, save, Some(&reader)).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_apply_authorization_without_peer_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        // polkit is on but unreachable: only unix socket callers ask it
+        let state = Arc::new(app_state(dir.path(), PolkitAuthority::unavailable("no polkit")));
+        let router = service_router(state).build();
+        let apply = "/api/plugins/missing/apply";
+
+        let tokens = TokenStore::open(dir.path().join("tokens.json"));
+        let (_, reader) = tokens.create("reader", vec![Scope::Read], None).unwrap();
+        let (_, applier) = tokens.create("applier", vec![Scope::Apply], None).unwrap();
+        let app = HttpAuth::new(tokens).apply(router.clone());
+        assert_eq!(send(&app, Method::POST, apply, Some(&reader)).await, StatusCode::FORBIDDEN);
+        // Authorized by the token; the handler reports the unknown plugin
+        assert_eq!(send(&app, Method::POST, apply, Some(&applier)).await, StatusCode::NOT_FOUND);
+
+        let send_with = |extension: Option<Caller>| {
+            let router = router.clone();
+            async move {
+                let mut request = Request::builder()
+                    .method(Method::POST)
+                    .uri(apply)
+                    .header(header::CONTENT_TYPE, "application/json");
+                match extension {
+                    Some(Caller::Local(peer)) => request = request.extension(peer),
+                    Some(Caller::Certificate(identity)) => request = request.extension(identity),
+                    _ => {}
+                }
+                let body = Body::from(r#"{"state": {}}"#);
+                router.oneshot(request.body(body).unwrap()).await.unwrap().status()
+            }
+        };
+        let certificate = |role| {
+            Caller::Certificate(PeerIdentity {
+                subject: "node2.fleet".to_string(),
+                fingerprint: "ab".repeat(32),
+                role,
+            })
+        };
+
+        assert_eq!(send_with(None).await, StatusCode::FORBIDDEN);
+        assert_eq!(send_with(Some(certificate(None))).await, StatusCode::FORBIDDEN);
+        assert_eq!(send_with(Some(certificate(Some(Scope::Read)))).await, StatusCode::FORBIDDEN);
+        assert_eq!(send_with(Some(certificate(Some(Scope::Apply)))).await, StatusCode::NOT_FOUND);
+
+        // Local callers still need polkit
+        let local = Caller::Local(PeerCredentials {
+            pid: Some(std::process::id()),
+            uid: 0,
+            gid: 0,
+        });
+        assert_eq!(send_with(Some(local)).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -30,7 +30,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .timeout(Duration::from_secs(300))
         .build()?;
 
-    let api_key = "1e4ffc3e35d14302ae8c38a3b88afbdf.6rcSE8GW_DsKPquVev9o7obK";
+    let api_key = std::env::var("OLLAMA_API_KEY")?;
 
     let request = ChatRequest {
         model: std::env::var("OLLAMA_MODEL").unwrap_or_else(|_| "llama2".to_string()),
@@ -53,6 +53,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("✅ Success! AI Response:");
         println!("Model: {}", chat_response.model.unwrap_or("unknown".to_string()));
         println!("Content: {}", chat_response.message.content);
+        println!("Done: {}", chat_response.done);
     } else {
         println!("❌ API Error: {}", response.status());
         let error_text = response.text().await?;
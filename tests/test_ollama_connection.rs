@@ -1,21 +1,28 @@
-// Quick test to verify Ollama AI connection works
+
+
+#[tokio::main]
+async fn main() {
+    println!("Run with: cargo test --lib --features web test_ollama");
+}// Quick test to verify Ollama AI connection works
 // Compile: rustc --edition 2021 test_ollama_connection.rs -L target/debug/deps --extern op_dbus=target/debug/libop_dbus.rlib --extern tokio --extern anyhow
 // Or just: cargo test --lib --features web test_ollama
 
 #[cfg(test)]
 mod tests {
     #[tokio::test]
+    #[ignore = "needs OLLAMA_API_KEY and network access"]
     async fn test_ollama_client() {
         use op_dbus::mcp::ollama::OllamaClient;
 
         let api_key = std::env::var("OLLAMA_API_KEY")
             .expect("OLLAMA_API_KEY must be set");
 
-        let client = OllamaClient::deepseek_cloud(api_key); // TODO: Rename method to cloud()
+        let client = OllamaClient::cloud(api_key);
+        let model = std::env::var("OLLAMA_DEFAULT_MODEL").unwrap_or_else(|_| "llama2".to_string());
 
         println!("🧪 Testing AI connection...");
 
-        match client.deepseek_chat("Hello! Please respond with just 'OK' if you can read this.").await { // TODO: Rename method to chat()
+        match client.simple_chat(&model, "Hello! Please respond with just 'OK' if you can read this.").await {
             Ok(response) => {
                 println!("✅ AI responded: {}", response);
                 assert!(!response.is_empty(), "Response should not be empty");
@@ -55,8 +62,3 @@ mod tests {
         }
     }
 }
-
-#[tokio::main]
-async fn main() {
-    println!("Run with: cargo test --lib --features web test_ollama");
-}